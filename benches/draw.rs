@@ -0,0 +1,53 @@
+//! Drawing into a window-sized framebuffer.
+
+use std::hint::black_box;
+
+use file_explorer::draw::{dither, draw_background, draw_text, draw_texture};
+use file_explorer::window::OffscreenBitmap;
+use file_explorer::{font, image};
+
+mod harness;
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 720;
+
+fn main() {
+	let mut bencher = harness::Bencher::from_args();
+	let mut bitmap = OffscreenBitmap::new(WIDTH, HEIGHT);
+	let bitmap_data = bitmap.bitmap_data();
+
+	let logo = image::load_from_slice(include_bytes!("../assets/logo.png"))
+		.expect("logo.png is a valid image")
+		.into_texture();
+
+	bencher.bench("draw/background", || {
+		draw_background(bitmap_data, black_box(3), black_box(5));
+	});
+	bencher.bench("draw/texture", || {
+		draw_texture(bitmap_data, black_box(&logo), black_box(60), black_box(80));
+	});
+	bencher.bench("draw/dither_full_frame", || {
+		dither(bitmap_data, 0, 0, black_box(WIDTH), black_box(HEIGHT));
+	});
+	let line = "The quick brown fox jumps over the lazy dog 0123456789";
+	bencher.bench("draw/text_line", || {
+		draw_text(
+			bitmap_data,
+			font::ui(),
+			black_box(line),
+			(10, 10),
+			0xFFEBDBB2,
+		);
+	});
+	bencher.bench("draw/text_screen", || {
+		for row in 0..HEIGHT / 20 {
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				black_box(line),
+				(10, row * 20),
+				0xFFEBDBB2,
+			);
+		}
+	});
+}
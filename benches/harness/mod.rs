@@ -0,0 +1,105 @@
+//! Small benchmark harness in the spirit of criterion. Every benchmark is warmed up, timed over
+//! [SAMPLES] samples and compared with the median of the previous run, kept in
+//! `target/bench-baseline`. Benchmarks can be filtered by name: `cargo bench -- dither`.
+
+use std::{
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+const WARM_UP: Duration = Duration::from_millis(500);
+const SAMPLES: usize = 50;
+/// Each sample runs the benchmark for about this long.
+const SAMPLE_TIME: Duration = Duration::from_millis(20);
+
+pub struct Bencher {
+	filter: Option<String>,
+	baseline_dir: PathBuf,
+}
+
+impl Bencher {
+	/// Filtered by the first argument that isn't a flag, cargo passes `--bench`.
+	pub fn from_args() -> Self {
+		let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+		let target_dir = std::env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+		Bencher {
+			filter,
+			baseline_dir: PathBuf::from(target_dir).join("bench-baseline"),
+		}
+	}
+
+	/// Times `routine`, which should pass its result through [std::hint::black_box].
+	pub fn bench(&mut self, name: &str, mut routine: impl FnMut()) {
+		if self
+			.filter
+			.as_ref()
+			.is_some_and(|filter| !name.contains(filter.as_str()))
+		{
+			return;
+		}
+
+		let mut iterations = 0_u32;
+		let start = Instant::now();
+		while start.elapsed() < WARM_UP {
+			routine();
+			iterations += 1;
+		}
+		let estimate = start.elapsed() / iterations;
+		let iterations_per_sample =
+			(SAMPLE_TIME.as_nanos() / estimate.as_nanos().max(1)).max(1) as u32;
+
+		let mut samples: Vec<Duration> = (0..SAMPLES)
+			.map(|_| {
+				let start = Instant::now();
+				for _ in 0..iterations_per_sample {
+					routine();
+				}
+				start.elapsed() / iterations_per_sample
+			})
+			.collect();
+		samples.sort();
+		let median = samples[SAMPLES / 2];
+
+		let change = match self.load_baseline(name) {
+			Some(baseline) => {
+				let change = (median.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0;
+				format!("change: {change:+.1}%")
+			}
+			None => "no baseline".to_string(),
+		};
+		println!(
+			"{name:<28} time: [{:>10} {:>10} {:>10}]  {change}",
+			format_duration(samples[0]),
+			format_duration(median),
+			format_duration(samples[SAMPLES - 1]),
+		);
+		self.save_baseline(name, median);
+	}
+
+	fn baseline_path(&self, name: &str) -> PathBuf {
+		self.baseline_dir.join(name.replace('/', "-"))
+	}
+
+	fn load_baseline(&self, name: &str) -> Option<Duration> {
+		let text = std::fs::read_to_string(self.baseline_path(name)).ok()?;
+		Some(Duration::from_nanos(text.trim().parse().ok()?))
+	}
+
+	fn save_baseline(&self, name: &str, median: Duration) {
+		let saved = std::fs::create_dir_all(&self.baseline_dir)
+			.and_then(|_| std::fs::write(self.baseline_path(name), median.as_nanos().to_string()));
+		if let Err(err) = saved {
+			eprintln!("Failed to save the baseline of {name}: {err}");
+		}
+	}
+}
+
+fn format_duration(duration: Duration) -> String {
+	let nanos = duration.as_nanos() as f64;
+	match nanos {
+		n if n < 1e3 => format!("{n:.0} ns"),
+		n if n < 1e6 => format!("{:.2} µs", n / 1e3),
+		n if n < 1e9 => format!("{:.2} ms", n / 1e6),
+		n => format!("{:.2} s", n / 1e9),
+	}
+}
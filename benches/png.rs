@@ -0,0 +1,43 @@
+//! PNG decoding of a small image with transparency and of a large photo-like one.
+
+use std::hint::black_box;
+
+use file_explorer::png::Png;
+
+mod harness;
+
+fn main() {
+	let mut bencher = harness::Bencher::from_args();
+
+	let logo = include_bytes!("../assets/logo.png");
+	bencher.bench("png/decode_logo", || {
+		black_box(Png::load_from_slice(black_box(logo)).unwrap());
+	});
+
+	let photo = Png::encode(1024, 768, &photo_like_pixels(1024, 768));
+	bencher.bench("png/decode_1024x768", || {
+		black_box(Png::load_from_slice(black_box(&photo)).unwrap());
+	});
+}
+
+/// Smooth gradients with noise on top, which compresses about as badly as photos do.
+fn photo_like_pixels(width: usize, height: usize) -> Vec<u32> {
+	let mut seed = 0x2545_F491_u32;
+	let mut noise = move || {
+		// xorshift32
+		seed ^= seed << 13;
+		seed ^= seed >> 17;
+		seed ^= seed << 5;
+		seed % 16
+	};
+	let mut pixels = Vec::with_capacity(width * height);
+	for y in 0..height {
+		for x in 0..width {
+			let r = (x * 255 / width) as u32 ^ noise();
+			let g = (y * 255 / height) as u32 ^ noise();
+			let b = ((x + y) * 255 / (width + height)) as u32 ^ noise();
+			pixels.push(0xFF00_0000 | (r << 16) | (g << 8) | b);
+		}
+	}
+	pixels
+}
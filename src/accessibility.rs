@@ -0,0 +1,218 @@
+//! Accessibility tree exposed to assistive technology (screen readers).
+//!
+//! The UI is custom-drawn, so there are no native controls the OS could inspect. Instead, every
+//! frame the app builds a snapshot of its widgets ([Tree]) which the platform layer ([crate::uia])
+//! serves to clients from its own threads. Requests coming back from clients, like invoking a
+//! button, are queued and handled on the next frame by the main loop.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::app::{State, ViewMode};
+use crate::draw::Rect;
+use crate::uia;
+use crate::{breadcrumb, status_bar, toolbar};
+
+const BREADCRUMB_ID: u32 = 1;
+const STATUS_BAR_ID: u32 = 2;
+const TOOLBAR_BUTTON_IDS: u32 = 1000;
+const GRID_ITEM_IDS: u32 = 10000;
+const LIST_ITEM_IDS: u32 = 1 << 30;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+	Button,
+	Edit,
+	ListItem,
+	Text,
+}
+
+/// What happens when a client activates a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Request {
+	ExecuteCommand(&'static str),
+	SelectGridItem(usize),
+	SelectListItem(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+	/// Identifies the node across snapshots.
+	pub id: u32,
+	pub role: Role,
+	pub name: String,
+	pub value: Option<String>,
+	/// In window client coordinates.
+	pub bounds: Rect,
+	pub selected: bool,
+	pub focused: bool,
+	pub action: Option<Request>,
+}
+
+/// Flat list of accessible widgets in reading order. The window itself is the implicit root.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+	pub nodes: Vec<Node>,
+}
+
+impl Tree {
+	pub fn get(&self, id: u32) -> Option<&Node> {
+		self.nodes.iter().find(|node| node.id == id)
+	}
+
+	pub fn focused(&self) -> Option<&Node> {
+		self.nodes.iter().find(|node| node.focused)
+	}
+
+	/// Topmost node containing the point (in client coordinates).
+	pub fn node_at(&self, pos: (i32, i32)) -> Option<&Node> {
+		self.nodes
+			.iter()
+			.rev()
+			.find(|node| node.bounds.contains(pos))
+	}
+}
+
+/// State shared with the platform accessibility providers.
+#[derive(Debug, Default)]
+pub struct Shared {
+	pub tree: Mutex<Tree>,
+	pub requests: Mutex<Vec<Request>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Accessibility {
+	shared: Arc<Shared>,
+	host: uia::Host,
+	/// Focused node of the previous snapshot.
+	focused: Option<(u32, String)>,
+	/// Directory of the previous snapshot.
+	current_dir: Option<PathBuf>,
+}
+
+impl Accessibility {
+	pub fn shared(&self) -> &Arc<Shared> {
+		&self.shared
+	}
+
+	pub fn host_mut(&mut self) -> &mut uia::Host {
+		&mut self.host
+	}
+
+	/// Replaces the tree with a new snapshot of `state` and notifies clients about focus changes and
+	/// navigation.
+	pub fn update(&mut self, state: &State, content_viewport: Rect, window_width: usize) {
+		let tree = build_tree(state, content_viewport, window_width);
+		let focused = tree.focused().map(|node| (node.id, node.name.clone()));
+		*self.shared.tree.lock().unwrap() = tree;
+
+		let current_dir = state.history.current();
+		if self.current_dir.as_deref() != Some(current_dir) {
+			// Skip the initial directory, the window title is announced on startup anyway
+			if self.current_dir.is_some() {
+				self.host
+					.announce(&format!("Opened {}", current_dir.display()));
+			}
+			self.current_dir = Some(current_dir.to_path_buf());
+		}
+
+		if focused != self.focused {
+			if let Some((id, _)) = focused {
+				self.host.raise_focus_changed(&self.shared, id);
+			}
+			self.focused = focused;
+		}
+	}
+
+	/// Requests made by clients since the last call.
+	pub fn take_requests(&self) -> Vec<Request> {
+		std::mem::take(&mut *self.shared.requests.lock().unwrap())
+	}
+}
+
+fn build_tree(state: &State, content_viewport: Rect, window_width: usize) -> Tree {
+	let mut nodes = Vec::new();
+
+	for (idx, (bounds, tooltip, command_id)) in toolbar::buttons().enumerate() {
+		nodes.push(Node {
+			id: TOOLBAR_BUTTON_IDS + idx as u32,
+			role: Role::Button,
+			name: tooltip.to_string(),
+			value: None,
+			bounds,
+			selected: false,
+			focused: false,
+			action: Some(Request::ExecuteCommand(command_id)),
+		});
+	}
+
+	let current_dir = state.history.current().display().to_string();
+	nodes.push(Node {
+		id: BREADCRUMB_ID,
+		role: Role::Edit,
+		name: "Address".to_string(),
+		value: Some(
+			state
+				.breadcrumb
+				.editor_text()
+				.map_or(current_dir, str::to_string),
+		),
+		bounds: Rect::new(0, breadcrumb::TOP, window_width, breadcrumb::HEIGHT),
+		selected: false,
+		focused: state.breadcrumb.is_editing(),
+		action: None,
+	});
+
+	if state.view_mode == ViewMode::List {
+		let list_view = &state.list_view;
+		let selection = list_view.selection();
+		for (idx, bounds) in list_view.visible_rows(content_viewport) {
+			nodes.push(Node {
+				id: LIST_ITEM_IDS + idx as u32,
+				role: Role::ListItem,
+				name: list_view.entries()[idx].name.clone(),
+				value: None,
+				bounds,
+				selected: selection.is_selected(idx),
+				focused: selection.cursor() == Some(idx) && !state.breadcrumb.is_editing(),
+				action: Some(Request::SelectListItem(idx)),
+			});
+		}
+	}
+
+	if let (ViewMode::Grid, Some(thumbnails)) = (state.view_mode, &state.thumbnails) {
+		let selection = state.grid.selection();
+		for &(idx, bounds) in state.grid.visible_cells(content_viewport, &state.arena) {
+			let path = thumbnails.path(idx);
+			let name = path.file_name().unwrap_or(path.as_os_str());
+			nodes.push(Node {
+				id: GRID_ITEM_IDS + idx as u32,
+				role: Role::ListItem,
+				name: name.to_string_lossy().into_owned(),
+				value: None,
+				bounds,
+				selected: selection.is_selected(idx),
+				focused: selection.cursor() == Some(idx) && !state.breadcrumb.is_editing(),
+				action: Some(Request::SelectGridItem(idx)),
+			});
+		}
+	}
+
+	nodes.push(Node {
+		id: STATUS_BAR_ID,
+		role: Role::Text,
+		name: state.status_bar.summary(),
+		value: None,
+		bounds: Rect::new(
+			0,
+			content_viewport.y + content_viewport.height,
+			window_width,
+			status_bar::HEIGHT,
+		),
+		selected: false,
+		focused: false,
+		action: None,
+	});
+
+	Tree { nodes }
+}
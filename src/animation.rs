@@ -0,0 +1,212 @@
+//! Tweening of values over time, driven by the frame delta time. All animations can be turned off
+//! with [set_enabled], in which case tweens jump straight to their targets.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::draw::Texture;
+
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+	Linear,
+	EaseInQuad,
+	EaseOutQuad,
+	EaseInOutQuad,
+	EaseOutCubic,
+	EaseInOutCubic,
+}
+
+impl Easing {
+	/// Maps linear progress `t` (0..=1) to eased progress.
+	pub fn apply(self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Easing::Linear => t,
+			Easing::EaseInQuad => t * t,
+			Easing::EaseOutQuad => 1.0 - (1.0 - t) * (1.0 - t),
+			Easing::EaseInOutQuad => {
+				if t < 0.5 {
+					2.0 * t * t
+				} else {
+					1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+				}
+			}
+			Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+			Easing::EaseInOutCubic => {
+				if t < 0.5 {
+					4.0 * t * t * t
+				} else {
+					1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+				}
+			}
+		}
+	}
+}
+
+/// A value moving from one number to another over a fixed duration.
+#[derive(Debug, Clone)]
+pub struct Tween {
+	from: f32,
+	to: f32,
+	/// In seconds.
+	duration: f32,
+	/// In seconds.
+	elapsed: f32,
+	easing: Easing,
+}
+
+impl Default for Tween {
+	fn default() -> Self {
+		Tween::new(0.0)
+	}
+}
+
+impl Tween {
+	/// A tween resting at `value`.
+	pub fn new(value: f32) -> Self {
+		Tween {
+			from: value,
+			to: value,
+			duration: 0.0,
+			elapsed: 0.0,
+			easing: Easing::Linear,
+		}
+	}
+
+	/// Starts animating from the current value towards `target`. Retargeting an animation that's
+	/// still running continues smoothly from wherever it currently is.
+	pub fn animate_to(&mut self, target: f32, duration: Duration, easing: Easing) {
+		if target == self.to {
+			return;
+		}
+		if !enabled() || duration.is_zero() {
+			self.set(target);
+			return;
+		}
+		self.from = self.value();
+		self.to = target;
+		self.duration = duration.as_secs_f32();
+		self.elapsed = 0.0;
+		self.easing = easing;
+	}
+
+	/// Jumps to `value` without animating.
+	pub fn set(&mut self, value: f32) {
+		*self = Tween::new(value);
+	}
+
+	pub fn update(&mut self, dt: Duration) {
+		if !enabled() {
+			self.elapsed = self.duration;
+			return;
+		}
+		self.elapsed = (self.elapsed + dt.as_secs_f32()).min(self.duration);
+	}
+
+	pub fn value(&self) -> f32 {
+		if self.is_finished() {
+			return self.to;
+		}
+		let t = self.easing.apply(self.elapsed / self.duration);
+		self.from + (self.to - self.from) * t
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.elapsed >= self.duration
+	}
+}
+
+/// Endlessly repeating 0..1 phase, e.g. for indeterminate progress bars.
+#[derive(Debug, Clone)]
+pub struct Marquee {
+	/// In seconds.
+	period: f32,
+	phase: f32,
+}
+
+impl Default for Marquee {
+	fn default() -> Self {
+		Marquee::new(Duration::from_millis(1500))
+	}
+}
+
+impl Marquee {
+	pub fn new(period: Duration) -> Self {
+		Marquee {
+			period: period.as_secs_f32(),
+			phase: 0.0,
+		}
+	}
+
+	pub fn update(&mut self, dt: Duration) {
+		if !enabled() {
+			self.phase = 0.0;
+			return;
+		}
+		self.phase = (self.phase + dt.as_secs_f32() / self.period).fract();
+	}
+
+	pub fn phase(&self) -> f32 {
+		self.phase
+	}
+}
+
+/// Plays a sequence of textures, e.g. frames of an animated image, looping forever. While
+/// animations are disabled, the first frame is shown.
+#[derive(Debug)]
+pub struct FrameSequence {
+	/// Frames with the time they're shown for.
+	frames: Vec<(Texture, Duration)>,
+	current: usize,
+	/// Time spent on the current frame.
+	elapsed: Duration,
+}
+
+impl FrameSequence {
+	/// `frames` must not be empty.
+	pub fn new(frames: Vec<(Texture, Duration)>) -> Self {
+		assert!(!frames.is_empty(), "frame sequence without frames");
+		FrameSequence {
+			frames,
+			current: 0,
+			elapsed: Duration::ZERO,
+		}
+	}
+
+	pub fn update(&mut self, dt: Duration) {
+		if !enabled() {
+			self.current = 0;
+			self.elapsed = Duration::ZERO;
+			return;
+		}
+		if self.frames.len() < 2 {
+			return;
+		}
+		self.elapsed += dt;
+		loop {
+			// Like browsers, treat very short delays as a sane minimum
+			let delay = self.frames[self.current].1.max(MIN_FRAME_DELAY);
+			if self.elapsed < delay {
+				break;
+			}
+			self.elapsed -= delay;
+			self.current = (self.current + 1) % self.frames.len();
+		}
+	}
+
+	pub fn texture(&self) -> &Texture {
+		&self.frames[self.current].0
+	}
+}
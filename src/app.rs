@@ -0,0 +1,1003 @@
+//! The explorer itself: its state, and updating and rendering it every frame.
+
+use std::fmt::Debug;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+use crate::accessibility::Request;
+use crate::arena::FrameArena;
+use crate::args::Args;
+use crate::breadcrumb::Breadcrumb;
+use crate::command::CommandRegistry;
+use crate::config::Config;
+use crate::debug_panel::DebugPanel;
+#[cfg(feature = "debug_server")]
+use crate::debug_server::DebugServer;
+#[cfg(feature = "demo")]
+use crate::demo::{self, Demo};
+use crate::draw::{draw_rectangle, Rect};
+use crate::error::ErrorKind;
+use crate::event::{Event, EventBus};
+#[cfg(feature = "gamepad")]
+use crate::gamepad;
+use crate::grid::ThumbnailGrid;
+use crate::i18n::tr;
+use crate::jobs::Priority;
+use crate::key::Key;
+use crate::keymap::Keymap;
+use crate::list_view::ListView;
+use crate::navigation::History;
+use crate::ops::{self, Operation};
+use crate::overlay::Overlays;
+use crate::palette::CommandPalette;
+use crate::pane::{self, Pane, Side};
+use crate::plugin::Plugins;
+use crate::png::Png;
+use crate::profiler::{self, Stage};
+use crate::recording::Playback;
+use crate::screen::{Screen, ScreenStack};
+use crate::search_index::SearchIndexes;
+use crate::shortcut::{Shortcut, Shortcuts};
+use crate::sidebar::{self, Sidebar};
+use crate::status_bar::{StatusBar, StatusEvent};
+use crate::thumbnails::{ImageThumbnails, PendingListing};
+use crate::toast::{Toast, Toasts};
+use crate::toolbar::Toolbar;
+use crate::update_thread::{Snapshot, UpdateThread};
+use crate::watcher::DirectoryWatcher;
+use crate::window::BitmapData;
+use crate::window::{Event as WindowEvent, EventPoster, Keyboard, Mouse, OffscreenBitmap, Window};
+use crate::{
+	animation, breadcrumb, command, crash, debug_panel, error, event, font, jobs, screen,
+	status_bar, thumbnail_cache, toolbar,
+};
+
+/// Opens the explorer window and runs it until it's closed.
+pub fn run(args: Args) -> error::Result<()> {
+	if let Some(path) = args.render_to.clone() {
+		return render_to_file(args, &path);
+	}
+
+	let mut window = match args.windowed {
+		Some((width, height)) => Window::open_with_size(width, height)?,
+		None => Window::open()?,
+	};
+	// Input recordings for reproducing bugs, see the recording module
+	if let Some(path) = std::env::var_os("FILE_EXPLORER_RECORD") {
+		window.record_input(path)?;
+	}
+	if let Some(path) = std::env::var_os("FILE_EXPLORER_PLAYBACK") {
+		window.play_input(Playback::load_from_path(path)?);
+	}
+
+	let mut state = new_state(args)?;
+
+	#[cfg(feature = "fps")]
+	let mut last_fps_log = Instant::now();
+
+	#[cfg(feature = "gamepad")]
+	let mut gamepad = gamepad::Gamepad::default();
+	#[cfg(feature = "debug_server")]
+	let debug_server = DebugServer::start()
+		.map_err(|err| warn!("Failed to start the debug server: {err}"))
+		.ok();
+
+	// Summons the explorer from anywhere
+	let summon = Shortcut::new(Key::E).ctrl().alt();
+	let summon_hotkey = window
+		.register_global_hotkey(summon)
+		.map_err(|err| warn!("Failed to register global hotkey {summon}: {err}"))
+		.ok();
+
+	let mut last_frame = Instant::now();
+	let mut last_crash_state: Option<Instant> = None;
+	loop {
+		let input = profiler::scope(Stage::Input);
+		let mut close = false;
+		for event in window.poll_events() {
+			match event {
+				WindowEvent::Close => close = true,
+				WindowEvent::Hotkey(id) if Some(id) == summon_hotkey => window.bring_to_front(),
+				WindowEvent::DirectoryChanged => {
+					let changed = mem::take(&mut *state.changed_dirs.lock().unwrap());
+					for path in changed {
+						state.events.publish(Event::DirectoryChanged { path });
+					}
+				}
+				// Layouts follow the bitmap's size every frame, only the scroll offsets need to
+				// keep the selection in view
+				WindowEvent::Resize { .. } => {
+					let viewport = content_viewport(&state, window.window_data.bitmap_data);
+					state.list_view.reveal_selected();
+					state.grid.reveal_cursor(viewport);
+				}
+				_ => (),
+			}
+		}
+		if close {
+			break;
+		}
+		#[cfg(feature = "gamepad")]
+		gamepad.poll(&mut window.window_data.keyboard);
+		drop(input);
+
+		let now = Instant::now();
+		let dt = now - last_frame;
+		last_frame = now;
+
+		{
+			let _scope = profiler::scope(Stage::Update);
+			#[cfg(feature = "debug_server")]
+			if let Some(debug_server) = &debug_server {
+				debug_server.poll(&mut state);
+			}
+			update(&mut window, &mut state, dt);
+			event::dispatch(&mut state);
+		}
+		{
+			let _scope = profiler::scope(Stage::Layout);
+			let bitmap_data = window.window_data.bitmap_data;
+			window.window_data.accessibility.update(
+				&state,
+				content_viewport(&state, bitmap_data),
+				bitmap_data.bitmap_width as usize,
+			);
+		}
+		{
+			let _scope = profiler::scope(Stage::Render);
+			render(window.window_data.bitmap_data, &mut state);
+		}
+		{
+			let _scope = profiler::scope(Stage::Present);
+			window.render();
+		}
+		profiler::end_frame();
+		state.arena.reset();
+		if last_crash_state.is_none_or(|last| last.elapsed() >= CRASH_STATE_INTERVAL) {
+			crash::update_state(&state);
+			last_crash_state = Some(Instant::now());
+		}
+		#[cfg(feature = "demo")]
+		state.demo.animate();
+
+		#[cfg(feature = "fps")]
+		if last_fps_log.elapsed() >= Duration::from_millis(100) {
+			let fps = (1000.0 / (dt.as_millis() as f64)) as u32;
+			info!("FPS: {fps}");
+			last_fps_log = Instant::now();
+		}
+	}
+
+	// Cancels the thumbnails still loading, so the jobs finish quickly
+	drop(state);
+	jobs::shutdown();
+	Ok(())
+}
+
+fn new_state(args: Args) -> error::Result<Box<State>> {
+	let (mut config, config_errors) = match args.fresh_session {
+		true => (Config::default(), Vec::new()),
+		false => Config::load(),
+	};
+	if let Some(theme) = args.theme {
+		config.theme = theme;
+	}
+	animation::set_enabled(config.animations);
+	let view_mode = config.view_mode;
+	let thumbnail_size = config.thumbnail_size;
+	let start = match &args.path {
+		Some(path) => std::path::absolute(path)?,
+		None => std::env::current_dir()?,
+	};
+	let (plugins, plugin_errors) = Plugins::load();
+	let mut commands = CommandRegistry::with_builtin_commands();
+	commands.register_plugin_commands(&plugins);
+	let (keymap, keymap_errors) = match args.fresh_session {
+		true => (Keymap::default(), Vec::new()),
+		false => Keymap::load(&commands, &config.keybindings),
+	};
+	commands.set_shortcuts(&keymap);
+	// A fresh session doesn't pick up changes of the settings either
+	let config_path = Config::path().filter(|_| !args.fresh_session);
+
+	#[cfg(feature = "demo")]
+	let watched = config_path.into_iter().chain(demo::texture_paths());
+	#[cfg(not(feature = "demo"))]
+	let watched = config_path;
+
+	let mut state = Box::new(State {
+		#[cfg(feature = "demo")]
+		demo: Demo::default(),
+
+		config,
+		update_thread: UpdateThread::spawn(watched),
+		dir_watchers: Vec::new(),
+		changed_dirs: Arc::default(),
+		args,
+		plugins: Arc::new(plugins),
+		commands,
+		shortcuts: keymap.shortcuts(),
+		keymap,
+		palette: CommandPalette::default(),
+		status_bar: StatusBar::default(),
+		toasts: Toasts::default(),
+		arena: FrameArena::default(),
+		debug_panel: DebugPanel::default(),
+		overlays: Overlays::default(),
+		toolbar: Toolbar::default(),
+		breadcrumb: Breadcrumb::default(),
+		sidebar: Sidebar::default(),
+		screens: ScreenStack::default(),
+		events: EventBus::default(),
+
+		history: History::new(start),
+		view_mode,
+		list_view: ListView::default(),
+		other_pane: None,
+		active_pane: Side::Left,
+		grid: ThumbnailGrid::new(thumbnail_size),
+		thumbnails: None,
+		listing: None,
+		reported_pending: None,
+		last_new_folder: None,
+		file_clipboard: None,
+		operation: None,
+		search_indexes: SearchIndexes::default(),
+	});
+	state.list_view.set_show_hidden(state.config.show_hidden);
+	state.search_indexes.set_enabled(state.config.search_index);
+	jobs::spawn(Priority::Low, thumbnail_cache::prune);
+	report_problems(&mut state.toasts, "config_problem", &config_errors);
+	report_problems(&mut state.toasts, "keymap_problem", &keymap_errors);
+	report_problems(&mut state.toasts, "plugin_problem", &plugin_errors);
+	Ok(state)
+}
+
+/// Renders the first frame into a framebuffer of the `--windowed` size (or 1280x720) and saves it
+/// as a PNG file at `path`, without opening a window. The directory view is drawn once it's done
+/// loading, in its final state.
+fn render_to_file(args: Args, path: &Path) -> error::Result<()> {
+	let (width, height) = args.windowed.unwrap_or((1280, 720));
+	let (width, height) = (width as usize, height as usize);
+	let mut state = new_state(args)?;
+	animation::set_enabled(false);
+	let mut bitmap = OffscreenBitmap::new(width, height);
+	let viewport = content_viewport(&state, bitmap.bitmap_data());
+	let (mouse, keyboard) = (Mouse::default(), Keyboard::default());
+
+	// Listing and thumbnails load on jobs, wait for them to have something to show
+	let deadline = Instant::now() + RENDER_TO_FILE_TIMEOUT;
+	loop {
+		if Instant::now() >= deadline {
+			warn!("Rendering before the directory finished loading");
+			break;
+		}
+		match state.view_mode {
+			ViewMode::List => {
+				if let Some(result) = state.list_view.poll(state.history.current()) {
+					let item_count = result?;
+					state
+						.status_bar
+						.handle(StatusEvent::ListingChanged { item_count });
+					break;
+				}
+			}
+			ViewMode::Grid => {
+				let dt = Duration::from_millis(10);
+				update_grid(&mut state, &mouse, &keyboard, viewport, dt);
+				let loaded = state.thumbnails.as_ref();
+				if loaded.is_some_and(|thumbnails| thumbnails.pending_count() == 0) {
+					break;
+				}
+			}
+		}
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	event::dispatch(&mut state);
+	command::update_enabled(&mut state);
+	render(bitmap.bitmap_data(), &mut state);
+	// The framebuffer has no alpha
+	let pixels: Vec<u32> = bitmap.pixels().iter().map(|p| p | 0xFF00_0000).collect();
+	Png::save_to_path(path, width, height, &pixels)?;
+	info!("Rendered to {}", path.display());
+	Ok(())
+}
+
+#[derive(Debug)]
+pub struct State {
+	#[cfg(feature = "demo")]
+	pub demo: Demo,
+
+	/// Command-line options the explorer was started with.
+	pub args: Args,
+	pub config: Config,
+	/// Watches the configuration file (and the textures of the demo) for changes.
+	pub update_thread: UpdateThread,
+	/// Watchers of the directories of both panes, None where watching failed, see
+	/// [watch_directories].
+	pub dir_watchers: Vec<(PathBuf, Option<DirectoryWatcher>)>,
+	/// Directories the watchers reported changes of, taken when the window is woken up with
+	/// [WindowEvent::DirectoryChanged].
+	pub changed_dirs: Arc<Mutex<Vec<PathBuf>>>,
+	/// Shared with the thumbnail worker, which renders the previews of plugins.
+	pub plugins: Arc<Plugins>,
+	pub commands: CommandRegistry,
+	pub keymap: Keymap,
+	/// Dispatches the bindings of [State::keymap].
+	pub shortcuts: Shortcuts<&'static str>,
+	pub palette: CommandPalette,
+	pub status_bar: StatusBar,
+	pub toasts: Toasts,
+	/// Allocations of the current frame, see [FrameArena].
+	pub arena: FrameArena,
+	pub debug_panel: DebugPanel,
+	/// Popups and tooltips drawn above everything but the toasts and the command palette.
+	pub overlays: Overlays,
+	pub toolbar: Toolbar,
+	pub breadcrumb: Breadcrumb,
+	pub sidebar: Sidebar,
+	pub screens: ScreenStack,
+	pub events: EventBus,
+
+	/// Directory of the active pane.
+	pub history: History,
+	pub view_mode: ViewMode,
+	/// Listing of the active pane.
+	pub list_view: ListView,
+	/// Inactive pane of the dual-pane view, None while it's off.
+	pub other_pane: Option<Pane>,
+	pub active_pane: Side,
+	pub grid: ThumbnailGrid,
+	/// Thumbnails of images in the current directory, loaded when the grid view is shown.
+	pub thumbnails: Option<ImageThumbnails>,
+	/// Listing of the current directory for [State::thumbnails], while it's running.
+	pub listing: Option<PendingListing>,
+	/// Number of thumbnails still loading when the progress was last published, None if it
+	/// wasn't published since a copy or move took over the progress bar.
+	pub reported_pending: Option<usize>,
+	/// Folder created by the last "New folder" command, removed again by its undo.
+	pub last_new_folder: Option<PathBuf>,
+	/// Items copied or cut, waiting for the "Paste" command.
+	pub file_clipboard: Option<(ops::Kind, Vec<PathBuf>)>,
+	/// Copy or move running in the background.
+	pub operation: Option<Operation>,
+	/// Indexes of searched folders, if enabled in the settings.
+	pub search_indexes: SearchIndexes,
+}
+
+impl State {
+	/// Paths of the items selected in the current view.
+	pub fn selected_paths(&self) -> Vec<PathBuf> {
+		match (self.view_mode, &self.thumbnails) {
+			(ViewMode::List, _) => self
+				.list_view
+				.selection()
+				.iter()
+				.map(|idx| self.list_view.entries()[idx].path.clone())
+				.collect(),
+			(ViewMode::Grid, Some(thumbnails)) => self
+				.grid
+				.selection()
+				.iter()
+				.map(|idx| thumbnails.path(idx).to_path_buf())
+				.collect(),
+			(ViewMode::Grid, None) => Vec::new(),
+		}
+	}
+}
+
+const BACKGROUND_COLOR: u32 = 0x282828;
+/// How long rendering to a file waits for the current directory to load.
+const RENDER_TO_FILE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the state summary of crash reports is refreshed.
+const CRASH_STATE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ViewMode {
+	List,
+	Grid,
+}
+
+/// Applies the file system changes noticed by the update thread.
+fn apply_snapshot(state: &mut State, snapshot: Snapshot) {
+	for path in snapshot.changed_files {
+		if Config::path().as_ref() == Some(&path) {
+			reload_config(state);
+		}
+		#[cfg(feature = "demo")]
+		match state.demo.reload_texture(&path) {
+			Some(Ok(())) => info!("Reloaded {}", path.display()),
+			Some(Err(err)) => {
+				error!("Failed to reload {}: {err}", path.display());
+				let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+				state
+					.toasts
+					.push(Toast::error(tr!("failed_to_read", path, err.to_string())));
+			}
+			None => (),
+		}
+	}
+	if let Some((count, total_size)) = snapshot.selection_size {
+		state
+			.events
+			.publish(Event::SelectionChanged { count, total_size });
+	}
+	if let Some(drives) = snapshot.drives {
+		state.events.publish(Event::DeviceChanged { drives });
+	}
+}
+
+/// Watches the directories of both panes for changes if they aren't watched yet, and stops
+/// watching those no longer shown. Directories that can't be watched are only reloaded on request.
+/// Watchers that failed, e.g. because the directory was removed and created again, are started
+/// anew.
+fn watch_directories(poster: EventPoster, state: &mut State) {
+	let shown: Vec<PathBuf> = std::iter::once(state.history.current())
+		.chain(state.other_pane.iter().map(|pane| pane.history.current()))
+		.map(Path::to_path_buf)
+		.collect();
+	state.dir_watchers.retain(|(dir, watcher)| {
+		shown.contains(dir) && !watcher.as_ref().is_some_and(DirectoryWatcher::is_finished)
+	});
+	for dir in shown {
+		if state.dir_watchers.iter().any(|(watched, _)| *watched == dir) {
+			continue;
+		}
+		let changed_dirs = Arc::clone(&state.changed_dirs);
+		let changed = dir.clone();
+		let watcher = DirectoryWatcher::start(&dir, false, move |_| {
+			let mut changed_dirs = changed_dirs.lock().unwrap();
+			if !changed_dirs.contains(&changed) {
+				changed_dirs.push(changed.clone());
+			}
+			drop(changed_dirs);
+			poster.directory_changed()
+		})
+		.map_err(|err| warn!("Failed to watch {}: {err}", dir.display()))
+		.ok();
+		state.dir_watchers.push((dir, watcher));
+	}
+}
+
+/// Applies the changed configuration file.
+fn reload_config(state: &mut State) {
+	info!("Reloading config");
+	let (mut config, config_errors) = Config::load();
+	if let Some(theme) = state.args.theme {
+		config.theme = theme;
+	}
+	if config.theme != state.config.theme {
+		state.events.publish(Event::ThemeChanged(config.theme));
+	}
+	state.config = config;
+	apply_config(state);
+	report_problems(&mut state.toasts, "config_problem", &config_errors);
+}
+
+/// Applies the settings of [State::config] that take effect right away. The default view mode
+/// only applies on the next start.
+pub fn apply_config(state: &mut State) {
+	animation::set_enabled(state.config.animations);
+	let (keymap, keymap_errors) = match state.args.fresh_session {
+		// Without the user's keymap file, like at the start
+		true => Keymap::parse("", &state.config.keybindings, &state.commands),
+		false => Keymap::load(&state.commands, &state.config.keybindings),
+	};
+	state.shortcuts = keymap.shortcuts();
+	state.commands.set_shortcuts(&keymap);
+	state.keymap = keymap;
+	let show_hidden_changed = state.list_view.set_show_hidden(state.config.show_hidden);
+	if let Some(other) = &mut state.other_pane {
+		other.list_view.set_show_hidden(state.config.show_hidden);
+	}
+	state.search_indexes.set_enabled(state.config.search_index);
+	let size_changed = state.grid.thumbnail_size() != state.config.thumbnail_size;
+	if size_changed {
+		state.grid = ThumbnailGrid::new(state.config.thumbnail_size);
+	}
+	if show_hidden_changed || size_changed {
+		// Listed again on the next update
+		state.thumbnails = None;
+		state.listing = None;
+	}
+	report_problems(&mut state.toasts, "keymap_problem", &keymap_errors);
+}
+
+/// Shows the first of `errors` in a toast, the rest are only logged.
+fn report_problems(toasts: &mut Toasts, key: &str, errors: &[impl std::error::Error]) {
+	let Some(err) = errors.first() else {
+		return;
+	};
+	let message = tr!(key, err.to_string());
+	let message = match errors.len() {
+		1 => message,
+		n => tr!("more_problems", message, n - 1),
+	};
+	toasts.push(Toast::error(message));
+}
+
+fn update(window: &mut Window, state: &mut State, dt: Duration) {
+	watch_directories(window.event_poster(), state);
+	let keyboard = &window.window_data.keyboard;
+	let bitmap_data = &mut window.window_data.bitmap_data;
+
+	state.palette.animate(dt);
+	state.status_bar.animate(dt);
+	state.toasts.animate(dt);
+
+	if let Some(snapshot) = state.update_thread.take_snapshot() {
+		apply_snapshot(state, snapshot);
+	}
+	update_operation(state);
+	state.search_indexes.poll();
+	command::update_enabled(state);
+
+	for request in window.window_data.accessibility.take_requests() {
+		match request {
+			Request::ExecuteCommand(command_id) => {
+				command::execute(state, command_id);
+			}
+			Request::SelectGridItem(idx) => {
+				let viewport = content_viewport(state, *bitmap_data);
+				state.grid.select_only(idx, viewport);
+			}
+			Request::SelectListItem(idx) => {
+				let viewport = content_viewport(state, *bitmap_data);
+				state.list_view.select_only(idx, viewport);
+			}
+		}
+	}
+
+	if keyboard.just_pressed(Key::P)
+		&& keyboard.is_pressed(Key::Control)
+		&& keyboard.is_pressed(Key::Shift)
+	{
+		state.palette.toggle(&state.commands);
+		return;
+	}
+	if state.palette.is_open() {
+		if let Some(command_id) = state.palette.update(keyboard, &state.commands) {
+			command::execute(state, command_id);
+		}
+		return;
+	}
+
+	state.overlays.update(
+		&mut window.window_data.mouse,
+		&mut window.window_data.keyboard,
+	);
+	let list_focused = state.view_mode == ViewMode::List
+		&& matches!(state.screens.top(), Screen::DirectoryBrowser)
+		&& !state.breadcrumb.is_editing();
+	if list_focused {
+		let chording = state.shortcuts.is_chording(&window.window_data.keyboard);
+		state
+			.list_view
+			.update_filter(&mut window.window_data.keyboard, chording);
+	}
+	let keyboard = &window.window_data.keyboard;
+
+	if let Some(command_id) =
+		state
+			.toasts
+			.update(&mut window.window_data.mouse, *bitmap_data, &state.arena)
+	{
+		command::execute(state, command_id);
+	}
+
+	debug_panel::update(
+		state,
+		&mut window.window_data.mouse,
+		content_viewport(state, *bitmap_data),
+	);
+
+	let mouse = &window.window_data.mouse;
+	if let Screen::DirectoryBrowser = state.screens.top() {
+		update_browser(state, mouse, keyboard, *bitmap_data, dt);
+	} else if let Some(path) = screen::update(state, mouse, keyboard, window_area(*bitmap_data)) {
+		navigate(state, path);
+	}
+}
+
+/// Handles the input of the [Screen::DirectoryBrowser], when it's the topmost screen.
+fn update_browser(
+	state: &mut State,
+	mouse: &Mouse,
+	keyboard: &Keyboard,
+	bitmap_data: BitmapData,
+	dt: Duration,
+) {
+	let bitmap_width = bitmap_data.bitmap_width as usize;
+	if let Some(path) = state.breadcrumb.update(
+		state.history.current(),
+		mouse,
+		keyboard,
+		bitmap_width,
+		&mut state.overlays,
+	) {
+		navigate(state, path);
+	}
+	if state.breadcrumb.is_editing() {
+		return;
+	}
+
+	if let Some(command_id) =
+		state
+			.toolbar
+			.update(mouse, bitmap_width, &state.commands, &mut state.overlays)
+	{
+		command::execute(state, command_id);
+	}
+	for command_id in state.shortcuts.dispatch(keyboard, mouse) {
+		command::execute(state, command_id);
+	}
+	let pending_chord = state.shortcuts.pending().map(|first| first.label());
+	state
+		.status_bar
+		.handle(StatusEvent::ChordPending(pending_chord));
+
+	#[cfg(feature = "demo")]
+	if state.demo.visible {
+		state.demo.update(keyboard, bitmap_data);
+		return;
+	}
+
+	if let Some(path) = state.sidebar.update(mouse, sidebar_area(bitmap_data)) {
+		navigate(state, path);
+	}
+	update_inactive_pane(state, mouse, bitmap_data);
+	let viewport = content_viewport(state, bitmap_data);
+	match state.view_mode {
+		ViewMode::List => update_list(state, mouse, keyboard, viewport),
+		ViewMode::Grid => update_grid(state, mouse, keyboard, viewport, dt),
+	}
+}
+
+/// Lists the directory of the inactive pane of the dual-pane view and scrolls it. Clicking it
+/// makes it the active pane, which then handles the click.
+fn update_inactive_pane(state: &mut State, mouse: &Mouse, bitmap_data: BitmapData) {
+	let Some(pane) = &mut state.other_pane else {
+		return;
+	};
+	if let Some(Err(err)) = pane.list_view.poll(pane.history.current()) {
+		error!("Failed to list {}: {err}", pane.history.current().display());
+		if err.kind() == ErrorKind::NotFound {
+			pane.history.up();
+		}
+	}
+	let (_, inactive) = pane::areas(content_area(bitmap_data), state.active_pane);
+	let hovered = inactive.contains(mouse.position());
+	let scroll_y = match hovered {
+		true => mouse.scroll_pixels().1 as isize,
+		false => 0,
+	};
+	pane.list_view
+		.handle_scroll(scroll_y, pane::view_area(inactive));
+	if hovered && mouse.was_left_pressed() {
+		switch_pane(state);
+	}
+}
+
+fn update_list(state: &mut State, mouse: &Mouse, keyboard: &Keyboard, viewport: Rect) {
+	let selection_before = state.list_view.selection().clone();
+	if let Some(result) = state.list_view.poll(state.history.current()) {
+		match result {
+			Ok(item_count) => state
+				.status_bar
+				.handle(StatusEvent::ListingChanged { item_count }),
+			Err(err) => {
+				report_listing_error(state, err);
+				return;
+			}
+		}
+	}
+	if let Some(dir) = state.list_view.update(viewport, mouse, keyboard) {
+		navigate(state, dir);
+	}
+	if state.list_view.selection() != &selection_before {
+		// Reported to the status bar once measured
+		state
+			.update_thread
+			.measure_selection(state.selected_paths());
+	}
+}
+
+fn update_grid(
+	state: &mut State,
+	mouse: &Mouse,
+	keyboard: &Keyboard,
+	viewport: Rect,
+	dt: Duration,
+) {
+	let current = state.history.current();
+	let listed = state.thumbnails.as_ref().map(ImageThumbnails::dir) == Some(current);
+	if !listed && state.listing.as_ref().map(PendingListing::dir) != Some(current) {
+		state.thumbnails = None;
+		state.grid.set_item_count(0);
+		state.listing = Some(ImageThumbnails::for_dir(
+			current.to_path_buf(),
+			state.grid.thumbnail_size(),
+			state.config.show_hidden,
+			Arc::clone(&state.plugins),
+		));
+	}
+	if let Some(result) = state.listing.as_ref().and_then(PendingListing::poll) {
+		state.listing = None;
+		match result {
+			Ok(thumbnails) => {
+				state.grid.set_item_count(thumbnails.len());
+				state.status_bar.handle(StatusEvent::ListingChanged {
+					item_count: thumbnails.len(),
+				});
+				state.thumbnails = Some(thumbnails);
+			}
+			Err(err) => {
+				report_listing_error(state, err);
+				state.view_mode = ViewMode::List;
+				return;
+			}
+		}
+	}
+
+	let Some(thumbnails) = &mut state.thumbnails else {
+		return;
+	};
+	thumbnails.animate(dt);
+	let selection_changed = state.grid.update(viewport, mouse, keyboard, thumbnails, dt);
+
+	// A running copy or move has the progress bar to itself
+	let pending = thumbnails.pending_count();
+	match state.operation {
+		Some(_) => state.reported_pending = None,
+		None if state.reported_pending != Some(pending) => {
+			state.reported_pending = Some(pending);
+			let event = match pending {
+				0 => Event::OperationFinished,
+				_ => Event::OperationProgress {
+					label: tr!("loading_thumbnails"),
+					fraction: None,
+				},
+			};
+			state.events.publish(event);
+		}
+		None => (),
+	}
+
+	if selection_changed {
+		let paths = state
+			.grid
+			.selection()
+			.iter()
+			.map(|idx| thumbnails.path(idx).to_path_buf())
+			.collect();
+		// Reported to the status bar once measured
+		state.update_thread.measure_selection(paths);
+	}
+}
+
+/// Shows the progress of the running copy or move, and how it went once it finished.
+fn update_operation(state: &mut State) {
+	let Some(operation) = &mut state.operation else {
+		return;
+	};
+	let kind = operation.kind();
+	let Some(result) = operation.poll() else {
+		let progress = operation.progress();
+		let name = progress
+			.current_file
+			.file_name()
+			.unwrap_or_default()
+			.to_string_lossy();
+		let label = match kind {
+			ops::Kind::Copy => tr!("copying", name.as_ref()),
+			ops::Kind::Move => tr!("moving", name.as_ref()),
+		};
+		let label = match progress.eta {
+			Some(eta) => tr!("time_left", label, eta.as_secs() + 1),
+			None => label,
+		};
+		let fraction = progress.fraction();
+		state
+			.events
+			.publish(Event::OperationProgress { label, fraction });
+		return;
+	};
+
+	state.operation = None;
+	state.events.publish(Event::OperationFinished);
+	state.events.publish(Event::DirectoryChanged {
+		path: state.history.current().to_path_buf(),
+	});
+	if let Some(other) = &state.other_pane {
+		state.events.publish(Event::DirectoryChanged {
+			path: other.history.current().to_path_buf(),
+		});
+	}
+	match result {
+		Ok(targets) => {
+			let text = match kind {
+				ops::Kind::Copy => tr!("copy_finished", targets.len()),
+				ops::Kind::Move => tr!("move_finished", targets.len()),
+			};
+			state.toasts.push(Toast::info(text));
+			if let Some(first) = targets.into_iter().next() {
+				match &mut state.other_pane {
+					// Copied or moved into the inactive pane
+					Some(other)
+						if first.parent() != Some(state.history.current())
+							&& first.parent() == Some(other.history.current()) =>
+					{
+						other.list_view.select_path(first)
+					}
+					_ => state.list_view.select_path(first),
+				}
+			}
+		}
+		Err(err) if err.kind() == ErrorKind::Cancelled => {
+			state
+				.status_bar
+				.handle(StatusEvent::OperationMessage(tr!("operation_cancelled")));
+		}
+		Err(err) => {
+			error!("{kind:?} failed: {err}");
+			let text = match kind {
+				ops::Kind::Copy => tr!("copy_failed", err.to_string()),
+				ops::Kind::Move => tr!("move_failed", err.to_string()),
+			};
+			state.toasts.push(Toast::error(text));
+		}
+	}
+}
+
+/// Shows why the current directory couldn't be listed, and leaves it if it was removed.
+fn report_listing_error(state: &mut State, err: error::Error) {
+	let path = font::ui().compact_path(state.history.current(), status_bar::MESSAGE_PATH_WIDTH);
+	error!(
+		"Failed to list {}: {err}",
+		state.history.current().display()
+	);
+	let message = match err.kind() {
+		ErrorKind::NotFound => tr!("directory_gone", path),
+		ErrorKind::PermissionDenied => tr!("access_denied", path),
+		_ => tr!("failed_to_read", path, err.to_string()),
+	};
+	state.toasts.push(Toast::error(message));
+	// A removed directory can't be listed in any view, leave it
+	if err.kind() == ErrorKind::NotFound {
+		state.history.up();
+	}
+}
+
+/// Part of the window above the status bar.
+fn window_area(bitmap_data: BitmapData) -> Rect {
+	let height = (bitmap_data.bitmap_height as usize).saturating_sub(status_bar::HEIGHT);
+	Rect::new(0, 0, bitmap_data.bitmap_width as usize, height)
+}
+
+/// Part of the window between the breadcrumb bar and the status bar, right of the sidebar.
+fn content_area(bitmap_data: BitmapData) -> Rect {
+	let sidebar = sidebar_area(bitmap_data);
+	let width = (bitmap_data.bitmap_width as usize).saturating_sub(sidebar.width);
+	Rect::new(sidebar.width, sidebar.y, width, sidebar.height)
+}
+
+/// Where the view of the active pane goes: the content area, less the inactive pane and the
+/// headers in the dual-pane view.
+fn content_viewport(state: &State, bitmap_data: BitmapData) -> Rect {
+	let area = content_area(bitmap_data);
+	match state.other_pane {
+		Some(_) => pane::view_area(pane::areas(area, state.active_pane).0),
+		None => area,
+	}
+}
+
+/// Left edge of the window between the breadcrumb bar and the status bar.
+fn sidebar_area(bitmap_data: BitmapData) -> Rect {
+	let top = toolbar::HEIGHT + breadcrumb::HEIGHT;
+	let bottom = (bitmap_data.bitmap_height as usize).saturating_sub(status_bar::HEIGHT);
+	let width = sidebar::WIDTH.min(bitmap_data.bitmap_width as usize);
+	Rect::new(0, top, width, bottom.saturating_sub(top))
+}
+
+/// Makes the inactive pane of the dual-pane view the active one.
+pub fn switch_pane(state: &mut State) {
+	let Some(other) = &mut state.other_pane else {
+		return;
+	};
+	mem::swap(&mut state.history, &mut other.history);
+	mem::swap(&mut state.list_view, &mut other.list_view);
+	state.active_pane = state.active_pane.other();
+	state.status_bar.handle(StatusEvent::ListingChanged {
+		item_count: state.list_view.entries().len(),
+	});
+}
+
+/// Navigates to `path` if it's an existing directory, otherwise reports an error in the status bar.
+fn navigate(state: &mut State, path: PathBuf) {
+	if path.is_dir() {
+		state.history.navigate_to(path);
+	} else {
+		let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+		let message = tr!("not_a_directory", path);
+		state
+			.status_bar
+			.handle(StatusEvent::OperationMessage(message));
+	}
+}
+
+fn render(bitmap_data: BitmapData, state: &mut State) {
+	if state.screens.is_browser_visible() {
+		render_browser(bitmap_data, state);
+	}
+	state.screens.render(bitmap_data);
+	state.debug_panel.render(bitmap_data);
+	state.status_bar.render(bitmap_data, &state.arena);
+	state.overlays.render(bitmap_data);
+	state.toasts.render(bitmap_data, &state.arena);
+	state.palette.render(bitmap_data, &state.commands);
+}
+
+/// Draws the [Screen::DirectoryBrowser].
+fn render_browser(bitmap_data: BitmapData, state: &State) {
+	#[cfg(feature = "demo")]
+	let demo_visible = state.demo.visible;
+	#[cfg(not(feature = "demo"))]
+	let demo_visible = false;
+
+	if demo_visible {
+		#[cfg(feature = "demo")]
+		state.demo.render(bitmap_data);
+	} else {
+		let (width, height) = (bitmap_data.bitmap_width, bitmap_data.bitmap_height);
+		draw_rectangle(
+			bitmap_data,
+			(0, 0),
+			(width as usize, height as usize),
+			BACKGROUND_COLOR,
+		);
+		if let Some(other) = &state.other_pane {
+			let area = content_area(bitmap_data);
+			let (active, inactive) = pane::areas(area, state.active_pane);
+			pane::render_header(bitmap_data, active, state.history.current(), true);
+			pane::render_header(bitmap_data, inactive, other.history.current(), false);
+			other
+				.list_view
+				.render(bitmap_data, pane::view_area(inactive));
+			pane::render_divider(bitmap_data, area);
+		}
+		let viewport = content_viewport(state, bitmap_data);
+		match state.view_mode {
+			ViewMode::List => state.list_view.render(bitmap_data, viewport),
+			ViewMode::Grid => render_grid(bitmap_data, state),
+		}
+		state.sidebar.render(
+			bitmap_data,
+			sidebar_area(bitmap_data),
+			state.history.current(),
+		);
+	}
+	state.toolbar.render(bitmap_data, &state.commands);
+	state
+		.breadcrumb
+		.render(bitmap_data, state.history.current(), &state.overlays);
+}
+
+fn render_grid(bitmap_data: BitmapData, state: &State) {
+	let Some(thumbnails) = &state.thumbnails else {
+		return;
+	};
+	state.grid.render(
+		bitmap_data,
+		content_viewport(state, bitmap_data),
+		thumbnails,
+		|idx| {
+			let path = thumbnails.path(idx);
+			let name = path.file_name().unwrap_or(path.as_os_str());
+			state.arena.alloc_str(&name.to_string_lossy())
+		},
+	);
+}
@@ -0,0 +1,4 @@
+//! Codecs of archive formats. ZIP entries are raw DEFLATE streams, decompressed by the same
+//! [deflate](crate::deflate) decoder PNG uses.
+
+pub use crate::deflate::{inflate, Error as DeflateError, Inflater};
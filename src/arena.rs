@@ -0,0 +1,180 @@
+//! Bump allocator for data that only lives until the end of the frame, like layouts and formatted
+//! labels. Allocating is a pointer bump, and [FrameArena::reset] frees everything at once while
+//! keeping the memory for the next frame.
+//!
+//! Only `Copy` values can be allocated, as nothing is dropped on reset.
+
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Write};
+use std::ptr::NonNull;
+
+/// Size of the first chunk, later ones double until an allocation fits.
+const FIRST_CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_ALIGN: usize = 16;
+
+#[derive(Debug)]
+pub struct FrameArena {
+	chunks: RefCell<Vec<Chunk>>,
+	/// Index into `chunks` of the chunk being filled.
+	current: Cell<usize>,
+	/// Bytes used of the current chunk.
+	used: Cell<usize>,
+	/// Reused by [FrameArena::format].
+	scratch: RefCell<String>,
+}
+
+#[derive(Debug)]
+struct Chunk {
+	ptr: NonNull<u8>,
+	size: usize,
+}
+
+// Owns its memory like a Box<[u8]>
+unsafe impl Send for Chunk {}
+
+impl Chunk {
+	fn new(size: usize) -> Chunk {
+		let layout = Chunk::layout(size);
+		// Safety: the layout isn't zero sized
+		let ptr = unsafe { alloc::alloc(layout) };
+		let Some(ptr) = NonNull::new(ptr) else {
+			alloc::handle_alloc_error(layout);
+		};
+		Chunk { ptr, size }
+	}
+
+	fn layout(size: usize) -> Layout {
+		Layout::from_size_align(size, CHUNK_ALIGN).expect("arena chunk too big")
+	}
+}
+
+impl Drop for Chunk {
+	fn drop(&mut self) {
+		// Safety: allocated in Chunk::new with the same layout
+		unsafe { alloc::dealloc(self.ptr.as_ptr(), Chunk::layout(self.size)) };
+	}
+}
+
+impl Default for FrameArena {
+	fn default() -> Self {
+		FrameArena {
+			chunks: RefCell::new(vec![Chunk::new(FIRST_CHUNK_SIZE)]),
+			current: Cell::new(0),
+			used: Cell::new(0),
+			scratch: RefCell::new(String::new()),
+		}
+	}
+}
+
+impl FrameArena {
+	/// Frees everything allocated since the last reset. Taking `&mut self` guarantees nothing
+	/// borrows the allocations anymore.
+	pub fn reset(&mut self) {
+		self.current.set(0);
+		self.used.set(0);
+	}
+
+	/// Bytes allocated since the last reset, including alignment padding.
+	pub fn allocated_bytes(&self) -> usize {
+		let chunks = self.chunks.borrow();
+		let full: usize = chunks[..self.current.get()]
+			.iter()
+			.map(|chunk| chunk.size)
+			.sum();
+		full + self.used.get()
+	}
+
+	pub fn alloc<T: Copy>(&self, value: T) -> &T {
+		let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+		// Safety: the memory is fresh, aligned and big enough for a T
+		unsafe {
+			ptr.as_ptr().write(value);
+			&*ptr.as_ptr()
+		}
+	}
+
+	pub fn alloc_slice<T: Copy>(&self, values: &[T]) -> &[T] {
+		let layout = Layout::array::<T>(values.len()).expect("arena slice too big");
+		let ptr = self.alloc_layout(layout).cast::<T>();
+		// Safety: the memory is fresh, aligned and big enough for `values.len()` Ts
+		unsafe {
+			std::ptr::copy_nonoverlapping(values.as_ptr(), ptr.as_ptr(), values.len());
+			std::slice::from_raw_parts(ptr.as_ptr(), values.len())
+		}
+	}
+
+	/// Collects `values` into a slice. Room for the upper bound of the iterator's size hint is
+	/// allocated, iterators without one are collected into a temporary `Vec` first.
+	pub fn alloc_iter<T: Copy>(&self, values: impl IntoIterator<Item = T>) -> &[T] {
+		let values = values.into_iter();
+		let Some(max) = values.size_hint().1 else {
+			return self.alloc_slice(&values.collect::<Vec<T>>());
+		};
+		let layout = Layout::array::<T>(max).expect("arena slice too big");
+		let ptr = self.alloc_layout(layout).cast::<T>();
+		let mut len = 0;
+		for value in values.take(max) {
+			// Safety: within the `max` Ts allocated above
+			unsafe { ptr.as_ptr().add(len).write(value) };
+			len += 1;
+		}
+		// Safety: the first `len` Ts were written
+		unsafe { std::slice::from_raw_parts(ptr.as_ptr(), len) }
+	}
+
+	pub fn alloc_str(&self, s: &str) -> &str {
+		let bytes = self.alloc_slice(s.as_bytes());
+		// Safety: copied from a str
+		unsafe { std::str::from_utf8_unchecked(bytes) }
+	}
+
+	/// Formats `args` into the arena, usually through [arena_format].
+	pub fn format(&self, args: fmt::Arguments) -> &str {
+		if let Some(s) = args.as_str() {
+			return s;
+		}
+		let mut scratch = self.scratch.borrow_mut();
+		scratch.clear();
+		scratch
+			.write_fmt(args)
+			.expect("formatting into a String failed");
+		self.alloc_str(&scratch)
+	}
+
+	fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+		let mut chunks = self.chunks.borrow_mut();
+		loop {
+			let chunk = &chunks[self.current.get()];
+			let start = chunk.ptr.as_ptr() as usize + self.used.get();
+			let padding = start.next_multiple_of(layout.align()) - start;
+			let end = self.used.get() + padding + layout.size();
+			if end <= chunk.size {
+				self.used.set(end);
+				// Safety: within the chunk, which isn't null
+				return unsafe {
+					NonNull::new_unchecked(chunk.ptr.as_ptr().add(end - layout.size()))
+				};
+			}
+
+			// The rest of the chunk is wasted until the next reset
+			let next = self.current.get() + 1;
+			let needed = layout.size() + layout.align();
+			if next == chunks.len() || chunks[next].size < needed {
+				let size = (chunk.size * 2).max(needed.next_power_of_two());
+				chunks.insert(next, Chunk::new(size));
+			}
+			self.current.set(next);
+			self.used.set(0);
+		}
+	}
+}
+
+/// Like `format!`, but the string lives in `$arena` until it's reset:
+/// `arena_format!(state.arena, "{count} items")`.
+macro_rules! arena_format {
+	($arena:expr, $($arg:tt)*) => {
+		$arena.format(format_args!($($arg)*))
+	};
+}
+pub(crate) use arena_format;
@@ -0,0 +1,141 @@
+//! Command-line options, parsed before the window is created so that mistakes fail fast.
+
+use std::{ffi::OsString, fmt::Display, path::PathBuf, str::FromStr};
+
+use log::LevelFilter;
+
+use crate::config::Theme;
+
+pub const USAGE: &str = "\
+Usage: file-explorer [options]
+
+Options:
+  --log-level <level>           off, error, warn, info, debug or trace
+  --theme <name>                Theme to use instead of the configured one (dark)
+  --windowed <width>x<height>   Initial window size, e.g. 1280x720, also the size of
+                                --render-to images
+  --path <dir>                  Directory to open instead of the current one
+  --fresh-session               Start with the default settings and keymap, leaving their
+                                files alone
+  --render-to <png>             Render the directory view to a PNG file without opening a
+                                window, then exit. Also --headless-screenshot
+  -h, --help                    Print this help";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Args {
+	pub log_level: Option<LevelFilter>,
+	pub theme: Option<Theme>,
+	pub windowed: Option<(u32, u32)>,
+	/// Directory to start in, instead of the current one.
+	pub path: Option<PathBuf>,
+	pub fresh_session: bool,
+	/// PNG file to render the first frame to, instead of opening a window.
+	pub render_to: Option<PathBuf>,
+	/// Print [USAGE] and exit.
+	pub help: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	UnknownOption(String),
+	MissingValue {
+		option: &'static str,
+	},
+	InvalidValue {
+		option: &'static str,
+		value: String,
+		expected: &'static str,
+	},
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::UnknownOption(option) => write!(f, "Unknown option {option}."),
+			Error::MissingValue { option } => write!(f, "{option} needs a value."),
+			Error::InvalidValue {
+				option,
+				value,
+				expected,
+			} => write!(f, "Invalid {option} \"{value}\", expected {expected}."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Args {
+	/// Options of the running process.
+	pub fn from_env() -> Result<Args, Error> {
+		Args::parse(std::env::args_os().skip(1))
+	}
+
+	/// Parses options, without the program name. Values follow their option as the next argument
+	/// or after `=`, e.g. `--log-level=debug`.
+	pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Args, Error> {
+		let mut parsed = Args::default();
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			let arg = arg.to_string_lossy().into_owned();
+			let (name, inline_value) = match arg.split_once('=') {
+				Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+				_ => (arg.as_str(), None),
+			};
+			let mut value = |option: &'static str| -> Result<String, Error> {
+				match inline_value.clone() {
+					Some(value) => Ok(value),
+					None => args
+						.next()
+						.map(|value| value.to_string_lossy().into_owned())
+						.ok_or(Error::MissingValue { option }),
+				}
+			};
+			match name {
+				"--log-level" => {
+					let level = value("--log-level")?;
+					parsed.log_level =
+						Some(
+							LevelFilter::from_str(&level).map_err(|_| Error::InvalidValue {
+								option: "--log-level",
+								value: level,
+								expected: "off, error, warn, info, debug or trace",
+							})?,
+						);
+				}
+				"--theme" => {
+					let theme = value("--theme")?;
+					parsed.theme = Some(Theme::parse(&theme).ok_or(Error::InvalidValue {
+						option: "--theme",
+						value: theme,
+						expected: "dark",
+					})?);
+				}
+				"--windowed" => {
+					let size = value("--windowed")?;
+					parsed.windowed = Some(parse_size(&size).ok_or(Error::InvalidValue {
+						option: "--windowed",
+						value: size,
+						expected: "<width>x<height>, e.g. 1280x720",
+					})?);
+				}
+				"--path" => parsed.path = Some(value("--path")?.into()),
+				"--fresh-session" => parsed.fresh_session = true,
+				"--render-to" => parsed.render_to = Some(value("--render-to")?.into()),
+				// The option's original name
+				"--headless-screenshot" => {
+					parsed.render_to = Some(value("--headless-screenshot")?.into());
+				}
+				"-h" | "--help" => parsed.help = true,
+				_ => return Err(Error::UnknownOption(arg)),
+			}
+		}
+		Ok(parsed)
+	}
+}
+
+/// `<width>x<height>` with both at least 1.
+fn parse_size(s: &str) -> Option<(u32, u32)> {
+	let (width, height) = s.split_once(['x', 'X'])?;
+	let (width, height) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+	(width > 0 && height > 0).then_some((width, height))
+}
@@ -0,0 +1,86 @@
+//! Files the explorer ships with, embedded in the binary so that it runs from any working
+//! directory. Files in the `assets` directory of the working directory take precedence, so that
+//! skins can be changed without rebuilding.
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use log::warn;
+
+use crate::draw::Texture;
+use crate::image;
+
+/// Directory with files overriding the embedded ones, relative to the working directory.
+pub const DIR: &str = "assets";
+
+/// Embedded files by name, relative to [DIR].
+const EMBEDDED: &[(&str, &[u8])] = &[
+	("logo.png", include_bytes!("../assets/logo.png")),
+	("lang/en.txt", include_bytes!("../assets/lang/en.txt")),
+];
+
+/// Path of the file overriding the embedded asset `name`.
+pub fn path(name: &str) -> PathBuf {
+	PathBuf::from(DIR).join(name)
+}
+
+/// The embedded asset `name`.
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+	EMBEDDED
+		.iter()
+		.find(|(embedded_name, _)| *embedded_name == name)
+		.map(|(_, data)| *data)
+}
+
+/// The embedded asset `name` as text. Embedded text files are UTF-8.
+pub fn embedded_text(name: &str) -> Option<&'static str> {
+	embedded(name).map(|data| std::str::from_utf8(data).expect("embedded text isn't UTF-8"))
+}
+
+/// Contents of the asset `name`, from [DIR] if the file is there, otherwise the embedded one.
+pub fn load(name: &str) -> Option<Cow<'static, [u8]>> {
+	match std::fs::read(path(name)) {
+		Ok(data) => Some(Cow::Owned(data)),
+		Err(_) => embedded(name).map(Cow::Borrowed),
+	}
+}
+
+/// The texture `name`. Files in [DIR] that fail to decode fall back to the embedded texture, and
+/// a missing texture is replaced by a placeholder, so that a broken asset never stops the
+/// explorer from starting.
+pub fn texture(name: &str) -> Texture {
+	let path = path(name);
+	if path.exists() {
+		match image::load_from_path(&path) {
+			Ok(image) => return image.into_texture(),
+			Err(err) => warn!("Failed to load {}: {err}", path.display()),
+		}
+	}
+	match embedded(name).map(image::load_from_slice) {
+		Some(Ok(image)) => image.into_texture(),
+		Some(Err(err)) => {
+			warn!("Failed to decode embedded {name}: {err}");
+			placeholder()
+		}
+		None => {
+			warn!("Missing texture {name}");
+			placeholder()
+		}
+	}
+}
+
+/// Magenta and black checkerboard standing in for missing textures.
+pub fn placeholder() -> Texture {
+	const SIZE: usize = 64;
+	const CELL: usize = 8;
+	let mut texture = Texture::new(SIZE, SIZE);
+	for y in 0..SIZE {
+		for x in 0..SIZE {
+			texture.bitmap[y * SIZE + x] = match (x / CELL + y / CELL) % 2 {
+				0 => 0xFFD3869B,
+				_ => 0xFF282828,
+			};
+		}
+	}
+	texture
+}
@@ -0,0 +1,125 @@
+//! Texture atlases: many small textures (icons, glyphs) packed into a single big one. Parts of the
+//! atlas are referred to by [Region] handles and drawn with [Atlas::draw].
+
+use log::debug;
+
+use crate::draw::Texture;
+use crate::draw::{draw_texture_region, Rect};
+use crate::window::BitmapData;
+
+/// Atlases grow downwards once a row would get wider than this.
+const DEFAULT_MAX_WIDTH: usize = 1024;
+
+/// Handle of a texture added to an [AtlasBuilder], valid for the [Atlas] it builds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Region(usize);
+
+/// Collects textures to pack into an [Atlas].
+pub struct AtlasBuilder {
+	textures: Vec<Texture>,
+	max_width: usize,
+}
+
+#[derive(Debug)]
+pub struct Atlas {
+	texture: Texture,
+	/// Indexed by [Region].
+	rects: Vec<Rect>,
+}
+
+impl Default for AtlasBuilder {
+	fn default() -> Self {
+		AtlasBuilder::new(DEFAULT_MAX_WIDTH)
+	}
+}
+
+impl AtlasBuilder {
+	/// Rows of the atlas are at most `max_width` wide, unless a single texture is wider.
+	pub fn new(max_width: usize) -> Self {
+		AtlasBuilder {
+			textures: Vec::new(),
+			max_width,
+		}
+	}
+
+	pub fn add(&mut self, texture: Texture) -> Region {
+		self.textures.push(texture);
+		Region(self.textures.len() - 1)
+	}
+
+	/// Packs the textures into shelves: rows filled left to right, tallest textures first so that
+	/// textures in a row are of similar height and little space is wasted.
+	pub fn build(self) -> Atlas {
+		let mut order: Vec<usize> = (0..self.textures.len()).collect();
+		order.sort_by_key(|&idx| std::cmp::Reverse(self.textures[idx].height));
+
+		let mut rects = vec![Rect::default(); self.textures.len()];
+		let (mut x, mut y, mut row_height, mut width) = (0, 0, 0, 0);
+		for &idx in &order {
+			let texture = &self.textures[idx];
+			if x > 0 && x + texture.width > self.max_width {
+				y += row_height;
+				x = 0;
+				row_height = 0;
+			}
+			rects[idx] = Rect::new(x, y, texture.width, texture.height);
+			x += texture.width;
+			row_height = row_height.max(texture.height);
+			width = width.max(x);
+		}
+		let height = y + row_height;
+
+		let mut atlas = Texture::new(width, height);
+		for (texture, rect) in self.textures.iter().zip(&rects) {
+			for (row, atlas_row) in texture
+				.bitmap
+				.chunks_exact(texture.width)
+				.zip(atlas.bitmap[rect.y * width..].chunks_exact_mut(width))
+			{
+				atlas_row[rect.x..rect.x + rect.width].copy_from_slice(row);
+			}
+		}
+		debug!(
+			"Packed {} textures into a {width}x{height} atlas",
+			self.textures.len()
+		);
+
+		Atlas {
+			texture: atlas,
+			rects,
+		}
+	}
+}
+
+impl Atlas {
+	pub fn texture(&self) -> &Texture {
+		&self.texture
+	}
+
+	/// Where `region` lies in [Atlas::texture].
+	pub fn rect(&self, region: Region) -> Rect {
+		self.rects[region.0]
+	}
+
+	/// Draws `region` with its top left corner at `pos`.
+	pub fn draw(&self, bitmap_data: BitmapData, region: Region, (x, y): (usize, usize)) {
+		let clip = Rect::new(
+			0,
+			0,
+			bitmap_data.bitmap_width as usize,
+			bitmap_data.bitmap_height as usize,
+		);
+		self.draw_clipped(bitmap_data, region, (x as isize, y as isize), clip);
+	}
+
+	/// Like [Atlas::draw], touching only pixels inside of `clip`.
+	pub fn draw_clipped(
+		&self,
+		bitmap_data: BitmapData,
+		region: Region,
+		pos: (isize, isize),
+		clip: Rect,
+	) {
+		draw_texture_region(bitmap_data, &self.texture, self.rect(region), pos, clip);
+	}
+}
@@ -0,0 +1,690 @@
+//! Windows bitmap (BMP) decoder. Handles the core and info header variants, 1/4/8 bit palettized,
+//! 16/24/32 bit direct color (with or without bit field masks) and RLE4/RLE8 compressed images.
+
+use std::{fmt::Display, path::PathBuf};
+
+use log::debug;
+
+use crate::draw::Pos;
+
+const FILE_HEADER_SIZE: usize = 14;
+const CORE_HEADER_SIZE: u32 = 12;
+const INFO_HEADER_SIZE: u32 = 40;
+
+const COMPRESSION_RGB: u32 = 0;
+const COMPRESSION_RLE8: u32 = 1;
+const COMPRESSION_RLE4: u32 = 2;
+const COMPRESSION_BITFIELDS: u32 = 3;
+const COMPRESSION_ALPHABITFIELDS: u32 = 6;
+
+/// Images bigger than this many pixels are rejected instead of allocating gigabytes for a
+/// corrupted header.
+const MAX_PIXELS: usize = 1 << 28;
+
+pub struct Bmp {
+	width: usize,
+	height: usize,
+	/// 0xAARRGGBB, top row first.
+	pixels: Vec<u32>,
+	filename: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	UnsupportedHeader(u32),
+	UnsupportedFormat {
+		bits_per_pixel: u16,
+		compression: u32,
+	},
+	InvalidSize,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not a BMP file."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::UnsupportedHeader(size) => write!(f, "Unknown header of {size} bytes."),
+			Error::UnsupportedFormat {
+				bits_per_pixel,
+				compression,
+			} => write!(
+				f,
+				"Unsupported {bits_per_pixel} bits per pixel with compression {compression}."
+			),
+			Error::InvalidSize => write!(f, "Invalid image size."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// The parts of the DIB header the decoder cares about.
+#[derive(Debug)]
+struct Header {
+	width: usize,
+	height: usize,
+	/// Rows are stored bottom-up unless the height is negative.
+	top_down: bool,
+	bits_per_pixel: u16,
+	compression: u32,
+	/// Number of palette entries, 0 means the maximum for the bit depth.
+	colors_used: u32,
+	/// Red, green, blue and alpha masks of direct color pixels.
+	masks: Option<[u32; 4]>,
+	/// Palette entries are 3 bytes long in core headers, 4 otherwise.
+	palette_entry_size: usize,
+	palette_offset: usize,
+}
+
+impl Bmp {
+	pub fn load_from_path(p: &str) -> Result<Self, Error> {
+		let data = std::fs::read(p).map_err(|err| Error::Io {
+			err,
+			filename: p.into(),
+		})?;
+		let mut bmp = Bmp::load_from_slice(&data)?;
+		bmp.filename = Some(p.to_string());
+		Ok(bmp)
+	}
+
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		if data.get(..2) != Some(b"BM") {
+			return Err(Error::BadMagic);
+		}
+		let pixel_offset = read_u32(data, 10)? as usize;
+		let header = parse_header(data, FILE_HEADER_SIZE)?;
+		debug!("BMP header: {header:?}");
+
+		Ok(Bmp {
+			width: header.width,
+			height: header.height,
+			pixels: decode(data, &header, pixel_offset)?,
+			filename: None,
+		})
+	}
+
+	/// Decodes a DIB without the file header, e.g. CF_DIB data from the clipboard.
+	pub fn load_from_dib(data: &[u8]) -> Result<Self, Error> {
+		let header = parse_header(data, 0)?;
+		debug!("DIB header: {header:?}");
+		// Direct color bitmaps may come with a palette for displays with few colors too
+		let palette_len = match header.bits_per_pixel {
+			1 | 4 | 8 if header.colors_used == 0 => 1 << header.bits_per_pixel,
+			_ => header.colors_used as usize,
+		};
+		let pixel_offset = header.palette_offset + palette_len * header.palette_entry_size;
+
+		Ok(Bmp {
+			width: header.width,
+			height: header.height,
+			pixels: decode(data, &header, pixel_offset)?,
+			filename: None,
+		})
+	}
+
+	/// Decodes a bitmap stored in an ICO or CUR file: a DIB without the file header, with a
+	/// 1 bit transparency mask after the color bitmap. The height in the header covers both.
+	pub fn load_from_icon(data: &[u8]) -> Result<Self, Error> {
+		let mut header = parse_header(data, 0)?;
+		header.height /= 2;
+		if header.height == 0 {
+			return Err(Error::InvalidSize);
+		}
+		debug!("Icon bitmap header: {header:?}");
+
+		let palette_len = match header.bits_per_pixel {
+			1 | 4 | 8 if header.colors_used > 0 => header.colors_used as usize,
+			1 | 4 | 8 => 1 << header.bits_per_pixel,
+			_ => 0,
+		};
+		let pixel_offset = header.palette_offset + palette_len * header.palette_entry_size;
+		let mut pixels = decode(data, &header, pixel_offset)?;
+
+		// 32 bit icons have an alpha channel, the mask only matters for the others
+		let has_alpha = header.bits_per_pixel == 32 && pixels.iter().any(|p| p >> 24 != 0xFF);
+		if header.compression == COMPRESSION_RGB && !has_alpha {
+			let (width, height) = (header.width, header.height);
+			let stride = (width * header.bits_per_pixel as usize).div_ceil(32) * 4;
+			let mask_stride = width.div_ceil(32) * 4;
+			let mask_offset = pixel_offset + stride * height;
+			for row in 0..height {
+				let start = mask_offset + row * mask_stride;
+				// Some icons leave the mask out, treat missing rows as opaque
+				let Some(mask) = data.get(start..start + mask_stride) else {
+					break;
+				};
+				let y = height - 1 - row;
+				for (x, pixel) in pixels[y * width..(y + 1) * width].iter_mut().enumerate() {
+					if mask[x / 8] & (0x80 >> (x % 8)) != 0 {
+						*pixel = 0;
+					}
+				}
+			}
+		}
+
+		Ok(Bmp {
+			width: header.width,
+			height: header.height,
+			pixels,
+			filename: None,
+		})
+	}
+}
+
+impl From<Bmp> for crate::draw::Texture {
+	fn from(img: Bmp) -> Self {
+		let texture = crate::draw::Texture {
+			bitmap: img.pixels,
+			width: img.width,
+			height: img.height,
+			pos: Pos { x: 0, y: 0 },
+		};
+		debug!(
+			"From file \"{filename}\" created texture: {texture:#?}",
+			filename = img.filename.as_deref().unwrap_or("unknown")
+		);
+		texture
+	}
+}
+
+/// Rejects empty images and those above [MAX_PIXELS].
+fn check_size(width: usize, height: usize) -> Result<(), Error> {
+	match width == 0 || height == 0 || width.saturating_mul(height) > MAX_PIXELS {
+		true => Err(Error::InvalidSize),
+		false => Ok(()),
+	}
+}
+
+/// Parses the DIB header starting at `offset`.
+fn parse_header(data: &[u8], offset: usize) -> Result<Header, Error> {
+	let header_size = read_u32(data, offset)?;
+	let at = |field: usize| offset + field;
+
+	if header_size == CORE_HEADER_SIZE {
+		let width = read_u16(data, at(4))? as usize;
+		let height = read_u16(data, at(6))? as usize;
+		check_size(width, height)?;
+		return Ok(Header {
+			width,
+			height,
+			top_down: false,
+			bits_per_pixel: read_u16(data, at(10))?,
+			compression: COMPRESSION_RGB,
+			colors_used: 0,
+			masks: None,
+			palette_entry_size: 3,
+			palette_offset: at(CORE_HEADER_SIZE as usize),
+		});
+	}
+	if header_size < INFO_HEADER_SIZE {
+		return Err(Error::UnsupportedHeader(header_size));
+	}
+
+	let width = read_u32(data, at(4))? as i32;
+	let height = read_u32(data, at(8))? as i32;
+	let bits_per_pixel = read_u16(data, at(14))?;
+	let compression = read_u32(data, at(16))?;
+	let colors_used = read_u32(data, at(32))?;
+
+	let mut palette_offset = at(header_size as usize);
+	let masks = match compression {
+		COMPRESSION_BITFIELDS | COMPRESSION_ALPHABITFIELDS => {
+			// Masks are a part of V2+ headers, info headers are followed by them instead
+			let masks_offset = at(INFO_HEADER_SIZE as usize);
+			let has_alpha = compression == COMPRESSION_ALPHABITFIELDS || header_size >= 56;
+			let alpha = if has_alpha {
+				read_u32(data, masks_offset + 12)?
+			} else {
+				0
+			};
+			if header_size == INFO_HEADER_SIZE {
+				palette_offset += if has_alpha { 16 } else { 12 };
+			}
+			Some([
+				read_u32(data, masks_offset)?,
+				read_u32(data, masks_offset + 4)?,
+				read_u32(data, masks_offset + 8)?,
+				alpha,
+			])
+		}
+		_ => None,
+	};
+
+	if width <= 0 || height == 0 || height == i32::MIN {
+		return Err(Error::InvalidSize);
+	}
+	let (width, height) = (width as usize, height.unsigned_abs() as usize);
+	check_size(width, height)?;
+
+	Ok(Header {
+		width,
+		height,
+		top_down: (read_u32(data, at(8))? as i32) < 0,
+		bits_per_pixel,
+		compression,
+		colors_used,
+		masks,
+		palette_entry_size: 4,
+		palette_offset,
+	})
+}
+
+fn decode(data: &[u8], header: &Header, pixel_offset: usize) -> Result<Vec<u32>, Error> {
+	let palette = read_palette(data, header, pixel_offset);
+	match (header.bits_per_pixel, header.compression) {
+		(8, COMPRESSION_RLE8) => Ok(decode_rle(data, pixel_offset, header, &palette, false)),
+		(4, COMPRESSION_RLE4) => Ok(decode_rle(data, pixel_offset, header, &palette, true)),
+		(1 | 4 | 8 | 24, COMPRESSION_RGB)
+		| (16 | 32, COMPRESSION_RGB | COMPRESSION_BITFIELDS | COMPRESSION_ALPHABITFIELDS) => {
+			decode_uncompressed(data, pixel_offset, header, &palette)
+		}
+		(bits_per_pixel, compression) => Err(Error::UnsupportedFormat {
+			bits_per_pixel,
+			compression,
+		}),
+	}
+}
+
+/// Palette as 0xAARRGGBB colors. Entries missing from the file stay black.
+fn read_palette(data: &[u8], header: &Header, pixel_offset: usize) -> Vec<u32> {
+	if header.bits_per_pixel > 8 {
+		return Vec::new();
+	}
+	let max_colors = 1 << header.bits_per_pixel;
+	let count = match header.colors_used as usize {
+		0 => max_colors,
+		count => count.min(max_colors),
+	};
+
+	let mut palette = vec![0xFF000000; max_colors];
+	let end = pixel_offset.min(data.len());
+	let entries = data
+		.get(header.palette_offset..end)
+		.unwrap_or_default()
+		.chunks_exact(header.palette_entry_size);
+	for (color, entry) in palette.iter_mut().zip(entries).take(count) {
+		// Blue, green, red (, unused)
+		*color = u32::from_le_bytes([entry[0], entry[1], entry[2], 0xFF]);
+	}
+	palette
+}
+
+fn decode_uncompressed(
+	data: &[u8],
+	pixel_offset: usize,
+	header: &Header,
+	palette: &[u32],
+) -> Result<Vec<u32>, Error> {
+	let (width, height) = (header.width, header.height);
+	let bits_per_pixel = header.bits_per_pixel as usize;
+	// Rows are padded to 4 bytes
+	let stride = (width * bits_per_pixel).div_ceil(32) * 4;
+	let masks = header.masks.unwrap_or(match bits_per_pixel {
+		16 => [0x7C00, 0x03E0, 0x001F, 0],
+		// The fourth byte is officially unused, but often holds alpha anyway, see below
+		_ => [0xFF0000, 0xFF00, 0xFF, 0xFF000000],
+	});
+
+	let mut pixels = vec![0; width * height];
+	for row in 0..height {
+		let start = pixel_offset + row * stride;
+		let line = data.get(start..start + stride).ok_or(Error::FileEnd)?;
+		let y = if header.top_down {
+			row
+		} else {
+			height - 1 - row
+		};
+		let output = &mut pixels[y * width..(y + 1) * width];
+
+		for (x, pixel) in output.iter_mut().enumerate() {
+			*pixel = match bits_per_pixel {
+				1 | 4 | 8 => {
+					let bit = x * bits_per_pixel;
+					let shift = 8 - bits_per_pixel - bit % 8;
+					let idx = (line[bit / 8] >> shift) as usize & ((1 << bits_per_pixel) - 1);
+					palette[idx]
+				}
+				16 => from_masks(
+					u16::from_le_bytes([line[x * 2], line[x * 2 + 1]]) as u32,
+					&masks,
+				),
+				24 => u32::from_le_bytes([line[x * 3], line[x * 3 + 1], line[x * 3 + 2], 0xFF]),
+				_ => from_masks(
+					u32::from_le_bytes(line[x * 4..x * 4 + 4].try_into().unwrap()),
+					&masks,
+				),
+			};
+		}
+	}
+
+	if bits_per_pixel == 32 && header.masks.is_none() && pixels.iter().all(|p| p >> 24 == 0) {
+		// The unused byte really was unused
+		pixels.iter_mut().for_each(|pixel| *pixel |= 0xFF000000);
+	}
+	Ok(pixels)
+}
+
+/// Converts a direct color pixel to 0xAARRGGBB. A zero alpha mask means opaque.
+fn from_masks(value: u32, &[red, green, blue, alpha]: &[u32; 4]) -> u32 {
+	let alpha = if alpha == 0 {
+		0xFF
+	} else {
+		channel(value, alpha)
+	};
+	u32::from_be_bytes([
+		alpha,
+		channel(value, red),
+		channel(value, green),
+		channel(value, blue),
+	])
+}
+
+/// Extracts the bits of `mask` from `value`, scaled to 0-255.
+fn channel(value: u32, mask: u32) -> u8 {
+	if mask == 0 {
+		return 0;
+	}
+	let shift = mask.trailing_zeros();
+	let max = (mask >> shift) as u64;
+	let sample = ((value & mask) >> shift) as u64;
+	(sample * 255 / max) as u8
+}
+
+/// Decodes run length encoded palette indices. Pixels skipped by the encoding stay transparent.
+/// Truncated data ends the image early instead of failing.
+fn decode_rle(
+	data: &[u8],
+	pixel_offset: usize,
+	header: &Header,
+	palette: &[u32],
+	four_bit: bool,
+) -> Vec<u32> {
+	let (width, height) = (header.width, header.height);
+	let mut pixels = vec![0; width * height];
+	let mut put = |x: usize, row: usize, idx: u8| {
+		if x < width && row < height {
+			let y = if header.top_down {
+				row
+			} else {
+				height - 1 - row
+			};
+			pixels[y * width + x] = palette.get(idx as usize).copied().unwrap_or(0xFF000000);
+		}
+	};
+	// In RLE4 a byte holds two indices, high nibble first
+	let nibble = |byte: u8, n: usize| {
+		if n.is_multiple_of(2) {
+			byte >> 4
+		} else {
+			byte & 0x0F
+		}
+	};
+
+	let (mut x, mut row) = (0, 0);
+	let mut idx = pixel_offset;
+	while let Some(&[count, value]) = data.get(idx..idx + 2) {
+		idx += 2;
+		if count > 0 {
+			for n in 0..count as usize {
+				put(x, row, if four_bit { nibble(value, n) } else { value });
+				x += 1;
+			}
+			continue;
+		}
+		match value {
+			// End of line
+			0 => {
+				x = 0;
+				row += 1;
+			}
+			// End of bitmap
+			1 => break,
+			// Move the cursor
+			2 => {
+				let Some(&[dx, dy]) = data.get(idx..idx + 2) else {
+					break;
+				};
+				idx += 2;
+				x += dx as usize;
+				row += dy as usize;
+			}
+			// Run of literal indices, padded to 2 bytes
+			len => {
+				let len = len as usize;
+				let bytes = if four_bit { len.div_ceil(2) } else { len };
+				let Some(literal) = data.get(idx..idx + bytes) else {
+					break;
+				};
+				for n in 0..len {
+					let index = if four_bit {
+						nibble(literal[n / 2], n)
+					} else {
+						literal[n]
+					};
+					put(x, row, index);
+					x += 1;
+				}
+				idx += bytes.next_multiple_of(2);
+			}
+		}
+	}
+	pixels
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+	let bytes = data.get(offset..offset + 2).ok_or(Error::FileEnd)?;
+	// BMP uses Little Endian
+	Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+	let bytes = data.get(offset..offset + 4).ok_or(Error::FileEnd)?;
+	Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Info header of a `width`x`height` bitmap, negative heights are top-down.
+	fn info_header(width: i32, height: i32, bits_per_pixel: u16, compression: u32) -> Vec<u8> {
+		let mut header = Vec::with_capacity(INFO_HEADER_SIZE as usize);
+		header.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+		header.extend_from_slice(&width.to_le_bytes());
+		header.extend_from_slice(&height.to_le_bytes());
+		header.extend_from_slice(&1u16.to_le_bytes());
+		header.extend_from_slice(&bits_per_pixel.to_le_bytes());
+		header.extend_from_slice(&compression.to_le_bytes());
+		// Image size, resolution, colors used and important colors
+		header.resize(INFO_HEADER_SIZE as usize, 0);
+		header
+	}
+
+	/// BMP file of a DIB header followed by the palette or masks, then the pixel data.
+	fn bmp_file(dib_header: &[u8], palette: &[u8], pixel_data: &[u8]) -> Vec<u8> {
+		let pixel_offset = FILE_HEADER_SIZE + dib_header.len() + palette.len();
+		let mut file = b"BM".to_vec();
+		file.extend_from_slice(&((pixel_offset + pixel_data.len()) as u32).to_le_bytes());
+		file.extend_from_slice(&[0; 4]);
+		file.extend_from_slice(&(pixel_offset as u32).to_le_bytes());
+		file.extend_from_slice(dib_header);
+		file.extend_from_slice(palette);
+		file.extend_from_slice(pixel_data);
+		file
+	}
+
+	fn decode(file: &[u8]) -> (usize, usize, Vec<u32>) {
+		let bmp = Bmp::load_from_slice(file).unwrap();
+		(bmp.width, bmp.height, bmp.pixels)
+	}
+
+	#[test]
+	fn direct_color_bottom_up() {
+		// Rows of 2 pixels, 6 bytes padded to 8, the bottom row first
+		let pixel_data = [
+			0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0, 0, //
+			0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0, 0,
+		];
+		let file = bmp_file(&info_header(2, 2, 24, COMPRESSION_RGB), &[], &pixel_data);
+		assert_eq!(
+			decode(&file),
+			(2, 2, vec![0xFF0000FF, 0xFFFFFFFF, 0xFFFF0000, 0xFF00FF00])
+		);
+	}
+
+	#[test]
+	fn palette_top_down() {
+		// Blue, green, red, unused
+		let palette = [0x00, 0x00, 0xFF, 0, 0xFF, 0x00, 0x00, 0];
+		let file = bmp_file(
+			&info_header(3, -2, 1, COMPRESSION_RGB),
+			&palette,
+			&[0b1010_0000, 0, 0, 0, 0b0110_0000, 0, 0, 0],
+		);
+		assert_eq!(
+			decode(&file),
+			(
+				3,
+				2,
+				vec![0xFF0000FF, 0xFFFF0000, 0xFF0000FF, 0xFFFF0000, 0xFF0000FF, 0xFF0000FF]
+			)
+		);
+	}
+
+	#[test]
+	fn default_masks() {
+		// 5 bits per channel: white and pure red
+		let file = bmp_file(
+			&info_header(2, 1, 16, COMPRESSION_RGB),
+			&[],
+			&[0xFF, 0x7F, 0x00, 0x7C],
+		);
+		assert_eq!(decode(&file).2, [0xFFFFFFFF, 0xFFFF0000]);
+
+		// The fourth byte is alpha, unless it's zero everywhere
+		let file = bmp_file(
+			&info_header(2, 1, 32, COMPRESSION_RGB),
+			&[],
+			&[1, 2, 3, 0, 4, 5, 6, 0],
+		);
+		assert_eq!(decode(&file).2, [0xFF030201, 0xFF060504]);
+		let file = bmp_file(
+			&info_header(2, 1, 32, COMPRESSION_RGB),
+			&[],
+			&[1, 2, 3, 0, 4, 5, 6, 0x80],
+		);
+		assert_eq!(decode(&file).2, [0x00030201, 0x80060504]);
+	}
+
+	#[test]
+	fn bit_field_masks() {
+		// Red, green, blue and alpha masks after the info header, 4 bits each
+		let masks: Vec<u8> = [0x0F00u32, 0x00F0, 0x000F, 0xF000]
+			.iter()
+			.flat_map(|mask| mask.to_le_bytes())
+			.collect();
+		let file = bmp_file(
+			&info_header(1, 1, 16, COMPRESSION_ALPHABITFIELDS),
+			&masks,
+			&[0x5F, 0x8A, 0, 0],
+		);
+		assert_eq!(decode(&file).2, [0x88AA55FF]);
+	}
+
+	#[test]
+	fn rle8() {
+		let palette = [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0];
+		let pixel_data = [
+			// Three white pixels, end of line
+			3, 1, 0, 0, //
+			// A literal run of 3 (padded to 4 bytes), then move right by one
+			0, 3, 1, 0, 1, 0, 0, 2, 1, 0, //
+			// A pixel past the right edge is dropped, then the bitmap ends
+			1, 1, 0, 1,
+		];
+		let file = bmp_file(&info_header(4, 2, 8, COMPRESSION_RLE8), &palette, &pixel_data);
+		// Bottom-up: the first line is the bottom one, skipped pixels stay transparent
+		assert_eq!(
+			decode(&file).2,
+			[
+				0xFFFFFFFF, 0xFF000000, 0xFFFFFFFF, 0, //
+				0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0,
+			]
+		);
+	}
+
+	#[test]
+	fn core_header() {
+		let mut header = CORE_HEADER_SIZE.to_le_bytes().to_vec();
+		for field in [1u16, 1, 1, 8] {
+			header.extend_from_slice(&field.to_le_bytes());
+		}
+		// Palette entries are 3 bytes long
+		let palette = [0x10, 0x20, 0x30, 0x40, 0x50, 0x60];
+		let file = bmp_file(&header, &palette, &[1, 0, 0, 0]);
+		assert_eq!(decode(&file), (1, 1, vec![0xFF605040]));
+	}
+
+	#[test]
+	fn icon_mask() {
+		// The height covers the color bitmap and the mask
+		let mut dib = info_header(2, 4, 24, COMPRESSION_RGB);
+		let colors = [0xFF; 16];
+		// Rows are padded to 4 bytes, the left pixel of the bottom row is transparent
+		let mask = [0b1000_0000, 0, 0, 0, 0, 0, 0, 0];
+		dib.extend_from_slice(&colors);
+		dib.extend_from_slice(&mask);
+		let bmp = Bmp::load_from_icon(&dib).unwrap();
+		assert_eq!((bmp.width, bmp.height), (2, 2));
+		assert_eq!(bmp.pixels, [0xFFFFFFFF, 0xFFFFFFFF, 0, 0xFFFFFFFF]);
+	}
+
+	#[test]
+	fn damaged_files() {
+		let file = bmp_file(&info_header(2, 2, 24, COMPRESSION_RGB), &[], &[0; 16]);
+		for len in [0, 1, 2, 10, 20, file.len() - 1] {
+			assert!(Bmp::load_from_slice(&file[..len]).is_err(), "{len} bytes");
+		}
+		assert!(matches!(
+			Bmp::load_from_slice(&file[..file.len() - 1]),
+			Err(Error::FileEnd)
+		));
+		assert!(matches!(
+			Bmp::load_from_slice(b"PNG plus some bytes"),
+			Err(Error::BadMagic)
+		));
+
+		// Sizes are checked before allocating
+		for (width, height) in [(0, 1), (1, 0), (-1, 1), (1 << 20, 1 << 20), (1, i32::MIN)] {
+			let file = bmp_file(&info_header(width, height, 24, COMPRESSION_RGB), &[], &[]);
+			assert!(matches!(
+				Bmp::load_from_slice(&file),
+				Err(Error::InvalidSize)
+			));
+		}
+
+		let file = bmp_file(&info_header(1, 1, 24, COMPRESSION_RLE8), &[], &[0; 4]);
+		assert!(matches!(
+			Bmp::load_from_slice(&file),
+			Err(Error::UnsupportedFormat { .. })
+		));
+	}
+
+	#[test]
+	fn truncated_rle_ends_early() {
+		let palette = [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0];
+		// A literal run that's cut off
+		let file = bmp_file(&info_header(2, 1, 8, COMPRESSION_RLE8), &palette, &[1, 1, 0, 5, 1]);
+		assert_eq!(decode(&file).2, [0xFFFFFFFF, 0]);
+	}
+}
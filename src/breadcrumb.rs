@@ -0,0 +1,358 @@
+//! Breadcrumb bar below the toolbar. Shows the current path split into clickable segments.
+//! Clicking the empty space after the last segment, or the "Edit path" command, turns the bar into
+//! an editable path field.
+//! When the path doesn't fit, leading segments are collapsed into a "..." menu.
+
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+use crate::draw::{draw_rectangle, draw_text, DrawList, Rect};
+use crate::font;
+use crate::overlay::{LayerId, LayerKind, Overlays};
+use crate::text_input::{self, TextInput, TextInputEvent};
+use crate::toolbar;
+use crate::window::{BitmapData, Keyboard, Mouse};
+
+pub const TOP: usize = toolbar::HEIGHT;
+const PADDING: usize = 4;
+pub const HEIGHT: usize = text_input::HEIGHT + 2 * PADDING;
+const SEGMENT_PADDING: usize = 6;
+/// Minimum width of the clickable space after the last segment.
+const MIN_TAIL_WIDTH: usize = 48;
+
+const MENU_LAYER: LayerId = "breadcrumb.overflow_menu";
+
+const SEPARATOR: &str = ">";
+const OVERFLOW_LABEL: &str = "...";
+
+const BACKGROUND_COLOR: u32 = 0x32302f;
+const HOVER_COLOR: u32 = 0x504945;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const SEPARATOR_COLOR: u32 = 0x928374;
+const MENU_COLOR: u32 = 0x1d2021;
+
+#[derive(Debug, Default)]
+pub struct Breadcrumb {
+	/// Path field shown instead of the segments while editing.
+	editor: Option<TextInput>,
+	/// Last known cursor position, used for hover highlights.
+	mouse_pos: (i32, i32),
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+	label: String,
+	path: PathBuf,
+}
+
+/// Positions of breadcrumb elements for the current path and window width.
+struct Layout {
+	/// Segments hidden behind the overflow button, in path order.
+	collapsed: Vec<Segment>,
+	/// Overflow button position and width, present if any segments are collapsed.
+	overflow_button: Option<(usize, usize)>,
+	/// Visible segments with their x position and width.
+	visible: Vec<(Segment, usize, usize)>,
+	/// Where the clickable empty space after the last segment starts.
+	tail_x: usize,
+}
+
+impl Breadcrumb {
+	pub fn is_editing(&self) -> bool {
+		self.editor.is_some()
+	}
+
+	/// Turns the bar into a path field holding `current`.
+	pub fn start_editing(&mut self, current: &Path) {
+		self.editor = Some(TextInput::new(current.display().to_string()));
+	}
+
+	/// Contents of the path field while editing.
+	pub fn editor_text(&self) -> Option<&str> {
+		self.editor.as_ref().map(TextInput::text)
+	}
+
+	/// Handles input of the current frame. Returns the path the user wants to navigate to.
+	/// Paths typed into the editor are returned as-is, without checking that they exist.
+	pub fn update(
+		&mut self,
+		current: &Path,
+		mouse: &Mouse,
+		keyboard: &Keyboard,
+		width: usize,
+		overlays: &mut Overlays,
+	) -> Option<PathBuf> {
+		self.mouse_pos = mouse.position();
+		let click = mouse.was_left_pressed().then_some(self.mouse_pos);
+
+		if let Some(editor) = &mut self.editor {
+			match editor.update(keyboard) {
+				Some(TextInputEvent::Submit) => {
+					let path = PathBuf::from(editor.text().trim());
+					self.editor = None;
+					return Some(path);
+				}
+				Some(TextInputEvent::Cancel) => self.editor = None,
+				Some(TextInputEvent::Changed) | None => (),
+			}
+			if click.is_some_and(|(_, y)| !in_bar(y)) {
+				self.editor = None;
+			}
+			return None;
+		}
+
+		let layout = layout(current, width);
+		if let Some(pos) = overlays.take_click(MENU_LAYER) {
+			overlays.close(MENU_LAYER);
+			let (menu_x, _) = layout.overflow_button?;
+			return menu_item_at(&layout.collapsed, menu_x, pos).map(|s| s.path.clone());
+		}
+		if overlays.is_open(MENU_LAYER) {
+			// Redrawn every frame for the hover highlight, the layout may have changed as well
+			match layout.overflow_button {
+				Some((menu_x, _)) => show_menu(overlays, &layout.collapsed, menu_x, self.mouse_pos),
+				None => overlays.close(MENU_LAYER),
+			}
+		}
+
+		let (x, y) = click?;
+
+		if !in_bar(y) || x < 0 {
+			return None;
+		}
+		let x = x as usize;
+
+		if let Some((button_x, button_width)) = layout.overflow_button {
+			if (button_x..button_x + button_width).contains(&x) {
+				show_menu(overlays, &layout.collapsed, button_x, self.mouse_pos);
+				return None;
+			}
+		}
+		if let Some((segment, _, _)) = layout
+			.visible
+			.iter()
+			.find(|(_, seg_x, seg_width)| (*seg_x..seg_x + seg_width).contains(&x))
+		{
+			return Some(segment.path.clone());
+		}
+		if x >= layout.tail_x {
+			self.start_editing(current);
+		}
+		None
+	}
+
+	/// The overflow menu is drawn separately, as a layer of `overlays`.
+	pub fn render(&self, bitmap_data: BitmapData, current: &Path, overlays: &Overlays) {
+		let width = bitmap_data.bitmap_width as usize;
+		draw_rectangle(bitmap_data, (0, TOP), (width, HEIGHT), BACKGROUND_COLOR);
+
+		if let Some(editor) = &self.editor {
+			editor.render(
+				bitmap_data,
+				(PADDING, TOP + PADDING),
+				width.saturating_sub(2 * PADDING),
+			);
+			return;
+		}
+
+		let layout = layout(current, width);
+		let text_y = TOP + PADDING * 2 + font::SCALE;
+		let (mouse_x, mouse_y) = self.mouse_pos;
+		let hovered = |x: usize, width: usize| {
+			in_bar(mouse_y) && mouse_x >= 0 && (x..x + width).contains(&(mouse_x as usize))
+		};
+		let button_y = TOP + PADDING;
+
+		if let Some((x, button_width)) = layout.overflow_button {
+			if hovered(x, button_width) || overlays.is_open(MENU_LAYER) {
+				draw_rectangle(
+					bitmap_data,
+					(x, button_y),
+					(button_width, text_input::HEIGHT),
+					HOVER_COLOR,
+				);
+			}
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				OVERFLOW_LABEL,
+				(x + SEGMENT_PADDING, text_y),
+				TEXT_COLOR,
+			);
+			let separator_x = x + button_width;
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				SEPARATOR,
+				(separator_x, text_y),
+				SEPARATOR_COLOR,
+			);
+		}
+
+		for (idx, (segment, x, segment_width)) in layout.visible.iter().enumerate() {
+			if hovered(*x, *segment_width) {
+				draw_rectangle(
+					bitmap_data,
+					(*x, button_y),
+					(*segment_width, text_input::HEIGHT),
+					HOVER_COLOR,
+				);
+			}
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&segment.label,
+				(x + SEGMENT_PADDING, text_y),
+				TEXT_COLOR,
+			);
+			if idx + 1 < layout.visible.len() {
+				let separator_x = x + segment_width;
+				draw_text(
+					bitmap_data,
+					font::ui(),
+					SEPARATOR,
+					(separator_x, text_y),
+					SEPARATOR_COLOR,
+				);
+			}
+		}
+	}
+}
+
+fn in_bar(y: i32) -> bool {
+	(TOP as i32..(TOP + HEIGHT) as i32).contains(&y)
+}
+
+fn segments(path: &Path) -> Vec<Segment> {
+	let mut segments: Vec<Segment> = Vec::new();
+	let mut current = PathBuf::new();
+	for component in path.components() {
+		current.push(component);
+		match component {
+			Component::Prefix(prefix) => segments.push(Segment {
+				label: prefix.as_os_str().to_string_lossy().into_owned(),
+				path: current.clone(),
+			}),
+			// On Windows the root directory belongs to the drive segment ("C:" + "\")
+			Component::RootDir => match segments.last_mut() {
+				Some(drive) => drive.path = current.clone(),
+				None => segments.push(Segment {
+					label: MAIN_SEPARATOR.to_string(),
+					path: current.clone(),
+				}),
+			},
+			Component::Normal(name) => segments.push(Segment {
+				label: name.to_string_lossy().into_owned(),
+				path: current.clone(),
+			}),
+			Component::CurDir | Component::ParentDir => (),
+		}
+	}
+	segments
+}
+
+fn segment_width(label: &str) -> usize {
+	font::ui().text_width(label) + 2 * SEGMENT_PADDING
+}
+
+fn layout(path: &Path, width: usize) -> Layout {
+	let mut collapsed = segments(path);
+	let separator_width = font::ui().text_width(SEPARATOR);
+	let available = width.saturating_sub(2 * PADDING + MIN_TAIL_WIDTH);
+	let overflow_width = segment_width(OVERFLOW_LABEL) + separator_width;
+
+	// Going from the last segment backwards, take as many segments as fit. The last segment is
+	// always shown, even if it has to be clipped.
+	let mut used = 0;
+	let mut visible_count = 0;
+	for (idx, segment) in collapsed.iter().enumerate().rev() {
+		let needed = segment_width(&segment.label) + separator_width;
+		let overflow = if idx > 0 { overflow_width } else { 0 };
+		if visible_count > 0 && used + needed + overflow > available {
+			break;
+		}
+		used += needed;
+		visible_count += 1;
+	}
+
+	let shown = collapsed.split_off(collapsed.len() - visible_count);
+	let mut x = PADDING;
+	let overflow_button = (!collapsed.is_empty()).then(|| {
+		let button = (x, segment_width(OVERFLOW_LABEL));
+		x += overflow_width;
+		button
+	});
+
+	let mut visible = Vec::with_capacity(shown.len());
+	for segment in shown {
+		let segment_width = segment_width(&segment.label);
+		visible.push((segment, x, segment_width));
+		x += segment_width + separator_width;
+	}
+
+	Layout {
+		collapsed,
+		overflow_button,
+		visible,
+		tail_x: x,
+	}
+}
+
+/// Collapsed segments are listed nearest ancestor first.
+fn menu_items(collapsed: &[Segment]) -> impl Iterator<Item = (usize, &Segment)> {
+	collapsed.iter().rev().enumerate()
+}
+
+fn menu_item_rect(menu_x: usize, row: usize, width: usize) -> (usize, usize, usize, usize) {
+	let row_height = font::LINE_HEIGHT + 2 * PADDING;
+	(menu_x, TOP + HEIGHT + row * row_height, width, row_height)
+}
+
+fn menu_width(collapsed: &[Segment]) -> usize {
+	collapsed
+		.iter()
+		.map(|segment| segment_width(&segment.label))
+		.max()
+		.unwrap_or_default()
+}
+
+fn menu_item_at(collapsed: &[Segment], menu_x: usize, (x, y): (i32, i32)) -> Option<&Segment> {
+	if x < 0 || y < 0 {
+		return None;
+	}
+	let width = menu_width(collapsed);
+	menu_items(collapsed)
+		.find(|&(row, _)| {
+			let (item_x, item_y, item_width, item_height) = menu_item_rect(menu_x, row, width);
+			(item_x..item_x + item_width).contains(&(x as usize))
+				&& (item_y..item_y + item_height).contains(&(y as usize))
+		})
+		.map(|(_, segment)| segment)
+}
+
+/// Opens the overflow menu layer, or redraws it if it's already open.
+fn show_menu(overlays: &mut Overlays, collapsed: &[Segment], menu_x: usize, mouse: (i32, i32)) {
+	let width = menu_width(collapsed);
+	let (_, menu_y, _, row_height) = menu_item_rect(menu_x, 0, width);
+	let bounds = Rect::new(menu_x, menu_y, width, collapsed.len() * row_height);
+	let draw_list = overlays.open(MENU_LAYER, LayerKind::Popup, bounds);
+	record_menu(draw_list, collapsed, menu_x, mouse);
+}
+
+fn record_menu(draw_list: &mut DrawList, collapsed: &[Segment], menu_x: usize, mouse: (i32, i32)) {
+	let width = menu_width(collapsed);
+	let hovered = menu_item_at(collapsed, menu_x, mouse).map(|segment| &segment.path);
+	for (row, segment) in menu_items(collapsed) {
+		let (x, y, width, height) = menu_item_rect(menu_x, row, width);
+		let color = if hovered == Some(&segment.path) {
+			HOVER_COLOR
+		} else {
+			MENU_COLOR
+		};
+		draw_list.rectangle((x, y), (width, height), color);
+		draw_list.text(
+			segment.label.as_str(),
+			(x + SEGMENT_PADDING, y + PADDING + font::SCALE),
+			TEXT_COLOR,
+		);
+	}
+}
@@ -0,0 +1,90 @@
+//! Reading images from the clipboard. PNG data, as put there by browsers and most screenshot
+//! tools, is preferred over CF_DIB, which loses transparency.
+
+use std::fmt::Display;
+
+use windows::Win32::Foundation::{HGLOBAL, HWND};
+use windows::Win32::System::DataExchange::{
+	CloseClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+	RegisterClipboardFormatW,
+};
+use windows::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+use windows::Win32::System::Ole::CF_DIB;
+
+use crate::bmp::{self, Bmp};
+use crate::draw::Texture;
+use crate::png::{self, Png};
+use crate::string::WideString;
+
+#[derive(Debug)]
+pub enum Error {
+	/// Another application has the clipboard open.
+	Busy,
+	Png(png::Error),
+	Bmp(bmp::Error),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Error::Busy => write!(f, "The clipboard is in use by another application"),
+			Error::Png(err) => write!(f, "Invalid PNG image on the clipboard: {err}"),
+			Error::Bmp(err) => write!(f, "Invalid bitmap on the clipboard: {err}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Keeps the clipboard open until dropped.
+struct OpenedClipboard;
+
+impl OpenedClipboard {
+	fn open() -> Result<Self, Error> {
+		// Without an owner window the clipboard is associated with the current task
+		match unsafe { OpenClipboard(HWND::default()) }.as_bool() {
+			true => Ok(OpenedClipboard),
+			false => Err(Error::Busy),
+		}
+	}
+
+	/// Copy of the clipboard's data in `format`, None if there's none.
+	fn data(&self, format: u32) -> Option<Vec<u8>> {
+		if !unsafe { IsClipboardFormatAvailable(format) }.as_bool() {
+			return None;
+		}
+		// Owned by the clipboard, only locked for copying
+		let handle = unsafe { GetClipboardData(format) }.ok()?;
+		let global = HGLOBAL(handle.0);
+		let ptr = unsafe { GlobalLock(global) };
+		if ptr.is_null() {
+			return None;
+		}
+		// Safety: the locked memory is GlobalSize bytes long
+		let data = unsafe { std::slice::from_raw_parts(ptr as *const u8, GlobalSize(global)) };
+		let data = data.to_vec();
+		unsafe { GlobalUnlock(global) };
+		Some(data)
+	}
+}
+
+impl Drop for OpenedClipboard {
+	fn drop(&mut self) {
+		unsafe { CloseClipboard() };
+	}
+}
+
+/// The image on the clipboard, None if it holds something else.
+pub fn image() -> Result<Option<Texture>, Error> {
+	let png_format = unsafe { RegisterClipboardFormatW(WideString::new("PNG").as_pcwstr()) };
+	let clipboard = OpenedClipboard::open()?;
+	if let Some(data) = clipboard.data(png_format) {
+		let png = Png::load_from_slice(&data).map_err(Error::Png)?;
+		return Ok(Some(png.into()));
+	}
+	if let Some(data) = clipboard.data(CF_DIB.0 as u32) {
+		let bmp = Bmp::load_from_dib(&data).map_err(Error::Bmp)?;
+		return Ok(Some(bmp.into()));
+	}
+	Ok(None)
+}
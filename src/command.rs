@@ -0,0 +1,908 @@
+//! Registry of named commands. Every action that should be reachable from the command palette,
+//! the toolbar and shortcuts is registered here under a stable id. Along with the action, commands
+//! carry what's needed to show hints for them: a description, the shortcut bound in the keymap and
+//! whether they can run right now.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use log::{error, info};
+
+use crate::animation;
+use crate::app::{self, State, ViewMode};
+use crate::clipboard;
+#[cfg(feature = "demo")]
+use crate::demo;
+use crate::error::{self, Context, ErrorKind};
+use crate::filename;
+use crate::font;
+use crate::fs::SortKey;
+use crate::i18n::tr;
+use crate::key::{Key, MouseButton};
+use crate::keymap::Keymap;
+use crate::ops::{self, Operation};
+use crate::pane::{Pane, Side};
+use crate::plugin::Plugins;
+use crate::png::Png;
+use crate::profiler;
+use crate::screen::{self, Properties, Screen};
+use crate::shortcut::{Chord, Shortcut};
+use crate::state_dump;
+use crate::status_bar::{self, StatusEvent};
+use crate::toast::Toast;
+
+#[derive(Debug, Copy, Clone)]
+pub enum CommandAction {
+	Builtin(fn(&mut State)),
+	/// Command of a plugin, run on the selected files.
+	Plugin,
+}
+
+/// Shortcuts that directly execute a command.
+pub const KEY_BINDINGS: &[(Shortcut, &str)] = &[
+	(Shortcut::new(Key::BrowserBack), "navigate.back"),
+	(Shortcut::new(Key::BrowserForward), "navigate.forward"),
+	(Shortcut::mouse(MouseButton::Button4), "navigate.back"),
+	(Shortcut::mouse(MouseButton::Button5), "navigate.forward"),
+	(Shortcut::new(Key::Back), "navigate.up"),
+	(Shortcut::new(Key::F3), "debug.dump_state"),
+	(Shortcut::new(Key::F5), "listing.refresh"),
+	(Shortcut::new(Key::N).ctrl().shift(), "listing.new_folder"),
+	(Shortcut::new(Key::Return).alt(), "listing.properties"),
+	(Shortcut::new(Key::C).ctrl(), "listing.copy"),
+	(Shortcut::new(Key::X).ctrl(), "listing.cut"),
+	(Shortcut::new(Key::V).ctrl(), "listing.paste"),
+	(Shortcut::new(Key::L).ctrl(), "navigate.edit_path"),
+	(Shortcut::new(Key::F).ctrl(), "search.open"),
+	(Shortcut::new(Key::D).alt(), "navigate.edit_path"),
+	(
+		Shortcut::new(Key::D).ctrl().shift(),
+		"view.toggle_dual_pane",
+	),
+	(Shortcut::new(Key::Tab), "pane.switch"),
+	(Shortcut::new(Key::F5).shift(), "pane.copy_to_other"),
+	(Shortcut::new(Key::F6), "pane.move_to_other"),
+	(Shortcut::new(Key::GamepadB), "navigate.up"),
+	(Shortcut::new(Key::GamepadLeftShoulder), "navigate.back"),
+	(Shortcut::new(Key::GamepadRightShoulder), "navigate.forward"),
+	(Shortcut::new(Key::GamepadY), "view.toggle_mode"),
+];
+
+/// Chords that directly execute a command, for those used to vim-style navigation.
+pub const CHORD_BINDINGS: &[(Chord, &str)] = &[
+	(
+		Shortcut::new(Key::G).then(Shortcut::new(Key::H)),
+		"navigate.home",
+	),
+	(
+		Shortcut::new(Key::G).then(Shortcut::new(Key::D)),
+		"navigate.downloads",
+	),
+];
+
+#[derive(Debug)]
+pub struct Command {
+	/// Stable identifier, e.g. `"debug.dump_state"`.
+	pub id: &'static str,
+	/// Human readable name shown in the UI.
+	pub title: &'static str,
+	/// One sentence about what the command does, shown below the title.
+	pub description: &'static str,
+	pub action: CommandAction,
+	/// First shortcut bound to the command, see [CommandRegistry::set_shortcuts].
+	pub shortcut: Option<Chord>,
+	/// Whether the command can run in the current state, see [update_enabled].
+	pub enabled: bool,
+	is_enabled: fn(&State) -> bool,
+}
+
+impl Command {
+	/// Disables the command while `predicate` returns false.
+	pub fn enabled_when(&mut self, predicate: fn(&State) -> bool) -> &mut Command {
+		self.is_enabled = predicate;
+		self
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct CommandRegistry {
+	commands: Vec<Command>,
+}
+
+impl CommandRegistry {
+	/// Registry populated with all the built-in commands.
+	pub fn with_builtin_commands() -> Self {
+		let mut registry = CommandRegistry::default();
+		registry.register(
+			"debug.dump_state",
+			"Dump state to file",
+			"Saves the explorer's state as JSON for bug reports.",
+			dump_state,
+		);
+		registry.register(
+			"debug.toggle_panel",
+			"Toggle debug panel",
+			"Shows or hides the panel with timings and debugging tools.",
+			toggle_debug_panel,
+		);
+		registry.register(
+			"debug.toggle_trace",
+			"Start/stop frame trace",
+			"Records the frame stages into a trace file until run again.",
+			toggle_trace,
+		);
+		#[cfg(feature = "demo")]
+		demo::register_commands(&mut registry);
+		registry
+			.register(
+				"navigate.back",
+				"Go back",
+				"Opens the previously visited folder.",
+				navigate_back,
+			)
+			.enabled_when(|state| state.history.can_go_back());
+		registry
+			.register(
+				"navigate.forward",
+				"Go forward",
+				"Opens the folder left by going back.",
+				navigate_forward,
+			)
+			.enabled_when(|state| state.history.can_go_forward());
+		registry
+			.register(
+				"navigate.up",
+				"Go to parent directory",
+				"Opens the folder containing the current one.",
+				navigate_up,
+			)
+			.enabled_when(|state| state.history.current().parent().is_some());
+		registry.register(
+			"navigate.edit_path",
+			"Edit path",
+			"Turns the breadcrumb bar into a field for typing a folder to open.",
+			edit_path,
+		);
+		registry.register(
+			"navigate.home",
+			"Go to home directory",
+			"Opens your user profile folder.",
+			navigate_home,
+		);
+		registry.register(
+			"navigate.downloads",
+			"Go to Downloads",
+			"Opens the Downloads folder of your user profile.",
+			navigate_downloads,
+		);
+		registry.register(
+			"listing.refresh",
+			"Refresh",
+			"Lists the current folder again.",
+			refresh,
+		);
+		registry.register(
+			"listing.open_terminal",
+			"Open terminal here",
+			"Runs the terminal command of the settings in the current folder.",
+			open_terminal,
+		);
+		registry.register(
+			"listing.new_folder",
+			"New folder",
+			"Creates an empty folder in the current one.",
+			new_folder,
+		);
+		registry
+			.register(
+				"listing.undo_new_folder",
+				"Undo new folder",
+				"Removes the folder created last, if it's still empty.",
+				undo_new_folder,
+			)
+			.enabled_when(|state| state.last_new_folder.is_some());
+		registry
+			.register(
+				"listing.properties",
+				"Properties",
+				"Shows the details of the selected item.",
+				show_properties,
+			)
+			.enabled_when(|state| !state.selected_paths().is_empty());
+		registry
+			.register(
+				"listing.copy",
+				"Copy",
+				"Remembers the selected items for pasting copies of them.",
+				copy_selection,
+			)
+			.enabled_when(|state| !state.selected_paths().is_empty());
+		registry
+			.register(
+				"listing.cut",
+				"Cut",
+				"Remembers the selected items for moving them with Paste.",
+				cut_selection,
+			)
+			.enabled_when(|state| !state.selected_paths().is_empty());
+		registry
+			.register(
+				"listing.paste",
+				"Paste",
+				"Copies or moves the copied or cut items here, or saves a copied image.",
+				paste,
+			)
+			.enabled_when(|state| state.operation.is_none());
+		registry
+			.register(
+				"listing.cancel_operation",
+				"Cancel copying",
+				"Stops the running copy or move, keeping the files done so far.",
+				cancel_operation,
+			)
+			.enabled_when(|state| state.operation.is_some());
+		registry.register(
+			"listing.paste_image",
+			"Paste image as file",
+			"Saves the image on the clipboard as a PNG file in the current folder.",
+			paste_image,
+		);
+		registry.register(
+			"listing.sort_by_name",
+			"Sort by name",
+			"Sorts the list by name, or the other way around if it already is.",
+			|state| sort_by(state, SortKey::Name),
+		);
+		registry.register(
+			"listing.sort_by_size",
+			"Sort by size",
+			"Sorts the list by size, or the other way around if it already is.",
+			|state| sort_by(state, SortKey::Size),
+		);
+		registry.register(
+			"listing.sort_by_modified",
+			"Sort by date modified",
+			"Sorts the list by when the items were last changed, or the other way around if it already is.",
+			|state| sort_by(state, SortKey::Modified),
+		);
+		registry.register(
+			"listing.sort_by_type",
+			"Sort by type",
+			"Sorts the list by file extension, or the other way around if it already is.",
+			|state| sort_by(state, SortKey::Type),
+		);
+		registry.register(
+			"listing.toggle_folders_first",
+			"Toggle folders first",
+			"Lists folders before the files, or sorts them along with the files.",
+			toggle_folders_first,
+		);
+		registry.register(
+			"view.toggle_mode",
+			"Switch view mode",
+			"Switches between the list and the thumbnail grid.",
+			toggle_view_mode,
+		);
+		registry.register(
+			"view.toggle_dual_pane",
+			"Toggle dual-pane view",
+			"Shows a second listing next to the current one, or hides it.",
+			toggle_dual_pane,
+		);
+		registry
+			.register(
+				"pane.switch",
+				"Switch pane",
+				"Makes the other pane of the dual-pane view the active one.",
+				app::switch_pane,
+			)
+			.enabled_when(|state| state.other_pane.is_some());
+		registry
+			.register(
+				"pane.copy_to_other",
+				"Copy to other pane",
+				"Copies the selected items into the folder of the other pane.",
+				copy_to_other_pane,
+			)
+			.enabled_when(can_transfer_to_other_pane);
+		registry
+			.register(
+				"pane.move_to_other",
+				"Move to other pane",
+				"Moves the selected items into the folder of the other pane.",
+				move_to_other_pane,
+			)
+			.enabled_when(can_transfer_to_other_pane);
+		registry.register(
+			"search.open",
+			"Search",
+			"Searches the current folder and its subfolders for names or patterns like *.png.",
+			open_search,
+		);
+		registry
+			.register(
+				"search.rebuild_index",
+				"Rebuild search index",
+				"Indexes the current folder again, e.g. after changes made by other programs.",
+				rebuild_search_index,
+			)
+			.enabled_when(|state| state.search_indexes.is_enabled());
+		registry.register(
+			"settings.open",
+			"Open settings",
+			"Opens the settings screen.",
+			open_settings,
+		);
+		registry.register(
+			"settings.toggle_animations",
+			"Toggle animations",
+			"Turns animations on or off until the next start.",
+			toggle_animations,
+		);
+		registry
+	}
+
+	/// Registers a new command, enabled unless [Command::enabled_when] says otherwise.
+	/// Registering an id twice replaces the previous command.
+	pub fn register(
+		&mut self,
+		id: &'static str,
+		title: &'static str,
+		description: &'static str,
+		action: fn(&mut State),
+	) -> &mut Command {
+		self.insert(Command {
+			id,
+			title,
+			description,
+			action: CommandAction::Builtin(action),
+			shortcut: None,
+			enabled: true,
+			is_enabled: |_| true,
+		})
+	}
+
+	/// Registers the commands of all `plugins`. They are enabled while something is selected.
+	pub fn register_plugin_commands(&mut self, plugins: &Plugins) {
+		for command in plugins.commands() {
+			self.insert(Command {
+				id: command.id,
+				title: command.title,
+				// Plugins only provide a title
+				description: command.title,
+				action: CommandAction::Plugin,
+				shortcut: None,
+				enabled: true,
+				is_enabled: |state| !state.selected_paths().is_empty(),
+			});
+		}
+	}
+
+	fn insert(&mut self, command: Command) -> &mut Command {
+		let id = command.id;
+		match self.commands.iter().position(|c| c.id == id) {
+			Some(idx) => {
+				self.commands[idx] = command;
+				&mut self.commands[idx]
+			}
+			None => {
+				self.commands.push(command);
+				self.commands.last_mut().unwrap()
+			}
+		}
+	}
+
+	/// Updates the shortcut of every command after the keymap changed.
+	pub fn set_shortcuts(&mut self, keymap: &Keymap) {
+		for command in &mut self.commands {
+			command.shortcut = keymap.chords_of(command.id).next();
+		}
+	}
+
+	pub fn get(&self, id: &str) -> Option<&Command> {
+		self.commands.iter().find(|c| c.id == id)
+	}
+
+	pub fn commands(&self) -> &[Command] {
+		&self.commands
+	}
+}
+
+/// Refreshes [Command::enabled] of every command, once per frame.
+pub fn update_enabled(state: &mut State) {
+	let enabled: Vec<bool> = state
+		.commands
+		.commands
+		.iter()
+		.map(|command| (command.is_enabled)(state))
+		.collect();
+	for (command, enabled) in state.commands.commands.iter_mut().zip(enabled) {
+		command.enabled = enabled;
+	}
+}
+
+/// Runs the command registered under `id`, unless it's disabled. Returns false if there is no such
+/// command.
+pub fn execute(state: &mut State, id: &str) -> bool {
+	let Some(command) = state.commands.get(id) else {
+		return false;
+	};
+	let (title, action) = (command.title, command.action);
+	if !(command.is_enabled)(state) {
+		info!("Command {id} is disabled");
+		message(state, tr!("command_disabled", title));
+		return true;
+	}
+	info!("Executing command {id}");
+	// Posted before running the action so that the command can replace it with its own message
+	state
+		.status_bar
+		.handle(StatusEvent::OperationMessage(title.to_string()));
+	match action {
+		CommandAction::Builtin(action) => action(state),
+		CommandAction::Plugin => run_plugin_command(state, id),
+	}
+	true
+}
+
+/// Runs the plugin command `id` on every selected file it applies to.
+fn run_plugin_command(state: &mut State, id: &str) {
+	let plugins = Arc::clone(&state.plugins);
+	let Some(command) = plugins.command(id) else {
+		return;
+	};
+	let mut paths = state.selected_paths();
+	paths.retain(|path| command.applies_to(path));
+	if paths.is_empty() {
+		message(state, tr!("plugin_no_files"));
+		return;
+	}
+	for path in paths {
+		if let Err(err) = command.run(&path) {
+			error!("{err}");
+			state
+				.toasts
+				.push(Toast::error(tr!("plugin_failed", err.to_string())));
+		}
+	}
+}
+
+fn dump_state(state: &mut State) {
+	let toast = match state_dump::save(state) {
+		Ok(path) => {
+			info!("Dumped state to {}", path.display());
+			let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+			Toast::info(tr!("state_dumped", path))
+		}
+		Err(err) => {
+			error!("Failed to dump state: {err}");
+			Toast::error(tr!("state_dump_failed", err.to_string()))
+		}
+	};
+	state.toasts.push(toast);
+}
+
+fn toggle_debug_panel(state: &mut State) {
+	state.debug_panel.toggle();
+}
+
+/// Records a trace of the frame stages, saved to the temp directory once stopped.
+fn toggle_trace(state: &mut State) {
+	if !profiler::is_tracing() {
+		profiler::start_trace();
+		message(state, tr!("trace_started"));
+		return;
+	}
+	let path = std::env::temp_dir().join("file-explorer-trace.json");
+	match profiler::stop_trace(&path) {
+		Ok(()) => {
+			info!("Saved trace to {}", path.display());
+			let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+			message(state, tr!("trace_saved", path));
+		}
+		Err(err) => {
+			error!("Failed to save trace to {}: {err}", path.display());
+			state
+				.toasts
+				.push(Toast::error(tr!("trace_failed", err.to_string())));
+		}
+	}
+}
+
+fn navigate_back(state: &mut State) {
+	if !state.history.back() {
+		message(state, tr!("nothing_to_go_back"));
+	}
+}
+
+fn navigate_forward(state: &mut State) {
+	if !state.history.forward() {
+		message(state, tr!("nothing_to_go_forward"));
+	}
+}
+
+fn navigate_up(state: &mut State) {
+	if !state.history.up() {
+		message(state, tr!("already_at_top"));
+	}
+}
+
+fn edit_path(state: &mut State) {
+	let current = state.history.current().to_path_buf();
+	state.breadcrumb.start_editing(&current);
+}
+
+fn navigate_home(state: &mut State) {
+	match home_dir() {
+		Some(home) => go_to(state, home),
+		None => message(state, tr!("home_unknown")),
+	}
+}
+
+fn navigate_downloads(state: &mut State) {
+	match home_dir() {
+		Some(home) => go_to(state, home.join("Downloads")),
+		None => message(state, tr!("home_unknown")),
+	}
+}
+
+/// The user's profile folder.
+pub fn home_dir() -> Option<PathBuf> {
+	std::env::var_os("USERPROFILE").map(PathBuf::from)
+}
+
+fn go_to(state: &mut State, path: PathBuf) {
+	if path.is_dir() {
+		state.history.navigate_to(path);
+	} else {
+		let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+		message(state, tr!("not_a_directory", path));
+	}
+}
+
+fn show_properties(state: &mut State) {
+	let Some(path) = state.selected_paths().into_iter().next() else {
+		message(state, tr!("nothing_selected"));
+		return;
+	};
+	match Properties::read(path.clone()) {
+		Ok(properties) => state.screens.push(Screen::Properties(properties)),
+		Err(err) => {
+			let path = font::ui().compact_path(&path, status_bar::MESSAGE_PATH_WIDTH);
+			state
+				.toasts
+				.push(Toast::error(tr!("failed_to_read", path, err.to_string())));
+		}
+	}
+}
+
+fn open_search(state: &mut State) {
+	screen::open_search(state, String::new());
+}
+
+fn rebuild_search_index(state: &mut State) {
+	let dir = state.history.current().to_path_buf();
+	state.search_indexes.rebuild(&dir);
+	message(state, tr!("search_index_rebuilding"));
+}
+
+fn open_settings(state: &mut State) {
+	state.screens.push(Screen::Settings {
+		selected_command: None,
+		recording: false,
+	});
+}
+
+fn refresh(state: &mut State) {
+	// Reloaded on the next update
+	state.thumbnails = None;
+	state.list_view.reload();
+	let path = font::ui().compact_path(state.history.current(), status_bar::MESSAGE_PATH_WIDTH);
+	message(state, tr!("refreshed", path));
+}
+
+/// Runs the terminal command of the configuration in the current directory.
+fn open_terminal(state: &mut State) {
+	let dir = state.history.current();
+	let mut args = split_command_line(&state.config.terminal_command)
+		.into_iter()
+		.map(|arg| arg.replace("{dir}", &dir.to_string_lossy()));
+	let Some(program) = args.next() else {
+		state
+			.toasts
+			.push(Toast::error(tr!("terminal_failed", "no command set")));
+		return;
+	};
+	let mut command = std::process::Command::new(&program);
+	command.args(args).current_dir(dir);
+	#[cfg(windows)]
+	{
+		use std::os::windows::process::CommandExt;
+		// Console programs would otherwise share the console of a debug build
+		command.creation_flags(CREATE_NEW_CONSOLE);
+	}
+	if let Err(err) = command.spawn() {
+		error!("Failed to run {program}: {err}");
+		state
+			.toasts
+			.push(Toast::error(tr!("terminal_failed", err.to_string())));
+	}
+}
+
+#[cfg(windows)]
+const CREATE_NEW_CONSOLE: u32 = 0x10;
+
+/// Splits `line` at whitespace outside of double quotes. The quotes are removed.
+fn split_command_line(line: &str) -> Vec<String> {
+	let mut args = Vec::new();
+	let mut arg: Option<String> = None;
+	let mut quoted = false;
+	for c in line.chars() {
+		match c {
+			'"' => {
+				quoted = !quoted;
+				arg.get_or_insert_with(String::new);
+			}
+			c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+			c => arg.get_or_insert_with(String::new).push(c),
+		}
+	}
+	args.extend(arg);
+	args
+}
+
+fn new_folder(state: &mut State) {
+	let toast = match create_new_folder(state.history.current().to_path_buf()) {
+		Ok(path) => {
+			let toast = Toast::info(tr!("folder_created", file_name(&path)))
+				.with_action(tr!("undo"), "listing.undo_new_folder");
+			state.last_new_folder = Some(path);
+			toast
+		}
+		Err(err) => {
+			error!("Failed to create a folder: {err}");
+			match err.kind() {
+				ErrorKind::PermissionDenied => Toast::error(tr!("folder_create_denied")),
+				_ => Toast::error(tr!("folder_create_failed", err.to_string())),
+			}
+		}
+	};
+	state.toasts.push(toast);
+}
+
+/// Removes the folder created by the last [new_folder], as long as it's still empty.
+fn undo_new_folder(state: &mut State) {
+	let Some(path) = state.last_new_folder.take() else {
+		message(state, tr!("nothing_to_undo"));
+		return;
+	};
+	let toast = match std::fs::remove_dir(&path) {
+		Ok(()) => Toast::info(tr!("folder_removed", file_name(&path))),
+		Err(err) => {
+			error!("Failed to remove {}: {err}", path.display());
+			Toast::error(tr!(
+				"folder_remove_failed",
+				file_name(&path),
+				err.to_string()
+			))
+		}
+	};
+	state.toasts.push(toast);
+}
+
+fn file_name(path: &Path) -> String {
+	path.file_name()
+		.unwrap_or(path.as_os_str())
+		.to_string_lossy()
+		.into_owned()
+}
+
+/// Creates a "New folder" directory, or "New folder (n)" if such a name is already taken.
+fn create_new_folder(mut path: PathBuf) -> error::Result<PathBuf> {
+	// Translations may not make valid names
+	path.push(filename::sanitize(&tr!("new_folder_name")));
+	for n in 2_u32.. {
+		match std::fs::create_dir(&path) {
+			Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+				path.set_file_name(filename::sanitize(&tr!("new_folder_name_numbered", n)));
+			}
+			result => return result.with_path(&path).map(|_| path),
+		}
+	}
+	unreachable!()
+}
+
+fn copy_selection(state: &mut State) {
+	remember_selection(state, ops::Kind::Copy);
+}
+
+fn cut_selection(state: &mut State) {
+	remember_selection(state, ops::Kind::Move);
+}
+
+fn remember_selection(state: &mut State, kind: ops::Kind) {
+	let paths = state.selected_paths();
+	if paths.is_empty() {
+		message(state, tr!("nothing_selected"));
+		return;
+	}
+	let text = match kind {
+		ops::Kind::Copy => tr!("clipboard_copy", paths.len()),
+		ops::Kind::Move => tr!("clipboard_cut", paths.len()),
+	};
+	state.file_clipboard = Some((kind, paths));
+	message(state, text);
+}
+
+/// Starts copying or moving the items of [State::file_clipboard] into the current directory,
+/// falling back to [paste_image] when there are none. Cut items can only be pasted once.
+fn paste(state: &mut State) {
+	if state.operation.is_some() {
+		message(state, tr!("operation_running"));
+		return;
+	}
+	let Some((kind, paths)) = state.file_clipboard.clone() else {
+		paste_image(state);
+		return;
+	};
+	if kind == ops::Kind::Move {
+		state.file_clipboard = None;
+	}
+	let destination = state.history.current().to_path_buf();
+	info!("{kind:?} of {paths:?} into {}", destination.display());
+	state.operation = Some(Operation::start(kind, paths, destination));
+}
+
+fn can_transfer_to_other_pane(state: &State) -> bool {
+	state.other_pane.is_some() && state.operation.is_none() && !state.selected_paths().is_empty()
+}
+
+fn copy_to_other_pane(state: &mut State) {
+	transfer_to_other_pane(state, ops::Kind::Copy);
+}
+
+fn move_to_other_pane(state: &mut State) {
+	transfer_to_other_pane(state, ops::Kind::Move);
+}
+
+/// Starts copying or moving the selected items into the directory of the inactive pane.
+fn transfer_to_other_pane(state: &mut State, kind: ops::Kind) {
+	let Some(other) = &state.other_pane else {
+		return;
+	};
+	let destination = other.history.current().to_path_buf();
+	let paths = state.selected_paths();
+	info!("{kind:?} of {paths:?} into {}", destination.display());
+	state.operation = Some(Operation::start(kind, paths, destination));
+}
+
+fn cancel_operation(state: &mut State) {
+	if let Some(operation) = &state.operation {
+		operation.cancel();
+	}
+}
+
+/// Saves the image on the clipboard as a new PNG file in the current directory and selects it.
+fn paste_image(state: &mut State) {
+	let texture = match clipboard::image() {
+		Ok(Some(texture)) => texture,
+		Ok(None) => {
+			message(state, tr!("nothing_to_paste"));
+			return;
+		}
+		Err(err) => {
+			error!("{err}");
+			state
+				.toasts
+				.push(Toast::error(tr!("paste_failed", err.to_string())));
+			return;
+		}
+	};
+	let png = Png::encode(texture.width, texture.height, &texture.bitmap);
+	match save_pasted_image(state.history.current().to_path_buf(), &png) {
+		Ok(path) => {
+			info!("Pasted image to {}", path.display());
+			state
+				.toasts
+				.push(Toast::info(tr!("image_pasted", file_name(&path))));
+			state.thumbnails = None;
+			state.list_view.reload();
+			state.list_view.select_path(path);
+		}
+		Err(err) => {
+			error!("Failed to save pasted image: {err}");
+			state
+				.toasts
+				.push(Toast::error(tr!("paste_failed", err.to_string())));
+		}
+	}
+}
+
+/// Writes `png` to a new file in `dir`, named after the current time.
+fn save_pasted_image(mut path: PathBuf, png: &[u8]) -> error::Result<PathBuf> {
+	let time = state_dump::utc_timestamp(SystemTime::now());
+	path.push(filename::sanitize(&tr!("pasted_image_name", &time)));
+	path.set_extension("png");
+	for n in 2_u32.. {
+		let file = OpenOptions::new().write(true).create_new(true).open(&path);
+		match file {
+			Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+				let name = tr!("pasted_image_name_numbered", &time, n);
+				path.set_file_name(filename::sanitize(&name));
+				path.set_extension("png");
+			}
+			Err(err) => return Err(err).with_path(&path),
+			Ok(mut file) => return file.write_all(png).with_path(&path).map(|_| path),
+		}
+	}
+	unreachable!()
+}
+
+fn sort_by(state: &mut State, key: SortKey) {
+	let order = state.list_view.sort_order().toggled(key);
+	state.list_view.set_sort_order(order);
+	let column = match key {
+		SortKey::Name => tr!("column_name"),
+		SortKey::Size => tr!("column_size"),
+		SortKey::Modified => tr!("column_modified"),
+		SortKey::Type => tr!("column_type"),
+	};
+	let text = match order.descending {
+		true => tr!("sorted_descending", column),
+		false => tr!("sorted_ascending", column),
+	};
+	message(state, text);
+}
+
+fn toggle_folders_first(state: &mut State) {
+	let mut order = state.list_view.sort_order();
+	order.folders_first = !order.folders_first;
+	state.list_view.set_sort_order(order);
+	let text = match order.folders_first {
+		true => tr!("folders_first_on"),
+		false => tr!("folders_first_off"),
+	};
+	message(state, text);
+}
+
+fn toggle_view_mode(state: &mut State) {
+	state.view_mode = match state.view_mode {
+		ViewMode::List => ViewMode::Grid,
+		ViewMode::Grid => ViewMode::List,
+	};
+	let text = tr!("view_mode", format!("{:?}", state.view_mode));
+	message(state, text);
+}
+
+/// Both panes start out in the current directory, the active one on the left.
+fn toggle_dual_pane(state: &mut State) {
+	state.other_pane = match state.other_pane {
+		Some(_) => None,
+		None => Some(Pane::new(
+			state.history.current().to_path_buf(),
+			state.config.show_hidden,
+		)),
+	};
+	state.active_pane = Side::Left;
+	let text = match state.other_pane {
+		Some(_) => tr!("dual_pane_on"),
+		None => tr!("dual_pane_off"),
+	};
+	message(state, text);
+}
+
+fn toggle_animations(state: &mut State) {
+	animation::set_enabled(!animation::enabled());
+	let text = match animation::enabled() {
+		true => tr!("animations_on"),
+		false => tr!("animations_off"),
+	};
+	message(state, text);
+}
+
+fn message(state: &mut State, text: String) {
+	state.status_bar.handle(StatusEvent::OperationMessage(text));
+}
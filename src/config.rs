@@ -0,0 +1,392 @@
+//! User settings, kept in `config.toml` in the user's application data directory. A missing file
+//! is created with the defaults. Changes to the file are picked up while the explorer runs.
+//!
+//! ```toml
+//! theme = "dark"
+//!
+//! [view]
+//! # "list" or "grid", the view the explorer starts in
+//! mode = "list"
+//! animations = true
+//! # Whether hidden files and files starting with a dot are listed
+//! show_hidden = false
+//! # Edge of the grid view's thumbnails in pixels, from 32 to 512
+//! thumbnail_size = 128
+//!
+//! [confirmations]
+//! delete = true
+//! overwrite = true
+//!
+//! [search]
+//! # Whether searched folders are indexed, so that searching them again is instant
+//! index = false
+//!
+//! [terminal]
+//! # Run by "Open terminal here", {dir} is replaced by the current directory
+//! command = "wt.exe -d {dir}"
+//!
+//! [keybindings]
+//! # Replace the built-in shortcuts of a command, see the keymap module
+//! "navigate.up" = ["Alt+Up", "Backspace"]
+//! "listing.refresh" = "none"
+//! ```
+
+use std::{fmt::Display, io, path::PathBuf};
+
+use log::{debug, error, warn};
+
+use crate::app::ViewMode;
+use crate::toml::{self, Document, Table, Value};
+
+const FILE_NAME: &str = "config.toml";
+/// Smallest and largest thumbnail size.
+pub const THUMBNAIL_SIZES: std::ops::RangeInclusive<usize> = 32..=512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+	pub theme: Theme,
+	/// View the explorer starts in.
+	pub view_mode: ViewMode,
+	pub animations: bool,
+	pub show_hidden: bool,
+	/// Edge of the grid view's thumbnails in pixels, within [THUMBNAIL_SIZES].
+	pub thumbnail_size: usize,
+	pub confirm_delete: bool,
+	pub confirm_overwrite: bool,
+	/// Whether searched folders are indexed, see [crate::search_index].
+	pub search_index: bool,
+	/// Command line of "Open terminal here", `{dir}` stands for the current directory.
+	pub terminal_command: String,
+	/// Shortcuts replacing the built-in ones of commands, by command id. No shortcuts unbind the
+	/// command.
+	pub keybindings: Vec<(String, Vec<String>)>,
+}
+
+/// Only the dark gruvbox theme exists so far.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Theme {
+	Dark,
+}
+
+impl Theme {
+	pub const ALL: &'static [Theme] = &[Theme::Dark];
+
+	pub fn parse(name: &str) -> Option<Theme> {
+		match name {
+			"dark" => Some(Theme::Dark),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Theme::Dark => "dark",
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: io::Error,
+		filename: PathBuf,
+	},
+	Syntax(toml::Error),
+	/// A setting with a value of the wrong type or out of range, the default is used instead.
+	InvalidValue {
+		key: String,
+		message: String,
+	},
+	UnknownKey {
+		key: String,
+	},
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::Syntax(err) => write!(f, "{err}"),
+			Error::InvalidValue { key, message } => write!(f, "{key}: {message}."),
+			Error::UnknownKey { key } => write!(f, "Unknown setting {key}."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			theme: Theme::Dark,
+			view_mode: ViewMode::List,
+			animations: true,
+			show_hidden: false,
+			thumbnail_size: 128,
+			confirm_delete: true,
+			confirm_overwrite: true,
+			search_index: false,
+			terminal_command: "cmd.exe".to_string(),
+			keybindings: Vec::new(),
+		}
+	}
+}
+
+impl Config {
+	/// Where the configuration file lives, if the application data directory is known.
+	pub fn path() -> Option<PathBuf> {
+		let app_data = std::env::var_os("APPDATA")?;
+		Some(
+			PathBuf::from(app_data)
+				.join("file-explorer")
+				.join(FILE_NAME),
+		)
+	}
+
+	/// The settings of the configuration file, creating it if it's missing. Invalid settings keep
+	/// their defaults and are reported, the rest still applies.
+	pub fn load() -> (Config, Vec<Error>) {
+		let Some(path) = Config::path() else {
+			return (Config::default(), Vec::new());
+		};
+		match std::fs::read_to_string(&path) {
+			Ok(text) => {
+				debug!("Loading config from {}", path.display());
+				Config::parse(&text)
+			}
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				let config = Config::default();
+				if let Err(err) = config.save() {
+					error!("Failed to create {}: {err}", path.display());
+				}
+				(config, Vec::new())
+			}
+			Err(err) => (
+				Config::default(),
+				vec![Error::Io {
+					err,
+					filename: path,
+				}],
+			),
+		}
+	}
+
+	pub fn parse(text: &str) -> (Config, Vec<Error>) {
+		let mut config = Config::default();
+		let document = match toml::parse(text) {
+			Ok(document) => document,
+			Err(err) => return (config, vec![Error::Syntax(err)]),
+		};
+
+		let mut errors = Vec::new();
+		let mut settings = Settings {
+			errors: &mut errors,
+		};
+		let tables = std::iter::once(("", &document.root)).chain(
+			document
+				.tables
+				.iter()
+				.map(|(name, table)| (name.as_str(), table)),
+		);
+		for (table_name, table) in tables {
+			for (key, value) in table {
+				let full_key = match table_name {
+					"" => key.clone(),
+					_ => format!("{table_name}.{key}"),
+				};
+				match full_key.as_str() {
+					"theme" => {
+						if let Some(theme) = settings.string(&full_key, value) {
+							match Theme::parse(theme) {
+								Some(theme) => config.theme = theme,
+								None => settings.invalid(&full_key, "the only theme is \"dark\""),
+							}
+						}
+					}
+					"view.mode" => {
+						if let Some(mode) = settings.string(&full_key, value) {
+							match mode {
+								"list" => config.view_mode = ViewMode::List,
+								"grid" => config.view_mode = ViewMode::Grid,
+								_ => settings.invalid(&full_key, "expected \"list\" or \"grid\""),
+							}
+						}
+					}
+					"view.animations" => settings.boolean(&full_key, value, &mut config.animations),
+					"view.show_hidden" => {
+						settings.boolean(&full_key, value, &mut config.show_hidden)
+					}
+					"view.thumbnail_size" => match value {
+						Value::Integer(size) if THUMBNAIL_SIZES.contains(&(*size as usize)) => {
+							config.thumbnail_size = *size as usize
+						}
+						_ => settings.invalid(&full_key, "expected a number from 32 to 512"),
+					},
+					"confirmations.delete" => {
+						settings.boolean(&full_key, value, &mut config.confirm_delete)
+					}
+					"confirmations.overwrite" => {
+						settings.boolean(&full_key, value, &mut config.confirm_overwrite)
+					}
+					"search.index" => settings.boolean(&full_key, value, &mut config.search_index),
+					"terminal.command" => {
+						if let Some(command) = settings.string(&full_key, value) {
+							config.terminal_command = command.to_string();
+						}
+					}
+					_ if table_name == "keybindings" => {
+						if let Some(shortcuts) = settings.shortcuts(&full_key, value) {
+							config.keybindings.push((key.clone(), shortcuts));
+						}
+					}
+					_ => settings.errors.push(Error::UnknownKey { key: full_key }),
+				}
+			}
+		}
+		for err in &errors {
+			warn!("Config: {err}");
+		}
+		(config, errors)
+	}
+
+	/// Writes the settings to the configuration file. Comments in the file are lost.
+	pub fn save(&self) -> io::Result<()> {
+		let path = Config::path()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?;
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(&path, toml::to_string(&self.to_document()))
+	}
+
+	/// Replaces the shortcuts of `command_id`, no shortcuts unbind it.
+	pub fn set_keybindings(&mut self, command_id: &str, shortcuts: Vec<String>) {
+		match self
+			.keybindings
+			.iter_mut()
+			.find(|(bound, _)| bound == command_id)
+		{
+			Some((_, existing)) => *existing = shortcuts,
+			None => self.keybindings.push((command_id.to_string(), shortcuts)),
+		}
+	}
+
+	/// Gives `command_id` its built-in shortcuts back.
+	pub fn reset_keybindings(&mut self, command_id: &str) {
+		self.keybindings.retain(|(bound, _)| bound != command_id);
+	}
+
+	fn to_document(&self) -> Document {
+		let string = |s: &str| Value::String(s.to_string());
+		let view_mode = match self.view_mode {
+			ViewMode::List => "list",
+			ViewMode::Grid => "grid",
+		};
+		let keybindings: Table = self
+			.keybindings
+			.iter()
+			.map(|(command_id, shortcuts)| {
+				let shortcuts = match shortcuts.as_slice() {
+					[] => string("none"),
+					[shortcut] => string(shortcut),
+					shortcuts => Value::Array(shortcuts.iter().map(|s| string(s)).collect()),
+				};
+				(command_id.clone(), shortcuts)
+			})
+			.collect();
+		Document {
+			root: vec![("theme".to_string(), string(self.theme.name()))],
+			tables: vec![
+				(
+					"view".to_string(),
+					vec![
+						("mode".to_string(), string(view_mode)),
+						("animations".to_string(), Value::Boolean(self.animations)),
+						("show_hidden".to_string(), Value::Boolean(self.show_hidden)),
+						(
+							"thumbnail_size".to_string(),
+							Value::Integer(self.thumbnail_size as i64),
+						),
+					],
+				),
+				(
+					"confirmations".to_string(),
+					vec![
+						("delete".to_string(), Value::Boolean(self.confirm_delete)),
+						(
+							"overwrite".to_string(),
+							Value::Boolean(self.confirm_overwrite),
+						),
+					],
+				),
+				(
+					"search".to_string(),
+					vec![("index".to_string(), Value::Boolean(self.search_index))],
+				),
+				(
+					"terminal".to_string(),
+					vec![("command".to_string(), string(&self.terminal_command))],
+				),
+				("keybindings".to_string(), keybindings),
+			],
+		}
+	}
+}
+
+/// Reads values of settings, reporting the ones of the wrong type.
+struct Settings<'a> {
+	errors: &'a mut Vec<Error>,
+}
+
+impl Settings<'_> {
+	fn invalid(&mut self, key: &str, message: &str) {
+		self.errors.push(Error::InvalidValue {
+			key: key.to_string(),
+			message: message.to_string(),
+		});
+	}
+
+	fn string<'v>(&mut self, key: &str, value: &'v Value) -> Option<&'v str> {
+		match value {
+			Value::String(s) => Some(s),
+			other => {
+				self.invalid(
+					key,
+					&format!("expected a string, not {}", other.type_name()),
+				);
+				None
+			}
+		}
+	}
+
+	fn boolean(&mut self, key: &str, value: &Value, setting: &mut bool) {
+		match value {
+			Value::Boolean(b) => *setting = *b,
+			other => self.invalid(
+				key,
+				&format!("expected true or false, not {}", other.type_name()),
+			),
+		}
+	}
+
+	/// A shortcut, an array of them or "none".
+	fn shortcuts(&mut self, key: &str, value: &Value) -> Option<Vec<String>> {
+		match value {
+			Value::String(s) if s.eq_ignore_ascii_case("none") => Some(Vec::new()),
+			Value::String(s) => Some(vec![s.clone()]),
+			Value::Array(values) => values
+				.iter()
+				.map(|value| self.string(key, value).map(str::to_string))
+				.collect(),
+			other => {
+				self.invalid(
+					key,
+					&format!("expected shortcuts, not {}", other.type_name()),
+				);
+				None
+			}
+		}
+	}
+}
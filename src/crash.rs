@@ -0,0 +1,174 @@
+//! Crash reports. Panics of the main thread and fatal exceptions (e.g. access violations in
+//! plugins) write a report with the backtrace, the last log lines and a summary of the app state
+//! to the temp directory, and point the user to it in a message box before the process exits.
+//!
+//! Panics of other threads are handled by whoever runs them, e.g. the [jobs](crate::jobs) log them.
+
+use std::backtrace::Backtrace;
+use std::ffi::c_void;
+use std::fmt::Write;
+use std::panic::PanicHookInfo;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Diagnostics::Debug::{SetUnhandledExceptionFilter, EXCEPTION_POINTERS};
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK};
+
+use crate::app::{State, ViewMode};
+use crate::logger;
+use crate::screen::Screen;
+use crate::state_dump::utc_timestamp;
+use crate::string::WideString;
+
+const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+/// Summary of the state, kept up to date by [update_state] since the state itself can't be
+/// looked at while it may be in the middle of changing.
+static STATE_SUMMARY: Mutex<String> = Mutex::new(String::new());
+/// Whether to show a message box, instead of just writing the report.
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+/// Only the first crash is reported, e.g. an exception raised while reporting a panic isn't.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the panic hook and the exception filter. Without `interactive` the report is only
+/// written, e.g. when rendering screenshots in scripts.
+pub fn install(interactive: bool) {
+	INTERACTIVE.store(interactive, Ordering::Relaxed);
+	let default_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+		default_hook(info);
+		if std::thread::current().name() == Some("main") {
+			report(&panic_description(info));
+		}
+	}));
+	// Only exceptions nothing else handles, unlike a vectored handler, which also sees those a
+	// shell extension catches itself
+	unsafe { SetUnhandledExceptionFilter(Some(exception_filter)) };
+}
+
+/// Refreshes the state summary of crash reports.
+pub fn update_state(state: &State) {
+	let mut summary = String::new();
+	let _ = writeln!(summary, "Directory: {}", state.history.current().display());
+	let view_mode = match state.view_mode {
+		ViewMode::List => "list",
+		ViewMode::Grid => "grid",
+	};
+	let _ = writeln!(summary, "View mode: {view_mode}");
+	let screens: Vec<&str> = state.screens.iter().map(Screen::name).collect();
+	let _ = writeln!(summary, "Screens: {}", screens.join(" > "));
+	if state.view_mode == ViewMode::List {
+		let _ = writeln!(summary, "Items: {}", state.list_view.entries().len());
+	}
+	if let Some(thumbnails) = &state.thumbnails {
+		let _ = writeln!(
+			summary,
+			"Items: {}, {} selected, {} thumbnails loading",
+			thumbnails.len(),
+			state.grid.selection().len(),
+			thumbnails.pending_count()
+		);
+	}
+	let plugins: Vec<&str> = state.plugins.names().collect();
+	let _ = writeln!(summary, "Plugins: {}", plugins.join(", "));
+	let _ = writeln!(summary, "Theme: {}", state.config.theme.name());
+	if let Ok(mut state_summary) = STATE_SUMMARY.try_lock() {
+		*state_summary = summary;
+	}
+}
+
+fn panic_description(info: &PanicHookInfo) -> String {
+	let thread = std::thread::current();
+	format!("Thread '{}' {info}", thread.name().unwrap_or("<unnamed>"))
+}
+
+unsafe extern "system" fn exception_filter(pointers: *const EXCEPTION_POINTERS) -> i32 {
+	let Some(record) = pointers
+		.as_ref()
+		.and_then(|pointers| pointers.ExceptionRecord.as_ref())
+	else {
+		return EXCEPTION_CONTINUE_SEARCH;
+	};
+	// Other exceptions are either handled (e.g. panics unwinding) or, like stack overflows, leave
+	// too little stack to write a report
+	let name = match record.ExceptionCode.0 as u32 {
+		0xC000_0005 => "Access violation",
+		0xC000_001D => "Illegal instruction",
+		0xC000_0096 => "Privileged instruction",
+		0xC000_0094 => "Integer division by zero",
+		0xC000_0006 => "In-page error",
+		_ => return EXCEPTION_CONTINUE_SEARCH,
+	};
+	let mut description = format!(
+		"{name} (0x{:08X}) at {:p}",
+		record.ExceptionCode.0, record.ExceptionAddress
+	);
+	if record.ExceptionCode.0 as u32 == 0xC000_0005 && record.NumberParameters >= 2 {
+		let operation = match record.ExceptionInformation[0] {
+			0 => "reading",
+			1 => "writing",
+			_ => "executing",
+		};
+		let address = record.ExceptionInformation[1] as *const c_void;
+		let _ = write!(description, " {operation} {address:p}");
+	}
+	report(&description);
+	// Lets Windows terminate the process
+	EXCEPTION_CONTINUE_SEARCH
+}
+
+/// Writes the report and shows the message box, only for the first crash.
+fn report(description: &str) {
+	if REPORTED.swap(true, Ordering::Relaxed) {
+		return;
+	}
+	let now = SystemTime::now();
+	let report = format!(
+		"file-explorer {} crashed at {}\n\n{description}\n\nBacktrace:\n{}\n\nState:\n{}\n\
+		Last log lines:\n{}\n",
+		env!("CARGO_PKG_VERSION"),
+		utc_timestamp(now),
+		Backtrace::force_capture(),
+		STATE_SUMMARY
+			.try_lock()
+			.ok()
+			.filter(|summary| !summary.is_empty())
+			.map_or_else(|| "Unknown\n".to_string(), |summary| summary.clone()),
+		logger::tail().join("\n"),
+	);
+	let path = std::env::temp_dir().join(format!("file-explorer-crash-{}.txt", utc_timestamp(now)));
+	let message = match std::fs::write(&path, report) {
+		Ok(()) => {
+			eprintln!("Crash report saved to {}", path.display());
+			format!(
+				"The file explorer crashed: {description}\n\nA report was saved to {}",
+				path.display()
+			)
+		}
+		Err(err) => {
+			eprintln!(
+				"Failed to save the crash report to {}: {err}",
+				path.display()
+			);
+			format!("The file explorer crashed: {description}")
+		}
+	};
+	if INTERACTIVE.load(Ordering::Relaxed) {
+		show_message_box(&message);
+	}
+}
+
+fn show_message_box(message: &str) {
+	let text = WideString::new(message);
+	let caption = WideString::new("File explorer crashed");
+	unsafe {
+		MessageBoxW(
+			HWND::default(),
+			text.as_pcwstr(),
+			caption.as_pcwstr(),
+			MB_OK | MB_ICONERROR,
+		)
+	};
+}
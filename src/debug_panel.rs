@@ -0,0 +1,130 @@
+//! Panel with debugging toggles and actions along the right edge of the window. Built with the
+//! immediate-mode [Ui], so there's no widget state besides [UiMemory].
+
+use crate::animation;
+use crate::app::State;
+use crate::arena::arena_format;
+use crate::command;
+use crate::draw::{DrawList, Rect};
+use crate::profiler::{self, Stage};
+use crate::ui::{Ui, UiMemory};
+use crate::window::{BitmapData, Mouse};
+
+const WIDTH: usize = 260;
+const COMMAND_ROWS: usize = 6;
+
+#[derive(Debug, Default)]
+pub struct DebugPanel {
+	open: bool,
+	memory: UiMemory,
+	draw_list: DrawList,
+	/// Index into [crate::command::CommandRegistry::commands].
+	selected_command: Option<usize>,
+}
+
+impl DebugPanel {
+	pub fn toggle(&mut self) {
+		self.open = !self.open;
+		self.draw_list.clear();
+	}
+
+	pub fn render(&self, bitmap_data: BitmapData) {
+		if self.open {
+			self.draw_list.execute(bitmap_data);
+		}
+	}
+}
+
+/// Declares the panel for this frame. Clicks on the panel are consumed, so that the view below it
+/// doesn't react to them.
+pub fn update(state: &mut State, mouse: &mut Mouse, viewport: Rect) {
+	if !state.debug_panel.open {
+		return;
+	}
+	let width = WIDTH.min(viewport.width);
+	let area = Rect::new(
+		viewport.x + viewport.width - width,
+		viewport.y,
+		width,
+		viewport.height,
+	);
+
+	let mut command_to_run = None;
+	let mut animations = animation::enabled();
+	let panel = &mut state.debug_panel;
+	let mut ui = Ui::begin(&mut panel.draw_list, &mut panel.memory, mouse, area);
+
+	ui.heading("Directory");
+	ui.label(arena_format!(
+		state.arena,
+		"{}",
+		state.history.current().display()
+	));
+	ui.separator();
+
+	ui.heading("Rendering");
+	#[cfg(feature = "demo")]
+	{
+		ui.checkbox("Dithering", &mut state.demo.dithering);
+		ui.checkbox("Background scrolling", &mut state.demo.background.scrolling);
+	}
+	ui.checkbox("Animations", &mut animations);
+	ui.separator();
+
+	ui.heading("Frame time");
+	for stage in Stage::ALL {
+		let ms = profiler::average(stage).as_secs_f32() * 1000.0;
+		ui.label(arena_format!(state.arena, "{:<8} {ms:.2} ms", stage.name()));
+	}
+	let trace_label = match profiler::is_tracing() {
+		true => "Stop trace",
+		false => "Start trace",
+	};
+	if ui.button(trace_label) {
+		command_to_run = Some("debug.toggle_trace");
+	}
+	ui.separator();
+
+	#[cfg(feature = "demo")]
+	{
+		ui.heading("Demo");
+		if ui.button("Reset player") {
+			command_to_run = Some("player.reset");
+		}
+		if ui.button("Reset logo") {
+			command_to_run = Some("logo.reset");
+		}
+		ui.separator();
+	}
+
+	ui.heading("Commands");
+	let titles = state.arena.alloc_iter(
+		state
+			.commands
+			.commands()
+			.iter()
+			.map(|command| command.title),
+	);
+	ui.list(
+		"commands",
+		titles,
+		&mut panel.selected_command,
+		COMMAND_ROWS,
+	);
+	if ui.button("Run") {
+		command_to_run = panel
+			.selected_command
+			.map(|idx| state.commands.commands()[idx].id);
+	}
+
+	let hovered = ui.is_hovered();
+	if hovered {
+		mouse.consume_press();
+	}
+	if animations != animation::enabled() {
+		animation::set_enabled(animations);
+	}
+	if let Some(command_id) = command_to_run {
+		command::execute(state, command_id);
+	}
+}
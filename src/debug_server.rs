@@ -0,0 +1,158 @@
+//! Debug server for driving the explorer from integration tests and external tools, built with
+//! the `debug_server` feature. It listens on localhost, on the port in FILE_EXPLORER_DEBUG_PORT or
+//! [DEFAULT_PORT], and speaks a line based protocol:
+//!
+//! - `state` replies with the state as a single line of JSON, like the state dump of F3
+//! - `run <command id>` runs the command and replies `ok`, or `error: ...` if there's no such
+//!   command
+//! - `logs` streams every line logged from then on, until the connection is closed
+//!
+//! Requests are answered on the main thread by [DebugServer::poll], once per frame.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use log::{debug, error, info, warn};
+
+use crate::app::State;
+use crate::{command, logger, state_dump};
+
+pub const DEFAULT_PORT: u16 = 47_600;
+
+/// How often the listener checks whether it should stop.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+enum Request {
+	State,
+	Run(String),
+}
+
+#[derive(Debug)]
+pub struct DebugServer {
+	requests: Receiver<(Request, Sender<String>)>,
+	stop: Arc<AtomicBool>,
+	listener: Option<JoinHandle<()>>,
+}
+
+impl DebugServer {
+	/// Starts listening, on the port in FILE_EXPLORER_DEBUG_PORT if it's set.
+	pub fn start() -> io::Result<DebugServer> {
+		let port = match std::env::var("FILE_EXPLORER_DEBUG_PORT") {
+			Ok(port) => port.parse().map_err(|_| {
+				io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!("invalid FILE_EXPLORER_DEBUG_PORT {port:?}"),
+				)
+			})?,
+			Err(_) => DEFAULT_PORT,
+		};
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+		listener.set_nonblocking(true)?;
+		info!("Debug server listening on {}", listener.local_addr()?);
+
+		let (sender, requests) = mpsc::channel();
+		let stop = Arc::new(AtomicBool::new(false));
+		let listener = {
+			let stop = Arc::clone(&stop);
+			std::thread::Builder::new()
+				.name("debug server".to_string())
+				.spawn(move || accept(listener, sender, &stop))?
+		};
+		Ok(DebugServer {
+			requests,
+			stop,
+			listener: Some(listener),
+		})
+	}
+
+	/// Answers the requests received since the last call.
+	pub fn poll(&self, state: &mut State) {
+		for (request, reply) in self.requests.try_iter() {
+			let response = match request {
+				Request::State => state_dump::to_json(state, SystemTime::now()).to_string(),
+				Request::Run(id) => match command::execute(state, &id) {
+					true => "ok".to_string(),
+					false => format!("error: unknown command {id}"),
+				},
+			};
+			// The client may be gone already
+			let _ = reply.send(response);
+		}
+	}
+}
+
+impl Drop for DebugServer {
+	/// Stops listening. Open connections are closed when their client sends the next request.
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		if let Some(listener) = self.listener.take() {
+			if listener.join().is_err() {
+				error!("The debug server panicked");
+			}
+		}
+	}
+}
+
+fn accept(listener: TcpListener, requests: Sender<(Request, Sender<String>)>, stop: &AtomicBool) {
+	while !stop.load(Ordering::Relaxed) {
+		match listener.accept() {
+			Ok((stream, addr)) => {
+				debug!("Debug client {addr} connected");
+				let requests = requests.clone();
+				let connection = std::thread::Builder::new()
+					.name(format!("debug client {addr}"))
+					.spawn(move || {
+						if let Err(err) = serve(stream, &requests) {
+							debug!("Debug client {addr} disconnected: {err}");
+						}
+					});
+				if let Err(err) = connection {
+					warn!("Failed to serve debug client {addr}: {err}");
+				}
+			}
+			Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+				std::thread::sleep(ACCEPT_INTERVAL)
+			}
+			Err(err) => {
+				error!("Debug server stopped: {err}");
+				return;
+			}
+		}
+	}
+}
+
+fn serve(stream: TcpStream, requests: &Sender<(Request, Sender<String>)>) -> io::Result<()> {
+	// Inherited from the listener
+	stream.set_nonblocking(false)?;
+	let mut writer = stream.try_clone()?;
+	for line in BufReader::new(stream).lines() {
+		let line = line?;
+		let request = match line.trim().split_once(' ') {
+			None if line.trim() == "state" => Request::State,
+			None if line.trim() == "logs" => return stream_logs(&mut writer),
+			Some(("run", id)) => Request::Run(id.trim().to_string()),
+			_ => {
+				writeln!(writer, "error: unknown request {:?}", line.trim())?;
+				continue;
+			}
+		};
+		let (reply, response) = mpsc::channel();
+		let exiting = || io::Error::new(io::ErrorKind::BrokenPipe, "the explorer is exiting");
+		requests.send((request, reply)).map_err(|_| exiting())?;
+		writeln!(writer, "{}", response.recv().map_err(|_| exiting())?)?;
+	}
+	Ok(())
+}
+
+fn stream_logs(writer: &mut TcpStream) -> io::Result<()> {
+	for line in logger::subscribe() {
+		writeln!(writer, "{line}")?;
+	}
+	Ok(())
+}
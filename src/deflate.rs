@@ -0,0 +1,918 @@
+//! DEFLATE (RFC 1951) compression and decompression, raw or wrapped in the zlib format
+//! (RFC 1950) as used by PNG. Decompression can be fed data in pieces with [Inflater].
+
+use std::fmt::Display;
+
+/// Back references reach at most this far back.
+const WINDOW_SIZE: usize = 32 * 1024;
+
+const BLOCK_STORED: u32 = 0;
+const BLOCK_FIXED: u32 = 1;
+const BLOCK_DYNAMIC: u32 = 2;
+
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+/// Order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const MAX_CODE_LENGTH: usize = 15;
+/// Codes up to this long are decoded with a single table lookup.
+const FAST_BITS: u32 = 9;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The data ended before the end of the stream.
+	UnexpectedEnd,
+	BadZlibHeader,
+	InvalidBlockType,
+	StoredLengthMismatch,
+	InvalidCodeLengths,
+	InvalidCode,
+	InvalidDistance,
+	ChecksumFailed,
+	/// The data decompresses to more than the limit, see [Inflater::with_limit].
+	TooMuchOutput,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::UnexpectedEnd => write!(f, "Compressed data ended abruptly."),
+			Error::BadZlibHeader => write!(f, "Invalid zlib header."),
+			Error::InvalidBlockType => write!(f, "Invalid block type."),
+			Error::StoredLengthMismatch => write!(f, "Stored block length doesn't match."),
+			Error::InvalidCodeLengths => write!(f, "Invalid Huffman code lengths."),
+			Error::InvalidCode => write!(f, "Invalid Huffman code."),
+			Error::InvalidDistance => write!(f, "Back reference too far back."),
+			Error::ChecksumFailed => write!(f, "Checksum doesn't match."),
+			Error::TooMuchOutput => write!(f, "Decompressed data is bigger than expected."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Decompresses raw DEFLATE data.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+	let mut inflater = Inflater::raw();
+	inflater.push(data)?;
+	inflater.finish()
+}
+
+/// Decompresses zlib wrapped DEFLATE data.
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+	let mut inflater = Inflater::zlib();
+	inflater.push(data)?;
+	inflater.finish()
+}
+
+/// Canonical Huffman code, see RFC 1951 3.2.2.
+struct Huffman {
+	/// Indexed by the next [FAST_BITS] bits of input: `symbol << 4 | code length`, 0 for codes
+	/// longer than that.
+	fast: Vec<u16>,
+	/// Number of codes of each length.
+	counts: [u16; MAX_CODE_LENGTH + 1],
+	/// Symbols ordered by code.
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	/// Builds the code from the code length of each symbol, 0 meaning the symbol is unused.
+	fn new(lengths: &[u8]) -> Result<Self, Error> {
+		let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+		for &len in lengths {
+			counts[len as usize] += 1;
+		}
+		counts[0] = 0;
+
+		// More codes of a length than there are bit patterns left makes the code ambiguous.
+		// Incomplete codes are fine, e.g. distance codes with a single symbol.
+		let mut left: i32 = 1;
+		for &count in &counts[1..] {
+			left = (left << 1) - count as i32;
+			if left < 0 {
+				return Err(Error::InvalidCodeLengths);
+			}
+		}
+
+		let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+		for len in 1..=MAX_CODE_LENGTH {
+			offsets[len + 1] = offsets[len] + counts[len];
+		}
+		let mut symbols = vec![0; offsets[MAX_CODE_LENGTH + 1] as usize];
+		for (symbol, &len) in lengths.iter().enumerate() {
+			if len != 0 {
+				symbols[offsets[len as usize] as usize] = symbol as u16;
+				offsets[len as usize] += 1;
+			}
+		}
+
+		let mut fast = vec![0; 1 << FAST_BITS];
+		let mut code = 0u32;
+		let mut idx = 0;
+		for len in 1..=FAST_BITS {
+			for _ in 0..counts[len as usize] {
+				// Codes are stored most significant bit first, input is read least significant
+				// bit first
+				let reversed = code.reverse_bits() >> (32 - len);
+				let entry = symbols[idx] << 4 | len as u16;
+				for fill in (reversed as usize..fast.len()).step_by(1 << len) {
+					fast[fill] = entry;
+				}
+				code += 1;
+				idx += 1;
+			}
+			code <<= 1;
+		}
+
+		Ok(Huffman {
+			fast,
+			counts,
+			symbols,
+		})
+	}
+
+	fn decode(&self, bits: &mut BitReader) -> Result<u16, Error> {
+		let available = bits.available();
+		let peeked = bits.peek(MAX_CODE_LENGTH as u32);
+		let entry = self.fast[peeked as usize & ((1 << FAST_BITS) - 1)];
+		if entry != 0 {
+			let len = (entry & 0x0F) as usize;
+			if len > available {
+				return Err(Error::UnexpectedEnd);
+			}
+			bits.consume(len);
+			return Ok(entry >> 4);
+		}
+
+		// Longer codes, one bit at a time
+		let (mut code, mut first, mut idx) = (0i32, 0i32, 0i32);
+		for len in 1..=MAX_CODE_LENGTH {
+			if len > available {
+				return Err(Error::UnexpectedEnd);
+			}
+			code |= ((peeked >> (len - 1)) & 1) as i32;
+			let count = self.counts[len] as i32;
+			if code - first < count {
+				bits.consume(len);
+				return Ok(self.symbols[(idx + code - first) as usize]);
+			}
+			idx += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(Error::InvalidCode)
+	}
+}
+
+/// Least significant bit first reader over the buffered input.
+struct BitReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl BitReader<'_> {
+	fn available(&self) -> usize {
+		self.data.len() * 8 - self.pos
+	}
+
+	/// Next `count` (at most 32) bits, padded with zeros past the end of the data.
+	fn peek(&self, count: u32) -> u32 {
+		let byte = self.pos / 8;
+		let mut buf = [0u8; 8];
+		let end = (byte + 8).min(self.data.len());
+		if byte < end {
+			buf[..end - byte].copy_from_slice(&self.data[byte..end]);
+		}
+		let value = u64::from_le_bytes(buf) >> (self.pos % 8);
+		(value & ((1u64 << count) - 1)) as u32
+	}
+
+	fn consume(&mut self, count: usize) {
+		self.pos += count;
+	}
+
+	fn bits(&mut self, count: u32) -> Result<u32, Error> {
+		if (count as usize) > self.available() {
+			return Err(Error::UnexpectedEnd);
+		}
+		let value = self.peek(count);
+		self.consume(count as usize);
+		Ok(value)
+	}
+
+	fn align_to_byte(&mut self) {
+		self.pos = self.pos.next_multiple_of(8);
+	}
+}
+
+enum State {
+	ZlibHeader,
+	BlockHeader,
+	/// Bytes of a stored block left to copy.
+	Stored(usize),
+	/// Literal/length and distance codes of the current block.
+	Compressed(Box<(Huffman, Huffman)>),
+	ZlibTrailer,
+	Done,
+}
+
+/// Incremental decompressor. Input is fed with [Inflater::push] as it arrives, output can be
+/// taken from [Inflater::output] after every push.
+pub struct Inflater {
+	zlib: bool,
+	state: State,
+	last_block: bool,
+	/// Input that wasn't decoded yet, starting at bit `bit_pos`.
+	input: Vec<u8>,
+	bit_pos: usize,
+	/// The last [WINDOW_SIZE] (or more) bytes of output, for back references.
+	window: Vec<u8>,
+	/// Output not taken by the user yet.
+	output: Vec<u8>,
+	adler: Adler32,
+	expected_checksum: u32,
+	/// Bytes decompressed so far, and how many may be.
+	produced: usize,
+	limit: usize,
+}
+
+impl Inflater {
+	pub fn zlib() -> Self {
+		Inflater::new(true)
+	}
+
+	pub fn raw() -> Self {
+		Inflater::new(false)
+	}
+
+	fn new(zlib: bool) -> Self {
+		Inflater {
+			zlib,
+			state: if zlib {
+				State::ZlibHeader
+			} else {
+				State::BlockHeader
+			},
+			last_block: false,
+			input: Vec::new(),
+			bit_pos: 0,
+			window: Vec::new(),
+			output: Vec::new(),
+			adler: Adler32::new(),
+			expected_checksum: 0,
+			produced: 0,
+			limit: usize::MAX,
+		}
+	}
+
+	/// Fails with [Error::TooMuchOutput] once the data decompresses to more than `limit` bytes,
+	/// e.g. the size the header of an image promises, rather than running out of memory.
+	pub fn with_limit(mut self, limit: usize) -> Self {
+		self.limit = limit;
+		self
+	}
+
+	/// Decompresses as much of the data pushed so far as possible.
+	pub fn push(&mut self, data: &[u8]) -> Result<(), Error> {
+		self.input.extend_from_slice(data);
+		let output_start = self.output.len();
+		let result = self.run();
+		if self.zlib {
+			self.adler.update(&self.output[output_start..]);
+		}
+
+		self.input.drain(..self.bit_pos / 8);
+		self.bit_pos %= 8;
+		if self.window.len() > 2 * WINDOW_SIZE {
+			self.window.drain(..self.window.len() - WINDOW_SIZE);
+		}
+
+		match result {
+			// Waiting for more input
+			Err(Error::UnexpectedEnd) => Ok(()),
+			Err(err) => Err(err),
+			Ok(()) if self.zlib && matches!(self.state, State::Done) => {
+				if self.adler.finish() != self.expected_checksum {
+					return Err(Error::ChecksumFailed);
+				}
+				Ok(())
+			}
+			Ok(()) => Ok(()),
+		}
+	}
+
+	/// Decompressed data, the user is free to take bytes out of it.
+	pub fn output(&mut self) -> &mut Vec<u8> {
+		&mut self.output
+	}
+
+	pub fn is_finished(&self) -> bool {
+		matches!(self.state, State::Done)
+	}
+
+	/// Remaining output, fails if the stream isn't complete.
+	pub fn finish(self) -> Result<Vec<u8>, Error> {
+		if !self.is_finished() {
+			return Err(Error::UnexpectedEnd);
+		}
+		Ok(self.output)
+	}
+
+	/// Runs until the input runs out or the stream ends. Whatever was being decoded when the input
+	/// ran out is rolled back to be retried on the next push.
+	fn run(&mut self) -> Result<(), Error> {
+		let input = std::mem::take(&mut self.input);
+		let mut bits = BitReader {
+			data: &input,
+			pos: self.bit_pos,
+		};
+		let result = self.run_with(&mut bits);
+		self.input = input;
+		result
+	}
+
+	fn run_with(&mut self, bits: &mut BitReader) -> Result<(), Error> {
+		loop {
+			// Every step either completes or leaves `bit_pos` where it started
+			let start = bits.pos;
+			match self.step(bits) {
+				Ok(true) => self.bit_pos = bits.pos,
+				Ok(false) => {
+					self.bit_pos = bits.pos;
+					return Ok(());
+				}
+				Err(err) => {
+					bits.pos = start;
+					self.bit_pos = start;
+					return Err(err);
+				}
+			}
+		}
+	}
+
+	/// Decodes one piece of the stream. Returns false if there's nothing more to do, because the
+	/// stream is done or more input is needed after some progress was made.
+	fn step(&mut self, bits: &mut BitReader) -> Result<bool, Error> {
+		match &mut self.state {
+			State::ZlibHeader => {
+				let (cmf, flg) = (bits.bits(8)?, bits.bits(8)?);
+				// Compression method 8 (deflate), no preset dictionary
+				if cmf & 0x0F != 8 || (cmf << 8 | flg) % 31 != 0 || flg & 0x20 != 0 {
+					return Err(Error::BadZlibHeader);
+				}
+				self.state = State::BlockHeader;
+			}
+			State::BlockHeader => {
+				if self.last_block {
+					self.state = if self.zlib {
+						State::ZlibTrailer
+					} else {
+						State::Done
+					};
+					return Ok(true);
+				}
+				let last = bits.bits(1)? == 1;
+				self.state = match bits.bits(2)? {
+					BLOCK_STORED => {
+						bits.align_to_byte();
+						let len = bits.bits(16)?;
+						let inverted = bits.bits(16)?;
+						if len != !inverted & 0xFFFF {
+							return Err(Error::StoredLengthMismatch);
+						}
+						State::Stored(len as usize)
+					}
+					BLOCK_FIXED => State::Compressed(Box::new(fixed_codes())),
+					BLOCK_DYNAMIC => State::Compressed(Box::new(read_dynamic_codes(bits)?)),
+					_ => return Err(Error::InvalidBlockType),
+				};
+				self.last_block = last;
+			}
+			State::Stored(remaining) => {
+				let available = bits.available() / 8;
+				let len = (*remaining).min(available);
+				let start = bits.pos / 8;
+				let bytes = &bits.data[start..start + len];
+				self.produced += len;
+				if self.produced > self.limit {
+					return Err(Error::TooMuchOutput);
+				}
+				self.window.extend_from_slice(bytes);
+				self.output.extend_from_slice(bytes);
+				bits.consume(len * 8);
+				*remaining -= len;
+				if *remaining > 0 {
+					return Ok(false);
+				}
+				self.state = State::BlockHeader;
+			}
+			State::Compressed(codes) => {
+				let (literals, distances) = &**codes;
+				// Symbol by symbol, so that running out of input only rolls back the last one
+				loop {
+					let start = bits.pos;
+					match decode_symbol(bits, literals, distances, &mut self.window) {
+						Ok(Some(from)) => {
+							self.produced += self.window.len() - from;
+							if self.produced > self.limit {
+								return Err(Error::TooMuchOutput);
+							}
+							self.output.extend_from_slice(&self.window[from..]);
+						}
+						Ok(None) => break,
+						Err(Error::UnexpectedEnd) => {
+							bits.pos = start;
+							return Ok(false);
+						}
+						Err(err) => return Err(err),
+					}
+				}
+				self.state = State::BlockHeader;
+			}
+			State::ZlibTrailer => {
+				bits.align_to_byte();
+				let checksum = bits.bits(32)?.swap_bytes();
+				self.expected_checksum = checksum;
+				self.state = State::Done;
+				return Ok(false);
+			}
+			State::Done => return Ok(false),
+		}
+		Ok(true)
+	}
+}
+
+/// Decodes a literal or a back reference into `window`. Returns where the new bytes start in
+/// the window, `None` at the end of the block.
+fn decode_symbol(
+	bits: &mut BitReader,
+	literals: &Huffman,
+	distances: &Huffman,
+	window: &mut Vec<u8>,
+) -> Result<Option<usize>, Error> {
+	let symbol = literals.decode(bits)?;
+	let start = window.len();
+	match symbol {
+		0..=255 => window.push(symbol as u8),
+		END_OF_BLOCK => return Ok(None),
+		_ => {
+			let idx = (symbol - 257) as usize;
+			if idx >= LENGTH_BASE.len() {
+				return Err(Error::InvalidCode);
+			}
+			let len = LENGTH_BASE[idx] as usize + bits.bits(LENGTH_EXTRA[idx] as u32)? as usize;
+			let idx = distances.decode(bits)? as usize;
+			if idx >= DISTANCE_BASE.len() {
+				return Err(Error::InvalidCode);
+			}
+			let distance =
+				DISTANCE_BASE[idx] as usize + bits.bits(DISTANCE_EXTRA[idx] as u32)? as usize;
+			if distance > window.len() {
+				return Err(Error::InvalidDistance);
+			}
+			// Byte by byte, the reference may overlap the bytes it produces
+			let from = window.len() - distance;
+			for offset in 0..len {
+				window.push(window[from + offset]);
+			}
+		}
+	}
+	Ok(Some(start))
+}
+
+/// The predefined codes of fixed Huffman blocks, see RFC 1951 3.2.6.
+fn fixed_codes() -> (Huffman, Huffman) {
+	let mut lengths = [0u8; 288];
+	lengths[..144].fill(8);
+	lengths[144..256].fill(9);
+	lengths[256..280].fill(7);
+	lengths[280..].fill(8);
+	let literals = Huffman::new(&lengths).expect("the fixed code is valid");
+	let distances = Huffman::new(&[5; 30]).expect("the fixed code is valid");
+	(literals, distances)
+}
+
+/// Reads the code definitions at the start of a dynamic Huffman block, see RFC 1951 3.2.7.
+fn read_dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+	let literal_count = bits.bits(5)? as usize + 257;
+	let distance_count = bits.bits(5)? as usize + 1;
+	let code_length_count = bits.bits(4)? as usize + 4;
+
+	let mut code_length_lengths = [0u8; 19];
+	for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+		code_length_lengths[symbol] = bits.bits(3)? as u8;
+	}
+	let code_lengths = Huffman::new(&code_length_lengths)?;
+
+	// Literal/length and distance code lengths form a single sequence, repeats may cross over
+	let mut lengths = vec![0u8; literal_count + distance_count];
+	let mut idx = 0;
+	while idx < lengths.len() {
+		let (value, repeat) = match code_lengths.decode(bits)? {
+			len @ 0..=15 => (len as u8, 1),
+			16 => {
+				let previous = *idx
+					.checked_sub(1)
+					.and_then(|previous| lengths.get(previous))
+					.ok_or(Error::InvalidCodeLengths)?;
+				(previous, 3 + bits.bits(2)? as usize)
+			}
+			17 => (0, 3 + bits.bits(3)? as usize),
+			_ => (0, 11 + bits.bits(7)? as usize),
+		};
+		let end = idx + repeat;
+		lengths
+			.get_mut(idx..end)
+			.ok_or(Error::InvalidCodeLengths)?
+			.fill(value);
+		idx = end;
+	}
+	if lengths[END_OF_BLOCK as usize] == 0 {
+		return Err(Error::InvalidCodeLengths);
+	}
+
+	let literals = Huffman::new(&lengths[..literal_count])?;
+	let distances = Huffman::new(&lengths[literal_count..])?;
+	Ok((literals, distances))
+}
+
+/// Compresses `data` into the zlib format. Uses the fixed Huffman codes with greedy LZ77
+/// matching, or stored blocks for data that doesn't get any smaller that way.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+	// Compression method 8 with a 32K window, default level. The check bits make the header a
+	// multiple of 31.
+	let mut output = vec![0x78, 0x9C];
+
+	let compressed = deflate_fixed(data);
+	if compressed.len() < data.len() + data.len() / 65535 * 5 + 5 {
+		output.extend_from_slice(&compressed);
+	} else {
+		deflate_stored(&mut output, data);
+	}
+
+	let mut adler = Adler32::new();
+	adler.update(data);
+	output.extend_from_slice(&adler.finish().to_be_bytes());
+	output
+}
+
+fn deflate_stored(output: &mut Vec<u8>, data: &[u8]) {
+	let mut chunks = data.chunks(u16::MAX as usize).peekable();
+	if chunks.peek().is_none() {
+		// Final empty stored block
+		output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+	}
+	while let Some(chunk) = chunks.next() {
+		let last = chunks.peek().is_none();
+		let len = chunk.len() as u16;
+		// Block header bits are followed by padding to the byte boundary
+		output.push(last as u8);
+		output.extend_from_slice(&len.to_le_bytes());
+		output.extend_from_slice(&(!len).to_le_bytes());
+		output.extend_from_slice(chunk);
+	}
+}
+
+/// Least significant bit first writer.
+#[derive(Default)]
+struct BitWriter {
+	output: Vec<u8>,
+	bits: u64,
+	count: u32,
+}
+
+impl BitWriter {
+	fn write(&mut self, value: u32, count: u32) {
+		self.bits |= (value as u64) << self.count;
+		self.count += count;
+		while self.count >= 8 {
+			self.output.push(self.bits as u8);
+			self.bits >>= 8;
+			self.count -= 8;
+		}
+	}
+
+	/// Writes a Huffman code, most significant bit first.
+	fn write_code(&mut self, code: u32, len: u32) {
+		self.write(code.reverse_bits() >> (32 - len), len);
+	}
+
+	fn finish(mut self) -> Vec<u8> {
+		if self.count > 0 {
+			self.output.push(self.bits as u8);
+		}
+		self.output
+	}
+}
+
+fn write_fixed_literal(writer: &mut BitWriter, symbol: u16) {
+	let symbol = symbol as u32;
+	match symbol {
+		0..=143 => writer.write_code(0x30 + symbol, 8),
+		144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+		256..=279 => writer.write_code(symbol - 256, 7),
+		_ => writer.write_code(0xC0 + symbol - 280, 8),
+	}
+}
+
+/// A single fixed Huffman block.
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+	const MIN_MATCH: usize = 3;
+	const MAX_MATCH: usize = 258;
+	const MAX_CHAIN: usize = 64;
+	const HASH_BITS: u32 = 15;
+
+	let hash = |pos: usize| {
+		let value = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+		(value.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+	};
+	// Most recent position of each hash and the previous position with the same hash
+	let mut head = vec![usize::MAX; 1 << HASH_BITS];
+	let mut previous = vec![usize::MAX; WINDOW_SIZE];
+	let insert = |pos: usize, head: &mut [usize], previous: &mut [usize]| {
+		if pos + MIN_MATCH <= data.len() {
+			let hash = hash(pos);
+			previous[pos % WINDOW_SIZE] = head[hash];
+			head[hash] = pos;
+		}
+	};
+
+	let mut writer = BitWriter::default();
+	// Final block, fixed codes
+	writer.write(1, 1);
+	writer.write(BLOCK_FIXED, 2);
+
+	let mut pos = 0;
+	while pos < data.len() {
+		let mut best = (0, 0);
+		if pos + MIN_MATCH <= data.len() {
+			let max_len = MAX_MATCH.min(data.len() - pos);
+			let mut candidate = head[hash(pos)];
+			for _ in 0..MAX_CHAIN {
+				if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+					break;
+				}
+				let len = data[candidate..]
+					.iter()
+					.zip(&data[pos..pos + max_len])
+					.take_while(|(a, b)| a == b)
+					.count();
+				if len > best.0 {
+					best = (len, pos - candidate);
+					if len == max_len {
+						break;
+					}
+				}
+				let next = previous[candidate % WINDOW_SIZE];
+				// Older entries of the ring buffer may have been overwritten
+				if next == usize::MAX || next >= candidate {
+					break;
+				}
+				candidate = next;
+			}
+		}
+
+		let (len, distance) = best;
+		if len >= MIN_MATCH {
+			let idx = LENGTH_BASE
+				.iter()
+				.rposition(|&base| base as usize <= len)
+				.unwrap();
+			write_fixed_literal(&mut writer, 257 + idx as u16);
+			writer.write(
+				(len - LENGTH_BASE[idx] as usize) as u32,
+				LENGTH_EXTRA[idx] as u32,
+			);
+			let idx = DISTANCE_BASE
+				.iter()
+				.rposition(|&base| base as usize <= distance)
+				.unwrap();
+			writer.write_code(idx as u32, 5);
+			writer.write(
+				(distance - DISTANCE_BASE[idx] as usize) as u32,
+				DISTANCE_EXTRA[idx] as u32,
+			);
+			for offset in 0..len {
+				insert(pos + offset, &mut head, &mut previous);
+			}
+			pos += len;
+		} else {
+			write_fixed_literal(&mut writer, data[pos] as u16);
+			insert(pos, &mut head, &mut previous);
+			pos += 1;
+		}
+	}
+	write_fixed_literal(&mut writer, END_OF_BLOCK);
+	writer.finish()
+}
+
+/// Checksum of the zlib format.
+struct Adler32 {
+	a: u32,
+	b: u32,
+}
+
+impl Adler32 {
+	fn new() -> Self {
+		Adler32 { a: 1, b: 0 }
+	}
+
+	fn update(&mut self, data: &[u8]) {
+		const MOD: u32 = 65521;
+		// The sums can't overflow within this many bytes
+		for chunk in data.chunks(5552) {
+			for &byte in chunk {
+				self.a += byte as u32;
+				self.b += self.a;
+			}
+			self.a %= MOD;
+			self.b %= MOD;
+		}
+	}
+
+	fn finish(&self) -> u32 {
+		self.b << 16 | self.a
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `" ".join(str(i * i % 1000) for i in range(60))` compressed by zlib at level 1, in dynamic
+	/// blocks.
+	const DYNAMIC_LEVEL_1: [u8; 121] = [
+		0x78, 0x01, 0x25, 0x8E, 0xC1, 0x11, 0x00, 0x31, 0x08, 0x02, 0x5B, 0xA1, 0x84, 0x98, 0x10,
+		0xA3, 0xFD, 0x37, 0x76, 0xEB, 0xDC, 0x8B, 0x51, 0x17, 0x61, 0x29, 0x64, 0xB5, 0x22, 0xB5,
+		0xAF, 0x4E, 0xCA, 0xAD, 0xB4, 0x2A, 0x14, 0x6B, 0x29, 0x36, 0x6A, 0x73, 0x85, 0x68, 0x10,
+		0x98, 0x7D, 0xD1, 0x6A, 0x9D, 0x6D, 0x78, 0xDC, 0x70, 0x36, 0x5A, 0xD6, 0xDD, 0xAD, 0xFB,
+		0x52, 0x09, 0x97, 0xE8, 0x63, 0x7E, 0xEC, 0x8B, 0x7B, 0xC3, 0x35, 0x3C, 0x36, 0xDC, 0x31,
+		0x5F, 0xE6, 0x1B, 0x5F, 0x0F, 0xDF, 0x4D, 0xCA, 0x25, 0x2D, 0xA1, 0x92, 0xF4, 0x37, 0x25,
+		0xE8, 0xD2, 0x54, 0x02, 0x8B, 0xE9, 0xB7, 0x08, 0x5D, 0x26, 0x30, 0x74, 0x07, 0x43, 0x1F,
+		0x73, 0xB1, 0xEF, 0xBF, 0x7F, 0x0C, 0x8D, 0xEB, 0xE0, 0x76, 0xC5, 0x07, 0x69, 0xC5, 0x28,
+		0x62,
+	];
+	/// The same at level 9.
+	const DYNAMIC_LEVEL_9: [u8; 120] = [
+		0x78, 0xDA, 0x2D, 0x8E, 0xD1, 0x15, 0x03, 0x30, 0x08, 0x02, 0x57, 0x61, 0x84, 0x98, 0xA0,
+		0xD1, 0xFD, 0x17, 0xEB, 0xA5, 0xED, 0x17, 0x4F, 0x3D, 0x84, 0xA5, 0x90, 0x35, 0x8A, 0xD2,
+		0x4E, 0x9D, 0x92, 0x47, 0x65, 0x75, 0x28, 0xD6, 0x52, 0x6C, 0xD4, 0xE6, 0x0A, 0x31, 0x20,
+		0x30, 0x3B, 0xD1, 0x1E, 0x9D, 0x6D, 0x78, 0xDC, 0x70, 0x36, 0xDA, 0x56, 0xEE, 0x51, 0xDE,
+		0x52, 0xC1, 0x15, 0x7A, 0x99, 0x2F, 0xFB, 0xE6, 0x3E, 0x70, 0x03, 0x8F, 0x0D, 0x77, 0xE4,
+		0xFF, 0x1B, 0x5F, 0x0F, 0xDF, 0xED, 0xE7, 0x0E, 0x15, 0x54, 0x91, 0x7E, 0x5F, 0x09, 0xBA,
+		0xCC, 0xF9, 0x16, 0x8B, 0xD7, 0x6F, 0x11, 0xBA, 0x4C, 0x60, 0x28, 0x1F, 0x86, 0x5E, 0xE6,
+		0x66, 0x3F, 0xBF, 0xFE, 0xF1, 0x68, 0x5C, 0x07, 0xB7, 0x3B, 0x3E, 0x69, 0xC5, 0x28, 0x62,
+	];
+	/// `abcabcabc` compressed by zlib at level 6, in a fixed block.
+	const FIXED_LEVEL_6: [u8; 13] = [
+		0x78, 0x9C, 0x4B, 0x4C, 0x4A, 0x4E, 0x04, 0x23, 0x00, 0x11, 0x3D, 0x03, 0x73,
+	];
+
+	fn squares() -> Vec<u8> {
+		let squares: Vec<String> = (0..60).map(|i| (i * i % 1000).to_string()).collect();
+		squares.join(" ").into_bytes()
+	}
+
+	/// Bytes that don't compress, from a xorshift generator.
+	fn noise(len: usize) -> Vec<u8> {
+		let mut state: u32 = 0x2545F491;
+		(0..len)
+			.map(|_| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				state as u8
+			})
+			.collect()
+	}
+
+	fn round_trip(data: &[u8]) {
+		let compressed = zlib_compress(data);
+		assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+	}
+
+	#[test]
+	fn stored_blocks() {
+		round_trip(&[]);
+		round_trip(&noise(100));
+		// More than fits in a single stored block
+		round_trip(&noise(150_000));
+
+		let mut raw = Vec::new();
+		deflate_stored(&mut raw, b"stored");
+		assert_eq!(inflate(&raw).unwrap(), b"stored");
+	}
+
+	#[test]
+	fn fixed_blocks() {
+		round_trip(b"a");
+		round_trip(&squares());
+		round_trip(&b"folder ".repeat(10_000));
+		assert_eq!(zlib_decompress(&FIXED_LEVEL_6).unwrap(), b"abcabcabc");
+		assert_eq!(
+			inflate(&deflate_fixed(b"fixed fixed fixed")).unwrap(),
+			b"fixed fixed fixed"
+		);
+	}
+
+	#[test]
+	fn dynamic_blocks() {
+		assert_eq!(zlib_decompress(&DYNAMIC_LEVEL_1).unwrap(), squares());
+		assert_eq!(zlib_decompress(&DYNAMIC_LEVEL_9).unwrap(), squares());
+	}
+
+	#[test]
+	fn pushed_in_pieces() {
+		let compressed = zlib_compress(&squares());
+		for data in [compressed.as_slice(), &DYNAMIC_LEVEL_9] {
+			let mut inflater = Inflater::zlib();
+			let mut output = Vec::new();
+			for byte in data {
+				inflater.push(std::slice::from_ref(byte)).unwrap();
+				output.append(inflater.output());
+			}
+			assert!(inflater.is_finished());
+			assert_eq!(output, squares());
+		}
+	}
+
+	#[test]
+	fn truncated() {
+		let compressed = zlib_compress(&squares());
+		for data in [compressed.as_slice(), &DYNAMIC_LEVEL_1, &FIXED_LEVEL_6] {
+			for len in 0..data.len() {
+				assert!(zlib_decompress(&data[..len]).is_err(), "{len} bytes");
+			}
+		}
+		assert_eq!(zlib_decompress(&[]), Err(Error::UnexpectedEnd));
+	}
+
+	#[test]
+	fn corrupt() {
+		let mut data = DYNAMIC_LEVEL_1;
+		*data.last_mut().unwrap() ^= 1;
+		assert_eq!(zlib_decompress(&data), Err(Error::ChecksumFailed));
+
+		let mut data = DYNAMIC_LEVEL_1;
+		data[0] = 0x79;
+		assert_eq!(zlib_decompress(&data), Err(Error::BadZlibHeader));
+
+		// Final block of the reserved type 3
+		assert_eq!(inflate(&[0x07]), Err(Error::InvalidBlockType));
+		// Length and its complement don't match
+		assert_eq!(
+			inflate(&[0x01, 0x05, 0x00, 0xFF, 0xFF]),
+			Err(Error::StoredLengthMismatch)
+		);
+
+		// A back reference before the start of the output
+		let mut writer = BitWriter::default();
+		writer.write(1, 1);
+		writer.write(BLOCK_FIXED, 2);
+		write_fixed_literal(&mut writer, b'a' as u16);
+		// Length 3, distance 2
+		write_fixed_literal(&mut writer, 257);
+		writer.write_code(1, 5);
+		write_fixed_literal(&mut writer, END_OF_BLOCK);
+		assert_eq!(inflate(&writer.finish()), Err(Error::InvalidDistance));
+
+		// Every bit flipped in turn, corruption mustn't panic
+		for bit in 0..DYNAMIC_LEVEL_9.len() * 8 {
+			let mut data = DYNAMIC_LEVEL_9;
+			data[bit / 8] ^= 1 << (bit % 8);
+			assert!(zlib_decompress(&data).is_err(), "bit {bit}");
+		}
+	}
+
+	#[test]
+	fn limited() {
+		for data in [vec![0; 100_000], noise(100_000)] {
+			let compressed = zlib_compress(&data);
+			let mut inflater = Inflater::zlib().with_limit(data.len());
+			inflater.push(&compressed).unwrap();
+			assert_eq!(inflater.finish().unwrap(), data);
+
+			let mut inflater = Inflater::zlib().with_limit(data.len() - 1);
+			assert_eq!(inflater.push(&compressed), Err(Error::TooMuchOutput));
+		}
+	}
+}
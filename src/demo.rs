@@ -0,0 +1,244 @@
+//! The rendering demo the explorer grew out of, built with the `demo` feature: a scrolling
+//! background, a player rectangle and textures to move around. It's shown in place of the views
+//! until toggled off with the `demo.toggle` command.
+
+use std::path::{Path, PathBuf};
+
+use crate::app::State;
+use crate::assets;
+use crate::command::CommandRegistry;
+use crate::draw::{dither, draw_background, draw_rectangle, draw_texture, Texture};
+use crate::image;
+use crate::key::{Key, ScanCode};
+use crate::window::{BitmapData, Keyboard};
+
+const LOGO: &str = "logo.png";
+const MOTORCYCLE: &str = "motorcycle.png";
+const TEXTURES: [&str; 2] = [LOGO, MOTORCYCLE];
+
+const LOGO_POS: (usize, usize) = (60, 80);
+const PLAYER_COLOR: u32 = 0xd3869b;
+
+#[derive(Debug)]
+pub struct Demo {
+	pub visible: bool,
+	pub background: BackgroundState,
+	pub player: PlayerState,
+	pub textures: Textures,
+	pub dithering: bool,
+}
+
+#[derive(Debug)]
+pub struct Textures {
+	pub logo: Texture,
+	pub motorcycle: Texture,
+}
+
+#[derive(Debug)]
+pub struct BackgroundState {
+	pub x_offset: usize,
+	pub y_offset: usize,
+	pub scrolling: bool,
+}
+
+#[derive(Debug)]
+pub struct PlayerState {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
+impl Default for Demo {
+	fn default() -> Self {
+		Demo {
+			visible: true,
+			background: BackgroundState {
+				x_offset: 0,
+				y_offset: 0,
+				scrolling: true,
+			},
+			player: PlayerState {
+				x: 0,
+				y: 0,
+				width: 50,
+				height: 500,
+			},
+			textures: Textures {
+				logo: assets::texture(LOGO).with_pos(LOGO_POS.0, LOGO_POS.1),
+				motorcycle: assets::texture(MOTORCYCLE).with_pos(200, 80),
+			},
+			dithering: true,
+		}
+	}
+}
+
+impl Textures {
+	/// The texture loaded from `path`.
+	fn by_path(&mut self, path: &Path) -> Option<&mut Texture> {
+		if path == assets::path(LOGO) {
+			Some(&mut self.logo)
+		} else if path == assets::path(MOTORCYCLE) {
+			Some(&mut self.motorcycle)
+		} else {
+			None
+		}
+	}
+}
+
+/// Files of the textures, to watch for changes.
+pub fn texture_paths() -> impl Iterator<Item = PathBuf> {
+	TEXTURES.into_iter().map(assets::path)
+}
+
+impl Demo {
+	/// Replaces the texture loaded from `path` with the file's new contents, keeping its position.
+	/// Returns None if `path` isn't a texture of the demo. Unreadable files (e.g. still being
+	/// written) keep the old texture until they change again.
+	pub fn reload_texture(&mut self, path: &Path) -> Option<Result<(), image::Error>> {
+		let texture = self.textures.by_path(path)?;
+		Some(image::load_from_path(path).map(|image| {
+			*texture = image.into_texture().with_pos(texture.pos.x, texture.pos.y);
+		}))
+	}
+
+	pub fn reset_player(&mut self) {
+		self.player.x = 0;
+		self.player.y = 0;
+	}
+
+	pub fn reset_logo(&mut self) {
+		self.textures.logo.pos.x = LOGO_POS.0;
+		self.textures.logo.pos.y = LOGO_POS.1;
+	}
+
+	/// Advances the background by a frame.
+	pub fn animate(&mut self) {
+		if self.background.scrolling {
+			self.background.x_offset += 1;
+			self.background.y_offset += 1;
+		}
+	}
+
+	/// Moves the player with the arrow keys and the logo with WASD.
+	pub fn update(&mut self, keyboard: &Keyboard, bitmap_data: BitmapData) {
+		let player = &mut self.player;
+		if keyboard.is_pressed(Key::Up) && player.y > 0 {
+			player.y = player.y.saturating_sub(5);
+		}
+		if keyboard.is_pressed(Key::Down)
+			&& (player.y as i32) < bitmap_data.bitmap_height - player.height as i32
+		{
+			player.y += 5;
+		}
+		if keyboard.is_pressed(Key::Left) {
+			player.x = player.x.saturating_sub(5);
+		}
+		if keyboard.is_pressed(Key::Right)
+			&& (player.x as i32) < bitmap_data.bitmap_width - player.width as i32
+		{
+			player.x += 5;
+		}
+		if keyboard.is_scancode_pressed(ScanCode::LEFT_BRACE) && player.height > 0 {
+			player.height -= 1;
+			player.y += 1;
+		}
+		if keyboard.is_scancode_pressed(ScanCode::RIGHT_BRACE) && player.y > 0 {
+			player.height += 1;
+			player.y -= 1;
+		}
+
+		//Moving logo texture, WASD by position so that it works on any layout
+		let logo = &mut self.textures.logo;
+		if keyboard.is_scancode_pressed(ScanCode::W) && logo.pos.y > 0 {
+			logo.pos.y = logo.pos.y.saturating_sub(5);
+		}
+		if keyboard.is_scancode_pressed(ScanCode::S)
+			&& (logo.pos.y as i32) < bitmap_data.bitmap_height - logo.height as i32
+		{
+			logo.pos.y += 5;
+		}
+		if keyboard.is_scancode_pressed(ScanCode::A) {
+			logo.pos.x = logo.pos.x.saturating_sub(5);
+		}
+		if keyboard.is_scancode_pressed(ScanCode::D)
+			&& (logo.pos.x as i32) < bitmap_data.bitmap_width - logo.width as i32
+		{
+			logo.pos.x += 5;
+		}
+	}
+
+	/// Draws the whole window, the rest of the UI is drawn on top.
+	pub fn render(&self, bitmap_data: BitmapData) {
+		draw_background(
+			bitmap_data,
+			self.background.x_offset,
+			self.background.y_offset,
+		);
+		draw_rectangle(
+			bitmap_data,
+			(self.player.x, self.player.y),
+			(self.player.width, self.player.height),
+			PLAYER_COLOR,
+		);
+		for texture in [&self.textures.logo, &self.textures.motorcycle] {
+			draw_texture(bitmap_data, texture, texture.pos.x, texture.pos.y);
+		}
+		if self.dithering {
+			for texture in [&self.textures.motorcycle, &self.textures.logo] {
+				dither(
+					bitmap_data,
+					texture.pos.x,
+					texture.pos.y,
+					texture.width,
+					texture.height,
+				);
+			}
+		}
+	}
+}
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+	registry.register(
+		"demo.toggle",
+		"Toggle demo",
+		"Shows or hides the rendering demo.",
+		toggle,
+	);
+	registry.register(
+		"player.reset",
+		"Reset player position",
+		"Moves the demo's player back to the start.",
+		|state| state.demo.reset_player(),
+	);
+	registry.register(
+		"logo.reset",
+		"Reset logo position",
+		"Moves the bouncing logo back to the start.",
+		|state| state.demo.reset_logo(),
+	);
+	registry.register(
+		"render.toggle_dithering",
+		"Toggle dithering",
+		"Turns the demo's ordered dithering on or off.",
+		toggle_dithering,
+	);
+	registry.register(
+		"background.toggle_scrolling",
+		"Toggle background scrolling",
+		"Starts or stops the scrolling of the demo's background.",
+		toggle_background_scrolling,
+	);
+}
+
+fn toggle(state: &mut State) {
+	state.demo.visible = !state.demo.visible;
+}
+
+fn toggle_dithering(state: &mut State) {
+	state.demo.dithering = !state.demo.dithering;
+}
+
+fn toggle_background_scrolling(state: &mut State) {
+	state.demo.background.scrolling = !state.demo.background.scrolling;
+}
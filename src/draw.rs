@@ -1,4 +1,135 @@
-use crate::{window::BitmapData, Texture};
+use std::fmt::Debug;
+use std::ops::Range;
+
+use crate::font::{self, Font};
+use crate::{ttf, window::BitmapData};
+
+/// Axis aligned rectangle in bitmap pixels.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Rect {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
+impl Rect {
+	pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+		Rect {
+			x,
+			y,
+			width,
+			height,
+		}
+	}
+
+	/// Whether the point (usually the mouse cursor) lies inside of the rectangle.
+	pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+		x >= 0
+			&& y >= 0 && (self.x..self.x + self.width).contains(&(x as usize))
+			&& (self.y..self.y + self.height).contains(&(y as usize))
+	}
+
+	/// Whether the two rectangles overlap.
+	pub fn intersects(&self, other: &Rect) -> bool {
+		self.x < other.x + other.width
+			&& other.x < self.x + self.width
+			&& self.y < other.y + other.height
+			&& other.y < self.y + self.height
+	}
+}
+
+/// Deferred drawing operation, see [DrawList].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+	Rectangle {
+		pos: (usize, usize),
+		size: (usize, usize),
+		color: u32,
+	},
+	RectangleBlended {
+		pos: (usize, usize),
+		size: (usize, usize),
+		color: u32,
+		alpha: f32,
+	},
+	Text {
+		/// Byte range of the text in [DrawList::text].
+		text: Range<usize>,
+		pos: (usize, usize),
+		color: u32,
+	},
+}
+
+/// Drawing operations recorded during update and replayed onto the bitmap during render. Lists
+/// are cleared and refilled every frame, keeping their memory.
+#[derive(Debug, Default, Clone)]
+pub struct DrawList {
+	commands: Vec<DrawCommand>,
+	/// Texts of all [DrawCommand::Text]s, one after another.
+	text: String,
+}
+
+impl DrawList {
+	pub fn clear(&mut self) {
+		self.commands.clear();
+		self.text.clear();
+	}
+
+	pub fn rectangle(&mut self, pos: (usize, usize), size: (usize, usize), color: u32) {
+		self.commands
+			.push(DrawCommand::Rectangle { pos, size, color });
+	}
+
+	pub fn rectangle_blended(
+		&mut self,
+		pos: (usize, usize),
+		size: (usize, usize),
+		color: u32,
+		alpha: f32,
+	) {
+		self.commands.push(DrawCommand::RectangleBlended {
+			pos,
+			size,
+			color,
+			alpha,
+		});
+	}
+
+	pub fn text(&mut self, text: &str, pos: (usize, usize), color: u32) {
+		let start = self.text.len();
+		self.text.push_str(text);
+		self.commands.push(DrawCommand::Text {
+			text: start..self.text.len(),
+			pos,
+			color,
+		});
+	}
+
+	/// Draws all recorded commands in order.
+	pub fn execute(&self, bitmap_data: BitmapData) {
+		for command in &self.commands {
+			match command {
+				DrawCommand::Rectangle { pos, size, color } => {
+					draw_rectangle(bitmap_data, *pos, *size, *color)
+				}
+				DrawCommand::RectangleBlended {
+					pos,
+					size,
+					color,
+					alpha,
+				} => draw_rectangle_blended(bitmap_data, *pos, *size, *color, *alpha),
+				DrawCommand::Text { text, pos, color } => draw_text(
+					bitmap_data,
+					font::ui(),
+					&self.text[text.clone()],
+					*pos,
+					*color,
+				),
+			}
+		}
+	}
+}
 
 pub fn draw_background(bitmap_data: BitmapData, x_offset: usize, y_offset: usize) {
 	let bitmap_memory = bitmap_data.into_slice();
@@ -29,6 +160,121 @@ pub fn draw_rectangle(
 	}
 }
 
+/// Like [draw_rectangle], but blends `color` over the existing pixels with the given opacity.
+pub fn draw_rectangle_blended(
+	bitmap_data: BitmapData,
+	(pos_x, pos_y): (usize, usize),
+	(width, height): (usize, usize),
+	color: u32,
+	alpha: f32,
+) {
+	let bitmap_memory = bitmap_data.into_slice();
+
+	for y in pos_y..(pos_y + height).min(bitmap_data.bitmap_height as usize) {
+		for x in pos_x..(pos_x + width).min(bitmap_data.bitmap_width as usize) {
+			let pixel = &mut bitmap_memory[y * bitmap_data.bitmap_width as usize + x];
+			*pixel = blend(*pixel, color, alpha);
+		}
+	}
+}
+
+/// Draws a single line of text with `font`. `(pos_x, pos_y)` is the top left corner of the first
+/// bitmap font glyph, TrueType glyphs are placed on a [font::LINE_HEIGHT] tall line around it.
+pub fn draw_text(
+	bitmap_data: BitmapData,
+	font: &Font,
+	text: &str,
+	(pos_x, pos_y): (usize, usize),
+	color: u32,
+) {
+	match font {
+		Font::Bitmap => draw_bitmap_text(bitmap_data, text, (pos_x, pos_y), color),
+		// Bitmap glyphs leave a font pixel of the line empty above them
+		Font::TrueType(font) => {
+			let top = pos_y as isize - font::SCALE as isize;
+			draw_truetype_text(bitmap_data, font, text, (pos_x, top), color)
+		}
+	}
+}
+
+fn draw_bitmap_text(
+	bitmap_data: BitmapData,
+	text: &str,
+	(pos_x, pos_y): (usize, usize),
+	color: u32,
+) {
+	let bitmap_memory = bitmap_data.into_slice();
+	let bitmap_width = bitmap_data.bitmap_width as usize;
+	let bitmap_height = bitmap_data.bitmap_height as usize;
+
+	for (char_idx, c) in text.chars().enumerate() {
+		let glyph_x = pos_x + char_idx * font::ADVANCE;
+		if glyph_x >= bitmap_width {
+			break;
+		}
+
+		for (row_idx, row) in font::glyph(c).iter().enumerate() {
+			for col_idx in 0..font::GLYPH_WIDTH {
+				if row & (1 << (font::GLYPH_WIDTH - 1 - col_idx)) == 0 {
+					continue;
+				}
+				for dy in 0..font::SCALE {
+					let y = pos_y + row_idx * font::SCALE + dy;
+					if y >= bitmap_height {
+						break;
+					}
+					for dx in 0..font::SCALE {
+						let x = glyph_x + col_idx * font::SCALE + dx;
+						if x >= bitmap_width {
+							break;
+						}
+						bitmap_memory[y * bitmap_width + x] = color;
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Draws a line of text whose top left corner is `(pos_x, pos_y)`, the baseline lies
+/// [ttf::Font::ascent] below it. Glyphs are rasterized on first use.
+fn draw_truetype_text(
+	bitmap_data: BitmapData,
+	font: &ttf::Font,
+	text: &str,
+	(pos_x, pos_y): (usize, isize),
+	color: u32,
+) {
+	let bitmap_memory = bitmap_data.into_slice();
+	let bitmap_width = bitmap_data.bitmap_width as isize;
+	let bitmap_height = bitmap_data.bitmap_height as isize;
+
+	let baseline = pos_y + font.ascent().round() as isize;
+	let mut pen_x = pos_x as f32;
+	for c in text.chars() {
+		let glyph = font.glyph(c);
+		if let Some(mask) = &glyph.mask {
+			let left = pen_x.round() as isize + glyph.offset.0;
+			let top = baseline + glyph.offset.1;
+			for y in top.max(0)..(top + mask.height() as isize).min(bitmap_height) {
+				let mask_row = (y - top) as usize * mask.width();
+				for x in left.max(0)..(left + mask.width() as isize).min(bitmap_width) {
+					let coverage = mask.pixels()[mask_row + (x - left) as usize] >> 24;
+					if coverage == 0 {
+						continue;
+					}
+					let pixel = &mut bitmap_memory[(y * bitmap_width + x) as usize];
+					*pixel = blend(*pixel, color, coverage as f32 / 255.0);
+				}
+			}
+		}
+		pen_x += glyph.advance;
+		if pen_x >= bitmap_width as f32 {
+			break;
+		}
+	}
+}
+
 pub fn draw_texture(bitmap_data: BitmapData, texture: &Texture, pos_x: usize, pos_y: usize) {
 	let bitmap_memory = bitmap_data.into_slice();
 
@@ -59,6 +305,68 @@ pub fn draw_texture(bitmap_data: BitmapData, texture: &Texture, pos_x: usize, po
 	}
 }
 
+/// Draws a texture whose top left corner is at `(pos_x, pos_y)`, which may lie outside of the
+/// bitmap. Only pixels inside of `clip` are touched.
+pub fn draw_texture_clipped(
+	bitmap_data: BitmapData,
+	texture: &Texture,
+	pos: (isize, isize),
+	clip: Rect,
+) {
+	let region = Rect::new(0, 0, texture.width, texture.height);
+	draw_texture_region(bitmap_data, texture, region, pos, clip);
+}
+
+/// Like [draw_texture_clipped], drawing only the `region` part of the texture, e.g. one image of
+/// an [crate::atlas::Atlas].
+pub fn draw_texture_region(
+	bitmap_data: BitmapData,
+	texture: &Texture,
+	region: Rect,
+	(pos_x, pos_y): (isize, isize),
+	clip: Rect,
+) {
+	let bitmap_memory = bitmap_data.into_slice();
+	let clip_right = (clip.x + clip.width).min(bitmap_data.bitmap_width as usize) as isize;
+	let clip_bottom = (clip.y + clip.height).min(bitmap_data.bitmap_height as usize) as isize;
+
+	let start_y = pos_y.max(clip.y as isize);
+	let end_y = (pos_y + region.height as isize).min(clip_bottom);
+	let start_x = pos_x.max(clip.x as isize);
+	let end_x = (pos_x + region.width as isize).min(clip_right);
+
+	for y in start_y..end_y {
+		let tex_y = region.y + (y - pos_y) as usize;
+		for x in start_x..end_x {
+			let tex_x = region.x + (x - pos_x) as usize;
+			let pixel =
+				&mut bitmap_memory[y as usize * bitmap_data.bitmap_width as usize + x as usize];
+			let texture_pixel = texture.bitmap[tex_y * texture.width + tex_x];
+			let alpha = (((texture_pixel >> 24) & 0xFF) as f32) / 255.0;
+			*pixel = blend(*pixel, texture_pixel, alpha);
+		}
+	}
+}
+
+fn blend(background: u32, foreground: u32, alpha: f32) -> u32 {
+	let r = lerp(
+		((background >> 16) & 0xFF) as f32,
+		((foreground >> 16) & 0xFF) as f32,
+		alpha,
+	);
+	let g = lerp(
+		((background >> 8) & 0xFF) as f32,
+		((foreground >> 8) & 0xFF) as f32,
+		alpha,
+	);
+	let b = lerp(
+		(background & 0xFF) as f32,
+		(foreground & 0xFF) as f32,
+		alpha,
+	);
+	((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
 fn lerp(v0: f32, v1: f32, t: f32) -> f32 {
 	v0 + t * (v1 - v0)
 }
@@ -99,7 +407,7 @@ pub fn dither(bitmap_data: BitmapData, x: usize, y: usize, width: usize, height:
 			{
 				let pixel_idx = (y + 1) * (bitmap_data.bitmap_width as usize) + (x + 1);
 				if let Some(pixel) = bitmap_memory.get_mut(pixel_idx) {
-					*pixel += quant_error * 1 / 16;
+					*pixel += quant_error / 16;
 				}
 			}
 		}
@@ -127,3 +435,95 @@ fn find_closest_palette_color(pixel: u32) -> u32 {
 	}
 	nearest_color
 }
+
+pub struct Texture {
+	/// RGBA image
+	pub(crate) bitmap: Vec<u32>,
+	pub(crate) width: usize,
+	pub(crate) height: usize,
+
+	pub(crate) pos: Pos,
+}
+
+impl Debug for Texture {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		struct DebuggableBitmap<'a>(&'a Vec<u32>);
+		impl<'a> Debug for DebuggableBitmap<'a> {
+			fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				f.debug_struct("Vec").field("len", &self.0.len()).finish()
+			}
+		}
+
+		f.debug_struct("Texture")
+			.field("bitmap", &DebuggableBitmap(&self.bitmap))
+			.field("width", &self.width)
+			.field("height", &self.height)
+			.field("pos", &self.pos)
+			.finish()
+	}
+}
+
+impl Texture {
+	/// Fully transparent texture.
+	pub fn new(width: usize, height: usize) -> Self {
+		Texture {
+			bitmap: vec![0; width * height],
+			width,
+			height,
+			pos: Pos { x: 0, y: 0 },
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	/// 0xAARRGGBB pixels, row by row.
+	pub fn pixels(&self) -> &[u32] {
+		&self.bitmap
+	}
+
+	pub fn with_pos(mut self, x: usize, y: usize) -> Self {
+		self.pos.x = x;
+		self.pos.y = y;
+		self
+	}
+
+	/// Returns a copy scaled down (nearest neighbour) to fit in `max_width`x`max_height`, keeping
+	/// the aspect ratio. Textures that already fit are copied as they are.
+	pub fn scaled_to_fit(&self, max_width: usize, max_height: usize) -> Texture {
+		let scale = f32::min(
+			max_width as f32 / self.width as f32,
+			max_height as f32 / self.height as f32,
+		)
+		.min(1.0);
+		let width = ((self.width as f32 * scale) as usize).max(1);
+		let height = ((self.height as f32 * scale) as usize).max(1);
+
+		let mut bitmap = Vec::with_capacity(width * height);
+		for y in 0..height {
+			let src_y = y * self.height / height;
+			for x in 0..width {
+				let src_x = x * self.width / width;
+				bitmap.push(self.bitmap[src_y * self.width + src_x]);
+			}
+		}
+
+		Texture {
+			bitmap,
+			width,
+			height,
+			pos: Pos { x: 0, y: 0 },
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct Pos {
+	pub x: usize,
+	pub y: usize,
+}
@@ -0,0 +1,169 @@
+//! Crate-wide error type. Module errors (image decoders, keymap, recording...) convert into it,
+//! keeping what kind of failure it was and which file it concerned, so that the UI can pick a
+//! recovery by [ErrorKind] instead of parsing messages.
+
+use std::{
+	fmt::Display,
+	io,
+	path::{Path, PathBuf},
+};
+
+use crate::{config, filename, image, keymap, png, recording};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What went wrong, coarse enough to choose a recovery action.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+	NotFound,
+	PermissionDenied,
+	AlreadyExists,
+	/// A file or folder name Windows doesn't allow.
+	InvalidName,
+	/// A file whose contents couldn't be understood, e.g. a corrupt image.
+	InvalidData,
+	/// A file format or a feature of it that isn't supported.
+	Unsupported,
+	Cancelled,
+	Other,
+}
+
+#[derive(Debug)]
+pub struct Error {
+	kind: ErrorKind,
+	/// File or directory the error concerns.
+	path: Option<PathBuf>,
+	source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl Error {
+	pub fn new(
+		kind: ErrorKind,
+		source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+	) -> Self {
+		Error {
+			kind,
+			path: None,
+			source: source.into(),
+		}
+	}
+
+	/// Attaches the file or directory the error concerns, unless it already has one.
+	pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+		if self.path.is_none() {
+			self.path = Some(path.into());
+		}
+		self
+	}
+
+	pub fn kind(&self) -> ErrorKind {
+		self.kind
+	}
+
+	pub fn path(&self) -> Option<&Path> {
+		self.path.as_deref()
+	}
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match &self.path {
+			Some(path) => write!(f, "{}: {}", path.display(), self.source),
+			None => write!(f, "{}", self.source),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(self.source.as_ref())
+	}
+}
+
+/// Attaches a path to the error of a result, see [Error::with_path].
+pub trait Context<T> {
+	fn with_path(self, path: impl Into<PathBuf>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+	fn with_path(self, path: impl Into<PathBuf>) -> Result<T> {
+		self.map_err(|err| err.into().with_path(path))
+	}
+}
+
+impl From<io::ErrorKind> for ErrorKind {
+	fn from(kind: io::ErrorKind) -> Self {
+		match kind {
+			io::ErrorKind::NotFound => ErrorKind::NotFound,
+			io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+			io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+			io::ErrorKind::InvalidFilename => ErrorKind::InvalidName,
+			io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => ErrorKind::InvalidData,
+			io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+			_ => ErrorKind::Other,
+		}
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Self {
+		Error::new(err.kind().into(), err)
+	}
+}
+
+impl From<image::Error> for Error {
+	fn from(err: image::Error) -> Self {
+		match err {
+			image::Error::Io { err, filename } => Error::from(err).with_path(filename),
+			image::Error::UnknownFormat => Error::new(ErrorKind::Unsupported, err),
+			image::Error::Cancelled => Error::new(ErrorKind::Cancelled, err),
+			image::Error::Png(err) => err.into(),
+			err => Error::new(ErrorKind::InvalidData, err),
+		}
+	}
+}
+
+impl From<png::Error> for Error {
+	fn from(err: png::Error) -> Self {
+		match err {
+			png::Error::Io { err, filename } => Error::from(err).with_path(filename),
+			png::Error::Read(err) => err.into(),
+			png::Error::UnsupportedFormat { .. } => Error::new(ErrorKind::Unsupported, err),
+			png::Error::Cancelled => Error::new(ErrorKind::Cancelled, err),
+			err => Error::new(ErrorKind::InvalidData, err),
+		}
+	}
+}
+
+impl From<recording::Error> for Error {
+	fn from(err: recording::Error) -> Self {
+		match err {
+			recording::Error::Io { err, filename } => Error::from(err).with_path(filename),
+			err => Error::new(ErrorKind::InvalidData, err),
+		}
+	}
+}
+
+impl From<keymap::Error> for Error {
+	fn from(err: keymap::Error) -> Self {
+		match err {
+			keymap::Error::Io { err, filename } => Error::from(err).with_path(filename),
+			err => Error::new(ErrorKind::InvalidData, err),
+		}
+	}
+}
+
+impl From<config::Error> for Error {
+	fn from(err: config::Error) -> Self {
+		match err {
+			config::Error::Io { err, filename } => Error::from(err).with_path(filename),
+			err => Error::new(ErrorKind::InvalidData, err),
+		}
+	}
+}
+
+impl From<filename::Error> for Error {
+	fn from(err: filename::Error) -> Self {
+		Error::new(ErrorKind::InvalidName, err)
+	}
+}
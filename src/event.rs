@@ -0,0 +1,111 @@
+//! Events between subsystems. The subsystem that notices a change publishes an [Event] on the
+//! [EventBus] of the state instead of updating the subsystems depending on it itself, and
+//! [dispatch] delivers the queued events to them once per frame, in the order they were published.
+//!
+//! ```ignore
+//! state.events.publish(Event::DirectoryChanged {
+//!     path: state.history.current().to_path_buf(),
+//! });
+//! ```
+
+use std::mem;
+use std::path::PathBuf;
+
+use log::{debug, info};
+
+use crate::app::State;
+use crate::config::Theme;
+use crate::i18n::tr;
+use crate::status_bar::StatusEvent;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+	/// Entries were added to or removed from `path`, the directory of a pane.
+	DirectoryChanged {
+		path: PathBuf,
+	},
+	/// The selected files were measured.
+	SelectionChanged {
+		count: usize,
+		total_size: u64,
+	},
+	/// A background operation is running. `fraction` is None when its progress is unknown.
+	OperationProgress {
+		label: String,
+		fraction: Option<f32>,
+	},
+	/// The running background operation finished.
+	OperationFinished,
+	ThemeChanged(Theme),
+	/// Drives were plugged in or removed, `drives` are the root folders of the ones there now.
+	DeviceChanged {
+		drives: Vec<PathBuf>,
+	},
+}
+
+/// Events published during the current frame.
+#[derive(Debug, Default)]
+pub struct EventBus {
+	queue: Vec<Event>,
+}
+
+impl EventBus {
+	pub fn publish(&mut self, event: Event) {
+		self.queue.push(event);
+	}
+
+	/// Events published since the last call, oldest first.
+	pub fn take(&mut self) -> Vec<Event> {
+		mem::take(&mut self.queue)
+	}
+}
+
+/// Delivers the published events to the subsystems handling them. Events published by the
+/// handlers are delivered too.
+pub fn dispatch(state: &mut State) {
+	loop {
+		let events = state.events.take();
+		if events.is_empty() {
+			return;
+		}
+		for event in events {
+			handle(state, event);
+		}
+	}
+}
+
+fn handle(state: &mut State, event: Event) {
+	match event {
+		Event::DirectoryChanged { path } => {
+			info!("{} changed, reloading", path.display());
+			// Reloaded on the next update, like with the refresh command
+			if state.history.current() == path {
+				state.thumbnails = None;
+				state.list_view.reload();
+			}
+			if let Some(other) = &mut state.other_pane {
+				if other.history.current() == path {
+					other.list_view.reload();
+				}
+			}
+		}
+		Event::SelectionChanged { count, total_size } => state
+			.status_bar
+			.handle(StatusEvent::SelectionChanged { count, total_size }),
+		Event::OperationProgress { label, fraction } => state
+			.status_bar
+			.handle(StatusEvent::Progress { label, fraction }),
+		Event::OperationFinished => state.status_bar.handle(StatusEvent::ProgressDone),
+		Event::ThemeChanged(theme) => {
+			info!("Switched to the {} theme", theme.name());
+			state.status_bar.handle(StatusEvent::OperationMessage(tr!(
+				"theme_changed",
+				theme.name()
+			)));
+		}
+		Event::DeviceChanged { drives } => {
+			debug!("Drives: {drives:?}");
+			state.sidebar.set_drives(drives);
+		}
+	}
+}
@@ -0,0 +1,113 @@
+//! Checks of file names against the rules of Windows, before trying to create or rename a file
+//! and getting a less helpful error from the file system.
+
+use std::fmt::Display;
+
+/// Names of devices, which can't be used as file names, not even with an extension.
+const RESERVED_NAMES: &[&str] = &[
+	"CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+	"COM9", "COM¹", "COM²", "COM³", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+	"LPT9", "LPT¹", "LPT²", "LPT³",
+];
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+/// Longest name, in UTF-16 code units.
+const MAX_LEN: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	Empty,
+	InvalidChar(char),
+	/// The name of a device, like CON or LPT1.
+	Reserved(String),
+	/// Windows drops trailing dots and spaces, so the file would get a different name.
+	TrailingDotOrSpace,
+	TooLong,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Empty => write!(f, "The name is empty."),
+			Error::InvalidChar(c) if c.is_control() => {
+				write!(f, "The name contains a control character.")
+			}
+			Error::InvalidChar(c) => write!(f, "The name can't contain {c}."),
+			Error::Reserved(name) => write!(f, "{name} is reserved for a device."),
+			Error::TrailingDotOrSpace => write!(f, "The name can't end with a dot or a space."),
+			Error::TooLong => write!(f, "The name is longer than {MAX_LEN} characters."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Whether `name` can be the name of a file or directory, reporting the first problem found.
+pub fn validate(name: &str) -> Result<(), Error> {
+	if name.is_empty() {
+		return Err(Error::Empty);
+	}
+	if let Some(c) = name.chars().find(|&c| is_invalid_char(c)) {
+		return Err(Error::InvalidChar(c));
+	}
+	if name.ends_with(['.', ' ']) {
+		return Err(Error::TrailingDotOrSpace);
+	}
+	if let Some(reserved) = reserved_name(name) {
+		return Err(Error::Reserved(reserved.to_string()));
+	}
+	if name.encode_utf16().count() > MAX_LEN {
+		return Err(Error::TooLong);
+	}
+	Ok(())
+}
+
+/// A valid name as close to `name` as possible: invalid characters become underscores, trailing
+/// dots and spaces are dropped, reserved names get an underscore appended and long names are cut
+/// short.
+pub fn sanitize(name: &str) -> String {
+	let mut sanitized: String = name
+		.chars()
+		.map(|c| match is_invalid_char(c) {
+			true => '_',
+			false => c,
+		})
+		.collect();
+	sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+	if let Some(stem_len) = reserved_name(&sanitized).map(str::len) {
+		sanitized.insert(stem_len, '_');
+	}
+
+	let mut len = 0;
+	let end = sanitized
+		.char_indices()
+		.find(|&(_, c)| {
+			len += c.len_utf16();
+			len > MAX_LEN
+		})
+		.map(|(idx, _)| idx);
+	if let Some(end) = end {
+		sanitized.truncate(end);
+		sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+	}
+
+	if sanitized.is_empty() {
+		sanitized.push('_');
+	}
+	sanitized
+}
+
+fn is_invalid_char(c: char) -> bool {
+	c.is_ascii_control() || INVALID_CHARS.contains(&c)
+}
+
+/// The part of `name` that's a reserved device name, if any. "CON" and "con.txt" are reserved,
+/// "console" isn't.
+fn reserved_name(name: &str) -> Option<&str> {
+	let stem = name.split('.').next().unwrap();
+	// Spaces before the extension are ignored as well, "CON .txt" is CON too
+	let stem = stem.trim_end_matches(' ');
+	RESERVED_NAMES
+		.iter()
+		.any(|reserved| stem.eq_ignore_ascii_case(reserved))
+		.then_some(stem)
+}
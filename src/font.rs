@@ -0,0 +1,312 @@
+//! Fonts of UI text: the built-in 5x7 bitmap font, and TrueType fonts (see [ttf]). The UI is drawn
+//! with [ui], laid out on the bitmap font's grid of [LINE_HEIGHT] tall lines.
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::{Arc, OnceLock};
+
+use log::warn;
+
+use crate::ttf;
+
+/// Width of a single glyph in font pixels (without spacing).
+pub const GLYPH_WIDTH: usize = 5;
+/// Height of a single glyph in font pixels.
+pub const GLYPH_HEIGHT: usize = 7;
+/// How many screen pixels a single font pixel takes.
+pub const SCALE: usize = 2;
+
+/// Horizontal distance between the starts of two consecutive characters, in screen pixels.
+pub const ADVANCE: usize = (GLYPH_WIDTH + 1) * SCALE;
+/// Height of a line of text, in screen pixels.
+pub const LINE_HEIGHT: usize = (GLYPH_HEIGHT + 2) * SCALE;
+
+/// Font the UI is drawn with when the system has one.
+const UI_FONT_FILE: &str = "segoeui.ttf";
+
+/// A font text is measured and drawn with, see [draw_text](crate::draw::draw_text).
+#[derive(Debug)]
+pub enum Font {
+	/// The built-in bitmap font, see [glyph].
+	Bitmap,
+	/// A TrueType font sized to fill a [LINE_HEIGHT] tall line.
+	TrueType(ttf::Font),
+}
+
+static UI_FONT: OnceLock<Font> = OnceLock::new();
+
+/// The font of the UI: Segoe UI, or the bitmap font if it can't be loaded. Tests always get the
+/// bitmap font, so that layouts don't depend on the fonts installed.
+pub fn ui() -> &'static Font {
+	UI_FONT.get_or_init(|| {
+		if cfg!(test) {
+			return Font::Bitmap;
+		}
+		let windows =
+			std::env::var_os("WINDIR").map_or_else(|| PathBuf::from("C:\\Windows"), PathBuf::from);
+		match ttf::Face::load(&windows.join("Fonts").join(UI_FONT_FILE)) {
+			Ok(face) => {
+				let size = face.size_for_height(LINE_HEIGHT as f32);
+				Font::TrueType(ttf::Font::new(Arc::new(face), size))
+			}
+			Err(err) => {
+				warn!("{err} Using the bitmap font.");
+				Font::Bitmap
+			}
+		}
+	})
+}
+
+/// Returns glyph rows for the given character. Each row uses the lowest 5 bits, most significant
+/// bit being the leftmost pixel. Characters outside of printable ASCII are rendered as '?'.
+pub fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+	let idx = match c {
+		' '..='~' => c as usize - ' ' as usize,
+		_ => '?' as usize - ' ' as usize,
+	};
+	&GLYPHS[idx]
+}
+
+impl Font {
+	/// Width of the given text in screen pixels.
+	pub fn text_width(&self, text: &str) -> usize {
+		text.chars().map(|c| self.advance(c)).sum::<f32>().round() as usize
+	}
+
+	/// Horizontal distance from `c` to the next character, in screen pixels.
+	fn advance(&self, c: char) -> f32 {
+		match self {
+			Font::Bitmap => ADVANCE as f32,
+			Font::TrueType(font) => font.glyph(c).advance,
+		}
+	}
+
+	/// Cuts `text` down to fit in `max_width` screen pixels, ending it with "..." if anything was
+	/// cut.
+	pub fn truncate<'a>(&self, text: &'a str, max_width: usize) -> Cow<'a, str> {
+		if self.text_width(text) <= max_width {
+			return Cow::Borrowed(text);
+		}
+		let max_width = max_width.saturating_sub(self.text_width("..."));
+		Cow::Owned(format!("{}...", self.fitting_start(text, max_width)))
+	}
+
+	/// The longest start of `text` that fits in `max_width` screen pixels.
+	pub fn fitting_start<'a>(&self, text: &'a str, max_width: usize) -> &'a str {
+		let mut width = 0.0;
+		let end = text
+			.char_indices()
+			.find(|&(_, c)| {
+				width += self.advance(c);
+				width.round() > max_width as f32
+			})
+			.map_or(text.len(), |(idx, _)| idx);
+		&text[..end]
+	}
+
+	/// The longest end of `text` that fits in `max_width` screen pixels.
+	pub fn fitting_end<'a>(&self, text: &'a str, max_width: usize) -> &'a str {
+		let mut width = 0.0;
+		let start = text
+			.char_indices()
+			.rev()
+			.find(|&(_, c)| {
+				width += self.advance(c);
+				width.round() > max_width as f32
+			})
+			.map_or(0, |(idx, c)| idx + c.len_utf8());
+		&text[start..]
+	}
+
+	/// Shortens `path` to fit in `max_width` screen pixels by collapsing directories in the middle
+	/// into "...", e.g. "C:\Users\...\projects\file-explorer". The root and the last component are
+	/// always kept, the last one is truncated if even they don't fit.
+	pub fn compact_path(&self, path: &Path, max_width: usize) -> String {
+		let mut root = String::new();
+		let mut names = Vec::new();
+		for component in path.components() {
+			match component {
+				Component::Prefix(prefix) => root.push_str(&prefix.as_os_str().to_string_lossy()),
+				Component::RootDir => root.push(MAIN_SEPARATOR),
+				component => names.push(component.as_os_str().to_string_lossy()),
+			}
+		}
+		let full = path.display().to_string();
+		let Some(last) = names.pop() else {
+			return full;
+		};
+		if self.text_width(&full) <= max_width {
+			return full;
+		}
+
+		let separator = MAIN_SEPARATOR.to_string();
+		// Collapses more and more directories, keeping as many of the outer ones as fit. The tail
+		// gets the extra one when they can't be split evenly, it's closer to where the user is
+		for collapsed in 1..=names.len() {
+			let kept = names.len() - collapsed;
+			let head = &names[..kept / 2];
+			let tail = &names[names.len() - (kept - kept / 2)..];
+			let parts: Vec<&str> = head
+				.iter()
+				.map(|name| name.as_ref())
+				.chain(["..."])
+				.chain(tail.iter().map(|name| name.as_ref()))
+				.chain([last.as_ref()])
+				.collect();
+			let compacted = format!("{root}{}", parts.join(&separator));
+			if self.text_width(&compacted) <= max_width {
+				return compacted;
+			}
+		}
+		let shortest = match names.is_empty() {
+			true => format!("{root}{last}"),
+			false => format!("{root}...{separator}{last}"),
+		};
+		self.truncate(&shortest, max_width).into_owned()
+	}
+}
+
+#[rustfmt::skip]
+static GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+	[0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+	[0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+	[0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+	[0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+	[0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+	[0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+	[0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // '\''
+	[0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+	[0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+	[0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+	[0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+	[0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+	[0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+	[0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+	[0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+	[0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+	[0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+	[0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+	[0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+	[0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+	[0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+	[0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+	[0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+	[0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+	[0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+	[0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+	[0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+	[0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+	[0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+	[0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+	[0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'A'
+	[0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+	[0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+	[0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+	[0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+	[0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+	[0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+	[0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+	[0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+	[0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+	[0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+	[0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+	[0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+	[0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+	[0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+	[0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+	[0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+	[0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+	[0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+	[0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+	[0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+	[0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+	[0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+	[0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+	[0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+	[0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+	[0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+	[0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+	[0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+	[0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+	[0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+	[0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+	[0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+	[0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+	[0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+	[0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+	[0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+	[0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+	[0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+	[0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+	[0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+	[0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+	[0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+	[0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+	[0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+	[0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+	[0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+	[0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+	[0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+	[0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+	[0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+	[0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+	[0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+	[0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+	[0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+	[0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn truncate() {
+		let font = Font::Bitmap;
+		assert_eq!(font.truncate("abcdefghi", 9 * ADVANCE), "abcdefghi");
+		assert_eq!(font.truncate("abcdefghij", 9 * ADVANCE), "abcdef...");
+		assert_eq!(
+			font.truncate("abcdefghij", 9 * ADVANCE + ADVANCE / 2),
+			"abcdef..."
+		);
+		assert_eq!(font.truncate("ąęółśćźż", 5 * ADVANCE), "ąę...");
+		assert_eq!(font.truncate("abc", 0), "...");
+	}
+
+	#[test]
+	fn fitting_parts() {
+		let font = Font::Bitmap;
+		assert_eq!(font.fitting_start("abcdef", 3 * ADVANCE + 5), "abc");
+		assert_eq!(font.fitting_end("abcdef", 3 * ADVANCE + 5), "def");
+		assert_eq!(font.fitting_end("zażółć", 3 * ADVANCE), "ółć");
+		assert_eq!(font.fitting_start("abc", 10 * ADVANCE), "abc");
+		assert_eq!(font.fitting_end("abc", 10 * ADVANCE), "abc");
+		assert_eq!(font.fitting_start("abc", ADVANCE - 1), "");
+		assert_eq!(font.fitting_end("abc", ADVANCE - 1), "");
+	}
+
+	#[test]
+	fn compact_path() {
+		let font = Font::Bitmap;
+		let path = Path::new("C:\\Users\\me\\projects\\file-explorer\\src");
+		assert_eq!(
+			font.compact_path(path, 40 * ADVANCE),
+			"C:\\Users\\me\\projects\\file-explorer\\src"
+		);
+		assert_eq!(
+			font.compact_path(path, 30 * ADVANCE),
+			"C:\\Users\\...\\file-explorer\\src"
+		);
+		assert_eq!(font.compact_path(path, 10 * ADVANCE), "C:\\...\\src");
+		assert_eq!(font.compact_path(path, 6 * ADVANCE), "C:\\...");
+	}
+}
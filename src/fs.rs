@@ -0,0 +1,190 @@
+//! Enumeration of directories. [DirListing::read] lists the entries of a directory with the
+//! details the views show, in no particular order, and [DirListing::sort_by] puts them in a
+//! [SortOrder], by default folders first, then by name.
+
+use std::cmp::Ordering;
+use std::fs::{DirEntry, Metadata};
+use std::io;
+use std::os::windows::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::profiler::{self, Stage};
+use crate::string;
+
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+	pub directory: bool,
+	/// Has the hidden attribute or, like on Unix, a name starting with a dot.
+	pub hidden: bool,
+	pub system: bool,
+	pub readonly: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortKey {
+	Name,
+	Size,
+	Modified,
+	/// The extension, folders have none.
+	Type,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SortOrder {
+	pub key: SortKey,
+	pub descending: bool,
+	/// Folders before files, whatever the key and direction.
+	pub folders_first: bool,
+}
+
+impl Default for SortOrder {
+	fn default() -> Self {
+		SortOrder {
+			key: SortKey::Name,
+			descending: false,
+			folders_first: true,
+		}
+	}
+}
+
+impl SortOrder {
+	/// Sorts by `key`, or the other way around if already sorted by it.
+	pub fn toggled(self, key: SortKey) -> SortOrder {
+		SortOrder {
+			key,
+			descending: self.key == key && !self.descending,
+			..self
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+	pub name: String,
+	pub path: PathBuf,
+	/// In bytes, 0 for folders.
+	pub size: u64,
+	pub modified: Option<SystemTime>,
+	pub attributes: Attributes,
+}
+
+impl Entry {
+	fn read(entry: &DirEntry) -> io::Result<Entry> {
+		let path = entry.path();
+		let name = entry.file_name().to_string_lossy().into_owned();
+		// Follows symbolic links, unlike the entry's metadata. Broken links are listed as they are.
+		let metadata = match std::fs::metadata(&path) {
+			Ok(metadata) => metadata,
+			Err(_) => entry.metadata()?,
+		};
+		let attributes = Attributes::of(&name, &metadata);
+		Ok(Entry {
+			size: match attributes.directory {
+				true => 0,
+				false => metadata.len(),
+			},
+			modified: metadata.modified().ok(),
+			attributes,
+			name,
+			path,
+		})
+	}
+
+	#[inline]
+	pub fn is_dir(&self) -> bool {
+		self.attributes.directory
+	}
+
+	/// Extension of files, empty for folders and files without one.
+	pub fn extension(&self) -> &str {
+		match self.is_dir() {
+			true => "",
+			false => Path::new(&self.name)
+				.extension()
+				.and_then(|extension| extension.to_str())
+				.unwrap_or(""),
+		}
+	}
+
+	/// Folders first, then by name.
+	pub fn cmp_folders_first(&self, other: &Entry) -> Ordering {
+		self.cmp_by(other, &SortOrder::default())
+	}
+
+	/// Compares by the key of `order`, then by name in natural order, see [string::cmp_natural].
+	pub fn cmp_by(&self, other: &Entry, order: &SortOrder) -> Ordering {
+		let folders = match order.folders_first {
+			true => other.is_dir().cmp(&self.is_dir()),
+			false => Ordering::Equal,
+		};
+		let by_key = match order.key {
+			SortKey::Name => Ordering::Equal,
+			SortKey::Size => self.size.cmp(&other.size),
+			SortKey::Modified => self.modified.cmp(&other.modified),
+			SortKey::Type => string::cmp_ignore_case(self.extension(), other.extension()),
+		}
+		.then_with(|| string::cmp_natural(&self.name, &other.name))
+		// Names that compare equal naturally, e.g. "a1" and "A01", still get a consistent order
+		.then_with(|| self.name.cmp(&other.name));
+		folders.then(match order.descending {
+			true => by_key.reverse(),
+			false => by_key,
+		})
+	}
+}
+
+impl Attributes {
+	fn of(name: &str, metadata: &Metadata) -> Attributes {
+		let attributes = metadata.file_attributes();
+		Attributes {
+			directory: metadata.is_dir(),
+			hidden: name.starts_with('.') || attributes & FILE_ATTRIBUTE_HIDDEN != 0,
+			system: attributes & FILE_ATTRIBUTE_SYSTEM != 0,
+			readonly: attributes & FILE_ATTRIBUTE_READONLY != 0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DirListing {
+	pub dir: PathBuf,
+	pub entries: Vec<Entry>,
+}
+
+impl DirListing {
+	/// Entries of `dir`, without the hidden ones unless `show_hidden`. Entries that vanish while
+	/// listing are skipped.
+	pub fn read(dir: &Path, show_hidden: bool) -> io::Result<DirListing> {
+		let _scope = profiler::scope(Stage::Fs);
+		let mut entries = Vec::new();
+		for entry in std::fs::read_dir(dir)? {
+			let entry = match Entry::read(&entry?) {
+				Ok(entry) => entry,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err),
+			};
+			if show_hidden || !entry.attributes.hidden {
+				entries.push(entry);
+			}
+		}
+		Ok(DirListing {
+			dir: dir.to_path_buf(),
+			entries,
+		})
+	}
+
+	/// Folders first, then by name, see [Entry::cmp_folders_first].
+	pub fn sort(&mut self) {
+		self.sort_by(&SortOrder::default());
+	}
+
+	/// See [Entry::cmp_by].
+	pub fn sort_by(&mut self, order: &SortOrder) {
+		self.entries.sort_by(|a, b| a.cmp_by(b, order));
+	}
+}
@@ -0,0 +1,188 @@
+//! Gamepad input through XInput. The first connected controller is polled every frame and its
+//! buttons are fed to the [Keyboard] as the gamepad virtual keys (e.g. [Key::GamepadA]), so they
+//! repeat when held and go through the same bindings as keys. The sticks and triggers count as
+//! buttons once pushed far enough.
+
+use std::time::{Duration, Instant};
+
+use log::info;
+use windows::Win32::{
+	Foundation::ERROR_SUCCESS,
+	UI::Input::XboxController::{XInputGetState, XINPUT_GAMEPAD, XINPUT_STATE},
+};
+
+use crate::key::Key;
+use crate::window::Keyboard;
+
+/// XInput supports up to 4 controllers.
+const MAX_CONTROLLERS: u32 = 4;
+/// Querying slots without a controller is slow, so they're only checked every once in a while.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stick deflection below which the stick is considered centered, out of 32767.
+const STICK_DEADZONE: i16 = 7849;
+/// Trigger pull below which the trigger is considered released, out of 255.
+const TRIGGER_THRESHOLD: u8 = 30;
+
+/// Bits of `XINPUT_GAMEPAD::wButtons`.
+const BUTTONS: &[(u16, Key)] = &[
+	(0x0001, Key::GamepadDpadUp),
+	(0x0002, Key::GamepadDpadDown),
+	(0x0004, Key::GamepadDpadLeft),
+	(0x0008, Key::GamepadDpadRight),
+	(0x0010, Key::GamepadMenu),
+	(0x0020, Key::GamepadView),
+	(0x0040, Key::GamepadLeftThumbstickButton),
+	(0x0080, Key::GamepadRightThumbstickButton),
+	(0x0100, Key::GamepadLeftShoulder),
+	(0x0200, Key::GamepadRightShoulder),
+	(0x1000, Key::GamepadA),
+	(0x2000, Key::GamepadB),
+	(0x4000, Key::GamepadX),
+	(0x8000, Key::GamepadY),
+];
+
+#[derive(Debug, Default)]
+pub struct Gamepad {
+	/// XInput slot of the controller in use.
+	user_index: Option<u32>,
+	last_probe: Option<Instant>,
+	/// Keys currently held down through the gamepad.
+	held: Vec<Key>,
+	state: XINPUT_GAMEPAD,
+}
+
+impl Gamepad {
+	/// Reads the controller state and presses or releases the corresponding keys of `keyboard`.
+	/// Called every frame, after the window messages are processed.
+	pub fn poll(&mut self, keyboard: &mut Keyboard) {
+		let state = match self.user_index {
+			Some(user_index) => read_state(user_index),
+			None => self.probe(),
+		};
+		let Some(state) = state else {
+			if self.user_index.take().is_some() {
+				info!("Gamepad disconnected");
+			}
+			self.state = XINPUT_GAMEPAD::default();
+			for key in self.held.drain(..) {
+				keyboard.inject(key, false);
+			}
+			return;
+		};
+		self.state = state;
+
+		let down = pressed_keys(&state);
+		for &key in &self.held {
+			if !down.contains(&key) {
+				keyboard.inject(key, false);
+			}
+		}
+		for &key in &down {
+			if !self.held.contains(&key) {
+				keyboard.inject(key, true);
+			}
+		}
+		self.held = down;
+	}
+
+	/// Looks for a connected controller, at most every [PROBE_INTERVAL].
+	fn probe(&mut self) -> Option<XINPUT_GAMEPAD> {
+		let now = Instant::now();
+		if self
+			.last_probe
+			.is_some_and(|last| now - last < PROBE_INTERVAL)
+		{
+			return None;
+		}
+		self.last_probe = Some(now);
+		(0..MAX_CONTROLLERS).find_map(|user_index| {
+			let state = read_state(user_index)?;
+			info!("Gamepad connected in slot {user_index}");
+			self.user_index = Some(user_index);
+			Some(state)
+		})
+	}
+
+	/// Position of the left stick, each axis from -1 to 1 with up being positive. Zero within the
+	/// deadzone.
+	pub fn left_stick(&self) -> (f32, f32) {
+		(axis(self.state.sThumbLX), axis(self.state.sThumbLY))
+	}
+
+	pub fn right_stick(&self) -> (f32, f32) {
+		(axis(self.state.sThumbRX), axis(self.state.sThumbRY))
+	}
+
+	/// How far the triggers are pulled, from 0 to 1.
+	pub fn triggers(&self) -> (f32, f32) {
+		(
+			self.state.bLeftTrigger as f32 / 255.0,
+			self.state.bRightTrigger as f32 / 255.0,
+		)
+	}
+}
+
+fn read_state(user_index: u32) -> Option<XINPUT_GAMEPAD> {
+	let mut state = XINPUT_STATE::default();
+	let result = unsafe { XInputGetState(user_index, &mut state) };
+	(result == ERROR_SUCCESS.0).then_some(state.Gamepad)
+}
+
+fn pressed_keys(state: &XINPUT_GAMEPAD) -> Vec<Key> {
+	let mut keys: Vec<Key> = BUTTONS
+		.iter()
+		.filter(|&&(mask, _)| state.wButtons.0 & mask != 0)
+		.map(|&(_, key)| key)
+		.collect();
+
+	let sticks = [
+		(
+			state.sThumbLX,
+			state.sThumbLY,
+			[
+				Key::GamepadLeftThumbstickLeft,
+				Key::GamepadLeftThumbstickRight,
+				Key::GamepadLeftThumbstickDown,
+				Key::GamepadLeftThumbstickUp,
+			],
+		),
+		(
+			state.sThumbRX,
+			state.sThumbRY,
+			[
+				Key::GamepadRightThumbstickLeft,
+				Key::GamepadRightThumbstickRight,
+				Key::GamepadRightThumbstickDown,
+				Key::GamepadRightThumbstickUp,
+			],
+		),
+	];
+	for (x, y, [left, right, down, up]) in sticks {
+		// Only the dominant direction, so that diagonals don't move in two directions at once
+		if x.unsigned_abs().max(y.unsigned_abs()) <= STICK_DEADZONE as u16 {
+			continue;
+		}
+		keys.push(match (x.unsigned_abs() > y.unsigned_abs(), x > 0, y > 0) {
+			(true, false, _) => left,
+			(true, true, _) => right,
+			(false, _, false) => down,
+			(false, _, true) => up,
+		});
+	}
+
+	if state.bLeftTrigger > TRIGGER_THRESHOLD {
+		keys.push(Key::GamepadLeftTrigger);
+	}
+	if state.bRightTrigger > TRIGGER_THRESHOLD {
+		keys.push(Key::GamepadRightTrigger);
+	}
+	keys
+}
+
+fn axis(value: i16) -> f32 {
+	if value.unsigned_abs() <= STICK_DEADZONE as u16 {
+		return 0.0;
+	}
+	(value as f32 / i16::MAX as f32).clamp(-1.0, 1.0)
+}
@@ -0,0 +1,721 @@
+//! GIF decoder. Decodes every frame of animated GIFs, composed onto the logical screen according
+//! to the frame disposal methods, along with the frame delays.
+
+use std::{fmt::Display, path::PathBuf, time::Duration};
+
+use log::debug;
+
+use crate::cancel::CancelToken;
+use crate::draw::Pos;
+
+const BLOCK_EXTENSION: u8 = 0x21;
+const BLOCK_IMAGE: u8 = 0x2C;
+const BLOCK_TRAILER: u8 = 0x3B;
+const EXTENSION_GRAPHIC_CONTROL: u8 = 0xF9;
+
+/// Clear the frame's area to transparent. Disposal methods other than this one and
+/// [DISPOSE_PREVIOUS] leave the frame on the screen.
+const DISPOSE_BACKGROUND: u8 = 2;
+/// Restore the frame's area to what it was before the frame.
+const DISPOSE_PREVIOUS: u8 = 3;
+
+const MAX_CODE_SIZE: u8 = 12;
+
+/// Images bigger than this many pixels are rejected instead of allocating gigabytes for a
+/// corrupted header.
+const MAX_PIXELS: usize = 1 << 28;
+/// Every frame holds the whole logical screen. Frames past this many pixels in total are
+/// dropped, the animation ends early instead.
+const MAX_FRAME_PIXELS: usize = 1 << 28;
+
+/// Interlaced images store every 8th row starting at 0, then every 8th starting at 4, then every
+/// 4th starting at 2 and finally every 2nd starting at 1. (start row, row step) of each pass.
+const INTERLACE_PASSES: [(usize, usize); 4] = [(0, 8), (4, 8), (2, 4), (1, 2)];
+
+pub struct Gif {
+	width: usize,
+	height: usize,
+	frames: Vec<Frame>,
+	filename: Option<String>,
+}
+
+pub struct Frame {
+	/// The whole logical screen after drawing the frame, 0xAARRGGBB.
+	pixels: Vec<u32>,
+	/// How long the frame is shown.
+	pub delay: Duration,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	InvalidSize,
+	UnknownBlock(u8),
+	NoPalette,
+	InvalidCodeSize(u8),
+	InvalidCode(u16),
+	NoFrames,
+	Cancelled,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not a GIF file."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::InvalidSize => write!(f, "Invalid image size."),
+			Error::UnknownBlock(label) => write!(f, "Unknown block {label:#X}."),
+			Error::NoPalette => write!(f, "Image without a color table."),
+			Error::InvalidCodeSize(size) => write!(f, "Invalid LZW code size {size}."),
+			Error::InvalidCode(code) => write!(f, "Invalid LZW code {code}."),
+			Error::NoFrames => write!(f, "No images in the file."),
+			Error::Cancelled => write!(f, "Decoding was cancelled."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Settings of the next image, from the graphic control extension.
+#[derive(Debug, Default, Clone, Copy)]
+struct GraphicControl {
+	disposal: u8,
+	delay: Duration,
+	transparent: Option<u8>,
+}
+
+/// Image descriptor of a frame.
+#[derive(Debug)]
+struct Descriptor {
+	left: usize,
+	top: usize,
+	width: usize,
+	height: usize,
+	interlaced: bool,
+}
+
+/// Cursor over the file data.
+struct Reader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+		let bytes = self
+			.data
+			.get(self.pos..self.pos + len)
+			.ok_or(Error::FileEnd)?;
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> Result<u8, Error> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, Error> {
+		// GIF uses Little Endian
+		Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+	}
+
+	fn color_table(&mut self, packed: u8) -> Result<Vec<u32>, Error> {
+		let len = 2 << (packed & 0x07);
+		Ok(self
+			.bytes(len * 3)?
+			.chunks_exact(3)
+			.map(|rgb| u32::from_be_bytes([0xFF, rgb[0], rgb[1], rgb[2]]))
+			.collect())
+	}
+
+	/// Concatenates data sub-blocks up to the block terminator.
+	fn sub_blocks(&mut self) -> Result<Vec<u8>, Error> {
+		let mut data = Vec::new();
+		loop {
+			let len = self.u8()? as usize;
+			if len == 0 {
+				return Ok(data);
+			}
+			data.extend_from_slice(self.bytes(len)?);
+		}
+	}
+}
+
+impl Gif {
+	pub fn load_from_path(p: &str) -> Result<Self, Error> {
+		let data = std::fs::read(p).map_err(|err| Error::Io {
+			err,
+			filename: p.into(),
+		})?;
+		let mut gif = Gif::load_from_slice(&data)?;
+		gif.filename = Some(p.to_string());
+		Ok(gif)
+	}
+
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		Gif::load(data, None)
+	}
+
+	/// Like [Gif::load_from_slice], giving up with [Error::Cancelled] between frames once `cancel`
+	/// is cancelled.
+	pub fn load_cancellable(data: &[u8], cancel: &CancelToken) -> Result<Self, Error> {
+		Gif::load(data, Some(cancel))
+	}
+
+	fn load(data: &[u8], cancel: Option<&CancelToken>) -> Result<Self, Error> {
+		let mut reader = Reader { data, pos: 0 };
+		if !matches!(reader.bytes(6), Ok(b"GIF87a" | b"GIF89a")) {
+			return Err(Error::BadMagic);
+		}
+
+		let width = reader.u16()? as usize;
+		let height = reader.u16()? as usize;
+		if width == 0 || height == 0 || width * height > MAX_PIXELS {
+			return Err(Error::InvalidSize);
+		}
+		let packed = reader.u8()?;
+		// Background color index and pixel aspect ratio. Like browsers, we use transparency
+		// as the background.
+		reader.bytes(2)?;
+		let global_palette = if packed & 0x80 != 0 {
+			Some(reader.color_table(packed)?)
+		} else {
+			None
+		};
+
+		let mut canvas = vec![0; width * height];
+		let mut frames = Vec::new();
+		let mut control = GraphicControl::default();
+		loop {
+			match reader.u8()? {
+				BLOCK_EXTENSION => {
+					let label = reader.u8()?;
+					let block = reader.sub_blocks()?;
+					if label == EXTENSION_GRAPHIC_CONTROL && block.len() >= 4 {
+						control = GraphicControl {
+							disposal: (block[0] >> 2) & 0x07,
+							// In hundredths of a second
+							delay: Duration::from_millis(
+								u16::from_le_bytes([block[1], block[2]]) as u64 * 10,
+							),
+							transparent: (block[0] & 0x01 != 0).then_some(block[3]),
+						};
+					} else {
+						debug!("Skipping GIF extension {label:#X} of {} bytes", block.len());
+					}
+				}
+				BLOCK_IMAGE => {
+					if cancel.is_some_and(CancelToken::is_cancelled) {
+						return Err(Error::Cancelled);
+					}
+					let (left, top) = (reader.u16()? as usize, reader.u16()? as usize);
+					let (frame_width, frame_height) =
+						(reader.u16()? as usize, reader.u16()? as usize);
+					let packed = reader.u8()?;
+					let descriptor = Descriptor {
+						left,
+						top,
+						width: frame_width,
+						height: frame_height,
+						interlaced: packed & 0x40 != 0,
+					};
+					if frame_width * frame_height > MAX_PIXELS {
+						return Err(Error::InvalidSize);
+					}
+					let local_palette = if packed & 0x80 != 0 {
+						Some(reader.color_table(packed)?)
+					} else {
+						None
+					};
+					let palette = local_palette
+						.as_ref()
+						.or(global_palette.as_ref())
+						.ok_or(Error::NoPalette)?;
+
+					let min_code_size = reader.u8()?;
+					let indices = lzw_decode(
+						min_code_size,
+						&reader.sub_blocks()?,
+						descriptor.width * descriptor.height,
+					)?;
+
+					let previous = (control.disposal == DISPOSE_PREVIOUS).then(|| canvas.clone());
+					draw_frame(&mut canvas, width, &descriptor, &indices, palette, control);
+					frames.push(Frame {
+						pixels: canvas.clone(),
+						delay: control.delay,
+					});
+					if frames.len() * canvas.len() >= MAX_FRAME_PIXELS {
+						debug!(
+							"Skipping the frames after frame {} of a big GIF",
+							frames.len()
+						);
+						break;
+					}
+					match (control.disposal, previous) {
+						(DISPOSE_PREVIOUS, Some(previous)) => canvas = previous,
+						(DISPOSE_BACKGROUND, _) => clear_area(&mut canvas, width, &descriptor),
+						_ => (),
+					}
+					// A graphic control extension only applies to the image right after it
+					control = GraphicControl::default();
+				}
+				BLOCK_TRAILER => break,
+				block => return Err(Error::UnknownBlock(block)),
+			}
+		}
+
+		if frames.is_empty() {
+			return Err(Error::NoFrames);
+		}
+		Ok(Gif {
+			width,
+			height,
+			frames,
+			filename: None,
+		})
+	}
+
+	/// Converts every frame into a texture, paired with its delay. Still images give a single
+	/// frame.
+	pub fn into_frames(self) -> Vec<(crate::draw::Texture, Duration)> {
+		debug!(
+			"From file \"{filename}\" created {count} textures of {width}x{height}",
+			filename = self.filename.as_deref().unwrap_or("unknown"),
+			count = self.frames.len(),
+			width = self.width,
+			height = self.height,
+		);
+		let (width, height) = (self.width, self.height);
+		self.frames
+			.into_iter()
+			.map(|frame| {
+				let texture = crate::draw::Texture {
+					bitmap: frame.pixels,
+					width,
+					height,
+					pos: Pos { x: 0, y: 0 },
+				};
+				(texture, frame.delay)
+			})
+			.collect()
+	}
+}
+
+/// Draws the palette `indices` of a frame onto the logical screen, skipping transparent pixels
+/// and the parts outside of the screen.
+fn draw_frame(
+	canvas: &mut [u32],
+	canvas_width: usize,
+	descriptor: &Descriptor,
+	indices: &[u8],
+	palette: &[u32],
+	control: GraphicControl,
+) {
+	let canvas_height = canvas.len() / canvas_width.max(1);
+	let rows = (0..descriptor.height).map(|row| row * descriptor.width);
+	let row_order: Vec<usize> = if descriptor.interlaced {
+		INTERLACE_PASSES
+			.iter()
+			.flat_map(|&(start, step)| (start..descriptor.height).step_by(step))
+			.collect()
+	} else {
+		(0..descriptor.height).collect()
+	};
+
+	for (offset, y) in rows.zip(row_order) {
+		let canvas_y = descriptor.top + y;
+		if canvas_y >= canvas_height {
+			continue;
+		}
+		let row = &indices[offset..offset + descriptor.width];
+		for (x, &idx) in row.iter().enumerate() {
+			let canvas_x = descriptor.left + x;
+			if canvas_x >= canvas_width || control.transparent == Some(idx) {
+				continue;
+			}
+			canvas[canvas_y * canvas_width + canvas_x] =
+				palette.get(idx as usize).copied().unwrap_or(0xFF000000);
+		}
+	}
+}
+
+fn clear_area(canvas: &mut [u32], canvas_width: usize, descriptor: &Descriptor) {
+	let canvas_height = canvas.len() / canvas_width.max(1);
+	for y in descriptor.top..(descriptor.top + descriptor.height).min(canvas_height) {
+		let start = y * canvas_width + descriptor.left.min(canvas_width);
+		let end = y * canvas_width + (descriptor.left + descriptor.width).min(canvas_width);
+		canvas[start..end].fill(0);
+	}
+}
+
+/// Decompresses variable code size LZW data into `pixel_count` palette indices. Missing indices
+/// of truncated data are filled with zeros.
+fn lzw_decode(min_code_size: u8, data: &[u8], pixel_count: usize) -> Result<Vec<u8>, Error> {
+	if !(1..MAX_CODE_SIZE).contains(&min_code_size) {
+		return Err(Error::InvalidCodeSize(min_code_size));
+	}
+	let clear = 1u16 << min_code_size;
+	let end = clear + 1;
+
+	// Every code is a previous code (its prefix) followed by one more index
+	let mut prefix = [0u16; 1 << MAX_CODE_SIZE];
+	let mut suffix = [0u8; 1 << MAX_CODE_SIZE];
+	for code in 0..clear {
+		suffix[code as usize] = code as u8;
+	}
+	let mut code_size = min_code_size + 1;
+	let mut next_code = end + 1;
+	let mut previous: Option<u16> = None;
+
+	let mut output = Vec::with_capacity(pixel_count);
+	let mut string = Vec::new();
+	// Writes the indices of `code` to `string`, in reverse
+	let expand = |string: &mut Vec<u8>, prefix: &[u16], suffix: &[u8], mut code: u16| {
+		string.clear();
+		while code > end {
+			string.push(suffix[code as usize]);
+			code = prefix[code as usize];
+		}
+		string.push(suffix[code as usize]);
+	};
+
+	// Codes are packed least significant bit first
+	let (mut bits, mut bit_count) = (0u32, 0u8);
+	let mut bytes = data.iter();
+	while output.len() < pixel_count {
+		while bit_count < code_size {
+			let Some(&byte) = bytes.next() else {
+				output.resize(pixel_count, 0);
+				return Ok(output);
+			};
+			bits |= (byte as u32) << bit_count;
+			bit_count += 8;
+		}
+		let code = (bits & ((1 << code_size) - 1)) as u16;
+		bits >>= code_size;
+		bit_count -= code_size;
+
+		if code == clear {
+			code_size = min_code_size + 1;
+			next_code = end + 1;
+			previous = None;
+			continue;
+		}
+		if code == end {
+			break;
+		}
+
+		let Some(prev) = previous else {
+			if code >= clear {
+				return Err(Error::InvalidCode(code));
+			}
+			output.push(code as u8);
+			previous = Some(code);
+			continue;
+		};
+		let first = if code < next_code {
+			expand(&mut string, &prefix, &suffix, code);
+			*string.last().unwrap()
+		} else if code == next_code {
+			// The code being defined right now: previous string plus its own first index
+			expand(&mut string, &prefix, &suffix, prev);
+			let first = *string.last().unwrap();
+			string.insert(0, first);
+			first
+		} else {
+			return Err(Error::InvalidCode(code));
+		};
+		output.extend(string.iter().rev());
+
+		if (next_code as usize) < prefix.len() {
+			prefix[next_code as usize] = prev;
+			suffix[next_code as usize] = first;
+			next_code += 1;
+			if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+				code_size += 1;
+			}
+		}
+		previous = Some(code);
+	}
+
+	output.resize(pixel_count, 0);
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The well known 1x1 transparent GIF: a white and black global color table, a graphic
+	/// control extension making index 0 transparent and a single pixel of index 0.
+	const TRANSPARENT_PIXEL: [u8; 43] = [
+		0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0xFF, 0xFF,
+		0xFF, 0x00, 0x00, 0x00, 0x21, 0xF9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2C, 0x00, 0x00,
+		0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3B,
+	];
+
+	/// Packs `indices` as LZW codes without compressing them, one code per index. Keeps track of
+	/// the codes the decoder defines to know the code size, and clears them before running out.
+	fn lzw_encode(min_code_size: u8, indices: &[u8]) -> Vec<u8> {
+		let clear = 1u32 << min_code_size;
+		let mut code_size = min_code_size + 1;
+		let mut next_code = clear + 2;
+		let (mut bits, mut bit_count) = (0u32, 0u8);
+		let mut output = Vec::new();
+		let mut emit = |code: u32, code_size: u8| {
+			bits |= code << bit_count;
+			bit_count += code_size;
+			while bit_count >= 8 {
+				output.push(bits as u8);
+				bits >>= 8;
+				bit_count -= 8;
+			}
+		};
+
+		emit(clear, code_size);
+		// The first code after a clear defines nothing, every other code defines one
+		let mut first = true;
+		for &index in indices {
+			emit(index as u32, code_size);
+			if !std::mem::take(&mut first) {
+				next_code += 1;
+				if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+					code_size += 1;
+				}
+			}
+			if next_code >= 4000 {
+				emit(clear, code_size);
+				code_size = min_code_size + 1;
+				next_code = clear + 2;
+				first = true;
+			}
+		}
+		emit(clear + 1, code_size);
+		emit(0, 7);
+		output
+	}
+
+	/// Image block of a frame, with a local color table if `palette` isn't empty.
+	fn image(
+		left: u16,
+		top: u16,
+		width: u16,
+		height: u16,
+		palette: &[u8],
+		indices: &[u8],
+	) -> Vec<u8> {
+		let mut block = vec![BLOCK_IMAGE];
+		for field in [left, top, width, height] {
+			block.extend_from_slice(&field.to_le_bytes());
+		}
+		match palette.len() {
+			0 => block.push(0),
+			len => {
+				// Table sizes are powers of two, at least 2 colors
+				let size_bits = (len / 3).next_power_of_two().trailing_zeros().max(1) - 1;
+				block.push(0x80 | size_bits as u8);
+				block.extend_from_slice(palette);
+			}
+		}
+		block.push(2);
+		for chunk in lzw_encode(2, indices).chunks(255) {
+			block.push(chunk.len() as u8);
+			block.extend_from_slice(chunk);
+		}
+		block.push(0);
+		block
+	}
+
+	fn graphic_control(disposal: u8, delay: u16, transparent: Option<u8>) -> Vec<u8> {
+		let mut block = vec![BLOCK_EXTENSION, EXTENSION_GRAPHIC_CONTROL, 4];
+		block.push(disposal << 2 | transparent.is_some() as u8);
+		block.extend_from_slice(&delay.to_le_bytes());
+		block.extend_from_slice(&[transparent.unwrap_or(0), 0]);
+		block
+	}
+
+	/// GIF file of a `width`x`height` screen with a global color table of red, green, blue and
+	/// white, followed by `blocks`.
+	fn gif_file(width: u16, height: u16, blocks: &[Vec<u8>]) -> Vec<u8> {
+		let mut file = b"GIF89a".to_vec();
+		file.extend_from_slice(&width.to_le_bytes());
+		file.extend_from_slice(&height.to_le_bytes());
+		file.extend_from_slice(&[0x81, 0, 0]);
+		file.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+		for block in blocks {
+			file.extend_from_slice(block);
+		}
+		file.push(BLOCK_TRAILER);
+		file
+	}
+
+	const RED: u32 = 0xFFFF0000;
+	const GREEN: u32 = 0xFF00FF00;
+	const BLUE: u32 = 0xFF0000FF;
+	const WHITE: u32 = 0xFFFFFFFF;
+
+	#[test]
+	fn transparent_pixel() {
+		let gif = Gif::load_from_slice(&TRANSPARENT_PIXEL).unwrap();
+		assert_eq!((gif.width, gif.height), (1, 1));
+		assert_eq!(gif.frames.len(), 1);
+		assert_eq!(gif.frames[0].pixels, [0]);
+	}
+
+	#[test]
+	fn lzw_known_codes() {
+		// Clear, 0, then code 6 before it's fully defined (0 0), then end
+		assert_eq!(lzw_decode(2, &[0x84, 0x0B], 3).unwrap(), [0, 0, 0]);
+		// Data ending early is padded with zeros
+		assert_eq!(lzw_decode(2, &[0x0C], 3).unwrap(), [1, 0, 0]);
+		// Code 7 can't be defined yet
+		assert!(matches!(
+			lzw_decode(2, &[0xC4, 0x01], 3),
+			Err(Error::InvalidCode(7))
+		));
+		assert!(matches!(
+			lzw_decode(0, &[], 1),
+			Err(Error::InvalidCodeSize(0))
+		));
+	}
+
+	#[test]
+	fn lzw_round_trip() {
+		// Enough codes to grow the code size to the maximum and clear the table
+		let indices: Vec<u8> = (0..10_000u32).map(|idx| (idx * 7 % 251) as u8).collect();
+		let decoded = lzw_decode(8, &lzw_encode(8, &indices), indices.len()).unwrap();
+		assert_eq!(decoded, indices);
+	}
+
+	#[test]
+	fn still_image() {
+		let file = gif_file(2, 2, &[image(0, 0, 2, 2, &[], &[0, 1, 2, 3])]);
+		let gif = Gif::load_from_slice(&file).unwrap();
+		assert_eq!(gif.frames.len(), 1);
+		assert_eq!(gif.frames[0].pixels, [RED, GREEN, BLUE, WHITE]);
+	}
+
+	#[test]
+	fn interlaced_rows() {
+		// One pixel per row, its index is the row number. Stored in pass order.
+		let height = 10;
+		let row_order = [0, 8, 4, 2, 6, 1, 3, 5, 7, 9];
+		let indices: Vec<u8> = row_order.iter().map(|&row| row % 4).collect();
+		let mut block = image(0, 0, 1, height, &[], &indices);
+		// Interlace flag in the packed byte of the descriptor
+		block[9] |= 0x40;
+		let gif = Gif::load_from_slice(&gif_file(1, height, &[block])).unwrap();
+		let expected: Vec<u32> = (0..height)
+			.map(|row| [RED, GREEN, BLUE, WHITE][row as usize % 4])
+			.collect();
+		assert_eq!(gif.frames[0].pixels, expected);
+	}
+
+	#[test]
+	fn animation_disposal() {
+		let file = gif_file(
+			2,
+			1,
+			&[
+				graphic_control(0, 10, None),
+				image(0, 0, 2, 1, &[], &[0, 0]),
+				// Drawn over the first frame, then cleared
+				graphic_control(DISPOSE_BACKGROUND, 20, None),
+				image(1, 0, 1, 1, &[], &[1]),
+				// Local color table of black and blue, then restored to before the frame
+				graphic_control(DISPOSE_PREVIOUS, 0, None),
+				image(0, 0, 2, 1, &[0, 0, 0, 0, 0, 255], &[1, 0]),
+				// Transparent pixels leave the screen as it is
+				graphic_control(0, 0, Some(3)),
+				image(0, 0, 2, 1, &[], &[3, 2]),
+			],
+		);
+		let gif = Gif::load_from_slice(&file).unwrap();
+		let frames: Vec<_> = gif.frames.iter().map(|frame| frame.pixels.clone()).collect();
+		assert_eq!(
+			frames,
+			[
+				vec![RED, RED],
+				vec![RED, GREEN],
+				vec![BLUE, 0xFF000000],
+				vec![RED, BLUE]
+			]
+		);
+		let delays: Vec<_> = gif.frames.iter().map(|frame| frame.delay).collect();
+		assert_eq!(
+			delays,
+			[
+				Duration::from_millis(100),
+				Duration::from_millis(200),
+				Duration::ZERO,
+				Duration::ZERO
+			]
+		);
+	}
+
+	#[test]
+	fn frame_outside_screen_is_clipped() {
+		let file = gif_file(1, 1, &[image(0, 0, 2, 2, &[], &[0, 1, 2, 3])]);
+		let gif = Gif::load_from_slice(&file).unwrap();
+		assert_eq!(gif.frames[0].pixels, [RED]);
+	}
+
+	#[test]
+	fn damaged_files() {
+		let file = gif_file(2, 2, &[image(0, 0, 2, 2, &[], &[0, 1, 2, 3])]);
+		// Every cut must fail or decode, never panic
+		for len in 0..file.len() {
+			let _ = Gif::load_from_slice(&file[..len]);
+		}
+		for len in [0, 5, 10, 20] {
+			assert!(Gif::load_from_slice(&file[..len]).is_err(), "{len} bytes");
+		}
+		assert!(matches!(
+			Gif::load_from_slice(&file[..file.len() - 1]),
+			Err(Error::FileEnd)
+		));
+
+		assert!(matches!(
+			Gif::load_from_slice(&gif_file(1, 1, &[])),
+			Err(Error::NoFrames)
+		));
+		assert!(matches!(
+			Gif::load_from_slice(&gif_file(1, 1, &[vec![0x99]])),
+			Err(Error::UnknownBlock(0x99))
+		));
+		assert!(matches!(
+			Gif::load_from_slice(&gif_file(0, 1, &[])),
+			Err(Error::InvalidSize)
+		));
+
+		// Without the global color table
+		let mut file = gif_file(1, 1, &[image(0, 0, 1, 1, &[], &[0])]);
+		file[10] = 0;
+		file.drain(13..25);
+		assert!(matches!(
+			Gif::load_from_slice(&file),
+			Err(Error::NoPalette)
+		));
+	}
+
+	#[test]
+	fn cancelled() {
+		let file = gif_file(1, 1, &[image(0, 0, 1, 1, &[], &[0])]);
+		let cancel = CancelToken::new();
+		assert!(Gif::load_cancellable(&file, &cancel).is_ok());
+		cancel.cancel();
+		assert!(matches!(
+			Gif::load_cancellable(&file, &cancel),
+			Err(Error::Cancelled)
+		));
+	}
+}
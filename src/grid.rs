@@ -0,0 +1,388 @@
+//! Thumbnail grid view. Both drawing and thumbnail loading are virtualized: only rows intersecting
+//! the viewport are drawn, and only their thumbnails are requested from the [ThumbnailSource].
+//! Thumbnails of rows that scroll away are cancelled.
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::animation::{Easing, Tween};
+use crate::arena::FrameArena;
+use crate::draw::Texture;
+use crate::draw::{draw_rectangle, draw_text, draw_texture_clipped, Rect};
+use crate::font;
+use crate::icons;
+use crate::key::Key;
+use crate::rubber_band::RubberBand;
+use crate::selection::{Selection, SelectionMode};
+use crate::thumbnails::ThumbnailSource;
+use crate::window::{BitmapData, Keyboard, Mouse};
+
+const CELL_PADDING: usize = 8;
+/// Rows above and below the viewport whose thumbnails are requested ahead of time.
+const PREFETCH_ROWS: usize = 1;
+const SCROLL_DURATION: Duration = Duration::from_millis(150);
+/// How fast the view scrolls while rubber-band selecting past its edge, in pixels per frame.
+const DRAG_SCROLL_SPEED: usize = 16;
+
+const SELECTED_COLOR: u32 = 0x504945;
+const PLACEHOLDER_COLOR: u32 = 0x3c3836;
+const TEXT_COLOR: u32 = 0xebdbb2;
+
+#[derive(Debug)]
+pub struct ThumbnailGrid {
+	thumbnail_size: usize,
+	item_count: usize,
+	/// Vertical scroll offset in pixels.
+	scroll: usize,
+	/// Currently displayed scroll offset, catching up with `scroll`.
+	scroll_position: Tween,
+	selection: Selection,
+	rubber_band: Option<RubberBand>,
+	/// Items whose thumbnails were requested and not cancelled yet.
+	requested: HashSet<usize>,
+	/// Icon shown in place of thumbnails that are still loading.
+	placeholder: Texture,
+}
+
+impl ThumbnailGrid {
+	pub fn new(thumbnail_size: usize) -> Self {
+		ThumbnailGrid {
+			thumbnail_size,
+			item_count: 0,
+			scroll: 0,
+			scroll_position: Tween::new(0.0),
+			selection: Selection::default(),
+			rubber_band: None,
+			requested: HashSet::new(),
+			placeholder: icons::IMAGE.rasterize(thumbnail_size / 2),
+		}
+	}
+
+	pub fn thumbnail_size(&self) -> usize {
+		self.thumbnail_size
+	}
+
+	/// Resets the grid for a new set of items.
+	pub fn set_item_count(&mut self, item_count: usize) {
+		self.item_count = item_count;
+		self.scroll = 0;
+		self.scroll_position.set(0.0);
+		self.selection.clear();
+		self.rubber_band = None;
+		self.requested.clear();
+	}
+
+	pub fn selection(&self) -> &Selection {
+		&self.selection
+	}
+
+	/// Selects just `idx` and scrolls it into view.
+	pub fn select_only(&mut self, idx: usize, viewport: Rect) {
+		if idx >= self.item_count {
+			return;
+		}
+		self.selection.select_only(idx);
+		self.scroll_to(idx, self.columns(viewport.width), viewport.height);
+	}
+
+	/// Scrolls the selection's cursor into view, e.g. after the viewport changed size.
+	pub fn reveal_cursor(&mut self, viewport: Rect) {
+		if let Some(cursor) = self.selection.cursor() {
+			self.scroll_to(cursor, self.columns(viewport.width), viewport.height);
+		}
+	}
+
+	/// Window coordinates of the visible cells, clipped to the viewport.
+	pub fn visible_cells<'a>(&self, viewport: Rect, arena: &'a FrameArena) -> &'a [(usize, Rect)] {
+		let columns = self.columns(viewport.width);
+		let scroll = self.displayed_scroll();
+		let cells = self
+			.visible_items(viewport.height, columns, 0)
+			.filter_map(|idx| {
+				let cell = self.cell_rect(idx, columns);
+				let top = (viewport.y + cell.y).saturating_sub(scroll).max(viewport.y);
+				let bottom = (viewport.y + cell.y + cell.height)
+					.saturating_sub(scroll)
+					.min(viewport.y + viewport.height);
+				let x = viewport.x + cell.x;
+				(top < bottom).then(|| (idx, Rect::new(x, top, cell.width, bottom - top)))
+			});
+		arena.alloc_iter(cells)
+	}
+
+	/// Handles input of the current frame and requests/cancels thumbnails based on what's
+	/// visible after scrolling. Returns true if the selection changed.
+	pub fn update(
+		&mut self,
+		viewport: Rect,
+		mouse: &Mouse,
+		keyboard: &Keyboard,
+		source: &mut impl ThumbnailSource,
+		dt: Duration,
+	) -> bool {
+		let columns = self.columns(viewport.width);
+		let selection_before = self.selection.clone();
+
+		if self.item_count > 0 {
+			self.handle_keyboard(keyboard, columns, viewport.height);
+		}
+
+		if keyboard.was_pressed(Key::Prior) {
+			self.scroll = self.scroll.saturating_sub(viewport.height);
+		}
+		if keyboard.was_pressed(Key::Next) {
+			self.scroll += viewport.height;
+		}
+		if viewport.contains(mouse.position()) {
+			let (_, scroll_y) = mouse.scroll_pixels();
+			self.scroll = self.scroll.saturating_add_signed(scroll_y as isize);
+		}
+
+		self.handle_mouse(mouse, keyboard, viewport, columns);
+
+		let max_scroll = self.content_height(columns).saturating_sub(viewport.height);
+		self.scroll = self.scroll.min(max_scroll);
+		self.scroll_position
+			.animate_to(self.scroll as f32, SCROLL_DURATION, Easing::EaseOutCubic);
+		self.scroll_position.update(dt);
+
+		let wanted = self.visible_items(viewport.height, columns, PREFETCH_ROWS);
+		self.requested.retain(|&idx| {
+			let keep = wanted.contains(&idx);
+			if !keep {
+				source.cancel(idx);
+			}
+			keep
+		});
+		for idx in wanted {
+			if self.requested.insert(idx) {
+				source.request(idx);
+			}
+		}
+		source.poll();
+
+		self.selection != selection_before
+	}
+
+	fn handle_keyboard(&mut self, keyboard: &Keyboard, columns: usize, height: usize) {
+		let last = self.item_count - 1;
+		let cursor = self.selection.cursor();
+		let mut target = None;
+		let (up, down) = (-(columns as isize), columns as isize);
+		// Held Up and Down jump by as many rows as fit in the viewport, Left and Right by a row
+		let page = height / self.cell_height();
+		let moves = [
+			(Key::Left, -1, 1),
+			(Key::Right, 1, 1),
+			(Key::Up, up, page),
+			(Key::Down, down, page),
+			(Key::GamepadDpadLeft, -1, 1),
+			(Key::GamepadDpadRight, 1, 1),
+			(Key::GamepadDpadUp, up, page),
+			(Key::GamepadDpadDown, down, page),
+			(Key::GamepadLeftThumbstickLeft, -1, 1),
+			(Key::GamepadLeftThumbstickRight, 1, 1),
+			(Key::GamepadLeftThumbstickUp, up, page),
+			(Key::GamepadLeftThumbstickDown, down, page),
+		];
+		for (key, delta, page) in moves {
+			let steps = keyboard.steps(key, page);
+			if steps > 0 {
+				let current = target.or(cursor).unwrap_or(0) as isize;
+				target = Some((current + delta * steps as isize).clamp(0, last as isize) as usize);
+			}
+		}
+		if keyboard.was_pressed(Key::Home) {
+			target = Some(0);
+		}
+		if keyboard.was_pressed(Key::End) {
+			target = Some(last);
+		}
+
+		if let Some(target) = target {
+			if keyboard.is_pressed(Key::Shift) {
+				self.selection.select_range_to(target);
+			} else {
+				self.selection.select_only(target);
+			}
+			self.scroll_to(target, columns, height);
+		}
+
+		if keyboard.was_pressed(Key::A) && keyboard.is_pressed(Key::Control) {
+			self.selection.select_all(self.item_count);
+		}
+	}
+
+	/// Clicks select single items, dragging selects everything touched by the rubber band.
+	fn handle_mouse(&mut self, mouse: &Mouse, keyboard: &Keyboard, viewport: Rect, columns: usize) {
+		let (mouse_x, mouse_y) = mouse.position();
+		let content_pos = (
+			(mouse_x - viewport.x as i32).clamp(0, viewport.width as i32) as usize,
+			(mouse_y - viewport.y as i32).clamp(0, viewport.height as i32) as usize
+				+ self.displayed_scroll(),
+		);
+
+		if mouse.was_left_pressed() && viewport.contains(mouse.position()) {
+			let mode = SelectionMode::from_modifiers(keyboard);
+			self.rubber_band = Some(RubberBand::begin(content_pos, self.selection.clone(), mode));
+		}
+
+		let Some(band) = &mut self.rubber_band else {
+			return;
+		};
+		band.drag_to(content_pos);
+
+		if band.is_active() {
+			// Scroll when dragging past the top or bottom edge
+			if mouse_y < viewport.y as i32 {
+				self.scroll = self.scroll.saturating_sub(DRAG_SCROLL_SPEED);
+			} else if mouse_y >= (viewport.y + viewport.height) as i32 {
+				self.scroll += DRAG_SCROLL_SPEED;
+			}
+
+			let band = self.rubber_band.as_ref().unwrap();
+			let hits = self.items_in(band.rect(), columns);
+			let mut selection = Selection::default();
+			selection.combine(band.base(), hits.into_iter(), band.mode());
+			self.selection = selection;
+		}
+
+		if !mouse.is_left_down() {
+			let band = self.rubber_band.take().unwrap();
+			if !band.is_active() {
+				match self.item_at(band.start(), columns) {
+					Some(idx) => self.selection.click(idx, band.mode()),
+					None if band.mode() == SelectionMode::Replace => self.selection.clear(),
+					None => (),
+				}
+			}
+		}
+	}
+
+	/// Draws visible cells. `label` returns the caption of an item.
+	pub fn render<'a>(
+		&self,
+		bitmap_data: BitmapData,
+		viewport: Rect,
+		source: &impl ThumbnailSource,
+		label: impl Fn(usize) -> &'a str,
+	) {
+		let columns = self.columns(viewport.width);
+		let cell_width = self.cell_width();
+		let cell_height = self.cell_height();
+
+		for idx in self.visible_items(viewport.height, columns, 0) {
+			let column = idx % columns;
+			let row = idx / columns;
+			let cell_x = (viewport.x + column * cell_width) as isize;
+			let cell_y =
+				(viewport.y + row * cell_height) as isize - self.displayed_scroll() as isize;
+
+			if self.selection.is_selected(idx) && cell_y >= viewport.y as isize {
+				draw_rectangle(
+					bitmap_data,
+					(cell_x as usize, cell_y as usize),
+					(cell_width, cell_height),
+					SELECTED_COLOR,
+				);
+			}
+
+			let thumb_x = cell_x + CELL_PADDING as isize;
+			let thumb_y = cell_y + CELL_PADDING as isize;
+			let thumbnail = source.get(idx);
+			if thumbnail.is_none() && thumb_y >= viewport.y as isize {
+				draw_rectangle(
+					bitmap_data,
+					(thumb_x as usize, thumb_y as usize),
+					(self.thumbnail_size, self.thumbnail_size),
+					PLACEHOLDER_COLOR,
+				);
+			}
+			// Center the thumbnail (or the placeholder icon) in its square
+			let texture = thumbnail.unwrap_or(&self.placeholder);
+			let x = thumb_x + (self.thumbnail_size - texture.width) as isize / 2;
+			let y = thumb_y + (self.thumbnail_size - texture.height) as isize / 2;
+			draw_texture_clipped(bitmap_data, texture, (x, y), viewport);
+
+			let label_y = thumb_y + (self.thumbnail_size + CELL_PADDING) as isize;
+			if label_y >= viewport.y as isize {
+				let text = font::ui().truncate(label(idx), self.thumbnail_size);
+				draw_text(
+					bitmap_data,
+					font::ui(),
+					&text,
+					(thumb_x as usize, label_y as usize),
+					TEXT_COLOR,
+				);
+			}
+		}
+
+		if let Some(band) = &self.rubber_band {
+			band.render(bitmap_data, viewport, self.displayed_scroll());
+		}
+	}
+
+	fn displayed_scroll(&self) -> usize {
+		self.scroll_position.value().round() as usize
+	}
+
+	/// Cell rectangle of item `idx`, in content coordinates.
+	fn cell_rect(&self, idx: usize, columns: usize) -> Rect {
+		Rect::new(
+			idx % columns * self.cell_width(),
+			idx / columns * self.cell_height(),
+			self.cell_width(),
+			self.cell_height(),
+		)
+	}
+
+	/// Items whose cells intersect `rect` (in content coordinates).
+	fn items_in(&self, rect: Rect, columns: usize) -> Vec<usize> {
+		let first_row = rect.y / self.cell_height();
+		let last_row = (rect.y + rect.height) / self.cell_height();
+		(first_row * columns..((last_row + 1) * columns).min(self.item_count))
+			.filter(|&idx| self.cell_rect(idx, columns).intersects(&rect))
+			.collect()
+	}
+
+	/// Item under the given point (in content coordinates).
+	fn item_at(&self, (x, y): (usize, usize), columns: usize) -> Option<usize> {
+		let point = Rect::new(x, y, 1, 1);
+		self.items_in(point, columns).first().copied()
+	}
+
+	fn cell_width(&self) -> usize {
+		self.thumbnail_size + 2 * CELL_PADDING
+	}
+
+	fn cell_height(&self) -> usize {
+		self.thumbnail_size + font::LINE_HEIGHT + 3 * CELL_PADDING
+	}
+
+	fn columns(&self, width: usize) -> usize {
+		(width / self.cell_width()).max(1)
+	}
+
+	fn content_height(&self, columns: usize) -> usize {
+		self.item_count.div_ceil(columns) * self.cell_height()
+	}
+
+	/// Items in rows intersecting the viewport, extended by `extra_rows` in both directions.
+	fn visible_items(&self, height: usize, columns: usize, extra_rows: usize) -> Range<usize> {
+		let scroll = self.displayed_scroll();
+		let first_row = (scroll / self.cell_height()).saturating_sub(extra_rows);
+		let last_row = (scroll + height).div_ceil(self.cell_height()) + extra_rows;
+		(first_row * columns).min(self.item_count)..(last_row * columns).min(self.item_count)
+	}
+
+	fn scroll_to(&mut self, idx: usize, columns: usize, height: usize) {
+		let row_top = idx / columns * self.cell_height();
+		let row_bottom = row_top + self.cell_height();
+		if row_top < self.scroll {
+			self.scroll = row_top;
+		} else if row_bottom > self.scroll + height {
+			self.scroll = row_bottom.saturating_sub(height);
+		}
+	}
+}
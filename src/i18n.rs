@@ -0,0 +1,242 @@
+//! Localized UI text. Messages are looked up by key in the catalog of the user's language, loaded
+//! from `assets/lang/<language>.txt`, with the built-in English catalog filling in what's missing:
+//!
+//! ```text
+//! # {0}, {1}, ... are replaced by the arguments
+//! refreshed = Refreshed {0}
+//! # Messages taking a count have a form for every plural category of the language
+//! items.one = {0} item
+//! items.other = {0} items
+//! ```
+//!
+//! [tr!] formats a message, e.g. `tr!("items", count)`. The first count among the arguments picks
+//! the plural form.
+
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use log::{debug, warn};
+use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
+use crate::assets;
+
+const ENGLISH: &str = "lang/en.txt";
+
+/// Formats the message `key` with the given arguments, see [translate].
+macro_rules! tr {
+	($key:expr $(, $arg:expr)* $(,)?) => {
+		$crate::i18n::translate($key, &[$($crate::i18n::Arg::from($arg)),*])
+	};
+}
+pub(crate) use tr;
+
+/// Argument of a message. Counts choose the plural form of the message.
+#[derive(Debug, Clone)]
+pub enum Arg {
+	Count(u64),
+	Text(String),
+}
+
+#[derive(Debug)]
+struct Catalog {
+	/// Language code, e.g. "en" or "pl".
+	language: String,
+	messages: HashMap<String, String>,
+}
+
+/// Plural categories of the Unicode CLDR. A language uses some of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Plural {
+	One,
+	Few,
+	Many,
+	Other,
+}
+
+static CATALOGS: OnceLock<(Catalog, Catalog)> = OnceLock::new();
+
+/// The message `key` in the user's language with `{n}` replaced by the `n`th argument. Falls back
+/// to English, and to the key itself if there's no such message at all.
+pub fn translate(key: &str, args: &[Arg]) -> String {
+	let (user, english) = CATALOGS.get_or_init(|| {
+		let english = Catalog::parse("en", assets::embedded_text(ENGLISH).unwrap());
+		(Catalog::load(&user_language()), english)
+	});
+	let count = args.iter().find_map(|arg| match arg {
+		Arg::Count(count) => Some(*count),
+		Arg::Text(_) => None,
+	});
+	let Some(message) = user
+		.message(key, count)
+		.or_else(|| english.message(key, count))
+	else {
+		warn!("Missing message {key}");
+		return key.to_string();
+	};
+	format(message, args)
+}
+
+impl Catalog {
+	/// An empty catalog if there's no file for `language`, so that English is used throughout.
+	fn load(language: &str) -> Catalog {
+		let path = assets::path(&format!("lang/{language}.txt"));
+		match std::fs::read_to_string(&path) {
+			Ok(text) => {
+				debug!("Loading messages from {}", path.display());
+				Catalog::parse(language, &text)
+			}
+			Err(err) => {
+				if language != "en" {
+					warn!("No messages for language {language}: {err}");
+				}
+				Catalog::parse(language, "")
+			}
+		}
+	}
+
+	fn parse(language: &str, text: &str) -> Catalog {
+		let mut messages = HashMap::new();
+		for (idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			match line.split_once('=') {
+				Some((key, message)) => {
+					let message = message.trim().replace("\\n", "\n");
+					messages.insert(key.trim().to_string(), message);
+				}
+				None => warn!(
+					"{language}.txt, line {}: expected \"key = message\"",
+					idx + 1
+				),
+			}
+		}
+		Catalog {
+			language: language.to_string(),
+			messages,
+		}
+	}
+
+	/// The form of message `key` for `count`, or the message without plural forms.
+	fn message(&self, key: &str, count: Option<u64>) -> Option<&str> {
+		let plural_form = |plural: Plural| {
+			let category = match plural {
+				Plural::One => "one",
+				Plural::Few => "few",
+				Plural::Many => "many",
+				Plural::Other => "other",
+			};
+			self.messages.get(&format!("{key}.{category}"))
+		};
+		count
+			.and_then(|count| plural_form(plural(&self.language, count)))
+			.or_else(|| count.and_then(|_| plural_form(Plural::Other)))
+			.or_else(|| self.messages.get(key))
+			.map(String::as_str)
+	}
+}
+
+/// Plural category of `count` in `language`, after the CLDR plural rules for integers.
+fn plural(language: &str, count: u64) -> Plural {
+	let (mod10, mod100) = (count % 10, count % 100);
+	match language {
+		"ja" | "ko" | "zh" | "vi" | "th" | "id" => Plural::Other,
+		"fr" | "pt" if count <= 1 => Plural::One,
+		"pl" if count == 1 => Plural::One,
+		"pl" | "ru" | "uk" if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) => {
+			Plural::Few
+		}
+		"pl" => Plural::Many,
+		"ru" | "uk" if mod10 == 1 && mod100 != 11 => Plural::One,
+		"ru" | "uk" => Plural::Many,
+		"cs" | "sk" if count == 1 => Plural::One,
+		"cs" | "sk" if (2..=4).contains(&count) => Plural::Few,
+		_ if count == 1 => Plural::One,
+		_ => Plural::Other,
+	}
+}
+
+/// Replaces `{n}` in `message` with the `n`th argument. Placeholders without an argument are kept.
+fn format(message: &str, args: &[Arg]) -> String {
+	let mut formatted = String::with_capacity(message.len());
+	let mut rest = message;
+	while let Some(start) = rest.find('{') {
+		formatted.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let arg = rest
+			.find('}')
+			.and_then(|end| Some((end, rest[1..end].parse::<usize>().ok()?)))
+			.and_then(|(end, idx)| Some((end, args.get(idx)?)));
+		match arg {
+			Some((end, arg)) => {
+				formatted.push_str(&arg.to_string());
+				rest = &rest[end + 1..];
+			}
+			None => {
+				formatted.push('{');
+				rest = &rest[1..];
+			}
+		}
+	}
+	formatted.push_str(rest);
+	formatted
+}
+
+/// Language of the user's locale, e.g. "pl" for "pl-PL". FILE_EXPLORER_LANG overrides it.
+fn user_language() -> String {
+	if let Ok(language) = std::env::var("FILE_EXPLORER_LANG") {
+		return language;
+	}
+	let mut buf = [0_u16; 85];
+	let len = unsafe { GetUserDefaultLocaleName(&mut buf) };
+	if len == 0 {
+		return "en".to_string();
+	}
+	let locale = crate::string::from_utf16_null_terminated(&buf);
+	locale.split('-').next().unwrap_or("en").to_lowercase()
+}
+
+impl Display for Arg {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Arg::Count(count) => write!(f, "{count}"),
+			Arg::Text(text) => write!(f, "{text}"),
+		}
+	}
+}
+
+impl From<usize> for Arg {
+	fn from(count: usize) -> Self {
+		Arg::Count(count as u64)
+	}
+}
+
+impl From<u64> for Arg {
+	fn from(count: u64) -> Self {
+		Arg::Count(count)
+	}
+}
+
+impl From<u32> for Arg {
+	fn from(count: u32) -> Self {
+		Arg::Count(count as u64)
+	}
+}
+
+impl From<String> for Arg {
+	fn from(text: String) -> Self {
+		Arg::Text(text)
+	}
+}
+
+impl From<&str> for Arg {
+	fn from(text: &str) -> Self {
+		Arg::Text(text.to_string())
+	}
+}
+
+impl From<&String> for Arg {
+	fn from(text: &String) -> Self {
+		Arg::Text(text.clone())
+	}
+}
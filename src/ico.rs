@@ -0,0 +1,297 @@
+//! ICO and CUR decoder. Both are containers of several images of the same icon at different sizes
+//! and bit depths, each stored either as a PNG file or as a BMP style bitmap.
+
+use std::{fmt::Display, path::PathBuf};
+
+use log::debug;
+
+use crate::bmp::{self, Bmp};
+use crate::draw::Texture;
+use crate::png::{self, Png, PNG_SIGNATURE};
+
+const TYPE_ICON: u16 = 1;
+const TYPE_CURSOR: u16 = 2;
+
+const HEADER_SIZE: usize = 6;
+const ENTRY_SIZE: usize = 16;
+
+pub struct Ico {
+	entries: Vec<Entry>,
+	data: Vec<u8>,
+	filename: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+	pub width: usize,
+	pub height: usize,
+	/// 0 if unknown, cursors don't store it.
+	pub bits_per_pixel: u16,
+	/// Position of the cursor's click point within the image. Only cursors have one.
+	pub hotspot: Option<(u16, u16)>,
+	offset: usize,
+	size: usize,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	NoImages,
+	Png(png::Error),
+	Bmp(bmp::Error),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not an ICO or CUR file."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::NoImages => write!(f, "No images in the file."),
+			Error::Png(err) => write!(f, "Invalid PNG image: {err}"),
+			Error::Bmp(err) => write!(f, "Invalid bitmap image: {err}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Ico {
+	pub fn load_from_path(p: &str) -> Result<Self, Error> {
+		let data = std::fs::read(p).map_err(|err| Error::Io {
+			err,
+			filename: p.into(),
+		})?;
+		let mut ico = Ico::load_from_vec(data)?;
+		ico.filename = Some(p.to_string());
+		Ok(ico)
+	}
+
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		Ico::load_from_vec(data.to_vec())
+	}
+
+	/// Only the directory of images is parsed, the images are decoded on demand.
+	fn load_from_vec(data: Vec<u8>) -> Result<Self, Error> {
+		let read_u16 = |offset: usize| -> Result<u16, Error> {
+			let bytes = data.get(offset..offset + 2).ok_or(Error::FileEnd)?;
+			// ICO uses Little Endian
+			Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+		};
+		let read_u32 = |offset: usize| -> Result<u32, Error> {
+			let bytes = data.get(offset..offset + 4).ok_or(Error::FileEnd)?;
+			Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+		};
+
+		let kind = read_u16(2)?;
+		if read_u16(0)? != 0 || (kind != TYPE_ICON && kind != TYPE_CURSOR) {
+			return Err(Error::BadMagic);
+		}
+		let count = read_u16(4)? as usize;
+
+		let mut entries = Vec::with_capacity(count);
+		for idx in 0..count {
+			let at = HEADER_SIZE + idx * ENTRY_SIZE;
+			let dimension = |byte: &u8| if *byte == 0 { 256 } else { *byte as usize };
+			let size = data.get(at..at + 2).ok_or(Error::FileEnd)?;
+			// Icons store the color planes and bit depth where cursors store the hotspot
+			let (first, second) = (read_u16(at + 4)?, read_u16(at + 6)?);
+			let (bits_per_pixel, hotspot) = match kind {
+				TYPE_ICON => (second, None),
+				_ => (0, Some((first, second))),
+			};
+			entries.push(Entry {
+				width: dimension(&size[0]),
+				height: dimension(&size[1]),
+				bits_per_pixel,
+				hotspot,
+				size: read_u32(at + 8)? as usize,
+				offset: read_u32(at + 12)? as usize,
+			});
+		}
+		debug!("Icon entries: {entries:#?}");
+
+		if entries.is_empty() {
+			return Err(Error::NoImages);
+		}
+		Ok(Ico {
+			entries,
+			data,
+			filename: None,
+		})
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	/// Picks the image best suited for drawing at `size`x`size`: the smallest one at least that
+	/// big, with the highest bit depth among those of the same size. Falls back to the biggest.
+	pub fn best_entry(&self, size: usize) -> &Entry {
+		let by_quality = |entry: &&Entry| (entry.width.max(entry.height), entry.bits_per_pixel);
+		self.entries
+			.iter()
+			.filter(|entry| entry.width.max(entry.height) >= size)
+			.min_by_key(|entry| {
+				let (dimension, bits_per_pixel) = by_quality(entry);
+				(dimension, std::cmp::Reverse(bits_per_pixel))
+			})
+			.or_else(|| self.entries.iter().max_by_key(by_quality))
+			.expect("icons have at least one entry")
+	}
+
+	pub fn texture(&self, entry: &Entry) -> Result<Texture, Error> {
+		let data = self
+			.data
+			.get(entry.offset..entry.offset + entry.size)
+			.ok_or(Error::FileEnd)?;
+		let texture = if data.starts_with(&PNG_SIGNATURE) {
+			Texture::from(Png::load_from_slice(data).map_err(Error::Png)?)
+		} else {
+			Texture::from(Bmp::load_from_icon(data).map_err(Error::Bmp)?)
+		};
+		debug!(
+			"Decoded {}x{} icon from \"{filename}\"",
+			texture.width,
+			texture.height,
+			filename = self.filename.as_deref().unwrap_or("unknown")
+		);
+		Ok(texture)
+	}
+
+	/// Texture of the [best entry](Ico::best_entry) for `size`.
+	pub fn best_texture(&self, size: usize) -> Result<Texture, Error> {
+		self.texture(self.best_entry(size))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// 1x1 32 bit bitmap of `pixel`, with an empty mask.
+	fn bitmap(pixel: u32) -> Vec<u8> {
+		let mut dib = Vec::new();
+		dib.extend_from_slice(&40u32.to_le_bytes());
+		// The height covers the color bitmap and the mask
+		dib.extend_from_slice(&1i32.to_le_bytes());
+		dib.extend_from_slice(&2i32.to_le_bytes());
+		dib.extend_from_slice(&1u16.to_le_bytes());
+		dib.extend_from_slice(&32u16.to_le_bytes());
+		dib.resize(40, 0);
+		dib.extend_from_slice(&pixel.to_le_bytes());
+		dib.extend_from_slice(&[0; 4]);
+		dib
+	}
+
+	/// ICO or CUR file of `images`, each with its size, planes or hotspot x and bit depth or
+	/// hotspot y.
+	fn ico_file(kind: u16, images: &[(u8, u16, u16, Vec<u8>)]) -> Vec<u8> {
+		let mut file = Vec::new();
+		for field in [0, kind, images.len() as u16] {
+			file.extend_from_slice(&field.to_le_bytes());
+		}
+		let mut offset = HEADER_SIZE + images.len() * ENTRY_SIZE;
+		for (size, first, second, data) in images {
+			file.extend_from_slice(&[*size, *size, 0, 0]);
+			file.extend_from_slice(&first.to_le_bytes());
+			file.extend_from_slice(&second.to_le_bytes());
+			file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+			file.extend_from_slice(&(offset as u32).to_le_bytes());
+			offset += data.len();
+		}
+		for (_, _, _, data) in images {
+			file.extend_from_slice(data);
+		}
+		file
+	}
+
+	#[test]
+	fn bitmap_and_png_entries() {
+		let png = Png::encode(2, 2, &[0xFF112233; 4]);
+		let file = ico_file(
+			TYPE_ICON,
+			&[
+				(1, 1, 32, bitmap(0x80445566)),
+				(2, 1, 32, png),
+				// 256 pixels is stored as 0
+				(0, 1, 8, Vec::new()),
+			],
+		);
+		let ico = Ico::load_from_slice(&file).unwrap();
+		let sizes: Vec<_> = ico
+			.entries()
+			.iter()
+			.map(|entry| (entry.width, entry.height, entry.bits_per_pixel, entry.hotspot))
+			.collect();
+		assert_eq!(
+			sizes,
+			[(1, 1, 32, None), (2, 2, 32, None), (256, 256, 8, None)]
+		);
+
+		let texture = ico.best_texture(1).unwrap();
+		assert_eq!((texture.width(), texture.height()), (1, 1));
+		assert_eq!(texture.pixels(), [0x80445566]);
+		let texture = ico.best_texture(2).unwrap();
+		assert_eq!((texture.width(), texture.height()), (2, 2));
+		assert_eq!(texture.pixels(), [0xFF112233; 4]);
+		// The empty 256 pixel entry fails to decode instead of panicking
+		assert!(ico.best_texture(300).is_err());
+	}
+
+	#[test]
+	fn best_entry() {
+		let file = ico_file(
+			TYPE_ICON,
+			&[(16, 1, 8, Vec::new()), (16, 1, 32, Vec::new()), (32, 1, 32, Vec::new())],
+		);
+		let ico = Ico::load_from_slice(&file).unwrap();
+		let best = |size| {
+			let entry = ico.best_entry(size);
+			(entry.width, entry.bits_per_pixel)
+		};
+		assert_eq!(best(8), (16, 32));
+		assert_eq!(best(16), (16, 32));
+		assert_eq!(best(17), (32, 32));
+		assert_eq!(best(64), (32, 32));
+	}
+
+	#[test]
+	fn cursor_hotspot() {
+		let file = ico_file(TYPE_CURSOR, &[(1, 3, 7, bitmap(0xFF000000))]);
+		let ico = Ico::load_from_slice(&file).unwrap();
+		assert_eq!(ico.entries()[0].hotspot, Some((3, 7)));
+		assert_eq!(ico.entries()[0].bits_per_pixel, 0);
+		assert!(ico.best_texture(1).is_ok());
+	}
+
+	#[test]
+	fn damaged_files() {
+		let file = ico_file(TYPE_ICON, &[(1, 1, 32, bitmap(0xFF000000))]);
+		for len in [0, 2, HEADER_SIZE, HEADER_SIZE + ENTRY_SIZE - 1] {
+			assert!(
+				matches!(Ico::load_from_slice(&file[..len]), Err(Error::FileEnd)),
+				"{len} bytes"
+			);
+		}
+		// Images are only read once they're drawn
+		let ico = Ico::load_from_slice(&file[..file.len() - 1]).unwrap();
+		assert!(matches!(ico.best_texture(1), Err(Error::FileEnd)));
+
+		assert!(matches!(
+			Ico::load_from_slice(&ico_file(TYPE_ICON, &[])),
+			Err(Error::NoImages)
+		));
+		assert!(matches!(
+			Ico::load_from_slice(&ico_file(3, &[])),
+			Err(Error::BadMagic)
+		));
+	}
+}
@@ -0,0 +1,171 @@
+//! Built-in icons stored as vector paths, rasterized at whatever size they're needed at.
+//!
+//! Every icon is drawn on a [GRID_SIZE]x[GRID_SIZE] grid and consists of layers, each a solid color
+//! and a path in the syntax accepted by [Path::parse]. Layers are filled in order, later ones on
+//! top.
+
+use crate::draw::Texture;
+use crate::vector::Path;
+
+/// Size of the grid icon coordinates are specified on.
+pub const GRID_SIZE: f32 = 24.0;
+
+const FOREGROUND: u32 = 0xebdbb2;
+const DARK: u32 = 0x3c3836;
+const FOLDER_COLOR: u32 = 0xfabd2f;
+const PAGE_COLOR: u32 = 0xd5c4a1;
+const PAGE_FOLD_COLOR: u32 = 0xa89984;
+const SKY_COLOR: u32 = 0x83a598;
+const HILL_COLOR: u32 = 0x8ec07c;
+
+#[derive(Debug)]
+pub struct Icon {
+	pub name: &'static str,
+	pub layers: &'static [Layer],
+}
+
+#[derive(Debug)]
+pub struct Layer {
+	/// RGB color.
+	pub color: u32,
+	pub path: &'static str,
+}
+
+impl Icon {
+	/// Renders the icon into a new `size`x`size` texture with a transparent background.
+	pub fn rasterize(&self, size: usize) -> Texture {
+		let mut texture = Texture::new(size, size);
+		let scale = size as f32 / GRID_SIZE;
+		for layer in self.layers {
+			let path = Path::parse(layer.path)
+				.unwrap_or_else(|err| panic!("invalid path of icon {}: {err}", self.name));
+			path.fill(&mut texture.bitmap, size, layer.color, scale);
+		}
+		texture
+	}
+}
+
+pub const BACK: Icon = Icon {
+	name: "back",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 4 12 L 11 5 L 13 7 L 9.5 10.5 L 20 10.5 L 20 13.5 L 9.5 13.5 L 13 17 L 11 19 Z",
+	}],
+};
+
+pub const FORWARD: Icon = Icon {
+	name: "forward",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 20 12 L 13 5 L 11 7 L 14.5 10.5 L 4 10.5 L 4 13.5 L 14.5 13.5 L 11 17 L 13 19 Z",
+	}],
+};
+
+pub const UP: Icon = Icon {
+	name: "up",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 12 4 L 19 11 L 17 13 L 13.5 9.5 L 13.5 20 L 10.5 20 L 10.5 9.5 L 7 13 L 5 11 Z",
+	}],
+};
+
+/// Marks the column a list is sorted by.
+pub const SORT_ASCENDING: Icon = Icon {
+	name: "sort_ascending",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 12 7 L 19 16 L 5 16 Z",
+	}],
+};
+
+pub const SORT_DESCENDING: Icon = Icon {
+	name: "sort_descending",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 5 8 L 19 8 L 12 17 Z",
+	}],
+};
+
+pub const REFRESH: Icon = Icon {
+	name: "refresh",
+	layers: &[
+		Layer {
+			color: FOREGROUND,
+			path: "M 17.1 5.9 C 13.8 3 8.7 3.5 5.9 6.9 C 3 10.2 3.5 15.3 6.9 18.1 \
+				C 10.2 21 15.3 20.5 18.1 17.1 L 16.2 15.5 C 14.3 17.9 10.8 18.2 8.5 16.2 \
+				C 6.1 14.3 5.8 10.8 7.8 8.5 C 9.7 6.1 13.2 5.8 15.5 7.8 Z",
+		},
+		Layer {
+			color: FOREGROUND,
+			path: "M 18.6 4.1 L 19.8 9.7 L 14.1 9.5 Z",
+		},
+	],
+};
+
+pub const NEW_FOLDER: Icon = Icon {
+	name: "new_folder",
+	layers: &[
+		Layer {
+			color: FOREGROUND,
+			path: "M 2 6 Q 2 5 3 5 L 9 5 L 11 7 L 21 7 Q 22 7 22 8 L 22 19 Q 22 20 21 20 \
+				L 3 20 Q 2 20 2 19 Z",
+		},
+		Layer {
+			color: DARK,
+			path: "M 11 10 L 13 10 L 13 12.5 L 15.5 12.5 L 15.5 14.5 L 13 14.5 L 13 17 L 11 17 \
+				L 11 14.5 L 8.5 14.5 L 8.5 12.5 L 11 12.5 Z",
+		},
+	],
+};
+
+pub const VIEW_MODE: Icon = Icon {
+	name: "view_mode",
+	layers: &[Layer {
+		color: FOREGROUND,
+		path: "M 4 4 L 11 4 L 11 11 L 4 11 Z M 13 4 L 20 4 L 20 11 L 13 11 Z \
+			M 4 13 L 11 13 L 11 20 L 4 20 Z M 13 13 L 20 13 L 20 20 L 13 20 Z",
+	}],
+};
+
+pub const FOLDER: Icon = Icon {
+	name: "folder",
+	layers: &[Layer {
+		color: FOLDER_COLOR,
+		path: "M 2 6 Q 2 5 3 5 L 9 5 L 11 7 L 21 7 Q 22 7 22 8 L 22 19 Q 22 20 21 20 \
+			L 3 20 Q 2 20 2 19 Z",
+	}],
+};
+
+pub const FILE: Icon = Icon {
+	name: "file",
+	layers: &[
+		Layer {
+			color: PAGE_COLOR,
+			path: "M 5 2 L 14 2 L 20 8 L 20 22 L 5 22 Z",
+		},
+		Layer {
+			color: PAGE_FOLD_COLOR,
+			path: "M 14 2 L 20 8 L 14 8 Z",
+		},
+	],
+};
+
+pub const IMAGE: Icon = Icon {
+	name: "image",
+	layers: &[
+		Layer {
+			color: SKY_COLOR,
+			path: "M 2 4 L 22 4 L 22 20 L 2 20 Z",
+		},
+		Layer {
+			color: FOLDER_COLOR,
+			path:
+				"M 18 8.5 C 18 9.3 17.3 10 16.5 10 C 15.7 10 15 9.3 15 8.5 C 15 7.7 15.7 7 16.5 7 \
+				C 17.3 7 18 7.7 18 8.5 Z",
+		},
+		Layer {
+			color: HILL_COLOR,
+			path: "M 2 17 L 8 10 L 13 15 L 16 12 L 22 18 L 22 20 L 2 20 Z",
+		},
+	],
+};
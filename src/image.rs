@@ -0,0 +1,360 @@
+//! Single entry point for decoding images of any supported format. The format is detected from the
+//! magic bytes at the start of the data, file extensions are not trusted.
+
+use std::{fmt::Display, path::Path, time::Duration};
+
+use log::warn;
+
+use crate::bmp::{self, Bmp};
+use crate::cancel::CancelToken;
+use crate::draw::Texture;
+use crate::gif::{self, Gif};
+use crate::ico::{self, Ico};
+#[cfg(feature = "jpeg")]
+use crate::jpeg::{self, Jpeg};
+use crate::png::{self, DecodeOptions, Png, PNG_SIGNATURE};
+use crate::profiler::{self, Stage};
+use crate::qoi::{self, Qoi};
+
+/// A decoded image. Animated images have several frames of the same size, still images one.
+#[derive(Debug)]
+pub struct Image {
+	frames: Vec<(Texture, Duration)>,
+}
+
+/// A format specific decoder.
+pub trait ImageDecoder {
+	/// Whether `data` starts with the magic bytes of the format.
+	fn sniff(data: &[u8]) -> bool;
+	fn decode(data: &[u8]) -> Result<Image, Error>;
+
+	/// Like [ImageDecoder::decode], following `options` as far as the format allows. By default
+	/// they're only checked for cancellation before decoding.
+	fn decode_with(data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		if options.is_cancelled() {
+			return Err(Error::Cancelled);
+		}
+		Self::decode(data)
+	}
+}
+
+/// Settings for [load_from_path_with].
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+	/// The image may come out scaled down, as long as its bigger dimension stays at least this
+	/// big. For formats that can skip work that way, e.g. when decoding thumbnails.
+	pub target_size: Option<usize>,
+	/// Decoding gives up with [Error::Cancelled] once cancelled. How soon depends on the format.
+	pub cancel: Option<CancelToken>,
+}
+
+impl LoadOptions {
+	fn is_cancelled(&self) -> bool {
+		self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+	Png,
+	Bmp,
+	Gif,
+	Ico,
+	Qoi,
+	#[cfg(feature = "jpeg")]
+	Jpeg,
+}
+
+const FORMATS: &[Format] = &[
+	Format::Png,
+	Format::Bmp,
+	Format::Gif,
+	Format::Ico,
+	Format::Qoi,
+	#[cfg(feature = "jpeg")]
+	Format::Jpeg,
+];
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: std::path::PathBuf,
+	},
+	UnknownFormat,
+	Cancelled,
+	Png(png::Error),
+	Bmp(bmp::Error),
+	Gif(gif::Error),
+	Ico(ico::Error),
+	Qoi(qoi::Error),
+	#[cfg(feature = "jpeg")]
+	Jpeg(jpeg::Error),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::UnknownFormat => write!(f, "Unknown image format."),
+			Error::Cancelled => write!(f, "Loading was cancelled."),
+			Error::Png(err) => write!(f, "{err}"),
+			Error::Bmp(err) => write!(f, "{err}"),
+			Error::Gif(err) => write!(f, "{err}"),
+			Error::Ico(err) => write!(f, "{err}"),
+			Error::Qoi(err) => write!(f, "{err}"),
+			#[cfg(feature = "jpeg")]
+			Error::Jpeg(err) => write!(f, "{err}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Format {
+	/// Format of the image in `data`, judging by its first bytes.
+	pub fn detect(data: &[u8]) -> Option<Format> {
+		FORMATS.iter().copied().find(|format| format.sniff(data))
+	}
+
+	/// Whether `path` has an extension of a supported format. Only a hint for listing files,
+	/// decoding goes by the contents.
+	pub fn is_image_path(path: &Path) -> bool {
+		let Some(extension) = path.extension() else {
+			return false;
+		};
+		FORMATS.iter().any(|format| {
+			format
+				.extensions()
+				.iter()
+				.any(|ext| extension.eq_ignore_ascii_case(ext))
+		})
+	}
+
+	pub fn extensions(self) -> &'static [&'static str] {
+		match self {
+			Format::Png => &["png", "apng"],
+			Format::Bmp => &["bmp", "dib"],
+			Format::Gif => &["gif"],
+			Format::Ico => &["ico", "cur"],
+			Format::Qoi => &["qoi"],
+			#[cfg(feature = "jpeg")]
+			Format::Jpeg => &["jpg", "jpeg", "jfif"],
+		}
+	}
+
+	fn sniff(self, data: &[u8]) -> bool {
+		match self {
+			Format::Png => Png::sniff(data),
+			Format::Bmp => Bmp::sniff(data),
+			Format::Gif => Gif::sniff(data),
+			Format::Ico => Ico::sniff(data),
+			Format::Qoi => Qoi::sniff(data),
+			#[cfg(feature = "jpeg")]
+			Format::Jpeg => Jpeg::sniff(data),
+		}
+	}
+
+	fn decode(self, data: &[u8]) -> Result<Image, Error> {
+		match self {
+			Format::Png => Png::decode(data),
+			Format::Bmp => Bmp::decode(data),
+			Format::Gif => Gif::decode(data),
+			Format::Ico => Ico::decode(data),
+			Format::Qoi => Qoi::decode(data),
+			#[cfg(feature = "jpeg")]
+			Format::Jpeg => Jpeg::decode(data),
+		}
+	}
+
+	fn decode_with(self, data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		match self {
+			Format::Png => Png::decode_with(data, options),
+			Format::Bmp => Bmp::decode_with(data, options),
+			Format::Gif => Gif::decode_with(data, options),
+			Format::Ico => Ico::decode_with(data, options),
+			Format::Qoi => Qoi::decode_with(data, options),
+			#[cfg(feature = "jpeg")]
+			Format::Jpeg => Jpeg::decode_with(data, options),
+		}
+	}
+}
+
+pub fn load_from_path(path: impl AsRef<Path>) -> Result<Image, Error> {
+	let data = read(path.as_ref())?;
+	load_from_slice(&data)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+	let _scope = profiler::scope(Stage::Fs);
+	std::fs::read(path).map_err(|err| Error::Io {
+		err,
+		filename: path.to_path_buf(),
+	})
+}
+
+pub fn load_from_slice(data: &[u8]) -> Result<Image, Error> {
+	Format::detect(data)
+		.ok_or(Error::UnknownFormat)?
+		.decode(data)
+}
+
+/// Like [load_from_path], following `options`. Scaled down images still have to be scaled to
+/// fit wherever they're shown.
+pub fn load_from_path_with(path: impl AsRef<Path>, options: &LoadOptions) -> Result<Image, Error> {
+	let data = read(path.as_ref())?;
+	Format::detect(&data)
+		.ok_or(Error::UnknownFormat)?
+		.decode_with(&data, options)
+}
+
+impl Image {
+	/// A still image.
+	pub fn new(texture: Texture) -> Self {
+		Image {
+			frames: vec![(texture, Duration::ZERO)],
+		}
+	}
+
+	pub fn animated(frames: Vec<(Texture, Duration)>) -> Self {
+		assert!(!frames.is_empty(), "images have at least one frame");
+		Image { frames }
+	}
+
+	/// The first frame of animated images.
+	pub fn into_texture(self) -> Texture {
+		self.frames.into_iter().next().unwrap().0
+	}
+
+	/// Every frame paired with its delay.
+	pub fn into_frames(self) -> Vec<(Texture, Duration)> {
+		self.frames
+	}
+}
+
+impl ImageDecoder for Png {
+	fn sniff(data: &[u8]) -> bool {
+		data.starts_with(&PNG_SIGNATURE)
+	}
+
+	/// Slightly damaged files are decoded anyway, the damage is only logged.
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		decode_png(data, DecodeOptions::lenient())
+	}
+
+	/// Scaled down while decoding, see [DecodeOptions::target_size].
+	fn decode_with(data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		let options = DecodeOptions {
+			target_size: options.target_size,
+			cancel: options.cancel.clone(),
+			..DecodeOptions::lenient()
+		};
+		decode_png(data, options)
+	}
+}
+
+fn decode_png(data: &[u8], options: DecodeOptions) -> Result<Image, Error> {
+	let (png, warnings) = Png::load_with_options(data, options).map_err(|err| match err {
+		png::Error::Cancelled => Error::Cancelled,
+		err => Error::Png(err),
+	})?;
+	for warning in warnings {
+		warn!("Damaged PNG: {warning}");
+	}
+	Ok(Image::animated(png.into_frames()))
+}
+
+impl ImageDecoder for Bmp {
+	fn sniff(data: &[u8]) -> bool {
+		data.starts_with(b"BM")
+	}
+
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		let bmp = Bmp::load_from_slice(data).map_err(Error::Bmp)?;
+		Ok(Image::new(Texture::from(bmp)))
+	}
+}
+
+impl ImageDecoder for Gif {
+	fn sniff(data: &[u8]) -> bool {
+		data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+	}
+
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		let gif = Gif::load_from_slice(data).map_err(Error::Gif)?;
+		Ok(Image::animated(gif.into_frames()))
+	}
+
+	fn decode_with(data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		let Some(cancel) = &options.cancel else {
+			return Gif::decode(data);
+		};
+		let gif = Gif::load_cancellable(data, cancel).map_err(|err| match err {
+			gif::Error::Cancelled => Error::Cancelled,
+			err => Error::Gif(err),
+		})?;
+		Ok(Image::animated(gif.into_frames()))
+	}
+}
+
+impl ImageDecoder for Ico {
+	fn sniff(data: &[u8]) -> bool {
+		// Reserved zero, then the type: 1 for icons, 2 for cursors
+		matches!(data.get(..4), Some([0, 0, 1 | 2, 0]))
+	}
+
+	/// Gives the biggest image of the icon.
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		let ico = Ico::load_from_slice(data).map_err(Error::Ico)?;
+		let texture = ico.best_texture(usize::MAX).map_err(Error::Ico)?;
+		Ok(Image::new(texture))
+	}
+
+	/// Gives the image best suited for [LoadOptions::target_size], e.g. the size of thumbnails,
+	/// instead of scaling the biggest one down.
+	fn decode_with(data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		if options.is_cancelled() {
+			return Err(Error::Cancelled);
+		}
+		let ico = Ico::load_from_slice(data).map_err(Error::Ico)?;
+		let size = options.target_size.unwrap_or(usize::MAX);
+		let texture = ico.best_texture(size).map_err(Error::Ico)?;
+		Ok(Image::new(texture))
+	}
+}
+
+impl ImageDecoder for Qoi {
+	fn sniff(data: &[u8]) -> bool {
+		data.starts_with(b"qoif")
+	}
+
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		let qoi = Qoi::load_from_slice(data).map_err(Error::Qoi)?;
+		Ok(Image::new(Texture::from(qoi)))
+	}
+}
+
+#[cfg(feature = "jpeg")]
+impl ImageDecoder for Jpeg {
+	fn sniff(data: &[u8]) -> bool {
+		data.starts_with(&[0xFF, 0xD8, 0xFF])
+	}
+
+	fn decode(data: &[u8]) -> Result<Image, Error> {
+		let jpeg = Jpeg::load_from_slice(data).map_err(Error::Jpeg)?;
+		Ok(Image::new(Texture::from(jpeg)))
+	}
+
+	fn decode_with(data: &[u8], options: &LoadOptions) -> Result<Image, Error> {
+		let Some(cancel) = &options.cancel else {
+			return Jpeg::decode(data);
+		};
+		let jpeg = Jpeg::load_cancellable(data, cancel).map_err(|err| match err {
+			jpeg::Error::Cancelled => Error::Cancelled,
+			err => Error::Jpeg(err),
+		})?;
+		Ok(Image::new(Texture::from(jpeg)))
+	}
+}
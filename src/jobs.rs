@@ -0,0 +1,195 @@
+//! Shared pool of worker threads for work that shouldn't block the main loop, like listing
+//! directories and loading thumbnails. Queued jobs of a higher [Priority] start first, and a job
+//! can wait for other jobs to finish before it starts, see [spawn_after].
+//!
+//! The workers are started by the first job. [shutdown] stops them on exit: queued jobs are dropped
+//! without running, and running ones are waited for, so long jobs should check [is_shutting_down]
+//! (or a [CancelToken](crate::cancel::CancelToken)) every now and then.
+
+use std::collections::{HashSet, VecDeque};
+use std::hash::{BuildHasherDefault, DefaultHasher};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use log::{debug, error};
+
+/// Order of queued jobs, not a thread priority: running jobs aren't preempted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+	/// Work nobody is waiting on yet, e.g. thumbnails.
+	Low,
+	/// Work the UI waits on, e.g. listing a directory.
+	Medium,
+	/// Work the user explicitly started, e.g. copying files.
+	High,
+}
+
+impl Priority {
+	const COUNT: usize = 3;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Queue {
+	next_id: u64,
+	/// Jobs that can start, indexed by [Priority], oldest first.
+	ready: [VecDeque<(JobId, Job)>; Priority::COUNT],
+	/// Jobs waiting for their dependencies.
+	blocked: Vec<Blocked>,
+	/// Jobs that didn't finish yet, whether they're ready, blocked or running. A large folder
+	/// queues thousands of thumbnails, a set keeps spawning and finishing them cheap. Hashed with
+	/// a fixed hasher, the random one can't be created in a static.
+	unfinished: HashSet<JobId, BuildHasherDefault<DefaultHasher>>,
+	workers_started: bool,
+	shutting_down: bool,
+}
+
+struct Blocked {
+	id: JobId,
+	priority: Priority,
+	/// Unfinished dependencies.
+	after: Vec<JobId>,
+	job: Job,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue {
+	next_id: 0,
+	ready: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+	blocked: Vec::new(),
+	unfinished: HashSet::with_hasher(BuildHasherDefault::new()),
+	workers_started: false,
+	shutting_down: false,
+});
+/// Signalled when a job becomes ready and on shutdown.
+static JOB_READY: Condvar = Condvar::new();
+static WORKERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// Queues `job` to run on a worker thread.
+pub fn spawn(priority: Priority, job: impl FnOnce() + Send + 'static) -> JobId {
+	spawn_after(priority, &[], job)
+}
+
+/// Queues `job` to run on a worker thread once the jobs `after` finished (or panicked).
+pub fn spawn_after(
+	priority: Priority,
+	after: &[JobId],
+	job: impl FnOnce() + Send + 'static,
+) -> JobId {
+	let mut queue = lock_queue();
+	let id = JobId(queue.next_id);
+	queue.next_id += 1;
+	if queue.shutting_down {
+		debug!("Dropping job {id:?} spawned during shutdown");
+		return id;
+	}
+	if !queue.workers_started {
+		queue.workers_started = true;
+		start_workers();
+	}
+
+	let after: Vec<JobId> = after
+		.iter()
+		.copied()
+		.filter(|dep| queue.unfinished.contains(dep))
+		.collect();
+	queue.unfinished.insert(id);
+	let job = Box::new(job);
+	if after.is_empty() {
+		queue.ready[priority as usize].push_back((id, job));
+		JOB_READY.notify_one();
+	} else {
+		queue.blocked.push(Blocked {
+			id,
+			priority,
+			after,
+			job,
+		});
+	}
+	id
+}
+
+pub fn is_shutting_down() -> bool {
+	lock_queue().shutting_down
+}
+
+/// Drops the queued jobs and waits for the running ones to finish. Jobs spawned afterwards are
+/// dropped too.
+pub fn shutdown() {
+	{
+		let mut queue = lock_queue();
+		queue.shutting_down = true;
+		let dropped = queue.ready.iter().map(VecDeque::len).sum::<usize>() + queue.blocked.len();
+		debug!("Shutting down the job workers, dropping {dropped} queued jobs");
+		queue.ready.iter_mut().for_each(VecDeque::clear);
+		queue.blocked.clear();
+		JOB_READY.notify_all();
+	}
+	let workers = std::mem::take(&mut *WORKERS.lock().unwrap());
+	for worker in workers {
+		if worker.join().is_err() {
+			error!("A job worker panicked");
+		}
+	}
+}
+
+fn lock_queue() -> MutexGuard<'static, Queue> {
+	// Jobs run outside of the lock, a poisoned queue is still consistent
+	QUEUE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn start_workers() {
+	let count = std::thread::available_parallelism().map_or(2, |n| n.get().clamp(2, 8));
+	let mut workers = WORKERS.lock().unwrap();
+	for idx in 0..count {
+		let worker = std::thread::Builder::new()
+			.name(format!("job worker {idx}"))
+			.spawn(run_worker)
+			.expect("failed to start a job worker");
+		workers.push(worker);
+	}
+}
+
+fn run_worker() {
+	while let Some((id, job)) = next_job() {
+		if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+			error!("Job {id:?} panicked");
+		}
+		finish(id);
+	}
+}
+
+/// Waits for the highest priority ready job, None once shutting down.
+fn next_job() -> Option<(JobId, Job)> {
+	let mut queue = lock_queue();
+	loop {
+		if queue.shutting_down {
+			return None;
+		}
+		if let Some(job) = queue.ready.iter_mut().rev().find_map(VecDeque::pop_front) {
+			return Some(job);
+		}
+		queue = JOB_READY.wait(queue).unwrap_or_else(|err| err.into_inner());
+	}
+}
+
+/// Marks `id` finished and readies the jobs that were only waiting for it.
+fn finish(id: JobId) {
+	let mut queue = lock_queue();
+	queue.unfinished.remove(&id);
+	let mut idx = 0;
+	while idx < queue.blocked.len() {
+		let blocked = &mut queue.blocked[idx];
+		blocked.after.retain(|&dep| dep != id);
+		if blocked.after.is_empty() {
+			let blocked = queue.blocked.swap_remove(idx);
+			queue.ready[blocked.priority as usize].push_back((blocked.id, blocked.job));
+			JOB_READY.notify_one();
+		} else {
+			idx += 1;
+		}
+	}
+}
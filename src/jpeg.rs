@@ -0,0 +1,850 @@
+//! Baseline JPEG decoder: Huffman coded sequential DCT images with 8 bit samples, grayscale or
+//! YCbCr with any chroma subsampling. Progressive and arithmetic coded images are not supported.
+
+use std::{f32::consts::PI, fmt::Display, path::PathBuf};
+
+use log::debug;
+
+use crate::cancel::CancelToken;
+use crate::draw::Pos;
+
+const MARKER_SOF0: u8 = 0xC0;
+/// Extended sequential, decoded the same as baseline as long as samples are 8 bit.
+const MARKER_SOF1: u8 = 0xC1;
+const MARKER_DHT: u8 = 0xC4;
+const MARKER_RST0: u8 = 0xD0;
+const MARKER_RST7: u8 = 0xD7;
+const MARKER_SOI: u8 = 0xD8;
+const MARKER_EOI: u8 = 0xD9;
+const MARKER_SOS: u8 = 0xDA;
+const MARKER_DQT: u8 = 0xDB;
+const MARKER_DRI: u8 = 0xDD;
+
+/// Images bigger than this many pixels are rejected instead of allocating gigabytes for a
+/// corrupted header.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Position of the n-th coefficient of the zigzag order in a row-major 8x8 block.
+const ZIGZAG: [usize; 64] = [
+	0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+	13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+	52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+pub struct Jpeg {
+	width: usize,
+	height: usize,
+	/// 0xAARRGGBB, top row first.
+	pixels: Vec<u32>,
+	filename: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	/// Start of frame marker of a process other than baseline, e.g. progressive.
+	UnsupportedProcess(u8),
+	UnsupportedPrecision(u8),
+	UnsupportedComponents(usize),
+	InvalidSize,
+	InvalidTable,
+	InvalidScan,
+	InvalidHuffmanCode,
+	NoFrame,
+	Cancelled,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not a JPEG file."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::UnsupportedProcess(marker) => {
+				write!(f, "Unsupported coding process (SOF marker {marker:#X}).")
+			}
+			Error::UnsupportedPrecision(bits) => write!(f, "Unsupported {bits} bit samples."),
+			Error::UnsupportedComponents(count) => {
+				write!(f, "Unsupported number of components: {count}.")
+			}
+			Error::InvalidSize => write!(f, "Invalid image size."),
+			Error::InvalidTable => write!(f, "Invalid quantization or Huffman table."),
+			Error::InvalidScan => write!(f, "Invalid scan header."),
+			Error::InvalidHuffmanCode => write!(f, "Invalid Huffman code in image data."),
+			Error::NoFrame => write!(f, "Image data before the frame header."),
+			Error::Cancelled => write!(f, "Decoding was cancelled."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug)]
+struct Component {
+	id: u8,
+	/// Horizontal and vertical sampling factors.
+	h: usize,
+	v: usize,
+	quant_table: usize,
+	dc_table: usize,
+	ac_table: usize,
+	/// DC coefficient of the previous block, DC values are coded as differences.
+	dc_pred: i32,
+	/// Decoded samples, padded to whole MCUs.
+	plane: Vec<u8>,
+	stride: usize,
+}
+
+struct Frame {
+	width: usize,
+	height: usize,
+	components: Vec<Component>,
+	max_h: usize,
+	max_v: usize,
+	mcus_x: usize,
+	mcus_y: usize,
+}
+
+/// Canonical Huffman table, decoded code length by code length (JPEG spec F.2.2.3).
+#[derive(Clone)]
+struct Huffman {
+	/// Largest code of each length, -1 if there's none.
+	max_code: [i32; 17],
+	/// Smallest code of each length.
+	min_code: [i32; 17],
+	/// Index of the value of the smallest code of each length.
+	value_offset: [i32; 17],
+	values: Vec<u8>,
+}
+
+impl Huffman {
+	fn new(counts: &[u8; 16], values: Vec<u8>) -> Self {
+		let mut table = Huffman {
+			max_code: [-1; 17],
+			min_code: [0; 17],
+			value_offset: [0; 17],
+			values,
+		};
+		let (mut code, mut idx) = (0, 0);
+		for len in 1..=16 {
+			let count = counts[len - 1] as i32;
+			table.value_offset[len] = idx;
+			table.min_code[len] = code;
+			code += count;
+			idx += count;
+			if count > 0 {
+				table.max_code[len] = code - 1;
+			}
+			code <<= 1;
+		}
+		table
+	}
+
+	fn decode(&self, bits: &mut BitReader) -> Result<u8, Error> {
+		let mut code = 0;
+		for len in 1..=16 {
+			code = (code << 1) | bits.bit() as i32;
+			if code <= self.max_code[len] {
+				let idx = self.value_offset[len] + code - self.min_code[len];
+				return self
+					.values
+					.get(idx as usize)
+					.copied()
+					.ok_or(Error::InvalidHuffmanCode);
+			}
+		}
+		Err(Error::InvalidHuffmanCode)
+	}
+}
+
+/// Reads entropy coded data, skipping stuffed zero bytes. Stops at markers, feeding zeros instead.
+struct BitReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+	bits: u32,
+	count: u32,
+}
+
+impl<'a> BitReader<'a> {
+	fn new(data: &'a [u8], pos: usize) -> Self {
+		BitReader {
+			data,
+			pos,
+			bits: 0,
+			count: 0,
+		}
+	}
+
+	fn bit(&mut self) -> u32 {
+		if self.count == 0 {
+			self.bits = self.next_byte() as u32;
+			self.count = 8;
+		}
+		self.count -= 1;
+		(self.bits >> self.count) & 1
+	}
+
+	fn next_byte(&mut self) -> u8 {
+		match self.data.get(self.pos..self.pos + 2) {
+			Some(&[0xFF, 0x00]) => {
+				self.pos += 2;
+				0xFF
+			}
+			// A marker, don't consume it
+			Some(&[0xFF, _]) => 0,
+			_ => match self.data.get(self.pos) {
+				Some(&byte) => {
+					self.pos += 1;
+					byte
+				}
+				None => 0,
+			},
+		}
+	}
+
+	fn receive(&mut self, len: u8) -> i32 {
+		(0..len).fold(0, |value, _| (value << 1) | self.bit() as i32)
+	}
+
+	/// Reads a `len` bit coefficient, where values with the top bit clear are negative.
+	fn receive_extend(&mut self, len: u8) -> i32 {
+		if len == 0 {
+			return 0;
+		}
+		let value = self.receive(len);
+		if value < 1 << (len - 1) {
+			value - (1 << len) + 1
+		} else {
+			value
+		}
+	}
+
+	/// Drops the remaining bits of the current byte and skips a restart marker, if there's one.
+	fn restart(&mut self) {
+		self.count = 0;
+		if let Some(&[0xFF, marker]) = self.data.get(self.pos..self.pos + 2) {
+			if (MARKER_RST0..=MARKER_RST7).contains(&marker) {
+				self.pos += 2;
+			}
+		}
+	}
+}
+
+impl Jpeg {
+	pub fn load_from_path(p: &str) -> Result<Self, Error> {
+		let data = std::fs::read(p).map_err(|err| Error::Io {
+			err,
+			filename: p.into(),
+		})?;
+		let mut jpeg = Jpeg::load_from_slice(&data)?;
+		jpeg.filename = Some(p.to_string());
+		Ok(jpeg)
+	}
+
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		Jpeg::load(data, None)
+	}
+
+	/// Like [Jpeg::load_from_slice], giving up with [Error::Cancelled] between rows of MCUs once
+	/// `cancel` is cancelled.
+	pub fn load_cancellable(data: &[u8], cancel: &CancelToken) -> Result<Self, Error> {
+		Jpeg::load(data, Some(cancel))
+	}
+
+	fn load(data: &[u8], cancel: Option<&CancelToken>) -> Result<Self, Error> {
+		if data.get(..2) != Some(&[0xFF, MARKER_SOI]) {
+			return Err(Error::BadMagic);
+		}
+
+		let mut quant_tables = [[0u16; 64]; 4];
+		let mut dc_tables: [Option<Huffman>; 4] = Default::default();
+		let mut ac_tables: [Option<Huffman>; 4] = Default::default();
+		let mut restart_interval = 0;
+		let mut frame: Option<Frame> = None;
+
+		let mut pos = 2;
+		loop {
+			// Markers may be preceded by any number of fill bytes
+			while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+				pos += 1;
+			}
+			let Some(&[0xFF, marker]) = data.get(pos..pos + 2) else {
+				return Err(Error::FileEnd);
+			};
+			pos += 2;
+			if marker == MARKER_EOI {
+				break;
+			}
+			if (MARKER_RST0..=MARKER_RST7).contains(&marker) {
+				continue;
+			}
+
+			let len = read_u16(data, pos)? as usize;
+			let segment = data.get(pos + 2..pos + len).ok_or(Error::FileEnd)?;
+			pos += len;
+
+			match marker {
+				MARKER_SOF0 | MARKER_SOF1 => frame = Some(parse_frame(segment)?),
+				// Other start of frame markers, except DHT, JPG and DAC which share the range
+				0xC2..=0xCF if ![MARKER_DHT, 0xC8, 0xCC].contains(&marker) => {
+					return Err(Error::UnsupportedProcess(marker));
+				}
+				MARKER_DQT => parse_quant_tables(segment, &mut quant_tables)?,
+				MARKER_DHT => parse_huffman_tables(segment, &mut dc_tables, &mut ac_tables)?,
+				MARKER_DRI => restart_interval = read_u16(segment, 0)? as usize,
+				MARKER_SOS => {
+					let frame = frame.as_mut().ok_or(Error::NoFrame)?;
+					let scan = Scan {
+						quant_tables: &quant_tables,
+						dc_tables: &dc_tables,
+						ac_tables: &ac_tables,
+						restart_interval,
+						cancel,
+					};
+					pos = scan.decode(frame, segment, data, pos)?;
+				}
+				_ => debug!("Skipping JPEG segment {marker:#X} of {len} bytes"),
+			}
+		}
+
+		let frame = frame.ok_or(Error::NoFrame)?;
+		Ok(Jpeg {
+			width: frame.width,
+			height: frame.height,
+			pixels: to_rgb(&frame),
+			filename: None,
+		})
+	}
+}
+
+impl From<Jpeg> for crate::draw::Texture {
+	fn from(img: Jpeg) -> Self {
+		let texture = crate::draw::Texture {
+			bitmap: img.pixels,
+			width: img.width,
+			height: img.height,
+			pos: Pos { x: 0, y: 0 },
+		};
+		debug!(
+			"From file \"{filename}\" created texture: {texture:#?}",
+			filename = img.filename.as_deref().unwrap_or("unknown")
+		);
+		texture
+	}
+}
+
+fn parse_frame(segment: &[u8]) -> Result<Frame, Error> {
+	let precision = *segment.first().ok_or(Error::FileEnd)?;
+	if precision != 8 {
+		return Err(Error::UnsupportedPrecision(precision));
+	}
+	let height = read_u16(segment, 1)? as usize;
+	let width = read_u16(segment, 3)? as usize;
+	let count = *segment.get(5).ok_or(Error::FileEnd)? as usize;
+	if width == 0 || height == 0 || width * height > MAX_PIXELS {
+		return Err(Error::InvalidSize);
+	}
+	if count != 1 && count != 3 {
+		return Err(Error::UnsupportedComponents(count));
+	}
+
+	let mut components = Vec::with_capacity(count);
+	for spec in segment
+		.get(6..6 + count * 3)
+		.ok_or(Error::FileEnd)?
+		.chunks_exact(3)
+	{
+		let (h, v) = ((spec[1] >> 4) as usize, (spec[1] & 0x0F) as usize);
+		if !(1..=4).contains(&h) || !(1..=4).contains(&v) || spec[2] > 3 {
+			return Err(Error::InvalidTable);
+		}
+		components.push(Component {
+			id: spec[0],
+			h,
+			v,
+			quant_table: spec[2] as usize,
+			dc_table: 0,
+			ac_table: 0,
+			dc_pred: 0,
+			plane: Vec::new(),
+			stride: 0,
+		});
+	}
+
+	let max_h = components.iter().map(|c| c.h).max().unwrap_or(1);
+	let max_v = components.iter().map(|c| c.v).max().unwrap_or(1);
+	let mcus_x = width.div_ceil(8 * max_h);
+	let mcus_y = height.div_ceil(8 * max_v);
+	for component in &mut components {
+		component.stride = mcus_x * component.h * 8;
+		component.plane = vec![0; component.stride * mcus_y * component.v * 8];
+	}
+
+	Ok(Frame {
+		width,
+		height,
+		components,
+		max_h,
+		max_v,
+		mcus_x,
+		mcus_y,
+	})
+}
+
+fn parse_quant_tables(mut segment: &[u8], tables: &mut [[u16; 64]; 4]) -> Result<(), Error> {
+	while let Some(&info) = segment.first() {
+		let (precision, id) = (info >> 4, (info & 0x0F) as usize);
+		let table = tables.get_mut(id).ok_or(Error::InvalidTable)?;
+		let size = if precision == 0 { 64 } else { 128 };
+		let values = segment.get(1..1 + size).ok_or(Error::FileEnd)?;
+		for (idx, value) in table.iter_mut().enumerate() {
+			*value = match precision {
+				0 => values[idx] as u16,
+				_ => u16::from_be_bytes([values[idx * 2], values[idx * 2 + 1]]),
+			};
+		}
+		segment = &segment[1 + size..];
+	}
+	Ok(())
+}
+
+fn parse_huffman_tables(
+	mut segment: &[u8],
+	dc_tables: &mut [Option<Huffman>; 4],
+	ac_tables: &mut [Option<Huffman>; 4],
+) -> Result<(), Error> {
+	while let Some(&info) = segment.first() {
+		let (class, id) = (info >> 4, (info & 0x0F) as usize);
+		let counts: [u8; 16] = segment
+			.get(1..17)
+			.ok_or(Error::FileEnd)?
+			.try_into()
+			.unwrap();
+		let total: usize = counts.iter().map(|&count| count as usize).sum();
+		let values = segment.get(17..17 + total).ok_or(Error::FileEnd)?.to_vec();
+		let tables = match class {
+			0 => &mut *dc_tables,
+			1 => &mut *ac_tables,
+			_ => return Err(Error::InvalidTable),
+		};
+		*tables.get_mut(id).ok_or(Error::InvalidTable)? = Some(Huffman::new(&counts, values));
+		segment = &segment[17 + total..];
+	}
+	Ok(())
+}
+
+/// Tables in effect for a scan.
+struct Scan<'a> {
+	quant_tables: &'a [[u16; 64]; 4],
+	dc_tables: &'a [Option<Huffman>; 4],
+	ac_tables: &'a [Option<Huffman>; 4],
+	restart_interval: usize,
+	cancel: Option<&'a CancelToken>,
+}
+
+impl Scan<'_> {
+	/// Decodes the entropy coded data following the scan header at `pos` into the component
+	/// planes of `frame`. Returns the position of the marker after the data.
+	fn decode(
+		&self,
+		frame: &mut Frame,
+		header: &[u8],
+		data: &[u8],
+		pos: usize,
+	) -> Result<usize, Error> {
+		let count = *header.first().ok_or(Error::FileEnd)? as usize;
+		let specs = header.get(1..1 + count * 2).ok_or(Error::FileEnd)?;
+		let mut scan_components = Vec::with_capacity(count);
+		for spec in specs.chunks_exact(2) {
+			let idx = frame
+				.components
+				.iter()
+				.position(|c| c.id == spec[0])
+				.ok_or(Error::InvalidScan)?;
+			let component = &mut frame.components[idx];
+			component.dc_table = (spec[1] >> 4) as usize;
+			component.ac_table = (spec[1] & 0x0F) as usize;
+			component.dc_pred = 0;
+			if component.dc_table > 3 || component.ac_table > 3 {
+				return Err(Error::InvalidTable);
+			}
+			scan_components.push(idx);
+		}
+		if scan_components.is_empty() {
+			return Err(Error::InvalidScan);
+		}
+
+		let mut bits = BitReader::new(data, pos);
+		let mut coefficients = [0i32; 64];
+		let idct_table = idct_table();
+		// A single component scan isn't interleaved and its MCU is a single block, covering only
+		// the part of the plane within the image
+		let (mcus_x, mcus_y) = match scan_components[..] {
+			[idx] => {
+				let c = &frame.components[idx];
+				let width = (frame.width * c.h).div_ceil(frame.max_h);
+				let height = (frame.height * c.v).div_ceil(frame.max_v);
+				(width.div_ceil(8), height.div_ceil(8))
+			}
+			_ => (frame.mcus_x, frame.mcus_y),
+		};
+
+		for mcu in 0..mcus_x * mcus_y {
+			if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+				bits.restart();
+				for &idx in &scan_components {
+					frame.components[idx].dc_pred = 0;
+				}
+			}
+			let (mcu_x, mcu_y) = (mcu % mcus_x, mcu / mcus_x);
+			if mcu_x == 0 && self.cancel.is_some_and(CancelToken::is_cancelled) {
+				return Err(Error::Cancelled);
+			}
+			for &idx in &scan_components {
+				let component = &mut frame.components[idx];
+				let (blocks_x, blocks_y) = match scan_components.len() {
+					1 => (1, 1),
+					_ => (component.h, component.v),
+				};
+				for block_y in 0..blocks_y {
+					for block_x in 0..blocks_x {
+						self.decode_block(component, &mut bits, &mut coefficients)?;
+						let x = (mcu_x * blocks_x + block_x) * 8;
+						let y = (mcu_y * blocks_y + block_y) * 8;
+						let offset = y * component.stride + x;
+						idct(
+							&coefficients,
+							&idct_table,
+							&mut component.plane[offset..],
+							component.stride,
+						);
+					}
+				}
+			}
+		}
+
+		// Find the marker ending the scan
+		let mut end = bits.pos;
+		while let Some(&[byte, next]) = data.get(end..end + 2) {
+			let is_marker =
+				byte == 0xFF && next != 0 && !(MARKER_RST0..=MARKER_RST7).contains(&next);
+			if is_marker {
+				return Ok(end);
+			}
+			end += 1;
+		}
+		Err(Error::FileEnd)
+	}
+
+	/// Decodes and dequantizes the coefficients of one block, in row-major order.
+	fn decode_block(
+		&self,
+		component: &mut Component,
+		bits: &mut BitReader,
+		coefficients: &mut [i32; 64],
+	) -> Result<(), Error> {
+		let dc_table = self.dc_tables[component.dc_table]
+			.as_ref()
+			.ok_or(Error::InvalidTable)?;
+		let ac_table = self.ac_tables[component.ac_table]
+			.as_ref()
+			.ok_or(Error::InvalidTable)?;
+		let quant = &self.quant_tables[component.quant_table];
+
+		coefficients.fill(0);
+		let dc_len = dc_table.decode(bits)?;
+		// Differences of 8 bit samples take at most 11 bits
+		if dc_len > 11 {
+			return Err(Error::InvalidHuffmanCode);
+		}
+		// Damaged files may add up differences past any real DC value
+		component.dc_pred = component.dc_pred.wrapping_add(bits.receive_extend(dc_len));
+		coefficients[0] = component.dc_pred.wrapping_mul(quant[0] as i32);
+
+		let mut k = 1;
+		while k < 64 {
+			let symbol = ac_table.decode(bits)?;
+			let (run, len) = ((symbol >> 4) as usize, symbol & 0x0F);
+			if len == 0 {
+				if run == 15 {
+					// Run of 16 zeros
+					k += 16;
+					continue;
+				}
+				// End of block
+				break;
+			}
+			k += run;
+			if k >= 64 {
+				return Err(Error::InvalidHuffmanCode);
+			}
+			coefficients[ZIGZAG[k]] = bits.receive_extend(len) * quant[k] as i32;
+			k += 1;
+		}
+		Ok(())
+	}
+}
+
+/// cos((2x + 1)uπ/16) scaled by C(u)/2, indexed by x and u.
+fn idct_table() -> [[f32; 8]; 8] {
+	let mut table = [[0f32; 8]; 8];
+	for (x, row) in table.iter_mut().enumerate() {
+		for (u, value) in row.iter_mut().enumerate() {
+			let scale = if u == 0 { 1.0 / 2f32.sqrt() } else { 1.0 };
+			*value = scale / 2.0 * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+		}
+	}
+	table
+}
+
+/// Inverse DCT of a block, written as 8 rows of 8 samples to `output`, `stride` apart.
+fn idct(coefficients: &[i32; 64], table: &[[f32; 8]; 8], output: &mut [u8], stride: usize) {
+	let mut rows = [0f32; 64];
+	for v in 0..8 {
+		for x in 0..8 {
+			rows[v * 8 + x] = (0..8)
+				.map(|u| coefficients[v * 8 + u] as f32 * table[x][u])
+				.sum();
+		}
+	}
+	for y in 0..8 {
+		for x in 0..8 {
+			let sample: f32 = (0..8).map(|v| rows[v * 8 + x] * table[y][v]).sum();
+			output[y * stride + x] = (sample + 128.0).round().clamp(0.0, 255.0) as u8;
+		}
+	}
+}
+
+/// Upsamples the chroma planes and converts the frame to 0xAARRGGBB pixels.
+fn to_rgb(frame: &Frame) -> Vec<u32> {
+	let mut pixels = Vec::with_capacity(frame.width * frame.height);
+	let sample = |c: &Component, x: usize, y: usize| {
+		let (x, y) = (x * c.h / frame.max_h, y * c.v / frame.max_v);
+		c.plane[y * c.stride + x] as f32
+	};
+	for y in 0..frame.height {
+		for x in 0..frame.width {
+			let pixel = match &frame.components[..] {
+				[luma] => {
+					let luma = sample(luma, x, y) as u8;
+					u32::from_be_bytes([0xFF, luma, luma, luma])
+				}
+				[luma, cb, cr] => {
+					let luma = sample(luma, x, y);
+					let (cb, cr) = (sample(cb, x, y) - 128.0, sample(cr, x, y) - 128.0);
+					let red = luma + 1.402 * cr;
+					let green = luma - 0.344136 * cb - 0.714136 * cr;
+					let blue = luma + 1.772 * cb;
+					u32::from_be_bytes([
+						0xFF,
+						red.round().clamp(0.0, 255.0) as u8,
+						green.round().clamp(0.0, 255.0) as u8,
+						blue.round().clamp(0.0, 255.0) as u8,
+					])
+				}
+				_ => unreachable!("checked when parsing the frame"),
+			};
+			pixels.push(pixel);
+		}
+	}
+	pixels
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+	let bytes = data.get(offset..offset + 2).ok_or(Error::FileEnd)?;
+	// JPEG uses Big Endian
+	Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+		let mut segment = vec![0xFF, marker];
+		segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+		segment.extend_from_slice(payload);
+		segment
+	}
+
+	/// Packs a string of '0' and '1' into entropy coded bytes, padded with ones and with 0xFF
+	/// bytes stuffed.
+	fn entropy_coded(bits: &str) -> Vec<u8> {
+		let bits: Vec<u8> = bits.bytes().filter(|bit| *bit != b' ').collect();
+		let mut bytes = Vec::new();
+		for chunk in bits.chunks(8) {
+			let byte = (0..8).fold(0u8, |byte, idx| {
+				(byte << 1) | (chunk.get(idx).map_or(1, |bit| bit - b'0'))
+			});
+			bytes.push(byte);
+			if byte == 0xFF {
+				bytes.push(0);
+			}
+		}
+		bytes
+	}
+
+	/// Frame header of a `width`x`height` image with components of the given id and sampling
+	/// factors, all using quantization table 0.
+	fn frame_header(width: u16, height: u16, components: &[(u8, u8)]) -> Vec<u8> {
+		let mut payload = vec![8];
+		payload.extend_from_slice(&height.to_be_bytes());
+		payload.extend_from_slice(&width.to_be_bytes());
+		payload.push(components.len() as u8);
+		for &(id, sampling) in components {
+			payload.extend_from_slice(&[id, sampling, 0]);
+		}
+		segment(MARKER_SOF0, &payload)
+	}
+
+	/// Quantization table of ones and Huffman tables with two DC codes: `00` for category 0
+	/// and `01` for category 9, and a single AC code: `0` for the end of block.
+	fn tables() -> Vec<u8> {
+		let mut tables = segment(MARKER_DQT, &[[0].as_slice(), &[1; 64]].concat());
+		let mut dc = vec![0x00, 0, 2];
+		dc.extend_from_slice(&[0; 14]);
+		dc.extend_from_slice(&[0, 9]);
+		tables.extend(segment(MARKER_DHT, &dc));
+		let mut ac = vec![0x10, 1];
+		ac.extend_from_slice(&[0; 15]);
+		ac.push(0x00);
+		tables.extend(segment(MARKER_DHT, &ac));
+		tables
+	}
+
+	/// Scan header of components using DC and AC tables 0, followed by the coded data.
+	fn scan(component_ids: &[u8], data: &[u8]) -> Vec<u8> {
+		let mut payload = vec![component_ids.len() as u8];
+		for &id in component_ids {
+			payload.extend_from_slice(&[id, 0x00]);
+		}
+		payload.extend_from_slice(&[0, 63, 0]);
+		let mut scan = segment(MARKER_SOS, &payload);
+		scan.extend_from_slice(data);
+		scan
+	}
+
+	fn jpeg_file(segments: &[Vec<u8>]) -> Vec<u8> {
+		let mut file = vec![0xFF, MARKER_SOI];
+		for segment in segments {
+			file.extend_from_slice(segment);
+		}
+		file.extend_from_slice(&[0xFF, MARKER_EOI]);
+		file
+	}
+
+	/// A DC coefficient of 400 raises every sample of a block by 400 / 8.
+	const DC_400: &str = "01 110010000 0";
+	const DC_MINUS_400: &str = "01 001101111 0";
+	const DC_0: &str = "00 0";
+
+	/// 16x8 grayscale image of a block 50 brighter than the middle gray and one of middle gray,
+	/// DC values being differences to the previous block.
+	fn gray_file() -> Vec<u8> {
+		jpeg_file(&[
+			tables(),
+			frame_header(16, 8, &[(1, 0x11)]),
+			scan(&[1], &entropy_coded(&format!("{DC_400} {DC_MINUS_400}"))),
+		])
+	}
+
+	fn gray(value: u8) -> u32 {
+		u32::from_be_bytes([0xFF, value, value, value])
+	}
+
+	#[test]
+	fn grayscale() {
+		let jpeg = Jpeg::load_from_slice(&gray_file()).unwrap();
+		assert_eq!((jpeg.width, jpeg.height), (16, 8));
+		for row in jpeg.pixels.chunks_exact(16) {
+			assert_eq!(row[..8], [gray(178); 8]);
+			assert_eq!(row[8..], [gray(128); 8]);
+		}
+	}
+
+	#[test]
+	fn subsampled_color() {
+		// One MCU of four luma blocks and a block of each chroma component, blue raised
+		let data = entropy_coded(&format!("{DC_0} {DC_0} {DC_0} {DC_0} {DC_400} {DC_0}"));
+		let file = jpeg_file(&[
+			tables(),
+			frame_header(13, 11, &[(1, 0x22), (2, 0x11), (3, 0x11)]),
+			scan(&[1, 2, 3], &data),
+		]);
+		let jpeg = Jpeg::load_from_slice(&file).unwrap();
+		assert_eq!((jpeg.width, jpeg.height), (13, 11));
+		assert_eq!(jpeg.pixels, [0xFF806FD9; 13 * 11]);
+	}
+
+	#[test]
+	fn restart_markers() {
+		// The DC prediction starts over after each restart marker
+		let mut data = entropy_coded(DC_400);
+		data.extend_from_slice(&[0xFF, MARKER_RST0]);
+		data.extend(entropy_coded(DC_400));
+		let file = jpeg_file(&[
+			tables(),
+			segment(MARKER_DRI, &1u16.to_be_bytes()),
+			frame_header(16, 8, &[(1, 0x11)]),
+			scan(&[1], &data),
+		]);
+		let jpeg = Jpeg::load_from_slice(&file).unwrap();
+		assert_eq!(jpeg.pixels, [gray(178); 16 * 8]);
+	}
+
+	#[test]
+	fn damaged_files() {
+		let file = gray_file();
+		// Every cut must fail or decode, never panic
+		for len in 0..file.len() {
+			let _ = Jpeg::load_from_slice(&file[..len]);
+		}
+		assert!(matches!(
+			Jpeg::load_from_slice(&file[..file.len() - 2]),
+			Err(Error::FileEnd)
+		));
+		assert!(matches!(
+			Jpeg::load_from_slice(b"\x89PNG"),
+			Err(Error::BadMagic)
+		));
+
+		// Scan before the frame header
+		let file = jpeg_file(&[tables(), scan(&[1], &entropy_coded(DC_400))]);
+		assert!(matches!(Jpeg::load_from_slice(&file), Err(Error::NoFrame)));
+
+		// Progressive
+		let mut progressive = frame_header(8, 8, &[(1, 0x11)]);
+		progressive[1] = 0xC2;
+		assert!(matches!(
+			Jpeg::load_from_slice(&jpeg_file(&[progressive])),
+			Err(Error::UnsupportedProcess(0xC2))
+		));
+
+		// DC categories above 11 don't exist
+		let mut file = gray_file();
+		let category = file.iter().position(|&byte| byte == 9).unwrap();
+		file[category] = 200;
+		assert!(matches!(
+			Jpeg::load_from_slice(&file),
+			Err(Error::InvalidHuffmanCode)
+		));
+	}
+
+	#[test]
+	fn cancelled() {
+		let cancel = CancelToken::new();
+		assert!(Jpeg::load_cancellable(&gray_file(), &cancel).is_ok());
+		cancel.cancel();
+		assert!(matches!(
+			Jpeg::load_cancellable(&gray_file(), &cancel),
+			Err(Error::Cancelled)
+		));
+	}
+}
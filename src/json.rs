@@ -0,0 +1,173 @@
+//! JSON writer for debugging output such as state dumps. There's no parser, nothing reads JSON.
+
+use std::fmt::{Display, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	/// Keys in insertion order.
+	Object(Vec<(String, Value)>),
+}
+
+impl Value {
+	/// Object built from key-value pairs.
+	pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Value)>) -> Value {
+		Value::Object(
+			fields
+				.into_iter()
+				.map(|(key, value)| (key.to_string(), value))
+				.collect(),
+		)
+	}
+
+	/// Indented with tabs, one value per line.
+	pub fn to_pretty_string(&self) -> String {
+		let mut out = String::new();
+		self.write_pretty(&mut out, 0);
+		out.push('\n');
+		out
+	}
+
+	fn write_pretty(&self, out: &mut String, depth: usize) {
+		let indent = |out: &mut String, depth: usize| out.extend((0..depth).map(|_| '\t'));
+		match self {
+			Value::Array(values) if !values.is_empty() => {
+				out.push_str("[\n");
+				for (idx, value) in values.iter().enumerate() {
+					indent(out, depth + 1);
+					value.write_pretty(out, depth + 1);
+					out.push_str(if idx + 1 < values.len() { ",\n" } else { "\n" });
+				}
+				indent(out, depth);
+				out.push(']');
+			}
+			Value::Object(fields) if !fields.is_empty() => {
+				out.push_str("{\n");
+				for (idx, (key, value)) in fields.iter().enumerate() {
+					indent(out, depth + 1);
+					write_string(out, key);
+					out.push_str(": ");
+					value.write_pretty(out, depth + 1);
+					out.push_str(if idx + 1 < fields.len() { ",\n" } else { "\n" });
+				}
+				indent(out, depth);
+				out.push('}');
+			}
+			value => write!(out, "{value}").unwrap(),
+		}
+	}
+}
+
+/// Compact JSON.
+impl Display for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Value::Null => write!(f, "null"),
+			Value::Bool(b) => write!(f, "{b}"),
+			// JSON has no infinities nor NaN
+			Value::Number(n) if !n.is_finite() => write!(f, "null"),
+			Value::Number(n) => write!(f, "{n}"),
+			Value::String(s) => {
+				let mut out = String::new();
+				write_string(&mut out, s);
+				f.write_str(&out)
+			}
+			Value::Array(values) => {
+				f.write_char('[')?;
+				for (idx, value) in values.iter().enumerate() {
+					if idx > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{value}")?;
+				}
+				f.write_char(']')
+			}
+			Value::Object(fields) => {
+				f.write_char('{')?;
+				for (idx, (key, value)) in fields.iter().enumerate() {
+					if idx > 0 {
+						f.write_char(',')?;
+					}
+					let mut out = String::new();
+					write_string(&mut out, key);
+					write!(f, "{out}:{value}")?;
+				}
+				f.write_char('}')
+			}
+		}
+	}
+}
+
+fn write_string(out: &mut String, s: &str) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+impl From<bool> for Value {
+	fn from(b: bool) -> Self {
+		Value::Bool(b)
+	}
+}
+
+impl From<usize> for Value {
+	fn from(n: usize) -> Self {
+		Value::Number(n as f64)
+	}
+}
+
+impl From<u64> for Value {
+	fn from(n: u64) -> Self {
+		Value::Number(n as f64)
+	}
+}
+
+impl From<f64> for Value {
+	fn from(n: f64) -> Self {
+		Value::Number(n)
+	}
+}
+
+impl From<&str> for Value {
+	fn from(s: &str) -> Self {
+		Value::String(s.to_string())
+	}
+}
+
+impl From<String> for Value {
+	fn from(s: String) -> Self {
+		Value::String(s)
+	}
+}
+
+impl From<&std::path::Path> for Value {
+	fn from(path: &std::path::Path) -> Self {
+		Value::String(path.display().to_string())
+	}
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+	fn from(value: Option<T>) -> Self {
+		value.map_or(Value::Null, Into::into)
+	}
+}
+
+impl<T: Into<Value>> FromIterator<T> for Value {
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+		Value::Array(iter.into_iter().map(Into::into).collect())
+	}
+}
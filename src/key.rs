@@ -1,5 +1,18 @@
-#[allow(dead_code)]
-#[derive(Copy, Clone, Hash, PartialEq, Eq)]
+use std::fmt::Display;
+
+use windows::Win32::{
+	Foundation::LPARAM,
+	UI::{
+		Input::KeyboardAndMouse::{
+			GetKeyNameTextW, GetKeyboardLayout, MapVirtualKeyExW, ToUnicodeEx, MAPVK_VK_TO_VSC_EX,
+		},
+		TextServices::HKL,
+	},
+};
+
+use crate::string::from_utf16_null_terminated;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 #[repr(u16)]
 pub enum Key {
 	Num0 = 48u16,
@@ -186,4 +199,465 @@ pub enum Key {
 	GamepadRightThumbstickLeft = 218u16,
 	LeftBrace = 219_u16,
 	RightBrace = 221_u16,
+	// Numpad keys sending the same virtual key codes as keys of the main keyboard, see
+	// Key::from_message
+	NumpadEnter = 0x10D_u16,
+	NumpadPrior = 0x121_u16,
+	NumpadNext = 0x122_u16,
+	NumpadEnd = 0x123_u16,
+	NumpadHome = 0x124_u16,
+	NumpadLeft = 0x125_u16,
+	NumpadUp = 0x126_u16,
+	NumpadRight = 0x127_u16,
+	NumpadDown = 0x128_u16,
+	NumpadInsert = 0x12D_u16,
+	NumpadDelete = 0x12E_u16,
+}
+
+/// Anything that can be pressed and bound to a shortcut.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Input {
+	Key(Key),
+	Mouse(MouseButton),
+	/// A notch of the mouse wheel.
+	Wheel(WheelDirection),
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum MouseButton {
+	Left,
+	Right,
+	Middle,
+	/// The side button usually used to go back.
+	Button4,
+	/// The side button usually used to go forward.
+	Button5,
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum WheelDirection {
+	Up,
+	Down,
+	Left,
+	Right,
+}
+
+const MOUSE_BUTTONS: &[MouseButton] = &[
+	MouseButton::Left,
+	MouseButton::Right,
+	MouseButton::Middle,
+	MouseButton::Button4,
+	MouseButton::Button5,
+];
+
+const WHEEL_DIRECTIONS: &[WheelDirection] = &[
+	WheelDirection::Up,
+	WheelDirection::Down,
+	WheelDirection::Left,
+	WheelDirection::Right,
+];
+
+impl From<Key> for Input {
+	fn from(key: Key) -> Self {
+		Input::Key(key)
+	}
+}
+
+impl Input {
+	/// Parses input names as written in keymap files: key names understood by [Key::from_name],
+	/// "Mouse" followed by a [MouseButton] (e.g. "MouseButton4") and "Wheel" followed by a
+	/// [WheelDirection] (e.g. "WheelDown"). Case insensitive.
+	pub fn from_name(name: &str) -> Option<Input> {
+		let input = MOUSE_BUTTONS
+			.iter()
+			.map(|&button| Input::Mouse(button))
+			.chain(WHEEL_DIRECTIONS.iter().map(|&dir| Input::Wheel(dir)))
+			.find(|input| input.to_string().eq_ignore_ascii_case(name));
+		input.or_else(|| Key::from_name(name).map(Input::Key))
+	}
+}
+
+impl Input {
+	/// Name of the input as the user knows it, see [Key::label].
+	pub fn label(&self) -> String {
+		match self {
+			Input::Key(key) => key.label(),
+			_ => self.to_string(),
+		}
+	}
+}
+
+impl Display for Input {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Input::Key(key) => write!(f, "{key:?}"),
+			Input::Mouse(button) => write!(f, "Mouse{button:?}"),
+			Input::Wheel(direction) => write!(f, "Wheel{direction:?}"),
+		}
+	}
+}
+
+/// Set in the codes of numpad keys that share virtual key codes with keys of the main keyboard.
+pub const NUMPAD_FLAG: u16 = 0x100;
+
+/// Physical position of a key on the keyboard, independent of the layout. Bindings that are about
+/// where keys are rather than what's printed on them (like WASD movement) use these, so that they
+/// stay in place on AZERTY or Dvorak layouts. Constants are named after the key found at the
+/// position on a US QWERTY keyboard.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct ScanCode(pub u16);
+
+impl ScanCode {
+	/// Scancodes are 8 bits with a flag for the extended keys, e.g. the arrows.
+	pub const COUNT: usize = 0x200;
+	const EXTENDED: u16 = 0x100;
+
+	pub const ESCAPE: ScanCode = ScanCode(0x01);
+	pub const Q: ScanCode = ScanCode(0x10);
+	pub const W: ScanCode = ScanCode(0x11);
+	pub const E: ScanCode = ScanCode(0x12);
+	pub const LEFT_BRACE: ScanCode = ScanCode(0x1A);
+	pub const RIGHT_BRACE: ScanCode = ScanCode(0x1B);
+	pub const A: ScanCode = ScanCode(0x1E);
+	pub const S: ScanCode = ScanCode(0x1F);
+	pub const D: ScanCode = ScanCode(0x20);
+	pub const Z: ScanCode = ScanCode(0x2C);
+	pub const X: ScanCode = ScanCode(0x2D);
+	pub const C: ScanCode = ScanCode(0x2E);
+	pub const SPACE: ScanCode = ScanCode(0x39);
+	pub const UP: ScanCode = ScanCode(Self::EXTENDED | 0x48);
+	pub const LEFT: ScanCode = ScanCode(Self::EXTENDED | 0x4B);
+	pub const RIGHT: ScanCode = ScanCode(Self::EXTENDED | 0x4D);
+	pub const DOWN: ScanCode = ScanCode(Self::EXTENDED | 0x50);
+
+	/// Scancode of a WM_KEYDOWN or WM_KEYUP message, stored in bits 16 to 24 of its lParam.
+	pub fn from_lparam(l_param: LPARAM) -> ScanCode {
+		ScanCode(((l_param.0 >> 16) & 0x1FF) as u16)
+	}
+
+	/// Whether the key sends the extended key prefix, which sets apart e.g. the arrows from the
+	/// numpad keys in the same place.
+	pub fn is_extended(self) -> bool {
+		self.0 & Self::EXTENDED != 0
+	}
+
+	pub fn index(self) -> usize {
+		self.0 as usize % Self::COUNT
+	}
+}
+
+impl Key {
+	/// Every key.
+	pub const ALL: &'static [Key] = &[
+		Key::Num0,
+		Key::Num1,
+		Key::Num2,
+		Key::Num3,
+		Key::Num4,
+		Key::Num5,
+		Key::Num6,
+		Key::Num7,
+		Key::Num8,
+		Key::Num9,
+		Key::A,
+		Key::B,
+		Key::C,
+		Key::D,
+		Key::E,
+		Key::F,
+		Key::G,
+		Key::H,
+		Key::I,
+		Key::J,
+		Key::K,
+		Key::L,
+		Key::M,
+		Key::N,
+		Key::O,
+		Key::P,
+		Key::Q,
+		Key::R,
+		Key::S,
+		Key::T,
+		Key::U,
+		Key::V,
+		Key::W,
+		Key::X,
+		Key::Y,
+		Key::Z,
+		Key::Lbutton,
+		Key::Rbutton,
+		Key::Cancel,
+		Key::Mbutton,
+		Key::Xbutton1,
+		Key::Xbutton2,
+		Key::Back,
+		Key::Tab,
+		Key::Clear,
+		Key::Return,
+		Key::Shift,
+		Key::Control,
+		Key::Menu,
+		Key::Pause,
+		Key::Capital,
+		Key::Kana,
+		Key::Escape,
+		Key::Convert,
+		Key::Nonconvert,
+		Key::Accept,
+		Key::Modechange,
+		Key::Space,
+		Key::Prior,
+		Key::Next,
+		Key::End,
+		Key::Home,
+		Key::Left,
+		Key::Up,
+		Key::Right,
+		Key::Down,
+		Key::Select,
+		Key::Print,
+		Key::Execute,
+		Key::Snapshot,
+		Key::Insert,
+		Key::Delete,
+		Key::Help,
+		Key::Lwin,
+		Key::Rwin,
+		Key::Apps,
+		Key::Sleep,
+		Key::Numpad0,
+		Key::Numpad1,
+		Key::Numpad2,
+		Key::Numpad3,
+		Key::Numpad4,
+		Key::Numpad5,
+		Key::Numpad6,
+		Key::Numpad7,
+		Key::Numpad8,
+		Key::Numpad9,
+		Key::Multiply,
+		Key::Add,
+		Key::Separator,
+		Key::Subtract,
+		Key::Decimal,
+		Key::Divide,
+		Key::F1,
+		Key::F2,
+		Key::F3,
+		Key::F4,
+		Key::F5,
+		Key::F6,
+		Key::F7,
+		Key::F8,
+		Key::F9,
+		Key::F10,
+		Key::F11,
+		Key::F12,
+		Key::F13,
+		Key::F14,
+		Key::F15,
+		Key::F16,
+		Key::F17,
+		Key::F18,
+		Key::F19,
+		Key::F20,
+		Key::F21,
+		Key::F22,
+		Key::F23,
+		Key::F24,
+		Key::NavigationView,
+		Key::NavigationMenu,
+		Key::NavigationUp,
+		Key::NavigationDown,
+		Key::NavigationLeft,
+		Key::NavigationRight,
+		Key::NavigationAccept,
+		Key::NavigationCancel,
+		Key::Numlock,
+		Key::Scroll,
+		Key::Lshift,
+		Key::Rshift,
+		Key::Lcontrol,
+		Key::Rcontrol,
+		Key::Lmenu,
+		Key::Rmenu,
+		Key::BrowserBack,
+		Key::BrowserForward,
+		Key::BrowserRefresh,
+		Key::BrowserStop,
+		Key::BrowserSearch,
+		Key::BrowserFavorites,
+		Key::BrowserHome,
+		Key::VolumeMute,
+		Key::VolumeDown,
+		Key::VolumeUp,
+		Key::MediaNextTrack,
+		Key::MediaPrevTrack,
+		Key::MediaStop,
+		Key::MediaPlayPause,
+		Key::LaunchMail,
+		Key::LaunchMediaSelect,
+		Key::LaunchApp1,
+		Key::LaunchApp2,
+		Key::Oem1,
+		Key::OemPlus,
+		Key::OemComma,
+		Key::OemMinus,
+		Key::OemPeriod,
+		Key::Oem2,
+		Key::Oem3,
+		Key::GamepadA,
+		Key::GamepadB,
+		Key::GamepadX,
+		Key::GamepadY,
+		Key::GamepadRightShoulder,
+		Key::GamepadLeftShoulder,
+		Key::GamepadLeftTrigger,
+		Key::GamepadRightTrigger,
+		Key::GamepadDpadUp,
+		Key::GamepadDpadDown,
+		Key::GamepadDpadLeft,
+		Key::GamepadDpadRight,
+		Key::GamepadMenu,
+		Key::GamepadView,
+		Key::GamepadLeftThumbstickButton,
+		Key::GamepadRightThumbstickButton,
+		Key::GamepadLeftThumbstickUp,
+		Key::GamepadLeftThumbstickDown,
+		Key::GamepadLeftThumbstickRight,
+		Key::GamepadLeftThumbstickLeft,
+		Key::GamepadRightThumbstickUp,
+		Key::GamepadRightThumbstickDown,
+		Key::GamepadRightThumbstickRight,
+		Key::GamepadRightThumbstickLeft,
+		Key::LeftBrace,
+		Key::RightBrace,
+		Key::NumpadEnter,
+		Key::NumpadPrior,
+		Key::NumpadNext,
+		Key::NumpadEnd,
+		Key::NumpadHome,
+		Key::NumpadLeft,
+		Key::NumpadUp,
+		Key::NumpadRight,
+		Key::NumpadDown,
+		Key::NumpadInsert,
+		Key::NumpadDelete,
+	];
+
+	/// Key code of a WM_KEYDOWN or WM_KEYUP message. The virtual key code of the message, except
+	/// for the numpad keys that share it with keys of the main keyboard: Enter and, with Num Lock
+	/// off, the navigation keys. Those get the code with [NUMPAD_FLAG] set, telling them apart
+	/// by the extended key flag of their scancodes.
+	pub fn from_message(virtual_key: u16, scancode: ScanCode) -> u16 {
+		let from_numpad = match Key::from_code(virtual_key) {
+			Some(Key::Return) => scancode.is_extended(),
+			Some(
+				Key::Prior
+				| Key::Next
+				| Key::End
+				| Key::Home
+				| Key::Left
+				| Key::Up
+				| Key::Right
+				| Key::Down
+				| Key::Insert
+				| Key::Delete,
+			) => !scancode.is_extended(),
+			_ => false,
+		};
+		match from_numpad {
+			true => virtual_key | NUMPAD_FLAG,
+			false => virtual_key,
+		}
+	}
+
+	/// The key of the main keyboard that a numpad key shares its virtual key code with, e.g.
+	/// [Key::Return] for [Key::NumpadEnter]. Other keys are their own main key.
+	pub fn main_key(self) -> Key {
+		Key::from_code(self as u16 & !NUMPAD_FLAG).unwrap_or(self)
+	}
+
+	/// Name of the key as the user knows it in the active keyboard layout: the character it types,
+	/// like "Z" for [Key::Y] on a German keyboard, or else the name Windows gives it in the
+	/// language of the layout, like "Num Enter". Keys unknown to the layout, e.g. gamepad buttons,
+	/// go by their variant names.
+	pub fn label(self) -> String {
+		let layout = unsafe { GetKeyboardLayout(0) };
+		let main_key = self.main_key();
+		let from_numpad = main_key != self;
+		let scancode = unsafe { MapVirtualKeyExW(main_key as u32, MAPVK_VK_TO_VSC_EX, layout) };
+		if scancode == 0 {
+			return format!("{self:?}");
+		}
+
+		// Numpad digits and operators type the same characters as keys of the main keyboard
+		let on_numpad =
+			from_numpad || (Key::Numpad0 as u16..=Key::Divide as u16).contains(&(self as u16));
+		if !on_numpad {
+			if let Some(c) = typed_char(self, scancode, layout) {
+				return c.to_uppercase().collect();
+			}
+		}
+		let extended = match self {
+			Key::NumpadEnter => true,
+			_ if from_numpad => false,
+			_ => scancode >> 8 == 0xE0,
+		};
+		key_name(scancode & 0xFF, extended).unwrap_or_else(|| format!("{self:?}"))
+	}
+
+	/// Key with the given virtual key code.
+	pub fn from_code(code: u16) -> Option<Key> {
+		Key::ALL.iter().copied().find(|&key| key as u16 == code)
+	}
+
+	/// Parses a key name as written in keymap files: a variant name like "F5" or "Back", or
+	/// one of the common aliases like "Backspace". Case insensitive.
+	pub fn from_name(name: &str) -> Option<Key> {
+		const ALIASES: &[(&str, Key)] = &[
+			("Backspace", Key::Back),
+			("Enter", Key::Return),
+			("Esc", Key::Escape),
+			("PageUp", Key::Prior),
+			("PageDown", Key::Next),
+			("NumpadPageUp", Key::NumpadPrior),
+			("NumpadPageDown", Key::NumpadNext),
+			("Alt", Key::Menu),
+			("Ctrl", Key::Control),
+			("Del", Key::Delete),
+			("Ins", Key::Insert),
+		];
+		let alias = ALIASES
+			.iter()
+			.find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+			.map(|&(_, key)| key);
+		alias.or_else(|| {
+			Key::ALL
+				.iter()
+				.copied()
+				.find(|key| format!("{key:?}").eq_ignore_ascii_case(name))
+		})
+	}
+}
+
+/// Visible character `key` types in `layout` without any modifiers held.
+fn typed_char(key: Key, scancode: u32, layout: HKL) -> Option<char> {
+	let mut buf = [0_u16; 8];
+	// Flag 0x4 keeps dead keys from changing what the next key typed by the user turns into
+	let len = unsafe { ToUnicodeEx(key as u32, scancode, &[0; 256], &mut buf, 0x4, layout) };
+	// Negative for dead keys, which still put their character into the buffer
+	let len = (len.unsigned_abs() as usize).min(buf.len());
+	let mut chars = char::decode_utf16(buf[..len].iter().copied());
+	let c = chars.next()?.ok()?;
+	(chars.next().is_none() && !c.is_control() && !c.is_whitespace()).then_some(c)
+}
+
+/// Name of the key with the given scancode in the language of the keyboard layout.
+fn key_name(scancode: u32, extended: bool) -> Option<String> {
+	let l_param = scancode << 16 | (extended as u32) << 24;
+	let mut buf = [0_u16; 64];
+	let len = unsafe { GetKeyNameTextW(l_param as i32, &mut buf) };
+	(len > 0).then(|| from_utf16_null_terminated(&buf))
 }
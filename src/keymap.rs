@@ -0,0 +1,268 @@
+//! User-rebindable keymap. Commands are bound to the built-in [KEY_BINDINGS] and
+//! [CHORD_BINDINGS] unless the keymap file in the user's application data directory says
+//! otherwise:
+//!
+//! ```text
+//! # Bindings listed here replace the built-in ones of the same command
+//! navigate.up = Alt+Up
+//! navigate.up = Backspace
+//! navigate.back = MouseButton4
+//! navigate.home = G, H
+//! listing.refresh = none
+//! ```
+//!
+//! The `[keybindings]` table of the configuration file works the same way and wins conflicts with
+//! the keymap file.
+
+use std::{fmt::Display, io, path::PathBuf};
+
+use log::{debug, warn};
+
+use crate::command::{CommandRegistry, CHORD_BINDINGS, KEY_BINDINGS};
+use crate::shortcut::{Chord, Shortcuts};
+
+const FILE_NAME: &str = "keymap.txt";
+
+/// Where a binding was written, for error messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Location {
+	/// Line of the keymap file.
+	Line(usize),
+	/// The `[keybindings]` table of the configuration file.
+	Config,
+}
+
+impl Display for Location {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Location::Line(line) => write!(f, "Line {line}"),
+			Location::Config => write!(f, "[keybindings]"),
+		}
+	}
+}
+
+/// Command ids bound to shortcuts and chords.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+	bindings: Vec<(Chord, &'static str)>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: io::Error,
+		filename: PathBuf,
+	},
+	/// A line that isn't a comment nor a `command = shortcut` pair.
+	Syntax {
+		line: usize,
+	},
+	UnknownCommand {
+		at: Location,
+		command: String,
+	},
+	InvalidShortcut {
+		at: Location,
+		shortcut: String,
+	},
+	/// `chord` can't be bound along with a binding of another command, see [Chord::conflicts_with].
+	/// The first binding is kept.
+	Conflict {
+		chord: Chord,
+		kept: &'static str,
+		dropped: &'static str,
+	},
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::Syntax { line } => {
+				write!(f, "Line {line}: expected \"command = shortcut\".")
+			}
+			Error::UnknownCommand { at, command } => {
+				write!(f, "{at}: unknown command \"{command}\".")
+			}
+			Error::InvalidShortcut { at, shortcut } => {
+				write!(f, "{at}: invalid shortcut \"{shortcut}\".")
+			}
+			Error::Conflict {
+				chord,
+				kept,
+				dropped,
+			} => write!(
+				f,
+				"{chord} of {dropped} conflicts with a shortcut of {kept}, {dropped} loses it."
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Default for Keymap {
+	/// The built-in bindings.
+	fn default() -> Self {
+		Keymap {
+			bindings: default_bindings().collect(),
+		}
+	}
+}
+
+impl Keymap {
+	/// Where the user's keymap file lives, if the application data directory is known.
+	pub fn path() -> Option<PathBuf> {
+		let app_data = std::env::var_os("APPDATA")?;
+		Some(
+			PathBuf::from(app_data)
+				.join("file-explorer")
+				.join(FILE_NAME),
+		)
+	}
+
+	/// The built-in bindings overridden by the `[keybindings]` of the configuration and the user's
+	/// keymap file. A missing file isn't an error. Bad lines and conflicting bindings are skipped
+	/// and reported, the rest still applies.
+	pub fn load(
+		commands: &CommandRegistry,
+		config: &[(String, Vec<String>)],
+	) -> (Keymap, Vec<Error>) {
+		let Some(path) = Keymap::path() else {
+			return Keymap::parse("", config, commands);
+		};
+		match std::fs::read_to_string(&path) {
+			Ok(text) => {
+				debug!("Loading keymap from {}", path.display());
+				Keymap::parse(&text, config, commands)
+			}
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				Keymap::parse("", config, commands)
+			}
+			Err(err) => {
+				let (keymap, mut errors) = Keymap::parse("", config, commands);
+				errors.insert(
+					0,
+					Error::Io {
+						err,
+						filename: path,
+					},
+				);
+				(keymap, errors)
+			}
+		}
+	}
+
+	/// Applies the `config` bindings and the keymap file `text` on top of the built-in bindings.
+	/// Every command bound in either loses its built-in bindings, `none` as the shortcut (or no
+	/// shortcuts in the configuration) only removes them.
+	pub fn parse(
+		text: &str,
+		config: &[(String, Vec<String>)],
+		commands: &CommandRegistry,
+	) -> (Keymap, Vec<Error>) {
+		let mut errors = Vec::new();
+		let mut entries: Vec<(Location, &str, &str)> = Vec::new();
+		for (command, shortcuts) in config {
+			if shortcuts.is_empty() {
+				entries.push((Location::Config, command, "none"));
+			}
+			for shortcut in shortcuts {
+				entries.push((Location::Config, command, shortcut));
+			}
+		}
+		for (idx, line) in text.lines().enumerate() {
+			let line_number = idx + 1;
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let Some((command, shortcut)) = line.split_once('=') else {
+				errors.push(Error::Syntax { line: line_number });
+				continue;
+			};
+			entries.push((Location::Line(line_number), command.trim(), shortcut.trim()));
+		}
+
+		let mut rebound: Vec<&'static str> = Vec::new();
+		let mut bindings = Vec::new();
+		for (at, command, shortcut) in entries {
+			let Some(command) = commands.get(command) else {
+				errors.push(Error::UnknownCommand {
+					at,
+					command: command.to_string(),
+				});
+				continue;
+			};
+			if shortcut.eq_ignore_ascii_case("none") {
+				rebound.push(command.id);
+				continue;
+			}
+			// Invalid lines leave the built-in bindings alone
+			match Chord::parse(shortcut) {
+				Some(chord) => {
+					rebound.push(command.id);
+					bindings.push((chord, command.id));
+				}
+				None => errors.push(Error::InvalidShortcut {
+					at,
+					shortcut: shortcut.to_string(),
+				}),
+			}
+		}
+
+		// User bindings go first so that they win conflicts with the built-in ones
+		let defaults = default_bindings().filter(|(_, command_id)| !rebound.contains(command_id));
+		let mut keymap = Keymap {
+			bindings: Vec::new(),
+		};
+		for (chord, command_id) in bindings.into_iter().chain(defaults) {
+			let conflict = keymap
+				.bindings
+				.iter()
+				.find(|(bound, _)| bound.conflicts_with(&chord));
+			match conflict {
+				Some(&(_, kept)) if kept == command_id => {}
+				Some(&(_, kept)) => errors.push(Error::Conflict {
+					chord,
+					kept,
+					dropped: command_id,
+				}),
+				None => keymap.bindings.push((chord, command_id)),
+			}
+		}
+		for err in &errors {
+			warn!("Keymap: {err}");
+		}
+		(keymap, errors)
+	}
+
+	/// Command bound to `chord`.
+	pub fn command(&self, chord: Chord) -> Option<&'static str> {
+		self.bindings
+			.iter()
+			.find(|(bound, _)| *bound == chord)
+			.map(|&(_, command_id)| command_id)
+	}
+
+	/// Shortcuts and chords bound to `command_id`.
+	pub fn chords_of<'a>(&'a self, command_id: &'a str) -> impl Iterator<Item = Chord> + 'a {
+		self.bindings
+			.iter()
+			.filter(move |(_, bound)| *bound == command_id)
+			.map(|&(chord, _)| chord)
+	}
+
+	pub fn shortcuts(&self) -> Shortcuts<&'static str> {
+		self.bindings.iter().copied().collect()
+	}
+}
+
+fn default_bindings() -> impl Iterator<Item = (Chord, &'static str)> {
+	KEY_BINDINGS
+		.iter()
+		.map(|&(shortcut, command_id)| (Chord::from(shortcut), command_id))
+		.chain(CHORD_BINDINGS.iter().copied())
+}
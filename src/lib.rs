@@ -0,0 +1,82 @@
+//! File explorer with its own windowing, software rendering and image decoding on top of Win32.
+//! The binary only runs [app::run], the layers below it can be used on their own.
+
+pub mod accessibility;
+pub mod animation;
+pub mod app;
+pub mod archive;
+pub mod arena;
+pub mod args;
+pub mod assets;
+pub mod atlas;
+pub mod bmp;
+pub mod breadcrumb;
+pub mod cancel;
+pub mod clipboard;
+pub mod command;
+pub mod config;
+pub mod crash;
+pub mod debug_panel;
+#[cfg(feature = "debug_server")]
+pub mod debug_server;
+pub mod deflate;
+#[cfg(feature = "demo")]
+pub mod demo;
+pub mod draw;
+pub mod error;
+pub mod event;
+pub mod filename;
+pub mod font;
+pub mod fs;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod gif;
+pub mod grid;
+pub mod i18n;
+pub mod ico;
+pub mod icons;
+pub mod image;
+pub mod jobs;
+#[cfg(feature = "jpeg")]
+pub mod jpeg;
+pub mod json;
+pub mod key;
+pub mod keymap;
+pub mod list_view;
+pub mod logger;
+pub mod navigation;
+pub mod ops;
+pub mod overlay;
+pub mod palette;
+pub mod pane;
+pub mod plugin;
+pub mod png;
+pub mod profiler;
+pub mod qoi;
+pub mod recording;
+pub mod rubber_band;
+pub mod screen;
+pub mod search;
+pub mod search_index;
+pub mod selection;
+pub mod shell_icons;
+pub mod shortcut;
+pub mod sidebar;
+pub mod state_dump;
+pub mod status_bar;
+pub mod string;
+pub mod text_input;
+pub mod thumbnail_cache;
+pub mod thumbnails;
+pub mod toast;
+pub mod toml;
+pub mod toolbar;
+pub mod touch;
+pub mod ttf;
+pub mod ui;
+pub mod uia;
+pub mod update_thread;
+pub mod vector;
+pub mod watcher;
+pub mod widgets;
+pub mod window;
@@ -0,0 +1,507 @@
+//! List view of the current directory, one row per entry with folders first. Like the thumbnail
+//! grid's, the listing runs on a job so slow directories (e.g. network shares) don't stall frames.
+//! The rows are a [FileList], this adds the listing, sorting, quick filter and mouse on top.
+//!
+//! A header row above the entries names the columns, clicking one sorts by it. Like the grid's,
+//! the [Selection] may hold several entries, picked with Shift and Ctrl or a rubber band.
+//!
+//! Typing narrows the entries down to the names matching the typed text, the quick filter, until
+//! it's cleared with Escape or another directory is listed. Text with wildcards filters like the
+//! recursive search does, e.g. `*.png`.
+
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use crate::draw::{draw_rectangle, draw_text, draw_texture_clipped, Rect, Texture};
+use crate::error::{self, Context};
+use crate::font;
+use crate::fs::{DirListing, Entry, SortKey, SortOrder};
+use crate::i18n::tr;
+use crate::icons;
+use crate::jobs::{self, Priority};
+use crate::key::Key;
+use crate::rubber_band::RubberBand;
+use crate::selection::{Selection, SelectionMode};
+use crate::string::{self, Glob};
+use crate::widgets::{columns, Column, FileList, ICON_SIZE, PADDING, ROW_HEIGHT, ROW_PADDING};
+use crate::window::{BitmapData, Keyboard, Mouse};
+
+const HEADER_HEIGHT: usize = ROW_HEIGHT;
+const SORT_ICON_SIZE: usize = 12;
+/// Two clicks on the same row within this time open it.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+/// How fast the view scrolls while rubber-band selecting past its edge, in pixels per frame.
+const DRAG_SCROLL_SPEED: usize = 16;
+
+const HEADER_COLOR: u32 = 0x32302f;
+const HEADER_TEXT_COLOR: u32 = 0xa89984;
+const SORTED_HEADER_TEXT_COLOR: u32 = 0xebdbb2;
+const FILTER_COLOR: u32 = 0x1d2021;
+const FILTER_TEXT_COLOR: u32 = 0xfabd2f;
+
+#[derive(Debug)]
+pub struct ListView {
+	/// Directory `entries` were listed from, None before the first listing and after
+	/// [ListView::reload].
+	dir: Option<PathBuf>,
+	/// Rows of the entries shown, those matching [ListView::filter].
+	list: FileList,
+	/// All entries while the quick filter is in use, empty otherwise.
+	unfiltered: Vec<Entry>,
+	/// Text of the quick filter, see [ListView::update_filter].
+	filter: String,
+	/// Directory being listed, and where its entries will arrive.
+	pending: Option<(PathBuf, Receiver<error::Result<Vec<Entry>>>)>,
+	rubber_band: Option<RubberBand>,
+	/// Entry to select once it's listed, see [ListView::select_path].
+	select_on_load: Option<PathBuf>,
+	/// Whether to scroll the selection's cursor into view on the next update.
+	reveal_selected: bool,
+	last_click: Option<(usize, Instant)>,
+	show_hidden: bool,
+	sort: SortOrder,
+	sort_ascending_icon: Texture,
+	sort_descending_icon: Texture,
+}
+
+impl Default for ListView {
+	fn default() -> Self {
+		ListView {
+			dir: None,
+			list: FileList::default(),
+			unfiltered: Vec::new(),
+			filter: String::new(),
+			pending: None,
+			rubber_band: None,
+			select_on_load: None,
+			reveal_selected: false,
+			last_click: None,
+			show_hidden: false,
+			sort: SortOrder::default(),
+			sort_ascending_icon: icons::SORT_ASCENDING.rasterize(SORT_ICON_SIZE),
+			sort_descending_icon: icons::SORT_DESCENDING.rasterize(SORT_ICON_SIZE),
+		}
+	}
+}
+
+impl ListView {
+	/// The entries shown, without those the quick filter hides.
+	pub fn entries(&self) -> &[Entry] {
+		self.list.entries()
+	}
+
+	pub fn filter(&self) -> &str {
+		&self.filter
+	}
+
+	pub fn selection(&self) -> &Selection {
+		self.list.selection()
+	}
+
+	/// Lists the directory again on the next [ListView::poll], keeping the entries until then. A
+	/// listing in progress is dropped, it may miss the changes that prompted the reload.
+	pub fn reload(&mut self) {
+		self.dir = None;
+		self.pending = None;
+	}
+
+	/// Selects the entry at `path` and scrolls to it, once it shows up in the next listing.
+	pub fn select_path(&mut self, path: PathBuf) {
+		self.select_on_load = Some(path);
+	}
+
+	/// Scrolls the selection's cursor into view on the next update, e.g. after the viewport shrank.
+	pub fn reveal_selected(&mut self) {
+		self.reveal_selected = true;
+	}
+
+	/// Whether hidden entries are listed, see [crate::fs::Attributes::hidden]. Changing it lists
+	/// the directory again. Returns true if it changed.
+	pub fn set_show_hidden(&mut self, show_hidden: bool) -> bool {
+		if self.show_hidden == show_hidden {
+			return false;
+		}
+		self.show_hidden = show_hidden;
+		self.reload();
+		true
+	}
+
+	pub fn sort_order(&self) -> SortOrder {
+		self.sort
+	}
+
+	/// Sorts the entries in `order`, keeping the selected entries selected and the cursor in view.
+	pub fn set_sort_order(&mut self, order: SortOrder) {
+		if self.sort == order {
+			return;
+		}
+		self.sort = order;
+		// A listing in progress is sorted the old way, it's started again
+		self.pending = None;
+		let paths = self.list.paths();
+		self.list.entries_mut().sort_by(|a, b| a.cmp_by(b, &order));
+		self.unfiltered.sort_by(|a, b| a.cmp_by(b, &order));
+		self.list.follow_selection(&paths);
+		self.reveal_selected = true;
+	}
+
+	/// Adds the text typed in the current frame to the quick filter. While it's in use, Backspace
+	/// removes its last character and Escape clears it. Takes the presses it handles from the
+	/// handlers after it, so that they don't trigger shortcuts too.
+	///
+	/// With `chording`, while the presses go to a chord, e.g. `G` then `H`, typing doesn't start
+	/// the filter.
+	pub fn update_filter(&mut self, keyboard: &mut Keyboard, chording: bool) {
+		if self.filter.is_empty() && chording {
+			return;
+		}
+		let mut filter = self.filter.clone();
+		let typed = keyboard.typed_text();
+		// Nothing to filter by yet
+		let typed = match filter.is_empty() {
+			true => typed.trim_start(),
+			false => typed,
+		};
+		if !typed.is_empty() {
+			filter.push_str(typed);
+			keyboard.consume_typing();
+		}
+		if !self.filter.is_empty() {
+			if keyboard.was_pressed(Key::Back) {
+				filter.pop();
+				keyboard.consume_press(Key::Back);
+			}
+			if keyboard.was_pressed(Key::Escape) {
+				filter.clear();
+				keyboard.consume_press(Key::Escape);
+			}
+		}
+		if filter != self.filter {
+			self.set_filter(filter);
+		}
+	}
+
+	/// Shows only the entries whose names match `filter`, see [ListView::matching]. The selected
+	/// entries that match stay selected, the first match is selected if none of them do.
+	fn set_filter(&mut self, filter: String) {
+		let paths = self.list.paths();
+		if self.filter.is_empty() {
+			self.unfiltered = mem::take(self.list.entries_mut());
+		}
+		self.filter = filter;
+		*self.list.entries_mut() = match self.filter.is_empty() {
+			true => mem::take(&mut self.unfiltered),
+			false => self.matching(&self.unfiltered),
+		};
+		self.list.follow_selection(&paths);
+		if self.list.selection().is_empty() && !self.list.entries().is_empty() {
+			self.list.selection_mut().select_only(0);
+		}
+		self.reveal_selected = true;
+	}
+
+	/// Those of `entries` matching the quick filter: its [Glob] if it has wildcards, otherwise
+	/// [string::fuzzy_match]. A class that wasn't closed yet while typing falls back to the latter.
+	fn matching(&self, entries: &[Entry]) -> Vec<Entry> {
+		let glob = match self.filter.contains(['*', '?', '[']) {
+			true => Glob::new(&self.filter).ok(),
+			false => None,
+		};
+		entries
+			.iter()
+			.filter(|entry| match &glob {
+				Some(glob) => glob.matches(&entry.name),
+				None => string::fuzzy_match(&self.filter, &entry.name).is_some(),
+			})
+			.cloned()
+			.collect()
+	}
+
+	/// Starts listing `dir` if it isn't listed or being listed yet, and returns the number of
+	/// entries once the listing finished.
+	pub fn poll(&mut self, dir: &Path) -> Option<error::Result<usize>> {
+		let pending = self.pending.as_ref().map(|(pending, _)| pending.as_path());
+		if self.dir.as_deref() != Some(dir) && pending != Some(dir) {
+			let (sender, result) = mpsc::channel();
+			let dir = dir.to_path_buf();
+			let (show_hidden, sort) = (self.show_hidden, self.sort);
+			self.pending = Some((dir.clone(), result));
+			jobs::spawn(Priority::Medium, move || {
+				let listing = DirListing::read(&dir, show_hidden).map(|mut listing| {
+					listing.sort_by(&sort);
+					listing.entries
+				});
+				// The listing is dropped if the directory changed again in the meantime
+				let _ = sender.send(listing.with_path(&dir));
+			});
+		}
+
+		let (_, result) = self.pending.as_ref()?;
+		// Also None if the job was dropped by the jobs shutting down
+		let entries = result.try_recv().ok()?;
+		let (dir, _) = self.pending.take().unwrap();
+		Some(entries.map(|entries| {
+			let count = entries.len();
+			// Reloading the same directory keeps the selection where it was, and the filter
+			let reloaded = self.dir.as_ref() == Some(&dir);
+			let (paths, cursor) = (self.list.paths(), self.list.selection().cursor());
+			if !reloaded {
+				self.list.selection_mut().clear();
+				self.list.scroll_to_top();
+				self.filter.clear();
+			}
+			// Its rows may have moved
+			self.rubber_band = None;
+			match self.filter.is_empty() {
+				true => {
+					*self.list.entries_mut() = entries;
+					self.unfiltered = Vec::new();
+				}
+				false => {
+					*self.list.entries_mut() = self.matching(&entries);
+					self.unfiltered = entries;
+				}
+			}
+			if reloaded {
+				self.list.follow_selection(&paths);
+				// E.g. the selected entry was deleted, the one after it takes its place
+				let len = self.list.entries().len();
+				if let Some(cursor) = cursor.filter(|_| self.list.selection().is_empty()) {
+					if len > 0 {
+						self.list.selection_mut().select_only(cursor.min(len - 1));
+					}
+				}
+			}
+			self.dir = Some(dir);
+			if let Some(path) = self.select_on_load.take() {
+				let entries = self.list.entries();
+				if let Some(idx) = entries.iter().position(|entry| entry.path == path) {
+					self.list.selection_mut().select_only(idx);
+					self.reveal_selected = true;
+				}
+			}
+			count
+		}))
+	}
+
+	/// Handles input of the current frame, see [FileList::handle_key], [FileList::handle_scroll]
+	/// and [ListView::handle_mouse]. Returns the folder to open, after Enter or a double click.
+	pub fn update(
+		&mut self,
+		viewport: Rect,
+		mouse: &Mouse,
+		keyboard: &Keyboard,
+	) -> Option<PathBuf> {
+		let (header, rows) = split_header(viewport);
+		if mouse.was_left_pressed() && header.contains(mouse.position()) {
+			let (mouse_x, _) = mouse.position();
+			let clicked = columns(viewport)
+				.into_iter()
+				.find(|column| (column.x..column.x + column.width).contains(&(mouse_x as usize)));
+			if let Some(column) = clicked {
+				self.set_sort_order(self.sort.toggled(column.key));
+			}
+		}
+
+		if let Some(cursor) = self.list.selection().cursor().filter(|_| self.reveal_selected) {
+			self.list.scroll_to(cursor, rows.height);
+		}
+		self.reveal_selected = false;
+
+		let open = self.list.handle_key(keyboard, rows.height);
+		let hovered = rows.contains(mouse.position());
+		let scroll_y = match hovered {
+			true => mouse.scroll_pixels().1 as isize,
+			false => 0,
+		};
+		// Also brings the offset back in range after the entries changed
+		self.list.handle_scroll(scroll_y, rows.height);
+		open.or(self.handle_mouse(mouse, keyboard, rows))
+	}
+
+	/// Selects just the entry at `idx` and scrolls it into view of the list shown in `viewport`.
+	pub fn select_only(&mut self, idx: usize, viewport: Rect) {
+		let (_, rows) = split_header(viewport);
+		self.list.select_only(idx, rows.height);
+	}
+
+	/// Scrolls by `delta` pixels, down if positive, with the list shown in `viewport`.
+	pub fn handle_scroll(&mut self, delta: isize, viewport: Rect) {
+		let (_, rows) = split_header(viewport);
+		self.list.handle_scroll(delta, rows.height);
+	}
+
+	/// Clicks select single rows, dragging selects every row touched by the rubber band. Returns
+	/// the folder of a row clicked twice in a row, a double click.
+	fn handle_mouse(&mut self, mouse: &Mouse, keyboard: &Keyboard, rows: Rect) -> Option<PathBuf> {
+		let (mouse_x, mouse_y) = mouse.position();
+		let content_pos = (
+			(mouse_x - rows.x as i32).clamp(0, rows.width as i32) as usize,
+			(mouse_y - rows.y as i32).clamp(0, rows.height as i32) as usize + self.list.scroll(),
+		);
+		let len = self.list.entries().len();
+
+		if mouse.was_left_pressed() && rows.contains(mouse.position()) {
+			let row = self.list.row_at(content_pos.1);
+			let now = Instant::now();
+			let double_click = row < len
+				&& self.last_click.is_some_and(|(last_row, time)| {
+					last_row == row && now - time < DOUBLE_CLICK_TIME
+				});
+			if double_click {
+				self.last_click = None;
+				return self.list.folder(row);
+			}
+			self.last_click = Some((row, now));
+			let mode = SelectionMode::from_modifiers(keyboard);
+			let base = self.list.selection().clone();
+			self.rubber_band = Some(RubberBand::begin(content_pos, base, mode));
+		}
+
+		let band = self.rubber_band.as_mut()?;
+		band.drag_to(content_pos);
+
+		if band.is_active() {
+			// Scroll when dragging past the top or bottom edge
+			if mouse_y < rows.y as i32 {
+				self.list
+					.handle_scroll(-(DRAG_SCROLL_SPEED as isize), rows.height);
+			} else if mouse_y >= (rows.y + rows.height) as i32 {
+				self.list
+					.handle_scroll(DRAG_SCROLL_SPEED as isize, rows.height);
+			}
+
+			let band = self.rubber_band.as_ref().unwrap();
+			let mut selection = Selection::default();
+			selection.combine(band.base(), self.list.rows_in(band.rect()), band.mode());
+			*self.list.selection_mut() = selection;
+		}
+
+		if !mouse.is_left_down() {
+			let band = self.rubber_band.take().unwrap();
+			if !band.is_active() {
+				let row = self.list.row_at(band.start().1);
+				let selection = self.list.selection_mut();
+				match row < len {
+					true => selection.click(row, band.mode()),
+					false if band.mode() == SelectionMode::Replace => selection.clear(),
+					false => (),
+				}
+			}
+		}
+		None
+	}
+
+	/// Draws the header row, the rows intersecting the viewport, the rubber band and the quick
+	/// filter.
+	pub fn render(&self, bitmap_data: BitmapData, viewport: Rect) {
+		let columns = columns(viewport);
+		self.render_header(bitmap_data, viewport, &columns);
+		let (_, rows) = split_header(viewport);
+		self.list.render(bitmap_data, rows, &columns);
+		if let Some(band) = &self.rubber_band {
+			band.render(bitmap_data, rows, self.list.scroll());
+		}
+		self.render_filter(bitmap_data, rows);
+	}
+
+	/// Entries at least partly visible in the list shown in `viewport`, with their rows clipped to
+	/// it.
+	pub fn visible_rows(&self, viewport: Rect) -> impl Iterator<Item = (usize, Rect)> + '_ {
+		let (_, rows) = split_header(viewport);
+		self.list.visible_rows(rows)
+	}
+
+	/// Draws the text of the quick filter in the bottom right corner of `rows`, if it's in use.
+	fn render_filter(&self, bitmap_data: BitmapData, rows: Rect) {
+		if self.filter.is_empty() {
+			return;
+		}
+		let max_width = rows.width.saturating_sub(4 * PADDING);
+		let text = tr!("quick_filter", &self.filter);
+		// The end of the filter is what was typed last
+		let text = font::ui().fitting_end(&text, max_width);
+		let width = font::ui().text_width(text) + 2 * PADDING;
+		let height = ROW_HEIGHT;
+		if rows.width < width + PADDING || rows.height < height + PADDING {
+			return;
+		}
+		let x = rows.x + rows.width - width - PADDING;
+		let y = rows.y + rows.height - height - PADDING;
+		draw_rectangle(bitmap_data, (x, y), (width, height), FILTER_COLOR);
+		draw_text(
+			bitmap_data,
+			font::ui(),
+			text,
+			(x + PADDING, y + ROW_PADDING),
+			FILTER_TEXT_COLOR,
+		);
+	}
+
+	/// Draws the names of the columns, marking the one the entries are sorted by.
+	fn render_header(&self, bitmap_data: BitmapData, viewport: Rect, columns: &[Column]) {
+		let (header, _) = split_header(viewport);
+		draw_rectangle(
+			bitmap_data,
+			(header.x, header.y),
+			(header.width, header.height),
+			HEADER_COLOR,
+		);
+		if header.height < HEADER_HEIGHT {
+			return;
+		}
+		for column in columns {
+			let (label, x) = match column.key {
+				// In line with the names of the entries
+				SortKey::Name => (tr!("column_name"), viewport.x + 2 * PADDING + ICON_SIZE),
+				SortKey::Size => (tr!("column_size"), column.x),
+				SortKey::Modified => (tr!("column_modified"), column.x),
+				SortKey::Type => (tr!("column_type"), column.x),
+			};
+			let sorted = column.key == self.sort.key;
+			let max_width = (column.x + column.width).saturating_sub(x + SORT_ICON_SIZE);
+			let label = font::ui().truncate(&label, max_width);
+			let color = match sorted {
+				true => SORTED_HEADER_TEXT_COLOR,
+				false => HEADER_TEXT_COLOR,
+			};
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&label,
+				(x, header.y + ROW_PADDING),
+				color,
+			);
+			if sorted {
+				let icon = match self.sort.descending {
+					true => &self.sort_descending_icon,
+					false => &self.sort_ascending_icon,
+				};
+				let icon_x = x + font::ui().text_width(&label);
+				let icon_y = header.y + (HEADER_HEIGHT - SORT_ICON_SIZE) / 2;
+				draw_texture_clipped(
+					bitmap_data,
+					icon,
+					(icon_x as isize, icon_y as isize),
+					header,
+				);
+			}
+		}
+	}
+}
+
+/// The header row of `viewport`, and the rest where the entries go.
+fn split_header(viewport: Rect) -> (Rect, Rect) {
+	let height = HEADER_HEIGHT.min(viewport.height);
+	(
+		Rect::new(viewport.x, viewport.y, viewport.width, height),
+		Rect::new(
+			viewport.x,
+			viewport.y + height,
+			viewport.width,
+			viewport.height - height,
+		),
+	)
+}
@@ -0,0 +1,89 @@
+//! Logger printing to stderr, which also keeps the last lines for crash reports and passes every
+//! line on to the receivers of [subscribe].
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Number of lines [tail] keeps.
+const TAIL_LEN: usize = 100;
+
+struct Logger {
+	start: OnceLock<Instant>,
+	tail: Mutex<VecDeque<String>>,
+	subscribers: Mutex<Vec<Sender<String>>>,
+}
+
+static LOGGER: Logger = Logger {
+	start: OnceLock::new(),
+	tail: Mutex::new(VecDeque::new()),
+	subscribers: Mutex::new(Vec::new()),
+};
+
+/// Installs the logger, logging everything up to debug (trace with debug assertions) until
+/// [log::set_max_level] says otherwise.
+pub fn init() {
+	LOGGER.start.get_or_init(Instant::now);
+	if log::set_logger(&LOGGER).is_ok() {
+		log::set_max_level(match cfg!(debug_assertions) {
+			true => LevelFilter::Trace,
+			false => LevelFilter::Debug,
+		});
+	}
+}
+
+/// The last logged lines, oldest first. Empty if the lines are locked, e.g. by a thread that
+/// panicked while logging.
+pub fn tail() -> Vec<String> {
+	match LOGGER.tail.try_lock() {
+		Ok(tail) => tail.iter().cloned().collect(),
+		Err(_) => Vec::new(),
+	}
+}
+
+/// Receives every line logged from now on, until the receiver is dropped.
+pub fn subscribe() -> Receiver<String> {
+	let (sender, receiver) = mpsc::channel();
+	LOGGER.subscribers.lock().unwrap().push(sender);
+	receiver
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= log::max_level()
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let elapsed = self.start.get_or_init(Instant::now).elapsed();
+		let line = format!(
+			"{:>9.3} {:<5} {}: {}",
+			elapsed.as_secs_f64(),
+			record.level(),
+			record.target(),
+			record.args()
+		);
+		let _ = writeln!(std::io::stderr(), "{line}");
+
+		if let Ok(mut subscribers) = self.subscribers.lock() {
+			subscribers.retain(|subscriber| subscriber.send(line.clone()).is_ok());
+		}
+		let Ok(mut tail) = self.tail.lock() else {
+			return;
+		};
+		if tail.len() == TAIL_LEN {
+			tail.pop_front();
+		}
+		tail.push_back(line);
+	}
+
+	fn flush(&self) {
+		let _ = std::io::stderr().flush();
+	}
+}
@@ -0,0 +1,82 @@
+//! Current directory and back/forward history.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct History {
+	current: PathBuf,
+	back: Vec<PathBuf>,
+	forward: Vec<PathBuf>,
+}
+
+impl History {
+	pub fn new(start: PathBuf) -> Self {
+		History {
+			current: start,
+			back: Vec::new(),
+			forward: Vec::new(),
+		}
+	}
+
+	pub fn current(&self) -> &Path {
+		&self.current
+	}
+
+	/// Directories [History::back] goes to, the most recent last.
+	pub fn back_entries(&self) -> &[PathBuf] {
+		&self.back
+	}
+
+	/// Directories [History::forward] goes to, the next one last.
+	pub fn forward_entries(&self) -> &[PathBuf] {
+		&self.forward
+	}
+
+	/// Goes to `path`, recording the current directory in the back history and dropping the
+	/// forward history.
+	pub fn navigate_to(&mut self, path: PathBuf) {
+		if path == self.current {
+			return;
+		}
+		let previous = std::mem::replace(&mut self.current, path);
+		self.back.push(previous);
+		self.forward.clear();
+	}
+
+	/// Returns false if there is nothing to go back to.
+	pub fn back(&mut self) -> bool {
+		let Some(previous) = self.back.pop() else {
+			return false;
+		};
+		let current = std::mem::replace(&mut self.current, previous);
+		self.forward.push(current);
+		true
+	}
+
+	/// Returns false if there is nothing to go forward to.
+	pub fn forward(&mut self) -> bool {
+		let Some(next) = self.forward.pop() else {
+			return false;
+		};
+		let current = std::mem::replace(&mut self.current, next);
+		self.back.push(current);
+		true
+	}
+
+	/// Goes to the parent directory. Returns false if the current directory is a root.
+	pub fn up(&mut self) -> bool {
+		let Some(parent) = self.current.parent().map(Path::to_path_buf) else {
+			return false;
+		};
+		self.navigate_to(parent);
+		true
+	}
+
+	pub fn can_go_back(&self) -> bool {
+		!self.back.is_empty()
+	}
+
+	pub fn can_go_forward(&self) -> bool {
+		!self.forward.is_empty()
+	}
+}
@@ -0,0 +1,390 @@
+//! File operations: recursive copy and move of files and folders into a destination folder. An
+//! [Operation] runs as a job, copying in chunks so that it can report [Progress] and be cancelled
+//! in between, while the main loop polls it once per frame.
+//!
+//! Moves within a volume are renames. Across volumes the items are copied and the sources removed
+//! once everything was copied, so a cancelled move leaves the sources as they were.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::cancel::CancelToken;
+use crate::error::{self, Context, Error, ErrorKind};
+use crate::jobs::{self, Priority};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Progress is reported at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+/// The rate is too noisy for an estimate of the time left before the operation ran this long.
+const ETA_AFTER: Duration = Duration::from_secs(1);
+/// What renaming across volumes fails with.
+const ERROR_NOT_SAME_DEVICE: i32 = 17;
+/// Highest number tried for a name that's taken, see [unused_target].
+const MAX_NAME_NUMBER: u32 = 10_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+	Copy,
+	Move,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+	pub bytes_done: u64,
+	pub bytes_total: u64,
+	/// File being copied.
+	pub current_file: PathBuf,
+	/// Estimated time left, None while it can't be estimated yet.
+	pub eta: Option<Duration>,
+}
+
+impl Progress {
+	/// None while the size of the items isn't known yet.
+	pub fn fraction(&self) -> Option<f32> {
+		(self.bytes_total > 0).then(|| self.bytes_done as f32 / self.bytes_total as f32)
+	}
+}
+
+/// A file or folder to create, and where its contents come from.
+#[derive(Debug)]
+struct Item {
+	source: PathBuf,
+	target: PathBuf,
+	/// 0 for folders.
+	size: u64,
+	is_dir: bool,
+}
+
+/// Copies or moves `sources` into the `destination` folder. Items whose name is taken in the
+/// destination get a number, like "a (2).txt". Returns the paths of the copied or moved items.
+///
+/// `on_progress` is called every [PROGRESS_INTERVAL] while files are copied, and once more when
+/// all of them were. Copies keep the modification time of their source. Once `cancel` is
+/// cancelled, the file being copied is removed and the operation stops with
+/// [ErrorKind::Cancelled], keeping what was copied before.
+pub fn run(
+	kind: Kind,
+	sources: &[PathBuf],
+	destination: &Path,
+	cancel: &CancelToken,
+	on_progress: impl FnMut(&Progress),
+) -> error::Result<Vec<PathBuf>> {
+	// Every target is picked and checked before the first rename, so that a bad source doesn't
+	// leave a move half done
+	let mut targets = Vec::with_capacity(sources.len());
+	for source in sources {
+		if kind == Kind::Move && source.parent() == Some(destination) {
+			targets.push(source.clone());
+			continue;
+		}
+		let target = unused_target(destination, source, &targets)?;
+		if target.starts_with(source) {
+			return Err(
+				Error::new(ErrorKind::Other, "A folder can't be copied into itself")
+					.with_path(source),
+			);
+		}
+		targets.push(target);
+	}
+
+	// Sources that can't be renamed, with their targets
+	let mut to_copy = Vec::new();
+	for (source, target) in sources.iter().zip(&targets) {
+		if source == target {
+			continue;
+		}
+		if kind == Kind::Move {
+			match fs::rename(source, target) {
+				Ok(()) => continue,
+				Err(err) if err.raw_os_error() == Some(ERROR_NOT_SAME_DEVICE) => (),
+				Err(err) => return Err(err).with_path(source),
+			}
+		}
+		to_copy.push((source.clone(), target.clone()));
+	}
+
+	let mut items = Vec::new();
+	let mut skipped_links = Vec::new();
+	for (source, target) in &to_copy {
+		plan(source, target, &mut items, &mut skipped_links)?;
+	}
+	// Removing the sources would remove the links, with nothing copied in their place. The sources
+	// are picked in one folder, so they're on one volume and none of them was renamed yet.
+	if kind == Kind::Move {
+		if let Some(link) = skipped_links.first() {
+			return Err(Error::new(
+				ErrorKind::Unsupported,
+				"Linked folders can't be moved to another volume",
+			)
+			.with_path(link));
+		}
+	}
+	let mut reporter = Reporter {
+		progress: Progress {
+			bytes_total: items.iter().map(|item| item.size).sum(),
+			..Progress::default()
+		},
+		start: Instant::now(),
+		last_report: None,
+		on_progress,
+	};
+	for item in &items {
+		check_cancelled(cancel)?;
+		match item.is_dir {
+			true => fs::create_dir(&item.target).with_path(&item.target)?,
+			false => copy_file(item, cancel, &mut reporter)?,
+		}
+	}
+	reporter.report();
+
+	if kind == Kind::Move {
+		for (source, _) in &to_copy {
+			let removed = match source.is_dir() {
+				true => fs::remove_dir_all(source),
+				false => fs::remove_file(source),
+			};
+			removed.with_path(source)?;
+		}
+	}
+	info!(
+		"{kind:?} of {} items into {} finished",
+		sources.len(),
+		destination.display()
+	);
+	Ok(targets)
+}
+
+/// `destination` joined with the name of `source`, numbered if the name is taken, on disk or by
+/// one of `planned`. Fails if the names up to [MAX_NAME_NUMBER] are all taken.
+fn unused_target(destination: &Path, source: &Path, planned: &[PathBuf]) -> error::Result<PathBuf> {
+	let is_free = |target: &Path| {
+		fs::symlink_metadata(target).is_err() && !planned.iter().any(|planned| planned == target)
+	};
+	let name = source
+		.file_name()
+		.ok_or_else(|| Error::new(ErrorKind::InvalidName, "No file name").with_path(source))?;
+	let target = destination.join(name);
+	if is_free(&target) {
+		return Ok(target);
+	}
+	let stem = Path::new(name)
+		.file_stem()
+		.unwrap_or(name)
+		.to_string_lossy();
+	let extension = match source.is_dir() {
+		true => None,
+		false => source
+			.extension()
+			.map(|extension| extension.to_string_lossy()),
+	};
+	for n in 2..=MAX_NAME_NUMBER {
+		let name = match &extension {
+			Some(extension) => format!("{stem} ({n}).{extension}"),
+			None => format!("{stem} ({n})"),
+		};
+		let target = destination.join(name);
+		if is_free(&target) {
+			return Ok(target);
+		}
+	}
+	let err = io::Error::new(
+		io::ErrorKind::AlreadyExists,
+		format!("No free name for {}", name.to_string_lossy()),
+	);
+	Err(err).with_path(target)
+}
+
+/// Appends `source` to `items`, and everything in it if it's a folder, parents first. Linked
+/// folders aren't followed, they're appended to `skipped_links` instead.
+fn plan(
+	source: &Path,
+	target: &Path,
+	items: &mut Vec<Item>,
+	skipped_links: &mut Vec<PathBuf>,
+) -> error::Result<()> {
+	let metadata = fs::metadata(source).with_path(source)?;
+	if !metadata.is_dir() {
+		items.push(Item {
+			source: source.to_path_buf(),
+			target: target.to_path_buf(),
+			size: metadata.len(),
+			is_dir: false,
+		});
+		return Ok(());
+	}
+	items.push(Item {
+		source: source.to_path_buf(),
+		target: target.to_path_buf(),
+		size: 0,
+		is_dir: true,
+	});
+	for entry in fs::read_dir(source).with_path(source)? {
+		let entry = entry.with_path(source)?;
+		// Linked folders could contain themselves
+		if entry.file_type().with_path(entry.path())?.is_symlink() && entry.path().is_dir() {
+			warn!("Skipping linked folder {}", entry.path().display());
+			skipped_links.push(entry.path());
+			continue;
+		}
+		plan(
+			&entry.path(),
+			&target.join(entry.file_name()),
+			items,
+			skipped_links,
+		)?;
+	}
+	Ok(())
+}
+
+fn copy_file(
+	item: &Item,
+	cancel: &CancelToken,
+	reporter: &mut Reporter<impl FnMut(&Progress)>,
+) -> error::Result<()> {
+	let mut source = File::open(&item.source).with_path(&item.source)?;
+	let mut target = File::options()
+		.write(true)
+		.create_new(true)
+		.open(&item.target)
+		.with_path(&item.target)?;
+	reporter.progress.current_file = item.source.clone();
+	let mut buffer = vec![0; CHUNK_SIZE];
+	let result = (|| {
+		loop {
+			check_cancelled(cancel)?;
+			let len = match source.read(&mut buffer) {
+				Ok(0) => break,
+				Ok(len) => len,
+				Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+				Err(err) => return Err(err).with_path(&item.source),
+			};
+			target.write_all(&buffer[..len]).with_path(&item.target)?;
+			reporter.advance(len as u64);
+		}
+		let metadata = source.metadata().with_path(&item.source)?;
+		let modified = metadata.modified().with_path(&item.source)?;
+		// Before the permissions, which may make the copy read-only
+		target.set_modified(modified).with_path(&item.target)?;
+		target
+			.set_permissions(metadata.permissions())
+			.with_path(&item.target)
+	})();
+	if result.is_err() {
+		// Half copied files are of no use
+		drop(target);
+		let _ = fs::remove_file(&item.target);
+	}
+	result
+}
+
+fn check_cancelled(cancel: &CancelToken) -> error::Result<()> {
+	match cancel.is_cancelled() || jobs::is_shutting_down() {
+		true => Err(Error::new(ErrorKind::Cancelled, "Cancelled")),
+		false => Ok(()),
+	}
+}
+
+struct Reporter<F> {
+	progress: Progress,
+	start: Instant,
+	last_report: Option<Instant>,
+	on_progress: F,
+}
+
+impl<F: FnMut(&Progress)> Reporter<F> {
+	/// Counts `bytes` more as copied, reporting the progress unless it was reported recently.
+	fn advance(&mut self, bytes: u64) {
+		self.progress.bytes_done += bytes;
+		if self
+			.last_report
+			.is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL)
+		{
+			return;
+		}
+		self.report();
+	}
+
+	/// Reports the progress right away, e.g. the final one.
+	fn report(&mut self) {
+		let now = Instant::now();
+		self.last_report = Some(now);
+		let elapsed = now - self.start;
+		let progress = &mut self.progress;
+		progress.eta = (elapsed >= ETA_AFTER && progress.bytes_done > 0).then(|| {
+			let left = progress.bytes_total.saturating_sub(progress.bytes_done);
+			elapsed.mul_f64(left as f64 / progress.bytes_done as f64)
+		});
+		(self.on_progress)(progress);
+	}
+}
+
+#[derive(Debug)]
+enum Message {
+	Progress(Progress),
+	Finished(error::Result<Vec<PathBuf>>),
+}
+
+/// A copy or move running in the background, see [run].
+#[derive(Debug)]
+pub struct Operation {
+	kind: Kind,
+	cancel: CancelToken,
+	messages: Receiver<Message>,
+	progress: Progress,
+}
+
+impl Operation {
+	pub fn start(kind: Kind, sources: Vec<PathBuf>, destination: PathBuf) -> Self {
+		let (sender, messages) = mpsc::channel();
+		let cancel = CancelToken::new();
+		let token = cancel.clone();
+		jobs::spawn(Priority::High, move || {
+			let progress_sender = sender.clone();
+			let result = run(kind, &sources, &destination, &token, |progress| {
+				let _ = progress_sender.send(Message::Progress(progress.clone()));
+			});
+			let _ = sender.send(Message::Finished(result));
+		});
+		Operation {
+			kind,
+			cancel,
+			messages,
+			progress: Progress::default(),
+		}
+	}
+
+	pub fn kind(&self) -> Kind {
+		self.kind
+	}
+
+	/// Progress as of the last [Operation::poll].
+	pub fn progress(&self) -> &Progress {
+		&self.progress
+	}
+
+	/// Stops the operation after the chunk being copied, see [run].
+	pub fn cancel(&self) {
+		self.cancel.cancel();
+	}
+
+	/// Takes the progress reported since the last call. Returns the result of [run] once the
+	/// operation finished.
+	pub fn poll(&mut self) -> Option<error::Result<Vec<PathBuf>>> {
+		loop {
+			match self.messages.try_recv() {
+				Ok(Message::Progress(progress)) => self.progress = progress,
+				Ok(Message::Finished(result)) => return Some(result),
+				Err(TryRecvError::Empty) => return None,
+				// The job was dropped by the jobs shutting down
+				Err(TryRecvError::Disconnected) => {
+					return Some(Err(Error::new(ErrorKind::Cancelled, "Cancelled")))
+				}
+			}
+		}
+	}
+}
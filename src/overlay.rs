@@ -0,0 +1,127 @@
+//! Layers drawn above the rest of the UI: menus, autocomplete dropdowns, tooltips, drag previews.
+//!
+//! A widget opens a layer under an id of its choosing and records the layer's drawing into a
+//! [DrawList]. [Overlays] keeps the layers stacked in the order they were opened, draws them after
+//! the normal content and dismisses popups in one place: Escape closes the topmost popup, a click
+//! outside of all popups closes every popup. Clicks inside a popup are taken away from the widgets
+//! below and kept for the owner of the popup, see [Overlays::take_click].
+
+use crate::draw::{DrawList, Rect};
+use crate::key::Key;
+use crate::window::{BitmapData, Keyboard, Mouse};
+
+pub type LayerId = &'static str;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayerKind {
+	/// Menus and dropdowns. Receive clicks and get dismissed by Escape or a click outside.
+	Popup,
+	/// Tooltips and drag previews. Only drawn, input goes through to whatever is below.
+	Passive,
+}
+
+#[derive(Debug)]
+struct Layer {
+	id: LayerId,
+	kind: LayerKind,
+	bounds: Rect,
+	draw_list: DrawList,
+}
+
+#[derive(Debug, Default)]
+pub struct Overlays {
+	/// Bottom to top.
+	layers: Vec<Layer>,
+	/// Click on a popup in the current frame, not yet taken by the popup's owner.
+	click: Option<(LayerId, (i32, i32))>,
+}
+
+impl Overlays {
+	/// Opens a layer on top of the others. A layer that's already open keeps its place in the
+	/// stack, only its bounds are updated and drawing cleared, so that owners can redraw their
+	/// layers every frame. Returns the draw list to record the layer's contents into.
+	pub fn open(&mut self, id: LayerId, kind: LayerKind, bounds: Rect) -> &mut DrawList {
+		let idx = match self.layers.iter().position(|layer| layer.id == id) {
+			Some(idx) => idx,
+			None => {
+				self.layers.push(Layer {
+					id,
+					kind,
+					bounds,
+					draw_list: DrawList::default(),
+				});
+				self.layers.len() - 1
+			}
+		};
+		let layer = &mut self.layers[idx];
+		layer.kind = kind;
+		layer.bounds = bounds;
+		layer.draw_list.clear();
+		&mut layer.draw_list
+	}
+
+	pub fn close(&mut self, id: LayerId) {
+		self.layers.retain(|layer| layer.id != id);
+		if self.click.is_some_and(|(click_id, _)| click_id == id) {
+			self.click = None;
+		}
+	}
+
+	pub fn is_open(&self, id: LayerId) -> bool {
+		self.layers.iter().any(|layer| layer.id == id)
+	}
+
+	/// Handles the input meant for the layers, before any widget sees it. Consumes presses on
+	/// popups and presses that dismiss them, as well as the Escape key press that dismisses one.
+	pub fn update(&mut self, mouse: &mut Mouse, keyboard: &mut Keyboard) {
+		self.click = None;
+
+		if keyboard.was_pressed(Key::Escape) {
+			if let Some(idx) = self.topmost_popup() {
+				self.layers.remove(idx);
+				keyboard.consume_press(Key::Escape);
+			}
+		}
+
+		if !mouse.was_left_pressed() {
+			return;
+		}
+		let pos = mouse.position();
+		let hit = self
+			.layers
+			.iter()
+			.rev()
+			.filter(|layer| layer.kind == LayerKind::Popup)
+			.find(|layer| layer.bounds.contains(pos));
+		if let Some(layer) = hit {
+			self.click = Some((layer.id, pos));
+			mouse.consume_press();
+		} else if self.topmost_popup().is_some() {
+			self.layers.retain(|layer| layer.kind != LayerKind::Popup);
+			mouse.consume_press();
+		}
+	}
+
+	/// Position of the click made on the popup `id` in the current frame.
+	pub fn take_click(&mut self, id: LayerId) -> Option<(i32, i32)> {
+		match self.click {
+			Some((click_id, pos)) if click_id == id => {
+				self.click = None;
+				Some(pos)
+			}
+			_ => None,
+		}
+	}
+
+	pub fn render(&self, bitmap_data: BitmapData) {
+		for layer in &self.layers {
+			layer.draw_list.execute(bitmap_data);
+		}
+	}
+
+	fn topmost_popup(&self) -> Option<usize> {
+		self.layers
+			.iter()
+			.rposition(|layer| layer.kind == LayerKind::Popup)
+	}
+}
@@ -0,0 +1,285 @@
+//! Command palette overlay (Ctrl+Shift+P). Lists registered commands, narrows them down with
+//! fuzzy matching as the user types and executes the selected one on Enter.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use crate::animation::{Easing, Tween};
+use crate::command::CommandRegistry;
+use crate::draw::{draw_rectangle, draw_rectangle_blended, draw_text};
+use crate::font;
+use crate::i18n::tr;
+use crate::key::Key;
+use crate::string::fuzzy_match;
+use crate::text_input::{TextInput, TextInputEvent};
+use crate::window::{BitmapData, Keyboard};
+
+const MAX_VISIBLE_ROWS: usize = 10;
+const PANEL_WIDTH: usize = 640;
+const PANEL_TOP: usize = 60;
+const PADDING: usize = 8;
+const EXPAND_DURATION: Duration = Duration::from_millis(120);
+
+const DIM_COLOR: u32 = 0x000000;
+const PANEL_COLOR: u32 = 0x282828;
+const SELECTED_COLOR: u32 = 0x504945;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const HINT_COLOR: u32 = 0x928374;
+const MATCH_COLOR: u32 = 0xfabd2f;
+
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+	open: bool,
+	query: TextInput,
+	/// Commands matching the query, best match first.
+	matches: Vec<Match>,
+	/// Index into `matches`.
+	selected: usize,
+	/// Index of the first visible row in `matches`.
+	scroll: usize,
+	/// How far the panel is expanded, from 0 (hidden) to 1.
+	expansion: Tween,
+}
+
+#[derive(Debug)]
+struct Match {
+	/// Index into [CommandRegistry::commands].
+	command_idx: usize,
+	score: i32,
+	/// Char indices of the matched characters in the command title.
+	positions: Vec<usize>,
+}
+
+impl CommandPalette {
+	pub fn is_open(&self) -> bool {
+		self.open
+	}
+
+	pub fn toggle(&mut self, registry: &CommandRegistry) {
+		if self.open {
+			self.close();
+		} else {
+			self.open(registry);
+		}
+	}
+
+	pub fn open(&mut self, registry: &CommandRegistry) {
+		self.open = true;
+		self.query.clear();
+		self.refresh_matches(registry);
+		self.expansion
+			.animate_to(1.0, EXPAND_DURATION, Easing::EaseOutCubic);
+	}
+
+	/// Starts collapsing the palette. Matches are kept until the next [CommandPalette::open], so
+	/// that the collapsing panel still shows them.
+	pub fn close(&mut self) {
+		self.open = false;
+		self.expansion
+			.animate_to(0.0, EXPAND_DURATION, Easing::EaseInQuad);
+	}
+
+	/// Advances the expand/collapse animation. Called every frame, open or not.
+	pub fn animate(&mut self, dt: Duration) {
+		self.expansion.update(dt);
+	}
+
+	/// Handles keyboard input of the current frame. Returns the id of the command to execute if
+	/// one was chosen, in which case the palette is closed.
+	pub fn update(
+		&mut self,
+		keyboard: &Keyboard,
+		registry: &CommandRegistry,
+	) -> Option<&'static str> {
+		match self.query.update(keyboard) {
+			Some(TextInputEvent::Cancel) => {
+				self.close();
+				return None;
+			}
+			Some(TextInputEvent::Submit) => {
+				let chosen = self
+					.matches
+					.get(self.selected)
+					.map(|m| registry.commands()[m.command_idx].id);
+				self.close();
+				return chosen;
+			}
+			Some(TextInputEvent::Changed) => self.refresh_matches(registry),
+			None => (),
+		}
+
+		let up = keyboard.steps(Key::Up, MAX_VISIBLE_ROWS);
+		let down = keyboard.steps(Key::Down, MAX_VISIBLE_ROWS);
+		if up > 0 {
+			self.select(self.selected.saturating_sub(up));
+		}
+		if down > 0 {
+			self.select(self.selected + down);
+		}
+
+		None
+	}
+
+	/// Rows show the shortcut bound to the command, or the command id if unbound. Disabled
+	/// commands are dimmed, and the description of the selected one is shown below the rows.
+	pub fn render(&self, bitmap_data: BitmapData, registry: &CommandRegistry) {
+		let expansion = self.expansion.value();
+		if expansion <= 0.0 {
+			return;
+		}
+
+		let bitmap_width = bitmap_data.bitmap_width as usize;
+		let bitmap_height = bitmap_data.bitmap_height as usize;
+		draw_rectangle_blended(
+			bitmap_data,
+			(0, 0),
+			(bitmap_width, bitmap_height),
+			DIM_COLOR,
+			0.5 * expansion,
+		);
+
+		let panel_width = PANEL_WIDTH.min(bitmap_width);
+		let panel_x = (bitmap_width - panel_width) / 2;
+		let row_height = font::LINE_HEIGHT + PADDING;
+		let visible_rows = self.matches.len().clamp(1, MAX_VISIBLE_ROWS);
+		let full_height = PADDING + row_height * (visible_rows + 2) + PADDING;
+		let panel_height = (full_height as f32 * expansion).round() as usize;
+		let panel_bottom = PANEL_TOP + panel_height;
+		draw_rectangle(
+			bitmap_data,
+			(panel_x, PANEL_TOP),
+			(panel_width, panel_height),
+			PANEL_COLOR,
+		);
+
+		// Query input line
+		let input_y = PANEL_TOP + PADDING;
+		if input_y + row_height > panel_bottom {
+			return;
+		}
+		self.query.render(
+			bitmap_data,
+			(panel_x + PADDING, input_y),
+			panel_width - 2 * PADDING,
+		);
+		let text_x = panel_x + 2 * PADDING;
+		let text_offset_y = PADDING / 2 + font::SCALE;
+
+		let list_y = input_y + row_height;
+		if list_y + row_height > panel_bottom {
+			return;
+		}
+		if self.matches.is_empty() {
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&tr!("no_matching_commands"),
+				(text_x, list_y + text_offset_y),
+				HINT_COLOR,
+			);
+			return;
+		}
+
+		let visible = self
+			.matches
+			.iter()
+			.enumerate()
+			.skip(self.scroll)
+			.take(MAX_VISIBLE_ROWS);
+		for (row, (match_idx, m)) in visible.enumerate() {
+			let row_y = list_y + row * row_height;
+			// Rows are revealed one by one as the panel expands
+			if row_y + row_height > panel_bottom {
+				break;
+			}
+			if match_idx == self.selected {
+				draw_rectangle(
+					bitmap_data,
+					(panel_x + PADDING, row_y),
+					(panel_width - 2 * PADDING, row_height),
+					SELECTED_COLOR,
+				);
+			}
+
+			let command = &registry.commands()[m.command_idx];
+			let y = row_y + text_offset_y;
+			let title_color = match command.enabled {
+				true => TEXT_COLOR,
+				false => HINT_COLOR,
+			};
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				command.title,
+				(text_x, y),
+				title_color,
+			);
+			// Overdraw matched characters in the accent color
+			for (char_idx, (byte_idx, c)) in command.title.char_indices().enumerate() {
+				if m.positions.contains(&char_idx) {
+					let x = text_x + font::ui().text_width(&command.title[..byte_idx]);
+					let c = &command.title[byte_idx..byte_idx + c.len_utf8()];
+					draw_text(bitmap_data, font::ui(), c, (x, y), MATCH_COLOR);
+				}
+			}
+
+			let hint = match command.shortcut {
+				Some(chord) => chord.label(),
+				None => command.id.to_string(),
+			};
+			let hint_x = panel_x + panel_width - 2 * PADDING - font::ui().text_width(&hint);
+			if hint_x > text_x + font::ui().text_width(command.title) + PADDING {
+				draw_text(bitmap_data, font::ui(), &hint, (hint_x, y), HINT_COLOR);
+			}
+		}
+
+		let description_y = list_y + visible_rows * row_height;
+		if description_y + row_height > panel_bottom {
+			return;
+		}
+		if let Some(m) = self.matches.get(self.selected) {
+			let description = registry.commands()[m.command_idx].description;
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&font::ui().truncate(description, panel_width - 4 * PADDING),
+				(text_x, description_y + text_offset_y),
+				HINT_COLOR,
+			);
+		}
+	}
+
+	fn select(&mut self, idx: usize) {
+		if self.matches.is_empty() {
+			self.selected = 0;
+			self.scroll = 0;
+			return;
+		}
+		self.selected = idx.min(self.matches.len() - 1);
+		if self.selected < self.scroll {
+			self.scroll = self.selected;
+		} else if self.selected >= self.scroll + MAX_VISIBLE_ROWS {
+			self.scroll = self.selected + 1 - MAX_VISIBLE_ROWS;
+		}
+	}
+
+	fn refresh_matches(&mut self, registry: &CommandRegistry) {
+		self.matches = registry
+			.commands()
+			.iter()
+			.enumerate()
+			.filter_map(|(command_idx, command)| {
+				let (score, positions) = fuzzy_match(self.query.text(), command.title)?;
+				Some(Match {
+					command_idx,
+					score,
+					positions,
+				})
+			})
+			.collect();
+		// Stable sort keeps registration order for equally scored commands
+		self.matches.sort_by_key(|m| Reverse(m.score));
+		self.scroll = 0;
+		self.select(0);
+	}
+}
@@ -0,0 +1,125 @@
+//! Dual-pane view, side by side listings in the style of orthodox file managers. The active
+//! pane is the one of [State](crate::app::State): its directory is [State::history] and its
+//! listing [State::list_view], so every command works on it. The other one keeps its own
+//! directory and listing in a [Pane] until they trade places.
+//!
+//! [State::history]: crate::app::State::history
+//! [State::list_view]: crate::app::State::list_view
+
+use std::path::{Path, PathBuf};
+
+use crate::draw::{draw_rectangle, draw_text, Rect};
+use crate::font;
+use crate::list_view::ListView;
+use crate::navigation::History;
+use crate::window::BitmapData;
+
+const HEADER_PADDING: usize = 3;
+/// Height of the bar showing the directory of a pane.
+pub const HEADER_HEIGHT: usize = font::LINE_HEIGHT + 2 * HEADER_PADDING;
+const PADDING: usize = 8;
+/// Width of the line between the panes.
+const DIVIDER_WIDTH: usize = 1;
+
+const HEADER_COLOR: u32 = 0x32302f;
+const ACTIVE_HEADER_COLOR: u32 = 0x504945;
+const TEXT_COLOR: u32 = 0xa89984;
+const ACTIVE_TEXT_COLOR: u32 = 0xebdbb2;
+const DIVIDER_COLOR: u32 = 0x504945;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+	Left,
+	Right,
+}
+
+impl Side {
+	pub fn other(self) -> Side {
+		match self {
+			Side::Left => Side::Right,
+			Side::Right => Side::Left,
+		}
+	}
+}
+
+/// The inactive pane.
+#[derive(Debug)]
+pub struct Pane {
+	pub history: History,
+	pub list_view: ListView,
+}
+
+impl Pane {
+	pub fn new(dir: PathBuf, show_hidden: bool) -> Self {
+		let mut list_view = ListView::default();
+		list_view.set_show_hidden(show_hidden);
+		Pane {
+			history: History::new(dir),
+			list_view,
+		}
+	}
+}
+
+/// The active and the inactive pane within `area`, headers included.
+pub fn areas(area: Rect, active: Side) -> (Rect, Rect) {
+	let (left, right) = halves(area);
+	match active {
+		Side::Left => (left, right),
+		Side::Right => (right, left),
+	}
+}
+
+/// Splits `area` into the left and the right pane.
+fn halves(area: Rect) -> (Rect, Rect) {
+	let left_width = area.width.saturating_sub(DIVIDER_WIDTH) / 2;
+	let right_x = area.x + left_width + DIVIDER_WIDTH;
+	let right_width = (area.x + area.width).saturating_sub(right_x);
+	(
+		Rect::new(area.x, area.y, left_width, area.height),
+		Rect::new(right_x, area.y, right_width, area.height),
+	)
+}
+
+/// Part of `pane` below its header, where the listing goes.
+pub fn view_area(pane: Rect) -> Rect {
+	let header = HEADER_HEIGHT.min(pane.height);
+	Rect::new(pane.x, pane.y + header, pane.width, pane.height - header)
+}
+
+/// Draws the line between the panes of `area`.
+pub fn render_divider(bitmap_data: BitmapData, area: Rect) {
+	let (left, _) = halves(area);
+	draw_rectangle(
+		bitmap_data,
+		(left.x + left.width, area.y),
+		(DIVIDER_WIDTH.min(area.width), area.height),
+		DIVIDER_COLOR,
+	);
+}
+
+/// Draws the header of `pane` with the path of its directory.
+pub fn render_header(bitmap_data: BitmapData, pane: Rect, dir: &Path, active: bool) {
+	let (background, text_color) = match active {
+		true => (ACTIVE_HEADER_COLOR, ACTIVE_TEXT_COLOR),
+		false => (HEADER_COLOR, TEXT_COLOR),
+	};
+	let height = HEADER_HEIGHT.min(pane.height);
+	draw_rectangle(
+		bitmap_data,
+		(pane.x, pane.y),
+		(pane.width, height),
+		background,
+	);
+	if height < HEADER_HEIGHT {
+		return;
+	}
+	let max_width = pane.width.saturating_sub(2 * PADDING);
+	let path = font::ui().compact_path(dir, max_width);
+	draw_text(
+		bitmap_data,
+		font::ui(),
+		&font::ui().truncate(&path, max_width),
+		(pane.x + PADDING, pane.y + HEADER_PADDING),
+		text_color,
+	);
+}
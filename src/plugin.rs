@@ -0,0 +1,374 @@
+//! Third-party plugins rendering previews of and adding commands for file types the explorer
+//! doesn't know. Plugins are DLLs in the `plugins` directory next to the configuration file,
+//! loaded at startup and kept loaded until exit.
+//!
+//! A plugin exports a C function named `file_explorer_plugin` returning a pointer to a
+//! [PluginInfo] that stays valid as long as the DLL is loaded:
+//!
+//! ```c
+//! const PluginInfo *file_explorer_plugin(void);
+//! ```
+//!
+//! Strings are null-terminated UTF-8. Lists of extensions are separated by `;` and don't include
+//! the dot, e.g. `"pdf;stl"`. Callbacks return 0 on success and are called from any thread.
+
+use std::{
+	ffi::{c_char, CStr, CString},
+	fmt::Display,
+	io,
+	path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+use windows::core::PCSTR;
+use windows::Win32::Foundation::HMODULE;
+use windows::Win32::System::LibraryLoader::{FreeLibrary, GetProcAddress, LoadLibraryW};
+
+use crate::draw::Texture;
+use crate::string::WideString;
+
+/// Version of the plugin ABI, bumped on every incompatible change of [PluginInfo] and
+/// [PluginCommand]. Plugins built for another version aren't loaded.
+pub const API_VERSION: u32 = 1;
+
+const ENTRY_POINT: &[u8] = b"file_explorer_plugin\0";
+
+/// Fills `pixels`, `width`x`height` 0xAARRGGBB pixels, with a preview of the file at `path`.
+/// Pixels left transparent show the background.
+pub type RenderPreview =
+	unsafe extern "C" fn(path: *const c_char, pixels: *mut u32, width: u32, height: u32) -> i32;
+
+/// Runs a command on the file at `path`.
+pub type RunCommand = unsafe extern "C" fn(path: *const c_char) -> i32;
+
+/// What a plugin provides, returned by its entry point.
+#[repr(C)]
+pub struct PluginInfo {
+	/// Must be [API_VERSION].
+	pub api_version: u32,
+	pub name: *const c_char,
+	/// Files whose previews [PluginInfo::render_preview] renders.
+	pub preview_extensions: *const c_char,
+	pub render_preview: Option<RenderPreview>,
+	/// Array of `command_count` commands.
+	pub commands: *const PluginCommand,
+	pub command_count: usize,
+}
+
+#[repr(C)]
+pub struct PluginCommand {
+	/// Unique within the plugin. The command is registered as `plugin.<id>`.
+	pub id: *const c_char,
+	/// Shown in the command palette.
+	pub title: *const c_char,
+	/// Files the command applies to, null or empty for all files.
+	pub extensions: *const c_char,
+	pub run: RunCommand,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: io::Error,
+		filename: PathBuf,
+	},
+	/// A DLL without the `file_explorer_plugin` function.
+	MissingEntryPoint {
+		filename: PathBuf,
+	},
+	VersionMismatch {
+		filename: PathBuf,
+		version: u32,
+	},
+	/// A [PluginInfo] with null pointers where they aren't allowed.
+	InvalidInfo {
+		filename: PathBuf,
+	},
+	/// A callback of the plugin returned `code`.
+	Failed {
+		plugin: String,
+		path: PathBuf,
+		code: i32,
+	},
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::MissingEntryPoint { filename } => {
+				write!(f, "{} isn't a plugin.", filename.display())
+			}
+			Error::VersionMismatch { filename, version } => write!(
+				f,
+				"{} is built for plugin API {version}, expected {API_VERSION}.",
+				filename.display()
+			),
+			Error::InvalidInfo { filename } => {
+				write!(f, "{} describes itself incorrectly.", filename.display())
+			}
+			Error::Failed { plugin, path, code } => write!(
+				f,
+				"Plugin {plugin} failed on {} with error {code}.",
+				path.display()
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Default)]
+pub struct Plugins {
+	plugins: Vec<Plugin>,
+}
+
+#[derive(Debug)]
+pub struct Plugin {
+	pub name: String,
+	// Never unloaded, the commands and callbacks point into it
+	_library: HMODULE,
+	preview_extensions: Vec<String>,
+	render_preview: Option<RenderPreview>,
+	commands: Vec<Command>,
+}
+
+#[derive(Debug)]
+pub struct Command {
+	/// `plugin.<id>`, leaked like the plugin itself for the command registry.
+	pub id: &'static str,
+	pub title: &'static str,
+	plugin: String,
+	extensions: Vec<String>,
+	run: RunCommand,
+}
+
+impl Plugins {
+	/// Directory the plugins are loaded from, if the application data directory is known.
+	pub fn dir() -> Option<PathBuf> {
+		let app_data = std::env::var_os("APPDATA")?;
+		Some(
+			PathBuf::from(app_data)
+				.join("file-explorer")
+				.join("plugins"),
+		)
+	}
+
+	/// Loads the DLLs in [Plugins::dir]. A missing directory isn't an error. DLLs that aren't
+	/// plugins of this version are skipped and reported.
+	pub fn load() -> (Plugins, Vec<Error>) {
+		match Plugins::dir() {
+			Some(dir) => Plugins::load_dir(&dir),
+			None => (Plugins::default(), Vec::new()),
+		}
+	}
+
+	pub fn load_dir(dir: &Path) -> (Plugins, Vec<Error>) {
+		let mut plugins = Plugins::default();
+		let mut errors = Vec::new();
+		let entries = match std::fs::read_dir(dir) {
+			Ok(entries) => entries,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return (plugins, errors),
+			Err(err) => {
+				errors.push(Error::Io {
+					err,
+					filename: dir.to_path_buf(),
+				});
+				return (plugins, errors);
+			}
+		};
+		for entry in entries {
+			let path = match entry {
+				Ok(entry) => entry.path(),
+				Err(err) => {
+					errors.push(Error::Io {
+						err,
+						filename: dir.to_path_buf(),
+					});
+					continue;
+				}
+			};
+			if !has_extension(&path, &["dll".to_string()]) {
+				continue;
+			}
+			match Plugin::load(&path) {
+				Ok(plugin) => {
+					info!("Loaded plugin {} from {}", plugin.name, path.display());
+					plugins.plugins.push(plugin);
+				}
+				Err(err) => errors.push(err),
+			}
+		}
+		for err in &errors {
+			warn!("Plugins: {err}");
+		}
+		(plugins, errors)
+	}
+
+	/// Whether a plugin renders previews of `path`.
+	pub fn previews(&self, path: &Path) -> bool {
+		self.previewer(path).is_some()
+	}
+
+	/// Preview of `path` fitting in a `size`x`size` square, or None if no plugin renders previews
+	/// of such files.
+	pub fn render_preview(&self, path: &Path, size: usize) -> Option<Result<Texture, Error>> {
+		let (plugin, render_preview) = self.previewer(path)?;
+		let mut texture = Texture::new(size, size);
+		let c_path = c_path(path);
+		let code = unsafe {
+			render_preview(
+				c_path.as_ptr(),
+				texture.bitmap.as_mut_ptr(),
+				size as u32,
+				size as u32,
+			)
+		};
+		Some(match code {
+			0 => Ok(texture),
+			code => Err(Error::Failed {
+				plugin: plugin.name.clone(),
+				path: path.to_path_buf(),
+				code,
+			}),
+		})
+	}
+
+	fn previewer(&self, path: &Path) -> Option<(&Plugin, RenderPreview)> {
+		self.plugins.iter().find_map(|plugin| {
+			let render_preview = plugin.render_preview?;
+			has_extension(path, &plugin.preview_extensions).then_some((plugin, render_preview))
+		})
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.plugins.iter().map(|plugin| plugin.name.as_str())
+	}
+
+	pub fn commands(&self) -> impl Iterator<Item = &Command> {
+		self.plugins.iter().flat_map(|plugin| &plugin.commands)
+	}
+
+	pub fn command(&self, id: &str) -> Option<&Command> {
+		self.commands().find(|command| command.id == id)
+	}
+}
+
+impl Plugin {
+	fn load(path: &Path) -> Result<Plugin, Error> {
+		let wide_path = WideString::from_os_str(path.as_os_str());
+		let library = unsafe { LoadLibraryW(wide_path.as_pcwstr()) }.map_err(|err| Error::Io {
+			err: io::Error::other(err),
+			filename: path.to_path_buf(),
+		})?;
+		let plugin = unsafe { Plugin::from_library(library, path) };
+		if plugin.is_err() {
+			unsafe { FreeLibrary(library) };
+		}
+		plugin
+	}
+
+	/// Safety: `library` must be a loaded DLL.
+	unsafe fn from_library(library: HMODULE, path: &Path) -> Result<Plugin, Error> {
+		let filename = path.to_path_buf();
+		let Some(entry_point) = GetProcAddress(library, PCSTR(ENTRY_POINT.as_ptr())) else {
+			return Err(Error::MissingEntryPoint { filename });
+		};
+		let entry_point: unsafe extern "C" fn() -> *const PluginInfo =
+			std::mem::transmute(entry_point);
+		let Some(info) = entry_point().as_ref() else {
+			return Err(Error::InvalidInfo { filename });
+		};
+		if info.api_version != API_VERSION {
+			return Err(Error::VersionMismatch {
+				filename,
+				version: info.api_version,
+			});
+		}
+		let Some(name) = string(info.name) else {
+			return Err(Error::InvalidInfo { filename });
+		};
+
+		let mut commands = Vec::with_capacity(info.command_count);
+		if info.command_count > 0 {
+			if info.commands.is_null() {
+				return Err(Error::InvalidInfo { filename });
+			}
+			for command in std::slice::from_raw_parts(info.commands, info.command_count) {
+				let (Some(id), Some(title)) = (string(command.id), string(command.title)) else {
+					return Err(Error::InvalidInfo { filename });
+				};
+				debug!("Plugin {name} provides command {id}");
+				commands.push(Command {
+					id: Box::leak(format!("plugin.{id}").into_boxed_str()),
+					title: Box::leak(title.into_boxed_str()),
+					plugin: name.clone(),
+					extensions: extensions(command.extensions),
+					run: command.run,
+				});
+			}
+		}
+
+		Ok(Plugin {
+			preview_extensions: extensions(info.preview_extensions),
+			render_preview: info.render_preview,
+			commands,
+			name,
+			_library: library,
+		})
+	}
+}
+
+impl Command {
+	/// Whether the command can run on `path`.
+	pub fn applies_to(&self, path: &Path) -> bool {
+		self.extensions.is_empty() || has_extension(path, &self.extensions)
+	}
+
+	pub fn run(&self, path: &Path) -> Result<(), Error> {
+		let c_path = c_path(path);
+		match unsafe { (self.run)(c_path.as_ptr()) } {
+			0 => Ok(()),
+			code => Err(Error::Failed {
+				plugin: self.plugin.clone(),
+				path: path.to_path_buf(),
+				code,
+			}),
+		}
+	}
+}
+
+/// Safety: `s` must be null or point to a null-terminated string.
+unsafe fn string(s: *const c_char) -> Option<String> {
+	if s.is_null() {
+		return None;
+	}
+	Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+}
+
+/// Safety: like [string].
+unsafe fn extensions(s: *const c_char) -> Vec<String> {
+	string(s)
+		.unwrap_or_default()
+		.split(';')
+		.map(str::trim)
+		.filter(|ext| !ext.is_empty())
+		.map(str::to_string)
+		.collect()
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+	let Some(extension) = path.extension() else {
+		return false;
+	};
+	extensions
+		.iter()
+		.any(|ext| extension.eq_ignore_ascii_case(ext))
+}
+
+fn c_path(path: &Path) -> CString {
+	// Paths can't contain null characters on Windows
+	CString::new(path.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -1,40 +1,220 @@
 #![allow(clippy::upper_case_acronyms)]
 
 use std::{
+	collections::VecDeque,
 	fmt::Display,
-	io::{Read, Write},
-	path::PathBuf,
+	io::Read,
+	path::{Path, PathBuf},
+	time::Duration,
 };
 
-use flate2::read::ZlibDecoder;
 use log::{debug, error};
 
-use crate::Pos;
+use crate::cancel::CancelToken;
+use crate::deflate::{self, Inflater};
+use crate::draw::Pos;
 
 pub struct Png {
 	header: IHDR,
 	img_data: Vec<u8>,
 	filename: Option<String>,
+	color_space: ColorSpace,
+	/// Frames of an animated PNG (APNG). Empty for still images.
+	frames: Vec<Frame>,
+	metadata: Metadata,
 }
 
+/// Frame of an animated PNG, already composed with the previous frames into a full image.
+pub struct Frame {
+	/// Same layout as [Png::img_data].
+	img_data: Vec<u8>,
+	/// How long the frame is shown.
+	pub delay: Duration,
+}
+
+/// Color space information from the gAMA, sRGB and iCCP chunks.
+#[derive(Debug, Clone, Default)]
+pub struct ColorSpace {
+	/// Encoding gamma, usually 1/2.2.
+	pub gamma: Option<f32>,
+	/// Rendering intent, present if the image is in the sRGB color space.
+	pub srgb_intent: Option<u8>,
+	pub icc_profile: Option<IccProfile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IccProfile {
+	pub name: String,
+	/// Decompressed profile.
+	pub data: Vec<u8>,
+}
+
+/// Information about the image for showing to the user, from the header and the tEXt, zTXt, iTXt
+/// and pHYs chunks.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+	pub width: usize,
+	pub height: usize,
+	/// In file order. Keywords may repeat.
+	pub text: Vec<TextEntry>,
+	pub pixel_density: Option<PixelDensity>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextEntry {
+	/// What the text is, e.g. "Title", "Software" or "Comment".
+	pub keyword: String,
+	pub text: String,
+	/// Language tag of international text (iTXt), e.g. "en-US". Empty if not given.
+	pub language: String,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PixelDensity {
+	pub x: u32,
+	pub y: u32,
+	/// Whether `x` and `y` are pixels per meter. Otherwise they only give the pixel aspect ratio.
+	pub in_meters: bool,
+}
+
+impl Metadata {
+	/// Text of the first entry with `keyword`.
+	pub fn get(&self, keyword: &str) -> Option<&str> {
+		self.text
+			.iter()
+			.find(|entry| entry.keyword == keyword)
+			.map(|entry| entry.text.as_str())
+	}
+}
+
+impl PixelDensity {
+	/// Horizontal and vertical dots per inch, if the density is in physical units.
+	pub fn dpi(&self) -> Option<(f32, f32)> {
+		const METERS_PER_INCH: f32 = 0.0254;
+		let (x, y) = (self.x as f32, self.y as f32);
+		self.in_meters
+			.then_some((x * METERS_PER_INCH, y * METERS_PER_INCH))
+	}
+}
+
+/// How strictly damaged files are treated by [Png::load_with_options] and other decoding
+/// settings. The default rejects any damage.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+	/// Accept chunks whose CRC doesn't match their contents.
+	pub skip_checksums: bool,
+	/// Decode files whose image data ends early or is corrupt. The missing part of the image is
+	/// left blank, if it isn't bigger than [MAX_MISSING_BYTES].
+	pub allow_truncated: bool,
+	/// Skip critical chunks of unknown types instead of failing.
+	pub allow_unknown_critical: bool,
+	/// Scale the image down while decoding, by the biggest whole factor that keeps its bigger
+	/// dimension at least this big. Each square of pixels is averaged into one. Non-interlaced
+	/// images are scaled scanline by scanline, without ever holding the full size image.
+	pub target_size: Option<usize>,
+	/// Gives up with [Error::Cancelled] between chunks of image data once cancelled.
+	pub cancel: Option<CancelToken>,
+}
+
+/// Damage found in a file decoded despite it, see [DecodeOptions].
+#[derive(Debug, Clone)]
+pub enum Warning {
+	ChecksumFailed {
+		chunk_type: String,
+	},
+	UnknownCriticalChunk(String),
+	/// The file ends before IEND.
+	Truncated,
+	Deflate(deflate::Error),
+	MissingImageData {
+		missing_bytes: usize,
+	},
+}
+
+impl Display for Warning {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Warning::ChecksumFailed { chunk_type } => {
+				write!(f, "Checksum of chunk {chunk_type} doesn't match.")
+			}
+			Warning::UnknownCriticalChunk(chunk_type) => {
+				write!(f, "Skipped unknown critical chunk {chunk_type}.")
+			}
+			Warning::Truncated => write!(f, "File ended abruptly."),
+			Warning::Deflate(err) => write!(f, "Image data is damaged: {err}"),
+			Warning::MissingImageData { missing_bytes } => write!(
+				f,
+				"Image data is {missing_bytes} bytes short, the rest of the image is left blank."
+			),
+		}
+	}
+}
+
+impl DecodeOptions {
+	/// Gets as much of the image as possible out of damaged files.
+	pub fn lenient() -> Self {
+		DecodeOptions {
+			skip_checksums: true,
+			allow_truncated: true,
+			allow_unknown_critical: true,
+			..Default::default()
+		}
+	}
+}
+
+/// Gamma of the display the framebuffer is shown on. Assumed, not queried.
+const DISPLAY_GAMMA: f32 = 2.2;
+
+/// Images bigger than this many pixels are rejected instead of allocating gigabytes for a
+/// corrupted header.
+const MAX_PIXELS: usize = 1 << 28;
+/// Every animation frame holds the whole canvas. Frames past this many pixels in total are
+/// dropped, the animation ends early instead.
+const MAX_FRAME_PIXELS: usize = 1 << 28;
+
+/// Longest chunk allowed by the spec.
+const MAX_CHUNK_LEN: usize = (1 << 31) - 1;
+/// Longest chunk other than image data a [StreamingDecoder] buffers. Text and ICC profiles are
+/// far smaller, anything longer is more likely a corrupted length than a real chunk.
+const MAX_NON_IMAGE_CHUNK_LEN: usize = 16 * 1024 * 1024;
+
+/// Most image data bytes a truncated file may be missing to still be decoded, see
+/// [DecodeOptions::allow_truncated]. Files missing more fail with [Error::FileEnd], so that a
+/// few bytes claiming a huge image don't allocate it.
+const MAX_MISSING_BYTES: usize = 16 * 1024 * 1024;
+
 #[derive(Debug)]
 pub enum Error {
 	Io {
 		err: std::io::Error,
 		filename: PathBuf,
 	},
+	Read(std::io::Error),
 	BadMagic,
 	FileEnd,
 	ChecksumFailed,
 	ExpectedIHDR,
+	InvalidSize,
 	IncompleteBlock {
 		block_kind: &'static str,
 	},
 	NoIDAT,
-	Deflate(std::io::Error),
-	OnlyRGBA,
-	InterlaceNotSupported,
+	NoPLTE,
+	InvalidPLTE,
+	Deflate(deflate::Error),
+	UnsupportedFormat {
+		color_type: u8,
+		bit_depth: u8,
+	},
+	UnknownInterlaceMethod(u8),
 	InvalidFilterType,
+	InvalidFrame,
+	UnknownCriticalChunk(String),
+	ChunkTooLong {
+		chunk_type: String,
+		len: usize,
+	},
+	Cancelled,
 }
 
 impl Display for Error {
@@ -43,20 +223,38 @@ impl Display for Error {
 			Error::Io { err, filename } => {
 				write!(f, "Failed to load {}: {}.", filename.display(), err)
 			}
+			Error::Read(err) => write!(f, "Failed to read image data: {err}."),
 			Error::BadMagic => write!(f, "Unknown file format."),
 			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
 			Error::ChecksumFailed => write!(f, "Checksum doesn't match."),
 			Error::ExpectedIHDR => write!(f, "Expected IHDR block."),
+			Error::InvalidSize => write!(f, "Invalid image size."),
 			Error::IncompleteBlock { block_kind } => {
 				write!(f, "Missing fields in block {block_kind}")
 			}
 			Error::NoIDAT => write!(f, "Missing actual image data."),
+			Error::NoPLTE => write!(f, "Missing palette of an indexed-color image."),
+			Error::InvalidPLTE => write!(f, "Palette length isn't a multiple of 3."),
 			Error::Deflate(err) => write!(f, "Failed to decompress: {err}."),
-			Error::OnlyRGBA => write!(f, "This parser only supports RGBA images."),
-			Error::InterlaceNotSupported => {
-				write!(f, "This parser only supportes not interlaced images.")
+			Error::UnsupportedFormat {
+				color_type,
+				bit_depth,
+			} => write!(
+				f,
+				"Unsupported color type {color_type} with bit depth {bit_depth}."
+			),
+			Error::UnknownInterlaceMethod(method) => {
+				write!(f, "Unknown interlace method {method}.")
 			}
 			Error::InvalidFilterType => write!(f, "Unknown filter type."),
+			Error::InvalidFrame => write!(f, "Animation frame doesn't fit in the image."),
+			Error::UnknownCriticalChunk(chunk_type) => {
+				write!(f, "Unknown critical chunk {chunk_type}.")
+			}
+			Error::ChunkTooLong { chunk_type, len } => {
+				write!(f, "Chunk {chunk_type} of {len} bytes is too long.")
+			}
+			Error::Cancelled => write!(f, "Decoding was cancelled."),
 		}
 	}
 }
@@ -69,13 +267,37 @@ impl Png {
 			err,
 			filename: p.into(),
 		})?;
+		let mut png = Png::load_from_slice(&data)?;
+		png.filename = Some(p.to_string());
+		Ok(png)
+	}
+
+	/// Reads the whole PNG file from `reader` before decoding it.
+	pub fn load_from_reader(mut reader: impl Read) -> Result<Self, Error> {
+		let mut data = Vec::new();
+		reader.read_to_end(&mut data).map_err(Error::Read)?;
+		Png::load_from_slice(&data)
+	}
 
+	/// Decodes a PNG file already in memory, e.g. an embedded asset or clipboard contents.
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		let (png, _) = Png::load_with_options(data, DecodeOptions::default())?;
+		Ok(png)
+	}
+
+	/// Like [Png::load_from_slice], tolerating the damage allowed by `options`. Damage found is
+	/// returned alongside the image.
+	pub fn load_with_options(
+		data: &[u8],
+		options: DecodeOptions,
+	) -> Result<(Self, Vec<Warning>), Error> {
+		let mut warnings = Vec::new();
 		let mut state = parser::State { current_byte: 0 };
-		let data = parser::Data { data: &data };
+		let data = parser::Data { data };
 
 		parser::parse_magic(&mut state, &data)?;
 
-		let ihdr = match parser::parse_block(&mut state, &data)? {
+		let ihdr = match parser::parse_block(&mut state, &data, &options, &mut warnings)? {
 			PngBlock {
 				block: PngBlockKind::IHDR(ihdr),
 				..
@@ -84,97 +306,348 @@ impl Png {
 		};
 		debug!("IHDR: {ihdr:#?}");
 
-		// We only support one type of PNG :/
-		if ihdr.color_type != 6 || ihdr.bit_depth != 8 {
-			return Err(Error::OnlyRGBA);
+		if !ihdr.is_supported() {
+			return Err(Error::UnsupportedFormat {
+				color_type: ihdr.color_type,
+				bit_depth: ihdr.bit_depth,
+			});
 		}
-		if ihdr.interlace_method != 0 {
-			return Err(Error::InterlaceNotSupported);
+		if ihdr.interlace_method > INTERLACE_ADAM7 {
+			return Err(Error::UnknownInterlaceMethod(ihdr.interlace_method));
 		}
 
 		let mut blocks = Vec::new();
 		loop {
-			let block = parser::parse_block(&mut state, &data)?;
+			let block = match parser::parse_block(&mut state, &data, &options, &mut warnings) {
+				Ok(block) => block,
+				Err(Error::FileEnd) if options.allow_truncated => {
+					if !matches!(warnings.last(), Some(Warning::Truncated)) {
+						warnings.push(Warning::Truncated);
+					}
+					break;
+				}
+				Err(err) => return Err(err),
+			};
 			if let PngBlockKind::IEND = block.block {
 				break;
 			}
 			blocks.push(block);
 		}
 
-		let mut time = None;
+		let time = blocks.iter().find_map(|block| match block.block {
+			PngBlockKind::TIME(ref time) => Some(time),
+			_ => None,
+		});
+
+		let palette = blocks.iter().find_map(|block| match block.block {
+			PngBlockKind::PLTE(palette) => Some(palette),
+			_ => None,
+		});
+		if ihdr.color_type == COLOR_TYPE_INDEXED && palette.is_none() {
+			return Err(Error::NoPLTE);
+		}
 
-		// Print included text blocks. Set time if found.
-		for block in &blocks {
-			let text = match block.block {
-				PngBlockKind::TEXT(text) => text,
-				PngBlockKind::TIME(ref t) => {
-					time = Some(t);
-					continue;
-				}
-				_ => continue,
-			};
+		let transparency = blocks.iter().find_map(|block| match block.block {
+			PngBlockKind::TRNS(transparency) => Some(transparency),
+			_ => None,
+		});
+		let colors = ColorInfo {
+			palette: palette.unwrap_or_default(),
+			transparency: transparency.unwrap_or_default(),
+		};
 
-			let text = match std::str::from_utf8(text) {
-				Ok(v) => v,
-				Err(err) => {
-					error!("Failed to read text block: {err}");
-					continue;
+		let scale = options
+			.target_size
+			.map_or(1, |size| ihdr.scale_factor(size));
+		let mut raw_img = if scale > 1 && ihdr.interlace_method != INTERLACE_ADAM7 {
+			decode_scaled(&ihdr, &blocks, colors, scale, &options, &mut warnings)?
+		} else {
+			let mut decompressed_img = decompress_img_data(&ihdr, &blocks, &options, &mut warnings)?;
+			let expected_len = ihdr.image_data_len();
+			let missing_bytes = expected_len.saturating_sub(decompressed_img.len());
+			if missing_bytes > 0 && options.allow_truncated {
+				if missing_bytes > MAX_MISSING_BYTES {
+					return Err(Error::FileEnd);
 				}
+				warnings.push(Warning::MissingImageData { missing_bytes });
+				// Zeros decode as unfiltered transparent black (or black, without alpha) scanlines
+				decompressed_img.resize(expected_len, 0);
+			}
+			let decompressed_img_data = parser::Data {
+				data: &decompressed_img,
 			};
-			debug!("Text block: {text}");
-		}
-
-		let decompressed_img = decompress_img_data(&blocks)?;
-		let decompressed_img_data = parser::Data {
-			data: &decompressed_img,
+			let mut decompresseed_img_state = parser::State { current_byte: 0 };
+
+			let raw_img = if ihdr.interlace_method == INTERLACE_ADAM7 {
+				deinterlace(
+					&ihdr,
+					&mut decompresseed_img_state,
+					&decompressed_img_data,
+					colors,
+				)?
+			} else {
+				let scanlines =
+					unfilter(&ihdr, &mut decompresseed_img_state, &decompressed_img_data)?;
+				expand_to_rgba(&ihdr, scanlines, colors)
+			};
+			downscale(raw_img, ihdr.width as usize, scale)
+		};
+		rgba_to_bgra(&mut raw_img);
+
+		let animation = blocks.iter().find_map(|block| match block.block {
+			PngBlockKind::ACTL {
+				num_frames,
+				num_plays,
+			} => Some((num_frames, num_plays)),
+			_ => None,
+		});
+		let frames = match animation {
+			Some((num_frames, num_plays)) => {
+				debug!("Animation of {num_frames} frames, played {num_plays} times (0 = forever)");
+				let cancel = options.cancel.as_ref();
+				let mut frames = compose_frames(&ihdr, &blocks, colors, cancel)?;
+				for frame in &mut frames {
+					let img_data = std::mem::take(&mut frame.img_data);
+					frame.img_data = downscale(img_data, ihdr.width as usize, scale);
+				}
+				frames
+			}
+			None => Vec::new(),
 		};
-		let mut decompresseed_img_state = parser::State { current_byte: 0 };
-
-		let mut raw_img = Vec::with_capacity(decompressed_img.len());
-		let mut row_idx = 0;
-		while decompresseed_img_state.current_byte != decompressed_img.len() {
-			let filter_type: FilterType =
-				parser::get_u8(&mut decompresseed_img_state, &decompressed_img_data)?.try_into()?;
-			let encoded_line = parser::get_slice(
-				&mut decompresseed_img_state,
-				&decompressed_img_data,
-				ihdr.width as usize * 4,
-			)?;
-			decode_filter(&mut raw_img, encoded_line, filter_type, row_idx);
-			row_idx += 1;
-		}
-
-		raw_img
-			.as_mut_slice()
-			.chunks_exact_mut(4)
-			.map(|chunk| &mut chunk[0..3])
-			.for_each(|chunk| chunk.reverse());
 
 		if let Some(time) = time {
 			debug!("Time: {time:#?}");
 		}
 
-		Ok(Png {
-			header: ihdr,
+		let color_space = color_space(&blocks);
+		debug!("Color space: {color_space:?}");
+		let metadata = metadata(&ihdr, &blocks);
+		debug!("Metadata: {metadata:#?}");
+
+		let png = Png {
+			// Size of the decoded image, which may be scaled down
+			header: IHDR {
+				width: (ihdr.width as usize).div_ceil(scale) as u32,
+				height: (ihdr.height as usize).div_ceil(scale) as u32,
+				..ihdr
+			},
 			img_data: raw_img,
-			filename: Some(p.to_string()),
+			filename: None,
+			color_space,
+			frames,
+			metadata,
+		};
+		Ok((png, warnings))
+	}
+
+	/// Converts every frame of an animated PNG into a texture, paired with its delay. Still
+	/// images give a single frame.
+	pub fn into_frames(mut self) -> Vec<(crate::draw::Texture, Duration)> {
+		if self.frames.is_empty() {
+			return vec![(crate::draw::Texture::from(self), Duration::ZERO)];
+		}
+		let table = gamma_table(&self.color_space);
+		let (width, height) = (self.header.width as usize, self.header.height as usize);
+		std::mem::take(&mut self.frames)
+			.into_iter()
+			.map(|mut frame| {
+				if let Some(table) = &table {
+					apply_gamma(&mut frame.img_data, table);
+				}
+				(texture(frame.img_data, width, height), frame.delay)
+			})
+			.collect()
+	}
+
+	/// Encodes `pixels` (0xAARRGGBB, like [crate::draw::Texture]) as an 8 bit RGBA PNG file.
+	pub fn encode(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+		assert_eq!(
+			pixels.len(),
+			width * height,
+			"pixel count doesn't match the size"
+		);
+
+		let raw_img: Vec<u8> = pixels
+			.iter()
+			.flat_map(|pixel| {
+				let [a, r, g, b] = pixel.to_be_bytes();
+				[r, g, b, a]
+			})
+			.collect();
+		let filtered = encode_filters(&raw_img, width * 4, 4);
+
+		let compressed = deflate::zlib_compress(&filtered);
+
+		let mut ihdr = Vec::with_capacity(13);
+		ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+		ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+		ihdr.extend_from_slice(&[8, COLOR_TYPE_RGBA, 0, 0, 0]);
+
+		let mut png = Vec::with_capacity(compressed.len() + 64);
+		png.extend_from_slice(&PNG_SIGNATURE);
+		write_block(&mut png, b"IHDR", &ihdr);
+		write_block(&mut png, b"IDAT", &compressed);
+		write_block(&mut png, b"IEND", &[]);
+		png
+	}
+
+	pub fn save_to_path(
+		path: impl AsRef<Path>,
+		width: usize,
+		height: usize,
+		pixels: &[u32],
+	) -> Result<(), Error> {
+		let path = path.as_ref();
+		std::fs::write(path, Png::encode(width, height, pixels)).map_err(|err| Error::Io {
+			err,
+			filename: path.to_path_buf(),
 		})
 	}
+
+	pub fn color_space(&self) -> &ColorSpace {
+		&self.color_space
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Converts the image from its encoding gamma to the one of the display.
+	fn correct_gamma(&mut self) {
+		if let Some(table) = gamma_table(&self.color_space) {
+			apply_gamma(&mut self.img_data, &table);
+		}
+	}
 }
 
-impl From<Png> for crate::Texture {
-	fn from(img: Png) -> Self {
-		let mut img_data = std::mem::ManuallyDrop::new(img.img_data);
-		let ptr = img_data.as_mut_ptr().cast::<u32>();
-		let len = img_data.len() / 4;
-		let cap = img_data.capacity() / 4;
-		let bitmap = unsafe { Vec::<u32>::from_raw_parts(ptr, len, cap) };
-		let texture = crate::Texture {
-			bitmap,
-			width: img.header.width as usize,
-			height: img.header.height as usize,
-			pos: Pos { x: 0, y: 0 },
-		};
+/// Lookup table converting color samples from the encoding gamma of an image to the gamma of the
+/// display. sRGB images and images without gAMA need no correction, they're assumed to match the
+/// display already.
+fn gamma_table(color_space: &ColorSpace) -> Option<[u8; 256]> {
+	let gamma = color_space.gamma?;
+	if color_space.srgb_intent.is_some() || gamma <= 0.0 {
+		return None;
+	}
+	let exponent = 1.0 / (gamma * DISPLAY_GAMMA);
+	if (exponent - 1.0).abs() < 0.01 {
+		return None;
+	}
+
+	let mut table = [0u8; 256];
+	for (value, corrected) in table.iter_mut().enumerate() {
+		*corrected = ((value as f32 / 255.0).powf(exponent) * 255.0).round() as u8;
+	}
+	Some(table)
+}
+
+fn apply_gamma(img_data: &mut [u8], table: &[u8; 256]) {
+	for pixel in img_data.chunks_exact_mut(4) {
+		// Alpha (the last byte) is linear
+		for channel in &mut pixel[0..3] {
+			*channel = table[*channel as usize];
+		}
+	}
+}
+
+fn color_space(blocks: &[PngBlock]) -> ColorSpace {
+	let mut color_space = ColorSpace::default();
+	for block in blocks {
+		match block.block {
+			PngBlockKind::GAMA(gamma) => color_space.gamma = Some(gamma as f32 / 100_000.0),
+			PngBlockKind::SRGB(intent) => color_space.srgb_intent = Some(intent),
+			PngBlockKind::ICCP { name, profile } => match deflate::zlib_decompress(profile) {
+				Ok(data) => {
+					color_space.icc_profile = Some(IccProfile {
+						name: String::from_utf8_lossy(name).into_owned(),
+						data,
+					})
+				}
+				Err(err) => error!("Failed to decompress ICC profile: {err}"),
+			},
+			_ => (),
+		}
+	}
+	color_space
+}
+
+fn metadata(header: &IHDR, blocks: &[PngBlock]) -> Metadata {
+	let mut metadata = Metadata {
+		width: header.width as usize,
+		height: header.height as usize,
+		..Default::default()
+	};
+	for block in blocks {
+		match block.block {
+			PngBlockKind::TEXT(_) | PngBlockKind::ZTXT(_) | PngBlockKind::ITXT(_) => {
+				match text_entry(&block.block) {
+					Some(entry) => metadata.text.push(entry),
+					None => error!(
+						"Failed to read text block {}",
+						String::from_utf8_lossy(&block.chunk_type)
+					),
+				}
+			}
+			PngBlockKind::PHYS(density) => metadata.pixel_density = Some(density),
+			_ => (),
+		}
+	}
+	metadata
+}
+
+/// Decodes a tEXt, zTXt or iTXt chunk. `None` if it's malformed.
+fn text_entry(block: &PngBlockKind) -> Option<TextEntry> {
+	fn split_at_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+		let end = data.iter().position(|&byte| byte == 0)?;
+		Some((&data[..end], &data[end + 1..]))
+	}
+	let latin1 = |data: &[u8]| data.iter().map(|&byte| byte as char).collect::<String>();
+
+	let (PngBlockKind::TEXT(data) | PngBlockKind::ZTXT(data) | PngBlockKind::ITXT(data)) = block
+	else {
+		return None;
+	};
+	// Without a null separator, tEXt has a keyword only
+	let (keyword, rest) = match block {
+		PngBlockKind::TEXT(_) => split_at_null(data).unwrap_or((data, &[])),
+		_ => split_at_null(data)?,
+	};
+	let mut entry = TextEntry {
+		keyword: latin1(keyword),
+		text: String::new(),
+		language: String::new(),
+	};
+	match block {
+		PngBlockKind::TEXT(_) => entry.text = latin1(rest),
+		PngBlockKind::ZTXT(_) => {
+			// Compression method byte first, only zlib is defined
+			let text = deflate::zlib_decompress(rest.get(1..)?).ok()?;
+			entry.text = latin1(&text);
+		}
+		_ => {
+			// Compression flag and method, then the language tag and the keyword translated to
+			// that language
+			let ([compressed, _], rest) = rest.split_first_chunk::<2>()?;
+			let (language, rest) = split_at_null(rest)?;
+			let (_, text) = split_at_null(rest)?;
+			let text = match compressed {
+				0 => text.to_vec(),
+				_ => deflate::zlib_decompress(text).ok()?,
+			};
+			entry.text = String::from_utf8_lossy(&text).into_owned();
+			entry.language = latin1(language);
+		}
+	}
+	Some(entry)
+}
+
+impl From<Png> for crate::draw::Texture {
+	fn from(mut img: Png) -> Self {
+		img.correct_gamma();
+		let texture = texture(
+			std::mem::take(&mut img.img_data),
+			img.header.width as usize,
+			img.header.height as usize,
+		);
 		debug!(
 			"From file \"{filename}\" created texture: {texture:#?}",
 			filename = img.filename.as_deref().unwrap_or("unknown")
@@ -183,6 +656,279 @@ impl From<Png> for crate::Texture {
 	}
 }
 
+/// Decodes a PNG file while its bytes are still arriving, e.g. over the network. Bytes are pushed
+/// in with [StreamingDecoder::push] and finished rows pulled out with [StreamingDecoder::next_row],
+/// so that a partially loaded image can be shown right away and only a few scanlines are kept in
+/// memory. Interlaced images are the exception, their rows are available only once all image
+/// data arrived. Animation frames are ignored.
+#[derive(Default)]
+pub struct StreamingDecoder {
+	/// Received bytes that don't form a complete chunk yet.
+	buffer: Vec<u8>,
+	signature_checked: bool,
+	header: Option<IHDR>,
+	palette: Vec<u8>,
+	transparency: Vec<u8>,
+	color_space: ColorSpace,
+	gamma_table: Option<[u8; 256]>,
+	/// Decompresses IDAT data into a buffer of scanlines that weren't unfiltered yet. Created at
+	/// the first IDAT.
+	inflater: Option<Inflater>,
+	/// Last unfiltered scanline, the next one may be filtered relative to it.
+	previous_line: Vec<u8>,
+	next_y: usize,
+	rows: VecDeque<Row>,
+	finished: bool,
+}
+
+/// Decoded row of a [StreamingDecoder].
+pub struct Row {
+	pub y: usize,
+	/// 0xAARRGGBB, like [crate::draw::Texture].
+	pub pixels: Vec<u32>,
+}
+
+impl StreamingDecoder {
+	pub fn new() -> Self {
+		StreamingDecoder::default()
+	}
+
+	/// Image size, known once the header arrived.
+	pub fn size(&self) -> Option<(usize, usize)> {
+		let header = self.header.as_ref()?;
+		Some((header.width as usize, header.height as usize))
+	}
+
+	/// Whether the whole file arrived. Rows may still be waiting in [StreamingDecoder::next_row].
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	/// Oldest decoded row that wasn't taken yet.
+	pub fn next_row(&mut self) -> Option<Row> {
+		self.rows.pop_front()
+	}
+
+	/// Feeds the next bytes of the file. Bytes past the end of the file are ignored.
+	pub fn push(&mut self, data: &[u8]) -> Result<(), Error> {
+		let mut buffer = std::mem::take(&mut self.buffer);
+		buffer.extend_from_slice(data);
+
+		let mut consumed = 0;
+		if !self.signature_checked {
+			if buffer.len() < PNG_SIGNATURE.len() {
+				self.buffer = buffer;
+				return Ok(());
+			}
+			if buffer[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+				return Err(Error::BadMagic);
+			}
+			self.signature_checked = true;
+			consumed = PNG_SIGNATURE.len();
+		}
+
+		while !self.finished {
+			let rest = &buffer[consumed..];
+			// Length, chunk type, data, checksum
+			let Some(header) = rest.get(..8) else {
+				break;
+			};
+			let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+			let chunk_type = &header[4..];
+			let max_len = match chunk_type {
+				b"IDAT" | b"fdAT" => MAX_CHUNK_LEN,
+				_ => MAX_NON_IMAGE_CHUNK_LEN,
+			};
+			if len > max_len {
+				return Err(Error::ChunkTooLong {
+					chunk_type: String::from_utf8_lossy(chunk_type).into_owned(),
+					len,
+				});
+			}
+			let chunk_len = 12 + len;
+			if rest.len() < chunk_len {
+				break;
+			}
+			let mut state = parser::State { current_byte: 0 };
+			let chunk = parser::Data {
+				data: &rest[..chunk_len],
+			};
+			let block = parser::parse_block(
+				&mut state,
+				&chunk,
+				&DecodeOptions::default(),
+				&mut Vec::new(),
+			)?;
+			self.handle_block(block.block)?;
+			consumed += chunk_len;
+		}
+
+		buffer.drain(..consumed);
+		self.buffer = buffer;
+		Ok(())
+	}
+
+	fn handle_block(&mut self, block: PngBlockKind) -> Result<(), Error> {
+		if self.header.is_none() && !matches!(block, PngBlockKind::IHDR(_)) {
+			return Err(Error::ExpectedIHDR);
+		}
+		match block {
+			PngBlockKind::IHDR(ihdr) => {
+				if !ihdr.is_supported() {
+					return Err(Error::UnsupportedFormat {
+						color_type: ihdr.color_type,
+						bit_depth: ihdr.bit_depth,
+					});
+				}
+				if ihdr.interlace_method > INTERLACE_ADAM7 {
+					return Err(Error::UnknownInterlaceMethod(ihdr.interlace_method));
+				}
+				self.header = Some(ihdr);
+			}
+			PngBlockKind::PLTE(palette) => self.palette = palette.to_vec(),
+			PngBlockKind::TRNS(transparency) => self.transparency = transparency.to_vec(),
+			PngBlockKind::GAMA(gamma) => {
+				self.color_space.gamma = Some(gamma as f32 / 100_000.0);
+			}
+			PngBlockKind::SRGB(intent) => self.color_space.srgb_intent = Some(intent),
+			PngBlockKind::IDAT(IDAT { data }) => {
+				if self.inflater.is_none() {
+					let indexed = self.header.as_ref().unwrap().color_type == COLOR_TYPE_INDEXED;
+					if indexed && self.palette.is_empty() {
+						return Err(Error::NoPLTE);
+					}
+					// Color chunks come before image data, they're all known by now
+					self.gamma_table = gamma_table(&self.color_space);
+					let limit = self.header.as_ref().unwrap().image_data_len();
+					self.inflater = Some(Inflater::zlib().with_limit(limit));
+				}
+				let inflater = self.inflater.as_mut().unwrap();
+				inflater.push(data).map_err(Error::Deflate)?;
+				if !self.is_interlaced() {
+					self.take_scanlines()?;
+				}
+			}
+			PngBlockKind::IEND => {
+				let inflater = self.inflater.as_ref().ok_or(Error::NoIDAT)?;
+				if !inflater.is_finished() {
+					return Err(Error::Deflate(deflate::Error::UnexpectedEnd));
+				}
+				if self.is_interlaced() {
+					self.take_interlaced()?;
+				} else {
+					self.take_scanlines()?;
+				}
+				self.finished = true;
+			}
+			_ => (),
+		}
+		Ok(())
+	}
+
+	fn is_interlaced(&self) -> bool {
+		self.header
+			.as_ref()
+			.is_some_and(|header| header.interlace_method == INTERLACE_ADAM7)
+	}
+
+	/// Unfilters and converts all complete scanlines decompressed so far.
+	fn take_scanlines(&mut self) -> Result<(), Error> {
+		let header = self.header.as_ref().unwrap();
+		let row_header = IHDR {
+			height: 1,
+			..header.clone()
+		};
+		let line_len = header.line_len();
+		let colors = ColorInfo {
+			palette: &self.palette,
+			transparency: &self.transparency,
+		};
+		let decompressed = self.inflater.as_mut().unwrap().output();
+
+		let mut consumed = 0;
+		while decompressed.len() - consumed > line_len && self.next_y < header.height as usize {
+			let filter_type: FilterType = decompressed[consumed].try_into()?;
+			let encoded_line = &decompressed[consumed + 1..consumed + 1 + line_len];
+			consumed += line_len + 1;
+
+			let mut line = encoded_line.to_vec();
+			let previous =
+				(!self.previous_line.is_empty()).then_some(self.previous_line.as_slice());
+			decode_filter(&mut line, previous, filter_type, header.filter_bpp());
+
+			let rgba = expand_to_rgba(&row_header, line.clone(), colors);
+			self.previous_line = line;
+			self.rows.push_back(Row {
+				y: self.next_y,
+				pixels: to_texture_pixels(rgba, self.gamma_table.as_ref()),
+			});
+			self.next_y += 1;
+		}
+		decompressed.drain(..consumed);
+		Ok(())
+	}
+
+	/// Decodes the complete data of an interlaced image.
+	fn take_interlaced(&mut self) -> Result<(), Error> {
+		let header = self.header.as_ref().unwrap();
+		let colors = ColorInfo {
+			palette: &self.palette,
+			transparency: &self.transparency,
+		};
+		let decompressed = std::mem::take(self.inflater.as_mut().unwrap().output());
+		let mut state = parser::State { current_byte: 0 };
+		let data = parser::Data {
+			data: &decompressed,
+		};
+		let rgba = deinterlace(header, &mut state, &data, colors)?;
+		let pixels = to_texture_pixels(rgba, self.gamma_table.as_ref());
+		for (y, row) in pixels.chunks_exact(header.width as usize).enumerate() {
+			self.rows.push_back(Row {
+				y,
+				pixels: row.to_vec(),
+			});
+		}
+		self.next_y = header.height as usize;
+		Ok(())
+	}
+}
+
+fn to_texture_pixels(mut rgba: Vec<u8>, gamma_table: Option<&[u8; 256]>) -> Vec<u32> {
+	rgba_to_bgra(&mut rgba);
+	if let Some(table) = gamma_table {
+		apply_gamma(&mut rgba, table);
+	}
+	rgba.chunks_exact(4)
+		.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+		.collect()
+}
+
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+fn write_block(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+	png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+	png.extend_from_slice(chunk_type);
+	png.extend_from_slice(data);
+	let mut hasher = crc32fast::Hasher::new();
+	hasher.update(chunk_type);
+	hasher.update(data);
+	png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// Packs BGRA bytes into the pixels of a texture.
+fn texture(img_data: Vec<u8>, width: usize, height: usize) -> crate::draw::Texture {
+	let bitmap = img_data
+		.chunks_exact(4)
+		.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+		.collect();
+	crate::draw::Texture {
+		bitmap,
+		width,
+		height,
+		pos: Pos { x: 0, y: 0 },
+	}
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 struct PngBlock<'a> {
@@ -196,16 +942,44 @@ struct PngBlock<'a> {
 #[derive(Debug)]
 enum PngBlockKind<'a> {
 	IHDR(IHDR),
+	/// Palette, RGB triples.
+	PLTE(&'a [u8]),
 	IEND,
 	IDAT(IDAT<'a>),
+	TRNS(&'a [u8]),
+	/// Keyword and Latin-1 text.
 	TEXT(&'a [u8]),
+	/// Like tEXt, with compressed text.
+	ZTXT(&'a [u8]),
+	/// UTF-8 text, optionally compressed, with a language tag.
+	ITXT(&'a [u8]),
+	PHYS(PixelDensity),
 	TIME(TIME),
+	/// Gamma times 100000.
+	GAMA(u32),
+	/// Rendering intent.
+	SRGB(u8),
+	ICCP {
+		name: &'a [u8],
+		/// Zlib compressed.
+		profile: &'a [u8],
+	},
+	/// Animation control, marks the image as an APNG.
+	ACTL {
+		num_frames: u32,
+		/// 0 means forever.
+		num_plays: u32,
+	},
+	FCTL(FCTL),
+	/// Frame data, continues the frame of the preceding fcTL like IDAT does for the default
+	/// image. Sequence number stripped.
+	FDAT(&'a [u8]),
 
 	Unknown,
 }
 
 /// Image Header
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 struct IHDR {
 	width: u32,
@@ -217,6 +991,147 @@ struct IHDR {
 	interlace_method: u8,
 }
 
+const COLOR_TYPE_GRAYSCALE: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_INDEXED: u8 = 3;
+const COLOR_TYPE_RGBA: u8 = 6;
+
+const INTERLACE_ADAM7: u8 = 1;
+
+/// Adam7 passes as (x start, y start, x step, y step) in pixels of the full image.
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+	(0, 0, 8, 8),
+	(4, 0, 8, 8),
+	(0, 4, 4, 8),
+	(2, 0, 4, 4),
+	(0, 2, 2, 4),
+	(1, 0, 2, 2),
+	(0, 1, 1, 2),
+];
+
+impl IHDR {
+	fn is_supported(&self) -> bool {
+		matches!(
+			(self.color_type, self.bit_depth),
+			(COLOR_TYPE_GRAYSCALE, 1 | 2 | 4 | 8 | 16)
+				| (COLOR_TYPE_INDEXED, 1 | 2 | 4 | 8)
+				| (COLOR_TYPE_RGB | COLOR_TYPE_RGBA, 8 | 16)
+		)
+	}
+
+	fn channels(&self) -> usize {
+		match self.color_type {
+			COLOR_TYPE_GRAYSCALE | COLOR_TYPE_INDEXED => 1,
+			COLOR_TYPE_RGB => 3,
+			_ => 4,
+		}
+	}
+
+	fn bits_per_pixel(&self) -> usize {
+		self.channels() * self.bit_depth as usize
+	}
+
+	/// Length of a scanline in bytes, without the filter type byte. Pixels narrower than a byte
+	/// are packed, the last byte of a line may be padded.
+	fn line_len(&self) -> usize {
+		(self.width as usize * self.bits_per_pixel()).div_ceil(8)
+	}
+
+	/// Length of the decompressed image data: the scanlines with their filter type bytes, of
+	/// every pass for interlaced images.
+	fn image_data_len(&self) -> usize {
+		let (width, height) = (self.width as usize, self.height as usize);
+		if self.interlace_method != INTERLACE_ADAM7 {
+			return (self.line_len() + 1) * height;
+		}
+		ADAM7_PASSES
+			.iter()
+			.map(|&(x_start, y_start, x_step, y_step)| {
+				let pass = IHDR {
+					width: width.saturating_sub(x_start).div_ceil(x_step) as u32,
+					height: height.saturating_sub(y_start).div_ceil(y_step) as u32,
+					..self.clone()
+				};
+				if pass.width == 0 {
+					return 0;
+				}
+				(pass.line_len() + 1) * pass.height as usize
+			})
+			.sum()
+	}
+
+	/// Biggest whole factor the image can be scaled down by, keeping it at least `size` pixels
+	/// in its bigger dimension.
+	fn scale_factor(&self, size: usize) -> usize {
+		(self.width.max(self.height) as usize / size.max(1)).max(1)
+	}
+
+	/// Distance in bytes between corresponding bytes of neighbouring pixels, as used by filters.
+	/// Rounded up to 1 for bit depths below 8.
+	fn filter_bpp(&self) -> usize {
+		(self.bits_per_pixel() / 8).max(1)
+	}
+}
+
+/// Frame control of an animated PNG.
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+struct FCTL {
+	sequence_number: u32,
+	width: u32,
+	height: u32,
+	x_offset: u32,
+	y_offset: u32,
+	delay_num: u16,
+	delay_den: u16,
+	dispose_op: u8,
+	blend_op: u8,
+}
+
+/// Leave the canvas as is for the next frame.
+#[allow(dead_code)]
+const DISPOSE_OP_NONE: u8 = 0;
+/// Clear the frame region to transparent black for the next frame.
+const DISPOSE_OP_BACKGROUND: u8 = 1;
+/// Restore the frame region to what it was before the frame.
+const DISPOSE_OP_PREVIOUS: u8 = 2;
+
+/// Replace the frame region, alpha included.
+#[allow(dead_code)]
+const BLEND_OP_SOURCE: u8 = 0;
+/// Composite the frame over the region.
+const BLEND_OP_OVER: u8 = 1;
+
+impl FCTL {
+	fn delay(&self) -> Duration {
+		// A zero denominator means hundredths of a second
+		let den = if self.delay_den == 0 {
+			100
+		} else {
+			self.delay_den
+		};
+		Duration::from_secs_f32(self.delay_num as f32 / den as f32)
+	}
+}
+
+/// Chunks that affect how samples map to colors.
+#[derive(Debug, Copy, Clone)]
+struct ColorInfo<'a> {
+	/// PLTE, RGB triples.
+	palette: &'a [u8],
+	/// tRNS. Alpha of the palette entries for indexed-color images (entries past its end are
+	/// opaque), otherwise the 16 bit sample values of the single fully transparent color.
+	transparency: &'a [u8],
+}
+
+impl ColorInfo<'_> {
+	/// Transparent color key, the `idx`th 16 bit value of tRNS.
+	fn transparent_sample(&self, idx: usize) -> Option<u16> {
+		let bytes = self.transparency.get(idx * 2..idx * 2 + 2)?;
+		Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+	}
+}
+
 /// Image Data
 #[derive(Debug)]
 struct IDAT<'a> {
@@ -236,7 +1151,13 @@ struct TIME {
 }
 
 mod parser {
-	use super::{Error, PngBlock, PngBlockKind, IDAT, IHDR, TIME};
+	use super::{
+		DecodeOptions, Error, PixelDensity, PngBlock, PngBlockKind, Warning, FCTL, IDAT, IHDR,
+		MAX_PIXELS, TIME,
+	};
+
+	/// Only zlib is defined.
+	const COMPRESSION_METHOD_DEFLATE: u8 = 0;
 
 	pub(super) struct State {
 		/// Index of current byte in Self.data
@@ -252,7 +1173,7 @@ mod parser {
 			return Err(Error::BadMagic);
 		}
 
-		let expected_png_signature = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+		let expected_png_signature = &super::PNG_SIGNATURE;
 		let png_signature = &data.data[state.current_byte..(state.current_byte + 8)];
 		let signature_valid = png_signature == expected_png_signature;
 
@@ -264,34 +1185,70 @@ mod parser {
 		}
 	}
 
+	/// Parses the next chunk. Damage allowed by `options` is added to `warnings`.
 	pub(super) fn parse_block<'data>(
 		state: &mut State,
 		data: &'data Data,
+		options: &DecodeOptions,
+		warnings: &mut Vec<Warning>,
 	) -> Result<PngBlock<'data>, Error> {
-		let len = get_u32(state, data)? as usize;
+		let mut len = get_u32(state, data)? as usize;
 		let chunk_type = get_slice(state, data, 4)?;
+		let type_name = || String::from_utf8_lossy(chunk_type).into_owned();
+
+		// Keep what's there of image data cut off by the end of the file
+		let remaining = data.data.len() - state.current_byte;
+		let truncated = options.allow_truncated && chunk_type == b"IDAT" && len + 4 > remaining;
+		if truncated {
+			warnings.push(Warning::Truncated);
+			len = len.min(remaining);
+		}
 		let (chunk_data, block) = match chunk_type {
 			[b'I', b'H', b'D', b'R'] => parse_ihdr(state, data, len)?,
+			[b'P', b'L', b'T', b'E'] => parse_plte(state, data, len)?,
 			[b'I', b'E', b'N', b'D'] => parse_iend(len)?,
 			[b'I', b'D', b'A', b'T'] => parse_idat(state, data, len)?,
 			[b't', b'E', b'X', b't'] => parse_text(state, data, len)?,
+			[b'z', b'T', b'X', b't'] => parse_ztxt(state, data, len)?,
+			[b'i', b'T', b'X', b't'] => parse_itxt(state, data, len)?,
+			[b'p', b'H', b'Y', b's'] => parse_phys(state, data, len)?,
+			[b't', b'R', b'N', b'S'] => parse_trns(state, data, len)?,
 			[b't', b'I', b'M', b'E'] => parse_time(state, data, len)?,
+			[b'g', b'A', b'M', b'A'] => parse_gama(state, data, len)?,
+			[b's', b'R', b'G', b'B'] => parse_srgb(state, data, len)?,
+			[b'i', b'C', b'C', b'P'] => parse_iccp(state, data, len)?,
+			[b'a', b'c', b'T', b'L'] => parse_actl(state, data, len)?,
+			[b'f', b'c', b'T', b'L'] => parse_fctl(state, data, len)?,
+			[b'f', b'd', b'A', b'T'] => parse_fdat(state, data, len)?,
 			_ => {
+				// Critical chunks are marked by an uppercase first letter, decoding without
+				// understanding them may give a wrong image
+				if chunk_type[0].is_ascii_uppercase() {
+					if !options.allow_unknown_critical {
+						return Err(Error::UnknownCriticalChunk(type_name()));
+					}
+					warnings.push(Warning::UnknownCriticalChunk(type_name()));
+				}
 				let data = get_slice(state, data, len)?;
 				let block = PngBlockKind::Unknown;
 				(data, block)
 			}
 		};
 
-		let checksum = get_u32(state, data)?;
+		let checksum = if truncated { 0 } else { get_u32(state, data)? };
 		let calculated_checksum = {
 			let mut hasher = crc32fast::Hasher::new();
 			hasher.update(chunk_type);
 			hasher.update(chunk_data);
 			hasher.finalize()
 		};
-		if calculated_checksum != checksum {
-			return Err(Error::ChecksumFailed);
+		if calculated_checksum != checksum && !truncated {
+			if !options.skip_checksums {
+				return Err(Error::ChecksumFailed);
+			}
+			warnings.push(Warning::ChecksumFailed {
+				chunk_type: type_name(),
+			});
 		}
 
 		Ok(PngBlock {
@@ -321,6 +1278,10 @@ mod parser {
 		if expected_len != parsed_bytes {
 			return Err(Error::IncompleteBlock { block_kind: "IHDR" });
 		}
+		let pixels = (width as usize).saturating_mul(height as usize);
+		if width == 0 || height == 0 || pixels > MAX_PIXELS {
+			return Err(Error::InvalidSize);
+		}
 
 		let data = &data.data[start..state.current_byte];
 		Ok((
@@ -337,6 +1298,19 @@ mod parser {
 		))
 	}
 
+	fn parse_plte<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if !expected_len.is_multiple_of(3) {
+			return Err(Error::InvalidPLTE);
+		}
+		let data = get_slice(state, data, expected_len)?;
+
+		Ok((data, PngBlockKind::PLTE(data)))
+	}
+
 	fn parse_iend<'data>(expected_len: usize) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
 		if expected_len != 0 {
 			return Err(Error::IncompleteBlock { block_kind: "IEND" });
@@ -366,21 +1340,69 @@ mod parser {
 		Ok((data, PngBlockKind::TEXT(data)))
 	}
 
-	fn parse_time<'data>(
+	fn parse_ztxt<'data>(
 		state: &mut State,
 		data: &'data Data,
 		expected_len: usize,
 	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
-		let start = state.current_byte;
+		let data = get_slice(state, data, expected_len)?;
 
-		let year = get_u16(state, data)?;
-		let month = get_u8(state, data)?;
-		let day = get_u8(state, data)?;
-		let hour = get_u8(state, data)?;
-		let minute = get_u8(state, data)?;
-		let second = get_u8(state, data)?;
+		Ok((data, PngBlockKind::ZTXT(data)))
+	}
 
-		let parsed_bytes = state.current_byte - start;
+	fn parse_itxt<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		let data = get_slice(state, data, expected_len)?;
+
+		Ok((data, PngBlockKind::ITXT(data)))
+	}
+
+	fn parse_phys<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if expected_len != 9 {
+			return Err(Error::IncompleteBlock { block_kind: "pHYs" });
+		}
+		let start = state.current_byte;
+		let x = get_u32(state, data)?;
+		let y = get_u32(state, data)?;
+		// 1 is the meter, 0 an unknown unit
+		let in_meters = get_u8(state, data)? == 1;
+
+		let data = &data.data[start..state.current_byte];
+		Ok((data, PngBlockKind::PHYS(PixelDensity { x, y, in_meters })))
+	}
+
+	fn parse_trns<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		let data = get_slice(state, data, expected_len)?;
+
+		Ok((data, PngBlockKind::TRNS(data)))
+	}
+
+	fn parse_time<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		let start = state.current_byte;
+
+		let year = get_u16(state, data)?;
+		let month = get_u8(state, data)?;
+		let day = get_u8(state, data)?;
+		let hour = get_u8(state, data)?;
+		let minute = get_u8(state, data)?;
+		let second = get_u8(state, data)?;
+
+		let parsed_bytes = state.current_byte - start;
 		if expected_len != parsed_bytes {
 			return Err(Error::IncompleteBlock { block_kind: "tIME" });
 		}
@@ -399,6 +1421,128 @@ mod parser {
 		))
 	}
 
+	fn parse_gama<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if expected_len != 4 {
+			return Err(Error::IncompleteBlock { block_kind: "gAMA" });
+		}
+		let start = state.current_byte;
+		let gamma = get_u32(state, data)?;
+
+		let data = &data.data[start..state.current_byte];
+		Ok((data, PngBlockKind::GAMA(gamma)))
+	}
+
+	fn parse_srgb<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if expected_len != 1 {
+			return Err(Error::IncompleteBlock { block_kind: "sRGB" });
+		}
+		let start = state.current_byte;
+		let intent = get_u8(state, data)?;
+
+		let data = &data.data[start..state.current_byte];
+		Ok((data, PngBlockKind::SRGB(intent)))
+	}
+
+	fn parse_iccp<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		let data = get_slice(state, data, expected_len)?;
+
+		// Null terminated profile name, compression method, compressed profile
+		let incomplete = Error::IncompleteBlock { block_kind: "iCCP" };
+		let name_end = data.iter().position(|&b| b == 0).ok_or(incomplete)?;
+		let name = &data[..name_end];
+		let block = match data[name_end + 1..] {
+			[COMPRESSION_METHOD_DEFLATE, ref profile @ ..] => PngBlockKind::ICCP { name, profile },
+			_ => PngBlockKind::Unknown,
+		};
+		Ok((data, block))
+	}
+
+	fn parse_actl<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if expected_len != 8 {
+			return Err(Error::IncompleteBlock { block_kind: "acTL" });
+		}
+		let start = state.current_byte;
+		let num_frames = get_u32(state, data)?;
+		let num_plays = get_u32(state, data)?;
+
+		let data = &data.data[start..state.current_byte];
+		Ok((
+			data,
+			PngBlockKind::ACTL {
+				num_frames,
+				num_plays,
+			},
+		))
+	}
+
+	fn parse_fctl<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		let start = state.current_byte;
+
+		let sequence_number = get_u32(state, data)?;
+		let width = get_u32(state, data)?;
+		let height = get_u32(state, data)?;
+		let x_offset = get_u32(state, data)?;
+		let y_offset = get_u32(state, data)?;
+		let delay_num = get_u16(state, data)?;
+		let delay_den = get_u16(state, data)?;
+		let dispose_op = get_u8(state, data)?;
+		let blend_op = get_u8(state, data)?;
+
+		let parsed_bytes = state.current_byte - start;
+		if expected_len != parsed_bytes {
+			return Err(Error::IncompleteBlock { block_kind: "fcTL" });
+		}
+
+		let data = &data.data[start..state.current_byte];
+		Ok((
+			data,
+			PngBlockKind::FCTL(FCTL {
+				sequence_number,
+				width,
+				height,
+				x_offset,
+				y_offset,
+				delay_num,
+				delay_den,
+				dispose_op,
+				blend_op,
+			}),
+		))
+	}
+
+	fn parse_fdat<'data>(
+		state: &mut State,
+		data: &'data Data,
+		expected_len: usize,
+	) -> Result<(&'data [u8], PngBlockKind<'data>), Error> {
+		if expected_len < 4 {
+			return Err(Error::IncompleteBlock { block_kind: "fdAT" });
+		}
+		let data = get_slice(state, data, expected_len)?;
+
+		Ok((data, PngBlockKind::FDAT(&data[4..])))
+	}
+
 	fn get_u32(state: &mut State, data: &Data) -> Result<u32, Error> {
 		if state.current_byte + 4 > data.data.len() {
 			return Err(Error::FileEnd);
@@ -449,78 +1593,66 @@ mod parser {
 	}
 }
 
-struct IDATBlockStream<'a> {
-	blocks: &'a [PngBlock<'a>],
-	current_data_block: &'a [u8],
-	current_data_block_idx: usize,
-	current_buf_idx: usize,
+/// Decompresses the data of all IDAT blocks, which together form a single zlib stream of the
+/// image described by `header`. Decompressing stops with an error once it's bigger than the header
+/// promises. With [DecodeOptions::allow_truncated], whatever came out of a damaged stream is kept.
+fn decompress_img_data(
+	header: &IHDR,
+	blocks: &[PngBlock<'_>],
+	options: &DecodeOptions,
+	warnings: &mut Vec<Warning>,
+) -> Result<Vec<u8>, Error> {
+	inflate_img_data(header, blocks, options, warnings, |_| Ok(()))
 }
 
-impl<'a> IDATBlockStream<'a> {
-	fn new(blocks: &'a [PngBlock<'a>]) -> Result<Self, Error> {
-		let (data_block_idx, data_block) = blocks
-			.iter()
-			.enumerate()
-			.find_map(|(idx, block)| match block {
-				PngBlock {
-					block: PngBlockKind::IDAT(idat),
-					..
-				} => Some((idx, idat.data)),
-				_ => None,
-			})
-			.ok_or(Error::NoIDAT)?;
-
-		Ok(IDATBlockStream {
-			blocks,
-			current_data_block: data_block,
-			current_data_block_idx: data_block_idx,
-			current_buf_idx: 0,
-		})
+/// Like [decompress_img_data], handing the data decompressed so far to `consume` after every
+/// IDAT block. Whatever `consume` leaves in the buffer is returned at the end.
+fn inflate_img_data(
+	header: &IHDR,
+	blocks: &[PngBlock<'_>],
+	options: &DecodeOptions,
+	warnings: &mut Vec<Warning>,
+	mut consume: impl FnMut(&mut Vec<u8>) -> Result<(), Error>,
+) -> Result<Vec<u8>, Error> {
+	let mut inflater = Inflater::zlib().with_limit(header.image_data_len());
+	let mut result = Ok(());
+	let mut any_data = false;
+	for block in blocks {
+		if let PngBlockKind::IDAT(idat) = &block.block {
+			if options
+				.cancel
+				.as_ref()
+				.is_some_and(CancelToken::is_cancelled)
+			{
+				return Err(Error::Cancelled);
+			}
+			any_data = true;
+			result = inflater.push(idat.data);
+			consume(inflater.output())?;
+			if result.is_err() {
+				break;
+			}
+		}
 	}
-}
-
-impl<'a> std::io::Read for IDATBlockStream<'a> {
-	fn read(&mut self, mut buf: &mut [u8]) -> std::io::Result<usize> {
-		if self.current_buf_idx == self.current_data_block.len() {
-			let next_data_block = self
-				.blocks
-				.iter()
-				.enumerate()
-				.skip(self.current_data_block_idx + 1)
-				.find_map(|(idx, block)| match block {
-					PngBlock {
-						block: PngBlockKind::IDAT(idat),
-						..
-					} => Some((idx, idat.data)),
-					_ => None,
-				});
-
-			let Some((next_data_block_idx, next_data_block)) = next_data_block else {
-				return Ok(0);
-			};
-			self.current_data_block = next_data_block;
-			self.current_data_block_idx = next_data_block_idx;
-			self.current_buf_idx = 0;
+	if !any_data {
+		return Err(Error::NoIDAT);
+	}
+	let result = result.and_then(|()| match inflater.is_finished() {
+		true => Ok(()),
+		false => Err(deflate::Error::UnexpectedEnd),
+	});
+	match result {
+		Ok(()) => Ok(std::mem::take(inflater.output())),
+		Err(err) if options.allow_truncated => {
+			warnings.push(Warning::Deflate(err));
+			Ok(std::mem::take(inflater.output()))
 		}
-
-		let written = buf.write(&self.current_data_block[self.current_buf_idx..])?;
-		self.current_buf_idx += written;
-		Ok(written)
+		Err(err) => Err(Error::Deflate(err)),
 	}
 }
 
-fn decompress_img_data(blocks: &[PngBlock<'_>]) -> Result<Vec<u8>, Error> {
-	let block_stream = IDATBlockStream::new(blocks)?;
-
-	let mut deflater = ZlibDecoder::new(block_stream);
-	let mut data = Vec::new();
-	deflater.read_to_end(&mut data).map_err(Error::Deflate)?;
-
-	Ok(data)
-}
-
 #[repr(u8)]
-#[allow(dead_code)]
+#[derive(Copy, Clone)]
 enum FilterType {
 	None = 0,
 	Sub = 1,
@@ -536,71 +1668,589 @@ impl TryFrom<u8> for FilterType {
 		if v > FilterType::Paeth as u8 {
 			return Err(Error::InvalidFilterType);
 		}
-		unsafe { Ok(std::mem::transmute::<_, FilterType>(v)) }
+		unsafe { Ok(std::mem::transmute::<u8, FilterType>(v)) }
 	}
 }
 
-fn decode_filter(
-	output_img: &mut Vec<u8>,
-	encoded_line: &[u8],
+const FILTER_TYPES: [FilterType; 5] = [
+	FilterType::None,
+	FilterType::Sub,
+	FilterType::Up,
+	FilterType::Average,
+	FilterType::Paeth,
+];
+
+/// Filters every `line_len` bytes long line of `raw_img`, prefixing it with its filter type.
+/// Each line gets the filter with the lowest sum of absolute values of the output bytes taken as
+/// signed, the heuristic recommended by the PNG spec, which tends to compress best.
+fn encode_filters(raw_img: &[u8], line_len: usize, bpp: usize) -> Vec<u8> {
+	let height = raw_img.len().checked_div(line_len).unwrap_or(0);
+	let mut filtered = Vec::with_capacity((line_len + 1) * height);
+	let mut candidate = Vec::with_capacity(line_len);
+	let mut best = Vec::with_capacity(line_len);
+	for y_idx in 0..height {
+		let line = &raw_img[y_idx * line_len..(y_idx + 1) * line_len];
+		let previous = (y_idx > 0).then(|| &raw_img[(y_idx - 1) * line_len..y_idx * line_len]);
+
+		let mut best_cost = u64::MAX;
+		let mut best_filter = FilterType::None;
+		for filter_type in FILTER_TYPES {
+			candidate.clear();
+			encode_filter(&mut candidate, line, previous, filter_type, bpp);
+			let cost = candidate
+				.iter()
+				.map(|&byte| (byte as i8).unsigned_abs() as u64)
+				.sum();
+			if cost < best_cost {
+				best_cost = cost;
+				best_filter = filter_type;
+				std::mem::swap(&mut best, &mut candidate);
+			}
+		}
+		filtered.push(best_filter as u8);
+		filtered.extend_from_slice(&best);
+	}
+	filtered
+}
+
+/// Inverse of [decode_filter]. `previous` is the unfiltered line above.
+fn encode_filter(
+	output: &mut Vec<u8>,
+	line: &[u8],
+	previous: Option<&[u8]>,
 	filter_type: FilterType,
-	y_idx: usize,
+	bpp: usize,
 ) {
-	match filter_type {
-		FilterType::None => {
-			output_img.extend_from_slice(encoded_line);
+	for x_idx in 0..line.len() {
+		let a = if x_idx >= bpp { line[x_idx - bpp] } else { 0 };
+		let b = previous.map_or(0, |previous| previous[x_idx]);
+		let c = match previous {
+			Some(previous) if x_idx >= bpp => previous[x_idx - bpp],
+			_ => 0,
+		};
+		let predicted = match filter_type {
+			FilterType::None => 0,
+			FilterType::Sub => a,
+			FilterType::Up => b,
+			FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+			FilterType::Paeth => paeth(a as i16, b as i16, c as i16),
+		};
+		output.push(line[x_idx].wrapping_sub(predicted));
+	}
+}
+
+/// Reverses the filter of `line` in place. `previous` is the unfiltered line above, `None` for
+/// the first line. `bpp` is the filter byte distance, see [IHDR::filter_bpp].
+fn decode_filter(line: &mut [u8], previous: Option<&[u8]>, filter_type: FilterType, bpp: usize) {
+	// Pixels are handled as fixed size arrays, which lets the compiler keep them in registers and
+	// vectorize the per byte operations
+	macro_rules! specialized {
+		($unfilter:ident $(, $previous:expr)?) => {
+			match bpp {
+				1 => $unfilter::<1>(line $(, $previous)?),
+				2 => $unfilter::<2>(line $(, $previous)?),
+				3 => $unfilter::<3>(line $(, $previous)?),
+				4 => $unfilter::<4>(line $(, $previous)?),
+				6 => $unfilter::<6>(line $(, $previous)?),
+				8 => $unfilter::<8>(line $(, $previous)?),
+				_ => unreachable!("PNG pixels are 1, 2, 3, 4, 6 or 8 bytes"),
+			}
+		};
+	}
+
+	match (filter_type, previous) {
+		(FilterType::None, _) | (FilterType::Up, None) => (),
+		// Without a line above, Paeth always predicts the pixel to the left
+		(FilterType::Sub, _) | (FilterType::Paeth, None) => specialized!(unfilter_sub),
+		(FilterType::Up, Some(previous)) => {
+			for (byte, above) in line.iter_mut().zip(previous) {
+				*byte = byte.wrapping_add(*above);
+			}
+		}
+		(FilterType::Average, previous) => specialized!(unfilter_average, previous),
+		(FilterType::Paeth, Some(previous)) => specialized!(unfilter_paeth, previous),
+	}
+}
+
+fn unfilter_sub<const BPP: usize>(line: &mut [u8]) {
+	let mut left = [0u8; BPP];
+	for pixel in line.chunks_exact_mut(BPP) {
+		for i in 0..BPP {
+			pixel[i] = pixel[i].wrapping_add(left[i]);
+		}
+		left.copy_from_slice(pixel);
+	}
+}
+
+fn unfilter_average<const BPP: usize>(line: &mut [u8], previous: Option<&[u8]>) {
+	let mut left = [0u8; BPP];
+	let mut above = [0u8; BPP];
+	for (idx, pixel) in line.chunks_exact_mut(BPP).enumerate() {
+		if let Some(previous) = previous {
+			above.copy_from_slice(&previous[idx * BPP..(idx + 1) * BPP]);
+		}
+		for i in 0..BPP {
+			let average = ((left[i] as u16 + above[i] as u16) / 2) as u8;
+			pixel[i] = pixel[i].wrapping_add(average);
+		}
+		left.copy_from_slice(pixel);
+	}
+}
+
+fn unfilter_paeth<const BPP: usize>(line: &mut [u8], previous: &[u8]) {
+	let mut left = [0u8; BPP];
+	let mut above_left = [0u8; BPP];
+	for (pixel, above) in line.chunks_exact_mut(BPP).zip(previous.chunks_exact(BPP)) {
+		for i in 0..BPP {
+			let predicted = paeth(left[i] as i16, above[i] as i16, above_left[i] as i16);
+			pixel[i] = pixel[i].wrapping_add(predicted);
 		}
-		FilterType::Sub => {
-			for x_idx in 0..encoded_line.len() {
-				let a = encoded_line[x_idx];
-				let b = (x_idx > 3)
-					.then(|| output_img[y_idx * encoded_line.len() + (x_idx - 4)])
-					.unwrap_or_default();
+		left.copy_from_slice(pixel);
+		above_left.copy_from_slice(above);
+	}
+}
 
-				output_img.push(a.wrapping_add(b))
+/// Decodes the frames of an animated PNG and composes each onto the canvas left by the previous
+/// ones, as described by their fcTL chunks.
+fn compose_frames(
+	header: &IHDR,
+	blocks: &[PngBlock],
+	colors: ColorInfo,
+	cancel: Option<&CancelToken>,
+) -> Result<Vec<Frame>, Error> {
+	// Compressed data of each frame. IDAT belongs to the first frame only if it follows an fcTL,
+	// otherwise the default image isn't a part of the animation.
+	let mut frame_data: Vec<(FCTL, Vec<u8>)> = Vec::new();
+	for block in blocks {
+		match block.block {
+			PngBlockKind::FCTL(fctl) => frame_data.push((fctl, Vec::new())),
+			PngBlockKind::IDAT(IDAT { data }) | PngBlockKind::FDAT(data) => {
+				if let Some((_, frame)) = frame_data.last_mut() {
+					frame.extend_from_slice(data);
+				}
 			}
+			_ => (),
+		}
+	}
+
+	let width = header.width as usize;
+	let height = header.height as usize;
+	let mut canvas = vec![0u8; width * height * 4];
+	let mut frames = Vec::with_capacity(frame_data.len());
+	for (fctl, data) in frame_data {
+		if cancel.is_some_and(CancelToken::is_cancelled) {
+			return Err(Error::Cancelled);
+		}
+		let (x_offset, y_offset) = (fctl.x_offset as usize, fctl.y_offset as usize);
+		let (frame_width, frame_height) = (fctl.width as usize, fctl.height as usize);
+		if frame_width == 0
+			|| frame_height == 0
+			|| x_offset + frame_width > width
+			|| y_offset + frame_height > height
+		{
+			return Err(Error::InvalidFrame);
 		}
-		FilterType::Up => {
-			for x_idx in 0..encoded_line.len() {
-				let a = encoded_line[x_idx];
-				let b = (y_idx > 0)
-					.then(|| output_img[(y_idx - 1) * encoded_line.len() + x_idx])
-					.unwrap_or_default();
 
-				output_img.push(a.wrapping_add(b));
+		let frame_header = IHDR {
+			width: fctl.width,
+			height: fctl.height,
+			..header.clone()
+		};
+		let mut inflater = Inflater::zlib().with_limit(frame_header.image_data_len());
+		let decompressed = inflater
+			.push(&data)
+			.and_then(|()| inflater.finish())
+			.map_err(Error::Deflate)?;
+		let decompressed = parser::Data {
+			data: &decompressed,
+		};
+		let mut state = parser::State { current_byte: 0 };
+		let pixels = if header.interlace_method == INTERLACE_ADAM7 {
+			deinterlace(&frame_header, &mut state, &decompressed, colors)?
+		} else {
+			let scanlines = unfilter(&frame_header, &mut state, &decompressed)?;
+			expand_to_rgba(&frame_header, scanlines, colors)
+		};
+
+		// Restoring what was there before the first frame means clearing it, says the APNG spec
+		let dispose_op = match (frames.is_empty(), fctl.dispose_op) {
+			(true, DISPOSE_OP_PREVIOUS) => DISPOSE_OP_BACKGROUND,
+			(_, dispose_op) => dispose_op,
+		};
+		let previous = (dispose_op == DISPOSE_OP_PREVIOUS).then(|| canvas.clone());
+		for (y, row) in pixels.chunks_exact(frame_width * 4).enumerate() {
+			let start = ((y_offset + y) * width + x_offset) * 4;
+			let canvas_row = &mut canvas[start..start + frame_width * 4];
+			if fctl.blend_op == BLEND_OP_OVER {
+				for (dst, src) in canvas_row.chunks_exact_mut(4).zip(row.chunks_exact(4)) {
+					blend_over(dst, src);
+				}
+			} else {
+				canvas_row.copy_from_slice(row);
 			}
 		}
-		FilterType::Average => {
-			for x_idx in 0..encoded_line.len() {
-				let a = encoded_line[x_idx];
-				let b = (x_idx > 3)
-					.then(|| output_img[y_idx * encoded_line.len() + (x_idx - 4)])
-					.unwrap_or_default() as u16;
-				let c = (y_idx > 0)
-					.then(|| output_img[(y_idx - 1) * encoded_line.len() + x_idx])
-					.unwrap_or_default() as u16;
 
-				output_img.push(a.wrapping_add((b.wrapping_add(c) / 2) as u8));
+		let mut img_data = canvas.clone();
+		rgba_to_bgra(&mut img_data);
+		frames.push(Frame {
+			img_data,
+			delay: fctl.delay(),
+		});
+		if frames.len() * width * height >= MAX_FRAME_PIXELS {
+			debug!(
+				"Skipping the frames after frame {} of a big animation",
+				frames.len()
+			);
+			break;
+		}
+
+		match (dispose_op, previous) {
+			(DISPOSE_OP_PREVIOUS, Some(previous)) => canvas = previous,
+			(DISPOSE_OP_BACKGROUND, _) => {
+				for y in y_offset..y_offset + frame_height {
+					let start = (y * width + x_offset) * 4;
+					canvas[start..start + frame_width * 4].fill(0);
+				}
 			}
+			_ => (),
 		}
-		FilterType::Paeth => {
-			for x_idx in 0..encoded_line.len() {
-				let a = (x_idx > 3)
-					.then(|| output_img[y_idx * encoded_line.len() + (x_idx - 4)])
-					.unwrap_or_default() as i16;
-				let b = (y_idx > 0)
-					.then(|| output_img[(y_idx - 1) * encoded_line.len() + x_idx])
-					.unwrap_or_default() as i16;
-				let c = (x_idx > 3 && y_idx > 0)
-					.then(|| output_img[(y_idx - 1) * encoded_line.len() + (x_idx - 4)])
-					.unwrap_or_default() as i16;
+	}
+	Ok(frames)
+}
 
-				output_img.push(encoded_line[x_idx].wrapping_add(paeth(a, b, c)));
+/// Composites RGBA pixel `src` over `dst`, neither premultiplied.
+fn blend_over(dst: &mut [u8], src: &[u8]) {
+	let src_alpha = src[3] as u32;
+	match src_alpha {
+		0 => return,
+		255 => {
+			dst.copy_from_slice(src);
+			return;
+		}
+		_ => (),
+	}
+	let dst_weight = dst[3] as u32 * (255 - src_alpha);
+	// Output alpha, times 255
+	let alpha = src_alpha * 255 + dst_weight;
+	for channel in 0..3 {
+		let color = src[channel] as u32 * src_alpha * 255 + dst[channel] as u32 * dst_weight;
+		dst[channel] = (color / alpha) as u8;
+	}
+	dst[3] = (alpha / 255) as u8;
+}
+
+/// Swaps the red and blue channel of every pixel, which is the order textures use.
+fn rgba_to_bgra(img_data: &mut [u8]) {
+	img_data
+		.chunks_exact_mut(4)
+		.map(|chunk| &mut chunk[0..3])
+		.for_each(|chunk| chunk.reverse());
+}
+
+/// Reverses the filtering of the scanlines of an image (or of one interlacing pass, described by
+/// `header`), reading them from `data`.
+fn unfilter(
+	header: &IHDR,
+	state: &mut parser::State,
+	data: &parser::Data,
+) -> Result<Vec<u8>, Error> {
+	let line_len = header.line_len();
+	let height = header.height as usize;
+	if (header.width as usize).saturating_mul(height) > MAX_PIXELS {
+		return Err(Error::InvalidSize);
+	}
+	// Every scanline is preceded by its filter type byte
+	if data.data.len().saturating_sub(state.current_byte) < (line_len + 1) * height {
+		return Err(Error::FileEnd);
+	}
+	let mut raw_img = vec![0; line_len * height];
+	for row_idx in 0..height {
+		let filter_type: FilterType = parser::get_u8(state, data)?.try_into()?;
+		let encoded_line = parser::get_slice(state, data, line_len)?;
+		let (done, rest) = raw_img.split_at_mut(row_idx * line_len);
+		let line = &mut rest[..line_len];
+		line.copy_from_slice(encoded_line);
+		let previous = (row_idx > 0).then(|| &done[(row_idx - 1) * line_len..]);
+		decode_filter(line, previous, filter_type, header.filter_bpp());
+	}
+	Ok(raw_img)
+}
+
+/// Decodes a non-interlaced image scaled down by `scale`, unfiltering and scaling scanlines as
+/// they're decompressed.
+fn decode_scaled(
+	header: &IHDR,
+	blocks: &[PngBlock],
+	colors: ColorInfo,
+	scale: usize,
+	options: &DecodeOptions,
+	warnings: &mut Vec<Warning>,
+) -> Result<Vec<u8>, Error> {
+	let mut decoder = ScaledDecoder {
+		header,
+		row_header: IHDR {
+			height: 1,
+			..header.clone()
+		},
+		colors,
+		scaler: BoxScaler::new(header.width as usize, scale),
+		previous_line: Vec::new(),
+		next_y: 0,
+	};
+	let mut rest = inflate_img_data(header, blocks, options, warnings, |decompressed| {
+		decoder.take_scanlines(decompressed)
+	})?;
+
+	let rows_left = header.height as usize - decoder.next_y;
+	let missing_bytes = (rows_left * (header.line_len() + 1)).saturating_sub(rest.len());
+	if missing_bytes > 0 {
+		if !options.allow_truncated || missing_bytes > MAX_MISSING_BYTES {
+			return Err(Error::FileEnd);
+		}
+		warnings.push(Warning::MissingImageData { missing_bytes });
+		// Zeros decode as unfiltered transparent black (or black, without alpha) scanlines
+		rest.resize(rest.len() + missing_bytes, 0);
+		decoder.take_scanlines(&mut rest)?;
+	}
+	Ok(decoder.scaler.finish())
+}
+
+struct ScaledDecoder<'a> {
+	header: &'a IHDR,
+	row_header: IHDR,
+	colors: ColorInfo<'a>,
+	scaler: BoxScaler,
+	/// Last unfiltered scanline, the next one may be filtered relative to it.
+	previous_line: Vec<u8>,
+	next_y: usize,
+}
+
+impl ScaledDecoder<'_> {
+	/// Unfilters and scales all complete scanlines in `decompressed`, removing them from it.
+	fn take_scanlines(&mut self, decompressed: &mut Vec<u8>) -> Result<(), Error> {
+		let line_len = self.header.line_len();
+		let mut consumed = 0;
+		while decompressed.len() - consumed > line_len && self.next_y < self.header.height as usize
+		{
+			let filter_type: FilterType = decompressed[consumed].try_into()?;
+			let mut line = decompressed[consumed + 1..consumed + 1 + line_len].to_vec();
+			consumed += line_len + 1;
+
+			let previous = (self.next_y > 0).then_some(self.previous_line.as_slice());
+			decode_filter(&mut line, previous, filter_type, self.header.filter_bpp());
+			let rgba = expand_to_rgba(&self.row_header, line.clone(), self.colors);
+			self.scaler.add_row(&rgba);
+			self.previous_line = line;
+			self.next_y += 1;
+		}
+		decompressed.drain(..consumed);
+		Ok(())
+	}
+}
+
+/// Scales an RGBA image `width` pixels wide down by `scale`.
+fn downscale(rgba: Vec<u8>, width: usize, scale: usize) -> Vec<u8> {
+	if scale == 1 {
+		return rgba;
+	}
+	let mut scaler = BoxScaler::new(width, scale);
+	for row in rgba.chunks_exact(width * 4) {
+		scaler.add_row(row);
+	}
+	scaler.finish()
+}
+
+/// Scales RGBA rows down by a whole factor as they come, each `scale`x`scale` square of pixels
+/// becoming their average. Squares at the right and bottom edges may be cut short.
+struct BoxScaler {
+	width: usize,
+	scale: usize,
+	/// Channel sums of every output pixel of the current output row.
+	sums: Vec<u64>,
+	rows_summed: usize,
+	output: Vec<u8>,
+}
+
+impl BoxScaler {
+	fn new(width: usize, scale: usize) -> Self {
+		BoxScaler {
+			width,
+			scale,
+			sums: vec![0; width.div_ceil(scale) * 4],
+			rows_summed: 0,
+			output: Vec::new(),
+		}
+	}
+
+	fn add_row(&mut self, rgba: &[u8]) {
+		for (x, pixel) in rgba.chunks_exact(4).enumerate() {
+			let sum = &mut self.sums[x / self.scale * 4..][..4];
+			for (sum, &channel) in sum.iter_mut().zip(pixel) {
+				*sum += channel as u64;
 			}
 		}
+		self.rows_summed += 1;
+		if self.rows_summed == self.scale {
+			self.flush();
+		}
+	}
+
+	fn flush(&mut self) {
+		for (out_x, sum) in self.sums.chunks_exact_mut(4).enumerate() {
+			let columns = self.scale.min(self.width - out_x * self.scale);
+			let count = (columns * self.rows_summed) as u64;
+			for channel in sum {
+				self.output.push((*channel / count) as u8);
+				*channel = 0;
+			}
+		}
+		self.rows_summed = 0;
+	}
+
+	fn finish(mut self) -> Vec<u8> {
+		if self.rows_summed > 0 {
+			self.flush();
+		}
+		self.output
 	}
 }
 
+/// Decodes the seven Adam7 passes, each a small image of its own, and puts their pixels together
+/// into a single RGBA image.
+fn deinterlace(
+	header: &IHDR,
+	state: &mut parser::State,
+	data: &parser::Data,
+	colors: ColorInfo,
+) -> Result<Vec<u8>, Error> {
+	let width = header.width as usize;
+	let height = header.height as usize;
+	let mut rgba = vec![0; width * height * 4];
+
+	for (x_start, y_start, x_step, y_step) in ADAM7_PASSES {
+		let pass_width = width.saturating_sub(x_start).div_ceil(x_step);
+		let pass_height = height.saturating_sub(y_start).div_ceil(y_step);
+		// Empty passes have no scanlines at all, not even filter type bytes
+		if pass_width == 0 || pass_height == 0 {
+			continue;
+		}
+		let pass_header = IHDR {
+			width: pass_width as u32,
+			height: pass_height as u32,
+			..header.clone()
+		};
+		let scanlines = unfilter(&pass_header, state, data)?;
+		let pass = expand_to_rgba(&pass_header, scanlines, colors);
+
+		for (pass_y, row) in pass.chunks_exact(pass_width * 4).enumerate() {
+			let y = y_start + pass_y * y_step;
+			for (pass_x, pixel) in row.chunks_exact(4).enumerate() {
+				let x = x_start + pass_x * x_step;
+				let idx = (y * width + x) * 4;
+				rgba[idx..idx + 4].copy_from_slice(pixel);
+			}
+		}
+	}
+	Ok(rgba)
+}
+
+/// Converts unfiltered scanlines to 8 bit RGBA. 16 bit samples are reduced to 8 bits, after
+/// being compared with the transparent color key.
+fn expand_to_rgba(header: &IHDR, scanlines: Vec<u8>, colors: ColorInfo) -> Vec<u8> {
+	let width = header.width as usize;
+	let bit_depth = header.bit_depth as usize;
+	let mut rgba = Vec::with_capacity(width * header.height as usize * 4);
+	match header.color_type {
+		COLOR_TYPE_RGBA if bit_depth == 8 => return scanlines,
+		COLOR_TYPE_RGBA => {
+			rgba.extend(
+				scanlines
+					.chunks_exact(2)
+					.map(|sample| reduce_to_8_bits(wide_sample(sample, 0, bit_depth), bit_depth)),
+			);
+		}
+		COLOR_TYPE_RGB => {
+			let key = (0..3)
+				.map(|idx| colors.transparent_sample(idx))
+				.collect::<Option<Vec<_>>>();
+			for pixel in scanlines.chunks_exact(3 * bit_depth / 8) {
+				let rgb: [u16; 3] = std::array::from_fn(|idx| wide_sample(pixel, idx, bit_depth));
+				let transparent = key
+					.as_ref()
+					.is_some_and(|key| rgb.iter().zip(key).all(|(c, k)| c == k));
+				let alpha = if transparent { 0 } else { 0xFF };
+				let [r, g, b] = rgb.map(|sample| reduce_to_8_bits(sample, bit_depth));
+				rgba.extend_from_slice(&[r, g, b, alpha]);
+			}
+		}
+		COLOR_TYPE_INDEXED => {
+			for line in scanlines.chunks_exact(header.line_len()) {
+				for x in 0..width {
+					let idx = packed_sample(line, x, bit_depth) as usize;
+					// Out of range indices are an error per spec, show them as black instead
+					let [r, g, b] = colors
+						.palette
+						.get(idx * 3..idx * 3 + 3)
+						.map_or([0; 3], |rgb| [rgb[0], rgb[1], rgb[2]]);
+					let alpha = colors.transparency.get(idx).copied().unwrap_or(0xFF);
+					rgba.extend_from_slice(&[r, g, b, alpha]);
+				}
+			}
+		}
+		_ => {
+			let key = colors.transparent_sample(0);
+			for line in scanlines.chunks_exact(header.line_len()) {
+				for x in 0..width {
+					let (sample, gray) = match bit_depth {
+						16 => {
+							let sample = wide_sample(line, x, bit_depth);
+							(sample, reduce_to_8_bits(sample, bit_depth))
+						}
+						_ => {
+							let sample = packed_sample(line, x, bit_depth);
+							(sample as u16, scale_to_8_bits(sample, bit_depth))
+						}
+					};
+					let alpha = if key == Some(sample) { 0 } else { 0xFF };
+					rgba.extend_from_slice(&[gray, gray, gray, alpha]);
+				}
+			}
+		}
+	}
+	rgba
+}
+
+/// Sample `idx` of a pixel or scanline with 8 or 16 bit (big endian) samples.
+fn wide_sample(data: &[u8], idx: usize, bit_depth: usize) -> u16 {
+	match bit_depth {
+		16 => u16::from_be_bytes([data[idx * 2], data[idx * 2 + 1]]),
+		_ => data[idx] as u16,
+	}
+}
+
+/// Keeps the most significant byte of 16 bit samples, 8 bit ones are left as they are.
+fn reduce_to_8_bits(sample: u16, bit_depth: usize) -> u8 {
+	match bit_depth {
+		16 => (sample >> 8) as u8,
+		_ => sample as u8,
+	}
+}
+
+/// Sample `idx` of a scanline with samples of at most 8 bits, packed starting from the most
+/// significant bit.
+fn packed_sample(line: &[u8], idx: usize, bit_depth: usize) -> u8 {
+	let bit = idx * bit_depth;
+	let shift = 8 - bit_depth - bit % 8;
+	let mask = ((1u16 << bit_depth) - 1) as u8;
+	(line[bit / 8] >> shift) & mask
+}
+
+/// Scales a sample of lower bit depth to the full 0-255 range, e.g. 1 bit white (1) to 255.
+fn scale_to_8_bits(sample: u8, bit_depth: usize) -> u8 {
+	let max = (1u16 << bit_depth) - 1;
+	(sample as u16 * 255 / max) as u8
+}
+
 fn paeth(a: i16, b: i16, c: i16) -> u8 {
 	let p = a + b - c;
 	let pa = (p - a).abs();
@@ -614,3 +2264,587 @@ fn paeth(a: i16, b: i16, c: i16) -> u8 {
 		c as u8
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Builds a PNG file out of chunks, computing their checksums.
+	fn png_file(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+		let mut png = PNG_SIGNATURE.to_vec();
+		for (chunk_type, data) in chunks {
+			write_block(&mut png, chunk_type, data);
+		}
+		write_block(&mut png, b"IEND", &[]);
+		png
+	}
+
+	fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> Vec<u8> {
+		let mut ihdr = Vec::with_capacity(13);
+		ihdr.extend_from_slice(&width.to_be_bytes());
+		ihdr.extend_from_slice(&height.to_be_bytes());
+		ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace]);
+		ihdr
+	}
+
+	/// Compressed image data of `lines`, each with filter type None.
+	fn idat(lines: &[&[u8]]) -> Vec<u8> {
+		let scanlines: Vec<u8> = lines
+			.iter()
+			.flat_map(|line| std::iter::once(FilterType::None as u8).chain(line.iter().copied()))
+			.collect();
+		deflate::zlib_compress(&scanlines)
+	}
+
+	/// Decodes a non-interlaced image with the given lines and returns its RGBA pixels.
+	fn decode_lines(
+		width: u32,
+		bit_depth: u8,
+		color_type: u8,
+		extra: &[(&[u8; 4], Vec<u8>)],
+		lines: &[&[u8]],
+	) -> Vec<[u8; 4]> {
+		let mut chunks = vec![(
+			b"IHDR",
+			ihdr(width, lines.len() as u32, bit_depth, color_type, 0),
+		)];
+		chunks.extend(extra.iter().cloned());
+		chunks.push((b"IDAT", idat(lines)));
+		rgba_pixels(&Png::load_from_slice(&png_file(&chunks)).unwrap().img_data)
+	}
+
+	/// Converts the BGRA image data of a decoded image back to RGBA pixels.
+	fn rgba_pixels(img_data: &[u8]) -> Vec<[u8; 4]> {
+		img_data
+			.chunks_exact(4)
+			.map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+			.collect()
+	}
+
+	fn gray(value: u8) -> [u8; 4] {
+		[value, value, value, 0xFF]
+	}
+
+	/// Bytes from a xorshift generator.
+	fn noise(len: usize, mut state: u32) -> Vec<u8> {
+		(0..len)
+			.map(|_| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				state as u8
+			})
+			.collect()
+	}
+
+	#[test]
+	fn grayscale_bit_depths() {
+		let decode = |bit_depth, line: &[u8]| {
+			decode_lines(4, bit_depth, COLOR_TYPE_GRAYSCALE, &[], &[line])
+		};
+		assert_eq!(
+			decode(1, &[0b1010_0000]),
+			[gray(255), gray(0), gray(255), gray(0)]
+		);
+		assert_eq!(
+			decode(2, &[0b00_01_10_11]),
+			[gray(0), gray(85), gray(170), gray(255)]
+		);
+		assert_eq!(
+			decode(4, &[0x0F, 0x8F]),
+			[gray(0), gray(255), gray(136), gray(255)]
+		);
+		assert_eq!(
+			decode(8, &[0, 1, 128, 255]),
+			[gray(0), gray(1), gray(128), gray(255)]
+		);
+		assert_eq!(
+			decode(16, &[0x12, 0x34, 0xFF, 0xFF, 0x00, 0xFF, 0x80, 0x00]),
+			[gray(0x12), gray(0xFF), gray(0), gray(0x80)]
+		);
+	}
+
+	#[test]
+	fn indexed_bit_depths() {
+		let palette = vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 9, 9, 9];
+		let transparency = vec![0, 128];
+		let extra = [(b"PLTE", palette), (b"tRNS", transparency)];
+		let decode = |bit_depth, line: &[u8]| {
+			decode_lines(4, bit_depth, COLOR_TYPE_INDEXED, &extra, &[line])
+		};
+		let expected = [[255, 0, 0, 0], [0, 255, 0, 128], [0, 0, 255, 255], [9, 9, 9, 255]];
+		assert_eq!(decode(2, &[0b00_01_10_11]), expected);
+		assert_eq!(decode(4, &[0x01, 0x23]), expected);
+		assert_eq!(decode(8, &[0, 1, 2, 3]), expected);
+		assert_eq!(
+			decode(1, &[0b0110_0000]),
+			[[255, 0, 0, 0], [0, 255, 0, 128], [0, 255, 0, 128], [255, 0, 0, 0]]
+		);
+		// Indices past the palette are black
+		assert_eq!(decode(8, &[0, 1, 2, 200])[3], [0, 0, 0, 255]);
+	}
+
+	#[test]
+	fn indexed_without_palette() {
+		let chunks = [
+			(b"IHDR", ihdr(1, 1, 8, COLOR_TYPE_INDEXED, 0)),
+			(b"IDAT", idat(&[&[0]])),
+		];
+		assert!(matches!(
+			Png::load_from_slice(&png_file(&chunks)),
+			Err(Error::NoPLTE)
+		));
+	}
+
+	#[test]
+	fn truecolor_bit_depths() {
+		assert_eq!(
+			decode_lines(2, 8, COLOR_TYPE_RGB, &[], &[&[1, 2, 3, 4, 5, 6]]),
+			[[1, 2, 3, 255], [4, 5, 6, 255]]
+		);
+		assert_eq!(
+			decode_lines(1, 16, COLOR_TYPE_RGB, &[], &[&[1, 2, 3, 4, 5, 6]]),
+			[[1, 3, 5, 255]]
+		);
+		assert_eq!(
+			decode_lines(2, 8, COLOR_TYPE_RGBA, &[], &[&[1, 2, 3, 4, 5, 6, 7, 8]]),
+			[[1, 2, 3, 4], [5, 6, 7, 8]]
+		);
+		assert_eq!(
+			decode_lines(1, 16, COLOR_TYPE_RGBA, &[], &[&[1, 2, 3, 4, 5, 6, 7, 8]]),
+			[[1, 3, 5, 7]]
+		);
+	}
+
+	#[test]
+	fn unsupported_formats() {
+		// Grayscale with alpha, and RGB with 4 bits per sample
+		for (bit_depth, color_type) in [(8, 4), (4, COLOR_TYPE_RGB)] {
+			let chunks = [
+				(b"IHDR", ihdr(1, 1, bit_depth, color_type, 0)),
+				(b"IDAT", idat(&[&[0; 8]])),
+			];
+			assert!(matches!(
+				Png::load_from_slice(&png_file(&chunks)),
+				Err(Error::UnsupportedFormat { .. })
+			));
+		}
+	}
+
+	#[test]
+	fn transparent_color_key() {
+		let key = vec![0x00, 0x80];
+		assert_eq!(
+			decode_lines(2, 8, COLOR_TYPE_GRAYSCALE, &[(b"tRNS", key)], &[&[0x80, 0x81]]),
+			[[0x80, 0x80, 0x80, 0], gray(0x81)]
+		);
+
+		let key = vec![0, 1, 0, 2, 0, 3];
+		assert_eq!(
+			decode_lines(2, 8, COLOR_TYPE_RGB, &[(b"tRNS", key)], &[&[1, 2, 3, 1, 2, 4]]),
+			[[1, 2, 3, 0], [1, 2, 4, 255]]
+		);
+	}
+
+	#[test]
+	fn wide_samples_are_keyed_before_reduction() {
+		let header = IHDR {
+			width: 2,
+			height: 1,
+			bit_depth: 16,
+			color_type: COLOR_TYPE_GRAYSCALE,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		};
+		let colors = ColorInfo {
+			palette: &[],
+			transparency: &[0x12, 0x34],
+		};
+		// Both samples reduce to 0x12, only the first matches the key
+		let rgba = expand_to_rgba(&header, vec![0x12, 0x34, 0x12, 0x35], colors);
+		assert_eq!(rgba, [0x12, 0x12, 0x12, 0, 0x12, 0x12, 0x12, 0xFF]);
+
+		let header = IHDR {
+			color_type: COLOR_TYPE_RGBA,
+			width: 1,
+			..header
+		};
+		let samples = vec![0xAB, 0xFF, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0x00];
+		let rgba = expand_to_rgba(&header, samples, colors);
+		assert_eq!(rgba, [0xAB, 0x01, 0x00, 0xFF]);
+	}
+
+	#[test]
+	fn filters_known_vectors() {
+		let mut line = [1, 1, 1, 1];
+		decode_filter(&mut line, None, FilterType::Sub, 1);
+		assert_eq!(line, [1, 2, 3, 4]);
+
+		let mut line = [1, 2, 3, 4];
+		decode_filter(&mut line, Some(&[10, 20, 30, 40]), FilterType::Up, 2);
+		assert_eq!(line, [11, 22, 33, 44]);
+
+		// Left is 0 for the first pixel
+		let mut line = [5, 5, 5, 5];
+		decode_filter(&mut line, Some(&[10, 20, 30, 40]), FilterType::Average, 2);
+		assert_eq!(line, [10, 15, 25, 32]);
+
+		// The first byte is predicted by the one above, the second by its left neighbour: of 25
+		// (left), 20 (above) and 10 (above left), 25 is closest to 25 + 20 - 10
+		let mut line = [15, 1];
+		decode_filter(&mut line, Some(&[10, 20]), FilterType::Paeth, 1);
+		assert_eq!(line, [25, 26]);
+	}
+
+	#[test]
+	fn filters_round_trip() {
+		for bpp in [1, 2, 3, 4, 6, 8] {
+			let len = bpp * 7;
+			let previous = noise(len, bpp as u32);
+			let original = noise(len, bpp as u32 + 100);
+			for filter_type in FILTER_TYPES {
+				for previous in [None, Some(previous.as_slice())] {
+					let mut line = Vec::new();
+					encode_filter(&mut line, &original, previous, filter_type, bpp);
+					decode_filter(&mut line, previous, filter_type, bpp);
+					let filter = filter_type as u8;
+					assert_eq!(line, original, "filter {filter}, {bpp} bytes per pixel");
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn invalid_filter_type() {
+		let chunks = [
+			(b"IHDR", ihdr(1, 1, 8, COLOR_TYPE_GRAYSCALE, 0)),
+			(b"IDAT", deflate::zlib_compress(&[5, 0])),
+		];
+		assert!(matches!(
+			Png::load_from_slice(&png_file(&chunks)),
+			Err(Error::InvalidFilterType)
+		));
+	}
+
+	/// Splits pixels into Adam7 passes of unfiltered scanlines, keeping the first `channels` bytes
+	/// of each.
+	fn interlace(pixels: &[[u8; 4]], width: usize, height: usize, channels: usize) -> Vec<u8> {
+		let mut scanlines = Vec::new();
+		for (x_start, y_start, x_step, y_step) in ADAM7_PASSES {
+			if x_start >= width || y_start >= height {
+				continue;
+			}
+			for y in (y_start..height).step_by(y_step) {
+				scanlines.push(FilterType::None as u8);
+				for x in (x_start..width).step_by(x_step) {
+					scanlines.extend_from_slice(&pixels[y * width + x][..channels]);
+				}
+			}
+		}
+		scanlines
+	}
+
+	#[test]
+	fn adam7() {
+		// Odd sizes leave some passes empty
+		for (width, height) in [(8, 8), (5, 3), (1, 1), (10, 2)] {
+			let pixels: Vec<[u8; 4]> = noise(width * height * 4, 7)
+				.chunks_exact(4)
+				.map(|pixel| pixel.try_into().unwrap())
+				.collect();
+			let scanlines = interlace(&pixels, width, height, 4);
+			let header = ihdr(width as u32, height as u32, 8, COLOR_TYPE_RGBA, 1);
+			assert_eq!(
+				IHDR {
+					width: width as u32,
+					height: height as u32,
+					bit_depth: 8,
+					color_type: COLOR_TYPE_RGBA,
+					compression_method: 0,
+					filter_method: 0,
+					interlace_method: INTERLACE_ADAM7,
+				}
+				.image_data_len(),
+				scanlines.len()
+			);
+			let chunks = [
+				(b"IHDR", header),
+				(b"IDAT", deflate::zlib_compress(&scanlines)),
+			];
+			let png = Png::load_from_slice(&png_file(&chunks)).unwrap();
+			assert_eq!(rgba_pixels(&png.img_data), pixels, "{width}x{height}");
+		}
+	}
+
+	#[test]
+	fn encode_round_trip() {
+		let (width, height) = (5, 3);
+		let pixels: Vec<u32> = noise(width * height * 4, 3)
+			.chunks_exact(4)
+			.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+			.collect();
+		let png = Png::load_from_slice(&Png::encode(width, height, &pixels)).unwrap();
+		assert_eq!(png.metadata().width, width);
+		assert_eq!(png.metadata().height, height);
+		let texture = crate::draw::Texture::from(png);
+		assert_eq!(texture.pixels(), pixels);
+	}
+
+	#[test]
+	fn streaming_matches_whole_file() {
+		let (width, height) = (4, 6);
+		let pixels: Vec<u32> = noise(width * height * 4, 11)
+			.chunks_exact(4)
+			.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+			.collect();
+		let file = Png::encode(width, height, &pixels);
+
+		let mut decoder = StreamingDecoder::new();
+		let mut rows = Vec::new();
+		for byte in file.chunks(3) {
+			decoder.push(byte).unwrap();
+			rows.extend(std::iter::from_fn(|| decoder.next_row()));
+		}
+		assert!(decoder.is_finished());
+		assert_eq!(decoder.size(), Some((width, height)));
+		assert_eq!(rows.len(), height);
+		for (y, row) in rows.iter().enumerate() {
+			assert_eq!(row.y, y);
+			assert_eq!(row.pixels, pixels[y * width..(y + 1) * width]);
+		}
+	}
+
+	/// Frame control of a frame `width` pixels wide and one pixel high, shown for half a second.
+	fn fctl(
+		sequence_number: u32,
+		width: u32,
+		x_offset: u32,
+		dispose_op: u8,
+		blend_op: u8,
+	) -> Vec<u8> {
+		let mut fctl = Vec::new();
+		for value in [sequence_number, width, 1, x_offset, 0] {
+			fctl.extend_from_slice(&value.to_be_bytes());
+		}
+		fctl.extend_from_slice(&[0, 1, 0, 2, dispose_op, blend_op]);
+		fctl
+	}
+
+	fn fdat(sequence_number: u32, line: &[u8]) -> Vec<u8> {
+		let mut fdat = sequence_number.to_be_bytes().to_vec();
+		fdat.extend(idat(&[line]));
+		fdat
+	}
+
+	#[test]
+	fn apng_dispose_and_blend() {
+		const RED: [u8; 4] = [255, 0, 0, 255];
+		const GREEN: [u8; 4] = [0, 255, 0, 255];
+		const HALF_BLUE: [u8; 4] = [0, 0, 255, 128];
+		const WHITE: [u8; 4] = [255; 4];
+		const CLEAR: [u8; 4] = [0; 4];
+		let mut actl = 4u32.to_be_bytes().to_vec();
+		actl.extend_from_slice(&0u32.to_be_bytes());
+		let chunks = [
+			(b"IHDR", ihdr(2, 1, 8, COLOR_TYPE_RGBA, 0)),
+			(b"acTL", actl),
+			// Restoring before the first frame clears it
+			(b"fcTL", fctl(0, 2, 0, DISPOSE_OP_PREVIOUS, BLEND_OP_SOURCE)),
+			(b"IDAT", idat(&[&[RED, GREEN].concat()])),
+			(b"fcTL", fctl(1, 1, 1, DISPOSE_OP_NONE, BLEND_OP_OVER)),
+			(b"fdAT", fdat(2, &HALF_BLUE)),
+			(b"fcTL", fctl(3, 1, 0, DISPOSE_OP_PREVIOUS, BLEND_OP_SOURCE)),
+			(b"fdAT", fdat(4, &WHITE)),
+			(b"fcTL", fctl(5, 1, 0, DISPOSE_OP_NONE, BLEND_OP_OVER)),
+			(b"fdAT", fdat(6, &CLEAR)),
+		];
+		let png = Png::load_from_slice(&png_file(&chunks)).unwrap();
+		let frames: Vec<_> = png
+			.frames
+			.iter()
+			.map(|frame| rgba_pixels(&frame.img_data))
+			.collect();
+		assert_eq!(
+			frames,
+			[
+				vec![RED, GREEN],
+				vec![CLEAR, HALF_BLUE],
+				vec![WHITE, HALF_BLUE],
+				vec![CLEAR, HALF_BLUE],
+			]
+		);
+		assert_eq!(png.frames[0].delay, Duration::from_millis(500));
+	}
+
+	#[test]
+	fn apng_frame_outside_canvas() {
+		let mut actl = 1u32.to_be_bytes().to_vec();
+		actl.extend_from_slice(&0u32.to_be_bytes());
+		let chunks = [
+			(b"IHDR", ihdr(2, 1, 8, COLOR_TYPE_RGBA, 0)),
+			(b"acTL", actl),
+			(b"fcTL", fctl(0, 2, 1, DISPOSE_OP_NONE, BLEND_OP_SOURCE)),
+			(b"IDAT", idat(&[&[0; 8]])),
+		];
+		assert!(matches!(
+			Png::load_from_slice(&png_file(&chunks)),
+			Err(Error::InvalidFrame)
+		));
+	}
+
+	#[test]
+	fn blend_over_translucent() {
+		let mut dst = [255, 255, 255, 255];
+		blend_over(&mut dst, &[255, 0, 0, 128]);
+		assert_eq!(dst, [255, 127, 127, 255]);
+
+		let mut dst = [0, 0, 0, 0];
+		blend_over(&mut dst, &[10, 20, 30, 40]);
+		assert_eq!(dst, [10, 20, 30, 40]);
+	}
+
+	/// A 16x16 image whose data doesn't compress, so that cutting the file cuts the image.
+	fn noisy_file() -> Vec<u8> {
+		let pixels: Vec<u32> = noise(16 * 16 * 4, 5)
+			.chunks_exact(4)
+			.map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+			.collect();
+		Png::encode(16, 16, &pixels)
+	}
+
+	#[test]
+	fn lenient_truncated() {
+		let file = noisy_file();
+		let truncated = &file[..file.len() / 2];
+		assert!(Png::load_from_slice(truncated).is_err());
+
+		let (png, warnings) = Png::load_with_options(truncated, DecodeOptions::lenient()).unwrap();
+		assert_eq!(png.img_data.len(), 16 * 16 * 4);
+		assert!(matches!(warnings.first(), Some(Warning::Truncated)));
+		assert!(warnings
+			.iter()
+			.any(|warning| matches!(warning, Warning::MissingImageData { .. })));
+		// The rows that arrived are decoded, the rest is transparent black
+		let decoded = Png::load_from_slice(&file).unwrap();
+		assert_eq!(png.img_data[..16 * 4], decoded.img_data[..16 * 4]);
+		assert!(png.img_data[png.img_data.len() - 16 * 4..]
+			.iter()
+			.all(|&byte| byte == 0));
+
+		// Without any image data there's nothing to show
+		let header_only = &file[..PNG_SIGNATURE.len() + 25];
+		assert!(Png::load_with_options(header_only, DecodeOptions::lenient()).is_err());
+	}
+
+	#[test]
+	fn lenient_checksum() {
+		let mut file = Png::encode(1, 1, &[0xFF00FF00]);
+		// Last byte of the IHDR checksum
+		file[PNG_SIGNATURE.len() + 24] ^= 1;
+		assert!(matches!(
+			Png::load_from_slice(&file),
+			Err(Error::ChecksumFailed)
+		));
+
+		let (png, warnings) = Png::load_with_options(&file, DecodeOptions::lenient()).unwrap();
+		assert_eq!(rgba_pixels(&png.img_data), [[0, 255, 0, 255]]);
+		assert!(matches!(
+			&warnings[..],
+			[Warning::ChecksumFailed { chunk_type }] if chunk_type == "IHDR"
+		));
+	}
+
+	#[test]
+	fn unknown_critical_chunk() {
+		let chunks = [
+			(b"IHDR", ihdr(1, 1, 8, COLOR_TYPE_GRAYSCALE, 0)),
+			(b"ABCD", vec![1, 2, 3]),
+			(b"abcd", vec![1, 2, 3]),
+			(b"IDAT", idat(&[&[7]])),
+		];
+		let file = png_file(&chunks);
+		assert!(matches!(
+			Png::load_from_slice(&file),
+			Err(Error::UnknownCriticalChunk(chunk_type)) if chunk_type == "ABCD"
+		));
+		let (png, warnings) = Png::load_with_options(&file, DecodeOptions::lenient()).unwrap();
+		assert_eq!(rgba_pixels(&png.img_data), [gray(7)]);
+		assert_eq!(warnings.len(), 1);
+	}
+
+	#[test]
+	fn cancelled() {
+		let cancel = CancelToken::new();
+		cancel.cancel();
+		let options = DecodeOptions {
+			cancel: Some(cancel),
+			..Default::default()
+		};
+		assert!(matches!(
+			Png::load_with_options(&noisy_file(), options),
+			Err(Error::Cancelled)
+		));
+	}
+
+	#[test]
+	fn target_size() {
+		// 4x4, each 2x2 square a different shade
+		let lines: Vec<Vec<u8>> = (0..4)
+			.map(|y| (0..4).map(|x| (y / 2 * 2 + x / 2) as u8 * 60 + (x + y) as u8 % 2).collect())
+			.collect();
+		let lines: Vec<&[u8]> = lines.iter().map(Vec::as_slice).collect();
+		for interlace_method in [0, INTERLACE_ADAM7] {
+			let scanlines = match interlace_method {
+				0 => idat(&lines),
+				_ => {
+					let pixels: Vec<[u8; 4]> = lines.concat().into_iter().map(gray).collect();
+					deflate::zlib_compress(&interlace(&pixels, 4, 4, 1))
+				}
+			};
+			let chunks = [
+				(b"IHDR", ihdr(4, 4, 8, COLOR_TYPE_GRAYSCALE, interlace_method)),
+				(b"IDAT", scanlines),
+			];
+			let options = DecodeOptions {
+				target_size: Some(2),
+				..Default::default()
+			};
+			let (png, _) = Png::load_with_options(&png_file(&chunks), options).unwrap();
+			assert_eq!((png.metadata().width, png.metadata().height), (4, 4));
+			assert_eq!((png.header.width, png.header.height), (2, 2));
+			assert_eq!(
+				rgba_pixels(&png.img_data),
+				[gray(0), gray(60), gray(120), gray(180)]
+			);
+		}
+	}
+
+	#[test]
+	fn box_scaler_edges() {
+		// 3x3 down by 2, the right column and bottom row are squares cut short
+		let mut scaler = BoxScaler::new(3, 2);
+		for row in [[10, 20, 30], [30, 40, 50], [60, 70, 80]] {
+			let rgba: Vec<u8> = row.iter().flat_map(|&value| [value, 0, 0, 255]).collect();
+			scaler.add_row(&rgba);
+		}
+		let output = scaler.finish();
+		assert_eq!(
+			rgba_pixels(&output),
+			[[0, 0, 25, 255], [0, 0, 40, 255], [0, 0, 65, 255], [0, 0, 80, 255]]
+		);
+	}
+
+	#[test]
+	fn image_data_limit() {
+		// Twice the data the header allows
+		let chunks = [
+			(b"IHDR", ihdr(2, 1, 8, COLOR_TYPE_GRAYSCALE, 0)),
+			(b"IDAT", idat(&[&[1, 2], &[3, 4]])),
+		];
+		assert!(matches!(
+			Png::load_from_slice(&png_file(&chunks)),
+			Err(Error::Deflate(deflate::Error::TooMuchOutput))
+		));
+	}
+}
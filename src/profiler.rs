@@ -0,0 +1,195 @@
+//! Per-frame timings of the stages of the main loop, for attributing slow frames. Code is
+//! instrumented with [scope] guards, nested scopes only count towards the innermost stage. The
+//! timings of recent frames are shown in the debug panel, and while a trace is recording every
+//! scope is kept for [stop_trace] to save in the Chrome trace format (chrome://tracing,
+//! ui.perfetto.dev).
+
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	fmt::Write,
+	io,
+	path::Path,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+/// Number of frames [average] is taken over.
+const HISTORY_LEN: usize = 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stage {
+	/// Processing window messages and polling input devices.
+	Input,
+	Update,
+	Layout,
+	Render,
+	/// Copying the framebuffer to the window.
+	Present,
+	/// File system access, on any thread.
+	Fs,
+}
+
+impl Stage {
+	pub const ALL: [Stage; 6] = [
+		Stage::Input,
+		Stage::Update,
+		Stage::Layout,
+		Stage::Render,
+		Stage::Present,
+		Stage::Fs,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Stage::Input => "input",
+			Stage::Update => "update",
+			Stage::Layout => "layout",
+			Stage::Render => "render",
+			Stage::Present => "present",
+			Stage::Fs => "fs",
+		}
+	}
+}
+
+/// Time spent in each of [Stage::ALL] during a frame.
+type FrameTimes = [Duration; Stage::ALL.len()];
+
+#[derive(Debug)]
+struct Profiler {
+	current: FrameTimes,
+	history: VecDeque<FrameTimes>,
+	/// Scopes recorded since [start_trace], None if no trace is recording.
+	trace: Option<Trace>,
+}
+
+#[derive(Debug)]
+struct Trace {
+	start: Instant,
+	events: Vec<TraceEvent>,
+}
+
+#[derive(Debug)]
+struct TraceEvent {
+	stage: Stage,
+	thread: u32,
+	start: Duration,
+	duration: Duration,
+}
+
+static PROFILER: Mutex<Profiler> = Mutex::new(Profiler {
+	current: [Duration::ZERO; Stage::ALL.len()],
+	history: VecDeque::new(),
+	trace: None,
+});
+
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(1);
+
+thread_local! {
+	static THREAD_ID: u32 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+	/// Time spent in the nested scopes of each open scope of the thread.
+	static OPEN_SCOPES: RefCell<Vec<Duration>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Measures the time until it's dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct Scope {
+	stage: Stage,
+	start: Instant,
+}
+
+/// Starts measuring `stage`, until the returned guard is dropped.
+pub fn scope(stage: Stage) -> Scope {
+	OPEN_SCOPES.with_borrow_mut(|scopes| scopes.push(Duration::ZERO));
+	Scope {
+		stage,
+		start: Instant::now(),
+	}
+}
+
+impl Drop for Scope {
+	fn drop(&mut self) {
+		let duration = self.start.elapsed();
+		let nested = OPEN_SCOPES.with_borrow_mut(|scopes| {
+			let nested = scopes.pop().unwrap_or_default();
+			if let Some(parent) = scopes.last_mut() {
+				*parent += duration;
+			}
+			nested
+		});
+
+		let mut profiler = PROFILER.lock().unwrap();
+		profiler.current[self.stage as usize] += duration.saturating_sub(nested);
+		if let Some(trace) = &mut profiler.trace {
+			trace.events.push(TraceEvent {
+				stage: self.stage,
+				thread: THREAD_ID.with(|id| *id),
+				start: self.start.saturating_duration_since(trace.start),
+				duration,
+			});
+		}
+	}
+}
+
+/// Closes the current frame, called once per iteration of the main loop.
+pub fn end_frame() {
+	let mut profiler = PROFILER.lock().unwrap();
+	let frame = std::mem::take(&mut profiler.current);
+	if profiler.history.len() == HISTORY_LEN {
+		profiler.history.pop_front();
+	}
+	profiler.history.push_back(frame);
+}
+
+/// Average time per frame spent in `stage` over the last frames.
+pub fn average(stage: Stage) -> Duration {
+	let profiler = PROFILER.lock().unwrap();
+	let frames = profiler.history.len().max(1) as u32;
+	profiler
+		.history
+		.iter()
+		.map(|frame| frame[stage as usize])
+		.sum::<Duration>()
+		/ frames
+}
+
+pub fn is_tracing() -> bool {
+	PROFILER.lock().unwrap().trace.is_some()
+}
+
+/// Starts keeping every scope, dropping those of a trace that's already recording.
+pub fn start_trace() {
+	PROFILER.lock().unwrap().trace = Some(Trace {
+		start: Instant::now(),
+		events: Vec::new(),
+	});
+}
+
+/// Stops recording and writes the trace to `path` as Chrome trace JSON. Does nothing if no trace
+/// is recording.
+pub fn stop_trace(path: &Path) -> io::Result<()> {
+	let Some(trace) = PROFILER.lock().unwrap().trace.take() else {
+		return Ok(());
+	};
+	let mut json = String::from("{\"traceEvents\":[\n");
+	for (idx, event) in trace.events.iter().enumerate() {
+		if idx > 0 {
+			json.push_str(",\n");
+		}
+		write!(
+			json,
+			"{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+			event.stage.name(),
+			event.thread,
+			event.start.as_micros(),
+			event.duration.as_micros(),
+		)
+		.unwrap();
+	}
+	json.push_str("\n]}\n");
+	std::fs::write(path, json)
+}
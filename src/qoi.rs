@@ -0,0 +1,344 @@
+//! QOI ("Quite OK Image") encoder and decoder. Far simpler and faster than PNG at a somewhat worse
+//! compression ratio, which makes it a good fit for images we write and read back ourselves.
+
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::draw::Pos;
+
+const MAGIC: &[u8; 4] = b"qoif";
+const HEADER_SIZE: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+// The other operations are told apart by their top 2 bits
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_MASK: u8 = 0xC0;
+
+/// Longest run of a single OP_RUN. 63 and 64 would collide with OP_RGB and OP_RGBA.
+const MAX_RUN: u8 = 62;
+
+/// Images bigger than this many pixels are rejected instead of allocating gigabytes for a
+/// corrupted header.
+const MAX_PIXELS: usize = 1 << 28;
+
+pub struct Qoi {
+	width: usize,
+	height: usize,
+	/// 0xAARRGGBB, top row first.
+	pixels: Vec<u32>,
+	filename: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	InvalidSize,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not a QOI file."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::InvalidSize => write!(f, "Invalid image size."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Position of a color in the table of recently seen colors.
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+	(r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+impl Qoi {
+	pub fn load_from_path(p: &str) -> Result<Self, Error> {
+		let data = std::fs::read(p).map_err(|err| Error::Io {
+			err,
+			filename: p.into(),
+		})?;
+		let mut qoi = Qoi::load_from_slice(&data)?;
+		qoi.filename = Some(p.to_string());
+		Ok(qoi)
+	}
+
+	pub fn load_from_slice(data: &[u8]) -> Result<Self, Error> {
+		if data.get(..4) != Some(MAGIC) {
+			return Err(Error::BadMagic);
+		}
+		let header = data.get(..HEADER_SIZE).ok_or(Error::FileEnd)?;
+		let width = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+		let height = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+		// Channel count and color space are informative only, pixels are always decoded as RGBA
+		debug!(
+			"QOI header: {width}x{height}, {} channels, color space {}",
+			header[12], header[13]
+		);
+		if width == 0 || height == 0 || width.saturating_mul(height) > MAX_PIXELS {
+			return Err(Error::InvalidSize);
+		}
+
+		let pixel_count = width * height;
+		let mut pixels = Vec::with_capacity(pixel_count);
+		let mut index = [[0u8; 4]; 64];
+		let mut pixel = [0, 0, 0, 0xFF];
+		let mut bytes = data[HEADER_SIZE..].iter().copied();
+		let mut next = || bytes.next().ok_or(Error::FileEnd);
+
+		while pixels.len() < pixel_count {
+			let op = next()?;
+			let mut run = 1;
+			match op {
+				OP_RGB => pixel = [next()?, next()?, next()?, pixel[3]],
+				OP_RGBA => pixel = [next()?, next()?, next()?, next()?],
+				_ => match op & OP_MASK {
+					OP_INDEX => pixel = index[op as usize],
+					OP_DIFF => {
+						// 2 bits per channel, biased by 2
+						let diff = |shift: u8| ((op >> shift) & 0x03).wrapping_sub(2);
+						pixel[0] = pixel[0].wrapping_add(diff(4));
+						pixel[1] = pixel[1].wrapping_add(diff(2));
+						pixel[2] = pixel[2].wrapping_add(diff(0));
+					}
+					OP_LUMA => {
+						// Green difference biased by 32, red and blue relative to it biased by 8
+						let green = (op & 0x3F).wrapping_sub(32);
+						let second = next()?;
+						pixel[0] =
+							pixel[0].wrapping_add(green.wrapping_add(second >> 4).wrapping_sub(8));
+						pixel[1] = pixel[1].wrapping_add(green);
+						pixel[2] = pixel[2]
+							.wrapping_add(green.wrapping_add(second & 0x0F).wrapping_sub(8));
+					}
+					_ => run = (op & 0x3F) as usize + 1,
+				},
+			}
+			index[hash(pixel)] = pixel;
+			let [r, g, b, a] = pixel;
+			let value = u32::from_be_bytes([a, r, g, b]);
+			let run = run.min(pixel_count - pixels.len());
+			pixels.extend(std::iter::repeat_n(value, run));
+		}
+
+		Ok(Qoi {
+			width,
+			height,
+			pixels,
+			filename: None,
+		})
+	}
+
+	/// Encodes `pixels` (0xAARRGGBB, like [crate::draw::Texture]) as a QOI file.
+	pub fn encode(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+		assert_eq!(
+			pixels.len(),
+			width * height,
+			"pixel count doesn't match the size"
+		);
+
+		let opaque = pixels.iter().all(|pixel| pixel >> 24 == 0xFF);
+		let mut qoi = Vec::with_capacity(HEADER_SIZE + pixels.len() + END_MARKER.len());
+		qoi.extend_from_slice(MAGIC);
+		qoi.extend_from_slice(&(width as u32).to_be_bytes());
+		qoi.extend_from_slice(&(height as u32).to_be_bytes());
+		// Channels, color space (sRGB with linear alpha)
+		qoi.extend_from_slice(&[if opaque { 3 } else { 4 }, 0]);
+
+		let mut index = [[0u8; 4]; 64];
+		let mut previous = [0, 0, 0, 0xFF];
+		let mut run = 0;
+		for (idx, value) in pixels.iter().enumerate() {
+			let [a, r, g, b] = value.to_be_bytes();
+			let pixel = [r, g, b, a];
+
+			if pixel == previous {
+				run += 1;
+				if run == MAX_RUN || idx + 1 == pixels.len() {
+					qoi.push(OP_RUN | (run - 1));
+					run = 0;
+				}
+				continue;
+			}
+			if run > 0 {
+				qoi.push(OP_RUN | (run - 1));
+				run = 0;
+			}
+
+			let slot = hash(pixel);
+			if index[slot] == pixel {
+				qoi.push(OP_INDEX | slot as u8);
+			} else if a != previous[3] {
+				index[slot] = pixel;
+				qoi.extend_from_slice(&[OP_RGBA, r, g, b, a]);
+			} else {
+				index[slot] = pixel;
+				let [dr, dg, db] = [0, 1, 2].map(|c| pixel[c].wrapping_sub(previous[c]) as i8);
+				let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+				if [dr, dg, db].iter().all(|d| (-2..=1).contains(d)) {
+					qoi.push(
+						OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8,
+					);
+				} else if (-32..=31).contains(&dg)
+					&& (-8..=7).contains(&dr_dg)
+					&& (-8..=7).contains(&db_dg)
+				{
+					qoi.push(OP_LUMA | (dg + 32) as u8);
+					qoi.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+				} else {
+					qoi.extend_from_slice(&[OP_RGB, r, g, b]);
+				}
+			}
+			previous = pixel;
+		}
+
+		qoi.extend_from_slice(&END_MARKER);
+		qoi
+	}
+
+	pub fn save_to_path(
+		path: impl AsRef<Path>,
+		width: usize,
+		height: usize,
+		pixels: &[u32],
+	) -> Result<(), Error> {
+		let path = path.as_ref();
+		std::fs::write(path, Qoi::encode(width, height, pixels)).map_err(|err| Error::Io {
+			err,
+			filename: path.to_path_buf(),
+		})
+	}
+}
+
+impl From<Qoi> for crate::draw::Texture {
+	fn from(img: Qoi) -> Self {
+		let texture = crate::draw::Texture {
+			bitmap: img.pixels,
+			width: img.width,
+			height: img.height,
+			pos: Pos { x: 0, y: 0 },
+		};
+		debug!(
+			"From file \"{filename}\" created texture: {texture:#?}",
+			filename = img.filename.as_deref().unwrap_or("unknown")
+		);
+		texture
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn header(width: u32, height: u32, channels: u8) -> Vec<u8> {
+		let mut header = MAGIC.to_vec();
+		header.extend_from_slice(&width.to_be_bytes());
+		header.extend_from_slice(&height.to_be_bytes());
+		header.extend_from_slice(&[channels, 0]);
+		header
+	}
+
+	#[test]
+	fn every_operation() {
+		let mut file = header(7, 1, 4);
+		file.extend_from_slice(&[
+			OP_RGB, 10, 20, 30,
+			// Red +1, green -1, blue +0
+			OP_DIFF | 3 << 4 | 1 << 2 | 2,
+			// Green +8, red -8+3, blue -8+12 relative to green
+			OP_LUMA | 40, 3 << 4 | 12,
+			OP_RGBA, 1, 2, 3, 4,
+			// Back to the first color
+			OP_INDEX | hash([10, 20, 30, 0xFF]) as u8,
+			// Twice more
+			OP_RUN | 1,
+		]);
+		file.extend_from_slice(&END_MARKER);
+		let qoi = Qoi::load_from_slice(&file).unwrap();
+		assert_eq!(
+			qoi.pixels,
+			[0xFF0A141E, 0xFF0B131E, 0xFF0E1B2A, 0x04010203, 0xFF0A141E, 0xFF0A141E, 0xFF0A141E]
+		);
+	}
+
+	#[test]
+	fn encodes_runs() {
+		// The color before the first pixel is opaque black
+		let file = Qoi::encode(3, 1, &[0xFF000000; 3]);
+		let mut expected = header(3, 1, 3);
+		expected.push(OP_RUN | 2);
+		expected.extend_from_slice(&END_MARKER);
+		assert_eq!(file, expected);
+
+		// Runs longer than MAX_RUN are split
+		let pixels = vec![0xFF000000; 100];
+		let file = Qoi::encode(10, 10, &pixels);
+		assert_eq!(file[HEADER_SIZE..file.len() - END_MARKER.len()], [OP_RUN | 61, OP_RUN | 37]);
+		assert_eq!(Qoi::load_from_slice(&file).unwrap().pixels, pixels);
+	}
+
+	#[test]
+	fn round_trip() {
+		// A mix of small steps, repeats and random colors
+		let mut state: u32 = 0x2545F491;
+		let mut color = 0x80808080u32;
+		let pixels: Vec<u32> = (0..1000)
+			.map(|idx| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				color = match idx % 5 {
+					0 => state,
+					1 => color.wrapping_add(0x00010101),
+					2 => color.wrapping_sub(0x00070305),
+					_ => color,
+				};
+				color
+			})
+			.collect();
+		let qoi = Qoi::load_from_slice(&Qoi::encode(40, 25, &pixels)).unwrap();
+		assert_eq!((qoi.width, qoi.height), (40, 25));
+		assert_eq!(qoi.pixels, pixels);
+	}
+
+	#[test]
+	fn damaged_files() {
+		let file = Qoi::encode(2, 2, &[0x11223344, 0x55667788, 0x99AABBCC, 0xDDEEFF00]);
+		// The end marker is optional, the pixels aren't
+		for len in [0, 3, 10, HEADER_SIZE, HEADER_SIZE + 7] {
+			assert!(Qoi::load_from_slice(&file[..len]).is_err(), "{len} bytes");
+		}
+		assert!(matches!(
+			Qoi::load_from_slice(&file[..HEADER_SIZE + 7]),
+			Err(Error::FileEnd)
+		));
+		assert!(Qoi::load_from_slice(&file[..file.len() - END_MARKER.len()]).is_ok());
+		assert!(matches!(
+			Qoi::load_from_slice(b"qoix and more bytes"),
+			Err(Error::BadMagic)
+		));
+		for (width, height) in [(0, 1), (1, 0), (1 << 16, 1 << 16)] {
+			assert!(matches!(
+				Qoi::load_from_slice(&header(width, height, 4)),
+				Err(Error::InvalidSize)
+			));
+		}
+	}
+}
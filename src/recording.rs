@@ -0,0 +1,203 @@
+//! Recording of input events to a file and playing them back, for reproducing bugs and driving the
+//! UI in tests. Recordings are text, one event per line prefixed with the milliseconds since the
+//! recording started:
+//!
+//! ```text
+//! 1532 key_down 40 336
+//! 1610 key_up 40 336
+//! 2044 mouse_down left 210 96
+//! 2101 char 97
+//! ```
+
+use std::{
+	collections::VecDeque,
+	fmt::Display,
+	fs::File,
+	io::{self, LineWriter, Write},
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::key::{MouseButton, ScanCode};
+use crate::window::{HotkeyId, InputEvent};
+
+const MOUSE_BUTTONS: &[(MouseButton, &str)] = &[
+	(MouseButton::Left, "left"),
+	(MouseButton::Right, "right"),
+	(MouseButton::Middle, "middle"),
+	(MouseButton::Button4, "button4"),
+	(MouseButton::Button5, "button5"),
+];
+
+/// Writes events to a file as they come.
+#[derive(Debug)]
+pub struct Recorder {
+	// Flushed after every line, so that a crash loses nothing
+	file: LineWriter<File>,
+	start: Instant,
+}
+
+/// Recorded events waiting to be played back.
+#[derive(Debug)]
+pub struct Playback {
+	events: VecDeque<(Duration, InputEvent)>,
+	/// When the playback started.
+	start: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io { err: io::Error, filename: PathBuf },
+	InvalidLine { line: usize },
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::InvalidLine { line } => write!(f, "Invalid event on line {line}."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Recorder {
+	pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path.as_ref();
+		let file = File::create(path)?;
+		info!("Recording input to {}", path.display());
+		Ok(Recorder {
+			file: LineWriter::new(file),
+			start: Instant::now(),
+		})
+	}
+
+	pub fn record(&mut self, event: InputEvent) -> io::Result<()> {
+		let millis = self.start.elapsed().as_millis();
+		writeln!(self.file, "{millis} {}", format_event(event))
+	}
+}
+
+impl Playback {
+	pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+		let path = path.as_ref();
+		let text = std::fs::read_to_string(path).map_err(|err| Error::Io {
+			err,
+			filename: path.to_path_buf(),
+		})?;
+		Playback::parse(&text)
+	}
+
+	pub fn parse(text: &str) -> Result<Self, Error> {
+		let mut events = VecDeque::new();
+		for (idx, line) in text.lines().enumerate() {
+			if line.trim().is_empty() {
+				continue;
+			}
+			let event = parse_line(line).ok_or(Error::InvalidLine { line: idx + 1 })?;
+			events.push_back(event);
+		}
+		Ok(Playback {
+			events,
+			start: None,
+		})
+	}
+
+	/// Takes the events that are due at `now`. The playback clock starts with the first call.
+	pub fn due_events(&mut self, now: Instant) -> Vec<InputEvent> {
+		let elapsed = now - *self.start.get_or_insert(now);
+		let mut due = Vec::new();
+		while let Some(&(at, event)) = self.events.front() {
+			if at > elapsed {
+				break;
+			}
+			due.push(event);
+			self.events.pop_front();
+		}
+		due
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.events.is_empty()
+	}
+}
+
+fn format_event(event: InputEvent) -> String {
+	let button_name = |button| {
+		MOUSE_BUTTONS
+			.iter()
+			.find(|&&(known, _)| known == button)
+			.map(|&(_, name)| name)
+			.unwrap()
+	};
+	match event {
+		InputEvent::KeyDown { key, scancode } => format!("key_down {key} {}", scancode.0),
+		InputEvent::KeyUp { key, scancode } => format!("key_up {key} {}", scancode.0),
+		// As a number, so that whitespace survives
+		InputEvent::Char(c) => format!("char {}", c as u32),
+		InputEvent::MouseMove { x, y } => format!("mouse_move {x} {y}"),
+		InputEvent::MouseDown { button, x, y } => {
+			format!("mouse_down {} {x} {y}", button_name(button))
+		}
+		InputEvent::MouseUp { button, x, y } => {
+			format!("mouse_up {} {x} {y}", button_name(button))
+		}
+		InputEvent::Wheel { delta, horizontal } => format!("wheel {delta} {horizontal}"),
+		InputEvent::FocusLost => "focus_lost".to_string(),
+		InputEvent::Hotkey(id) => format!("hotkey {}", id.0),
+	}
+}
+
+fn parse_line(line: &str) -> Option<(Duration, InputEvent)> {
+	let mut parts = line.split_whitespace();
+	let millis: u64 = parts.next()?.parse().ok()?;
+	let kind = parts.next()?;
+	let args: Vec<&str> = parts.collect();
+	let number = |idx: usize| args.get(idx)?.parse::<i32>().ok();
+	let button = |idx: usize| {
+		let name = args.get(idx)?;
+		MOUSE_BUTTONS
+			.iter()
+			.find(|(_, known)| known == name)
+			.map(|&(button, _)| button)
+	};
+
+	let event = match kind {
+		"key_down" | "key_up" => {
+			let key = args.first()?.parse().ok()?;
+			let scancode = ScanCode(args.get(1)?.parse().ok()?);
+			match kind {
+				"key_down" => InputEvent::KeyDown { key, scancode },
+				_ => InputEvent::KeyUp { key, scancode },
+			}
+		}
+		"char" => InputEvent::Char(char::from_u32(args.first()?.parse().ok()?)?),
+		"mouse_move" => InputEvent::MouseMove {
+			x: number(0)?,
+			y: number(1)?,
+		},
+		"mouse_down" => InputEvent::MouseDown {
+			button: button(0)?,
+			x: number(1)?,
+			y: number(2)?,
+		},
+		"mouse_up" => InputEvent::MouseUp {
+			button: button(0)?,
+			x: number(1)?,
+			y: number(2)?,
+		},
+		"wheel" => InputEvent::Wheel {
+			delta: number(0)?,
+			horizontal: args.get(1)?.parse().ok()?,
+		},
+		"focus_lost" => InputEvent::FocusLost,
+		"hotkey" => InputEvent::Hotkey(HotkeyId(number(0)?)),
+		_ => return None,
+	};
+	Some((Duration::from_millis(millis), event))
+}
@@ -0,0 +1,101 @@
+//! Drag-to-select rectangle.
+
+use crate::draw::{draw_rectangle, draw_rectangle_blended, Rect};
+use crate::selection::{Selection, SelectionMode};
+use crate::window::BitmapData;
+
+/// How far the cursor has to move before a press turns into a rubber-band drag.
+const DRAG_THRESHOLD: usize = 4;
+
+const FILL_COLOR: u32 = 0x83a598;
+const BORDER_COLOR: u32 = 0x83a598;
+
+/// In-progress rubber-band drag. Positions are in content coordinates (relative to the top left
+/// corner of the scrolled content, not the window), so the rectangle stays anchored while the view
+/// scrolls during the drag.
+#[derive(Debug)]
+pub struct RubberBand {
+	start: (usize, usize),
+	current: (usize, usize),
+	active: bool,
+	/// Selection at the start of the drag.
+	base: Selection,
+	mode: SelectionMode,
+}
+
+impl RubberBand {
+	pub fn begin(start: (usize, usize), base: Selection, mode: SelectionMode) -> Self {
+		RubberBand {
+			start,
+			current: start,
+			active: false,
+			base,
+			mode,
+		}
+	}
+
+	/// Updates the dragged corner. The drag becomes active once the cursor moves far enough.
+	pub fn drag_to(&mut self, pos: (usize, usize)) {
+		self.current = pos;
+		if self.start.0.abs_diff(pos.0) > DRAG_THRESHOLD
+			|| self.start.1.abs_diff(pos.1) > DRAG_THRESHOLD
+		{
+			self.active = true;
+		}
+	}
+
+	/// Whether the press turned into a drag, as opposed to being a plain click.
+	pub fn is_active(&self) -> bool {
+		self.active
+	}
+
+	/// Where the drag started.
+	pub fn start(&self) -> (usize, usize) {
+		self.start
+	}
+
+	pub fn base(&self) -> &Selection {
+		&self.base
+	}
+
+	pub fn mode(&self) -> SelectionMode {
+		self.mode
+	}
+
+	/// The selection rectangle in content coordinates.
+	pub fn rect(&self) -> Rect {
+		let x = self.start.0.min(self.current.0);
+		let y = self.start.1.min(self.current.1);
+		Rect::new(
+			x,
+			y,
+			self.start.0.abs_diff(self.current.0),
+			self.start.1.abs_diff(self.current.1),
+		)
+	}
+
+	/// Draws the rectangle of a view scrolled by `scroll` pixels and shown in `viewport`.
+	pub fn render(&self, bitmap_data: BitmapData, viewport: Rect, scroll: usize) {
+		if !self.active {
+			return;
+		}
+		let rect = self.rect();
+		// Convert to window coordinates, clipped to the viewport
+		let top = (viewport.y + rect.y).saturating_sub(scroll).max(viewport.y);
+		let bottom = (viewport.y + rect.y + rect.height)
+			.saturating_sub(scroll)
+			.min(viewport.y + viewport.height);
+		let left = viewport.x + rect.x;
+		let right = (left + rect.width).min(viewport.x + viewport.width);
+		if top >= bottom || left >= right {
+			return;
+		}
+
+		let (width, height) = (right - left, bottom - top);
+		draw_rectangle_blended(bitmap_data, (left, top), (width, height), FILL_COLOR, 0.3);
+		draw_rectangle(bitmap_data, (left, top), (width, 1), BORDER_COLOR);
+		draw_rectangle(bitmap_data, (left, bottom - 1), (width, 1), BORDER_COLOR);
+		draw_rectangle(bitmap_data, (left, top), (1, height), BORDER_COLOR);
+		draw_rectangle(bitmap_data, (right - 1, top), (1, height), BORDER_COLOR);
+	}
+}
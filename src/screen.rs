@@ -0,0 +1,557 @@
+//! Stack of screens. The directory browser is always at the bottom, other screens are pushed on
+//! top of it and popped with Escape or their Back button. Only the topmost screen handles input,
+//! the screens below it are still drawn unless an opaque screen covers them.
+//!
+//! Screens besides the browser are built with the immediate-mode [Ui]: [update] declares the
+//! topmost one every frame, and [ScreenStack::render] replays the recorded drawing of every
+//! visible one.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::error;
+
+use crate::app::{self, State, ViewMode};
+use crate::arena::arena_format;
+use crate::config::Theme;
+use crate::draw::{draw_rectangle_blended, DrawList, Rect};
+use crate::event::Event;
+use crate::font;
+use crate::i18n::tr;
+use crate::key::{Input, Key, MouseButton};
+use crate::search::{self, Pattern, Search};
+use crate::search_index::SearchIndexes;
+use crate::shortcut::{Chord, Shortcut};
+use crate::state_dump::utc_timestamp;
+use crate::status_bar;
+use crate::text_input::{TextInput, TextInputEvent};
+use crate::toast::Toast;
+use crate::ui::{Ui, UiMemory};
+use crate::window::{BitmapData, Keyboard, Mouse};
+
+const PROPERTIES_WIDTH: usize = 420;
+const PROPERTIES_HEIGHT: usize = 260;
+const SEARCH_RESULT_ROWS: usize = 16;
+const KEYBINDING_ROWS: usize = 14;
+/// Thumbnail sizes the settings cycle through.
+const THUMBNAIL_SIZES: &[usize] = &[64, 96, 128, 192, 256];
+/// Darkens the screens below a dialog.
+const DIM_COLOR: u32 = 0x1d2021;
+const DIM_ALPHA: f32 = 0.6;
+
+#[derive(Debug)]
+pub enum Screen {
+	/// The current directory with the toolbar, breadcrumb and sidebar around it.
+	DirectoryBrowser,
+	Settings {
+		/// Index into the registered commands, of the one whose shortcuts are being edited.
+		selected_command: Option<usize>,
+		/// Whether the next press becomes a shortcut of the selected command.
+		recording: bool,
+	},
+	/// Dialog with details of a file or folder, above the screen it was opened from.
+	Properties(Properties),
+	/// Entries below `root` whose names match `query`, searched for while it's typed, see
+	/// [crate::search].
+	SearchResults {
+		root: PathBuf,
+		query: TextInput,
+		/// None while the query is empty or not a valid pattern.
+		search: Option<Search>,
+		/// Why the query isn't a valid pattern.
+		error: Option<String>,
+		selected: Option<usize>,
+	},
+}
+
+#[derive(Debug)]
+pub struct Properties {
+	pub path: PathBuf,
+	pub is_dir: bool,
+	pub size: u64,
+	pub modified: Option<SystemTime>,
+	pub readonly: bool,
+}
+
+impl Properties {
+	pub fn read(path: PathBuf) -> io::Result<Properties> {
+		let metadata = std::fs::metadata(&path)?;
+		Ok(Properties {
+			is_dir: metadata.is_dir(),
+			size: metadata.len(),
+			modified: metadata.modified().ok(),
+			readonly: metadata.permissions().readonly(),
+			path,
+		})
+	}
+}
+
+impl Screen {
+	/// Whether the screen covers the whole window, so the screens below don't need to be drawn.
+	pub fn is_opaque(&self) -> bool {
+		!matches!(self, Screen::Properties(_))
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Screen::DirectoryBrowser => "directory_browser",
+			Screen::Settings { .. } => "settings",
+			Screen::Properties(_) => "properties",
+			Screen::SearchResults { .. } => "search_results",
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Entry {
+	screen: Screen,
+	/// Recorded by [update] while the screen is on top, replayed by [ScreenStack::render].
+	draw_list: DrawList,
+}
+
+#[derive(Debug)]
+pub struct ScreenStack {
+	/// Bottom to top, never empty.
+	entries: Vec<Entry>,
+	memory: UiMemory,
+}
+
+impl Default for ScreenStack {
+	fn default() -> Self {
+		ScreenStack {
+			entries: vec![Entry {
+				screen: Screen::DirectoryBrowser,
+				draw_list: DrawList::default(),
+			}],
+			memory: UiMemory::default(),
+		}
+	}
+}
+
+impl ScreenStack {
+	pub fn top(&self) -> &Screen {
+		&self.entries.last().unwrap().screen
+	}
+
+	/// Bottom to top.
+	pub fn iter(&self) -> impl Iterator<Item = &Screen> {
+		self.entries.iter().map(|entry| &entry.screen)
+	}
+
+	/// Opens `screen` above the current one. A screen that's already open is moved to the top
+	/// instead, e.g. opening the settings twice only needs one Escape to get back.
+	pub fn push(&mut self, screen: Screen) {
+		self.entries
+			.retain(|entry| entry.screen.name() != screen.name());
+		self.entries.push(Entry {
+			screen,
+			draw_list: DrawList::default(),
+		});
+	}
+
+	/// Closes the topmost screen, except the directory browser at the bottom. Returns the closed
+	/// screen.
+	pub fn pop(&mut self) -> Option<Screen> {
+		if self.entries.len() == 1 {
+			return None;
+		}
+		self.entries.pop().map(|entry| entry.screen)
+	}
+
+	/// Whether the directory browser is drawn, i.e. no opaque screen covers it.
+	pub fn is_browser_visible(&self) -> bool {
+		self.entries[1..]
+			.iter()
+			.all(|entry| !entry.screen.is_opaque())
+	}
+
+	/// Draws the screens above the browser, from the topmost opaque one up. Dialogs dim what's
+	/// below them.
+	pub fn render(&self, bitmap_data: BitmapData) {
+		let first = self
+			.entries
+			.iter()
+			.rposition(|entry| entry.screen.is_opaque())
+			.unwrap_or(0);
+		for entry in &self.entries[first.max(1)..] {
+			if !entry.screen.is_opaque() {
+				let (width, height) = (bitmap_data.bitmap_width, bitmap_data.bitmap_height);
+				draw_rectangle_blended(
+					bitmap_data,
+					(0, 0),
+					(width as usize, height as usize),
+					DIM_COLOR,
+					DIM_ALPHA,
+				);
+			}
+			entry.draw_list.execute(bitmap_data);
+		}
+	}
+}
+
+/// Opens the search of the entries below the current directory, searching for `query` right away
+/// unless it's empty.
+pub fn open_search(state: &mut State, query: String) {
+	let root = state.history.current().to_path_buf();
+	let (search, error) = start_search(&mut state.search_indexes, &root, &query);
+	state.screens.push(Screen::SearchResults {
+		root,
+		query: TextInput::new(query),
+		search,
+		error,
+		selected: None,
+	});
+}
+
+/// Starts searching `root` for `query`, in its index if there's one. Returns the search, or why
+/// the query isn't a valid pattern.
+fn start_search(
+	indexes: &mut SearchIndexes,
+	root: &Path,
+	query: &str,
+) -> (Option<Search>, Option<String>) {
+	if query.is_empty() {
+		return (None, None);
+	}
+	let pattern = match Pattern::parse(query) {
+		Ok(pattern) => pattern,
+		Err(err) => return (None, Some(err.to_string())),
+	};
+	let search = match indexes.index_of(root) {
+		Some(index) => Search::from_index(root.to_path_buf(), pattern, index),
+		None => {
+			// For the next search, if indexing is enabled
+			indexes.request(root);
+			Search::start(root.to_path_buf(), pattern)
+		}
+	};
+	(Some(search), None)
+}
+
+/// Handles the input of the topmost screen, unless it's the directory browser, which the app
+/// updates itself. `window` is the window's area above the status bar. Returns a directory to
+/// navigate to.
+pub fn update(
+	state: &mut State,
+	mouse: &Mouse,
+	keyboard: &Keyboard,
+	window: Rect,
+) -> Option<PathBuf> {
+	if matches!(state.screens.top(), Screen::DirectoryBrowser) {
+		return None;
+	}
+	let recording = matches!(
+		state.screens.top(),
+		Screen::Settings {
+			recording: true,
+			..
+		}
+	);
+	if recording {
+		record_shortcut(state, mouse, keyboard);
+	} else if keyboard.was_pressed(Key::Escape) {
+		state.screens.pop();
+		return None;
+	}
+
+	let area = match state.screens.top().is_opaque() {
+		true => window,
+		false => centered(window, PROPERTIES_WIDTH, PROPERTIES_HEIGHT),
+	};
+	let stack = &mut state.screens;
+	let entry = stack.entries.last_mut().unwrap();
+	let mut ui = Ui::begin(&mut entry.draw_list, &mut stack.memory, mouse, area);
+	let mut close = false;
+	let mut navigate_to = None;
+	let mut select = None;
+	let mut settings_changed = false;
+
+	match &mut entry.screen {
+		Screen::DirectoryBrowser => unreachable!(),
+		Screen::Settings {
+			selected_command,
+			recording,
+		} => {
+			let config = &mut state.config;
+			ui.column(0, 2);
+			ui.heading("Settings");
+			let theme = arena_format!(state.arena, "Theme: {}##theme", config.theme.name());
+			if ui.button(theme) {
+				let idx = Theme::ALL.iter().position(|&theme| theme == config.theme);
+				let next = idx.map_or(0, |idx| (idx + 1) % Theme::ALL.len());
+				config.theme = Theme::ALL[next];
+				state.events.publish(Event::ThemeChanged(config.theme));
+				settings_changed = true;
+			}
+			let view_mode = match config.view_mode {
+				ViewMode::List => "list",
+				ViewMode::Grid => "grid",
+			};
+			if ui.button(arena_format!(
+				state.arena,
+				"Default view: {view_mode}##view_mode"
+			)) {
+				config.view_mode = match config.view_mode {
+					ViewMode::List => ViewMode::Grid,
+					ViewMode::Grid => ViewMode::List,
+				};
+				settings_changed = true;
+			}
+			let size = config.thumbnail_size;
+			if ui.button(arena_format!(
+				state.arena,
+				"Thumbnail size: {size}##thumbnail_size"
+			)) {
+				// Sizes set in the file that aren't in the list go to the next larger one
+				config.thumbnail_size = THUMBNAIL_SIZES
+					.iter()
+					.copied()
+					.find(|&next| next > size)
+					.unwrap_or(THUMBNAIL_SIZES[0]);
+				settings_changed = true;
+			}
+			settings_changed |= ui.checkbox("Animations", &mut config.animations);
+			settings_changed |= ui.checkbox("Show hidden files", &mut config.show_hidden);
+			settings_changed |= ui.checkbox("Index searched folders", &mut config.search_index);
+			settings_changed |= ui.checkbox("Confirm deleting", &mut config.confirm_delete);
+			settings_changed |= ui.checkbox("Confirm overwriting", &mut config.confirm_overwrite);
+			ui.separator();
+			close = ui.button("Back");
+
+			ui.column(1, 2);
+			let commands = state.commands.commands();
+			let selected = selected_command.and_then(|idx| commands.get(idx));
+			match (selected, *recording) {
+				(Some(command), true) => ui.heading(arena_format!(
+					state.arena,
+					"Press a shortcut for \"{}\", Escape cancels",
+					command.title
+				)),
+				_ => ui.heading("Keyboard shortcuts"),
+			}
+			let rows = state.arena.alloc_iter(commands.iter().map(|command| {
+				let chords: Vec<String> = state
+					.keymap
+					.chords_of(command.id)
+					.map(|chord| chord.label())
+					.collect();
+				match chords.is_empty() {
+					true => arena_format!(state.arena, "{}: none", command.title),
+					false => {
+						arena_format!(state.arena, "{}: {}", command.title, chords.join(" / "))
+					}
+				}
+			}));
+			if ui.list("keybindings", rows, selected_command, KEYBINDING_ROWS) {
+				*recording = false;
+			}
+			if let Some(command) = selected {
+				if ui.button("Add shortcut") {
+					*recording = true;
+				}
+				if ui.button("Remove shortcuts") {
+					config.set_keybindings(command.id, Vec::new());
+					settings_changed = true;
+				}
+				if ui.button("Reset to default") {
+					config.reset_keybindings(command.id);
+					settings_changed = true;
+				}
+			}
+		}
+		Screen::Properties(properties) => {
+			let path = &properties.path;
+			let name = path.file_name().unwrap_or(path.as_os_str());
+			ui.heading("Properties");
+			ui.label(arena_format!(state.arena, "{}", name.to_string_lossy()));
+			ui.separator();
+			let location = path.parent().unwrap_or(path);
+			let location = font::ui().compact_path(location, area.width);
+			ui.label(arena_format!(state.arena, "Location: {location}"));
+			let kind = match properties.is_dir {
+				true => "Folder",
+				false => "File",
+			};
+			ui.label(arena_format!(state.arena, "Type: {kind}"));
+			if !properties.is_dir {
+				let size = status_bar::format_size(properties.size);
+				ui.label(arena_format!(state.arena, "Size: {size}"));
+			}
+			if let Some(modified) = properties.modified {
+				let modified = utc_timestamp(modified);
+				ui.label(arena_format!(state.arena, "Modified: {modified}"));
+			}
+			if properties.readonly {
+				ui.label("Read-only");
+			}
+			close = ui.button("Close");
+		}
+		Screen::SearchResults {
+			root,
+			query,
+			search,
+			error,
+			selected,
+		} => {
+			let mut open = false;
+			match query.update(keyboard) {
+				Some(TextInputEvent::Changed) => {
+					// Dropping the previous search cancels it
+					(*search, *error) = start_search(&mut state.search_indexes, root, query.text());
+					*selected = None;
+				}
+				Some(TextInputEvent::Submit) => open = true,
+				_ => (),
+			}
+			if let Some(search) = search {
+				search.poll();
+			}
+
+			let location = font::ui().compact_path(root, area.width);
+			ui.heading(arena_format!(state.arena, "Search in {location}"));
+			ui.text_field(query, "Part of a name, or a pattern like *.png");
+			let matches = search.as_ref().map_or(&[][..], Search::matches);
+			let status = match (search.as_ref(), error) {
+				(_, Some(error)) => arena_format!(state.arena, "Invalid pattern: {error}"),
+				(None, None) => "",
+				(Some(search), None) if !search.is_finished() => arena_format!(
+					state.arena,
+					"{} results, searching ({} folders so far)",
+					matches.len(),
+					search.searched_count()
+				),
+				(Some(_), None) if matches.len() >= search::MAX_MATCHES => arena_format!(
+					state.arena,
+					"Stopped at the first {} results",
+					matches.len()
+				),
+				(Some(search), None) => match search.indexed() {
+					Some(indexed) => arena_format!(
+						state.arena,
+						"{} results, from the index of {}",
+						matches.len(),
+						&utc_timestamp(indexed)[..10]
+					),
+					None => arena_format!(state.arena, "{} results", matches.len()),
+				},
+			};
+			ui.label(status);
+			let names = state.arena.alloc_iter(matches.iter().map(|found| {
+				let relative = found.path.strip_prefix(&*root).unwrap_or(&found.path);
+				state.arena.alloc_str(&relative.to_string_lossy())
+			}));
+			ui.list("results", names, selected, SEARCH_RESULT_ROWS);
+			open |= ui.button("Open");
+			if state.search_indexes.is_enabled() && ui.button("Rebuild index") {
+				state.search_indexes.rebuild(root);
+			}
+			// Folders are opened, files are selected in their folder
+			if let Some(found) = selected.and_then(|idx| matches.get(idx)).filter(|_| open) {
+				match found.is_dir {
+					true => navigate_to = Some(found.path.clone()),
+					false => {
+						navigate_to = found.path.parent().map(Path::to_path_buf);
+						select = Some(found.path.clone());
+					}
+				}
+				close = true;
+			}
+			close |= ui.button("Back");
+		}
+	}
+	if close {
+		state.screens.pop();
+	}
+	if let Some(path) = select {
+		state.list_view.select_path(path);
+	}
+	if settings_changed {
+		save_settings(state);
+	}
+	navigate_to
+}
+
+/// Binds the first shortcut pressed during the frame to the command selected in the settings,
+/// or cancels the recording on Escape.
+fn record_shortcut(state: &mut State, mouse: &Mouse, keyboard: &Keyboard) {
+	let Some(Entry {
+		screen: Screen::Settings {
+			selected_command,
+			recording,
+		},
+		..
+	}) = state.screens.entries.last_mut()
+	else {
+		return;
+	};
+	// Modifiers only count together with another input, and the left button picks widgets
+	let pressed = keyboard
+		.presses()
+		.chain(mouse.presses())
+		.find(|(input, _)| {
+			!matches!(
+				input,
+				Input::Key(Key::Control | Key::Shift | Key::Menu) | Input::Mouse(MouseButton::Left)
+			)
+		});
+	let Some((input, modifiers)) = pressed else {
+		return;
+	};
+	*recording = false;
+	if input == Input::Key(Key::Escape) {
+		return;
+	}
+	let Some(command) = selected_command.and_then(|idx| state.commands.commands().get(idx)) else {
+		return;
+	};
+	let chord = Chord::from(Shortcut { input, modifiers });
+	let command_id = command.id;
+
+	// The shortcut moves over from commands it was bound to
+	for command in state.commands.commands() {
+		let chords: Vec<Chord> = state.keymap.chords_of(command.id).collect();
+		if command.id != command_id && chords.iter().any(|bound| bound.conflicts_with(&chord)) {
+			let kept = chords
+				.iter()
+				.filter(|bound| !bound.conflicts_with(&chord))
+				.map(Chord::to_string)
+				.collect();
+			state.config.set_keybindings(command.id, kept);
+		}
+	}
+	let mut shortcuts: Vec<String> = state
+		.keymap
+		.chords_of(command_id)
+		.filter(|&bound| bound != chord)
+		.map(|bound| bound.to_string())
+		.collect();
+	shortcuts.push(chord.to_string());
+	state.config.set_keybindings(command_id, shortcuts);
+	save_settings(state);
+}
+
+/// Applies the changed settings and writes them to the configuration file. A fresh session keeps
+/// the user's file as it is.
+fn save_settings(state: &mut State) {
+	app::apply_config(state);
+	if state.args.fresh_session {
+		return;
+	}
+	if let Err(err) = state.config.save() {
+		error!("Failed to save the settings: {err}");
+		state
+			.toasts
+			.push(Toast::error(tr!("settings_save_failed", err.to_string())));
+	}
+}
+
+fn centered(area: Rect, width: usize, height: usize) -> Rect {
+	let (width, height) = (width.min(area.width), height.min(area.height));
+	Rect::new(
+		area.x + (area.width - width) / 2,
+		area.y + (area.height - height) / 2,
+		width,
+		height,
+	)
+}
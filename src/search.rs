@@ -0,0 +1,238 @@
+//! Recursive search of a directory tree in the background. Every directory is listed by its own
+//! job on the [jobs] workers, which queues jobs for the subdirectories it finds, so the tree is
+//! walked by all the workers at once. Matches are sent to the UI as they're found, see
+//! [Search::poll]. Directories with a [search index](crate::search_index) are searched in their
+//! index instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use log::debug;
+
+use crate::cancel::CancelToken;
+use crate::jobs::{self, Priority};
+use crate::search_index::{self, SearchIndex};
+use crate::string::{fold_case, Glob, GlobError};
+
+/// The search stops once it found this many matches.
+pub const MAX_MATCHES: usize = 10_000;
+
+/// What names are searched for.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+	/// Names containing the text, ignoring case.
+	Substring(String),
+	/// Names matching the wildcard pattern, see [Glob]. Patterns with a separator match the path
+	/// relative to the searched directory instead, e.g. `src/**/*.rs`.
+	Glob { glob: Glob, whole_path: bool },
+}
+
+impl Pattern {
+	/// A glob if `query` contains wildcards, a substring otherwise.
+	pub fn parse(query: &str) -> Result<Pattern, GlobError> {
+		if !query.contains(['*', '?', '[']) {
+			return Ok(Pattern::Substring(query.chars().map(fold_case).collect()));
+		}
+		Ok(Pattern::Glob {
+			glob: Glob::new(query)?,
+			whole_path: query.contains(['/', '\\']),
+		})
+	}
+
+	/// Whether the entry at `relative`, its path relative to the searched directory, matches.
+	pub fn matches(&self, relative: &Path, name: &str) -> bool {
+		match self {
+			Pattern::Substring(needle) => {
+				let name: String = name.chars().map(fold_case).collect();
+				name.contains(needle.as_str())
+			}
+			Pattern::Glob { glob, whole_path } => match whole_path {
+				true => glob.matches(&relative.to_string_lossy()),
+				false => glob.matches(name),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Match {
+	pub path: PathBuf,
+	pub is_dir: bool,
+}
+
+/// A search running in the background, cancelled when dropped.
+#[derive(Debug)]
+pub struct Search {
+	root: PathBuf,
+	cancel: CancelToken,
+	receiver: Receiver<Match>,
+	matches: Vec<Match>,
+	/// Directories queued or being listed.
+	pending: Arc<AtomicUsize>,
+	/// Directories listed so far.
+	searched: Arc<AtomicUsize>,
+	/// When the tree of the index searched was walked, None when searching the directories.
+	indexed: Option<SystemTime>,
+}
+
+/// Shared by the jobs of a search.
+struct Walk {
+	root: PathBuf,
+	pattern: Pattern,
+	cancel: CancelToken,
+	pending: Arc<AtomicUsize>,
+	searched: Arc<AtomicUsize>,
+	found: AtomicUsize,
+}
+
+impl Search {
+	/// Starts searching the entries below `root` for names matching `pattern`.
+	pub fn start(root: PathBuf, pattern: Pattern) -> Self {
+		let (sender, receiver) = mpsc::channel();
+		let cancel = CancelToken::new();
+		let pending = Arc::new(AtomicUsize::new(1));
+		let searched = Arc::new(AtomicUsize::new(0));
+		debug!("Searching {} for {pattern:?}", root.display());
+		let walk = Arc::new(Walk {
+			root: root.clone(),
+			pattern,
+			cancel: cancel.clone(),
+			pending: Arc::clone(&pending),
+			searched: Arc::clone(&searched),
+			found: AtomicUsize::new(0),
+		});
+		spawn_dir(walk, root.clone(), sender);
+		Search {
+			root,
+			cancel,
+			receiver,
+			matches: Vec::new(),
+			pending,
+			searched,
+			indexed: None,
+		}
+	}
+
+	/// Searches `index`, which has the entries below `root`, instead of listing the directories.
+	pub fn from_index(root: PathBuf, pattern: Pattern, index: Arc<RwLock<SearchIndex>>) -> Self {
+		let (sender, receiver) = mpsc::channel();
+		let cancel = CancelToken::new();
+		let pending = Arc::new(AtomicUsize::new(1));
+		let indexed = search_index::read(&index).built();
+		debug!("Searching the index of {} for {pattern:?}", root.display());
+		{
+			let (root, cancel, pending) = (root.clone(), cancel.clone(), Arc::clone(&pending));
+			jobs::spawn(Priority::Medium, move || {
+				let matches = search_index::read(&index).search(&root, &pattern, &cancel);
+				for found in matches {
+					let _ = sender.send(found);
+				}
+				pending.fetch_sub(1, Ordering::Release);
+			});
+		}
+		Search {
+			root,
+			cancel,
+			receiver,
+			matches: Vec::new(),
+			pending,
+			searched: Arc::new(AtomicUsize::new(0)),
+			indexed: Some(indexed),
+		}
+	}
+
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	/// Matches received so far by [Search::poll], in the order they were found.
+	pub fn matches(&self) -> &[Match] {
+		&self.matches
+	}
+
+	/// When the tree of the index searched was walked, None when searching the directories.
+	pub fn indexed(&self) -> Option<SystemTime> {
+		self.indexed
+	}
+
+	/// Number of directories searched so far.
+	pub fn searched_count(&self) -> usize {
+		self.searched.load(Ordering::Relaxed)
+	}
+
+	/// Whether every directory was searched, or the search was cancelled.
+	pub fn is_finished(&self) -> bool {
+		self.pending.load(Ordering::Acquire) == 0
+	}
+
+	pub fn cancel(&self) {
+		self.cancel.cancel();
+	}
+
+	/// Takes the matches found since the last call. Returns true if there were any.
+	pub fn poll(&mut self) -> bool {
+		let count = self.matches.len();
+		self.matches.extend(self.receiver.try_iter());
+		self.matches.len() > count
+	}
+}
+
+impl Drop for Search {
+	fn drop(&mut self) {
+		self.cancel.cancel();
+	}
+}
+
+/// Queues the listing of `dir`, which is already counted as pending.
+fn spawn_dir(walk: Arc<Walk>, dir: PathBuf, sender: Sender<Match>) {
+	jobs::spawn(Priority::Low, move || {
+		search_dir(&walk, &dir, &sender);
+		walk.pending.fetch_sub(1, Ordering::Release);
+	});
+}
+
+fn search_dir(walk: &Arc<Walk>, dir: &Path, sender: &Sender<Match>) {
+	if walk.cancel.is_cancelled() || jobs::is_shutting_down() {
+		return;
+	}
+	let entries = match std::fs::read_dir(dir) {
+		Ok(entries) => entries,
+		// E.g. access denied, the rest of the tree is still searched
+		Err(err) => {
+			debug!("Skipping {} in search: {err}", dir.display());
+			return;
+		}
+	};
+	walk.searched.fetch_add(1, Ordering::Relaxed);
+	for entry in entries.flatten() {
+		if walk.cancel.is_cancelled() {
+			return;
+		}
+		// Links aren't followed, they could lead back up the tree
+		let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+		let path = entry.path();
+		let name = entry.file_name();
+		let relative = path.strip_prefix(&walk.root).unwrap_or(&path);
+		if walk.pattern.matches(relative, &name.to_string_lossy()) {
+			// Other jobs may have found the last ones in the meantime
+			let found = walk.found.fetch_add(1, Ordering::Relaxed) + 1;
+			if found > MAX_MATCHES {
+				return;
+			}
+			if found == MAX_MATCHES {
+				walk.cancel.cancel();
+			}
+			let _ = sender.send(Match {
+				path: path.clone(),
+				is_dir,
+			});
+		}
+		if is_dir {
+			walk.pending.fetch_add(1, Ordering::Relaxed);
+			spawn_dir(Arc::clone(walk), path, sender.clone());
+		}
+	}
+}
@@ -0,0 +1,789 @@
+//! Optional index of the entries below a directory, so that searching it again returns right away
+//! instead of walking the tree, see [Search::from_index](crate::search::Search::from_index). An
+//! index is built in the background the first time its directory is searched, kept in a file in
+//! the application data directory, and updated from the change notifications of the tree below
+//! its root, see [SearchIndexes::poll]. Saved indexes older than [MAX_AGE] are built again, as
+//! changes made while the explorer wasn't running are missed.
+//!
+//! The folders of an index are updated one at a time, in the order their changes came in, and
+//! the updated index is saved once no change came for [SAVE_DELAY].
+//!
+//! The file starts with [MAGIC] and [VERSION], followed by the root, the time of the build and the
+//! entries sorted by path. Paths are front coded: each only stores the number of bytes it shares
+//! with the previous one and the rest. Numbers are little endian.
+
+use std::collections::HashSet;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+
+use crate::cancel::CancelToken;
+use crate::jobs::{self, Priority};
+use crate::search::{self, Match, Pattern};
+use crate::string;
+use crate::watcher::{Changes, DirectoryWatcher};
+
+const MAGIC: &[u8; 4] = b"FEIX";
+const VERSION: u8 = 1;
+const EXTENSION: &str = "idx";
+/// Saved indexes built longer ago are built again instead of loaded.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long an updated index waits for more changes before it's saved.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+	/// Path relative to the root of the index.
+	pub path: String,
+	pub is_dir: bool,
+	pub size: u64,
+	/// Seconds since the Unix epoch, 0 if unknown.
+	pub modified: u64,
+}
+
+/// The entries below `root`, sorted by path.
+#[derive(Debug)]
+pub struct SearchIndex {
+	root: PathBuf,
+	built: SystemTime,
+	entries: Vec<IndexEntry>,
+}
+
+impl SearchIndex {
+	/// Walks the tree below `root`. Directories that can't be listed are left out, only `root`
+	/// itself has to be.
+	pub fn build(root: PathBuf, cancel: &CancelToken) -> io::Result<SearchIndex> {
+		let built = SystemTime::now();
+		let mut entries = list(&root, Path::new(""))?;
+		let dirs = entries.iter().filter(|entry| entry.is_dir);
+		let dirs: Vec<PathBuf> = dirs.map(|entry| PathBuf::from(&entry.path)).collect();
+		entries.extend(walk(&root, dirs, cancel)?);
+		entries.sort_by(|a, b| a.path.cmp(&b.path));
+		info!("Indexed {} entries below {}", entries.len(), root.display());
+		Ok(SearchIndex {
+			root,
+			built,
+			entries,
+		})
+	}
+
+	/// Where the index of `root` is kept, if the application data directory is known.
+	pub fn file_path(root: &Path) -> Option<PathBuf> {
+		let app_data = std::env::var_os("APPDATA")?;
+		// The same folder may be written with different case
+		let hash = string::hash_ignore_case(&root.to_string_lossy());
+		Some(
+			PathBuf::from(app_data)
+				.join("file-explorer")
+				.join("index")
+				.join(format!("{hash:016x}.{EXTENSION}")),
+		)
+	}
+
+	/// Reads the index of `root` saved by [SearchIndex::save].
+	pub fn load(root: &Path) -> io::Result<SearchIndex> {
+		let path = SearchIndex::file_path(root)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?;
+		let index = SearchIndex::decode(&std::fs::read(path)?)?;
+		// Another folder with the same hash
+		if index.root != root {
+			return Err(invalid_data("the index is of another folder"));
+		}
+		Ok(index)
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		save(&self.root, &self.encode())
+	}
+
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	/// When the tree was walked, changes since were only picked up from notifications.
+	pub fn built(&self) -> SystemTime {
+		self.built
+	}
+
+	pub fn entries(&self) -> &[IndexEntry] {
+		&self.entries
+	}
+
+	/// Whether the index has the entries below `dir`.
+	pub fn covers(&self, dir: &Path) -> bool {
+		dir.starts_with(&self.root)
+	}
+
+	/// Entries below `dir` whose names match `pattern`, like a [Search](crate::search::Search) of
+	/// `dir` would find them, at most [search::MAX_MATCHES].
+	pub fn search(&self, dir: &Path, pattern: &Pattern, cancel: &CancelToken) -> Vec<Match> {
+		let Ok(dir) = dir.strip_prefix(&self.root) else {
+			return Vec::new();
+		};
+		let mut matches = Vec::new();
+		for entry in &self.entries {
+			let path = Path::new(&entry.path);
+			let Ok(relative) = path.strip_prefix(dir) else {
+				continue;
+			};
+			let Some(name) = path.file_name() else {
+				continue;
+			};
+			if pattern.matches(relative, &name.to_string_lossy()) {
+				matches.push(Match {
+					path: self.root.join(path),
+					is_dir: entry.is_dir,
+				});
+				if matches.len() == search::MAX_MATCHES || cancel.is_cancelled() {
+					break;
+				}
+			}
+		}
+		matches
+	}
+
+	/// Lists `dir` again after a change notification, see [SearchIndex::apply]. Entries added,
+	/// removed or changed in it are updated, and the trees below new folders are walked. Only
+	/// reads the index, so that searches can go on meanwhile.
+	pub fn update_dir(&self, dir: &Path, cancel: &CancelToken) -> io::Result<Option<DirUpdate>> {
+		let Ok(relative) = dir.strip_prefix(&self.root) else {
+			return Ok(None);
+		};
+		let listed = match list(dir, relative) {
+			Ok(listed) => listed,
+			// Removed with everything in it, the notification of its parent removes the folder
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+			Err(err) => return Err(err),
+		};
+		let old_dirs: Vec<PathBuf> = self
+			.entries
+			.iter()
+			.filter(|entry| entry.is_dir && is_child(entry, relative))
+			.map(|entry| PathBuf::from(&entry.path))
+			.collect();
+		let new_dirs: Vec<PathBuf> = listed
+			.iter()
+			.filter(|entry| entry.is_dir)
+			.map(|entry| PathBuf::from(&entry.path))
+			.collect();
+		let added_dirs = new_dirs.iter().filter(|dir| !old_dirs.contains(dir));
+		let below_added = walk(&self.root, added_dirs.cloned().collect(), cancel)?;
+		let removed_dirs = old_dirs
+			.into_iter()
+			.filter(|dir| !new_dirs.contains(dir))
+			.collect();
+		Ok(Some(DirUpdate {
+			dir: dir.to_path_buf(),
+			relative: relative.to_path_buf(),
+			removed_dirs,
+			added: listed.into_iter().chain(below_added).collect(),
+		}))
+	}
+
+	/// Replaces the entries of a folder with those listed by [SearchIndex::update_dir]. Entries
+	/// already in the index from an update of another folder, e.g. of a new folder's parent that
+	/// walked it, are replaced too.
+	pub fn apply(&mut self, update: DirUpdate) {
+		let added: HashSet<&str> = update
+			.added
+			.iter()
+			.map(|entry| entry.path.as_str())
+			.collect();
+		self.entries.retain(|entry| {
+			let path = Path::new(&entry.path);
+			let removed_below = update.removed_dirs.iter().any(|dir| path.starts_with(dir));
+			!is_child(entry, &update.relative)
+				&& !removed_below
+				&& !added.contains(entry.path.as_str())
+		});
+		self.entries.extend(update.added);
+		self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+		debug!(
+			"Updated the index of {} for {}",
+			self.root.display(),
+			update.dir.display()
+		);
+	}
+
+	fn encode(&self) -> Vec<u8> {
+		let mut data = Vec::new();
+		data.extend_from_slice(MAGIC);
+		data.push(VERSION);
+		write_bytes(&mut data, self.root.to_string_lossy().as_bytes());
+		data.extend_from_slice(&seconds(self.built).to_le_bytes());
+		data.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+		let mut previous: &[u8] = &[];
+		for entry in &self.entries {
+			let path = entry.path.as_bytes();
+			let mut shared = path
+				.iter()
+				.zip(previous)
+				.take_while(|(a, b)| a == b)
+				.count()
+				.min(u16::MAX as usize);
+			// Both parts have to stay valid UTF-8
+			while !entry.path.is_char_boundary(shared) {
+				shared -= 1;
+			}
+			data.extend_from_slice(&(shared as u16).to_le_bytes());
+			write_bytes(&mut data, &path[shared..]);
+			data.push(entry.is_dir as u8);
+			data.extend_from_slice(&entry.size.to_le_bytes());
+			data.extend_from_slice(&entry.modified.to_le_bytes());
+			previous = path;
+		}
+		data
+	}
+
+	fn decode(data: &[u8]) -> io::Result<SearchIndex> {
+		let mut reader = Reader { data, pos: 0 };
+		if reader.take(MAGIC.len())? != MAGIC {
+			return Err(invalid_data("not an index file"));
+		}
+		let version = reader.u8()?;
+		if version != VERSION {
+			return Err(invalid_data(&format!("unsupported version {version}")));
+		}
+		let root = PathBuf::from(reader.string()?);
+		let built = UNIX_EPOCH + Duration::from_secs(reader.u64()?);
+		let count = reader.u32()? as usize;
+		// Not trusting the count with the allocation, a corrupt one could be huge
+		let mut entries = Vec::with_capacity(count.min(data.len() / 23));
+		let mut previous = String::new();
+		for _ in 0..count {
+			let shared = reader.u16()? as usize;
+			let suffix = reader.string()?;
+			let Some(prefix) = previous.get(..shared) else {
+				return Err(invalid_data("invalid path prefix"));
+			};
+			let path = format!("{prefix}{suffix}");
+			entries.push(IndexEntry {
+				is_dir: reader.u8()? != 0,
+				size: reader.u64()?,
+				modified: reader.u64()?,
+				path: path.clone(),
+			});
+			previous = path;
+		}
+		Ok(SearchIndex {
+			root,
+			built,
+			entries,
+		})
+	}
+}
+
+/// New entries of a folder of a [SearchIndex], see [SearchIndex::update_dir].
+#[derive(Debug)]
+pub struct DirUpdate {
+	dir: PathBuf,
+	/// `dir` relative to the root of the index.
+	relative: PathBuf,
+	/// Folders no longer there, whose trees are removed.
+	removed_dirs: Vec<PathBuf>,
+	/// The entries of the folder, and the trees below its new folders.
+	added: Vec<IndexEntry>,
+}
+
+/// Whether `entry` is directly in the folder at `relative`.
+fn is_child(entry: &IndexEntry, relative: &Path) -> bool {
+	Path::new(&entry.path).parent() == Some(relative)
+}
+
+/// Writes the [encoded](SearchIndex::encode) index of `root` where [SearchIndex::load] finds it.
+fn save(root: &Path, data: &[u8]) -> io::Result<()> {
+	let path = SearchIndex::file_path(root)
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?;
+	if let Some(dir) = path.parent() {
+		std::fs::create_dir_all(dir)?;
+	}
+	// A crash while writing leaves the previous index as it was
+	let temp_path = path.with_extension("tmp");
+	std::fs::write(&temp_path, data)?;
+	std::fs::rename(temp_path, path)
+}
+
+/// Direct entries of `dir`, whose path relative to the root of the index is `relative`.
+fn list(dir: &Path, relative: &Path) -> io::Result<Vec<IndexEntry>> {
+	let mut entries = Vec::new();
+	for entry in std::fs::read_dir(dir)?.flatten() {
+		let Ok(metadata) = entry.metadata() else {
+			continue;
+		};
+		let path = relative.join(entry.file_name());
+		entries.push(IndexEntry {
+			path: path.to_string_lossy().into_owned(),
+			// Links aren't followed, they could lead back up the tree
+			is_dir: metadata.is_dir(),
+			size: metadata.len(),
+			modified: metadata.modified().map_or(0, seconds),
+		});
+	}
+	Ok(entries)
+}
+
+/// Entries of the trees below `dirs`, relative to `root`, without the `dirs` themselves.
+fn walk(root: &Path, mut dirs: Vec<PathBuf>, cancel: &CancelToken) -> io::Result<Vec<IndexEntry>> {
+	let mut entries = Vec::new();
+	while let Some(dir) = dirs.pop() {
+		if cancel.is_cancelled() || jobs::is_shutting_down() {
+			return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+		}
+		let Ok(listed) = list(&root.join(&dir), &dir) else {
+			continue;
+		};
+		dirs.extend(
+			listed
+				.iter()
+				.filter(|entry| entry.is_dir)
+				.map(|entry| PathBuf::from(&entry.path)),
+		);
+		entries.extend(listed);
+	}
+	Ok(entries)
+}
+
+/// Indexes of the searched directories, while indexing is enabled in the settings.
+#[derive(Debug, Default)]
+pub struct SearchIndexes {
+	enabled: bool,
+	indexes: Vec<Loaded>,
+	/// Indexes being loaded or built.
+	pending: Vec<Pending>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+	/// Shared with the jobs searching and updating it.
+	index: Arc<RwLock<SearchIndex>>,
+	/// Watches the tree below the root, None if that failed.
+	watcher: Option<DirectoryWatcher>,
+	changes: Receiver<Changes>,
+	/// Changed folders waiting for the running job to finish.
+	queued: Vec<PathBuf>,
+	/// Disconnected once the running update or save job finished, None if none is running.
+	running: Option<Receiver<()>>,
+	/// When the index was last updated, None if it was saved since.
+	unsaved_since: Option<Instant>,
+}
+
+impl Loaded {
+	fn new(index: SearchIndex) -> Self {
+		let (sender, changes) = mpsc::channel();
+		let watcher = DirectoryWatcher::start(&index.root, true, move |changes| {
+			sender.send(changes).is_ok()
+		})
+		.map_err(|err| warn!("Failed to watch {}: {err}", index.root.display()))
+		.ok();
+		Loaded {
+			index: Arc::new(RwLock::new(index)),
+			watcher,
+			changes,
+			queued: Vec::new(),
+			running: None,
+			unsaved_since: None,
+		}
+	}
+
+	/// Starts updating the queued folders once the running job finished, or saving the index
+	/// once it wasn't updated for [SAVE_DELAY]. A single job runs at a time, so that updates are
+	/// applied in order and a folder isn't listed by two updates at once.
+	fn run_next(&mut self) {
+		let running = self
+			.running
+			.as_ref()
+			.is_some_and(|done| done.try_recv() == Err(TryRecvError::Empty));
+		if running {
+			return;
+		}
+		self.running = None;
+		if !self.queued.is_empty() {
+			let dirs = mem::take(&mut self.queued);
+			self.running = Some(update_later(Arc::clone(&self.index), dirs));
+			self.unsaved_since = Some(Instant::now());
+		} else if self
+			.unsaved_since
+			.is_some_and(|since| since.elapsed() >= SAVE_DELAY)
+		{
+			self.unsaved_since = None;
+			self.running = Some(save_later(Arc::clone(&self.index)));
+		}
+	}
+}
+
+/// Updates `index` with the entries of `dirs` in the background, one after another, see
+/// [SearchIndex::update_dir]. The index is only locked for writing to apply each update.
+fn update_later(index: Arc<RwLock<SearchIndex>>, dirs: Vec<PathBuf>) -> Receiver<()> {
+	let (done, finished) = mpsc::channel();
+	jobs::spawn(Priority::Low, move || {
+		for dir in dirs {
+			let result = read(&index).update_dir(&dir, &CancelToken::new());
+			match result {
+				Ok(Some(update)) => index
+					.write()
+					.unwrap_or_else(PoisonError::into_inner)
+					.apply(update),
+				Ok(None) => (),
+				Err(err) => warn!("Failed to update the index of {}: {err}", dir.display()),
+			}
+		}
+		drop(done);
+	});
+	finished
+}
+
+/// Saves `index` in the background, encoding it under the read lock and writing it without.
+fn save_later(index: Arc<RwLock<SearchIndex>>) -> Receiver<()> {
+	let (done, finished) = mpsc::channel();
+	jobs::spawn(Priority::Low, move || {
+		let (root, data) = {
+			let index = read(&index);
+			(index.root.clone(), index.encode())
+		};
+		if let Err(err) = save(&root, &data) {
+			warn!("Failed to save the index of {}: {err}", root.display());
+		}
+		drop(done);
+	});
+	finished
+}
+
+#[derive(Debug)]
+struct Pending {
+	root: PathBuf,
+	result: Receiver<io::Result<SearchIndex>>,
+	cancel: CancelToken,
+}
+
+impl SearchIndexes {
+	/// Disabling the indexes drops the loaded ones, their files stay.
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		if !enabled {
+			self.indexes.clear();
+			for pending in self.pending.drain(..) {
+				pending.cancel.cancel();
+			}
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// The index with the entries below `dir`, if one is loaded.
+	pub fn index_of(&self, dir: &Path) -> Option<Arc<RwLock<SearchIndex>>> {
+		self.indexes
+			.iter()
+			.find(|loaded| read(&loaded.index).covers(dir))
+			.map(|loaded| Arc::clone(&loaded.index))
+	}
+
+	/// Loads the saved index of `dir` in the background, or builds it if there's none yet.
+	pub fn request(&mut self, dir: &Path) {
+		let pending = self
+			.pending
+			.iter()
+			.any(|pending| dir.starts_with(&pending.root));
+		if !self.enabled || pending || self.index_of(dir).is_some() {
+			return;
+		}
+		self.spawn(dir.to_path_buf(), true);
+	}
+
+	/// Walks the tree of the index with the entries below `dir` again, or of `dir` if there's no
+	/// such index. The old index is used until the new one is built.
+	pub fn rebuild(&mut self, dir: &Path) {
+		if !self.enabled {
+			return;
+		}
+		let root = match self.index_of(dir) {
+			Some(index) => read(&index).root.clone(),
+			None => dir.to_path_buf(),
+		};
+		self.pending.retain(|pending| {
+			let replaced = pending.root.starts_with(&root);
+			if replaced {
+				pending.cancel.cancel();
+			}
+			!replaced
+		});
+		self.spawn(root, false);
+	}
+
+	/// Takes the indexes loaded or built since the last call, and updates the loaded ones with the
+	/// changes of their trees. Called every frame.
+	pub fn poll(&mut self) {
+		let mut rebuilt = Vec::new();
+		for loaded in &mut self.indexes {
+			for changes in loaded.changes.try_iter() {
+				match changes {
+					Changes::In(dirs) => {
+						for dir in dirs {
+							if !loaded.queued.contains(&dir) {
+								loaded.queued.push(dir);
+							}
+						}
+					}
+					Changes::Unknown => rebuilt.push(read(&loaded.index).root.clone()),
+					Changes::Failed => loaded.watcher = None,
+				}
+			}
+			loaded.run_next();
+		}
+		for root in rebuilt {
+			self.rebuild(&root);
+		}
+
+		let mut finished = Vec::new();
+		self.pending
+			.retain(|pending| match pending.result.try_recv() {
+				Ok(result) => {
+					finished.push((pending.root.clone(), result));
+					false
+				}
+				Err(TryRecvError::Empty) => true,
+				// The job was dropped by the jobs shutting down
+				Err(TryRecvError::Disconnected) => false,
+			});
+		for (root, result) in finished {
+			match result {
+				Ok(index) => {
+					// Replaces the old index of a rebuild, and those of folders below it
+					self.indexes
+						.retain(|loaded| !read(&loaded.index).root.starts_with(&root));
+					self.indexes.push(Loaded::new(index));
+				}
+				Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+				Err(err) => warn!("Failed to index {}: {err}", root.display()),
+			}
+		}
+	}
+
+	fn spawn(&mut self, root: PathBuf, load: bool) {
+		let (sender, result) = mpsc::channel();
+		let cancel = CancelToken::new();
+		let token = cancel.clone();
+		let dir = root.clone();
+		jobs::spawn(Priority::Low, move || {
+			let loaded = match load {
+				true => SearchIndex::load(&dir)
+					.map_err(|err| debug!("No index of {} to load: {err}", dir.display()))
+					.ok()
+					.filter(|index| {
+						let age = index.built.elapsed().unwrap_or_default();
+						let fresh = age < MAX_AGE;
+						if !fresh {
+							debug!("The index of {} is outdated", dir.display());
+						}
+						fresh
+					}),
+				false => None,
+			};
+			let result = match loaded {
+				Some(index) => Ok(index),
+				None => SearchIndex::build(dir, &token).inspect(|index| {
+					if let Err(err) = index.save() {
+						warn!(
+							"Failed to save the index of {}: {err}",
+							index.root.display()
+						);
+					}
+				}),
+			};
+			let _ = sender.send(result);
+		});
+		self.pending.push(Pending {
+			root,
+			result,
+			cancel,
+		});
+	}
+}
+
+/// Reads `index`, which jobs only write to briefly.
+pub fn read(index: &RwLock<SearchIndex>) -> RwLockReadGuard<'_, SearchIndex> {
+	index.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn seconds(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_secs())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("Invalid index: {message}"),
+	)
+}
+
+/// Writes `bytes` with their length before them.
+fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+	data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+	data.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+		let bytes = self
+			.data
+			.get(self.pos..self.pos + len)
+			.ok_or_else(|| invalid_data("unexpected end of file"))?;
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> io::Result<u8> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn u16(&mut self) -> io::Result<u16> {
+		Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> io::Result<u64> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+	}
+
+	/// UTF-8 written by [write_bytes].
+	fn string(&mut self) -> io::Result<&'a str> {
+		let len = self.u32()? as usize;
+		std::str::from_utf8(self.take(len)?).map_err(|_| invalid_data("invalid UTF-8"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(path: &str, is_dir: bool) -> IndexEntry {
+		IndexEntry {
+			path: Path::new(path).to_string_lossy().into_owned(),
+			is_dir,
+			size: path.len() as u64,
+			modified: 1_700_000_000,
+		}
+	}
+
+	/// Relative path of the parts joined by the platform's separator.
+	fn join(parts: &[&str]) -> String {
+		let path: PathBuf = parts.iter().collect();
+		path.to_string_lossy().into_owned()
+	}
+
+	fn index(entries: Vec<IndexEntry>) -> SearchIndex {
+		SearchIndex {
+			root: PathBuf::from("root"),
+			built: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+			entries,
+		}
+	}
+
+	fn paths(index: &SearchIndex) -> Vec<&str> {
+		index.entries.iter().map(|entry| entry.path.as_str()).collect()
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut entries = vec![
+			entry("photos", true),
+			entry(&join(&["photos", "2023"]), true),
+			entry(&join(&["photos", "2023", "beach.png"]), false),
+			entry(&join(&["photos", "2023", "beach.png.bak"]), false),
+			entry(&join(&["photos", "żółw.jpg"]), false),
+			entry("readme.txt", false),
+		];
+		entries.sort_by(|a, b| a.path.cmp(&b.path));
+		let original = index(entries);
+
+		let decoded = SearchIndex::decode(&original.encode()).unwrap();
+		assert_eq!(decoded.root, original.root);
+		assert_eq!(decoded.built, original.built);
+		assert_eq!(decoded.entries, original.entries);
+
+		let empty = index(Vec::new());
+		assert!(SearchIndex::decode(&empty.encode()).unwrap().entries.is_empty());
+	}
+
+	#[test]
+	fn shared_prefix_splits_characters() {
+		// Both share the first byte of 'ż' and 'ź', which can't be split off
+		let original = index(vec![entry("aż", false), entry("aź", false)]);
+		let decoded = SearchIndex::decode(&original.encode()).unwrap();
+		assert_eq!(decoded.entries, original.entries);
+	}
+
+	#[test]
+	fn rejects_invalid_files() {
+		let data = index(vec![entry("a", false), entry("b", false)]).encode();
+
+		let mut bad_magic = data.clone();
+		bad_magic[0] = b'X';
+		assert!(SearchIndex::decode(&bad_magic).is_err());
+
+		let mut bad_version = data.clone();
+		bad_version[MAGIC.len()] = VERSION + 1;
+		let err = SearchIndex::decode(&bad_version).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+		for len in 0..data.len() {
+			assert!(SearchIndex::decode(&data[..len]).is_err(), "{len} bytes");
+		}
+	}
+
+	#[test]
+	fn apply_overlapping_updates() {
+		let mut index = index(vec![entry("docs", true), entry("old.txt", false)]);
+		let new = join(&["docs", "new"]);
+		let nested = join(&["docs", "new", "notes.txt"]);
+
+		// The notification of the new folder's entries is handled first
+		index.apply(DirUpdate {
+			dir: PathBuf::from("root").join(&new),
+			relative: PathBuf::from(&new),
+			removed_dirs: Vec::new(),
+			added: vec![entry(&nested, false)],
+		});
+		// Then the update of "docs" finds the new folder and walks it again
+		index.apply(DirUpdate {
+			dir: PathBuf::from("root").join("docs"),
+			relative: PathBuf::from("docs"),
+			removed_dirs: Vec::new(),
+			added: vec![entry(&new, true), entry(&nested, false)],
+		});
+		assert_eq!(paths(&index), ["docs", new.as_str(), nested.as_str(), "old.txt"]);
+
+		// The root lists "docs" again and the file is gone
+		index.apply(DirUpdate {
+			dir: PathBuf::from("root"),
+			relative: PathBuf::new(),
+			removed_dirs: Vec::new(),
+			added: vec![entry("docs", true)],
+		});
+		assert_eq!(paths(&index), ["docs", new.as_str(), nested.as_str()]);
+
+		// Removing the folder removes its tree
+		index.apply(DirUpdate {
+			dir: PathBuf::from("root").join("docs"),
+			relative: PathBuf::from("docs"),
+			removed_dirs: vec![PathBuf::from(&new)],
+			added: Vec::new(),
+		});
+		assert_eq!(paths(&index), ["docs"]);
+	}
+}
@@ -0,0 +1,133 @@
+//! Multi-item selection shared by the list widgets.
+
+use std::collections::BTreeSet;
+
+use crate::key::Key;
+use crate::window::Keyboard;
+
+/// How a new set of items combines with the existing selection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SelectionMode {
+	/// Only the new items end up selected.
+	Replace,
+	/// New items are added to the selection (Shift).
+	Add,
+	/// New items flip their selection state (Ctrl).
+	Toggle,
+}
+
+impl SelectionMode {
+	/// Mode picked by the currently held modifier keys.
+	pub fn from_modifiers(keyboard: &Keyboard) -> Self {
+		if keyboard.is_pressed(Key::Control) {
+			SelectionMode::Toggle
+		} else if keyboard.is_pressed(Key::Shift) {
+			SelectionMode::Add
+		} else {
+			SelectionMode::Replace
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Selection {
+	items: BTreeSet<usize>,
+	/// Item with the keyboard focus.
+	cursor: Option<usize>,
+	/// Start of Shift range selections.
+	anchor: Option<usize>,
+}
+
+impl Selection {
+	pub fn clear(&mut self) {
+		*self = Selection::default();
+	}
+
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	pub fn is_selected(&self, idx: usize) -> bool {
+		self.items.contains(&idx)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+		self.items.iter().copied()
+	}
+
+	pub fn cursor(&self) -> Option<usize> {
+		self.cursor
+	}
+
+	/// Selects just `idx` (plain click or arrow key).
+	pub fn select_only(&mut self, idx: usize) {
+		self.items.clear();
+		self.items.insert(idx);
+		self.cursor = Some(idx);
+		self.anchor = Some(idx);
+	}
+
+	/// Flips selection of `idx` (Ctrl+click).
+	pub fn toggle(&mut self, idx: usize) {
+		if !self.items.remove(&idx) {
+			self.items.insert(idx);
+		}
+		self.cursor = Some(idx);
+		self.anchor = Some(idx);
+	}
+
+	/// Selects everything between the anchor and `idx` (Shift+click or Shift+arrow key).
+	pub fn select_range_to(&mut self, idx: usize) {
+		let anchor = self.anchor.unwrap_or(idx);
+		self.items = (anchor.min(idx)..=anchor.max(idx)).collect();
+		self.cursor = Some(idx);
+		self.anchor = Some(anchor);
+	}
+
+	/// Applies a click on `idx` according to the held modifiers.
+	pub fn click(&mut self, idx: usize, mode: SelectionMode) {
+		match mode {
+			SelectionMode::Replace => self.select_only(idx),
+			SelectionMode::Add => self.select_range_to(idx),
+			SelectionMode::Toggle => self.toggle(idx),
+		}
+	}
+
+	pub fn select_all(&mut self, item_count: usize) {
+		self.items = (0..item_count).collect();
+	}
+
+	/// Moves the selection along with the items after they were reordered or some went away.
+	/// `new_index` gives the new position of an item, None if it's gone.
+	pub fn remap(&mut self, new_index: impl Fn(usize) -> Option<usize>) {
+		self.items = self
+			.items
+			.iter()
+			.filter_map(|&idx| new_index(idx))
+			.collect();
+		self.cursor = self.cursor.and_then(&new_index);
+		self.anchor = self.anchor.and_then(&new_index);
+	}
+
+	/// Replaces the selection with `base` combined with `items` using `mode`. Used by rubber-band
+	/// selection, which recomputes the result from the selection at drag start on every move.
+	pub fn combine(
+		&mut self,
+		base: &Selection,
+		items: impl Iterator<Item = usize>,
+		mode: SelectionMode,
+	) {
+		let items: BTreeSet<usize> = items.collect();
+		self.items = match mode {
+			SelectionMode::Replace => items,
+			SelectionMode::Add => base.items.union(&items).copied().collect(),
+			SelectionMode::Toggle => base.items.symmetric_difference(&items).copied().collect(),
+		};
+		self.anchor = base.anchor;
+		self.cursor = base.cursor;
+	}
+}
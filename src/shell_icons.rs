@@ -0,0 +1,213 @@
+//! Icons of files and folders as the Windows shell shows them, queried with `SHGetFileInfoW` and
+//! converted into textures. Files share the icon of their extension, except for the kinds that
+//! carry their own (programs, shortcuts and icon files), which are looked up by path.
+//!
+//! Lookups that fail are cached too, callers fall back to the built-in [crate::icons].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use log::debug;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Gdi::{
+	DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFO, BITMAPINFOHEADER,
+	BI_RGB, DIB_RGB_COLORS, HBITMAP,
+};
+use windows::Win32::Storage::FileSystem::{
+	FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL, FILE_FLAGS_AND_ATTRIBUTES,
+};
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+use windows::Win32::UI::Shell::{
+	SHGetFileInfoW, SHFILEINFOW, SHGFI_FLAGS, SHGFI_ICON, SHGFI_LARGEICON, SHGFI_SMALLICON,
+	SHGFI_USEFILEATTRIBUTES,
+};
+use windows::Win32::UI::WindowsAndMessaging::{DestroyIcon, GetIconInfo, HICON, ICONINFO};
+
+use crate::draw::Texture;
+use crate::fs::Entry;
+use crate::string::WideString;
+
+/// Extensions of files with an icon of their own.
+const PER_FILE_EXTENSIONS: &[&str] = &["exe", "lnk", "ico", "cur"];
+
+#[derive(Debug)]
+pub struct ShellIcons {
+	/// Icons are scaled down to fit this size.
+	size: usize,
+	folder: RefCell<Option<Option<Rc<Texture>>>>,
+	/// Lowercase extension to icon.
+	by_extension: RefCell<HashMap<String, Option<Rc<Texture>>>>,
+	by_path: RefCell<HashMap<PathBuf, Option<Rc<Texture>>>>,
+}
+
+impl ShellIcons {
+	/// Icons at most `size` pixels wide and high. Sizes above 16 get the shell's large icons.
+	pub fn new(size: usize) -> Self {
+		// The shell may use COM objects to extract icons
+		let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+		ShellIcons {
+			size,
+			folder: RefCell::default(),
+			by_extension: RefCell::default(),
+			by_path: RefCell::default(),
+		}
+	}
+
+	/// Icon the shell shows for `entry`, None if it has none or the lookup failed.
+	pub fn icon(&self, entry: &Entry) -> Option<Rc<Texture>> {
+		if entry.is_dir() {
+			let mut folder = self.folder.borrow_mut();
+			return folder
+				.get_or_insert_with(|| {
+					self.query(Path::new("folder"), FILE_ATTRIBUTE_DIRECTORY, true)
+				})
+				.clone();
+		}
+
+		let extension = entry
+			.path
+			.extension()
+			.map(|extension| extension.to_string_lossy().to_lowercase())
+			.unwrap_or_default();
+		if PER_FILE_EXTENSIONS.contains(&extension.as_str()) {
+			return self
+				.by_path
+				.borrow_mut()
+				.entry(entry.path.clone())
+				.or_insert_with(|| self.query(&entry.path, FILE_ATTRIBUTE_NORMAL, false))
+				.clone();
+		}
+		self.by_extension
+			.borrow_mut()
+			.entry(extension)
+			.or_insert_with_key(|extension| {
+				let name = PathBuf::from(format!("file.{extension}"));
+				self.query(&name, FILE_ATTRIBUTE_NORMAL, true)
+			})
+			.clone()
+	}
+
+	/// With `by_attributes`, the file at `path` isn't accessed, only its name and `attributes`
+	/// decide the icon.
+	fn query(
+		&self,
+		path: &Path,
+		attributes: FILE_FLAGS_AND_ATTRIBUTES,
+		by_attributes: bool,
+	) -> Option<Rc<Texture>> {
+		let mut flags = SHGFI_ICON
+			| match self.size <= 16 {
+				true => SHGFI_SMALLICON,
+				false => SHGFI_LARGEICON,
+			};
+		if by_attributes {
+			flags |= SHGFI_USEFILEATTRIBUTES;
+		}
+		let icon = file_info_icon(path, attributes, flags)?;
+		let texture = unsafe {
+			let texture = icon_texture(icon);
+			DestroyIcon(icon);
+			texture
+		};
+		if texture.is_none() {
+			debug!("No shell icon for {}", path.display());
+		}
+		texture.map(|texture| Rc::new(texture.scaled_to_fit(self.size, self.size)))
+	}
+}
+
+fn file_info_icon(
+	path: &Path,
+	attributes: FILE_FLAGS_AND_ATTRIBUTES,
+	flags: SHGFI_FLAGS,
+) -> Option<HICON> {
+	let path = WideString::from_os_str(path);
+	let mut info = SHFILEINFOW::default();
+	let result = unsafe {
+		SHGetFileInfoW(
+			path.as_pcwstr(),
+			attributes,
+			Some(&mut info),
+			mem::size_of::<SHFILEINFOW>() as u32,
+			flags,
+		)
+	};
+	(result != 0 && !info.hIcon.is_invalid()).then_some(info.hIcon)
+}
+
+/// Copies the image of `icon` into a texture. Icons without an alpha channel get it from their
+/// mask.
+unsafe fn icon_texture(icon: HICON) -> Option<Texture> {
+	let mut info = ICONINFO::default();
+	if !GetIconInfo(icon, &mut info).as_bool() {
+		return None;
+	}
+	// The bitmaps are copies owned by the caller. Monochrome icons have no color bitmap and
+	// aren't supported.
+	let color = bitmap_pixels(info.hbmColor);
+	let mask = bitmap_pixels(info.hbmMask);
+	DeleteObject(info.hbmColor);
+	DeleteObject(info.hbmMask);
+
+	let (width, height, mut pixels) = color?;
+	if pixels.iter().all(|pixel| pixel >> 24 == 0) {
+		let (_, _, mask) = mask?;
+		for (pixel, mask) in pixels.iter_mut().zip(mask) {
+			// Black mask pixels are the opaque ones
+			if mask & 0xFFFFFF == 0 {
+				*pixel |= 0xFF000000;
+			}
+		}
+	}
+	let mut texture = Texture::new(width, height);
+	texture.bitmap = pixels;
+	Some(texture)
+}
+
+/// Pixels of `bitmap` as 0xAARRGGBB, top row first.
+unsafe fn bitmap_pixels(bitmap: HBITMAP) -> Option<(usize, usize, Vec<u32>)> {
+	if bitmap.is_invalid() {
+		return None;
+	}
+	let mut header = BITMAP::default();
+	let size = mem::size_of::<BITMAP>() as i32;
+	if GetObjectW(
+		bitmap,
+		size,
+		Some((&mut header as *mut BITMAP).cast::<c_void>()),
+	) == 0
+	{
+		return None;
+	}
+	let (width, height) = (header.bmWidth, header.bmHeight.abs());
+	let mut info = BITMAPINFO {
+		bmiHeader: BITMAPINFOHEADER {
+			biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+			biWidth: width,
+			// Negative for top-down rows
+			biHeight: -height,
+			biPlanes: 1,
+			biBitCount: 32,
+			biCompression: BI_RGB.0 as u32,
+			..Default::default()
+		},
+		..Default::default()
+	};
+	let mut pixels = vec![0u32; width as usize * height as usize];
+	let device_context = GetDC(HWND::default());
+	let lines = GetDIBits(
+		device_context,
+		bitmap,
+		0,
+		height as u32,
+		Some(pixels.as_mut_ptr().cast::<c_void>()),
+		&mut info,
+		DIB_RGB_COLORS,
+	);
+	ReleaseDC(HWND::default(), device_context);
+	(lines == height).then_some((width as usize, height as usize, pixels))
+}
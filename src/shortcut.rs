@@ -0,0 +1,310 @@
+//! Shortcuts: a key, mouse button or wheel notch together with the modifiers held while pressing
+//! it, so that Ctrl+C and plain C are told apart. [Shortcuts] dispatches the presses of a frame to
+//! whatever is bound to them, including [Chord]s of two shortcuts pressed one after the other.
+
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::key::{Input, Key, MouseButton, WheelDirection};
+use crate::window::{Keyboard, Mouse};
+
+/// Modifier keys held down. Left and right variants are not told apart.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+	pub ctrl: bool,
+	pub shift: bool,
+	pub alt: bool,
+}
+
+/// How long the second shortcut of a chord is waited for.
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Shortcut {
+	pub input: Input,
+	pub modifiers: Modifiers,
+}
+
+impl Shortcut {
+	/// `key` pressed without any modifiers.
+	pub const fn new(key: Key) -> Self {
+		Shortcut::input(Input::Key(key))
+	}
+
+	pub const fn mouse(button: MouseButton) -> Self {
+		Shortcut::input(Input::Mouse(button))
+	}
+
+	pub const fn wheel(direction: WheelDirection) -> Self {
+		Shortcut::input(Input::Wheel(direction))
+	}
+
+	/// `input` pressed without any modifiers.
+	pub const fn input(input: Input) -> Self {
+		Shortcut {
+			input,
+			modifiers: Modifiers {
+				ctrl: false,
+				shift: false,
+				alt: false,
+			},
+		}
+	}
+
+	pub const fn ctrl(mut self) -> Self {
+		self.modifiers.ctrl = true;
+		self
+	}
+
+	pub const fn shift(mut self) -> Self {
+		self.modifiers.shift = true;
+		self
+	}
+
+	pub const fn alt(mut self) -> Self {
+		self.modifiers.alt = true;
+		self
+	}
+
+	/// A chord of this shortcut followed by `second`.
+	pub const fn then(self, second: Shortcut) -> Chord {
+		Chord {
+			first: self,
+			second: Some(second),
+		}
+	}
+
+	/// Parses shortcuts written like "Ctrl+Shift+P": any modifiers followed by an input name as
+	/// understood by [Input::from_name].
+	pub fn parse(text: &str) -> Option<Shortcut> {
+		let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+		let input = Input::from_name(parts.pop()?)?;
+		let mut shortcut = Shortcut::input(input);
+		for modifier in parts {
+			let modifier = modifier.to_ascii_lowercase();
+			shortcut = match modifier.as_str() {
+				"ctrl" | "control" => shortcut.ctrl(),
+				"shift" => shortcut.shift(),
+				"alt" => shortcut.alt(),
+				_ => return None,
+			};
+		}
+		Some(shortcut)
+	}
+
+	/// The shortcut as shown to the user, e.g. "Strg+Z" for [Key::Y] with Ctrl on a German
+	/// keyboard. Unlike [Display], which spells out the key names understood by [Shortcut::parse],
+	/// follows the active keyboard layout, see [Key::label].
+	pub fn label(&self) -> String {
+		let Modifiers { ctrl, shift, alt } = self.modifiers;
+		let mut label = String::new();
+		for (held, key) in [(ctrl, Key::Control), (shift, Key::Shift), (alt, Key::Menu)] {
+			if held {
+				label.push_str(&key.label());
+				label.push('+');
+			}
+		}
+		label.push_str(&self.input.label());
+		label
+	}
+
+	/// Whether a press of `input` with `modifiers` held triggers the shortcut. The modifiers have
+	/// to match exactly: Ctrl+Shift+C doesn't trigger Ctrl+C.
+	pub fn matches(&self, input: Input, modifiers: Modifiers) -> bool {
+		self.input == input && self.modifiers == modifiers
+	}
+}
+
+impl Display for Shortcut {
+	/// E.g. "Ctrl+Shift+P".
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let Modifiers { ctrl, shift, alt } = self.modifiers;
+		for (held, name) in [(ctrl, "Ctrl"), (shift, "Shift"), (alt, "Alt")] {
+			if held {
+				write!(f, "{name}+")?;
+			}
+		}
+		write!(f, "{}", self.input)
+	}
+}
+
+/// A shortcut, optionally followed by a second one pressed after it, like "G, H".
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+	pub first: Shortcut,
+	pub second: Option<Shortcut>,
+}
+
+impl From<Shortcut> for Chord {
+	fn from(shortcut: Shortcut) -> Self {
+		Chord {
+			first: shortcut,
+			second: None,
+		}
+	}
+}
+
+impl Chord {
+	/// Parses a shortcut as understood by [Shortcut::parse], or two of them separated by a comma.
+	pub fn parse(text: &str) -> Option<Chord> {
+		match text.split_once(',') {
+			Some((first, second)) => Some(Shortcut::parse(first)?.then(Shortcut::parse(second)?)),
+			None => Shortcut::parse(text).map(Chord::from),
+		}
+	}
+
+	/// The chord as shown to the user, see [Shortcut::label].
+	pub fn label(&self) -> String {
+		match self.second {
+			Some(second) => format!("{}, {}", self.first.label(), second.label()),
+			None => self.first.label(),
+		}
+	}
+
+	/// Whether both chords can't be bound at once: they're the same, or one of them is the first
+	/// shortcut of the other, which waits for a second one instead of triggering the shorter one.
+	pub fn conflicts_with(&self, other: &Chord) -> bool {
+		self.first == other.first
+			&& (self.second.is_none() || other.second.is_none() || self.second == other.second)
+	}
+}
+
+impl Display for Chord {
+	/// E.g. "G, H".
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.first)?;
+		if let Some(second) = self.second {
+			write!(f, ", {second}")?;
+		}
+		Ok(())
+	}
+}
+
+/// Shortcuts and chords bound to actions, e.g. command ids.
+#[derive(Debug)]
+pub struct Shortcuts<T> {
+	bindings: Vec<(Chord, T)>,
+	/// First shortcut of a chord that was pressed, waiting for the second one since the instant.
+	pending: Option<(Shortcut, Instant)>,
+}
+
+impl<T> Default for Shortcuts<T> {
+	fn default() -> Self {
+		Shortcuts {
+			bindings: Vec::new(),
+			pending: None,
+		}
+	}
+}
+
+impl<T> FromIterator<(Chord, T)> for Shortcuts<T> {
+	fn from_iter<I: IntoIterator<Item = (Chord, T)>>(iter: I) -> Self {
+		Shortcuts {
+			bindings: iter.into_iter().collect(),
+			pending: None,
+		}
+	}
+}
+
+impl<T: Copy> Shortcuts<T> {
+	/// Binds `chord` to `action`. A chord can be bound to several actions, all of them are
+	/// dispatched.
+	pub fn register(&mut self, chord: impl Into<Chord>, action: T) {
+		self.bindings.push((chord.into(), action));
+	}
+
+	/// Actions bound to the inputs pressed during the last batch of processed messages, key presses
+	/// first. Repeats of a held key dispatch its actions again.
+	///
+	/// A press of the first shortcut of a chord dispatches nothing, even if the shortcut is also
+	/// bound alone. The press that follows completes the chord, or cancels it if no chord goes on
+	/// that way. Modifier keys don't count as presses while a chord is pending, so that the second
+	/// shortcut can have modifiers.
+	///
+	/// Numpad keys that aren't bound anywhere act as their main keyboard twins, see
+	/// [Key::main_key].
+	pub fn dispatch(&mut self, keyboard: &Keyboard, mouse: &Mouse) -> Vec<T> {
+		let now = Instant::now();
+		self.expire_pending(now);
+
+		let mut actions = Vec::new();
+		for (input, modifiers) in keyboard.presses().chain(mouse.presses()) {
+			let input = self.bound_input(input);
+			if let Some((first, _)) = self.pending {
+				if matches!(input, Input::Key(key) if is_modifier(key)) {
+					continue;
+				}
+				self.pending = None;
+				actions.extend(self.bindings.iter().filter_map(|&(chord, action)| {
+					let second = chord.second?;
+					(chord.first == first && second.matches(input, modifiers)).then_some(action)
+				}));
+				continue;
+			}
+
+			let starts_chord = self
+				.bindings
+				.iter()
+				.any(|(chord, _)| chord.second.is_some() && chord.first.matches(input, modifiers));
+			if starts_chord {
+				self.pending = Some((Shortcut { input, modifiers }, now));
+				continue;
+			}
+			actions.extend(self.bindings.iter().filter_map(|&(chord, action)| {
+				(chord.second.is_none() && chord.first.matches(input, modifiers)).then_some(action)
+			}));
+		}
+		actions
+	}
+
+	/// Whether [Shortcuts::dispatch] takes the presses of `keyboard` as part of a chord: one is
+	/// pending, or a press is the first shortcut of one.
+	pub fn is_chording(&self, keyboard: &Keyboard) -> bool {
+		let pending = self
+			.pending
+			.is_some_and(|(_, pressed_at)| pressed_at.elapsed() <= CHORD_TIMEOUT);
+		pending
+			|| keyboard.presses().any(|(input, modifiers)| {
+				let input = self.bound_input(input);
+				self.bindings.iter().any(|(chord, _)| {
+					chord.second.is_some() && chord.first.matches(input, modifiers)
+				})
+			})
+	}
+
+	/// First shortcut of a chord waiting for its second one, to show that the next press is part
+	/// of the chord.
+	pub fn pending(&self) -> Option<Shortcut> {
+		self.pending.map(|(first, _)| first)
+	}
+
+	/// `input`, or the main key of an unbound numpad key.
+	fn bound_input(&self, input: Input) -> Input {
+		let Input::Key(key) = input else {
+			return input;
+		};
+		let is_bound = self.bindings.iter().any(|(chord, _)| {
+			chord.first.input == input || chord.second.is_some_and(|second| second.input == input)
+		});
+		match is_bound {
+			true => input,
+			false => Input::Key(key.main_key()),
+		}
+	}
+
+	fn expire_pending(&mut self, now: Instant) {
+		if self
+			.pending
+			.is_some_and(|(_, pressed_at)| now - pressed_at > CHORD_TIMEOUT)
+		{
+			self.pending = None;
+		}
+	}
+}
+
+fn is_modifier(key: Key) -> bool {
+	matches!(
+		key,
+		Key::Shift | Key::Control | Key::Menu | Key::Lwin | Key::Rwin
+	)
+}
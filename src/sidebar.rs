@@ -0,0 +1,157 @@
+//! Sidebar along the left edge of the window with places to jump to: the user's folders and the
+//! drives. Drives plugged in or removed while the explorer runs are noticed by the
+//! [update thread](crate::update_thread).
+
+use std::path::{Path, PathBuf};
+
+use crate::command;
+use crate::draw::{draw_rectangle, draw_text, draw_texture, Rect, Texture};
+use crate::font;
+use crate::icons;
+use crate::window::{BitmapData, Mouse};
+
+pub const WIDTH: usize = 180;
+const ICON_SIZE: usize = 16;
+const ROW_PADDING: usize = 4;
+const ROW_HEIGHT: usize = font::LINE_HEIGHT + 2 * ROW_PADDING;
+const PADDING: usize = 8;
+
+/// Folders of the user's profile shown below it, if they exist.
+const USER_FOLDERS: [&str; 4] = ["Desktop", "Documents", "Downloads", "Pictures"];
+
+const BACKGROUND_COLOR: u32 = 0x32302f;
+const HOVER_COLOR: u32 = 0x504945;
+const CURRENT_COLOR: u32 = 0x665c54;
+const TEXT_COLOR: u32 = 0xebdbb2;
+
+#[derive(Debug)]
+struct Place {
+	label: String,
+	path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Sidebar {
+	/// The user's folders.
+	places: Vec<Place>,
+	drives: Vec<Place>,
+	hovered: Option<usize>,
+	folder_icon: Texture,
+}
+
+impl Default for Sidebar {
+	/// Looks up the user's folders once, folders created later show up on the next start.
+	fn default() -> Self {
+		let mut places = Vec::new();
+		if let Some(home) = command::home_dir() {
+			places.push(Place {
+				label: "Home".to_string(),
+				path: home.clone(),
+			});
+			for folder in USER_FOLDERS {
+				let path = home.join(folder);
+				if path.is_dir() {
+					places.push(Place {
+						label: folder.to_string(),
+						path,
+					});
+				}
+			}
+		}
+		let mut sidebar = Sidebar {
+			places,
+			drives: Vec::new(),
+			hovered: None,
+			folder_icon: icons::FOLDER.rasterize(ICON_SIZE),
+		};
+		sidebar.set_drives(drives());
+		sidebar
+	}
+}
+
+impl Sidebar {
+	/// Replaces the drives, given by their root folders.
+	pub fn set_drives(&mut self, drives: Vec<PathBuf>) {
+		self.drives = drives
+			.into_iter()
+			.map(|path| Place {
+				label: path.to_string_lossy().trim_end_matches('\\').to_string(),
+				path,
+			})
+			.collect();
+	}
+
+	/// The user's folders followed by the drives.
+	fn places(&self) -> impl Iterator<Item = &Place> {
+		self.places.iter().chain(&self.drives)
+	}
+
+	/// Handles the mouse within `area`. Returns the place that was clicked.
+	pub fn update(&mut self, mouse: &Mouse, area: Rect) -> Option<PathBuf> {
+		self.hovered = None;
+		if !area.contains(mouse.position()) {
+			return None;
+		}
+		let (_, mouse_y) = mouse.position();
+		let idx = (mouse_y as usize - area.y) / ROW_HEIGHT;
+		let path = self.places().nth(idx)?.path.clone();
+		self.hovered = Some(idx);
+		mouse.was_left_pressed().then_some(path)
+	}
+
+	/// Draws the places into `area`, highlighting the one `current` is in.
+	pub fn render(&self, bitmap_data: BitmapData, area: Rect, current: &Path) {
+		draw_rectangle(
+			bitmap_data,
+			(area.x, area.y),
+			(area.width, area.height),
+			BACKGROUND_COLOR,
+		);
+		// The longest matching place, so Documents wins over Home
+		let current_idx = self
+			.places()
+			.enumerate()
+			.filter(|(_, place)| current.starts_with(&place.path))
+			.max_by_key(|(_, place)| place.path.components().count())
+			.map(|(idx, _)| idx);
+
+		for (idx, place) in self.places().enumerate() {
+			let y = area.y + idx * ROW_HEIGHT;
+			if y + ROW_HEIGHT > area.y + area.height {
+				break;
+			}
+			let highlight = match (current_idx == Some(idx), self.hovered == Some(idx)) {
+				(true, _) => Some(CURRENT_COLOR),
+				(false, true) => Some(HOVER_COLOR),
+				(false, false) => None,
+			};
+			if let Some(color) = highlight {
+				draw_rectangle(bitmap_data, (area.x, y), (area.width, ROW_HEIGHT), color);
+			}
+			draw_texture(
+				bitmap_data,
+				&self.folder_icon,
+				area.x + PADDING,
+				y + (ROW_HEIGHT - ICON_SIZE) / 2,
+			);
+			let text_x = area.x + 2 * PADDING + ICON_SIZE;
+			let max_width = (area.x + area.width).saturating_sub(text_x + PADDING);
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&font::ui().truncate(&place.label, max_width),
+				(text_x, y + ROW_PADDING),
+				TEXT_COLOR,
+			);
+		}
+	}
+}
+
+/// Root folders of the drives there are, e.g. `C:\`.
+pub fn drives() -> Vec<PathBuf> {
+	// A: and B: are left out, probing floppy drives is slow
+	('C'..='Z')
+		.map(|letter| PathBuf::from(format!("{letter}:\\")))
+		.filter(|path| path.is_dir())
+		.collect()
+}
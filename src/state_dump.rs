@@ -0,0 +1,158 @@
+//! Snapshot of the explorer's state as a JSON file, for attaching to bug reports.
+
+use std::{
+	io,
+	path::PathBuf,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::animation;
+use crate::app::{State, ViewMode};
+use crate::json::Value;
+use crate::screen::Screen;
+
+/// Writes the state to a timestamped file in the temp directory and returns its path.
+pub fn save(state: &State) -> io::Result<PathBuf> {
+	let now = SystemTime::now();
+	let path =
+		std::env::temp_dir().join(format!("file-explorer-state-{}.json", utc_timestamp(now)));
+	std::fs::write(&path, to_json(state, now).to_pretty_string())?;
+	Ok(path)
+}
+
+pub fn to_json(state: &State, now: SystemTime) -> Value {
+	let view_mode = |mode: ViewMode| match mode {
+		ViewMode::List => "list",
+		ViewMode::Grid => "grid",
+	};
+
+	let listing = state.thumbnails.as_ref().map(|thumbnails| {
+		Value::object([
+			("directory", thumbnails.dir().into()),
+			(
+				"items",
+				(0..thumbnails.len())
+					.map(|idx| thumbnails.path(idx))
+					.collect(),
+			),
+			("pending_thumbnails", thumbnails.pending_count().into()),
+		])
+	});
+	let selection = state.grid.selection();
+
+	let config = &state.config;
+	let keybindings = config
+		.keybindings
+		.iter()
+		.map(|(command_id, shortcuts)| {
+			(
+				command_id.clone(),
+				shortcuts.iter().map(String::as_str).collect(),
+			)
+		})
+		.collect();
+	let keymap = state
+		.commands
+		.commands()
+		.iter()
+		.filter_map(|command| {
+			let chords: Value = state
+				.keymap
+				.chords_of(command.id)
+				.map(|chord| chord.to_string())
+				.collect();
+			let bound = chords != Value::Array(Vec::new());
+			bound.then(|| (command.id.to_string(), chords))
+		})
+		.collect();
+
+	Value::object([
+		("version", env!("CARGO_PKG_VERSION").into()),
+		("time", utc_timestamp(now).into()),
+		(
+			"history",
+			Value::object([
+				("current", state.history.current().into()),
+				(
+					"back",
+					state
+						.history
+						.back_entries()
+						.iter()
+						.map(|p| p.as_path())
+						.collect(),
+				),
+				(
+					"forward",
+					state
+						.history
+						.forward_entries()
+						.iter()
+						.map(|p| p.as_path())
+						.collect(),
+				),
+			]),
+		),
+		("view_mode", view_mode(state.view_mode).into()),
+		("screens", state.screens.iter().map(Screen::name).collect()),
+		("listing", listing.into()),
+		(
+			"selection",
+			Value::object([
+				("items", selection.iter().collect()),
+				("cursor", selection.cursor().into()),
+			]),
+		),
+		(
+			"settings",
+			Value::object([
+				("theme", format!("{:?}", config.theme).into()),
+				("view_mode", view_mode(config.view_mode).into()),
+				("animations", config.animations.into()),
+				("confirm_delete", config.confirm_delete.into()),
+				("confirm_overwrite", config.confirm_overwrite.into()),
+				("terminal_command", config.terminal_command.as_str().into()),
+				("keybindings", Value::Object(keybindings)),
+			]),
+		),
+		("keymap", Value::Object(keymap)),
+		(
+			"rendering",
+			Value::object([
+				#[cfg(feature = "demo")]
+				("dithering", state.demo.dithering.into()),
+				("animations", animation::enabled().into()),
+			]),
+		),
+		("plugins", state.plugins.names().collect()),
+		("last_new_folder", state.last_new_folder.as_deref().into()),
+	])
+}
+
+/// `YYYY-MM-DDTHH-MM-SSZ`, with dashes instead of colons to be usable in file names.
+pub fn utc_timestamp(time: SystemTime) -> String {
+	let secs = time
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+	// Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+	let z = days as i64 + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z.rem_euclid(146_097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+	format!(
+		"{year:04}-{month:02}-{day:02}T{:02}-{:02}-{:02}Z",
+		secs_of_day / 3600,
+		secs_of_day / 60 % 60,
+		secs_of_day % 60
+	)
+}
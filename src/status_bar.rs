@@ -0,0 +1,216 @@
+//! Status bar drawn along the bottom edge of the window. It doesn't inspect the application state
+//! by itself, other parts of the app keep it up to date by sending [StatusEvent]s.
+
+use std::time::{Duration, Instant};
+
+use crate::animation::Marquee;
+use crate::arena::{arena_format, FrameArena};
+use crate::draw::{draw_rectangle, draw_text};
+use crate::font;
+use crate::i18n::tr;
+use crate::window::BitmapData;
+
+/// How long operation messages stay visible.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+
+pub const HEIGHT: usize = font::LINE_HEIGHT + 2 * PADDING;
+const PADDING: usize = 4;
+const PROGRESS_WIDTH: usize = 120;
+/// Paths in messages are compacted to this width, see [font::compact_path].
+pub const MESSAGE_PATH_WIDTH: usize = 480;
+
+const BACKGROUND_COLOR: u32 = 0x3c3836;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const MESSAGE_COLOR: u32 = 0xfabd2f;
+const PROGRESS_TRACK_COLOR: u32 = 0x504945;
+const PROGRESS_COLOR: u32 = 0x83a598;
+
+#[derive(Debug)]
+pub enum StatusEvent {
+	/// Directory listing was (re)loaded.
+	ListingChanged {
+		item_count: usize,
+	},
+	SelectionChanged {
+		count: usize,
+		total_size: u64,
+	},
+	SortModeChanged(&'static str),
+	/// Transient message about a finished (or failed) operation.
+	OperationMessage(String),
+	/// A background operation is running. `fraction` is None when its progress is unknown, in which
+	/// case an indeterminate marquee is shown.
+	Progress {
+		label: String,
+		fraction: Option<f32>,
+	},
+	/// The running background operation finished.
+	ProgressDone,
+	/// The first shortcut of a chord was pressed and the second one is awaited, or not anymore.
+	ChordPending(Option<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct StatusBar {
+	item_count: usize,
+	selected_count: usize,
+	selected_size: u64,
+	sort_mode: Option<&'static str>,
+	message: Option<(String, Instant)>,
+	progress: Option<(String, Option<f32>)>,
+	pending_chord: Option<String>,
+	marquee: Marquee,
+}
+
+impl StatusBar {
+	pub fn handle(&mut self, event: StatusEvent) {
+		match event {
+			StatusEvent::ListingChanged { item_count } => {
+				self.item_count = item_count;
+				self.selected_count = 0;
+				self.selected_size = 0;
+			}
+			StatusEvent::SelectionChanged { count, total_size } => {
+				self.selected_count = count;
+				self.selected_size = total_size;
+			}
+			StatusEvent::SortModeChanged(sort_mode) => self.sort_mode = Some(sort_mode),
+			StatusEvent::OperationMessage(message) => {
+				self.message = Some((message, Instant::now()));
+			}
+			StatusEvent::Progress { label, fraction } => self.progress = Some((label, fraction)),
+			StatusEvent::ProgressDone => self.progress = None,
+			StatusEvent::ChordPending(chord) => self.pending_chord = chord,
+		}
+	}
+
+	/// Advances the progress marquee.
+	pub fn animate(&mut self, dt: Duration) {
+		if self.progress.is_some() {
+			self.marquee.update(dt);
+		}
+	}
+
+	pub fn render(&mut self, bitmap_data: BitmapData, arena: &FrameArena) {
+		if self
+			.message
+			.as_ref()
+			.is_some_and(|(_, shown_at)| shown_at.elapsed() > MESSAGE_TIMEOUT)
+		{
+			self.message = None;
+		}
+
+		let bitmap_width = bitmap_data.bitmap_width as usize;
+		let Some(y) = (bitmap_data.bitmap_height as usize).checked_sub(HEIGHT) else {
+			return;
+		};
+		draw_rectangle(
+			bitmap_data,
+			(0, y),
+			(bitmap_width, HEIGHT),
+			BACKGROUND_COLOR,
+		);
+
+		let text_y = y + PADDING + font::SCALE;
+		let left = self.summary();
+		draw_text(
+			bitmap_data,
+			font::ui(),
+			&left,
+			(PADDING * 2, text_y),
+			TEXT_COLOR,
+		);
+
+		if let Some((label, fraction)) = &self.progress {
+			let x = PADDING * 6 + font::ui().text_width(&left);
+			draw_text(bitmap_data, font::ui(), label, (x, text_y), TEXT_COLOR);
+			let bar_x = x + font::ui().text_width(label) + PADDING * 2;
+			self.render_progress_bar(bitmap_data, (bar_x, y + PADDING * 2), *fraction);
+		}
+
+		let mut right = bitmap_width.saturating_sub(PADDING * 2);
+		if let Some(sort_mode) = self.sort_mode {
+			let text = tr!("sort_mode", sort_mode);
+			right = right.saturating_sub(font::ui().text_width(&text));
+			draw_text(bitmap_data, font::ui(), &text, (right, text_y), TEXT_COLOR);
+			right = right.saturating_sub(PADDING * 4);
+		}
+		if let Some(chord) = &self.pending_chord {
+			let text = arena_format!(arena, "{chord}, ...");
+			right = right.saturating_sub(font::ui().text_width(text));
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				text,
+				(right, text_y),
+				MESSAGE_COLOR,
+			);
+		}
+
+		if let Some((message, _)) = &self.message {
+			let x = (bitmap_width.saturating_sub(font::ui().text_width(message))) / 2;
+			draw_text(bitmap_data, font::ui(), message, (x, text_y), MESSAGE_COLOR);
+		}
+	}
+
+	/// Item and selection counts shown on the left side.
+	pub fn summary(&self) -> String {
+		let mut summary = tr!("items", self.item_count);
+		if self.selected_count > 0 {
+			summary.push_str(" | ");
+			summary.push_str(&tr!(
+				"selected",
+				self.selected_count,
+				format_size(self.selected_size)
+			));
+		}
+		summary
+	}
+
+	fn render_progress_bar(
+		&self,
+		bitmap_data: BitmapData,
+		(x, y): (usize, usize),
+		fraction: Option<f32>,
+	) {
+		let height = HEIGHT - PADDING * 4;
+		draw_rectangle(
+			bitmap_data,
+			(x, y),
+			(PROGRESS_WIDTH, height),
+			PROGRESS_TRACK_COLOR,
+		);
+		let (start, width) = match fraction {
+			Some(fraction) => (
+				0,
+				(PROGRESS_WIDTH as f32 * fraction.clamp(0.0, 1.0)) as usize,
+			),
+			None => {
+				// A third of the bar sweeping from left to right
+				let width = PROGRESS_WIDTH / 3;
+				let start = ((PROGRESS_WIDTH + width) as f32 * self.marquee.phase()) as usize;
+				let end = start.min(PROGRESS_WIDTH);
+				let start = start.saturating_sub(width);
+				(start, end - start)
+			}
+		};
+		if width > 0 {
+			draw_rectangle(bitmap_data, (x + start, y), (width, height), PROGRESS_COLOR);
+		}
+	}
+}
+
+/// Formats a byte count using binary units, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+	if bytes < 1024 {
+		return format!("{bytes} B");
+	}
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{size:.1} {}", UNITS[unit])
+}
@@ -1,4 +1,11 @@
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
 use std::iter;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+
+use windows::core::PCWSTR;
 
 pub trait WindowsStrings: AsRef<str> {
 	fn to_utf16_with_null(&self) -> Vec<u16> {
@@ -10,3 +17,572 @@ pub trait WindowsStrings: AsRef<str> {
 }
 
 impl<T> WindowsStrings for T where T: AsRef<str> {}
+
+/// Conversions of strings that may not be valid Unicode, like paths, which keep unpaired
+/// surrogates as they are instead of replacing them.
+pub trait WindowsOsStrings: AsRef<OsStr> {
+	fn to_wide_with_null(&self) -> Vec<u16> {
+		self.as_ref()
+			.encode_wide()
+			.chain(iter::once(0))
+			.collect::<Vec<_>>()
+	}
+}
+
+impl<T> WindowsOsStrings for T where T: AsRef<OsStr> + ?Sized {}
+
+/// Null-terminated UTF-16 string for passing to Win32 functions. The [PCWSTR] it hands out points
+/// into its buffer, so the string has to be kept in a variable for as long as the pointer is used,
+/// rather than converted in place:
+///
+/// ```ignore
+/// let title = WideString::new("File Explorer");
+/// CreateWindowExW(.., title.as_pcwstr(), ..);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WideString(Vec<u16>);
+
+impl WideString {
+	pub fn new(s: impl AsRef<str>) -> Self {
+		WideString(s.to_utf16_with_null())
+	}
+
+	/// Keeps unpaired surrogates, see [WindowsOsStrings].
+	pub fn from_os_str(s: impl AsRef<OsStr>) -> Self {
+		WideString(s.to_wide_with_null())
+	}
+
+	pub fn as_pcwstr(&self) -> PCWSTR {
+		PCWSTR(self.0.as_ptr())
+	}
+}
+
+/// Text of a Win32 string buffer, e.g. a window title or a volume name, up to the first null or
+/// the end of the buffer. Unpaired surrogates, which Windows allows but Rust strings don't, become
+/// U+FFFD.
+pub fn from_utf16_null_terminated(buf: &[u16]) -> String {
+	String::from_utf16_lossy(until_null(buf))
+}
+
+/// Like [from_utf16_null_terminated], but keeps unpaired surrogates so that the string can be
+/// passed back to Windows unchanged.
+pub fn os_string_from_utf16_null_terminated(buf: &[u16]) -> OsString {
+	OsString::from_wide(until_null(buf))
+}
+
+pub fn path_from_utf16_null_terminated(buf: &[u16]) -> PathBuf {
+	PathBuf::from(os_string_from_utf16_null_terminated(buf))
+}
+
+fn until_null(buf: &[u16]) -> &[u16] {
+	let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+	&buf[..len]
+}
+
+/// Simple Unicode case folding: maps `c` to the character all its case variants fold to, so that
+/// comparing folded strings ignores case in any script, not just ASCII. Unlike full case folding
+/// never turns one character into several ('ß' stays 'ß' rather than becoming "ss"), matching how
+/// Windows compares file names.
+pub fn fold_case(c: char) -> char {
+	match c {
+		// Cherokee folds to uppercase, as its uppercase letters came first
+		'\u{13A0}'..='\u{13F5}' => c,
+		'\u{13F8}'..='\u{13FD}' => char::from_u32(c as u32 - 8).unwrap(),
+		'\u{AB70}'..='\u{ABBF}' => char::from_u32(c as u32 - 0xAB70 + 0x13A0).unwrap(),
+		// Variant forms whose lowercase mapping is themselves
+		'ς' => 'σ',
+		'ſ' => 's',
+		'ϐ' => 'β',
+		'ϑ' => 'θ',
+		'ϕ' => 'φ',
+		'ϖ' => 'π',
+		'ϰ' => 'κ',
+		'ϱ' => 'ρ',
+		'ϵ' => 'ε',
+		'ẛ' => 'ṡ',
+		'\u{1FBE}' => 'ι',
+		'ẞ' => 'ß',
+		_ => {
+			let mut lower = c.to_lowercase();
+			match (lower.next(), lower.next()) {
+				(Some(lower), None) => lower,
+				// Only full folding has a mapping, e.g. for 'İ'
+				_ => c,
+			}
+		}
+	}
+}
+
+/// Orders strings by their case folded characters, see [fold_case].
+pub fn cmp_ignore_case(a: &str, b: &str) -> Ordering {
+	a.chars().map(fold_case).cmp(b.chars().map(fold_case))
+}
+
+/// 64-bit FNV-1a hash of the case folded `text`, see [fold_case]. Unlike the hashers of std it
+/// stays the same across builds, for naming files after paths.
+pub fn hash_ignore_case(text: &str) -> u64 {
+	let mut hash = 0xcbf29ce484222325;
+	for c in text.chars().map(fold_case) {
+		for &byte in c.encode_utf8(&mut [0; 4]).as_bytes() {
+			hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+		}
+	}
+	hash
+}
+
+/// Orders strings like people read them: runs of digits compare by their value, so "file2.png"
+/// comes before "file10.png", and everything else compares like [cmp_ignore_case]. Numbers only
+/// differing in leading zeros, like "01" and "1", compare equal.
+pub fn cmp_natural(a: &str, b: &str) -> Ordering {
+	let (mut a, mut b) = (a, b);
+	loop {
+		let (Some(a_char), Some(b_char)) = (a.chars().next(), b.chars().next()) else {
+			// The shorter string first
+			return b.is_empty().cmp(&a.is_empty());
+		};
+		if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+			let (a_digits, a_rest) = split_digits(a);
+			let (b_digits, b_rest) = split_digits(b);
+			let ordering = cmp_digits(a_digits, b_digits);
+			if ordering.is_ne() {
+				return ordering;
+			}
+			(a, b) = (a_rest, b_rest);
+		} else {
+			let ordering = fold_case(a_char).cmp(&fold_case(b_char));
+			if ordering.is_ne() {
+				return ordering;
+			}
+			(a, b) = (&a[a_char.len_utf8()..], &b[b_char.len_utf8()..]);
+		}
+	}
+}
+
+/// The leading ASCII digits of `s`, and the rest.
+fn split_digits(s: &str) -> (&str, &str) {
+	let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	s.split_at(end)
+}
+
+/// Compares runs of ASCII digits by their value, however many digits they have.
+fn cmp_digits(a: &str, b: &str) -> Ordering {
+	let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+	a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Whether two names refer to the same file on a case-insensitive file system, e.g. to detect
+/// duplicates when copying.
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+	cmp_ignore_case(a, b) == Ordering::Equal
+}
+
+/// Case-insensitive subsequence match of `query` in `text`, for narrowing down lists as the user
+/// types. Returns the score (higher is better) and char indices of the matched characters for
+/// highlighting them, or None if `text` doesn't contain all characters of `query` in order.
+/// Matches at the start of words score higher, as do consecutive ones.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+	let mut positions = Vec::with_capacity(query.len());
+	let mut score = 0;
+	let mut query_chars = query.chars().map(fold_case).peekable();
+	let mut prev: Option<(usize, char)> = None;
+
+	for (idx, c) in text.chars().enumerate() {
+		let Some(&wanted) = query_chars.peek() else {
+			break;
+		};
+		if fold_case(c) != wanted {
+			prev = Some((idx, c));
+			continue;
+		}
+
+		score += 1;
+		match prev {
+			None => score += 8,
+			Some((_, prev_c)) if !prev_c.is_alphanumeric() => score += 6,
+			// camelCase boundary
+			Some((_, prev_c)) if prev_c.is_lowercase() && c.is_uppercase() => score += 6,
+			_ => (),
+		}
+		if positions.last().is_some_and(|&last| last + 1 == idx) {
+			score += 4;
+		}
+		positions.push(idx);
+		query_chars.next();
+		prev = Some((idx, c));
+	}
+
+	if query_chars.peek().is_some() {
+		return None;
+	}
+	Some((score, positions))
+}
+
+/// Compiled wildcard pattern for matching file names and paths:
+///
+/// - `?` matches any character but a path separator
+/// - `*` matches any run of characters within a path component
+/// - `**` matches across components, `a/**/b` matches `a/b` as well as `a/x/y/b`
+/// - `[abc]`, `[a-z]` match a character of the class, `[!a-z]` one that's not in it
+///
+/// Both `/` and `\` separate components. There's no escape character, as `\` is a separator, but
+/// `[*]` matches a literal `*`. Matching ignores case, like Windows does, see [fold_case].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+	tokens: Vec<GlobToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GlobToken {
+	Char(char),
+	Separator,
+	AnyChar,
+	/// `*`
+	AnyRun,
+	/// `**` not followed by a separator.
+	AnyPath,
+	/// `**` followed by a separator, which it consumes: any number of whole components.
+	AnyComponents,
+	Class {
+		negated: bool,
+		ranges: Vec<(char, char)>,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlobError {
+	/// A `[` without its `]`, at the given char index.
+	UnclosedClass { at: usize },
+}
+
+impl Display for GlobError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			GlobError::UnclosedClass { at } => {
+				write!(f, "Character class at {at} isn't closed with ].")
+			}
+		}
+	}
+}
+
+impl std::error::Error for GlobError {}
+
+impl Glob {
+	pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+		let chars: Vec<char> = pattern.chars().collect();
+		let mut tokens = Vec::new();
+		let mut idx = 0;
+		while idx < chars.len() {
+			let token = match chars[idx] {
+				'*' if chars.get(idx + 1) == Some(&'*') => {
+					idx += 1;
+					match chars.get(idx + 1) {
+						Some(&c) if is_separator(c) => {
+							idx += 1;
+							GlobToken::AnyComponents
+						}
+						_ => GlobToken::AnyPath,
+					}
+				}
+				'*' => GlobToken::AnyRun,
+				'?' => GlobToken::AnyChar,
+				'[' => {
+					let (token, end) = parse_class(&chars, idx)?;
+					idx = end;
+					token
+				}
+				c if is_separator(c) => GlobToken::Separator,
+				c => GlobToken::Char(fold_case(c)),
+			};
+			tokens.push(token);
+			idx += 1;
+		}
+		Ok(Glob { tokens })
+	}
+
+	/// Whether the whole of `text` matches the pattern.
+	pub fn matches(&self, text: &str) -> bool {
+		let text: Vec<char> = text.chars().collect();
+		// States known not to match, by token and char index, so that runs of stars don't take
+		// exponential time
+		let mut failed = vec![false; (self.tokens.len() + 1) * (text.len() + 1)];
+		self.matches_from(0, 0, &text, &mut failed)
+	}
+
+	fn matches_from(&self, token: usize, pos: usize, text: &[char], failed: &mut [bool]) -> bool {
+		let state = token * (text.len() + 1) + pos;
+		if failed[state] {
+			return false;
+		}
+		let Some(current) = self.tokens.get(token) else {
+			return pos == text.len();
+		};
+		let next = token + 1;
+		let c = text.get(pos).copied();
+		let matched = match current {
+			GlobToken::Char(wanted) => {
+				c.is_some_and(|c| fold_case(c) == *wanted)
+					&& self.matches_from(next, pos + 1, text, failed)
+			}
+			GlobToken::Separator => {
+				c.is_some_and(is_separator) && self.matches_from(next, pos + 1, text, failed)
+			}
+			GlobToken::AnyChar => {
+				c.is_some_and(|c| !is_separator(c))
+					&& self.matches_from(next, pos + 1, text, failed)
+			}
+			GlobToken::Class { negated, ranges } => {
+				c.is_some_and(|c| {
+					let folded = fold_case(c);
+					let in_class = ranges.iter().any(|&(from, to)| {
+						(from..=to).contains(&c) || (from..=to).contains(&folded)
+					});
+					!is_separator(c) && in_class != *negated
+				}) && self.matches_from(next, pos + 1, text, failed)
+			}
+			GlobToken::AnyRun => {
+				let run = text[pos..]
+					.iter()
+					.take_while(|&&c| !is_separator(c))
+					.count();
+				(0..=run).any(|len| self.matches_from(next, pos + len, text, failed))
+			}
+			GlobToken::AnyPath => {
+				(pos..=text.len()).any(|end| self.matches_from(next, end, text, failed))
+			}
+			GlobToken::AnyComponents => {
+				// Nothing, or anything ending with a separator
+				self.matches_from(next, pos, text, failed)
+					|| (pos..text.len()).any(|idx| {
+						is_separator(text[idx]) && self.matches_from(next, idx + 1, text, failed)
+					})
+			}
+		};
+		if !matched {
+			failed[state] = true;
+		}
+		matched
+	}
+}
+
+/// Parses the class starting with the `[` at `start`. Returns the token and the index of its `]`.
+fn parse_class(chars: &[char], start: usize) -> Result<(GlobToken, usize), GlobError> {
+	let mut idx = start + 1;
+	let negated = matches!(chars.get(idx), Some('!' | '^'));
+	if negated {
+		idx += 1;
+	}
+	let mut ranges = Vec::new();
+	// A ] right at the start is part of the class
+	let first = idx;
+	loop {
+		let Some(&c) = chars.get(idx) else {
+			return Err(GlobError::UnclosedClass { at: start });
+		};
+		if c == ']' && idx != first {
+			break;
+		}
+		match (chars.get(idx + 1), chars.get(idx + 2)) {
+			(Some('-'), Some(&to)) if to != ']' => {
+				ranges.push((fold_case(c), fold_case(to)));
+				idx += 3;
+			}
+			_ => {
+				ranges.push((fold_case(c), fold_case(c)));
+				idx += 1;
+			}
+		}
+	}
+	Ok((GlobToken::Class { negated, ranges }, idx))
+}
+
+fn is_separator(c: char) -> bool {
+	c == '/' || c == '\\'
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matches(pattern: &str, text: &str) -> bool {
+		Glob::new(pattern).unwrap().matches(text)
+	}
+
+	#[test]
+	fn literal() {
+		assert!(matches("photo.png", "photo.png"));
+		assert!(!matches("photo.png", "photo.jpg"));
+		assert!(!matches("photo", "photo.png"));
+		assert!(matches("", ""));
+		assert!(!matches("", "a"));
+	}
+
+	#[test]
+	fn ignores_case() {
+		assert!(matches("*.PNG", "photo.png"));
+		assert!(matches("straße", "STRAẞE"));
+		assert!(matches("[a-c]", "B"));
+		assert!(matches("[A-C]", "b"));
+	}
+
+	#[test]
+	fn question_mark() {
+		assert!(matches("a?c", "abc"));
+		assert!(!matches("a?c", "ac"));
+		assert!(!matches("a?c", "a/c"));
+		assert!(matches("??", "ąę"));
+	}
+
+	#[test]
+	fn star() {
+		assert!(matches("*", ""));
+		assert!(matches("*", "anything"));
+		assert!(matches("*.png", ".png"));
+		assert!(matches("a*b*c", "aXbYbZc"));
+		assert!(!matches("a*b*c", "aXbYbZ"));
+		assert!(!matches("*.png", "dir/photo.png"));
+		assert!(matches("*/*.png", "dir\\photo.png"));
+	}
+
+	#[test]
+	fn double_star() {
+		assert!(matches("**", "a/b/c"));
+		assert!(matches("**/*.png", "photo.png"));
+		assert!(matches("**/*.png", "a/b/photo.png"));
+		assert!(!matches("**/*.png", "a/b/photo.jpg"));
+		assert!(matches("a/**/b", "a/b"));
+		assert!(matches("a/**/b", "a/x/y/b"));
+		assert!(!matches("a/**/b", "a/xb"));
+		assert!(matches("a**", "abc/def"));
+		assert!(matches(
+			"C:\\Users\\**\\*.txt",
+			"c:/users/me/notes/todo.txt"
+		));
+	}
+
+	#[test]
+	fn classes() {
+		assert!(matches("[abc].txt", "b.txt"));
+		assert!(!matches("[abc].txt", "d.txt"));
+		assert!(matches("img[0-9][0-9]", "img42"));
+		assert!(!matches("img[0-9]", "imgx"));
+		assert!(matches("[!0-9]*", "x1"));
+		assert!(!matches("[!0-9]*", "1x"));
+		assert!(matches("[^a]", "b"));
+		assert!(matches("[]]", "]"));
+		assert!(matches("[a-]", "-"));
+		assert!(matches("[*]", "*"));
+		assert!(!matches("[*]", "x"));
+		assert!(!matches("[!a]", "/"));
+	}
+
+	#[test]
+	fn unclosed_class() {
+		assert_eq!(Glob::new("ab[cd"), Err(GlobError::UnclosedClass { at: 2 }));
+		assert_eq!(Glob::new("[!"), Err(GlobError::UnclosedClass { at: 0 }));
+		assert_eq!(Glob::new("[]"), Err(GlobError::UnclosedClass { at: 0 }));
+	}
+
+	#[test]
+	fn many_stars_are_fast() {
+		let text = "a".repeat(100);
+		assert!(!matches("*a*a*a*a*a*a*a*a*b", &text));
+		assert!(!matches("**a**a**a**a**a**a**b", &text));
+	}
+
+	#[test]
+	fn folds_case_beyond_ascii() {
+		assert_eq!(cmp_ignore_case("ŻÓŁW", "żółw"), Ordering::Equal);
+		assert_eq!(cmp_ignore_case("ΣΟΦΟΣ", "σοφος"), Ordering::Equal);
+		assert!(!eq_ignore_case("straße", "STRASSE"));
+		assert!(eq_ignore_case("straße", "STRAẞE"));
+		assert!(eq_ignore_case("\u{212A}elvin", "kelvin"));
+		assert!(eq_ignore_case("ᏣᎳᎩ", "ꮳꮃꭹ"));
+		assert!(eq_ignore_case("ᏸ", "Ᏸ"));
+		// Only full case folding maps the dotted capital I
+		assert!(!eq_ignore_case("İ", "i"));
+		assert_eq!(cmp_ignore_case("a", "B"), Ordering::Less);
+		assert_eq!(cmp_ignore_case("ab", "a"), Ordering::Greater);
+	}
+
+	#[test]
+	fn folded_variants_hash_alike() {
+		let variants = [
+			("Photos", "PHOTOS"),
+			("ŻÓŁW", "żółw"),
+			("ΟΔΟΣ", "οδος"),
+			("straße", "STRAẞE"),
+			("\u{212B}ngström", "åNGSTRÖM"),
+			("ᏣᎳᎩ", "ꮳꮃꭹ"),
+		];
+		for (a, b) in variants {
+			assert!(eq_ignore_case(a, b), "{a} {b}");
+			assert_eq!(hash_ignore_case(a), hash_ignore_case(b), "{a} {b}");
+		}
+		assert_ne!(hash_ignore_case("photos"), hash_ignore_case("photo"));
+		assert_ne!(hash_ignore_case("ß"), hash_ignore_case("ss"));
+		// FNV-1a offset basis
+		assert_eq!(hash_ignore_case(""), 0xcbf29ce484222325);
+	}
+
+	#[test]
+	fn natural_order() {
+		assert_eq!(cmp_natural("file2.png", "file10.png"), Ordering::Less);
+		assert_eq!(cmp_natural("file10", "file9"), Ordering::Greater);
+		assert_eq!(cmp_natural("Photo", "photo"), Ordering::Equal);
+		assert_eq!(cmp_natural("file", "file1"), Ordering::Less);
+		assert_eq!(cmp_natural("", "a"), Ordering::Less);
+		assert_eq!(cmp_natural("1", "a"), Ordering::Less);
+		assert_eq!(cmp_natural("v1.10", "v1.9"), Ordering::Greater);
+
+		let mut names = vec!["img12", "IMG1", "img02b", "img2", "Img0"];
+		names.sort_by(|a, b| cmp_natural(a, b));
+		assert_eq!(names, ["Img0", "IMG1", "img2", "img02b", "img12"]);
+	}
+
+	#[test]
+	fn natural_order_leading_zeros() {
+		assert_eq!(cmp_natural("a01", "a1"), Ordering::Equal);
+		assert_eq!(cmp_natural("a007", "a8"), Ordering::Less);
+		assert_eq!(cmp_natural("a000", "a0"), Ordering::Equal);
+		assert_eq!(cmp_natural("a00", "a1"), Ordering::Less);
+	}
+
+	#[test]
+	fn natural_order_long_numbers() {
+		// Longer than any integer type
+		let nines = format!("x{}", "9".repeat(30));
+		let power = format!("x1{}", "0".repeat(30));
+		assert_eq!(cmp_natural(&nines, &power), Ordering::Less);
+		let padded = format!("{}5", "0".repeat(40));
+		assert_eq!(cmp_natural(&padded, "4"), Ordering::Greater);
+		assert_eq!(cmp_natural(&padded, "5"), Ordering::Equal);
+		let long = "12345678901234567890123456789";
+		assert_eq!(cmp_natural(long, &long.replace('9', "8")), Ordering::Greater);
+	}
+
+	#[test]
+	fn fuzzy_match_positions() {
+		assert_eq!(fuzzy_match("", "anything"), Some((0, Vec::new())));
+		assert_eq!(fuzzy_match("abc", "abc").unwrap().1, [0, 1, 2]);
+		assert_eq!(fuzzy_match("ABC", "aXbXc").unwrap().1, [0, 2, 4]);
+		assert_eq!(fuzzy_match("łdź", "ŁÓDŹ").unwrap().1, [0, 2, 3]);
+		assert_eq!(fuzzy_match("ba", "ab"), None);
+		assert_eq!(fuzzy_match("abc", "ab"), None);
+		assert_eq!(fuzzy_match("a", ""), None);
+	}
+
+	#[test]
+	fn fuzzy_match_scores() {
+		let score = |query, text| fuzzy_match(query, text).unwrap().0;
+		// First character, word start, camelCase boundary, consecutive
+		assert_eq!(score("a", "a"), 9);
+		assert_eq!(score("fb", "foo_bar"), 16);
+		assert_eq!(score("fb", "fooBar"), 16);
+		assert_eq!(score("fo", "foo"), 14);
+		assert_eq!(score("fb", "xfoobar"), 2);
+
+		assert!(score("fb", "foo_bar") > score("fb", "xfoobar"));
+		assert!(score("nf", "new_folder") > score("nf", "unfold"));
+		assert!(score("abc", "xabc") > score("abc", "xaxbxc"));
+	}
+}
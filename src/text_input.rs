@@ -0,0 +1,133 @@
+//! Single line text field.
+
+use crate::draw::{draw_rectangle, draw_text};
+use crate::font::{self, Font};
+use crate::key::Key;
+use crate::window::{BitmapData, Keyboard};
+
+const PADDING: usize = 4;
+pub const HEIGHT: usize = font::LINE_HEIGHT + 2 * PADDING;
+
+const BACKGROUND_COLOR: u32 = 0x3c3836;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const CURSOR_COLOR: u32 = 0xfabd2f;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextInputEvent {
+	/// Text was modified.
+	Changed,
+	/// Enter was pressed.
+	Submit,
+	/// Escape was pressed.
+	Cancel,
+}
+
+#[derive(Debug, Default)]
+pub struct TextInput {
+	text: String,
+	/// Cursor position as a char index.
+	cursor: usize,
+}
+
+impl TextInput {
+	/// Creates a text input with the cursor placed at the end of `text`.
+	pub fn new(text: String) -> Self {
+		let cursor = text.chars().count();
+		TextInput { text, cursor }
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn clear(&mut self) {
+		self.text.clear();
+		self.cursor = 0;
+	}
+
+	/// Handles keyboard input of the current frame.
+	pub fn update(&mut self, keyboard: &Keyboard) -> Option<TextInputEvent> {
+		if keyboard.was_pressed(Key::Escape) {
+			return Some(TextInputEvent::Cancel);
+		}
+		if keyboard.was_pressed(Key::Return) {
+			return Some(TextInputEvent::Submit);
+		}
+
+		let mut changed = false;
+		let len = self.text.chars().count();
+		if keyboard.was_pressed(Key::Left) {
+			self.cursor = self.cursor.saturating_sub(1);
+		}
+		if keyboard.was_pressed(Key::Right) {
+			self.cursor = (self.cursor + 1).min(len);
+		}
+		if keyboard.was_pressed(Key::Home) {
+			self.cursor = 0;
+		}
+		if keyboard.was_pressed(Key::End) {
+			self.cursor = len;
+		}
+		if keyboard.was_pressed(Key::Back) && self.cursor > 0 {
+			self.cursor -= 1;
+			self.text.remove(self.byte_idx(self.cursor));
+			changed = true;
+		}
+		if keyboard.was_pressed(Key::Delete) && self.cursor < len {
+			self.text.remove(self.byte_idx(self.cursor));
+			changed = true;
+		}
+
+		let typed = keyboard.typed_text();
+		if !typed.is_empty() {
+			self.text.insert_str(self.byte_idx(self.cursor), typed);
+			self.cursor += typed.chars().count();
+			changed = true;
+		}
+
+		changed.then_some(TextInputEvent::Changed)
+	}
+
+	/// Draws the field at `(x, y)`. Text that doesn't fit is scrolled so that the cursor stays
+	/// visible.
+	pub fn render(&self, bitmap_data: BitmapData, (x, y): (usize, usize), width: usize) {
+		draw_rectangle(bitmap_data, (x, y), (width, HEIGHT), BACKGROUND_COLOR);
+
+		let (visible, cursor_x) = self.visible(font::ui(), width.saturating_sub(2 * PADDING));
+		let text_y = y + PADDING + font::SCALE;
+		draw_text(
+			bitmap_data,
+			font::ui(),
+			visible,
+			(x + PADDING, text_y),
+			TEXT_COLOR,
+		);
+
+		let cursor_x = x + PADDING + cursor_x;
+		draw_rectangle(
+			bitmap_data,
+			(cursor_x.saturating_sub(1), y + PADDING),
+			(font::SCALE, font::LINE_HEIGHT),
+			CURSOR_COLOR,
+		);
+	}
+
+	/// The part of the text shown in a field `max_width` pixels wide, scrolled so that the cursor
+	/// stays visible, and the cursor's distance from its start in pixels.
+	pub fn visible(&self, font: &Font, max_width: usize) -> (&str, usize) {
+		let cursor = self.byte_idx(self.cursor);
+		// The character after the cursor is kept in view too
+		let cursor_end = self.byte_idx(self.cursor + 1);
+		let start = cursor_end - font.fitting_end(&self.text[..cursor_end], max_width).len();
+		let start = start.min(cursor);
+		let visible = font.fitting_start(&self.text[start..], max_width);
+		(visible, font.text_width(&self.text[start..cursor]))
+	}
+
+	fn byte_idx(&self, char_idx: usize) -> usize {
+		self.text
+			.char_indices()
+			.nth(char_idx)
+			.map_or(self.text.len(), |(idx, _)| idx)
+	}
+}
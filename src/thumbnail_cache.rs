@@ -0,0 +1,105 @@
+//! Thumbnails kept on disk, so that the images of a folder aren't decoded again on every visit.
+//! Each thumbnail is a QOI file in the application data directory, named after the image's path,
+//! its modification time and the thumbnail size: an edited image or another size in the settings
+//! makes a new one. The oldest files are removed once the cache outgrows [MAX_SIZE], see [prune].
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+
+use crate::draw::Texture;
+use crate::qoi::Qoi;
+use crate::string;
+
+/// Size of the cache files [prune] keeps, in bytes.
+const MAX_SIZE: u64 = 256 * 1024 * 1024;
+const EXTENSION: &str = "qoi";
+
+/// Directory of the cache, if the application data directory is known.
+pub fn dir() -> Option<PathBuf> {
+	let app_data = std::env::var_os("APPDATA")?;
+	Some(
+		PathBuf::from(app_data)
+			.join("file-explorer")
+			.join("thumbnails"),
+	)
+}
+
+/// Cache file of the `size` thumbnail of the image at `path`, as it's now. None if the image
+/// can't be read.
+pub fn file_path(path: &Path, size: usize) -> Option<PathBuf> {
+	let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+	let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+	let key = format!("{}|{modified}|{size}", path.display());
+	let hash = string::hash_ignore_case(&key);
+	Some(dir()?.join(format!("{hash:016x}.{EXTENSION}")))
+}
+
+/// The thumbnail cached in `file`, None if there's none.
+pub fn load(file: &Path) -> Option<Texture> {
+	let data = std::fs::read(file).ok()?;
+	match Qoi::load_from_slice(&data) {
+		Ok(qoi) => Some(qoi.into()),
+		Err(err) => {
+			warn!("Invalid cached thumbnail {}: {err}", file.display());
+			None
+		}
+	}
+}
+
+/// Caches `thumbnail` in `file`. Failing to is only logged, the thumbnail is made again instead.
+pub fn store(file: &Path, thumbnail: &Texture) {
+	if let Some(dir) = file.parent() {
+		if let Err(err) = std::fs::create_dir_all(dir) {
+			warn!(
+				"Failed to create the thumbnail cache {}: {err}",
+				dir.display()
+			);
+			return;
+		}
+	}
+	let (width, height) = (thumbnail.width(), thumbnail.height());
+	if let Err(err) = Qoi::save_to_path(file, width, height, thumbnail.pixels()) {
+		warn!("Failed to cache a thumbnail: {err}");
+	}
+}
+
+/// Removes the files cached first while the cache is bigger than [MAX_SIZE]. Thumbnails of
+/// images that changed since are never loaded again, so they go eventually.
+pub fn prune() {
+	let Some(dir) = dir() else {
+		return;
+	};
+	let Ok(entries) = std::fs::read_dir(&dir) else {
+		return;
+	};
+	let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+		.flatten()
+		.filter_map(|entry| {
+			let metadata = entry.metadata().ok()?;
+			let modified = metadata.modified().ok()?;
+			Some((modified, metadata.len(), entry.path()))
+		})
+		.collect();
+	let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+	debug!("Thumbnail cache: {} files, {total} bytes", files.len());
+	if total <= MAX_SIZE {
+		return;
+	}
+	files.sort();
+	let mut removed = 0;
+	for (_, len, path) in files {
+		if total <= MAX_SIZE {
+			break;
+		}
+		match std::fs::remove_file(&path) {
+			Ok(()) => {
+				total -= len;
+				removed += 1;
+			}
+			Err(err) => warn!("Failed to remove {}: {err}", path.display()),
+		}
+	}
+	info!("Removed {removed} old thumbnails from the cache");
+}
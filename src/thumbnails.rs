@@ -0,0 +1,266 @@
+//! Background loading of image thumbnails, and of previews rendered by plugins, on the [jobs]
+//! pool. Thumbnails of still images are kept in the [thumbnail_cache].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error};
+
+use crate::animation::FrameSequence;
+use crate::cancel::CancelToken;
+use crate::draw::Texture;
+use crate::error::{self, Context};
+use crate::fs::DirListing;
+use crate::image::{self, Format, Image, LoadOptions};
+use crate::jobs::{self, Priority};
+use crate::plugin::Plugins;
+use crate::thumbnail_cache;
+
+/// Something that can produce thumbnails for items identified by their index. Widgets request
+/// thumbnails only for the items they're about to show and cancel them once they scroll away.
+pub trait ThumbnailSource {
+	/// Starts loading the thumbnail of item `idx`. Requesting an already requested item is a no-op.
+	fn request(&mut self, idx: usize);
+	/// Drops the thumbnail of item `idx`, aborting the load if it didn't finish yet.
+	fn cancel(&mut self, idx: usize);
+	/// Returns the thumbnail of item `idx` if it's already loaded.
+	fn get(&self, idx: usize) -> Option<&Texture>;
+	/// Collects thumbnails that finished loading since the last call.
+	fn poll(&mut self);
+}
+
+/// Loads thumbnails of image files on low priority jobs. Thumbnails of animated images play their
+/// animation, see [ImageThumbnails::animate].
+#[derive(Debug)]
+pub struct ImageThumbnails {
+	dir: PathBuf,
+	paths: Vec<PathBuf>,
+	size: usize,
+	plugins: Arc<Plugins>,
+	loaded: HashMap<usize, FrameSequence>,
+	/// Items whose jobs didn't send their thumbnail yet. Cancelling the token makes the job skip
+	/// the item, or abort it if it's already decoding.
+	pending: HashMap<usize, CancelToken>,
+	results_sender: Sender<(usize, FrameSequence)>,
+	results: Receiver<(usize, FrameSequence)>,
+}
+
+/// Listing of a directory running on a job, see [ImageThumbnails::for_dir].
+#[derive(Debug)]
+pub struct PendingListing {
+	dir: PathBuf,
+	size: usize,
+	plugins: Arc<Plugins>,
+	result: Receiver<error::Result<Vec<PathBuf>>>,
+}
+
+impl PendingListing {
+	/// Directory being listed.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// The thumbnails once the listing finished.
+	pub fn poll(&self) -> Option<error::Result<ImageThumbnails>> {
+		// Also None if the job was dropped by the jobs shutting down
+		let paths = self.result.try_recv().ok()?;
+		Some(paths.map(|paths| {
+			ImageThumbnails::new(
+				self.dir.clone(),
+				paths,
+				self.size,
+				Arc::clone(&self.plugins),
+			)
+		}))
+	}
+}
+
+impl ImageThumbnails {
+	/// Starts listing the images and files `plugins` preview in `dir` on a medium priority job.
+	/// Thumbnails are scaled down to fit in a `size`x`size` square.
+	pub fn for_dir(
+		dir: PathBuf,
+		size: usize,
+		show_hidden: bool,
+		plugins: Arc<Plugins>,
+	) -> PendingListing {
+		let (sender, result) = mpsc::channel();
+		{
+			let dir = dir.clone();
+			let plugins = Arc::clone(&plugins);
+			jobs::spawn(Priority::Medium, move || {
+				// The listing is dropped if the directory changed again in the meantime
+				let _ = sender.send(image_files(&dir, show_hidden, &plugins).with_path(&dir));
+			});
+		}
+		PendingListing {
+			dir,
+			size,
+			plugins,
+			result,
+		}
+	}
+
+	/// Thumbnails are scaled down to fit in a `size`x`size` square. Files that aren't images get
+	/// previews from `plugins`.
+	pub fn new(dir: PathBuf, paths: Vec<PathBuf>, size: usize, plugins: Arc<Plugins>) -> Self {
+		let (results_sender, results) = mpsc::channel();
+		ImageThumbnails {
+			dir,
+			paths,
+			size,
+			plugins,
+			loaded: HashMap::new(),
+			pending: HashMap::new(),
+			results_sender,
+			results,
+		}
+	}
+
+	/// Directory the images come from.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	pub fn len(&self) -> usize {
+		self.paths.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.paths.is_empty()
+	}
+
+	pub fn path(&self, idx: usize) -> &Path {
+		&self.paths[idx]
+	}
+
+	/// Advances the animations of loaded thumbnails.
+	pub fn animate(&mut self, dt: Duration) {
+		for frames in self.loaded.values_mut() {
+			frames.update(dt);
+		}
+	}
+
+	/// Number of requested thumbnails that didn't finish loading yet.
+	pub fn pending_count(&self) -> usize {
+		self.pending.len()
+	}
+}
+
+impl ThumbnailSource for ImageThumbnails {
+	fn request(&mut self, idx: usize) {
+		if self.loaded.contains_key(&idx) || self.pending.contains_key(&idx) {
+			return;
+		}
+		let Some(path) = self.paths.get(idx) else {
+			return;
+		};
+		let cancel = CancelToken::new();
+		self.pending.insert(idx, cancel.clone());
+		let (path, size) = (path.clone(), self.size);
+		let plugins = Arc::clone(&self.plugins);
+		let results = self.results_sender.clone();
+		jobs::spawn(Priority::Low, move || {
+			if let Some(frames) = load_thumbnail(&path, size, &plugins, cancel) {
+				// Nobody is waiting for the thumbnail anymore if the thumbnails were dropped
+				let _ = results.send((idx, frames));
+			}
+		});
+	}
+
+	fn cancel(&mut self, idx: usize) {
+		if let Some(cancel) = self.pending.remove(&idx) {
+			cancel.cancel();
+		}
+		self.loaded.remove(&idx);
+	}
+
+	fn get(&self, idx: usize) -> Option<&Texture> {
+		self.loaded.get(&idx).map(FrameSequence::texture)
+	}
+
+	fn poll(&mut self) {
+		for (idx, frames) in self.results.try_iter() {
+			// Results of requests cancelled while the worker was already decoding are dropped
+			if self.pending.remove(&idx).is_some() {
+				self.loaded.insert(idx, frames);
+			}
+		}
+	}
+}
+
+impl Drop for ImageThumbnails {
+	fn drop(&mut self) {
+		for cancel in self.pending.values() {
+			cancel.cancel();
+		}
+	}
+}
+
+/// Thumbnail of the file at `path`, None if it was cancelled or failed to load.
+fn load_thumbnail(
+	path: &Path,
+	size: usize,
+	plugins: &Plugins,
+	cancel: CancelToken,
+) -> Option<FrameSequence> {
+	if cancel.is_cancelled() {
+		debug!("Skipping cancelled thumbnail of {}", path.display());
+		return None;
+	}
+	if !Format::is_image_path(path) {
+		return match plugins.render_preview(path, size)? {
+			Ok(texture) => Some(FrameSequence::new(vec![(texture, Duration::ZERO)])),
+			Err(err) => {
+				error!("Failed to render preview: {err}");
+				None
+			}
+		};
+	}
+	let cache_file = thumbnail_cache::file_path(path, size);
+	if let Some(texture) = cache_file.as_deref().and_then(thumbnail_cache::load) {
+		return Some(FrameSequence::new(vec![(texture, Duration::ZERO)]));
+	}
+	let options = LoadOptions {
+		target_size: Some(size),
+		cancel: Some(cancel),
+	};
+	match image::load_from_path_with(path, &options).map(Image::into_frames) {
+		Ok(frames) => {
+			let frames: Vec<_> = frames
+				.into_iter()
+				.map(|(texture, delay)| (texture.scaled_to_fit(size, size), delay))
+				.collect();
+			// Animations would stop playing with only one frame cached
+			if let ([(texture, _)], Some(file)) = (frames.as_slice(), &cache_file) {
+				thumbnail_cache::store(file, texture);
+			}
+			Some(FrameSequence::new(frames))
+		}
+		Err(image::Error::Cancelled) => {
+			debug!("Cancelled loading thumbnail of {}", path.display());
+			None
+		}
+		Err(err) => {
+			error!("Failed to load thumbnail of {}: {err}", path.display());
+			None
+		}
+	}
+}
+
+/// Image files (that we can decode or `plugins` preview) directly inside of `dir`, sorted by name
+/// ignoring case.
+fn image_files(dir: &Path, show_hidden: bool, plugins: &Plugins) -> std::io::Result<Vec<PathBuf>> {
+	let mut listing = DirListing::read(dir, show_hidden)?;
+	listing.sort();
+	Ok(listing
+		.entries
+		.into_iter()
+		.filter(|entry| !entry.is_dir())
+		.map(|entry| entry.path)
+		.filter(|path| Format::is_image_path(path) || plugins.previews(path))
+		.collect())
+}
@@ -0,0 +1,211 @@
+//! Transient notifications stacked in the bottom right corner of the window, above the status bar.
+//! Each toast dismisses itself after a while (the timer is paused while it's hovered) or when it's
+//! clicked. A toast can carry an action button, e.g. "Undo", which executes a command.
+
+use std::time::Duration;
+
+use crate::arena::FrameArena;
+use crate::draw::{draw_rectangle, draw_text, Rect};
+use crate::font;
+use crate::status_bar;
+use crate::window::{BitmapData, Mouse};
+
+const INFO_TIMEOUT: Duration = Duration::from_secs(5);
+const ERROR_TIMEOUT: Duration = Duration::from_secs(8);
+/// Older toasts wait off-screen until newer ones go away.
+const MAX_VISIBLE: usize = 4;
+
+const PADDING: usize = 8;
+const MARGIN: usize = 8;
+const STRIPE_WIDTH: usize = 4;
+const HEIGHT: usize = font::LINE_HEIGHT + 2 * PADDING;
+const MAX_WIDTH: usize = 480;
+
+const BACKGROUND_COLOR: u32 = 0x1d2021;
+const HOVER_COLOR: u32 = 0x32302f;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const ACTION_COLOR: u32 = 0x83a598;
+const INFO_COLOR: u32 = 0x8ec07c;
+const ERROR_COLOR: u32 = 0xfb4934;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToastKind {
+	Info,
+	Error,
+}
+
+#[derive(Debug)]
+pub struct ToastAction {
+	pub label: String,
+	/// Command executed when the action is clicked.
+	pub command_id: &'static str,
+}
+
+#[derive(Debug)]
+pub struct Toast {
+	message: String,
+	kind: ToastKind,
+	action: Option<ToastAction>,
+	/// Time left until the toast is dismissed.
+	remaining: Duration,
+}
+
+impl Toast {
+	pub fn info(message: impl Into<String>) -> Self {
+		Toast {
+			message: message.into(),
+			kind: ToastKind::Info,
+			action: None,
+			remaining: INFO_TIMEOUT,
+		}
+	}
+
+	pub fn error(message: impl Into<String>) -> Self {
+		Toast {
+			message: message.into(),
+			kind: ToastKind::Error,
+			action: None,
+			remaining: ERROR_TIMEOUT,
+		}
+	}
+
+	pub fn with_action(mut self, label: impl Into<String>, command_id: &'static str) -> Self {
+		self.action = Some(ToastAction {
+			label: label.into(),
+			command_id,
+		});
+		self
+	}
+
+	fn width(&self) -> usize {
+		let action_width = self.action.as_ref().map_or(0, action_width);
+		STRIPE_WIDTH + 2 * PADDING + font::ui().text_width(&self.message) + action_width
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct Toasts {
+	/// Oldest first.
+	toasts: Vec<Toast>,
+	/// Index of the toast under the mouse cursor.
+	hovered: Option<usize>,
+}
+
+impl Toasts {
+	pub fn push(&mut self, toast: Toast) {
+		self.toasts.push(toast);
+	}
+
+	/// Counts down the timers of visible toasts, except for the hovered one.
+	pub fn animate(&mut self, dt: Duration) {
+		let first_visible = self.toasts.len().saturating_sub(MAX_VISIBLE);
+		for (idx, toast) in self.toasts.iter_mut().enumerate().skip(first_visible) {
+			if self.hovered != Some(idx) {
+				toast.remaining = toast.remaining.saturating_sub(dt);
+			}
+		}
+		let before = self.toasts.len();
+		self.toasts.retain(|toast| !toast.remaining.is_zero());
+		if self.toasts.len() != before {
+			self.hovered = None;
+		}
+	}
+
+	/// Handles mouse input of the current frame. Clicks on toasts are consumed, so that widgets
+	/// below them don't see them. Returns the command to execute if an action button was clicked.
+	pub fn update(
+		&mut self,
+		mouse: &mut Mouse,
+		bitmap_data: BitmapData,
+		arena: &FrameArena,
+	) -> Option<&'static str> {
+		let layout = self.layout(bitmap_data, arena);
+		let hit = layout
+			.iter()
+			.find(|(_, rect)| rect.contains(mouse.position()))
+			.copied();
+		self.hovered = hit.map(|(idx, _)| idx);
+
+		let (idx, rect) = hit?;
+		if !mouse.was_left_pressed() {
+			return None;
+		}
+		mouse.consume_press();
+
+		let toast = self.toasts.remove(idx);
+		self.hovered = None;
+		let action = toast.action?;
+		let action_x = (rect.x + rect.width).saturating_sub(action_width(&action));
+		(mouse.position().0 >= action_x as i32).then_some(action.command_id)
+	}
+
+	pub fn render(&self, bitmap_data: BitmapData, arena: &FrameArena) {
+		for &(idx, rect) in self.layout(bitmap_data, arena) {
+			let toast = &self.toasts[idx];
+			let background = if self.hovered == Some(idx) {
+				HOVER_COLOR
+			} else {
+				BACKGROUND_COLOR
+			};
+			draw_rectangle(
+				bitmap_data,
+				(rect.x, rect.y),
+				(rect.width, rect.height),
+				background,
+			);
+			let stripe_color = match toast.kind {
+				ToastKind::Info => INFO_COLOR,
+				ToastKind::Error => ERROR_COLOR,
+			};
+			draw_rectangle(
+				bitmap_data,
+				(rect.x, rect.y),
+				(STRIPE_WIDTH, rect.height),
+				stripe_color,
+			);
+
+			let text_y = rect.y + PADDING + font::SCALE;
+			let action_width = toast.action.as_ref().map_or(0, action_width);
+			let max_width = rect
+				.width
+				.saturating_sub(STRIPE_WIDTH + 2 * PADDING + action_width);
+			let message = font::ui().truncate(&toast.message, max_width);
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&message,
+				(rect.x + STRIPE_WIDTH + PADDING, text_y),
+				TEXT_COLOR,
+			);
+			if let Some(action) = &toast.action {
+				let x = (rect.x + rect.width + PADDING).saturating_sub(action_width);
+				draw_text(
+					bitmap_data,
+					font::ui(),
+					&action.label,
+					(x, text_y),
+					ACTION_COLOR,
+				);
+			}
+		}
+	}
+
+	/// Rectangles of the visible toasts, newest at the bottom.
+	fn layout<'a>(&self, bitmap_data: BitmapData, arena: &'a FrameArena) -> &'a [(usize, Rect)] {
+		let bitmap_width = bitmap_data.bitmap_width as usize;
+		let mut bottom = (bitmap_data.bitmap_height as usize).saturating_sub(status_bar::HEIGHT);
+		let layout = self.toasts.iter().enumerate().rev().take(MAX_VISIBLE);
+		arena.alloc_iter(layout.map_while(|(idx, toast)| {
+			let y = bottom.checked_sub(MARGIN + HEIGHT)?;
+			let width = toast.width().min(MAX_WIDTH).min(bitmap_width);
+			let x = bitmap_width.saturating_sub(width + MARGIN);
+			bottom = y;
+			Some((idx, Rect::new(x, y, width, HEIGHT)))
+		}))
+	}
+}
+
+/// Width of the clickable action button at the right end of a toast.
+fn action_width(action: &ToastAction) -> usize {
+	font::ui().text_width(&action.label) + 2 * PADDING
+}
@@ -0,0 +1,340 @@
+//! Reader and writer of the subset of TOML used by the configuration file: tables, dotted-free
+//! keys, strings, integers, floats, booleans and single-line arrays of those. Inline tables,
+//! multi-line strings and dates aren't supported.
+
+use std::fmt::{Display, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	String(String),
+	Integer(i64),
+	Float(f64),
+	Boolean(bool),
+	Array(Vec<Value>),
+}
+
+/// Key-value pairs in file order.
+pub type Table = Vec<(String, Value)>;
+
+/// The top-level table and the `[named]` tables, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+	pub root: Table,
+	pub tables: Vec<(String, Table)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+	pub line: usize,
+	pub message: String,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "Line {}: {}.", self.line, self.message)
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Value {
+	/// Name of the type for error messages.
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Value::String(_) => "a string",
+			Value::Integer(_) => "an integer",
+			Value::Float(_) => "a float",
+			Value::Boolean(_) => "a boolean",
+			Value::Array(_) => "an array",
+		}
+	}
+}
+
+impl Document {
+	pub fn table(&self, name: &str) -> Option<&Table> {
+		self.tables
+			.iter()
+			.find(|(table_name, _)| table_name == name)
+			.map(|(_, table)| table)
+	}
+}
+
+pub fn parse(text: &str) -> Result<Document, Error> {
+	let mut document = Document::default();
+	for (idx, line) in text.lines().enumerate() {
+		let error = |message: &str| Error {
+			line: idx + 1,
+			message: message.to_string(),
+		};
+		let mut parser = Parser {
+			chars: line.trim().chars().collect(),
+			pos: 0,
+		};
+		parser.skip_whitespace();
+		if parser.at_end_of_line() {
+			continue;
+		}
+
+		if parser.eat('[') {
+			parser.skip_whitespace();
+			let name = parser.key().map_err(|message| error(&message))?;
+			parser.skip_whitespace();
+			if !parser.eat(']') {
+				return Err(error("expected ] after the table name"));
+			}
+			if !parser.at_end_of_line() {
+				return Err(error("unexpected text after the table name"));
+			}
+			if document.table(&name).is_some() {
+				return Err(error(&format!("table {name} is defined twice")));
+			}
+			document.tables.push((name, Table::new()));
+			continue;
+		}
+
+		let key = parser.key().map_err(|message| error(&message))?;
+		parser.skip_whitespace();
+		if !parser.eat('=') {
+			return Err(error("expected = after the key"));
+		}
+		parser.skip_whitespace();
+		let value = parser.value().map_err(|message| error(&message))?;
+		if !parser.at_end_of_line() {
+			return Err(error("unexpected text after the value"));
+		}
+		let table = match document.tables.last_mut() {
+			Some((_, table)) => table,
+			None => &mut document.root,
+		};
+		if table.iter().any(|(existing, _)| *existing == key) {
+			return Err(error(&format!("key {key} is defined twice")));
+		}
+		table.push((key, value));
+	}
+	Ok(document)
+}
+
+/// Writes `document` in the syntax understood by [parse].
+pub fn to_string(document: &Document) -> String {
+	let mut text = String::new();
+	write_table(&mut text, &document.root);
+	for (name, table) in &document.tables {
+		if !text.is_empty() {
+			text.push('\n');
+		}
+		writeln!(text, "[{}]", format_key(name)).unwrap();
+		write_table(&mut text, table);
+	}
+	text
+}
+
+fn write_table(text: &mut String, table: &Table) {
+	for (key, value) in table {
+		writeln!(text, "{} = {value}", format_key(key)).unwrap();
+	}
+}
+
+/// Bare keys where possible, quoted otherwise.
+fn format_key(key: &str) -> String {
+	match !key.is_empty() && key.chars().all(is_bare_key_char) {
+		true => key.to_string(),
+		false => Value::String(key.to_string()).to_string(),
+	}
+}
+
+impl Display for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Value::String(s) => {
+				f.write_char('"')?;
+				for c in s.chars() {
+					match c {
+						'"' => f.write_str("\\\"")?,
+						'\\' => f.write_str("\\\\")?,
+						'\n' => f.write_str("\\n")?,
+						'\t' => f.write_str("\\t")?,
+						'\r' => f.write_str("\\r")?,
+						c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+						c => f.write_char(c)?,
+					}
+				}
+				f.write_char('"')
+			}
+			Value::Integer(n) => write!(f, "{n}"),
+			// Always with a fraction, so that it reads back as a float
+			Value::Float(n) if n.fract() == 0.0 && n.is_finite() => write!(f, "{n:.1}"),
+			Value::Float(n) => write!(f, "{n}"),
+			Value::Boolean(b) => write!(f, "{b}"),
+			Value::Array(values) => {
+				f.write_char('[')?;
+				for (idx, value) in values.iter().enumerate() {
+					if idx > 0 {
+						f.write_str(", ")?;
+					}
+					write!(f, "{value}")?;
+				}
+				f.write_char(']')
+			}
+		}
+	}
+}
+
+fn is_bare_key_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).copied()
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		let matches = self.peek() == Some(c);
+		if matches {
+			self.pos += 1;
+		}
+		matches
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+			self.pos += 1;
+		}
+	}
+
+	/// Whether only whitespace and maybe a comment are left.
+	fn at_end_of_line(&mut self) -> bool {
+		self.skip_whitespace();
+		matches!(self.peek(), None | Some('#'))
+	}
+
+	fn key(&mut self) -> Result<String, String> {
+		match self.peek() {
+			Some('"') => self.basic_string(),
+			Some('\'') => self.literal_string(),
+			_ => {
+				let start = self.pos;
+				while self.peek().is_some_and(is_bare_key_char) {
+					self.pos += 1;
+				}
+				match self.pos > start {
+					true => Ok(self.chars[start..self.pos].iter().collect()),
+					false => Err("expected a key".to_string()),
+				}
+			}
+		}
+	}
+
+	fn value(&mut self) -> Result<Value, String> {
+		match self.peek() {
+			Some('"') => self.basic_string().map(Value::String),
+			Some('\'') => self.literal_string().map(Value::String),
+			Some('[') => self.array(),
+			Some(_) => {
+				let start = self.pos;
+				while self
+					.peek()
+					.is_some_and(|c| !matches!(c, ',' | ']' | '#' | ' ' | '\t'))
+				{
+					self.pos += 1;
+				}
+				let word: String = self.chars[start..self.pos].iter().collect();
+				scalar(&word).ok_or_else(|| format!("invalid value {word}"))
+			}
+			None => Err("expected a value".to_string()),
+		}
+	}
+
+	fn array(&mut self) -> Result<Value, String> {
+		self.pos += 1;
+		let mut values = Vec::new();
+		loop {
+			self.skip_whitespace();
+			if self.eat(']') {
+				return Ok(Value::Array(values));
+			}
+			values.push(self.value()?);
+			self.skip_whitespace();
+			if self.eat(']') {
+				return Ok(Value::Array(values));
+			}
+			if !self.eat(',') {
+				return Err("expected , or ] in the array".to_string());
+			}
+		}
+	}
+
+	fn basic_string(&mut self) -> Result<String, String> {
+		self.pos += 1;
+		let mut s = String::new();
+		loop {
+			let c = self.peek().ok_or("unterminated string")?;
+			self.pos += 1;
+			match c {
+				'"' => return Ok(s),
+				'\\' => {
+					let escape = self.peek().ok_or("unterminated string")?;
+					self.pos += 1;
+					s.push(match escape {
+						'"' => '"',
+						'\\' => '\\',
+						'n' => '\n',
+						't' => '\t',
+						'r' => '\r',
+						'u' | 'U' => {
+							let len = if escape == 'u' { 4 } else { 8 };
+							let hex: String = self.chars.iter().skip(self.pos).take(len).collect();
+							self.pos += len;
+							u32::from_str_radix(&hex, 16)
+								.ok()
+								.and_then(char::from_u32)
+								.ok_or_else(|| format!("invalid escape \\{escape}{hex}"))?
+						}
+						other => return Err(format!("invalid escape \\{other}")),
+					});
+				}
+				c => s.push(c),
+			}
+		}
+	}
+
+	fn literal_string(&mut self) -> Result<String, String> {
+		self.pos += 1;
+		let start = self.pos;
+		while self.peek().is_some_and(|c| c != '\'') {
+			self.pos += 1;
+		}
+		if !self.eat('\'') {
+			return Err("unterminated string".to_string());
+		}
+		Ok(self.chars[start..self.pos - 1].iter().collect())
+	}
+}
+
+fn scalar(word: &str) -> Option<Value> {
+	match word {
+		"true" => return Some(Value::Boolean(true)),
+		"false" => return Some(Value::Boolean(false)),
+		_ => {}
+	}
+	let digits = word.replace('_', "");
+	if let Ok(n) = digits.parse::<i64>() {
+		return Some(Value::Integer(n));
+	}
+	// Rust would also take "inf" or "NaN", TOML only takes lowercase ones with an optional sign
+	let is_float = digits
+		.trim_start_matches(['+', '-'])
+		.chars()
+		.next()
+		.is_some_and(|c| c.is_ascii_digit());
+	match digits.trim_start_matches(['+', '-']) {
+		"inf" | "nan" => digits.parse().ok().map(Value::Float),
+		_ if is_float => digits.parse().ok().map(Value::Float),
+		_ => None,
+	}
+}
@@ -0,0 +1,231 @@
+//! Toolbar along the top edge of the window. Buttons don't do anything by themselves, clicking one
+//! executes a command from the [crate::command::CommandRegistry], same as its keyboard shortcut.
+
+use std::time::{Duration, Instant};
+
+use crate::atlas::{Atlas, AtlasBuilder, Region};
+use crate::command::CommandRegistry;
+use crate::draw::{draw_rectangle, draw_rectangle_blended, Rect};
+use crate::font;
+use crate::icons::{self, Icon};
+use crate::overlay::{LayerId, LayerKind, Overlays};
+use crate::window::{BitmapData, Mouse};
+
+const ICON_SIZE: usize = 24;
+const BUTTON_PADDING: usize = 4;
+const BUTTON_SIZE: usize = ICON_SIZE + 2 * BUTTON_PADDING;
+const PADDING: usize = 4;
+pub const HEIGHT: usize = BUTTON_SIZE + 2 * PADDING;
+
+const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+const TOOLTIP_LAYER: LayerId = "toolbar.tooltip";
+
+const BACKGROUND_COLOR: u32 = 0x3c3836;
+const HOVER_COLOR: u32 = 0x504945;
+const PRESSED_COLOR: u32 = 0x665c54;
+const TOOLTIP_COLOR: u32 = 0x1d2021;
+const TOOLTIP_TEXT_COLOR: u32 = 0xebdbb2;
+/// Opacity of the background drawn over the icons of disabled buttons.
+const DISABLED_ALPHA: f32 = 0.6;
+
+struct Button {
+	icon: &'static Icon,
+	command_id: &'static str,
+	tooltip: &'static str,
+}
+
+const BUTTONS: [Button; 6] = [
+	Button {
+		icon: &icons::BACK,
+		command_id: "navigate.back",
+		tooltip: "Back",
+	},
+	Button {
+		icon: &icons::FORWARD,
+		command_id: "navigate.forward",
+		tooltip: "Forward",
+	},
+	Button {
+		icon: &icons::UP,
+		command_id: "navigate.up",
+		tooltip: "Up",
+	},
+	Button {
+		icon: &icons::REFRESH,
+		command_id: "listing.refresh",
+		tooltip: "Refresh",
+	},
+	Button {
+		icon: &icons::NEW_FOLDER,
+		command_id: "listing.new_folder",
+		tooltip: "New folder",
+	},
+	Button {
+		icon: &icons::VIEW_MODE,
+		command_id: "view.toggle_mode",
+		tooltip: "Switch view mode",
+	},
+];
+
+#[derive(Debug)]
+pub struct Toolbar {
+	/// Hovered button and the time the cursor entered it.
+	hovered: Option<(usize, Instant)>,
+	/// Button the left mouse button went down on.
+	pressed: Option<usize>,
+	/// Rasterized icons of [BUTTONS].
+	icons: Atlas,
+	/// Icon of each button in `icons`.
+	icon_regions: Vec<Region>,
+}
+
+impl Default for Toolbar {
+	fn default() -> Self {
+		let mut icons = AtlasBuilder::default();
+		let icon_regions = BUTTONS
+			.iter()
+			.map(|button| icons.add(button.icon.rasterize(ICON_SIZE)))
+			.collect();
+		Toolbar {
+			hovered: None,
+			pressed: None,
+			icons: icons.build(),
+			icon_regions,
+		}
+	}
+}
+
+impl Toolbar {
+	/// Handles mouse input of the current frame and shows the tooltip of the hovered button, with
+	/// the shortcut of its command. Returns the id of the command to execute if an enabled button
+	/// was clicked.
+	pub fn update(
+		&mut self,
+		mouse: &Mouse,
+		bitmap_width: usize,
+		registry: &CommandRegistry,
+		overlays: &mut Overlays,
+	) -> Option<&'static str> {
+		let hovered = button_at(mouse.position());
+		if hovered != self.hovered.map(|(idx, _)| idx) {
+			self.hovered = hovered.map(|idx| (idx, Instant::now()));
+		}
+
+		if mouse.was_left_pressed() {
+			self.pressed = hovered;
+		}
+
+		match self.hovered {
+			Some((idx, hovered_since))
+				if hovered_since.elapsed() >= TOOLTIP_DELAY && self.pressed.is_none() =>
+			{
+				let (x, y) = button_pos(idx);
+				let button = &BUTTONS[idx];
+				let shortcut = registry
+					.get(button.command_id)
+					.and_then(|command| command.shortcut);
+				let text = match shortcut {
+					Some(chord) => format!("{} ({})", button.tooltip, chord.label()),
+					None => button.tooltip.to_string(),
+				};
+				show_tooltip(overlays, &text, (x, y + BUTTON_SIZE + 2), bitmap_width);
+			}
+			_ => overlays.close(TOOLTIP_LAYER),
+		}
+
+		if mouse.was_left_released() {
+			let pressed = self.pressed.take();
+			if pressed.is_some() && pressed == hovered {
+				return pressed
+					.map(|idx| BUTTONS[idx].command_id)
+					.filter(|&command_id| is_enabled(registry, command_id));
+			}
+		}
+		None
+	}
+
+	/// Buttons of disabled commands are dimmed.
+	pub fn render(&self, bitmap_data: BitmapData, registry: &CommandRegistry) {
+		let bitmap_width = bitmap_data.bitmap_width as usize;
+		draw_rectangle(
+			bitmap_data,
+			(0, 0),
+			(bitmap_width, HEIGHT),
+			BACKGROUND_COLOR,
+		);
+
+		for (idx, &icon) in self.icon_regions.iter().enumerate() {
+			let (x, y) = button_pos(idx);
+			let icon_pos = (x + BUTTON_PADDING, y + BUTTON_PADDING);
+			if !is_enabled(registry, BUTTONS[idx].command_id) {
+				self.icons.draw(bitmap_data, icon, icon_pos);
+				draw_rectangle_blended(
+					bitmap_data,
+					icon_pos,
+					(ICON_SIZE, ICON_SIZE),
+					BACKGROUND_COLOR,
+					DISABLED_ALPHA,
+				);
+				continue;
+			}
+			let hovered = self.hovered.is_some_and(|(hovered, _)| hovered == idx);
+			if hovered && self.pressed == Some(idx) {
+				draw_rectangle(
+					bitmap_data,
+					(x, y),
+					(BUTTON_SIZE, BUTTON_SIZE),
+					PRESSED_COLOR,
+				);
+			} else if hovered {
+				draw_rectangle(bitmap_data, (x, y), (BUTTON_SIZE, BUTTON_SIZE), HOVER_COLOR);
+			}
+			self.icons.draw(bitmap_data, icon, icon_pos);
+		}
+	}
+}
+
+/// Bounds, tooltip and command of every button.
+pub fn buttons() -> impl Iterator<Item = (Rect, &'static str, &'static str)> {
+	BUTTONS.iter().enumerate().map(|(idx, button)| {
+		let (x, y) = button_pos(idx);
+		let bounds = Rect::new(x, y, BUTTON_SIZE, BUTTON_SIZE);
+		(bounds, button.tooltip, button.command_id)
+	})
+}
+
+/// Buttons of commands that aren't registered stay enabled.
+fn is_enabled(registry: &CommandRegistry, command_id: &str) -> bool {
+	registry
+		.get(command_id)
+		.is_none_or(|command| command.enabled)
+}
+
+fn show_tooltip(overlays: &mut Overlays, text: &str, (x, y): (usize, usize), bitmap_width: usize) {
+	let width = font::ui().text_width(text) + 2 * PADDING;
+	let height = font::LINE_HEIGHT + PADDING;
+	let x = x.min(bitmap_width.saturating_sub(width));
+	let bounds = Rect::new(x, y, width, height);
+	let draw_list = overlays.open(TOOLTIP_LAYER, LayerKind::Passive, bounds);
+	draw_list.rectangle((x, y), (width, height), TOOLTIP_COLOR);
+	draw_list.text(
+		text,
+		(x + PADDING, y + PADDING / 2 + font::SCALE),
+		TOOLTIP_TEXT_COLOR,
+	);
+}
+
+fn button_pos(idx: usize) -> (usize, usize) {
+	(PADDING + idx * (BUTTON_SIZE + PADDING), PADDING)
+}
+
+fn button_at((x, y): (i32, i32)) -> Option<usize> {
+	if x < 0 || y < 0 {
+		return None;
+	}
+	let (x, y) = (x as usize, y as usize);
+	(0..BUTTONS.len()).find(|&idx| {
+		let (button_x, button_y) = button_pos(idx);
+		(button_x..button_x + BUTTON_SIZE).contains(&x)
+			&& (button_y..button_y + BUTTON_SIZE).contains(&y)
+	})
+}
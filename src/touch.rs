@@ -0,0 +1,123 @@
+//! Touch input. Windows reports touches as pointer messages, which are turned into the input events
+//! a mouse would cause: panning scrolls like the wheel, tapping clicks and pressing and holding
+//! right clicks, e.g. to open context menus.
+
+use std::time::{Duration, Instant};
+
+use crate::key::MouseButton;
+use crate::window::{InputEvent, SCROLL_PIXELS_PER_NOTCH, WHEEL_DELTA};
+
+/// How far a finger has to move before the touch becomes a pan instead of a tap.
+const PAN_THRESHOLD: i32 = 10;
+/// How long a finger has to stay down without moving to count as pressing and holding.
+const HOLD_DURATION: Duration = Duration::from_millis(600);
+
+/// Follows the first finger touching the window, further fingers are ignored.
+#[derive(Debug, Default)]
+pub struct Touch {
+	contact: Option<Contact>,
+}
+
+#[derive(Debug)]
+struct Contact {
+	pointer_id: u32,
+	start: (i32, i32),
+	last: (i32, i32),
+	started_at: Instant,
+	gesture: Gesture,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Gesture {
+	/// Could still become anything.
+	Undecided,
+	Pan,
+	/// The hold was already reported, the touch only ends now.
+	Hold,
+}
+
+impl Touch {
+	/// A finger touched the window at `pos`, in client area pixels.
+	pub fn down(&mut self, pointer_id: u32, pos: (i32, i32), now: Instant) -> Vec<InputEvent> {
+		if self.contact.is_some() {
+			return Vec::new();
+		}
+		self.contact = Some(Contact {
+			pointer_id,
+			start: pos,
+			last: pos,
+			started_at: now,
+			gesture: Gesture::Undecided,
+		});
+		let (x, y) = pos;
+		vec![InputEvent::MouseMove { x, y }]
+	}
+
+	pub fn update(&mut self, pointer_id: u32, pos: (i32, i32)) -> Vec<InputEvent> {
+		let Some(contact) = self.contact.as_mut().filter(|c| c.pointer_id == pointer_id) else {
+			return Vec::new();
+		};
+		let (dx, dy) = (pos.0 - contact.start.0, pos.1 - contact.start.1);
+		if contact.gesture == Gesture::Undecided && dx.abs().max(dy.abs()) > PAN_THRESHOLD {
+			contact.gesture = Gesture::Pan;
+		}
+		if contact.gesture != Gesture::Pan {
+			return Vec::new();
+		}
+
+		// The content follows the finger: moving it down scrolls up, like turning the wheel up
+		let (dx, dy) = (pos.0 - contact.last.0, pos.1 - contact.last.1);
+		contact.last = pos;
+		let to_delta = |pixels: i32| pixels * WHEEL_DELTA / SCROLL_PIXELS_PER_NOTCH;
+		let mut events = Vec::new();
+		if dy != 0 {
+			events.push(InputEvent::Wheel {
+				delta: to_delta(dy),
+				horizontal: false,
+			});
+		}
+		if dx != 0 {
+			events.push(InputEvent::Wheel {
+				delta: to_delta(-dx),
+				horizontal: true,
+			});
+		}
+		events
+	}
+
+	/// The finger left the window. Touches that were neither panned nor held are taps.
+	pub fn up(&mut self, pointer_id: u32) -> Vec<InputEvent> {
+		match self.contact.take() {
+			Some(contact) if contact.pointer_id == pointer_id => match contact.gesture {
+				Gesture::Undecided => click(MouseButton::Left, contact.start),
+				Gesture::Pan | Gesture::Hold => Vec::new(),
+			},
+			other => {
+				self.contact = other;
+				Vec::new()
+			}
+		}
+	}
+
+	/// Reports a press and hold once the finger stayed down long enough. Called every frame, as
+	/// holding still doesn't cause any messages.
+	pub fn poll(&mut self, now: Instant) -> Vec<InputEvent> {
+		match &mut self.contact {
+			Some(contact)
+				if contact.gesture == Gesture::Undecided
+					&& now - contact.started_at >= HOLD_DURATION =>
+			{
+				contact.gesture = Gesture::Hold;
+				click(MouseButton::Right, contact.start)
+			}
+			_ => Vec::new(),
+		}
+	}
+}
+
+fn click(button: MouseButton, (x, y): (i32, i32)) -> Vec<InputEvent> {
+	vec![
+		InputEvent::MouseDown { button, x, y },
+		InputEvent::MouseUp { button, x, y },
+	]
+}
@@ -0,0 +1,555 @@
+//! TrueType fonts. [Face] parses the tables of a `.ttf` file needed to draw text: character to
+//! glyph mapping (`cmap` formats 4 and 12), outlines (`glyf`, including composite glyphs) and
+//! horizontal metrics. [Font] is a face at a pixel size, rasterizing glyphs with the
+//! [vector](crate::vector) rasterizer the first time they're drawn and caching them after that.
+//! The UI font is one of them, see [font::ui](crate::font::ui).
+//!
+//! ```ignore
+//! let face = Face::load(Path::new("C:\\Windows\\Fonts\\segoeui.ttf"))?;
+//! let font = font::Font::TrueType(ttf::Font::new(Arc::new(face), 16.0));
+//! draw_text(bitmap_data, &font, "Hello", (10, 10), 0xebdbb2);
+//! ```
+//!
+//! Hinting instructions and kerning are ignored.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+
+use crate::draw::Texture;
+use crate::vector;
+
+/// Composite glyphs nested deeper than this are cut off, guarding against cycles.
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+#[derive(Debug)]
+pub enum Error {
+	Io {
+		err: std::io::Error,
+		filename: PathBuf,
+	},
+	BadMagic,
+	FileEnd,
+	MissingTable(&'static str),
+	/// No Unicode character map of a supported format.
+	UnsupportedCmap,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Io { err, filename } => {
+				write!(f, "Failed to load {}: {}.", filename.display(), err)
+			}
+			Error::BadMagic => write!(f, "Not a TrueType font."),
+			Error::FileEnd => write!(f, "File ended abruptly. Not enough data."),
+			Error::MissingTable(tag) => write!(f, "The font has no {tag} table."),
+			Error::UnsupportedCmap => write!(f, "The font has no supported Unicode character map."),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// A parsed font file.
+#[derive(Debug)]
+pub struct Face {
+	data: Vec<u8>,
+	units_per_em: f32,
+	ascender: f32,
+	descender: f32,
+	line_gap: f32,
+	glyph_count: u16,
+	/// Whether `loca` has 32-bit offsets.
+	long_offsets: bool,
+	loca: usize,
+	glyf: usize,
+	hmtx: usize,
+	h_metric_count: u16,
+	cmap: Cmap,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Cmap {
+	/// Offset of a format 4 subtable.
+	Segments(usize),
+	/// Offset of a format 12 subtable.
+	Groups(usize),
+}
+
+/// Point of an outline in font units, y up.
+#[derive(Debug, Copy, Clone)]
+struct Point {
+	x: f32,
+	y: f32,
+	on_curve: bool,
+}
+
+impl Face {
+	pub fn load(path: &Path) -> Result<Face, Error> {
+		let data = std::fs::read(path).map_err(|err| Error::Io {
+			err,
+			filename: path.to_path_buf(),
+		})?;
+		Face::parse(data)
+	}
+
+	pub fn parse(data: Vec<u8>) -> Result<Face, Error> {
+		match read_u32(&data, 0).ok_or(Error::FileEnd)? {
+			// Version 1.0, or "true" in old Apple fonts
+			0x0001_0000 | 0x7472_7565 => (),
+			_ => return Err(Error::BadMagic),
+		}
+		let table_count = read_u16(&data, 4).ok_or(Error::FileEnd)? as usize;
+		let table = |tag: &'static str| -> Result<usize, Error> {
+			(0..table_count)
+				.map(|idx| 12 + idx * 16)
+				.find(|&record| data.get(record..record + 4) == Some(tag.as_bytes()))
+				.and_then(|record| read_u32(&data, record + 8))
+				.map(|offset| offset as usize)
+				.ok_or(Error::MissingTable(tag))
+		};
+		let (head, hhea, maxp) = (table("head")?, table("hhea")?, table("maxp")?);
+		let (hmtx, loca, glyf, cmap) = (
+			table("hmtx")?,
+			table("loca")?,
+			table("glyf")?,
+			table("cmap")?,
+		);
+		let u16_at = |offset| read_u16(&data, offset).ok_or(Error::FileEnd);
+		let i16_at = |offset| u16_at(offset).map(|value| value as i16 as f32);
+
+		let face = Face {
+			units_per_em: u16_at(head + 18)?.max(1) as f32,
+			long_offsets: u16_at(head + 50)? != 0,
+			ascender: i16_at(hhea + 4)?,
+			descender: i16_at(hhea + 6)?,
+			line_gap: i16_at(hhea + 8)?,
+			h_metric_count: u16_at(hhea + 34)?,
+			glyph_count: u16_at(maxp + 4)?,
+			loca,
+			glyf,
+			hmtx,
+			cmap: find_cmap(&data, cmap).ok_or(Error::UnsupportedCmap)?,
+			data,
+		};
+		Ok(face)
+	}
+
+	/// Em size in pixels at which the distance from the ascender to the descender is `height`.
+	pub fn size_for_height(&self, height: f32) -> f32 {
+		height * self.units_per_em / (self.ascender - self.descender).max(1.0)
+	}
+
+	/// Index of the glyph of `c`, 0 (the "missing character" glyph) if the font has none.
+	fn glyph_index(&self, c: char) -> u16 {
+		let c = c as u32;
+		let data = &self.data;
+		let index = match self.cmap {
+			Cmap::Segments(table) => (|| {
+				let c = u16::try_from(c).ok()?;
+				let seg_count = read_u16(data, table + 6)? as usize / 2;
+				let end_codes = table + 14;
+				let start_codes = end_codes + 2 * seg_count + 2;
+				let deltas = start_codes + 2 * seg_count;
+				let range_offsets = deltas + 2 * seg_count;
+				for seg in 0..seg_count {
+					if read_u16(data, end_codes + 2 * seg)? < c {
+						continue;
+					}
+					let start = read_u16(data, start_codes + 2 * seg)?;
+					if start > c {
+						return None;
+					}
+					let delta = read_u16(data, deltas + 2 * seg)?;
+					let range_offset = read_u16(data, range_offsets + 2 * seg)? as usize;
+					if range_offset == 0 {
+						return Some(c.wrapping_add(delta));
+					}
+					// Relative to the position of the range offset itself
+					let glyph_id =
+						range_offsets + 2 * seg + range_offset + 2 * (c - start) as usize;
+					let glyph = read_u16(data, glyph_id)?;
+					return (glyph != 0).then(|| glyph.wrapping_add(delta));
+				}
+				None
+			})(),
+			Cmap::Groups(table) => (|| {
+				let group_count = read_u32(data, table + 12)? as usize;
+				for group in (0..group_count).map(|idx| table + 16 + idx * 12) {
+					let start = read_u32(data, group)?;
+					let end = read_u32(data, group + 4)?;
+					if (start..=end).contains(&c) {
+						let glyph = read_u32(data, group + 8)? + (c - start);
+						return u16::try_from(glyph).ok();
+					}
+				}
+				None
+			})(),
+		};
+		index.filter(|&index| index < self.glyph_count).unwrap_or(0)
+	}
+
+	/// Advance width of glyph `index` in font units.
+	fn advance(&self, index: u16) -> f32 {
+		// Glyphs past the last metric share its advance
+		let metric = index.min(self.h_metric_count.saturating_sub(1)) as usize;
+		read_u16(&self.data, self.hmtx + 4 * metric).unwrap_or(0) as f32
+	}
+
+	/// Contours of glyph `index`, empty for glyphs without an outline like the space.
+	fn outline(&self, index: u16, depth: usize) -> Option<Vec<Vec<Point>>> {
+		let data = &self.data;
+		let (start, end) = match self.long_offsets {
+			true => (
+				read_u32(data, self.loca + 4 * index as usize)? as usize,
+				read_u32(data, self.loca + 4 * index as usize + 4)? as usize,
+			),
+			false => (
+				2 * read_u16(data, self.loca + 2 * index as usize)? as usize,
+				2 * read_u16(data, self.loca + 2 * index as usize + 2)? as usize,
+			),
+		};
+		if end <= start {
+			return Some(Vec::new());
+		}
+		let glyph = self.glyf + start;
+		let contour_count = read_u16(data, glyph)? as i16;
+		match contour_count {
+			0.. => simple_outline(data, glyph, contour_count as usize),
+			_ if depth >= MAX_COMPONENT_DEPTH => Some(Vec::new()),
+			_ => self.composite_outline(glyph, depth),
+		}
+	}
+
+	/// Outline made of other glyphs, each moved and possibly scaled.
+	fn composite_outline(&self, glyph: usize, depth: usize) -> Option<Vec<Vec<Point>>> {
+		const ARGS_ARE_WORDS: u16 = 0x1;
+		const ARGS_ARE_XY_VALUES: u16 = 0x2;
+		const HAVE_SCALE: u16 = 0x8;
+		const MORE_COMPONENTS: u16 = 0x20;
+		const HAVE_X_AND_Y_SCALE: u16 = 0x40;
+		const HAVE_TWO_BY_TWO: u16 = 0x80;
+
+		let data = &self.data;
+		let f2dot14 = |offset| Some(read_u16(data, offset)? as i16 as f32 / 16384.0);
+		let mut contours = Vec::new();
+		let mut offset = glyph + 10;
+		loop {
+			let flags = read_u16(data, offset)?;
+			let component = read_u16(data, offset + 2)?;
+			offset += 4;
+			let (arg1, arg2) = match flags & ARGS_ARE_WORDS != 0 {
+				true => {
+					offset += 4;
+					(
+						read_u16(data, offset - 4)? as i16 as f32,
+						read_u16(data, offset - 2)? as i16 as f32,
+					)
+				}
+				false => {
+					offset += 2;
+					(
+						*data.get(offset - 2)? as i8 as f32,
+						*data.get(offset - 1)? as i8 as f32,
+					)
+				}
+			};
+			// Matching points instead of offsets is rare enough to place those unmoved
+			let (dx, dy) = match flags & ARGS_ARE_XY_VALUES != 0 {
+				true => (arg1, arg2),
+				false => (0.0, 0.0),
+			};
+			let [a, b, c, d] = if flags & HAVE_SCALE != 0 {
+				offset += 2;
+				let scale = f2dot14(offset - 2)?;
+				[scale, 0.0, 0.0, scale]
+			} else if flags & HAVE_X_AND_Y_SCALE != 0 {
+				offset += 4;
+				[f2dot14(offset - 4)?, 0.0, 0.0, f2dot14(offset - 2)?]
+			} else if flags & HAVE_TWO_BY_TWO != 0 {
+				offset += 8;
+				[
+					f2dot14(offset - 8)?,
+					f2dot14(offset - 6)?,
+					f2dot14(offset - 4)?,
+					f2dot14(offset - 2)?,
+				]
+			} else {
+				[1.0, 0.0, 0.0, 1.0]
+			};
+
+			for contour in self.outline(component, depth + 1)? {
+				contours.push(
+					contour
+						.into_iter()
+						.map(|point| Point {
+							x: a * point.x + c * point.y + dx,
+							y: b * point.x + d * point.y + dy,
+							on_curve: point.on_curve,
+						})
+						.collect(),
+				);
+			}
+			if flags & MORE_COMPONENTS == 0 {
+				return Some(contours);
+			}
+		}
+	}
+}
+
+/// Contours of a glyph that isn't composite.
+fn simple_outline(data: &[u8], glyph: usize, contour_count: usize) -> Option<Vec<Vec<Point>>> {
+	const ON_CURVE: u8 = 0x1;
+	const X_SHORT: u8 = 0x2;
+	const Y_SHORT: u8 = 0x4;
+	const REPEAT: u8 = 0x8;
+	const X_SAME_OR_POSITIVE: u8 = 0x10;
+	const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+	let end_points = glyph + 10;
+	let mut contour_ends = Vec::with_capacity(contour_count);
+	for idx in 0..contour_count {
+		contour_ends.push(read_u16(data, end_points + 2 * idx)? as usize);
+	}
+	let point_count = contour_ends.last().map_or(0, |&last| last + 1);
+	let instructions_len = read_u16(data, end_points + 2 * contour_count)? as usize;
+	let mut offset = end_points + 2 * contour_count + 2 + instructions_len;
+
+	let mut flags = Vec::with_capacity(point_count);
+	while flags.len() < point_count {
+		let flag = *data.get(offset)?;
+		offset += 1;
+		let repeat = match flag & REPEAT != 0 {
+			true => {
+				offset += 1;
+				*data.get(offset - 1)? as usize
+			}
+			false => 0,
+		};
+		for _ in 0..=repeat {
+			flags.push(flag);
+		}
+	}
+	flags.truncate(point_count);
+
+	let mut read_coordinates = |short: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+		let mut value = 0i32;
+		let mut coordinates = Vec::with_capacity(point_count);
+		for &flag in &flags {
+			if flag & short != 0 {
+				let delta = *data.get(offset)? as i32;
+				offset += 1;
+				value += match flag & same_or_positive != 0 {
+					true => delta,
+					false => -delta,
+				};
+			} else if flag & same_or_positive == 0 {
+				value += read_u16(data, offset)? as i16 as i32;
+				offset += 2;
+			}
+			coordinates.push(value as f32);
+		}
+		Some(coordinates)
+	};
+	let xs = read_coordinates(X_SHORT, X_SAME_OR_POSITIVE)?;
+	let ys = read_coordinates(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+	let mut contours = Vec::with_capacity(contour_count);
+	let mut start = 0;
+	for end in contour_ends {
+		if end < start || end >= point_count {
+			return None;
+		}
+		contours.push(
+			(start..=end)
+				.map(|idx| Point {
+					x: xs[idx],
+					y: ys[idx],
+					on_curve: flags[idx] & ON_CURVE != 0,
+				})
+				.collect(),
+		);
+		start = end + 1;
+	}
+	Some(contours)
+}
+
+/// The Unicode subtable of `cmap`, preferring the full repertoire of format 12.
+fn find_cmap(data: &[u8], cmap: usize) -> Option<Cmap> {
+	let table_count = read_u16(data, cmap + 2)? as usize;
+	let mut best = None;
+	for record in (0..table_count).map(|idx| cmap + 4 + idx * 8) {
+		let platform = read_u16(data, record)?;
+		let encoding = read_u16(data, record + 2)?;
+		let table = cmap + read_u32(data, record + 4)? as usize;
+		let unicode = platform == 0 || (platform == 3 && matches!(encoding, 1 | 10));
+		if !unicode {
+			continue;
+		}
+		match read_u16(data, table)? {
+			12 => return Some(Cmap::Groups(table)),
+			4 => best = Some(Cmap::Segments(table)),
+			_ => (),
+		}
+	}
+	best
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+	Some(u16::from_be_bytes(
+		data.get(offset..offset + 2)?.try_into().ok()?,
+	))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+	Some(u32::from_be_bytes(
+		data.get(offset..offset + 4)?.try_into().ok()?,
+	))
+}
+
+/// A rasterized glyph.
+#[derive(Debug)]
+pub struct Glyph {
+	/// White with the coverage as alpha, None for glyphs without an outline.
+	pub mask: Option<Texture>,
+	/// Position of the mask's top left corner relative to the pen on the baseline.
+	pub offset: (isize, isize),
+	/// Horizontal distance to the next glyph in pixels.
+	pub advance: f32,
+}
+
+/// A [Face] at a pixel size, with a cache of the glyphs drawn so far. Every size has a cache of
+/// its own, the face is shared.
+#[derive(Debug)]
+pub struct Font {
+	face: Arc<Face>,
+	/// Pixels per font unit.
+	scale: f32,
+	glyphs: Mutex<HashMap<char, Arc<Glyph>>>,
+}
+
+impl Font {
+	/// `face` with an em of `size` pixels.
+	pub fn new(face: Arc<Face>, size: f32) -> Self {
+		Font {
+			scale: size / face.units_per_em,
+			face,
+			glyphs: Mutex::default(),
+		}
+	}
+
+	/// Distance from the top of a line to the baseline, in pixels.
+	pub fn ascent(&self) -> f32 {
+		self.face.ascender * self.scale
+	}
+
+	/// Distance between the baselines of two lines, in pixels.
+	pub fn line_height(&self) -> f32 {
+		(self.face.ascender - self.face.descender + self.face.line_gap) * self.scale
+	}
+
+	pub fn text_width(&self, text: &str) -> f32 {
+		text.chars().map(|c| self.glyph(c).advance).sum()
+	}
+
+	/// The rasterized glyph of `c`, from the cache if it was drawn before.
+	pub fn glyph(&self, c: char) -> Arc<Glyph> {
+		if let Some(glyph) = self.glyphs.lock().unwrap().get(&c) {
+			return Arc::clone(glyph);
+		}
+		let glyph = Arc::new(self.rasterize(c));
+		self.glyphs.lock().unwrap().insert(c, Arc::clone(&glyph));
+		glyph
+	}
+
+	fn rasterize(&self, c: char) -> Glyph {
+		let index = self.face.glyph_index(c);
+		let advance = self.face.advance(index) * self.scale;
+		let contours = self.face.outline(index, 0).unwrap_or_else(|| {
+			warn!("Invalid outline of glyph {index} ({c:?})");
+			Vec::new()
+		});
+		let points = contours.iter().flatten();
+		let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+		let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+		for point in points {
+			// Flipped, y grows downwards on screen
+			let (x, y) = (point.x * self.scale, -point.y * self.scale);
+			(min_x, min_y) = (min_x.min(x), min_y.min(y));
+			(max_x, max_y) = (max_x.max(x), max_y.max(y));
+		}
+		if min_x > max_x {
+			return Glyph {
+				mask: None,
+				offset: (0, 0),
+				advance,
+			};
+		}
+
+		let (left, top) = (min_x.floor(), min_y.floor());
+		let width = (max_x.ceil() - left) as usize + 1;
+		let height = (max_y.ceil() - top) as usize + 1;
+		let mut path = vector::Path::new();
+		for contour in &contours {
+			let to_mask =
+				|point: &Point| (point.x * self.scale - left, -point.y * self.scale - top);
+			add_contour(&mut path, contour, to_mask);
+		}
+		let mut mask = Texture::new(width, height);
+		path.fill(&mut mask.bitmap, width, 0xFFFFFF, 1.0);
+		Glyph {
+			mask: Some(mask),
+			offset: (left as isize, top as isize),
+			advance,
+		}
+	}
+}
+
+/// Adds a closed contour of quadratic curves to `path`. Between two consecutive off-curve points
+/// lies an implied on-curve point in the middle of them.
+fn add_contour(path: &mut vector::Path, contour: &[Point], to_mask: impl Fn(&Point) -> (f32, f32)) {
+	let Some(first) = contour.first() else {
+		return;
+	};
+	let midpoint = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+	// Start on a point on the curve, which may be implied
+	let start_idx = contour.iter().position(|point| point.on_curve);
+	let start = match start_idx {
+		Some(idx) => to_mask(&contour[idx]),
+		None => midpoint(to_mask(first), to_mask(&contour[1 % contour.len()])),
+	};
+	let start_idx = start_idx.unwrap_or(0);
+	path.move_to(start.0, start.1);
+
+	// Wraps around to the start, which is the first control point if the start is implied
+	let mut control: Option<(f32, f32)> = None;
+	let following = contour[start_idx + 1..]
+		.iter()
+		.chain(&contour[..=start_idx]);
+	for point in following {
+		let position = to_mask(point);
+		match (point.on_curve, control) {
+			(true, None) => {
+				path.line_to(position.0, position.1);
+			}
+			(true, Some(c)) => {
+				path.quad_to(c.0, c.1, position.0, position.1);
+				control = None;
+			}
+			(false, None) => control = Some(position),
+			(false, Some(c)) => {
+				let implied = midpoint(c, position);
+				path.quad_to(c.0, c.1, implied.0, implied.1);
+				control = Some(position);
+			}
+		}
+	}
+	if let Some(c) = control {
+		path.quad_to(c.0, c.1, start.0, start.1);
+	}
+	path.close();
+}
@@ -0,0 +1,325 @@
+//! Immediate-mode GUI. Instead of keeping widget objects around, widgets are declared every frame
+//! by calling methods on [Ui], which handle the input of the current frame right away and record
+//! their drawing into a [DrawList] to be replayed during render:
+//!
+//! ```ignore
+//! let mut ui = Ui::begin(&mut draw_list, &mut memory, mouse, area);
+//! if ui.button("Refresh") {
+//!     command::execute(state, "listing.refresh");
+//! }
+//! ui.checkbox("Animations", &mut animations);
+//! ```
+//!
+//! The little state that has to survive between frames (the pressed widget, list scroll offsets)
+//! lives in [UiMemory], keyed by ids derived from widget labels.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::draw::{DrawList, Rect};
+use crate::font;
+use crate::text_input::TextInput;
+use crate::window::Mouse;
+
+const PADDING: usize = 6;
+const SPACING: usize = 4;
+const ROW_HEIGHT: usize = font::LINE_HEIGHT + 2 * PADDING;
+const CHECK_SIZE: usize = font::LINE_HEIGHT;
+
+const BACKGROUND_COLOR: u32 = 0x282828;
+const WIDGET_COLOR: u32 = 0x3c3836;
+const HOVER_COLOR: u32 = 0x504945;
+const ACTIVE_COLOR: u32 = 0x665c54;
+const SELECTED_COLOR: u32 = 0x458588;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const HINT_COLOR: u32 = 0x928374;
+const CHECK_COLOR: u32 = 0xfabd2f;
+const CURSOR_COLOR: u32 = 0xfabd2f;
+
+type Id = u64;
+
+/// Widget state kept between frames.
+#[derive(Debug, Default)]
+pub struct UiMemory {
+	/// Widget the left mouse button went down on.
+	active: Option<Id>,
+	/// First visible row of each list.
+	list_scroll: HashMap<Id, usize>,
+}
+
+pub struct Ui<'a> {
+	draw_list: &'a mut DrawList,
+	memory: &'a mut UiMemory,
+	mouse: &'a Mouse,
+	area: Rect,
+	/// Part of the area widgets are laid out in, all of it unless split by [Ui::column].
+	column: Rect,
+	/// Where the next widget goes.
+	cursor_y: usize,
+}
+
+/// Mouse interaction with a widget in the current frame.
+struct Response {
+	hovered: bool,
+	/// Held down on the widget.
+	active: bool,
+	/// Pressed and released on the widget.
+	clicked: bool,
+}
+
+impl<'a> Ui<'a> {
+	/// Starts a frame of widgets laid out top to bottom in `area`. Previously recorded drawing is
+	/// discarded.
+	pub fn begin(
+		draw_list: &'a mut DrawList,
+		memory: &'a mut UiMemory,
+		mouse: &'a Mouse,
+		area: Rect,
+	) -> Self {
+		draw_list.clear();
+		draw_list.rectangle(
+			(area.x, area.y),
+			(area.width, area.height),
+			BACKGROUND_COLOR,
+		);
+		// The pressed widget may have disappeared before the button was released
+		if !mouse.is_left_down() && !mouse.was_left_released() {
+			memory.active = None;
+		}
+		Ui {
+			draw_list,
+			memory,
+			mouse,
+			area,
+			column: area,
+			cursor_y: area.y + PADDING,
+		}
+	}
+
+	/// Lays out the next widgets from the top of column `idx` of `count` equally wide ones.
+	pub fn column(&mut self, idx: usize, count: usize) {
+		let width = self.area.width / count.max(1);
+		self.column = Rect::new(
+			self.area.x + idx * width,
+			self.area.y,
+			width,
+			self.area.height,
+		);
+		self.cursor_y = self.area.y + PADDING;
+	}
+
+	/// Whether the mouse cursor is over the UI area.
+	pub fn is_hovered(&self) -> bool {
+		self.area.contains(self.mouse.position())
+	}
+
+	pub fn label(&mut self, text: &str) {
+		let rect = self.allocate(font::LINE_HEIGHT);
+		let text = font::ui().truncate(text, rect.width);
+		self.draw_list
+			.text(&text, (rect.x, rect.y + font::SCALE), TEXT_COLOR);
+	}
+
+	/// Dimmed label, e.g. for section headers.
+	pub fn heading(&mut self, text: &str) {
+		let rect = self.allocate(font::LINE_HEIGHT);
+		let text = font::ui().truncate(text, rect.width);
+		self.draw_list
+			.text(&text, (rect.x, rect.y + font::SCALE), HINT_COLOR);
+	}
+
+	pub fn separator(&mut self) {
+		let rect = self.allocate(1);
+		self.draw_list
+			.rectangle((rect.x, rect.y), (rect.width, 1), WIDGET_COLOR);
+	}
+
+	/// Returns true when clicked.
+	pub fn button(&mut self, label: &str) -> bool {
+		let rect = self.allocate(ROW_HEIGHT);
+		let response = self.interact(id(label), rect);
+		let color = if response.active {
+			ACTIVE_COLOR
+		} else if response.hovered {
+			HOVER_COLOR
+		} else {
+			WIDGET_COLOR
+		};
+		self.draw_list
+			.rectangle((rect.x, rect.y), (rect.width, rect.height), color);
+		let max_width = rect.width.saturating_sub(2 * PADDING);
+		let text = font::ui().truncate(visible_label(label), max_width);
+		let text_x = rect.x + rect.width.saturating_sub(font::ui().text_width(&text)) / 2;
+		self.draw_list
+			.text(&text, (text_x, rect.y + PADDING + font::SCALE), TEXT_COLOR);
+		response.clicked
+	}
+
+	/// Single line field showing `input`, whose keyboard input is handled by
+	/// [TextInput::update]. Shows `hint` while it's empty.
+	pub fn text_field(&mut self, input: &TextInput, hint: &str) {
+		let rect = self.allocate(ROW_HEIGHT);
+		self.draw_list
+			.rectangle((rect.x, rect.y), (rect.width, rect.height), WIDGET_COLOR);
+		let text_pos = (rect.x + PADDING, rect.y + PADDING + font::SCALE);
+		let max_width = rect.width.saturating_sub(2 * PADDING);
+		if input.text().is_empty() {
+			self.draw_list
+				.text(&font::ui().truncate(hint, max_width), text_pos, HINT_COLOR);
+		}
+		let (visible, cursor_x) = input.visible(font::ui(), max_width);
+		self.draw_list.text(visible, text_pos, TEXT_COLOR);
+		self.draw_list.rectangle(
+			((text_pos.0 + cursor_x).saturating_sub(1), rect.y + PADDING),
+			(font::SCALE, font::LINE_HEIGHT),
+			CURSOR_COLOR,
+		);
+	}
+
+	/// Flips `value` when clicked. Returns true if it changed.
+	pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+		let rect = self.allocate(CHECK_SIZE);
+		let response = self.interact(id(label), rect);
+		if response.clicked {
+			*value = !*value;
+		}
+
+		let box_color = if response.hovered {
+			HOVER_COLOR
+		} else {
+			WIDGET_COLOR
+		};
+		self.draw_list
+			.rectangle((rect.x, rect.y), (CHECK_SIZE, CHECK_SIZE), box_color);
+		if *value {
+			let inset = CHECK_SIZE / 4;
+			self.draw_list.rectangle(
+				(rect.x + inset, rect.y + inset),
+				(CHECK_SIZE - 2 * inset, CHECK_SIZE - 2 * inset),
+				CHECK_COLOR,
+			);
+		}
+		let text_x = rect.x + CHECK_SIZE + PADDING;
+		let text = font::ui().truncate(
+			visible_label(label),
+			(rect.x + rect.width).saturating_sub(text_x),
+		);
+		self.draw_list
+			.text(&text, (text_x, rect.y + font::SCALE), TEXT_COLOR);
+		response.clicked
+	}
+
+	/// List of `items` showing at most `rows` of them at once. Clicking an item selects it. Longer
+	/// lists get paging buttons below the rows. Returns true if the selection changed.
+	pub fn list(
+		&mut self,
+		id_source: &str,
+		items: &[impl AsRef<str>],
+		selected: &mut Option<usize>,
+		rows: usize,
+	) -> bool {
+		let list_id = id(id_source);
+		let rows = rows.max(1);
+		let max_scroll = items.len().saturating_sub(rows);
+		let mut scroll = self
+			.memory
+			.list_scroll
+			.get(&list_id)
+			.copied()
+			.unwrap_or(0)
+			.min(max_scroll);
+
+		let row_height = font::LINE_HEIGHT + PADDING;
+		let rect = self.allocate(rows * row_height);
+		self.draw_list
+			.rectangle((rect.x, rect.y), (rect.width, rect.height), WIDGET_COLOR);
+
+		let mut changed = false;
+		for (row, (idx, item)) in items.iter().enumerate().skip(scroll).take(rows).enumerate() {
+			let row_rect = Rect::new(rect.x, rect.y + row * row_height, rect.width, row_height);
+			let response = self.interact(list_id.wrapping_add(1 + idx as u64), row_rect);
+			if response.clicked && *selected != Some(idx) {
+				*selected = Some(idx);
+				changed = true;
+			}
+
+			let color = if *selected == Some(idx) {
+				Some(SELECTED_COLOR)
+			} else if response.hovered {
+				Some(HOVER_COLOR)
+			} else {
+				None
+			};
+			if let Some(color) = color {
+				self.draw_list.rectangle(
+					(row_rect.x, row_rect.y),
+					(row_rect.width, row_rect.height),
+					color,
+				);
+			}
+			let max_width = row_rect.width.saturating_sub(2 * PADDING);
+			let text = font::ui().truncate(item.as_ref(), max_width);
+			self.draw_list.text(
+				&text,
+				(row_rect.x + PADDING, row_rect.y + PADDING / 2 + font::SCALE),
+				TEXT_COLOR,
+			);
+		}
+
+		if max_scroll > 0 {
+			let last = (scroll + rows).min(items.len());
+			self.heading(&format!("{}-{} of {}", scroll + 1, last, items.len()));
+			if self.button(&format!("Previous##{id_source}")) {
+				scroll = scroll.saturating_sub(rows);
+			}
+			if self.button(&format!("Next##{id_source}")) {
+				scroll = (scroll + rows).min(max_scroll);
+			}
+		}
+		self.memory.list_scroll.insert(list_id, scroll);
+
+		changed
+	}
+
+	/// Takes the next `height` pixels of the column.
+	fn allocate(&mut self, height: usize) -> Rect {
+		let rect = Rect::new(
+			self.column.x + PADDING,
+			self.cursor_y,
+			self.column.width.saturating_sub(2 * PADDING),
+			height,
+		);
+		self.cursor_y += height + SPACING;
+		rect
+	}
+
+	fn interact(&mut self, id: Id, rect: Rect) -> Response {
+		let hovered = rect.contains(self.mouse.position());
+		if hovered && self.mouse.was_left_pressed() {
+			self.memory.active = Some(id);
+		}
+		let mut clicked = false;
+		if self.memory.active == Some(id) && self.mouse.was_left_released() {
+			self.memory.active = None;
+			clicked = hovered;
+		}
+		Response {
+			hovered,
+			active: self.memory.active == Some(id),
+			clicked,
+		}
+	}
+}
+
+/// Widget id from its label. Anything after `##` is used only for the id, not shown, which
+/// allows multiple widgets with the same visible label.
+fn id(label: &str) -> Id {
+	let mut hasher = DefaultHasher::new();
+	label.hash(&mut hasher);
+	hasher.finish()
+}
+
+fn visible_label(label: &str) -> &str {
+	label.split("##").next().unwrap_or(label)
+}
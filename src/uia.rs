@@ -0,0 +1,477 @@
+//! UI Automation providers serving the [Tree](crate::accessibility::Tree) to screen readers and
+//! other assistive technology.
+//!
+//! The window answers `WM_GETOBJECT` with a fragment root provider. Every node of the tree is
+//! exposed as a child fragment identified by its id; providers look their node up in the latest
+//! snapshot on every call, so they never hold on to stale data. UIA calls providers from its own
+//! threads, which is why the tree lives behind a mutex.
+
+use std::fmt::Debug;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+
+use log::error;
+use windows::core::{implement, Error, IUnknown, Result, BSTR, HRESULT, PCWSTR};
+use windows::Win32::Foundation::{
+	BOOL, E_NOTIMPL, E_OUTOFMEMORY, HWND, LPARAM, LRESULT, POINT, VARIANT_BOOL, WPARAM,
+};
+use windows::Win32::Graphics::Gdi::{ClientToScreen, ScreenToClient};
+use windows::Win32::System::Com::{SAFEARRAY, VARIANT, VT_BOOL, VT_BSTR, VT_I4};
+use windows::Win32::System::Ole::{SafeArrayCreateVector, SafeArrayPutElement};
+use windows::Win32::UI::Accessibility::{
+	IInvokeProvider, IInvokeProvider_Impl, IRawElementProviderFragment,
+	IRawElementProviderFragmentRoot, IRawElementProviderFragmentRoot_Impl,
+	IRawElementProviderFragment_Impl, IRawElementProviderSimple, IRawElementProviderSimple_Impl,
+	ISelectionItemProvider, ISelectionItemProvider_Impl, IValueProvider, IValueProvider_Impl,
+	NavigateDirection, NavigateDirection_FirstChild, NavigateDirection_LastChild,
+	NavigateDirection_NextSibling, NavigateDirection_Parent, NavigateDirection_PreviousSibling,
+	NotificationKind_ActionCompleted, NotificationProcessing_ImportantMostRecent, ProviderOptions,
+	ProviderOptions_ServerSideProvider, UIA_AutomationFocusChangedEventId,
+	UIA_AutomationIdPropertyId, UIA_ButtonControlTypeId, UIA_ControlTypePropertyId,
+	UIA_EditControlTypeId, UIA_HasKeyboardFocusPropertyId, UIA_InvokePatternId,
+	UIA_IsEnabledPropertyId, UIA_IsKeyboardFocusablePropertyId, UIA_ListItemControlTypeId,
+	UIA_NamePropertyId, UIA_SelectionItemPatternId, UIA_TextControlTypeId, UIA_ValuePatternId,
+	UIA_ValueValuePropertyId, UiaAppendRuntimeId, UiaClientsAreListening, UiaDisconnectProvider,
+	UiaHostProviderFromHwnd, UiaRaiseAutomationEvent, UiaRaiseNotificationEvent, UiaRect,
+	UiaReturnRawElementProvider, UiaRootObjectId, UIA_CONTROLTYPE_ID, UIA_PATTERN_ID,
+	UIA_PROPERTY_ID,
+};
+
+use crate::accessibility::{Node, Request, Role, Shared};
+
+/// `UIA_E_ELEMENTNOTAVAILABLE`, returned when a provider's node is gone from the tree.
+const ELEMENT_NOT_AVAILABLE: HRESULT = HRESULT(0x8004_0201_u32 as i32);
+
+/// Owns the root provider of a window.
+#[derive(Default)]
+pub struct Host {
+	hwnd: HWND,
+	root: Option<IRawElementProviderSimple>,
+}
+
+impl Debug for Host {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Host")
+			.field("hwnd", &self.hwnd)
+			.field("connected", &self.root.is_some())
+			.finish()
+	}
+}
+
+impl Host {
+	/// Handles `WM_GETOBJECT`. Returns None for requests that aren't meant for UIA, which should be
+	/// passed on to `DefWindowProcW`.
+	pub fn handle_get_object(
+		&mut self,
+		hwnd: HWND,
+		w_param: WPARAM,
+		l_param: LPARAM,
+		shared: &Arc<Shared>,
+	) -> Option<LRESULT> {
+		if l_param.0 as i32 != UiaRootObjectId {
+			return None;
+		}
+		self.hwnd = hwnd;
+		let root = self.root.get_or_insert_with(|| {
+			RootProvider {
+				hwnd,
+				shared: Arc::clone(shared),
+			}
+			.into()
+		});
+		Some(unsafe { UiaReturnRawElementProvider(hwnd, w_param, l_param, &*root) })
+	}
+
+	/// Asks screen readers to read out `text`.
+	pub fn announce(&self, text: &str) {
+		let Some(root) = self.listening_root() else {
+			return;
+		};
+		let result = unsafe {
+			UiaRaiseNotificationEvent(
+				root,
+				NotificationKind_ActionCompleted,
+				NotificationProcessing_ImportantMostRecent,
+				&BSTR::from(text),
+				&BSTR::from("file-explorer.announcement"),
+			)
+		};
+		if let Err(err) = result {
+			error!("UiaRaiseNotificationEvent: {err}");
+		}
+	}
+
+	pub fn raise_focus_changed(&self, shared: &Arc<Shared>, id: u32) {
+		if self.listening_root().is_none() {
+			return;
+		}
+		let node: IRawElementProviderSimple = NodeProvider {
+			hwnd: self.hwnd,
+			shared: Arc::clone(shared),
+			id,
+		}
+		.into();
+		if let Err(err) =
+			unsafe { UiaRaiseAutomationEvent(&node, UIA_AutomationFocusChangedEventId) }
+		{
+			error!("UiaRaiseAutomationEvent: {err}");
+		}
+	}
+
+	/// Releases the providers held by UIA clients. Called when the window is destroyed.
+	pub fn disconnect(&mut self) {
+		if let Some(root) = self.root.take() {
+			if let Err(err) = unsafe { UiaDisconnectProvider(&root) } {
+				error!("UiaDisconnectProvider: {err}");
+			}
+		}
+	}
+
+	/// The root provider, if any client is interested in events.
+	fn listening_root(&self) -> Option<&IRawElementProviderSimple> {
+		let listening = unsafe { UiaClientsAreListening() }.as_bool();
+		self.root.as_ref().filter(|_| listening)
+	}
+}
+
+#[implement(
+	IRawElementProviderSimple,
+	IRawElementProviderFragment,
+	IRawElementProviderFragmentRoot
+)]
+struct RootProvider {
+	hwnd: HWND,
+	shared: Arc<Shared>,
+}
+
+impl RootProvider {
+	fn child(&self, node: Option<&Node>) -> Result<IRawElementProviderFragment> {
+		match node {
+			Some(node) => Ok(NodeProvider {
+				hwnd: self.hwnd,
+				shared: Arc::clone(&self.shared),
+				id: node.id,
+			}
+			.into()),
+			// Null provider
+			None => Err(Error::OK),
+		}
+	}
+}
+
+impl IRawElementProviderSimple_Impl for RootProvider {
+	fn ProviderOptions(&self) -> Result<ProviderOptions> {
+		Ok(ProviderOptions_ServerSideProvider)
+	}
+
+	fn GetPatternProvider(&self, _pattern_id: UIA_PATTERN_ID) -> Result<IUnknown> {
+		Err(Error::OK)
+	}
+
+	fn GetPropertyValue(&self, _property_id: UIA_PROPERTY_ID) -> Result<VARIANT> {
+		// Name, control type etc. come from the host provider of the window
+		Ok(VARIANT::default())
+	}
+
+	fn HostRawElementProvider(&self) -> Result<IRawElementProviderSimple> {
+		unsafe { UiaHostProviderFromHwnd(self.hwnd) }
+	}
+}
+
+impl IRawElementProviderFragment_Impl for RootProvider {
+	fn Navigate(&self, direction: NavigateDirection) -> Result<IRawElementProviderFragment> {
+		let tree = self.shared.tree.lock().unwrap();
+		match direction {
+			direction if direction == NavigateDirection_FirstChild => {
+				self.child(tree.nodes.first())
+			}
+			direction if direction == NavigateDirection_LastChild => self.child(tree.nodes.last()),
+			_ => Err(Error::OK),
+		}
+	}
+
+	fn GetRuntimeId(&self) -> Result<*mut SAFEARRAY> {
+		// The host provider supplies the runtime id of the window
+		Ok(std::ptr::null_mut())
+	}
+
+	fn BoundingRectangle(&self) -> Result<UiaRect> {
+		Ok(UiaRect::default())
+	}
+
+	fn GetEmbeddedFragmentRoots(&self) -> Result<*mut SAFEARRAY> {
+		Ok(std::ptr::null_mut())
+	}
+
+	fn SetFocus(&self) -> Result<()> {
+		Ok(())
+	}
+
+	fn FragmentRoot(&self) -> Result<IRawElementProviderFragmentRoot> {
+		Ok(RootProvider {
+			hwnd: self.hwnd,
+			shared: Arc::clone(&self.shared),
+		}
+		.into())
+	}
+}
+
+impl IRawElementProviderFragmentRoot_Impl for RootProvider {
+	fn ElementProviderFromPoint(&self, x: f64, y: f64) -> Result<IRawElementProviderFragment> {
+		let mut point = POINT {
+			x: x as i32,
+			y: y as i32,
+		};
+		unsafe { ScreenToClient(self.hwnd, &mut point) };
+		let tree = self.shared.tree.lock().unwrap();
+		match tree.node_at((point.x, point.y)) {
+			Some(node) => self.child(Some(node)),
+			// The point is in the window, but not on any of its widgets
+			None => Ok(RootProvider {
+				hwnd: self.hwnd,
+				shared: Arc::clone(&self.shared),
+			}
+			.into()),
+		}
+	}
+
+	fn GetFocus(&self) -> Result<IRawElementProviderFragment> {
+		let tree = self.shared.tree.lock().unwrap();
+		self.child(tree.focused())
+	}
+}
+
+/// Provider of a single [Node]. Implements the control pattern matching the node's role.
+#[implement(
+	IRawElementProviderSimple,
+	IRawElementProviderFragment,
+	IInvokeProvider,
+	ISelectionItemProvider,
+	IValueProvider
+)]
+#[derive(Clone)]
+struct NodeProvider {
+	hwnd: HWND,
+	shared: Arc<Shared>,
+	id: u32,
+}
+
+impl NodeProvider {
+	fn node(&self) -> Result<Node> {
+		let tree = self.shared.tree.lock().unwrap();
+		tree.get(self.id)
+			.cloned()
+			.ok_or_else(|| ELEMENT_NOT_AVAILABLE.into())
+	}
+
+	fn root(&self) -> RootProvider {
+		RootProvider {
+			hwnd: self.hwnd,
+			shared: Arc::clone(&self.shared),
+		}
+	}
+
+	/// Queues the node's action for the main loop.
+	fn activate(&self) -> Result<()> {
+		let request: Option<Request> = self.node()?.action;
+		if let Some(request) = request {
+			self.shared.requests.lock().unwrap().push(request);
+		}
+		Ok(())
+	}
+}
+
+impl IRawElementProviderSimple_Impl for NodeProvider {
+	fn ProviderOptions(&self) -> Result<ProviderOptions> {
+		Ok(ProviderOptions_ServerSideProvider)
+	}
+
+	fn GetPatternProvider(&self, pattern_id: UIA_PATTERN_ID) -> Result<IUnknown> {
+		let supported = match self.node()?.role {
+			Role::Button => UIA_InvokePatternId,
+			Role::ListItem => UIA_SelectionItemPatternId,
+			Role::Edit => UIA_ValuePatternId,
+			Role::Text => return Err(Error::OK),
+		};
+		if pattern_id != supported {
+			return Err(Error::OK);
+		}
+		Ok(self.clone().into())
+	}
+
+	fn GetPropertyValue(&self, property_id: UIA_PROPERTY_ID) -> Result<VARIANT> {
+		let node = self.node()?;
+		// The ids are constants named like types, which patterns would take for new bindings
+		let value = match property_id {
+			id if id == UIA_ControlTypePropertyId => {
+				// Control type ids are small positive numbers
+				variant_i32(control_type(node.role).0 as i32)
+			}
+			id if id == UIA_NamePropertyId => variant_bstr(&node.name),
+			id if id == UIA_AutomationIdPropertyId => variant_bstr(&node.id.to_string()),
+			id if id == UIA_IsKeyboardFocusablePropertyId => variant_bool(node.role != Role::Text),
+			id if id == UIA_HasKeyboardFocusPropertyId => variant_bool(node.focused),
+			id if id == UIA_IsEnabledPropertyId => variant_bool(true),
+			id if id == UIA_ValueValuePropertyId => match &node.value {
+				Some(value) => variant_bstr(value),
+				None => VARIANT::default(),
+			},
+			_ => VARIANT::default(),
+		};
+		Ok(value)
+	}
+
+	fn HostRawElementProvider(&self) -> Result<IRawElementProviderSimple> {
+		// Only the root is hosted by a window
+		Err(Error::OK)
+	}
+}
+
+impl IRawElementProviderFragment_Impl for NodeProvider {
+	fn Navigate(&self, direction: NavigateDirection) -> Result<IRawElementProviderFragment> {
+		let sibling = |offset: isize| {
+			let tree = self.shared.tree.lock().unwrap();
+			let idx = tree.nodes.iter().position(|node| node.id == self.id);
+			let sibling = idx
+				.and_then(|idx| idx.checked_add_signed(offset))
+				.and_then(|idx| tree.nodes.get(idx));
+			self.root().child(sibling)
+		};
+		match direction {
+			direction if direction == NavigateDirection_Parent => Ok(self.root().into()),
+			direction if direction == NavigateDirection_NextSibling => sibling(1),
+			direction if direction == NavigateDirection_PreviousSibling => sibling(-1),
+			// Leaf nodes
+			_ => Err(Error::OK),
+		}
+	}
+
+	fn GetRuntimeId(&self) -> Result<*mut SAFEARRAY> {
+		runtime_id(self.id)
+	}
+
+	fn BoundingRectangle(&self) -> Result<UiaRect> {
+		let bounds = self.node()?.bounds;
+		let mut origin = POINT {
+			x: bounds.x as i32,
+			y: bounds.y as i32,
+		};
+		unsafe { ClientToScreen(self.hwnd, &mut origin) };
+		Ok(UiaRect {
+			left: origin.x as f64,
+			top: origin.y as f64,
+			width: bounds.width as f64,
+			height: bounds.height as f64,
+		})
+	}
+
+	fn GetEmbeddedFragmentRoots(&self) -> Result<*mut SAFEARRAY> {
+		Ok(std::ptr::null_mut())
+	}
+
+	fn SetFocus(&self) -> Result<()> {
+		match self.node()?.role {
+			Role::ListItem => self.activate(),
+			_ => Ok(()),
+		}
+	}
+
+	fn FragmentRoot(&self) -> Result<IRawElementProviderFragmentRoot> {
+		Ok(self.root().into())
+	}
+}
+
+impl IInvokeProvider_Impl for NodeProvider {
+	fn Invoke(&self) -> Result<()> {
+		self.activate()
+	}
+}
+
+impl ISelectionItemProvider_Impl for NodeProvider {
+	fn Select(&self) -> Result<()> {
+		self.activate()
+	}
+
+	fn AddToSelection(&self) -> Result<()> {
+		Err(E_NOTIMPL.into())
+	}
+
+	fn RemoveFromSelection(&self) -> Result<()> {
+		Err(E_NOTIMPL.into())
+	}
+
+	fn IsSelected(&self) -> Result<BOOL> {
+		Ok(self.node()?.selected.into())
+	}
+
+	fn SelectionContainer(&self) -> Result<IRawElementProviderSimple> {
+		Ok(self.root().into())
+	}
+}
+
+impl IValueProvider_Impl for NodeProvider {
+	fn SetValue(&self, _value: &PCWSTR) -> Result<()> {
+		Err(E_NOTIMPL.into())
+	}
+
+	fn Value(&self) -> Result<BSTR> {
+		Ok(BSTR::from(self.node()?.value.unwrap_or_default()))
+	}
+
+	fn IsReadOnly(&self) -> Result<BOOL> {
+		Ok(true.into())
+	}
+}
+
+fn control_type(role: Role) -> UIA_CONTROLTYPE_ID {
+	match role {
+		Role::Button => UIA_ButtonControlTypeId,
+		Role::Edit => UIA_EditControlTypeId,
+		Role::ListItem => UIA_ListItemControlTypeId,
+		Role::Text => UIA_TextControlTypeId,
+	}
+}
+
+/// Runtime ids of fragments are made unique by prefixing them with [UiaAppendRuntimeId].
+fn runtime_id(id: u32) -> Result<*mut SAFEARRAY> {
+	unsafe {
+		let array = SafeArrayCreateVector(VT_I4, 0, 2);
+		if array.is_null() {
+			return Err(E_OUTOFMEMORY.into());
+		}
+		for (idx, value) in [UiaAppendRuntimeId as i32, id as i32].iter().enumerate() {
+			SafeArrayPutElement(array, &(idx as i32), (value as *const i32).cast())?;
+		}
+		Ok(array)
+	}
+}
+
+fn variant_i32(value: i32) -> VARIANT {
+	let mut variant = VARIANT::default();
+	unsafe {
+		let inner = &mut *variant.Anonymous.Anonymous;
+		inner.vt = VT_I4;
+		inner.Anonymous.lVal = value;
+	}
+	variant
+}
+
+fn variant_bool(value: bool) -> VARIANT {
+	let mut variant = VARIANT::default();
+	unsafe {
+		let inner = &mut *variant.Anonymous.Anonymous;
+		inner.vt = VT_BOOL;
+		inner.Anonymous.boolVal = VARIANT_BOOL(if value { -1 } else { 0 });
+	}
+	variant
+}
+
+fn variant_bstr(value: &str) -> VARIANT {
+	let mut variant = VARIANT::default();
+	unsafe {
+		let inner = &mut *variant.Anonymous.Anonymous;
+		inner.vt = VT_BSTR;
+		inner.Anonymous.bstrVal = ManuallyDrop::new(BSTR::from(value));
+	}
+	variant
+}
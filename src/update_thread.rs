@@ -0,0 +1,180 @@
+//! Thread keeping the state derived from the file system up to date: it notices changes of the
+//! configuration and asset files and of the drives, and measures selected files. It runs
+//! independently of the main loop, so file system access doesn't slow down frames, and a
+//! stalling Win32 call on the main thread (e.g. a slow `StretchDIBits` or a modal dialog) doesn't
+//! hold it up.
+//!
+//! Results are handed to the main thread through a double buffer: the thread fills the back
+//! [Snapshot] and swaps it to the front, where [UpdateThread::take_snapshot] picks it up once per
+//! frame.
+
+use std::mem;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{debug, error};
+
+use crate::profiler::{self, Stage};
+use crate::sidebar;
+use crate::watcher::FileWatcher;
+
+/// How long the thread sleeps between checks for changes when there are no requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the drives are checked for being plugged in or removed.
+const DRIVES_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What changed since the previous snapshot the main thread took.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+	/// Watched files that were modified, created or removed.
+	pub changed_files: Vec<PathBuf>,
+	/// Number and total size in bytes of the files of the last [UpdateThread::measure_selection].
+	pub selection_size: Option<(usize, u64)>,
+	/// Root folders of the drives, if they changed.
+	pub drives: Option<Vec<PathBuf>>,
+}
+
+impl Snapshot {
+	fn is_empty(&self) -> bool {
+		self.changed_files.is_empty() && self.selection_size.is_none() && self.drives.is_none()
+	}
+
+	/// Adds the newer `snapshot` to this one.
+	fn merge(&mut self, snapshot: Snapshot) {
+		for path in snapshot.changed_files {
+			if !self.changed_files.contains(&path) {
+				self.changed_files.push(path);
+			}
+		}
+		if snapshot.selection_size.is_some() {
+			self.selection_size = snapshot.selection_size;
+		}
+		if snapshot.drives.is_some() {
+			self.drives = snapshot.drives;
+		}
+	}
+}
+
+#[derive(Debug)]
+enum Message {
+	MeasureSelection(Vec<PathBuf>),
+	Stop,
+}
+
+#[derive(Debug)]
+pub struct UpdateThread {
+	messages: Sender<Message>,
+	/// Snapshot published by the thread that the main thread didn't take yet.
+	front: Arc<Mutex<Option<Snapshot>>>,
+	thread: Option<JoinHandle<()>>,
+}
+
+impl UpdateThread {
+	/// Starts the thread, watching `files` for changes.
+	pub fn spawn(files: impl IntoIterator<Item = PathBuf>) -> Self {
+		let files = FileWatcher::new(files);
+		let (messages, receiver) = mpsc::channel();
+		let front = Arc::new(Mutex::new(None));
+		let thread = {
+			let front = Arc::clone(&front);
+			std::thread::Builder::new()
+				.name("update".to_string())
+				.spawn(move || run(receiver, files, &front))
+				.map_err(|err| error!("Failed to start the update thread: {err}"))
+				.ok()
+		};
+		UpdateThread {
+			messages,
+			front,
+			thread,
+		}
+	}
+
+	/// Starts measuring the total size of `paths`, superseding unfinished measurements.
+	pub fn measure_selection(&self, paths: Vec<PathBuf>) {
+		self.send(Message::MeasureSelection(paths));
+	}
+
+	/// Changes published since the last call, None if there are none.
+	pub fn take_snapshot(&self) -> Option<Snapshot> {
+		self.front.lock().unwrap().take()
+	}
+
+	fn send(&self, message: Message) {
+		if self.messages.send(message).is_err() {
+			error!("The update thread is gone");
+		}
+	}
+}
+
+impl Drop for UpdateThread {
+	fn drop(&mut self) {
+		let _ = self.messages.send(Message::Stop);
+		if let Some(thread) = self.thread.take() {
+			if thread.join().is_err() {
+				error!("The update thread panicked");
+			}
+		}
+	}
+}
+
+fn run(messages: Receiver<Message>, mut files: FileWatcher, front: &Mutex<Option<Snapshot>>) {
+	let mut back = Snapshot::default();
+	let mut drives = sidebar::drives();
+	let mut drives_checked = Instant::now();
+	loop {
+		let first = match messages.recv_timeout(POLL_INTERVAL) {
+			Ok(message) => Some(message),
+			Err(RecvTimeoutError::Timeout) => None,
+			Err(RecvTimeoutError::Disconnected) => return,
+		};
+		// Only the latest selection is worth measuring
+		let mut selection = None;
+		for message in first.into_iter().chain(messages.try_iter()) {
+			match message {
+				Message::MeasureSelection(paths) => selection = Some(paths),
+				Message::Stop => return,
+			}
+		}
+
+		if let Some(paths) = selection {
+			back.selection_size = Some((paths.len(), total_size(&paths)));
+		}
+		back.changed_files = files.changed();
+		if drives_checked.elapsed() >= DRIVES_INTERVAL {
+			drives_checked = Instant::now();
+			let current = sidebar::drives();
+			if current != drives {
+				drives = current;
+				back.drives = Some(drives.clone());
+			}
+		}
+
+		if !back.is_empty() {
+			publish(front, mem::take(&mut back));
+		}
+	}
+}
+
+/// Swaps `snapshot` to the front, or adds it to the front one if the main thread didn't take
+/// that yet.
+fn publish(front: &Mutex<Option<Snapshot>>, snapshot: Snapshot) {
+	let mut front = front.lock().unwrap();
+	match &mut *front {
+		Some(unread) => unread.merge(snapshot),
+		None => *front = Some(snapshot),
+	}
+}
+
+fn total_size(paths: &[PathBuf]) -> u64 {
+	let _scope = profiler::scope(Stage::Fs);
+	debug!("Measuring {} selected files", paths.len());
+	paths
+		.iter()
+		.filter_map(|path| std::fs::metadata(path).ok())
+		.map(|metadata| metadata.len())
+		.sum()
+}
@@ -0,0 +1,280 @@
+//! Minimal vector path rasterizer. Paths are made of straight lines and quadratic/cubic Bézier
+//! curves and are filled using the non-zero winding rule with anti-aliased edges.
+
+use std::fmt::{Display, Formatter};
+
+/// Vertical samples taken per pixel row. Horizontal coverage is computed exactly.
+const SUBSAMPLES: usize = 4;
+/// Maximum distance (in pixels) between a curve and the line segments approximating it.
+const FLATTEN_TOLERANCE: f32 = 0.2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Segment {
+	MoveTo(f32, f32),
+	LineTo(f32, f32),
+	QuadTo(f32, f32, f32, f32),
+	CubicTo(f32, f32, f32, f32, f32, f32),
+	Close,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+	segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+	UnknownCommand(char),
+	/// A command is missing some of its coordinates.
+	MissingCoordinates(char),
+	InvalidNumber(String),
+}
+
+impl Display for ParseError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ParseError::UnknownCommand(c) => write!(f, "unknown path command `{c}`"),
+			ParseError::MissingCoordinates(c) => write!(f, "missing coordinates of command `{c}`"),
+			ParseError::InvalidNumber(s) => write!(f, "invalid number `{s}`"),
+		}
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+impl Path {
+	pub fn new() -> Self {
+		Path::default()
+	}
+
+	pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+		self.segments.push(Segment::MoveTo(x, y));
+		self
+	}
+
+	pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+		self.segments.push(Segment::LineTo(x, y));
+		self
+	}
+
+	/// Quadratic Bézier curve with control point `(cx, cy)`.
+	pub fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) -> &mut Self {
+		self.segments.push(Segment::QuadTo(cx, cy, x, y));
+		self
+	}
+
+	/// Cubic Bézier curve with control points `(c1x, c1y)` and `(c2x, c2y)`.
+	pub fn cubic_to(
+		&mut self,
+		c1x: f32,
+		c1y: f32,
+		c2x: f32,
+		c2y: f32,
+		x: f32,
+		y: f32,
+	) -> &mut Self {
+		self.segments
+			.push(Segment::CubicTo(c1x, c1y, c2x, c2y, x, y));
+		self
+	}
+
+	/// Closes the current contour with a line back to its starting point.
+	pub fn close(&mut self) -> &mut Self {
+		self.segments.push(Segment::Close);
+		self
+	}
+
+	/// Parses a subset of the SVG path syntax: absolute `M`, `L`, `Q`, `C` and `Z` commands with
+	/// whitespace or comma separated coordinates, e.g. `"M 4 4 L 20 4 Q 20 20 4 20 Z"`.
+	pub fn parse(data: &str) -> Result<Path, ParseError> {
+		let mut path = Path::new();
+		let mut tokens = data
+			.split(|c: char| c.is_whitespace() || c == ',')
+			.filter(|token| !token.is_empty())
+			.peekable();
+
+		while let Some(token) = tokens.next() {
+			let mut chars = token.chars();
+			let command = chars.next().unwrap();
+			// Allow commands glued to their first coordinate, e.g. `M4 4`
+			let glued = chars.as_str();
+			let arg_count = match command {
+				'M' | 'L' => 2,
+				'Q' => 4,
+				'C' => 6,
+				'Z' => 0,
+				_ => return Err(ParseError::UnknownCommand(command)),
+			};
+
+			let mut args = [0.0; 6];
+			for (idx, arg) in args.iter_mut().take(arg_count).enumerate() {
+				let number = match (idx, glued) {
+					(0, glued) if !glued.is_empty() => glued,
+					_ => tokens
+						.next()
+						.ok_or(ParseError::MissingCoordinates(command))?,
+				};
+				*arg = number
+					.parse()
+					.map_err(|_| ParseError::InvalidNumber(number.to_string()))?;
+			}
+
+			let [a, b, c, d, e, f] = args;
+			match command {
+				'M' => path.move_to(a, b),
+				'L' => path.line_to(a, b),
+				'Q' => path.quad_to(a, b, c, d),
+				'C' => path.cubic_to(a, b, c, d, e, f),
+				_ => path.close(),
+			};
+		}
+		Ok(path)
+	}
+
+	/// Fills the path into an ARGB `bitmap` of the given width, blending `color` (RGB) over the
+	/// existing pixels. Path coordinates are multiplied by `scale` first.
+	pub fn fill(&self, bitmap: &mut [u32], width: usize, color: u32, scale: f32) {
+		let height = bitmap.len() / width;
+		let edges = self.edges(scale);
+		let mut coverage = vec![0.0f32; width];
+		let mut crossings: Vec<(f32, i32)> = Vec::new();
+
+		for y in 0..height {
+			coverage.fill(0.0);
+			for sample in 0..SUBSAMPLES {
+				let sample_y = y as f32 + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+				crossings.clear();
+				for &((x0, y0), (x1, y1)) in &edges {
+					let (top, bottom, winding) = if y0 < y1 { (y0, y1, 1) } else { (y1, y0, -1) };
+					if sample_y < top || sample_y >= bottom {
+						continue;
+					}
+					let t = (sample_y - y0) / (y1 - y0);
+					crossings.push((x0 + t * (x1 - x0), winding));
+				}
+				crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+				let mut winding = 0;
+				for pair in crossings.windows(2) {
+					winding += pair[0].1;
+					if winding != 0 {
+						add_span(&mut coverage, pair[0].0, pair[1].0);
+					}
+				}
+			}
+
+			let row = &mut bitmap[y * width..(y + 1) * width];
+			for (pixel, &covered) in row.iter_mut().zip(&coverage) {
+				let alpha = (covered / SUBSAMPLES as f32).min(1.0);
+				if alpha > 0.0 {
+					*pixel = composite(*pixel, color, alpha);
+				}
+			}
+		}
+	}
+
+	/// Flattens the path into line segments, closing every contour.
+	fn edges(&self, scale: f32) -> Vec<((f32, f32), (f32, f32))> {
+		let mut edges = Vec::new();
+		let mut start = (0.0, 0.0);
+		let mut current = (0.0, 0.0);
+		let line_to = |edges: &mut Vec<_>, current: &mut (f32, f32), to: (f32, f32)| {
+			if *current != to {
+				edges.push((*current, to));
+			}
+			*current = to;
+		};
+
+		for segment in &self.segments {
+			match *segment {
+				Segment::MoveTo(x, y) => {
+					line_to(&mut edges, &mut current, start);
+					start = (x * scale, y * scale);
+					current = start;
+				}
+				Segment::LineTo(x, y) => line_to(&mut edges, &mut current, (x * scale, y * scale)),
+				Segment::QuadTo(cx, cy, x, y) => {
+					let p0 = current;
+					let p1 = (cx * scale, cy * scale);
+					let p2 = (x * scale, y * scale);
+					let steps = curve_steps(&[p0, p1, p2]);
+					for step in 1..=steps {
+						let t = step as f32 / steps as f32;
+						let mt = 1.0 - t;
+						let point = (
+							mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+							mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+						);
+						line_to(&mut edges, &mut current, point);
+					}
+				}
+				Segment::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+					let p0 = current;
+					let p1 = (c1x * scale, c1y * scale);
+					let p2 = (c2x * scale, c2y * scale);
+					let p3 = (x * scale, y * scale);
+					let steps = curve_steps(&[p0, p1, p2, p3]);
+					for step in 1..=steps {
+						let t = step as f32 / steps as f32;
+						let mt = 1.0 - t;
+						let (a, b, c, d) =
+							(mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+						let point = (
+							a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+							a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+						);
+						line_to(&mut edges, &mut current, point);
+					}
+				}
+				Segment::Close => line_to(&mut edges, &mut current, start),
+			}
+		}
+		line_to(&mut edges, &mut current, start);
+
+		// Horizontal edges never cross a scanline
+		edges.retain(|(from, to)| from.1 != to.1);
+		edges
+	}
+}
+
+/// Number of line segments needed to approximate a curve with the given control polygon.
+fn curve_steps(points: &[(f32, f32)]) -> usize {
+	let length: f32 = points
+		.windows(2)
+		.map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+		.sum();
+	((length / FLATTEN_TOLERANCE).sqrt().ceil() as usize).clamp(1, 64)
+}
+
+/// Adds the horizontal coverage of the span `from..to` to the pixels it touches.
+fn add_span(coverage: &mut [f32], from: f32, to: f32) {
+	let width = coverage.len() as f32;
+	let (from, to) = (from.clamp(0.0, width), to.clamp(0.0, width));
+	if from >= to {
+		return;
+	}
+	let first = from as usize;
+	let last = (to as usize).min(coverage.len() - 1);
+	if first == last {
+		coverage[first] += to - from;
+		return;
+	}
+	coverage[first] += (first + 1) as f32 - from;
+	for pixel in &mut coverage[first + 1..last] {
+		*pixel += 1.0;
+	}
+	coverage[last] += to - last as f32;
+}
+
+/// Composites `color` with opacity `alpha` over an ARGB pixel (non-premultiplied).
+fn composite(dst: u32, color: u32, alpha: f32) -> u32 {
+	let dst_alpha = ((dst >> 24) & 0xFF) as f32 / 255.0;
+	let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+	let channel = |shift: u32| {
+		let src = ((color >> shift) & 0xFF) as f32;
+		let dst = ((dst >> shift) & 0xFF) as f32;
+		let value = (src * alpha + dst * dst_alpha * (1.0 - alpha)) / out_alpha;
+		(value.round() as u32).min(0xFF) << shift
+	};
+	((out_alpha * 255.0).round() as u32) << 24 | channel(16) | channel(8) | channel(0)
+}
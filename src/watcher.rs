@@ -0,0 +1,302 @@
+//! Watching for changes while the explorer runs:
+//!
+//! - [FileWatcher] polls files, for reloading configuration and assets
+//! - [DirectoryWatcher] waits for changes of a directory's entries (or of a whole tree) with
+//!   `ReadDirectoryChangesW` on a thread, and reports the folders that changed. The directories
+//!   of both panes are watched to reload their listings, and the roots of search indexes to keep
+//!   them up to date
+
+use std::{
+	ffi::OsString,
+	io,
+	os::windows::ffi::OsStringExt,
+	path::{Path, PathBuf},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant, SystemTime},
+};
+
+use log::{debug, warn};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0};
+use windows::Win32::Storage::FileSystem::{
+	CreateFileW, ReadDirectoryChangesW, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OVERLAPPED,
+	FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE, FILE_NOTIFY_CHANGE_ATTRIBUTES,
+	FILE_NOTIFY_CHANGE_DIR_NAME, FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE,
+	FILE_NOTIFY_CHANGE_SIZE, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows::Win32::System::Threading::{
+	CreateEventW, ResetEvent, SetEvent, WaitForMultipleObjects, WaitForSingleObject, INFINITE,
+};
+use windows::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+
+use crate::profiler::{self, Stage};
+use crate::string::WideString;
+
+/// How often the files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Notices when watched files are modified, created or removed.
+#[derive(Debug)]
+pub struct FileWatcher {
+	/// Watched files with their modification time when last checked.
+	files: Vec<(PathBuf, Option<SystemTime>)>,
+	last_check: Instant,
+}
+
+impl FileWatcher {
+	pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+		FileWatcher {
+			files: paths
+				.into_iter()
+				.map(|path| {
+					let modified = modified_time(&path);
+					(path, modified)
+				})
+				.collect(),
+			last_check: Instant::now(),
+		}
+	}
+
+	/// Files that changed since the last call, checked at most every [WATCH_INTERVAL]. Called
+	/// repeatedly by the [update thread](crate::update_thread).
+	pub fn changed(&mut self) -> Vec<PathBuf> {
+		if self.last_check.elapsed() < WATCH_INTERVAL {
+			return Vec::new();
+		}
+		self.last_check = Instant::now();
+		let _scope = profiler::scope(Stage::Fs);
+		let mut changed = Vec::new();
+		for (path, last_modified) in &mut self.files {
+			let modified = modified_time(path);
+			if modified != *last_modified {
+				*last_modified = modified;
+				changed.push(path.clone());
+			}
+		}
+		changed
+	}
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path).ok()?.modified().ok()
+}
+
+/// Changes that show in the listing: entries created, removed or renamed, and the details of the
+/// list view.
+const FILTER: FILE_NOTIFY_CHANGE = FILE_NOTIFY_CHANGE(
+	FILE_NOTIFY_CHANGE_FILE_NAME.0
+		| FILE_NOTIFY_CHANGE_DIR_NAME.0
+		| FILE_NOTIFY_CHANGE_ATTRIBUTES.0
+		| FILE_NOTIFY_CHANGE_SIZE.0
+		| FILE_NOTIFY_CHANGE_LAST_WRITE.0,
+);
+const BUFFER_SIZE: usize = 16 * 1024;
+/// A burst of changes, like a copy of many files, is reported at most this often. Changes in
+/// between are buffered by the system and reported together.
+const POST_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What a [DirectoryWatcher] reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changes {
+	/// Entries of these folders changed.
+	In(Vec<PathBuf>),
+	/// There were too many changes to tell which folders they were in.
+	Unknown,
+	/// Watching failed, e.g. the directory was removed. Nothing is reported after.
+	Failed,
+}
+
+#[derive(Debug)]
+pub struct DirectoryWatcher {
+	dir: PathBuf,
+	handle: HANDLE,
+	/// Signalled to stop the thread, which waits for it along with the changes.
+	stop: HANDLE,
+	thread: Option<JoinHandle<()>>,
+}
+
+impl DirectoryWatcher {
+	/// Starts watching the entries of `dir`, and with `recursive` those of every folder below it.
+	/// `on_change` is called on the watcher's thread with the [Changes]. Watching stops once it
+	/// returns false.
+	pub fn start(
+		dir: &Path,
+		recursive: bool,
+		on_change: impl FnMut(Changes) -> bool + Send + 'static,
+	) -> io::Result<Self> {
+		let path = WideString::from_os_str(dir);
+		let handle = unsafe {
+			CreateFileW(
+				path.as_pcwstr(),
+				FILE_LIST_DIRECTORY.0,
+				// Without delete sharing, the directory couldn't be renamed or removed while watched
+				FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+				None,
+				OPEN_EXISTING,
+				// Backup semantics are required to open directories
+				FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+				HANDLE::default(),
+			)
+		}?;
+		let stop = match unsafe { CreateEventW(None, true, false, PCWSTR::null()) } {
+			Ok(stop) => stop,
+			Err(err) => {
+				unsafe { CloseHandle(handle) };
+				return Err(err.into());
+			}
+		};
+		let thread = {
+			let dir = dir.to_path_buf();
+			thread::Builder::new()
+				.name("directory watcher".to_string())
+				.spawn(move || watch(handle, &dir, recursive, stop, on_change))
+		};
+		let thread = match thread {
+			Ok(thread) => thread,
+			Err(err) => {
+				unsafe {
+					CloseHandle(handle);
+					CloseHandle(stop);
+				}
+				return Err(err);
+			}
+		};
+		debug!("Watching {}", dir.display());
+		Ok(DirectoryWatcher {
+			dir: dir.to_path_buf(),
+			handle,
+			stop,
+			thread: Some(thread),
+		})
+	}
+
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Whether watching stopped on its own, after [Changes::Failed] or `on_change` returned false.
+	pub fn is_finished(&self) -> bool {
+		self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+	}
+}
+
+impl Drop for DirectoryWatcher {
+	fn drop(&mut self) {
+		unsafe { SetEvent(self.stop) };
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+		unsafe {
+			CloseHandle(self.handle);
+			CloseHandle(self.stop);
+		}
+	}
+}
+
+fn watch(
+	dir_handle: HANDLE,
+	dir: &Path,
+	recursive: bool,
+	stop: HANDLE,
+	mut on_change: impl FnMut(Changes) -> bool,
+) {
+	let read_done = match unsafe { CreateEventW(None, true, false, PCWSTR::null()) } {
+		Ok(event) => event,
+		Err(err) => {
+			warn!("Failed to watch {}: {err}", dir.display());
+			return;
+		}
+	};
+	let mut overlapped = OVERLAPPED {
+		hEvent: read_done,
+		..Default::default()
+	};
+	// DWORD aligned, as ReadDirectoryChangesW requires
+	let mut buffer = vec![0u32; BUFFER_SIZE / 4];
+	loop {
+		unsafe { ResetEvent(read_done) };
+		let ok = unsafe {
+			ReadDirectoryChangesW(
+				dir_handle,
+				buffer.as_mut_ptr().cast(),
+				BUFFER_SIZE as u32,
+				recursive,
+				FILTER,
+				None,
+				Some(&mut overlapped),
+				None,
+			)
+		};
+		let mut returned = 0;
+		let ok = ok.as_bool() && {
+			let signalled = unsafe { WaitForMultipleObjects(&[read_done, stop], false, INFINITE) };
+			if signalled != WAIT_OBJECT_0 {
+				// The buffer has to outlive the read
+				unsafe {
+					CancelIoEx(dir_handle, Some(&overlapped));
+					GetOverlappedResult(dir_handle, &overlapped, &mut returned, true);
+				}
+				break;
+			}
+			unsafe { GetOverlappedResult(dir_handle, &overlapped, &mut returned, false) }.as_bool()
+		};
+		if !ok {
+			// E.g. the directory was removed, which the reloaded listing reports
+			warn!(
+				"Stopped watching {}: {}",
+				dir.display(),
+				io::Error::last_os_error()
+			);
+			on_change(Changes::Failed);
+			break;
+		}
+		// Nothing was returned if the changes overflowed the buffer
+		let changes = match returned {
+			0 => Changes::Unknown,
+			_ => {
+				let bytes = unsafe {
+					std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), returned as usize)
+				};
+				Changes::In(changed_dirs(dir, bytes))
+			}
+		};
+		if !on_change(changes) {
+			break;
+		}
+		if unsafe { WaitForSingleObject(stop, POST_INTERVAL.as_millis() as u32) } == WAIT_OBJECT_0 {
+			break;
+		}
+	}
+	unsafe { CloseHandle(read_done) };
+}
+
+/// Folders whose entries changed, from the FILE_NOTIFY_INFORMATION records in `bytes`.
+fn changed_dirs(dir: &Path, bytes: &[u8]) -> Vec<PathBuf> {
+	let u32_at = |offset: usize| {
+		bytes
+			.get(offset..offset + 4)
+			.map(|field| u32::from_le_bytes(field.try_into().unwrap()) as usize)
+	};
+	let mut dirs: Vec<PathBuf> = Vec::new();
+	let mut offset = 0;
+	// Next entry offset, action, file name length and the name, relative to `dir`
+	while let (Some(next), Some(name_len)) = (u32_at(offset), u32_at(offset + 8)) {
+		let Some(name) = bytes.get(offset + 12..offset + 12 + name_len) else {
+			break;
+		};
+		let name: Vec<u16> = name
+			.chunks_exact(2)
+			.map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+			.collect();
+		let path = dir.join(OsString::from_wide(&name));
+		let parent = path.parent().unwrap_or(dir);
+		if !dirs.iter().any(|known| known == parent) {
+			dirs.push(parent.to_path_buf());
+		}
+		if next == 0 {
+			break;
+		}
+		offset += next;
+	}
+	dirs
+}
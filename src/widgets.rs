@@ -0,0 +1,365 @@
+//! Widgets shared by the views of a directory.
+//!
+//! [FileList] draws directory entries as rows, one column per detail, and moves its selection
+//! with the keyboard. Only the rows intersecting the viewport are drawn, so that folders with
+//! many thousands of entries render as fast as small ones.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use windows::Win32::Foundation::{FILETIME, SYSTEMTIME};
+use windows::Win32::System::Time::{FileTimeToSystemTime, SystemTimeToTzSpecificLocalTime};
+
+use crate::draw::{draw_rectangle, draw_text, draw_texture_clipped, Rect, Texture};
+use crate::font;
+use crate::fs::{Entry, SortKey};
+use crate::icons;
+use crate::key::Key;
+use crate::selection::Selection;
+use crate::shell_icons::ShellIcons;
+use crate::status_bar::format_size;
+use crate::window::{BitmapData, Keyboard};
+
+pub const ICON_SIZE: usize = 16;
+pub const ROW_PADDING: usize = 3;
+pub const ROW_HEIGHT: usize = font::LINE_HEIGHT + 2 * ROW_PADDING;
+pub const PADDING: usize = 8;
+/// Columns right of the name with their width, left out from the right when the name would get
+/// narrower than [MIN_NAME_WIDTH].
+const DETAIL_COLUMNS: [(SortKey, usize); 3] = [
+	(SortKey::Size, 9 * font::ADVANCE),
+	(SortKey::Modified, 10 * font::ADVANCE),
+	(SortKey::Type, 6 * font::ADVANCE),
+];
+const MIN_NAME_WIDTH: usize = 16 * font::ADVANCE;
+
+const SELECTED_COLOR: u32 = 0x504945;
+const TEXT_COLOR: u32 = 0xebdbb2;
+const DIR_TEXT_COLOR: u32 = 0xfabd2f;
+const DETAIL_TEXT_COLOR: u32 = 0xa89984;
+
+/// A column of the list, in pixels from the left edge of the window.
+#[derive(Debug, Copy, Clone)]
+pub struct Column {
+	pub key: SortKey,
+	pub x: usize,
+	pub width: usize,
+}
+
+/// Columns fitting in `viewport`, the name first. The name takes the space the others leave.
+pub fn columns(viewport: Rect) -> Vec<Column> {
+	let mut shown = 0;
+	let mut details_width = 0;
+	for (_, width) in DETAIL_COLUMNS {
+		if viewport.width < MIN_NAME_WIDTH + details_width + width + PADDING {
+			break;
+		}
+		details_width += width + PADDING;
+		shown += 1;
+	}
+	let name_width = viewport.width - details_width;
+	let mut columns = vec![Column {
+		key: SortKey::Name,
+		x: viewport.x,
+		width: name_width,
+	}];
+	let mut x = viewport.x + name_width;
+	for (key, width) in DETAIL_COLUMNS.into_iter().take(shown) {
+		columns.push(Column { key, x, width });
+		x += width + PADDING;
+	}
+	columns
+}
+
+/// Rows of directory entries with a scroll offset and a [Selection], whose cursor is the
+/// highlighted row.
+#[derive(Debug)]
+pub struct FileList {
+	entries: Vec<Entry>,
+	selection: Selection,
+	/// Vertical scroll offset in pixels.
+	scroll: usize,
+	shell_icons: ShellIcons,
+	/// Shown for entries without a shell icon.
+	folder_icon: Texture,
+	file_icon: Texture,
+}
+
+impl Default for FileList {
+	fn default() -> Self {
+		FileList {
+			entries: Vec::new(),
+			selection: Selection::default(),
+			scroll: 0,
+			shell_icons: ShellIcons::new(ICON_SIZE),
+			folder_icon: icons::FOLDER.rasterize(ICON_SIZE),
+			file_icon: icons::FILE.rasterize(ICON_SIZE),
+		}
+	}
+}
+
+impl FileList {
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	/// The entries to change in place, e.g. to sort them. The selection is left as it is, see
+	/// [FileList::follow_selection] to keep it on the same entries.
+	pub fn entries_mut(&mut self) -> &mut Vec<Entry> {
+		&mut self.entries
+	}
+
+	pub fn selection(&self) -> &Selection {
+		&self.selection
+	}
+
+	pub fn selection_mut(&mut self) -> &mut Selection {
+		&mut self.selection
+	}
+
+	/// Paths of the entries, to find the selected ones again with [FileList::follow_selection]
+	/// after the entries changed.
+	pub fn paths(&self) -> Vec<PathBuf> {
+		self.entries
+			.iter()
+			.map(|entry| entry.path.clone())
+			.collect()
+	}
+
+	/// Keeps the selected entries selected after the entries changed from those at `old` paths,
+	/// e.g. got sorted or filtered. Entries no longer shown are deselected.
+	pub fn follow_selection(&mut self, old: &[PathBuf]) {
+		let new: HashMap<&Path, usize> = self
+			.entries
+			.iter()
+			.enumerate()
+			.map(|(idx, entry)| (entry.path.as_path(), idx))
+			.collect();
+		self.selection.remap(|idx| {
+			old.get(idx)
+				.and_then(|path| new.get(path.as_path()).copied())
+		});
+	}
+
+	pub fn scroll(&self) -> usize {
+		self.scroll
+	}
+
+	/// Scrolls back to the first row.
+	pub fn scroll_to_top(&mut self) {
+		self.scroll = 0;
+	}
+
+	/// Moves the selection with the arrow keys, Page Up/Down, Home and End, keeping it in view of
+	/// rows `height` pixels high. Shift extends the selection, Ctrl+A selects every entry. Returns
+	/// the folder at the cursor when Enter was pressed.
+	pub fn handle_key(&mut self, keyboard: &Keyboard, height: usize) -> Option<PathBuf> {
+		if self.entries.is_empty() {
+			return None;
+		}
+		let page = (height / ROW_HEIGHT).max(1);
+		let last = self.entries.len() - 1;
+		let mut target = None;
+		let moves = [
+			(Key::Up, -1, page),
+			(Key::Down, 1, page),
+			(Key::Prior, -(page as isize), 1),
+			(Key::Next, page as isize, 1),
+			(Key::GamepadDpadUp, -1, page),
+			(Key::GamepadDpadDown, 1, page),
+		];
+		for (key, delta, page) in moves {
+			let steps = keyboard.steps(key, page);
+			if steps > 0 {
+				let current = target.or(self.selection.cursor()).unwrap_or(0) as isize;
+				target = Some((current + delta * steps as isize).clamp(0, last as isize) as usize);
+			}
+		}
+		if keyboard.was_pressed(Key::Home) {
+			target = Some(0);
+		}
+		if keyboard.was_pressed(Key::End) {
+			target = Some(last);
+		}
+		if let Some(target) = target {
+			if keyboard.is_pressed(Key::Shift) {
+				self.selection.select_range_to(target);
+			} else {
+				self.selection.select_only(target);
+			}
+			self.scroll_to(target, height);
+		}
+		if keyboard.was_pressed(Key::A) && keyboard.is_pressed(Key::Control) {
+			self.selection.select_all(self.entries.len());
+		}
+		if keyboard.was_pressed(Key::Return) || keyboard.was_pressed(Key::GamepadA) {
+			return self.folder(self.selection.cursor()?);
+		}
+		None
+	}
+
+	/// Scrolls by `delta` pixels, down if positive, keeping the last row at the bottom of rows
+	/// `height` pixels high at most.
+	pub fn handle_scroll(&mut self, delta: isize, height: usize) {
+		let max_scroll = (self.entries.len() * ROW_HEIGHT).saturating_sub(height);
+		self.scroll = self.scroll.saturating_add_signed(delta).min(max_scroll);
+	}
+
+	/// Selects just the entry at `idx` and scrolls it into view of rows `height` pixels high.
+	pub fn select_only(&mut self, idx: usize, height: usize) {
+		if idx >= self.entries.len() {
+			return;
+		}
+		self.selection.select_only(idx);
+		self.scroll_to(idx, height);
+	}
+
+	/// Scrolls as little as needed for the row of `idx` to be in view of rows `height` pixels
+	/// high.
+	pub fn scroll_to(&mut self, idx: usize, height: usize) {
+		let row_top = idx * ROW_HEIGHT;
+		let row_bottom = row_top + ROW_HEIGHT;
+		if row_top < self.scroll {
+			self.scroll = row_top;
+		} else if row_bottom > self.scroll + height {
+			self.scroll = row_bottom.saturating_sub(height);
+		}
+	}
+
+	/// Index of the row at `y` pixels from the top of the content, which may be past the last one.
+	pub fn row_at(&self, y: usize) -> usize {
+		y / ROW_HEIGHT
+	}
+
+	/// Rows intersecting `rect`, in content coordinates.
+	pub fn rows_in(&self, rect: Rect) -> Range<usize> {
+		let first = rect.y / ROW_HEIGHT;
+		let last = (rect.y + rect.height) / ROW_HEIGHT + 1;
+		first.min(self.entries.len())..last.min(self.entries.len())
+	}
+
+	/// The folder at `idx`, None if it's a file.
+	pub fn folder(&self, idx: usize) -> Option<PathBuf> {
+		let entry = &self.entries[idx];
+		entry.is_dir().then(|| entry.path.clone())
+	}
+
+	/// Entries at least partly visible in `viewport`, with their rows clipped to it.
+	pub fn visible_rows(&self, viewport: Rect) -> impl Iterator<Item = (usize, Rect)> + '_ {
+		self.visible_in(viewport.height).map(move |idx| {
+			let top = (idx * ROW_HEIGHT).max(self.scroll) - self.scroll;
+			let bottom = ((idx + 1) * ROW_HEIGHT - self.scroll).min(viewport.height);
+			(idx, Rect::new(viewport.x, viewport.y + top, viewport.width, bottom - top))
+		})
+	}
+
+	/// Indices of the rows intersecting a viewport `height` pixels high.
+	fn visible_in(&self, height: usize) -> Range<usize> {
+		let first = self.scroll / ROW_HEIGHT;
+		let last = (self.scroll + height).div_ceil(ROW_HEIGHT);
+		first.min(self.entries.len())..last.min(self.entries.len())
+	}
+
+	/// Draws the rows intersecting `viewport` with the details of `columns`, see [columns].
+	pub fn render(&self, bitmap_data: BitmapData, viewport: Rect, columns: &[Column]) {
+		for idx in self.visible_in(viewport.height) {
+			let entry = &self.entries[idx];
+			let y = (viewport.y + idx * ROW_HEIGHT) as isize - self.scroll as isize;
+
+			if self.selection.is_selected(idx) && y >= viewport.y as isize {
+				let height = ROW_HEIGHT.min(viewport.y + viewport.height - y as usize);
+				draw_rectangle(
+					bitmap_data,
+					(viewport.x, y as usize),
+					(viewport.width, height),
+					SELECTED_COLOR,
+				);
+			}
+
+			let shell_icon = self.shell_icons.icon(entry);
+			let icon = match (&shell_icon, entry.is_dir()) {
+				(Some(icon), _) => icon,
+				(None, true) => &self.folder_icon,
+				(None, false) => &self.file_icon,
+			};
+			// Centered, shell icons may be smaller or larger than the built-in ones
+			let icon_x = viewport.x + PADDING + ICON_SIZE.saturating_sub(icon.width()) / 2;
+			let icon_y = y + ROW_HEIGHT.saturating_sub(icon.height()) as isize / 2;
+			draw_texture_clipped(bitmap_data, icon, (icon_x as isize, icon_y), viewport);
+
+			// Text can't be clipped, rows only partly visible go without it
+			let text_y = y + ROW_PADDING as isize;
+			if text_y < viewport.y as isize
+				|| text_y as usize + font::LINE_HEIGHT > viewport.y + viewport.height
+			{
+				continue;
+			}
+			let text_y = text_y as usize;
+			let text_x = viewport.x + 2 * PADDING + ICON_SIZE;
+			let name_column = columns[0];
+			let max_width = (name_column.x + name_column.width).saturating_sub(text_x + PADDING);
+			let color = match entry.is_dir() {
+				true => DIR_TEXT_COLOR,
+				false => TEXT_COLOR,
+			};
+			draw_text(
+				bitmap_data,
+				font::ui(),
+				&font::ui().truncate(&entry.name, max_width),
+				(text_x, text_y),
+				color,
+			);
+			for column in &columns[1..] {
+				if let Some(text) = detail(entry, column.key) {
+					draw_text(
+						bitmap_data,
+						font::ui(),
+						&font::ui().truncate(&text, column.width),
+						(column.x, text_y),
+						DETAIL_TEXT_COLOR,
+					);
+				}
+			}
+		}
+	}
+}
+
+/// What the column of `key` shows for `entry`, None if it has nothing to show.
+fn detail(entry: &Entry, key: SortKey) -> Option<String> {
+	match key {
+		SortKey::Name => Some(entry.name.clone()),
+		SortKey::Size => (!entry.is_dir()).then(|| format_size(entry.size)),
+		SortKey::Modified => entry.modified.and_then(local_date),
+		SortKey::Type => {
+			let extension = entry.extension();
+			(!extension.is_empty()).then(|| extension.to_uppercase())
+		}
+	}
+}
+
+/// The date of `time` in the local time zone, with the daylight saving time in effect at that date
+/// like Explorer shows it. None if it's out of the range Windows can convert.
+fn local_date(time: SystemTime) -> Option<String> {
+	// FILETIME counts 100 ns intervals since 1601-01-01
+	const UNIX_EPOCH_INTERVALS: u64 = 116_444_736_000_000_000;
+	let since_epoch = time.duration_since(UNIX_EPOCH).ok()?;
+	let intervals = u64::try_from(since_epoch.as_nanos() / 100).ok()? + UNIX_EPOCH_INTERVALS;
+	let file_time = FILETIME {
+		dwLowDateTime: intervals as u32,
+		dwHighDateTime: (intervals >> 32) as u32,
+	};
+	let mut utc = SYSTEMTIME::default();
+	let mut local = SYSTEMTIME::default();
+	unsafe {
+		FileTimeToSystemTime(&file_time, &mut utc).ok().ok()?;
+		SystemTimeToTzSpecificLocalTime(None, &utc, &mut local)
+			.ok()
+			.ok()?;
+	}
+	Some(format!(
+		"{:04}-{:02}-{:02}",
+		local.wYear, local.wMonth, local.wDay
+	))
+}
@@ -1,57 +1,175 @@
 use std::{
-	collections::HashMap,
 	ffi::c_void,
 	io,
 	mem::{self, MaybeUninit},
-	ops::ControlFlow,
-	slice, usize,
+	path::Path,
+	slice,
+	time::{Duration, Instant},
 };
 
 use log::{debug, error, info};
 use windows::{
 	core::PCWSTR,
 	Win32::{
-		Foundation::{GetLastError, HWND, LPARAM, LRESULT, RECT, WPARAM},
+		Foundation::{GetLastError, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
 		Graphics::Gdi::{
-			BeginPaint, EndPaint, GetDC, ReleaseDC, StretchDIBits, BITMAPINFO, BITMAPINFOHEADER,
-			BI_RGB, DIB_RGB_COLORS, GDI_ERROR, HBRUSH, HDC, PAINTSTRUCT, RGBQUAD, SRCCOPY,
+			BeginPaint, EndPaint, GetDC, ReleaseDC, ScreenToClient, StretchDIBits, BITMAPINFO,
+			BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, GDI_ERROR, HBRUSH, HDC, PAINTSTRUCT, RGBQUAD,
+			SRCCOPY,
 		},
 		System::{
 			LibraryLoader::GetModuleHandleW,
 			Memory::{VirtualAlloc, VirtualFree, MEM_COMMIT, MEM_RELEASE, PAGE_READWRITE},
 		},
+		UI::Input::KeyboardAndMouse::{
+			RegisterHotKey, UnregisterHotKey, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT,
+		},
+		UI::Input::Pointer::GetPointerType,
 		UI::WindowsAndMessaging::{
 			CreateWindowExW, DefWindowProcW, DispatchMessageW, GetClientRect, GetWindowLongPtrW,
-			PeekMessageW, PostQuitMessage, RegisterClassW, SetWindowLongPtrW, TranslateMessage,
-			CREATESTRUCTW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HCURSOR, HICON,
-			HMENU, MSG, PM_REMOVE, WINDOW_EX_STYLE, WM_ACTIVATEAPP, WM_CLOSE, WM_CREATE,
-			WM_DESTROY, WM_KEYDOWN, WM_KEYUP, WM_PAINT, WM_QUIT, WM_SIZE, WNDCLASSW,
-			WS_OVERLAPPEDWINDOW, WS_VISIBLE,
+			IsIconic, PeekMessageW, PostMessageW, RegisterClassW, SetForegroundWindow,
+			SetWindowLongPtrW, ShowWindow, SystemParametersInfoW, TranslateMessage, CREATESTRUCTW,
+			CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HCURSOR, HICON, HMENU, MSG,
+			PM_REMOVE, POINTER_INPUT_TYPE, PT_TOUCH, SPI_GETKEYBOARDDELAY, SPI_GETKEYBOARDSPEED,
+			SW_RESTORE, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, WINDOW_EX_STYLE, WM_ACTIVATEAPP,
+			WM_APP, WM_CHAR, WM_CLOSE, WM_CREATE, WM_DESTROY, WM_GETOBJECT, WM_HOTKEY, WM_KEYDOWN,
+			WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
+			WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_PAINT, WM_POINTERDOWN, WM_POINTERUP,
+			WM_POINTERUPDATE, WM_QUIT, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SIZE, WM_SYSKEYDOWN,
+			WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
 		},
 	},
 };
 
-use crate::key::Key;
-use crate::string::WindowsStrings;
+use crate::accessibility::Accessibility;
+use crate::key::{Input, Key, MouseButton, ScanCode, WheelDirection, NUMPAD_FLAG};
+use crate::recording::{Playback, Recorder};
+use crate::shortcut::{Modifiers, Shortcut};
+use crate::string::WideString;
+use crate::touch::Touch;
 
 pub struct Window {
 	window: HWND,
 
-	#[allow(dead_code)]
-	classname: Vec<u16>,
-	#[allow(dead_code)]
-	window_title: Vec<u16>,
-
 	pub window_data: Box<WindowData>,
+	/// Registered with [Window::register_global_hotkey].
+	hotkeys: Vec<HotkeyId>,
 }
 
+/// Identifies a global hotkey, see [Window::register_global_hotkey].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HotkeyId(pub i32);
+
 #[derive(Default)]
 pub struct WindowData {
 	pub bitmap_data: BitmapData,
 	pub keyboard: Keyboard,
+	pub mouse: Mouse,
+	pub accessibility: Accessibility,
+
+	/// Events since the last [Window::poll_events] call.
+	events: Vec<Event>,
+	recorder: Option<Recorder>,
+	playback: Option<Playback>,
+	touch: Touch,
+}
+
+/// What happened to the window since the last frame, see [Window::poll_events]. The [Keyboard]
+/// and [Mouse] of the window data hold the resulting input state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// `modifiers` are the ones held before the key went down. Not sent for repeats.
+	KeyDown {
+		key: Key,
+		modifiers: Modifiers,
+	},
+	KeyUp {
+		key: Key,
+	},
+	/// A printable character was typed.
+	Char(char),
+	/// The client area is now `width`x`height` pixels, and so is the bitmap.
+	Resize {
+		width: i32,
+		height: i32,
+	},
+	/// The user asked to close the window.
+	Close,
+	/// Coordinates are in client area pixels.
+	MouseMove {
+		x: i32,
+		y: i32,
+	},
+	MouseButton {
+		button: MouseButton,
+		pressed: bool,
+		x: i32,
+		y: i32,
+	},
+	/// `delta` is in the units of WM_MOUSEWHEEL, [WHEEL_DELTA] per notch. Positive values are
+	/// away from the user, or to the right.
+	MouseWheel {
+		delta: i32,
+		horizontal: bool,
+	},
+	/// A global hotkey was pressed, see [Window::register_global_hotkey].
+	Hotkey(HotkeyId),
+	/// Posted with [EventPoster::directory_changed].
+	DirectoryChanged,
+}
+
+/// Posted by [EventPoster::directory_changed].
+const WM_DIRECTORY_CHANGED: u32 = WM_APP;
+
+/// Posts events to the window's event loop from other threads, see [Window::event_poster].
+#[derive(Debug, Copy, Clone)]
+pub struct EventPoster(HWND);
+
+impl EventPoster {
+	/// Reports [Event::DirectoryChanged] on the next [Window::poll_events]. Returns false if the
+	/// window is gone.
+	pub fn directory_changed(&self) -> bool {
+		unsafe { PostMessageW(self.0, WM_DIRECTORY_CHANGED, WPARAM(0), LPARAM(0)) }.as_bool()
+	}
+}
 
-	#[allow(clippy::type_complexity)]
-	key_handlers: HashMap<Key, Box<dyn Fn(&mut BitmapData, &mut Keyboard)>>,
+/// Input from the window messages, in the form it's applied to the [Keyboard] and [Mouse]. Can be
+/// recorded and played back, see [crate::recording].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+	KeyDown {
+		key: u16,
+		scancode: ScanCode,
+	},
+	KeyUp {
+		key: u16,
+		scancode: ScanCode,
+	},
+	/// A printable character was typed.
+	Char(char),
+	/// Coordinates are in client area pixels.
+	MouseMove {
+		x: i32,
+		y: i32,
+	},
+	MouseDown {
+		button: MouseButton,
+		x: i32,
+		y: i32,
+	},
+	MouseUp {
+		button: MouseButton,
+		x: i32,
+		y: i32,
+	},
+	/// `delta` is in the units of WM_MOUSEWHEEL, 120 per notch.
+	Wheel {
+		delta: i32,
+		horizontal: bool,
+	},
+	FocusLost,
+	/// A global hotkey was pressed, whichever window had focus.
+	Hotkey(HotkeyId),
 }
 
 #[derive(Copy, Clone)]
@@ -84,27 +202,606 @@ impl BitmapData {
 	}
 }
 
+/// Framebuffer that isn't shown in a window, for rendering in benchmarks and tests.
+#[derive(Debug, Clone)]
+pub struct OffscreenBitmap {
+	pixels: Vec<u32>,
+	width: usize,
+	height: usize,
+}
+
+impl OffscreenBitmap {
+	/// Black framebuffer.
+	pub fn new(width: usize, height: usize) -> Self {
+		OffscreenBitmap {
+			pixels: vec![0; width * height],
+			width,
+			height,
+		}
+	}
+
+	/// The framebuffer for the drawing functions. Valid as long as `self` isn't dropped.
+	pub fn bitmap_data(&mut self) -> BitmapData {
+		BitmapData {
+			bitmap_memory: self.pixels.as_mut_ptr().cast(),
+			bitmap_memory_size: self.pixels.len() * mem::size_of::<u32>(),
+			bitmap_width: self.width as i32,
+			bitmap_height: self.height as i32,
+			..BitmapData::default()
+		}
+	}
+
+	/// 0x00RRGGBB pixels, row by row.
+	pub fn pixels(&self) -> &[u32] {
+		&self.pixels
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+}
+
 pub struct Keyboard {
 	keyboard: [bool; 65536],
+	/// State of [Keyboard::keyboard] at the end of the previous frame.
+	previous: [bool; 65536],
+	/// Keys that are down, by [ScanCode]. Unlike virtual keys, these don't depend on the keyboard
+	/// layout.
+	scancodes: [bool; ScanCode::COUNT],
+	/// Virtual key codes and scancodes of keys that went down (including repeats) since the last
+	/// [Window::poll_events] call, with the modifiers held at the time.
+	pressed: Vec<(u16, ScanCode, Modifiers)>,
+	/// Printable characters typed since the last [Window::poll_events] call.
+	typed: String,
+	/// Key codes of the repeats in `pressed` that jump by a page, see [Keyboard::steps].
+	page_jumps: Vec<u16>,
+	repeat: KeyRepeat,
+	/// The key being held down for repeats.
+	repeating: Option<HeldKey>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct HeldKey {
+	key: u16,
+	scancode: ScanCode,
+	since: Instant,
+	next_repeat: Instant,
+}
+
+/// Held navigation keys repeat faster the longer they're held, up to [MAX_ACCELERATION] times the
+/// normal rate once held for this long past the repeat delay.
+const ACCELERATION_RAMP: Duration = Duration::from_millis(1500);
+const MAX_ACCELERATION: f32 = 4.0;
+/// Navigation keys held for this long jump by a page with every repeat, see [Keyboard::steps].
+const PAGE_JUMP_AFTER: Duration = Duration::from_secs(3);
+const PAGE_JUMP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Timing of repeated presses of a held key: the first repeat comes after `delay`, the following
+/// ones every `interval`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyRepeat {
+	pub delay: Duration,
+	pub interval: Duration,
+}
+
+impl KeyRepeat {
+	/// The repeat delay and rate set in the system's keyboard settings.
+	pub fn system() -> Self {
+		let get = |action| {
+			let mut value = 0_u32;
+			let ok = unsafe {
+				SystemParametersInfoW(
+					action,
+					0,
+					Some((&mut value as *mut u32).cast()),
+					SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS::default(),
+				)
+			};
+			(ok.0 != 0).then_some(value)
+		};
+		let default = KeyRepeat::default_timing();
+		// The delay goes from 0 (about 250ms) to 3 (about 1s), the speed from 0 (about 2.5
+		// repeats per second) to 31 (about 30 per second)
+		let delay = get(SPI_GETKEYBOARDDELAY)
+			.map(|delay| Duration::from_millis(250 * (delay.min(3) as u64 + 1)))
+			.unwrap_or(default.delay);
+		let interval = get(SPI_GETKEYBOARDSPEED)
+			.map(|speed| Duration::from_secs_f32(1.0 / (2.5 + speed.min(31) as f32 * 27.5 / 31.0)))
+			.unwrap_or(default.interval);
+		KeyRepeat { delay, interval }
+	}
+
+	/// The system defaults, for when the settings can't be read.
+	fn default_timing() -> Self {
+		KeyRepeat {
+			delay: Duration::from_millis(500),
+			interval: Duration::from_millis(33),
+		}
+	}
+}
+
+impl Default for KeyRepeat {
+	fn default() -> Self {
+		KeyRepeat::system()
+	}
 }
 
 impl Default for Keyboard {
 	fn default() -> Self {
 		Keyboard {
 			keyboard: [false; 65536],
+			previous: [false; 65536],
+			scancodes: [false; ScanCode::COUNT],
+			pressed: Vec::new(),
+			typed: String::new(),
+			page_jumps: Vec::new(),
+			repeat: KeyRepeat::default(),
+			repeating: None,
 		}
 	}
 }
 
+/// Queries about keys of the main keyboard hold for their numpad twins as well (e.g. Enter and the
+/// numpad Enter), queries about numpad keys only for the numpad keys.
 impl Keyboard {
 	#[inline]
 	pub fn is_pressed(&self, key: Key) -> bool {
-		self.keyboard[key as usize]
+		is_down(&self.keyboard, key)
+	}
+
+	/// Whether `key` is down now but wasn't in the previous frame. Unlike [Keyboard::was_pressed]
+	/// true only once per press, however long the key is held.
+	#[inline]
+	pub fn just_pressed(&self, key: Key) -> bool {
+		is_down(&self.keyboard, key) && !is_down(&self.previous, key)
+	}
+
+	/// Whether `key` was down in the previous frame but isn't anymore.
+	#[inline]
+	pub fn just_released(&self, key: Key) -> bool {
+		!is_down(&self.keyboard, key) && is_down(&self.previous, key)
+	}
+
+	/// Whether `key` went down during the last batch of processed messages.
+	#[inline]
+	pub fn was_pressed(&self, key: Key) -> bool {
+		self.pressed
+			.iter()
+			.any(|&(pressed, _, _)| is_press_of(pressed, key))
+	}
+
+	/// Whether the key at the physical position `scancode` is down, whatever the layout maps it to.
+	#[inline]
+	pub fn is_scancode_pressed(&self, scancode: ScanCode) -> bool {
+		self.scancodes[scancode.index()]
+	}
+
+	/// Like [Keyboard::was_pressed], by physical position.
+	pub fn was_scancode_pressed(&self, scancode: ScanCode) -> bool {
+		self.pressed
+			.iter()
+			.any(|&(_, pressed, _)| pressed == scancode)
+	}
+
+	/// Modifier keys held down right now.
+	pub fn modifiers(&self) -> Modifiers {
+		Modifiers {
+			ctrl: self.is_pressed(Key::Control),
+			shift: self.is_pressed(Key::Shift),
+			alt: self.is_pressed(Key::Menu),
+		}
+	}
+
+	/// Keys pressed during the last batch of processed messages, with the modifiers held while
+	/// pressing them.
+	pub fn presses(&self) -> impl Iterator<Item = (Input, Modifiers)> + '_ {
+		self.pressed
+			.iter()
+			.filter_map(|&(key, _, modifiers)| Some((Input::Key(Key::from_code(key)?), modifiers)))
+	}
+
+	/// Text typed during the last batch of processed messages.
+	#[inline]
+	pub fn typed_text(&self) -> &str {
+		&self.typed
+	}
+
+	/// Marks a press of `key` in the current frame as handled, so that later handlers don't react
+	/// to it as well.
+	pub fn consume_press(&mut self, key: Key) {
+		self.pressed
+			.retain(|&(pressed, _, _)| !is_press_of(pressed, key));
+		self.page_jumps
+			.retain(|&pressed| !is_press_of(pressed, key));
+	}
+
+	/// Marks the presses of the current frame that typed text as handled, see
+	/// [Keyboard::consume_press], for when the text went into a field rather than to shortcuts.
+	/// Presses with Ctrl or Alt held don't type.
+	pub fn consume_typing(&mut self) {
+		self.pressed
+			.retain(|&(key, _, modifiers)| modifiers.ctrl || modifiers.alt || !is_text_key(key));
+	}
+
+	/// How far the presses of `key` during the last batch of processed messages move a cursor
+	/// through a list showing `page` rows at once: a row per press, but a page per repeat once the
+	/// key has been held for [PAGE_JUMP_AFTER]. Held arrow keys and their gamepad counterparts
+	/// also repeat faster and faster before that.
+	pub fn steps(&self, key: Key, page: usize) -> usize {
+		let presses = self
+			.pressed
+			.iter()
+			.filter(|&&(pressed, _, _)| is_press_of(pressed, key))
+			.count();
+		let jumps = self
+			.page_jumps
+			.iter()
+			.filter(|&&pressed| is_press_of(pressed, key))
+			.count();
+		presses - jumps + jumps * page.max(1)
+	}
+
+	/// Presses or releases `key` as if it came from the keyboard, for input devices that stand in
+	/// for keys.
+	pub fn inject(&mut self, key: Key, down: bool) {
+		// Not a real scancode, so that injected keys don't look like physical ones
+		let scancode = ScanCode(0);
+		match down {
+			true if !self.is_pressed(key) => self.key_down(key as u16, scancode),
+			false => self.key_up(key as u16, scancode),
+			true => {}
+		}
+	}
+
+	pub fn repeat(&self) -> KeyRepeat {
+		self.repeat
+	}
+
+	pub fn set_repeat(&mut self, repeat: KeyRepeat) {
+		self.repeat = repeat;
+	}
+
+	fn clear_frame_input(&mut self) {
+		self.previous = self.keyboard;
+		self.pressed.clear();
+		self.typed.clear();
+		self.page_jumps.clear();
+	}
+
+	fn key_down(&mut self, key: u16, scancode: ScanCode) {
+		// Read before the key goes down, pressing Shift alone is a press of Shift without modifiers
+		let modifiers = self.modifiers();
+		self.keyboard[key as usize] = true;
+		self.scancodes[scancode.index()] = true;
+		self.pressed.push((key, scancode, modifiers));
+		// Modifiers don't repeat and don't stop other keys from repeating, so that holding Down
+		// and then pressing Shift keeps scrolling
+		if !is_modifier(key) {
+			let now = Instant::now();
+			self.repeating = Some(HeldKey {
+				key,
+				scancode,
+				since: now,
+				next_repeat: now + self.repeat.delay,
+			});
+		}
+	}
+
+	fn key_up(&mut self, key: u16, scancode: ScanCode) {
+		self.keyboard[key as usize] = false;
+		self.scancodes[scancode.index()] = false;
+		if self.repeating.is_some_and(|held| held.key == key) {
+			self.repeating = None;
+		}
+	}
+
+	/// Key up messages go to whichever window has focus, so keys still held when the focus is lost
+	/// would otherwise stay down (and repeat) forever.
+	fn release_all(&mut self) {
+		self.keyboard.fill(false);
+		self.scancodes.fill(false);
+		self.repeating = None;
+	}
+
+	/// Adds the repeats of the held key that are due at `now` to the presses of this frame.
+	fn generate_repeats(&mut self, now: Instant) {
+		let modifiers = self.modifiers();
+		let Some(held) = &mut self.repeating else {
+			return;
+		};
+		let navigation = is_navigation(held.key);
+		// A zero interval would never catch up with `now`
+		let interval = self.repeat.interval.max(Duration::from_millis(1));
+		while held.next_repeat <= now {
+			self.pressed.push((held.key, held.scancode, modifiers));
+			let held_for = held.next_repeat - held.since;
+			held.next_repeat += if !navigation {
+				interval
+			} else if held_for >= PAGE_JUMP_AFTER {
+				self.page_jumps.push(held.key);
+				PAGE_JUMP_INTERVAL
+			} else {
+				let ramp = held_for.saturating_sub(self.repeat.delay).as_secs_f32()
+					/ ACCELERATION_RAMP.as_secs_f32();
+				interval.div_f32(1.0 + ramp.min(1.0) * (MAX_ACCELERATION - 1.0))
+			};
+		}
+	}
+}
+
+fn is_down(keyboard: &[bool; 65536], key: Key) -> bool {
+	keyboard[key as usize] || keyboard[key as usize | NUMPAD_FLAG as usize]
+}
+
+/// Whether a press of the key code `pressed` is a press of `key` or its numpad twin.
+fn is_press_of(pressed: u16, key: Key) -> bool {
+	pressed == key as u16 || pressed == key as u16 | NUMPAD_FLAG
+}
+
+/// Keys moving a cursor by a row or an item, which accelerate when held.
+fn is_navigation(key: u16) -> bool {
+	matches!(
+		Key::from_code(key).map(Key::main_key),
+		Some(
+			Key::Up
+				| Key::Down | Key::Left
+				| Key::Right | Key::GamepadDpadUp
+				| Key::GamepadDpadDown
+				| Key::GamepadDpadLeft
+				| Key::GamepadDpadRight
+				| Key::GamepadLeftThumbstickUp
+				| Key::GamepadLeftThumbstickDown
+				| Key::GamepadLeftThumbstickLeft
+				| Key::GamepadLeftThumbstickRight
+		)
+	)
+}
+
+/// Whether `key` types a character: letters, digits, space and punctuation, also on the numpad.
+fn is_text_key(key: u16) -> bool {
+	matches!(
+		key,
+		0x20 | 0x30..=0x39 | 0x41..=0x5A | 0x60..=0x6F | 0xBA..=0xC0 | 0xDB..=0xDF | 0xE2
+	)
+}
+
+fn is_modifier(key: u16) -> bool {
+	[Key::Shift, Key::Control, Key::Menu, Key::Lwin, Key::Rwin]
+		.iter()
+		.any(|&modifier| modifier as u16 == key)
+}
+
+/// Mouse state. Coordinates are in client area pixels.
+#[derive(Default)]
+pub struct Mouse {
+	x: i32,
+	y: i32,
+	/// Buttons currently held.
+	down: Vec<MouseButton>,
+	left_pressed: bool,
+	left_released: bool,
+	/// Buttons and wheel notches since the last [Window::poll_events] call, with the
+	/// modifiers held at the time.
+	pressed: Vec<(Input, Modifiers)>,
+	/// Wheel movement that doesn't make a whole notch yet, vertical and horizontal.
+	wheel_remainder: (i32, i32),
+	/// Wheel movement since the last [Window::poll_events] call, vertical and horizontal.
+	wheel_delta: (i32, i32),
+}
+
+/// Wheel movement of a single notch.
+pub const WHEEL_DELTA: i32 = 120;
+/// How far a notch of the wheel scrolls.
+pub const SCROLL_PIXELS_PER_NOTCH: i32 = 40;
+
+impl Mouse {
+	#[inline]
+	pub fn position(&self) -> (i32, i32) {
+		(self.x, self.y)
+	}
+
+	#[inline]
+	pub fn is_left_down(&self) -> bool {
+		self.is_down(MouseButton::Left)
+	}
+
+	pub fn is_down(&self, button: MouseButton) -> bool {
+		self.down.contains(&button)
+	}
+
+	/// Whether the left button went down during the last batch of processed messages.
+	#[inline]
+	pub fn was_left_pressed(&self) -> bool {
+		self.left_pressed
+	}
+
+	/// Whether the left button went up during the last batch of processed messages.
+	#[inline]
+	pub fn was_left_released(&self) -> bool {
+		self.left_released
+	}
+
+	/// Marks the press of the current frame as handled, so that widgets below the one that handled
+	/// it don't react to it as well.
+	pub fn consume_press(&mut self) {
+		self.left_pressed = false;
+		self.pressed
+			.retain(|&(input, _)| input != Input::Mouse(MouseButton::Left));
+	}
+
+	/// Buttons pressed and wheel notches scrolled during the last batch of processed messages,
+	/// with the modifiers held at the time.
+	pub fn presses(&self) -> impl Iterator<Item = (Input, Modifiers)> + '_ {
+		self.pressed.iter().copied()
+	}
+
+	fn clear_frame_input(&mut self) {
+		self.left_pressed = false;
+		self.left_released = false;
+		self.pressed.clear();
+		self.wheel_delta = (0, 0);
+	}
+
+	/// How far the wheel scrolled the content during the last batch of processed messages, in
+	/// pixels. Positive values scroll towards the end (down or right).
+	pub fn scroll_pixels(&self) -> (i32, i32) {
+		let (vertical, horizontal) = self.wheel_delta;
+		(
+			horizontal * SCROLL_PIXELS_PER_NOTCH / WHEEL_DELTA,
+			-vertical * SCROLL_PIXELS_PER_NOTCH / WHEEL_DELTA,
+		)
+	}
+
+	/// Turns the wheel delta of a WM_MOUSEWHEEL or WM_MOUSEHWHEEL message into notches. Precise
+	/// wheels and touchpads send fractions of a notch, those add up until they make one.
+	fn scroll(&mut self, delta: i32, horizontal: bool, modifiers: Modifiers) {
+		match horizontal {
+			false => self.wheel_delta.0 += delta,
+			true => self.wheel_delta.1 += delta,
+		}
+		let (remainder, directions) = match horizontal {
+			false => (
+				&mut self.wheel_remainder.0,
+				(WheelDirection::Up, WheelDirection::Down),
+			),
+			true => (
+				&mut self.wheel_remainder.1,
+				(WheelDirection::Right, WheelDirection::Left),
+			),
+		};
+		*remainder += delta;
+		while remainder.abs() >= WHEEL_DELTA {
+			let direction = match *remainder > 0 {
+				true => directions.0,
+				false => directions.1,
+			};
+			*remainder -= WHEEL_DELTA * remainder.signum();
+			self.pressed.push((Input::Wheel(direction), modifiers));
+		}
+	}
+
+	fn set_position(&mut self, (x, y): (i32, i32)) {
+		self.x = x;
+		self.y = y;
+	}
+}
+
+/// Position of a mouse message in client area pixels.
+fn mouse_position(l_param: LPARAM) -> (i32, i32) {
+	// Coordinates are signed, they can be negative on multi-monitor setups
+	(
+		(l_param.0 & 0xFFFF) as i16 as i32,
+		((l_param.0 >> 16) & 0xFFFF) as i16 as i32,
+	)
+}
+
+/// Button of a button down or up message.
+fn mouse_button(message: u32, w_param: WPARAM) -> MouseButton {
+	match message {
+		WM_LBUTTONDOWN | WM_LBUTTONUP => MouseButton::Left,
+		WM_RBUTTONDOWN | WM_RBUTTONUP => MouseButton::Right,
+		WM_MBUTTONDOWN | WM_MBUTTONUP => MouseButton::Middle,
+		// The high word tells which of the side buttons it was
+		_ if (w_param.0 >> 16) & 0xFFFF == 1 => MouseButton::Button4,
+		_ => MouseButton::Button5,
+	}
+}
+
+impl WindowData {
+	fn handle_input(&mut self, event: InputEvent) {
+		if let InputEvent::KeyDown { key, .. } = event {
+			// The system's own auto-repeat is ignored, repeats are generated in
+			// Window::poll_events with the configured timing
+			if self.keyboard.keyboard[key as usize] {
+				return;
+			}
+		}
+		if let Some(recorder) = &mut self.recorder {
+			if let Err(err) = recorder.record(event) {
+				error!("Failed to record input, recording stopped: {err}");
+				self.recorder = None;
+			}
+		}
+
+		let modifiers = self.keyboard.modifiers();
+		match event {
+			InputEvent::KeyDown { key, scancode } => {
+				self.keyboard.key_down(key, scancode);
+				if let Some(key) = Key::from_code(key) {
+					self.events.push(Event::KeyDown { key, modifiers });
+				}
+			}
+			InputEvent::KeyUp { key, scancode } => {
+				self.keyboard.key_up(key, scancode);
+				if let Some(key) = Key::from_code(key) {
+					self.events.push(Event::KeyUp { key });
+				}
+			}
+			InputEvent::Char(c) => {
+				self.keyboard.typed.push(c);
+				self.events.push(Event::Char(c));
+			}
+			InputEvent::MouseMove { x, y } => {
+				self.mouse.set_position((x, y));
+				self.events.push(Event::MouseMove { x, y });
+			}
+			InputEvent::MouseDown { button, x, y } => {
+				self.mouse.set_position((x, y));
+				if !self.mouse.is_down(button) {
+					self.mouse.down.push(button);
+				}
+				if button == MouseButton::Left {
+					self.mouse.left_pressed = true;
+				}
+				self.mouse.pressed.push((Input::Mouse(button), modifiers));
+				self.events.push(Event::MouseButton {
+					button,
+					pressed: true,
+					x,
+					y,
+				});
+			}
+			InputEvent::MouseUp { button, x, y } => {
+				self.mouse.set_position((x, y));
+				self.mouse.down.retain(|&down| down != button);
+				if button == MouseButton::Left {
+					self.mouse.left_released = true;
+				}
+				self.events.push(Event::MouseButton {
+					button,
+					pressed: false,
+					x,
+					y,
+				});
+			}
+			InputEvent::Wheel { delta, horizontal } => {
+				self.mouse.scroll(delta, horizontal, modifiers);
+				self.events.push(Event::MouseWheel { delta, horizontal });
+			}
+			InputEvent::FocusLost => self.keyboard.release_all(),
+			InputEvent::Hotkey(id) => self.events.push(Event::Hotkey(id)),
+		}
 	}
 }
 
 impl Window {
 	pub fn open() -> io::Result<Self> {
+		Window::create(CW_USEDEFAULT, CW_USEDEFAULT)
+	}
+
+	/// Opens a window of `width`x`height` pixels, borders included.
+	pub fn open_with_size(width: u32, height: u32) -> io::Result<Self> {
+		Window::create(
+			i32::try_from(width).unwrap_or(i32::MAX),
+			i32::try_from(height).unwrap_or(i32::MAX),
+		)
+	}
+
+	fn create(width: i32, height: i32) -> io::Result<Self> {
 		unsafe {
 			debug!("Create window");
 
@@ -115,7 +812,7 @@ impl Window {
 
 			let h_instance = GetModuleHandleW(PCWSTR::null())?;
 
-			let classname = "FileExplorerWindowClass".to_utf16_with_null();
+			let classname = WideString::new("FileExplorerWindowClass");
 			let wndclass = WNDCLASSW {
 				style: CS_HREDRAW | CS_VREDRAW,
 				lpfnWndProc: Some(main_window_callback),
@@ -126,7 +823,7 @@ impl Window {
 				hCursor: HCURSOR::default(),
 				hbrBackground: HBRUSH::default(),
 				lpszMenuName: PCWSTR::null(),
-				lpszClassName: PCWSTR(classname.as_ptr()),
+				lpszClassName: classname.as_pcwstr(),
 			};
 
 			let result = RegisterClassW(&wndclass);
@@ -135,19 +832,19 @@ impl Window {
 			}
 			debug!("Class registered");
 
-			let window_title = "File Explorer".to_utf16_with_null();
+			let window_title = WideString::new("File Explorer");
 
 			let window_data_ptr = (window_data.as_ref() as *const WindowData).cast::<c_void>();
 
 			let hwnd = CreateWindowExW(
 				WINDOW_EX_STYLE::default(),
-				PCWSTR(classname.as_ptr()),
-				PCWSTR(window_title.as_ptr()),
+				classname.as_pcwstr(),
+				window_title.as_pcwstr(),
 				WS_OVERLAPPEDWINDOW | WS_VISIBLE,
 				CW_USEDEFAULT,
 				CW_USEDEFAULT,
-				CW_USEDEFAULT,
-				CW_USEDEFAULT,
+				width,
+				height,
 				HWND::default(),
 				HMENU::default(),
 				h_instance,
@@ -162,28 +859,113 @@ impl Window {
 
 			let window = Window {
 				window: hwnd,
-				classname,
-				window_title,
-
 				window_data,
+				hotkeys: Vec::new(),
 			};
 
 			Ok(window)
 		}
 	}
 
-	pub fn process_messages(&mut self) -> ControlFlow<()> {
+	/// Processes the pending window messages, updating the [Keyboard] and [Mouse] for the next
+	/// frame, and returns what happened since the last call in order.
+	pub fn poll_events(&mut self) -> impl Iterator<Item = Event> {
+		self.window_data.keyboard.clear_frame_input();
+		self.window_data.mouse.clear_frame_input();
 		unsafe {
 			let mut msg = MaybeUninit::<MSG>::uninit();
 			while PeekMessageW(msg.as_mut_ptr(), HWND::default(), 0, 0, PM_REMOVE).0 != 0 {
 				if msg.assume_init_ref().message == WM_QUIT {
-					return ControlFlow::Break(());
+					self.window_data.events.push(Event::Close);
+					break;
 				}
 				TranslateMessage(msg.as_ptr());
 				DispatchMessageW(msg.as_ptr());
 			}
-			ControlFlow::Continue(())
+			let now = Instant::now();
+			if let Some(playback) = &mut self.window_data.playback {
+				let events = playback.due_events(now);
+				if playback.is_finished() {
+					info!("Input playback finished");
+					self.window_data.playback = None;
+				}
+				for event in events {
+					self.window_data.handle_input(event);
+				}
+			}
+			for event in self.window_data.touch.poll(now) {
+				self.window_data.handle_input(event);
+			}
+			self.window_data.keyboard.generate_repeats(now);
 		}
+		mem::take(&mut self.window_data.events).into_iter()
+	}
+
+	/// Writes all input from now on to `path`, to be replayed with [Window::play_input].
+	pub fn record_input(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+		self.window_data.recorder = Some(Recorder::create(path)?);
+		Ok(())
+	}
+
+	/// Registers `shortcut` system-wide: presses of it are reported as [Event::Hotkey] even when
+	/// another application has focus, and don't reach that application. Fails if another
+	/// application already registered the shortcut, or if it isn't a key.
+	pub fn register_global_hotkey(&mut self, shortcut: Shortcut) -> io::Result<HotkeyId> {
+		let Input::Key(key) = shortcut.input else {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("{shortcut} isn't a key"),
+			));
+		};
+		let Modifiers { ctrl, shift, alt } = shortcut.modifiers;
+		// Held hotkeys don't repeat, like the presses of a chord
+		let mut modifiers = MOD_NOREPEAT;
+		for (held, modifier) in [(ctrl, MOD_CONTROL), (shift, MOD_SHIFT), (alt, MOD_ALT)] {
+			if held {
+				modifiers |= modifier;
+			}
+		}
+		// Ids above 0xBFFF are reserved for shared libraries
+		let id = HotkeyId(self.hotkeys.iter().map(|id| id.0 + 1).max().unwrap_or(1));
+		// Numpad keys can't be told apart here, the hotkey is on both twins
+		let virtual_key = key.main_key() as u32;
+		if unsafe { RegisterHotKey(self.window, id.0, modifiers, virtual_key) }.0 == 0 {
+			return Err(io::Error::last_os_error());
+		}
+		debug!("Registered global hotkey {shortcut}");
+		self.hotkeys.push(id);
+		Ok(id)
+	}
+
+	pub fn unregister_global_hotkey(&mut self, id: HotkeyId) {
+		if !self.hotkeys.contains(&id) {
+			return;
+		}
+		self.hotkeys.retain(|&registered| registered != id);
+		if unsafe { UnregisterHotKey(self.window, id.0) }.0 == 0 {
+			error!("UnregisterHotKey: {}", io::Error::last_os_error());
+		}
+	}
+
+	pub fn event_poster(&self) -> EventPoster {
+		EventPoster(self.window)
+	}
+
+	/// Restores the window if minimized and gives it focus. Windows only lets applications take
+	/// the focus in response to input, like a global hotkey.
+	pub fn bring_to_front(&self) {
+		unsafe {
+			if IsIconic(self.window).0 != 0 {
+				ShowWindow(self.window, SW_RESTORE);
+			}
+			SetForegroundWindow(self.window);
+		}
+	}
+
+	/// Feeds recorded input to the window, starting with the next [Window::poll_events] call.
+	/// Real input still comes through as well.
+	pub fn play_input(&mut self, playback: Playback) {
+		self.window_data.playback = Some(playback);
 	}
 
 	pub fn render(&self) {
@@ -212,14 +994,6 @@ impl Window {
 			);
 		}
 	}
-
-	#[allow(dead_code)]
-	pub fn on_key_press<F>(&mut self, key: Key, f: F)
-	where
-		F: Fn(&mut BitmapData, &mut Keyboard) + 'static,
-	{
-		self.window_data.key_handlers.insert(key, Box::new(f));
-	}
 }
 
 /// Returns size of a given window in a form (width, height).
@@ -267,21 +1041,17 @@ unsafe extern "system" fn main_window_callback(
 
 	let window_data = &mut *(GetWindowLongPtrW(window_handle, GWLP_USERDATA) as *mut WindowData);
 	let bitmap_data = &mut window_data.bitmap_data;
-	let key_handlers = &mut window_data.key_handlers;
 
 	let mut callback_result = 0;
 
 	match message {
 		WM_CREATE => {
 			debug!("WM_CREATE");
-			let create_struct = &*mem::transmute::<_, *const CREATESTRUCTW>(l_param);
+			let create_struct = &*mem::transmute::<LPARAM, *const CREATESTRUCTW>(l_param);
 			let window_data_ptr = create_struct.lpCreateParams as isize;
 			SetWindowLongPtrW(window_handle, GWLP_USERDATA, window_data_ptr);
 		}
 		WM_SIZE => {
-			// Commenting this out gives us a stable "virtual dimensions", meaning, when the window
-			// is resized, everything will be stretched.
-			// /*
 			debug!("WM_SIZE");
 
 			let (width, height) = match window_dimensions(window_handle) {
@@ -292,19 +1062,32 @@ unsafe extern "system" fn main_window_callback(
 				}
 			};
 
-			info!("New size: {width}x{height}");
-
-			if let Err(err) = resize_dib_section(bitmap_data, width, height) {
-				error!("resize_dib_section: {err}");
+			// Minimizing sends a size of 0x0, the bitmap stays as it was
+			if width > 0 && height > 0 {
+				info!("New size: {width}x{height}");
+				match resize_dib_section(bitmap_data, width, height) {
+					Ok(()) => window_data.events.push(Event::Resize { width, height }),
+					Err(err) => error!("resize_dib_section: {err}"),
+				}
 			}
-			// */
 		}
 		WM_DESTROY => {
 			debug!("WM_DESTROY");
+			window_data.accessibility.host_mut().disconnect();
+		}
+		WM_GETOBJECT => {
+			let shared = window_data.accessibility.shared().clone();
+			let host = window_data.accessibility.host_mut();
+			match host.handle_get_object(window_handle, w_param, l_param, &shared) {
+				Some(result) => return result,
+				None => {
+					callback_result = DefWindowProcW(window_handle, message, w_param, l_param).0;
+				}
+			}
 		}
 		WM_CLOSE => {
 			debug!("Close requested");
-			PostQuitMessage(0);
+			window_data.events.push(Event::Close);
 		}
 		WM_ACTIVATEAPP => {
 			debug!("WM_ACTIVATEAPP");
@@ -330,18 +1113,93 @@ unsafe extern "system" fn main_window_callback(
 
 			EndPaint(window_handle, &paint);
 		},
-		WM_KEYDOWN => {
-			let was_down = window_data.keyboard.keyboard[w_param.0];
-			window_data.keyboard.keyboard[w_param.0] = true;
-			if !was_down {
-				let key: Key = unsafe { std::mem::transmute(w_param.0 as u16) };
-				if let Some(handler) = key_handlers.get(&key) {
-					handler(bitmap_data, &mut window_data.keyboard);
-				}
+		// Keys pressed while Alt is held (and F10) come as system keys
+		WM_KEYDOWN | WM_SYSKEYDOWN => {
+			let scancode = ScanCode::from_lparam(l_param);
+			window_data.handle_input(InputEvent::KeyDown {
+				key: Key::from_message(w_param.0 as u16, scancode),
+				scancode,
+			});
+			// Keeps Alt+F4 and the window menu working
+			if message == WM_SYSKEYDOWN {
+				callback_result = DefWindowProcW(window_handle, message, w_param, l_param).0;
 			}
 		}
-		WM_KEYUP => {
-			window_data.keyboard.keyboard[w_param.0] = false;
+		WM_KEYUP | WM_SYSKEYUP => {
+			let scancode = ScanCode::from_lparam(l_param);
+			window_data.handle_input(InputEvent::KeyUp {
+				key: Key::from_message(w_param.0 as u16, scancode),
+				scancode,
+			});
+			if message == WM_SYSKEYUP {
+				callback_result = DefWindowProcW(window_handle, message, w_param, l_param).0;
+			}
+		}
+		WM_KILLFOCUS => {
+			window_data.handle_input(InputEvent::FocusLost);
+		}
+		WM_DIRECTORY_CHANGED => window_data.events.push(Event::DirectoryChanged),
+		WM_HOTKEY => {
+			window_data.handle_input(InputEvent::Hotkey(HotkeyId(w_param.0 as i32)));
+		}
+		WM_MOUSEMOVE => {
+			let (x, y) = mouse_position(l_param);
+			window_data.handle_input(InputEvent::MouseMove { x, y });
+		}
+		WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN | WM_XBUTTONDOWN => {
+			let button = mouse_button(message, w_param);
+			let (x, y) = mouse_position(l_param);
+			window_data.handle_input(InputEvent::MouseDown { button, x, y });
+			if message == WM_XBUTTONDOWN {
+				callback_result = 1;
+			}
+		}
+		WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+			// Positions of wheel messages are in screen coordinates, unlike other mouse messages
+			let delta = ((w_param.0 >> 16) & 0xFFFF) as i16 as i32;
+			window_data.handle_input(InputEvent::Wheel {
+				delta,
+				horizontal: message == WM_MOUSEHWHEEL,
+			});
+		}
+		WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP => {
+			let button = mouse_button(message, w_param);
+			let (x, y) = mouse_position(l_param);
+			window_data.handle_input(InputEvent::MouseUp { button, x, y });
+			if message == WM_XBUTTONUP {
+				callback_result = 1;
+			}
+		}
+		WM_POINTERDOWN | WM_POINTERUPDATE | WM_POINTERUP => {
+			let pointer_id = (w_param.0 & 0xFFFF) as u32;
+			let mut pointer_type = POINTER_INPUT_TYPE::default();
+			let is_touch =
+				GetPointerType(pointer_id, &mut pointer_type).as_bool() && pointer_type == PT_TOUCH;
+			if !is_touch {
+				// Pens and the like are turned into mouse messages
+				callback_result = DefWindowProcW(window_handle, message, w_param, l_param).0;
+				return LRESULT(callback_result);
+			}
+			// Pointer messages come in screen coordinates
+			let (x, y) = mouse_position(l_param);
+			let mut point = POINT { x, y };
+			ScreenToClient(window_handle, &mut point);
+			let pos = (point.x, point.y);
+
+			let touch = &mut window_data.touch;
+			let events = match message {
+				WM_POINTERDOWN => touch.down(pointer_id, pos, Instant::now()),
+				WM_POINTERUPDATE => touch.update(pointer_id, pos),
+				_ => touch.up(pointer_id),
+			};
+			for event in events {
+				window_data.handle_input(event);
+			}
+		}
+		WM_CHAR => {
+			if let Some(c) = char::from_u32(w_param.0 as u32).filter(|c| !c.is_control()) {
+				window_data.handle_input(InputEvent::Char(c));
+			}
 		}
 		_ => {
 			callback_result = DefWindowProcW(window_handle, message, w_param, l_param).0;
@@ -360,7 +1218,28 @@ unsafe fn resize_dib_section(
 		return Ok(());
 	}
 
+	let bytes_per_pixel = 4;
+	let bitmap_memory_size = (width as usize * height as usize) * bytes_per_pixel;
+
+	// Memory allocated by VirtualAlloc is initialized to 0
+	let bitmap_memory = VirtualAlloc(None, bitmap_memory_size, MEM_COMMIT, PAGE_READWRITE);
+	if bitmap_memory.is_null() {
+		return Err(io::Error::last_os_error());
+	}
+
+	// Keeps the last frame, so the window doesn't flash black until the next one is drawn
 	if !bitmap_data.bitmap_memory.is_null() {
+		let old = bitmap_data.into_slice();
+		let new = slice::from_raw_parts_mut(
+			bitmap_memory.cast::<u32>(),
+			width as usize * height as usize,
+		);
+		let copy_width = (bitmap_data.bitmap_width.min(width)) as usize;
+		for y in 0..bitmap_data.bitmap_height.min(height) as usize {
+			let old_row = y * bitmap_data.bitmap_width as usize;
+			let new_row = y * width as usize;
+			new[new_row..new_row + copy_width].copy_from_slice(&old[old_row..old_row + copy_width]);
+		}
 		VirtualFree(bitmap_data.bitmap_memory, 0, MEM_RELEASE);
 	}
 
@@ -389,14 +1268,6 @@ unsafe fn resize_dib_section(
 		}],
 	};
 
-	let bytes_per_pixel = 4;
-	let bitmap_memory_size = (width as usize * height as usize) * bytes_per_pixel;
-
-	// Memory allocated by VirtualAlloc is initialized to 0
-	let bitmap_memory = VirtualAlloc(None, bitmap_memory_size, MEM_COMMIT, PAGE_READWRITE);
-	if bitmap_memory.is_null() {
-		return Err(io::Error::last_os_error());
-	}
 	bitmap_data.bitmap_memory = bitmap_memory;
 	bitmap_data.bitmap_memory_size = bitmap_memory_size;
 
@@ -0,0 +1,120 @@
+//! Renders known scenes into an offscreen framebuffer and compares them with the reference
+//! images in `tests/golden`. Differences in a color channel of up to [TOLERANCE] are accepted.
+//! A failing scene is saved next to `target/golden-actual` for inspection. After an intended
+//! change of the output, update the references with `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::path::{Path, PathBuf};
+
+use file_explorer::draw::{
+	dither, draw_background, draw_rectangle, draw_rectangle_blended, draw_text, draw_texture,
+	Texture,
+};
+use file_explorer::font::Font;
+use file_explorer::image;
+use file_explorer::png::Png;
+use file_explorer::window::OffscreenBitmap;
+
+const TOLERANCE: u32 = 2;
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+
+#[test]
+fn blending() {
+	let mut bitmap = OffscreenBitmap::new(WIDTH, HEIGHT);
+	let bitmap_data = bitmap.bitmap_data();
+	draw_background(bitmap_data, 7, 3);
+	draw_texture(bitmap_data, &logo(), 20, 10);
+	draw_rectangle_blended(bitmap_data, (160, 40), (120, 160), 0xFF458588, 0.5);
+	draw_rectangle_blended(bitmap_data, (200, 100), (100, 100), 0xFFCC241D, 0.25);
+	assert_matches_golden("blending", &bitmap);
+}
+
+#[test]
+fn dithering() {
+	let mut bitmap = OffscreenBitmap::new(WIDTH, HEIGHT);
+	let bitmap_data = bitmap.bitmap_data();
+	draw_background(bitmap_data, 0, 0);
+	draw_texture(bitmap_data, &logo(), 40, 20);
+	dither(bitmap_data, 0, 0, WIDTH, HEIGHT / 2);
+	assert_matches_golden("dithering", &bitmap);
+}
+
+#[test]
+fn text() {
+	let mut bitmap = OffscreenBitmap::new(WIDTH, HEIGHT);
+	let bitmap_data = bitmap.bitmap_data();
+	draw_rectangle(bitmap_data, (0, 0), (WIDTH, HEIGHT), 0xFF282828);
+	let lines = [
+		("The quick brown fox", 0xFFEBDBB2),
+		("jumps over the lazy dog", 0xFFFABD2F),
+		("0123456789 !?.,;:()[]", 0xFF83A598),
+		("Clipped at the right edge of the frame", 0xFFB8BB26),
+	];
+	for (idx, (line, color)) in lines.into_iter().enumerate() {
+		draw_text(bitmap_data, &Font::Bitmap, line, (8, 8 + idx * 24), color);
+	}
+	assert_matches_golden("text", &bitmap);
+}
+
+fn logo() -> Texture {
+	image::load_from_slice(include_bytes!("../assets/logo.png"))
+		.expect("logo.png is a valid image")
+		.into_texture()
+}
+
+fn assert_matches_golden(name: &str, bitmap: &OffscreenBitmap) {
+	// The framebuffer has no alpha, references are opaque
+	let pixels: Vec<u32> = bitmap.pixels().iter().map(|p| p | 0xFF00_0000).collect();
+	let encoded = Png::encode(bitmap.width(), bitmap.height(), &pixels);
+	let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/golden")
+		.join(format!("{name}.png"));
+
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		std::fs::write(&reference_path, encoded).unwrap();
+		return;
+	}
+
+	let reference = std::fs::read(&reference_path).unwrap_or_else(|err| {
+		panic!(
+			"Missing reference {} ({err}), create it with UPDATE_GOLDEN=1",
+			reference_path.display()
+		)
+	});
+	let reference = Texture::from(Png::load_from_slice(&reference).unwrap());
+	let size_matches = (reference.width(), reference.height()) == (bitmap.width(), bitmap.height());
+	let differing = match size_matches {
+		true => pixels
+			.iter()
+			.zip(reference.pixels())
+			.filter(|&(&actual, &expected)| max_channel_difference(actual, expected) > TOLERANCE)
+			.count(),
+		false => pixels.len(),
+	};
+	if differing > 0 {
+		let actual_path = save_actual(name, &encoded);
+		panic!(
+			"{name} differs from {} in {differing} pixels, rendered {}",
+			reference_path.display(),
+			actual_path.display()
+		);
+	}
+}
+
+fn max_channel_difference(a: u32, b: u32) -> u32 {
+	[16, 8, 0]
+		.into_iter()
+		.map(|shift| ((a >> shift) & 0xFF).abs_diff((b >> shift) & 0xFF))
+		.max()
+		.unwrap()
+}
+
+fn save_actual(name: &str, encoded: &[u8]) -> PathBuf {
+	let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden-actual");
+	let path = dir.join(format!("{name}.png"));
+	if let Err(err) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, encoded)) {
+		eprintln!("Failed to save {}: {err}", path.display());
+	}
+	path
+}